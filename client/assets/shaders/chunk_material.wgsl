@@ -19,31 +19,78 @@ var base_color_texture: texture_2d<f32>;
 @group(1) @binding(2)
 var base_color_sampler: sampler;
 
+// Shared clock for animations, seconds and ticks since the clock started
+struct RenderClock {
+    seconds: f32,
+    tick: u32,
+};
+
+@group(1) @binding(3)
+var<uniform> clock: RenderClock;
+
+// Distant chunks fade into the fog colour between the start and end distances
+struct Fog {
+    color: vec4<f32>,
+    start: f32,
+    end: f32,
+};
+
+@group(1) @binding(4)
+var<uniform> fog: Fog;
+
 #import bevy_pbr::mesh_functions
 
+#ifdef PACKED_VERTICES
+// Fixed point positions, see systems/chunk/mesh/vertex.rs
+let POSITION_SCALE: f32 = 256.0;
+let POSITION_OFFSET: f32 = 16.0;
+let UV_SCALE: f32 = 512.0;
+
+struct VertexInput {
+    @location(0) position: vec4<u32>,
+    @location(1) normal: vec4<f32>,
+    @location(2) uv: vec2<u32>,
+    @location(3) lighting: vec4<f32>,
+    @location(4) texture_rect: vec4<f32>
+};
+#else
 struct VertexInput {
     @location(0) position: vec4<f32>,
     @location(1) normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
-    @location(3) lighting: vec4<f32>
+    @location(3) lighting: vec4<f32>,
+    @location(4) texture_rect: vec4<f32>
 };
+#endif
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) world_position: vec4<f32>,
     @location(1) world_normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
-    @location(3) lighting: vec4<f32>
+    @location(3) lighting: vec4<f32>,
+    @location(4) texture_rect: vec4<f32>
 };
 
 @vertex
 fn vertex(vertex: VertexInput) -> VertexOutput {
     var out: VertexOutput;
 
-    out.world_position = mesh_position_local_to_world(mesh.model, vertex.position);
+#ifdef PACKED_VERTICES
+    let position = vec4(vec3<f32>(vertex.position.xyz) / POSITION_SCALE - POSITION_OFFSET, 1.0);
+    let normal = vertex.normal.xyz;
+    let uv = vec2<f32>(vertex.uv) / UV_SCALE;
+#else
+    let position = vertex.position;
+    let normal = vertex.normal;
+    let uv = vertex.uv;
+#endif
+
+    out.world_position = mesh_position_local_to_world(mesh.model, position);
     out.clip_position = mesh_position_world_to_clip(out.world_position);
-    out.world_normal = mesh_normal_local_to_world(vertex.normal);
-    out.uv = vertex.uv;
+    out.world_normal = mesh_normal_local_to_world(normal);
+    out.uv = uv;
+    out.texture_rect = vertex.texture_rect;
 
     let ambient = 0.02;
     out.lighting = vec4(vertex.lighting.xyz + ambient, 1.0);
@@ -56,14 +103,25 @@ struct FragmentInput {
     @location(0) world_position: vec4<f32>,
     @location(1) world_normal: vec3<f32>,
     @location(2) uv: vec2<f32>,
-    @location(3) lighting: vec4<f32>
+    @location(3) lighting: vec4<f32>,
+    @location(4) texture_rect: vec4<f32>
 };
 
 @fragment
 fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
     let output_color: vec4<f32> = material.color;
 
-    let output_color = output_color * textureSample(base_color_texture, base_color_sampler, in.uv);
+    // Merged faces repeat their texture across the quad, so wrap back to the start of the texture each time it
+    // repeats. Mipmaps are picked from the unwrapped coordinates so the seams don't sample the smallest level
+    let texture_size = in.texture_rect.zw - in.texture_rect.xy;
+    let uv = in.texture_rect.xy + fract(in.uv) * texture_size;
+    let output_color = output_color * textureSampleGrad(
+        base_color_texture,
+        base_color_sampler,
+        uv,
+        dpdx(in.uv) * texture_size,
+        dpdy(in.uv) * texture_size
+    );
 
     var input: PbrInput;
     input.material.base_color = vec4(1.0, 1.0, 1.0, 1.0);
@@ -85,11 +143,20 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
     input.N = apply_normal_mapping(
         input.material.flags,
         input.world_normal,
-        in.uv
+        uv
     );
     input.V = calculate_view(in.world_position, false);
 
     var pbr_color = (pbr(input) * 0.5) + vec4(0.5);
 
-    return pbr_color * in.lighting * output_color;
+    let color = pbr_color * in.lighting * output_color;
+
+    if (fog.end <= fog.start) {
+        return color;
+    }
+
+    let distance = length(in.world_position.xyz - view.world_position.xyz);
+    let fog_amount = clamp((distance - fog.start) / (fog.end - fog.start), 0.0, 1.0);
+
+    return vec4(mix(color.rgb, fog.color.rgb, fog_amount), color.a);
 }
\ No newline at end of file
@@ -0,0 +1,46 @@
+//! Measures how long the texture atlas takes to build, which happens during startup and whenever the resource pack changes.
+//!
+//! Run with `cargo bench -p rc_client --bench atlas`
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use fnv::FnvHashMap;
+use image::DynamicImage;
+use rc_client::systems::asset::atlas::atlas::pack_textures;
+use std::fs;
+
+/// About how many block textures a full resource pack has
+const PACK_SIZE: usize = 256;
+
+/// The textures the game ships with, repeated under different names until they're the size of a full pack
+fn realistic_pack() -> FnvHashMap<String, DynamicImage> {
+    let mut paths = fs::read_dir("assets/textures/game")
+        .unwrap()
+        .filter_map(|v| v.ok())
+        .map(|v| v.path())
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let base = paths
+        .iter()
+        .map(|v| image::open(v).unwrap())
+        .collect::<Vec<DynamicImage>>();
+
+    (0..PACK_SIZE)
+        .map(|i| (format!("game/texture_{}", i), base[i % base.len()].clone()))
+        .collect()
+}
+
+fn bench_atlas(c: &mut Criterion) {
+    let textures = realistic_pack();
+
+    c.bench_function("pack_textures", |b| {
+        b.iter_batched(
+            || (textures.clone(), FnvHashMap::default()),
+            |(mut textures, mut index)| pack_textures(&mut textures, &mut index, 4096),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_atlas);
+criterion_main!(benches);
@@ -0,0 +1,244 @@
+//! Measures how long chunk meshes take to build, and how many vertices they come out with.
+//!
+//! Run with `cargo bench -p rc_client --bench meshing`. To compare a change against the current mesher,
+//! save a baseline first with `-- --save-baseline before` and then compare with `-- --baseline before`
+//!
+//! Vertex counts don't depend on the machine, so each scenario checks its mesh against them before it's timed.
+//! Drawing every face by itself, with the chunk's edges drawn:
+//!
+//! | Scenario                   | Opaque vertices | Translucent vertices |
+//! |----------------------------|-----------------|----------------------|
+//! | all_air                    | 0               | 0                    |
+//! | solid                      | 6144            | 0                    |
+//! | checkerboard               | 49152           | 0                    |
+//! | terrain                    | 4944            | 832                  |
+//! | remesh_after_block_change  | 4968            | 832                  |
+
+use bevy::prelude::{Entity, Handle};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use nalgebra::Vector3;
+use rc_client::config::ChunkEdgeFaces;
+use rc_client::game::blocks::loading::BlockStatesFile;
+use rc_client::game::blocks::states::BlockStates;
+use rc_client::game::blocks::Block;
+use rc_client::systems::asset::atlas::index::TextureAtlasIndex;
+use rc_client::systems::chunk::data::{ChunkData, RawChunkData};
+use rc_client::systems::chunk::nearby_cache::NearbyChunkCache;
+use rc_client::systems::chunk::ChunkSystem;
+use rc_networking::constants::CHUNK_SIZE;
+
+const AIR: u32 = 0;
+const DIRT: u32 = 1;
+const GRASS: u32 = 2;
+const LONG_GRASS: u32 = 3;
+const STONE: u32 = 6;
+
+/// The block states the game ships with, with every face using the same texture as there's no atlas
+fn block_states() -> BlockStates {
+    let file =
+        serde_json::from_str::<BlockStatesFile>(include_str!("../assets/game/block_states.blocks"))
+            .unwrap();
+
+    let mut states = BlockStates::new();
+    states.states = file
+        .states
+        .iter()
+        .map(|v| Block::from_deserialised(v, |_| TextureAtlasIndex::new(0.0, 1.0, 0.0, 1.0)))
+        .collect();
+    states
+}
+
+fn fill(block: impl Fn(usize, usize, usize) -> u32) -> RawChunkData {
+    let mut world = RawChunkData::new(CHUNK_SIZE);
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                world.set(x, y, z, block(x, y, z));
+            }
+        }
+    }
+    world
+}
+
+/// Rolling hills of stone, dirt and grass with some long grass on top, like the generator makes
+fn terrain(x: usize, y: usize, z: usize) -> u32 {
+    let height = 8 + ((x as f32 * 0.4).sin() * 3.0 + (z as f32 * 0.3).cos() * 2.0) as usize;
+
+    if y + 4 < height {
+        STONE
+    } else if y < height {
+        DIRT
+    } else if y == height {
+        GRASS
+    } else if y == height + 1 && (x * 7 + z * 13) % 5 == 0 {
+        LONG_GRASS
+    } else {
+        AIR
+    }
+}
+
+/// The most faces a chunk can have, as no two blocks touch
+fn checkerboard(x: usize, y: usize, z: usize) -> u32 {
+    if (x + y + z) % 2 == 0 {
+        STONE
+    } else {
+        AIR
+    }
+}
+
+/// Each chunk to mesh, with the opaque and translucent vertices it's drawn with face by face
+fn scenarios() -> Vec<(&'static str, RawChunkData, (usize, usize))> {
+    vec![
+        ("all_air", fill(|_, _, _| AIR), (0, 0)),
+        ("solid", fill(|_, _, _| STONE), (6144, 0)),
+        ("checkerboard", fill(checkerboard), (49152, 0)),
+        ("terrain", fill(terrain), (4944, 832)),
+    ]
+}
+
+fn chunk(world: RawChunkData) -> ChunkData {
+    ChunkData::new(
+        world,
+        Entity::from_raw(0),
+        Entity::from_raw(1),
+        Vector3::zeros(),
+        Handle::default(),
+        Handle::default(),
+    )
+}
+
+fn bench_meshing(c: &mut Criterion) {
+    let states = block_states();
+    let mut group = c.benchmark_group("generate_mesh");
+
+    for (name, world, vertices) in scenarios() {
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        system.chunks.insert(Vector3::zeros(), chunk(world));
+
+        let cache = NearbyChunkCache::from_service(&system, Vector3::zeros());
+        let data = system.chunks.get(&Vector3::zeros()).unwrap();
+
+        let mesh = data.build_mesh(&system, &states, true, 1.0, None, &cache);
+        println!(
+            "{}: {} opaque vertices, {} translucent vertices",
+            name,
+            mesh.opaque.positions.len(),
+            mesh.translucent.positions.len()
+        );
+        assert_eq!(
+            (
+                mesh.opaque.positions.len(),
+                mesh.translucent.positions.len()
+            ),
+            vertices,
+            "{} vertices",
+            name
+        );
+
+        group.bench_with_input(BenchmarkId::from_parameter(name), data, |b, data| {
+            b.iter(|| black_box(data.build_mesh(&system, &states, true, 1.0, None, &cache)))
+        });
+    }
+
+    group.finish();
+}
+
+/// Breaking a block in the middle of terrain, which is the common case for rebuilding a mesh
+fn bench_remesh(c: &mut Criterion) {
+    let states = block_states();
+
+    let mut world = fill(terrain);
+    world.set(8, 4, 8, AIR);
+
+    let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+    system.chunks.insert(Vector3::zeros(), chunk(world));
+
+    let cache = NearbyChunkCache::from_service(&system, Vector3::zeros());
+    let data = system.chunks.get(&Vector3::zeros()).unwrap();
+
+    // The faces around the hole come out, the rest of the mesh is as it was
+    let mesh = data.build_mesh(&system, &states, true, 1.0, None, &cache);
+    assert_eq!(
+        (
+            mesh.opaque.positions.len(),
+            mesh.translucent.positions.len()
+        ),
+        (4968, 832)
+    );
+
+    c.bench_function("remesh_after_block_change", |b| {
+        b.iter(|| black_box(data.build_mesh(&system, &states, true, 1.0, None, &cache)))
+    });
+}
+
+/// Building the densest chunk with its buffers sized up front and without. Growing the buffers one face at a time
+/// reallocates every time they fill up, sizing them first should reallocate none of them
+fn bench_reserve(c: &mut Criterion) {
+    let states = block_states();
+    let mut group = c.benchmark_group("mesh_reserve");
+
+    let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+    system
+        .chunks
+        .insert(Vector3::zeros(), chunk(fill(checkerboard)));
+
+    let cache = NearbyChunkCache::from_service(&system, Vector3::zeros());
+    let data = system.chunks.get(&Vector3::zeros()).unwrap();
+
+    for (name, scale) in [("grown", 0.0), ("reserved", 1.0)] {
+        let mesh = data.build_mesh(&system, &states, true, scale, None, &cache);
+        println!(
+            "{}: {} vertices in a buffer with room for {}",
+            name,
+            mesh.opaque.positions.len(),
+            mesh.opaque.positions.capacity()
+        );
+
+        group.bench_with_input(BenchmarkId::from_parameter(name), &scale, |b, scale| {
+            b.iter(|| black_box(data.build_mesh(&system, &states, true, *scale, None, &cache)))
+        });
+    }
+
+    group.finish();
+}
+
+/// Drawing every face by itself against merging matching faces, for how long each takes and how many indices the
+/// mesh ends up with. Merging should win by the most on the solid chunk and not at all on the checkerboard
+fn bench_greedy(c: &mut Criterion) {
+    let states = block_states();
+    let mut group = c.benchmark_group("greedy_meshing");
+
+    for (name, world, _) in scenarios() {
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        system.chunks.insert(Vector3::zeros(), chunk(world));
+
+        let cache = NearbyChunkCache::from_service(&system, Vector3::zeros());
+        let data = system.chunks.get(&Vector3::zeros()).unwrap();
+
+        for (mesher, greedy) in [("per_face", None), ("greedy", Some(0))] {
+            let mesh = data.build_mesh(&system, &states, true, 1.0, greedy, &cache);
+            println!(
+                "{} {}: {} opaque indices, {} translucent indices",
+                name,
+                mesher,
+                mesh.opaque.indices.len(),
+                mesh.translucent.indices.len()
+            );
+
+            group.bench_with_input(BenchmarkId::new(mesher, name), &greedy, |b, greedy| {
+                b.iter(|| black_box(data.build_mesh(&system, &states, true, 1.0, *greedy, &cache)))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_meshing,
+    bench_remesh,
+    bench_reserve,
+    bench_greedy
+);
+criterion_main!(benches);
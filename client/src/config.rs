@@ -0,0 +1,486 @@
+use bevy::prelude::Resource;
+use rc_networking::compression::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+const CONFIG_PATH: &str = "client_settings.json";
+
+/// The lowest and highest speed multipliers allowed, to stop the player being stuck or moving faster than a server allows
+pub const MIN_SPEED_MULTIPLIER: f32 = 0.1;
+pub const MAX_SPEED_MULTIPLIER: f32 = 4.0;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Resource)]
+#[serde(default)]
+pub struct ClientConfig {
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    pub fly_speed: f32,
+    /// Steps up single block ledges when walking into them
+    pub auto_jump: bool,
+    /// Bobs the camera while walking, by an intensity from 0 to 2
+    pub view_bobbing: bool,
+    pub view_bobbing_intensity: f32,
+    /// Turns off camera bobbing and skips visual transitions, for players sensitive to motion.
+    /// Only what's drawn changes, the player moves and interacts the same
+    pub reduced_motion: bool,
+    /// How many ticks the render clock counts per second, matching the server's tick rate
+    pub clock_tick_rate: u32,
+    /// The shortest and longest time, in seconds, other players are drawn behind their latest position.
+    /// The delay in between adapts to how much the connection's latency varies
+    pub interpolation_delay_min: f32,
+    pub interpolation_delay_max: f32,
+    /// How long a day and night lasts, in seconds of the world clock
+    pub day_length: f32,
+    /// Draws the sun and moon in the sky
+    pub sun_and_moon: bool,
+    /// How far away, in chunks, chunks are loaded around the player
+    pub render_distance: i32,
+    /// How far away from spawn, in chunks, chunks are loaded and meshed before the player is let into the world
+    pub preload_radius: i32,
+    /// The longest, in seconds, to wait for preloading before letting the player in anyway
+    pub preload_timeout: f32,
+    /// Whether the game is shown in a window, a borderless window covering the monitor or exclusive fullscreen
+    pub display_mode: DisplayMode,
+    /// Which monitor the window opens on, counting from 0 in the order the system lists them. None uses the primary
+    /// monitor
+    pub monitor: Option<usize>,
+    /// The size of the window in pixels. Exclusive fullscreen switches the monitor to this resolution, or the closest
+    /// one the monitor supports
+    pub resolution: [u32; 2],
+    /// How many samples are taken per pixel to smooth jagged edges, 1 turns it off
+    pub msaa_samples: u32,
+    /// Makes bright parts of the picture glow. The game is drawn in HDR while it's on
+    pub bloom: bool,
+    /// How long, in seconds, to wait between placing or breaking blocks while the mouse button is held
+    pub interaction_cooldown: f32,
+    /// Smooths out mouse movement when looking around, from 0 for raw input up to 0.95
+    pub look_smoothing: f32,
+    /// Breaks blocks as soon as they're hit in creative, rather than mining them like in survival
+    pub creative_instant_break: bool,
+    /// Also writes the log to a file, for attaching to bug reports
+    pub log_to_file: bool,
+    pub log_directory: String,
+    /// The most detailed level written to the log file, such as "info" or "debug"
+    pub log_level: String,
+    /// How big, in bytes, the log file can get before it's rotated
+    pub log_file_size: u64,
+    /// How faces at the edge of a chunk are drawn when the chunk next to them hasn't loaded
+    pub chunk_edge_faces: ChunkEdgeFaces,
+    /// How much room is made in a chunk's mesh buffers per visible block side before it's meshed, so dense chunks
+    /// don't reallocate while they're built. Raise it for packs with many non-cube blocks, 0 turns it off
+    pub mesh_reserve_scale: f32,
+    /// Merges matching block faces next to each other into one larger face, so chunks have far fewer vertices.
+    /// Turn it off to draw every face by itself, for comparing against
+    pub greedy_meshing: bool,
+    /// How much, from 0 to 255, each channel of two faces' light can differ by for them to still be merged.
+    /// 0 only merges faces lit exactly the same, higher values merge more but make lighting blockier
+    pub greedy_light_tolerance: u8,
+    /// How far away, in blocks, a block can be targeted. Keep below the server's reach or edits are rejected
+    pub max_reach: f32,
+    /// How far out from a block its outline is drawn, in blocks
+    pub outline_inflation: f32,
+    /// The closest and furthest distances the camera draws, a larger near plane gives more depth precision
+    pub near_plane: f32,
+    pub far_plane: f32,
+    /// The distances, in blocks, that distant chunks start fading into the fog and are completely hidden by it
+    pub fog_start: f32,
+    pub fog_end: f32,
+    /// Captures the mouse again when the window regains focus during gameplay
+    pub capture_on_focus: bool,
+    /// Records every packet received from the server to a replay file, for reproducing desyncs
+    pub record_replay: bool,
+    pub replay_directory: String,
+    /// Where panoramas are saved, each in a folder of its own
+    pub panorama_directory: String,
+    /// The size of each face of a panorama in pixels
+    pub panorama_resolution: u32,
+    /// The longest, in seconds, a panorama waits for the chunks in view to load before capturing anyway
+    pub panorama_timeout: f32,
+    /// The schematic placed with the place schematic key
+    pub schematic_file: String,
+    /// A replay file to play back instead of connecting to a server
+    pub play_replay: Option<String>,
+    /// The name other players see, and the server knows you by for operators and your saved inventory and spawn
+    pub player_name: String,
+    /// How the server may compress large packets such as chunks, most preferred first. The server uses the first it
+    /// also supports, or sends them uncompressed. Just None opts out, to save CPU on a fast network
+    pub compression: Vec<Compression>,
+    /// Shows the name of the targeted block under the crosshair
+    pub block_tooltip: bool,
+    /// The largest the texture atlas can be on each side, in pixels. The graphics card's limit is used if it's lower
+    pub max_atlas_size: u32,
+    /// The largest a texture can be on each side, in pixels. Larger textures are scaled down to fit before they're
+    /// added to the atlas, 0 leaves them as they are
+    pub max_texture_size: u32,
+    /// How far away, in blocks, blocks such as water and portals can be heard looping their ambient sound
+    pub ambient_sound_range: f32,
+    /// The most ambient sounds played at once, the closest blocks are heard first
+    pub max_ambient_sounds: usize,
+    /// Shows just an fps counter, without the rest of the debug screen
+    pub fps_overlay: bool,
+    /// Which corner of the window the fps counter sits in
+    pub fps_overlay_corner: ScreenCorner,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            walk_speed: 1.0,
+            sprint_speed: 1.6,
+            fly_speed: 1.0,
+            auto_jump: false,
+            view_bobbing: true,
+            view_bobbing_intensity: 1.0,
+            reduced_motion: false,
+            clock_tick_rate: 20,
+            interpolation_delay_min: 0.05,
+            interpolation_delay_max: 0.3,
+            day_length: 1200.0,
+            sun_and_moon: true,
+            render_distance: 5,
+            preload_radius: 2,
+            preload_timeout: 10.0,
+            display_mode: DisplayMode::Windowed,
+            monitor: None,
+            resolution: [1280, 720],
+            msaa_samples: 1,
+            bloom: false,
+            interaction_cooldown: 0.2,
+            look_smoothing: 0.0,
+            creative_instant_break: true,
+            log_to_file: false,
+            log_directory: "logs".to_string(),
+            log_level: "info".to_string(),
+            log_file_size: 5 * 1024 * 1024,
+            chunk_edge_faces: ChunkEdgeFaces::UntilLoaded,
+            mesh_reserve_scale: 1.0,
+            greedy_meshing: true,
+            greedy_light_tolerance: 0,
+            max_reach: 15.0,
+            outline_inflation: 0.002,
+            near_plane: 0.1,
+            far_plane: 1000.0,
+            fog_start: 48.0,
+            fog_end: 80.0,
+            capture_on_focus: true,
+            record_replay: false,
+            replay_directory: "replays".to_string(),
+            panorama_directory: "panoramas".to_string(),
+            panorama_resolution: 1024,
+            panorama_timeout: 10.0,
+            schematic_file: "schematic.json".to_string(),
+            play_replay: None,
+            player_name: "Player".to_string(),
+            compression: vec![Compression::Fast, Compression::None],
+            block_tooltip: true,
+            max_atlas_size: 4096,
+            max_texture_size: 512,
+            ambient_sound_range: 16.0,
+            max_ambient_sounds: 8,
+            fps_overlay: false,
+            fps_overlay_corner: ScreenCorner::TopLeft,
+        }
+    }
+}
+
+/// How to draw faces against a chunk that isn't loaded yet
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChunkEdgeFaces {
+    /// Always drawn, so walls show inside the world where chunks meet
+    Always,
+    /// Never drawn, so there are gaps at the edge of the world while loading
+    Never,
+    /// Drawn until the neighbouring chunk loads, then the chunk is rebuilt without them
+    UntilLoaded,
+}
+
+impl ChunkEdgeFaces {
+    /// Whether to draw faces that border an unloaded chunk
+    pub fn draws_unloaded_faces(&self) -> bool {
+        match self {
+            ChunkEdgeFaces::Always | ChunkEdgeFaces::UntilLoaded => true,
+            ChunkEdgeFaces::Never => false,
+        }
+    }
+
+    /// Whether chunks next to a newly loaded chunk are rebuilt to match it
+    pub fn remesh_neighbours(&self) -> bool {
+        *self == ChunkEdgeFaces::UntilLoaded
+    }
+}
+
+/// A corner of the window to place something in
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScreenCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// How the game window is shown
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DisplayMode {
+    Windowed,
+    /// A window without borders covering the whole monitor, at the monitor's own resolution
+    Borderless,
+    /// Takes over the monitor, switching it to the chosen resolution
+    Fullscreen,
+}
+
+impl DisplayMode {
+    /// The mode after this one, wrapping back round to windowed
+    pub fn next(&self) -> DisplayMode {
+        match self {
+            DisplayMode::Windowed => DisplayMode::Borderless,
+            DisplayMode::Borderless => DisplayMode::Fullscreen,
+            DisplayMode::Fullscreen => DisplayMode::Windowed,
+        }
+    }
+}
+
+/// Bundles of graphics settings that can be switched between at once
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GraphicsPreset {
+    Low,
+    Medium,
+    High,
+    /// The settings have been changed away from every preset
+    Custom,
+}
+
+impl GraphicsPreset {
+    pub const ALL: [GraphicsPreset; 3] = [
+        GraphicsPreset::Low,
+        GraphicsPreset::Medium,
+        GraphicsPreset::High,
+    ];
+
+    /// The preset after this one, wrapping back round to the lowest
+    pub fn next(&self) -> GraphicsPreset {
+        match self {
+            GraphicsPreset::Low => GraphicsPreset::Medium,
+            GraphicsPreset::Medium => GraphicsPreset::High,
+            GraphicsPreset::High | GraphicsPreset::Custom => GraphicsPreset::Low,
+        }
+    }
+
+    /// The settings the preset sets, None for custom
+    pub fn settings(&self) -> Option<PresetSettings> {
+        let (render_distance, msaa_samples, bloom, fog_start, fog_end) = match self {
+            GraphicsPreset::Low => (3, 1, false, 32.0, 48.0),
+            GraphicsPreset::Medium => (5, 1, false, 48.0, 80.0),
+            GraphicsPreset::High => (8, 4, true, 96.0, 128.0),
+            GraphicsPreset::Custom => return None,
+        };
+
+        Some(PresetSettings {
+            render_distance,
+            msaa_samples,
+            bloom,
+            fog_start,
+            fog_end,
+        })
+    }
+}
+
+/// Every setting a graphics preset covers
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PresetSettings {
+    pub render_distance: i32,
+    pub msaa_samples: u32,
+    pub bloom: bool,
+    pub fog_start: f32,
+    pub fog_end: f32,
+}
+
+/// The ways the player can move, each with their own speed setting
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MovementMode {
+    Walk,
+    Sprint,
+    Fly,
+}
+
+impl ClientConfig {
+    /// The speed multiplier for a movement mode, clamped to the allowed range
+    pub fn movement_speed(&self, mode: MovementMode) -> f32 {
+        let speed = match mode {
+            MovementMode::Walk => self.walk_speed,
+            MovementMode::Sprint => self.sprint_speed,
+            MovementMode::Fly => self.fly_speed,
+        };
+
+        speed.clamp(MIN_SPEED_MULTIPLIER, MAX_SPEED_MULTIPLIER)
+    }
+
+    /// How much cosmetic motion is applied, 0 with reduced motion on. Anything that moves the camera
+    /// or animates on its own should be scaled by this
+    pub fn motion_scale(&self) -> f32 {
+        if self.reduced_motion {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Sets every setting a preset covers together. Custom leaves the settings as they are
+    pub fn apply_preset(&mut self, preset: GraphicsPreset) {
+        if let Some(settings) = preset.settings() {
+            self.render_distance = settings.render_distance;
+            self.msaa_samples = settings.msaa_samples;
+            self.bloom = settings.bloom;
+            self.fog_start = settings.fog_start;
+            self.fog_end = settings.fog_end;
+        }
+    }
+
+    /// The current values of the settings graphics presets cover
+    pub fn preset_settings(&self) -> PresetSettings {
+        PresetSettings {
+            render_distance: self.render_distance,
+            msaa_samples: self.msaa_samples,
+            bloom: self.bloom,
+            fog_start: self.fog_start,
+            fog_end: self.fog_end,
+        }
+    }
+
+    /// The preset the current settings match, or custom if they've been changed from all of them
+    pub fn graphics_preset(&self) -> GraphicsPreset {
+        GraphicsPreset::ALL
+            .into_iter()
+            .find(|preset| preset.settings() == Some(self.preset_settings()))
+            .unwrap_or(GraphicsPreset::Custom)
+    }
+}
+
+pub fn load_config() -> ClientConfig {
+    if !Path::new(CONFIG_PATH).exists() {
+        if let Ok(file) = File::create(CONFIG_PATH) {
+            let mut writer = BufWriter::new(file);
+            let _ = serde_json::to_writer_pretty(&mut writer, &ClientConfig::default());
+        }
+    }
+
+    let settings = if let Ok(file) = File::open(CONFIG_PATH) {
+        let reader = BufReader::new(file);
+
+        serde_json::from_reader(reader).ok()
+    } else {
+        None
+    };
+
+    settings.unwrap_or(ClientConfig::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applying_preset_sets_every_field() {
+        let mut config = ClientConfig::default();
+
+        config.apply_preset(GraphicsPreset::Low);
+        assert_eq!(config.render_distance, 3);
+        assert_eq!(config.msaa_samples, 1);
+        assert!(!config.bloom);
+        assert_eq!(config.fog_start, 32.0);
+        assert_eq!(config.fog_end, 48.0);
+        assert_eq!(config.graphics_preset(), GraphicsPreset::Low);
+
+        config.apply_preset(GraphicsPreset::High);
+        assert_eq!(config.render_distance, 8);
+        assert_eq!(config.msaa_samples, 4);
+        assert!(config.bloom);
+        assert_eq!(config.fog_start, 96.0);
+        assert_eq!(config.fog_end, 128.0);
+        assert_eq!(config.graphics_preset(), GraphicsPreset::High);
+    }
+
+    #[test]
+    fn default_settings_match_medium_preset() {
+        assert_eq!(
+            ClientConfig::default().graphics_preset(),
+            GraphicsPreset::Medium
+        );
+    }
+
+    #[test]
+    fn changed_setting_is_custom_and_custom_changes_nothing() {
+        let mut config = ClientConfig::default();
+        config.apply_preset(GraphicsPreset::Medium);
+        config.render_distance = 12;
+        assert_eq!(config.graphics_preset(), GraphicsPreset::Custom);
+
+        config.apply_preset(GraphicsPreset::Medium);
+        config.bloom = true;
+        assert_eq!(config.graphics_preset(), GraphicsPreset::Custom);
+
+        let before = config.clone();
+        config.apply_preset(GraphicsPreset::Custom);
+        assert_eq!(config, before);
+    }
+
+    #[test]
+    fn presets_cycle_back_round() {
+        let mut preset = GraphicsPreset::Custom;
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            preset = preset.next();
+            seen.push(preset);
+        }
+
+        assert_eq!(seen, GraphicsPreset::ALL.to_vec());
+        assert_eq!(preset.next(), GraphicsPreset::Low);
+    }
+
+    #[test]
+    fn each_movement_mode_uses_its_own_speed() {
+        let config = ClientConfig {
+            walk_speed: 0.5,
+            sprint_speed: 2.0,
+            fly_speed: 3.0,
+            ..ClientConfig::default()
+        };
+
+        assert_eq!(config.movement_speed(MovementMode::Walk), 0.5);
+        assert_eq!(config.movement_speed(MovementMode::Sprint), 2.0);
+        assert_eq!(config.movement_speed(MovementMode::Fly), 3.0);
+    }
+
+    #[test]
+    fn movement_speed_is_clamped() {
+        let config = ClientConfig {
+            walk_speed: 0.0,
+            sprint_speed: 100.0,
+            fly_speed: -1.0,
+            ..ClientConfig::default()
+        };
+
+        assert_eq!(
+            config.movement_speed(MovementMode::Walk),
+            MIN_SPEED_MULTIPLIER
+        );
+        assert_eq!(
+            config.movement_speed(MovementMode::Sprint),
+            MAX_SPEED_MULTIPLIER
+        );
+        assert_eq!(
+            config.movement_speed(MovementMode::Fly),
+            MIN_SPEED_MULTIPLIER
+        );
+    }
+
+    #[test]
+    fn reduced_motion_turns_off_cosmetic_motion() {
+        let mut config = ClientConfig::default();
+        assert_eq!(config.motion_scale(), 1.0);
+
+        config.reduced_motion = true;
+        assert_eq!(config.motion_scale(), 0.0);
+    }
+}
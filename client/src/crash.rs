@@ -0,0 +1,144 @@
+use crate::logging::recent_log_lines;
+use bevy::prelude::Res;
+use bevy::render::renderer::RenderAdapterInfo;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CRASH_REPORT_DIRECTORY: &str = "crash-reports";
+
+/// A description of the GPU, saved once the renderer starts so it can be included in crash reports
+static GPU_INFO: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn record_gpu_info(adapter: Res<RenderAdapterInfo>) {
+    if let Ok(mut info) = GPU_INFO.lock() {
+        *info = Some(format!("{:?}", **adapter));
+    }
+}
+
+/// Gets the message a panic was started with
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Builds the text of a crash report
+pub fn format_report(
+    message: &str,
+    location: Option<&str>,
+    backtrace: &str,
+    gpu: Option<&str>,
+    log_lines: &[String],
+) -> String {
+    let mut report = String::from("Rustcraft crash report\n\n");
+
+    report.push_str(&format!("Message: {}\n", message));
+    report.push_str(&format!("Location: {}\n", location.unwrap_or("Unknown")));
+    report.push_str(&format!(
+        "OS: {} ({})\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    ));
+    report.push_str(&format!("GPU: {}\n", gpu.unwrap_or("Unknown")));
+
+    report.push_str("\nBacktrace:\n");
+    report.push_str(backtrace);
+
+    report.push_str("\n\nRecent log:\n");
+    for line in log_lines {
+        report.push_str(line);
+        report.push('\n');
+    }
+
+    report
+}
+
+/// Writes a crash report whenever the game panics, on top of the usual panic output
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let message = panic_message(info.payload());
+        let location = info
+            .location()
+            .map(|v| format!("{}:{}:{}", v.file(), v.line(), v.column()));
+        let backtrace = Backtrace::force_capture().to_string();
+        let gpu = GPU_INFO.lock().ok().and_then(|v| v.clone());
+
+        let report = format_report(
+            &message,
+            location.as_deref(),
+            &backtrace,
+            gpu.as_deref(),
+            &recent_log_lines(),
+        );
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |v| v.as_secs());
+        let path = format!("{}/crash-{}.txt", CRASH_REPORT_DIRECTORY, timestamp);
+
+        match fs::create_dir_all(CRASH_REPORT_DIRECTORY).and_then(|_| fs::write(&path, report)) {
+            Ok(_) => eprintln!("Rustcraft crashed, a crash report was saved to {}", path),
+            Err(err) => eprintln!(
+                "Rustcraft crashed, and the crash report couldn't be saved: {:?}",
+                err
+            ),
+        }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_is_read_from_either_payload() {
+        let literal: Box<dyn Any + Send> = Box::new("chunk missing");
+        let formatted: Box<dyn Any + Send> = Box::new(format!("chunk {} missing", 4));
+        let other: Box<dyn Any + Send> = Box::new(4);
+
+        assert_eq!(panic_message(&*literal), "chunk missing");
+        assert_eq!(panic_message(&*formatted), "chunk 4 missing");
+        assert_eq!(panic_message(&*other), "Unknown panic");
+    }
+
+    #[test]
+    fn report_holds_every_section() {
+        let payload: Box<dyn Any + Send> = Box::new("chunk missing");
+        let lines = vec!["first line".to_string(), "second line".to_string()];
+
+        let report = format_report(
+            &panic_message(&*payload),
+            Some("src/main.rs:10:5"),
+            "0: rustcraft::main",
+            Some("Test GPU"),
+            &lines,
+        );
+
+        assert!(report.contains("Message: chunk missing\n"));
+        assert!(report.contains("Location: src/main.rs:10:5\n"));
+        assert!(report.contains(&format!("OS: {}", std::env::consts::OS)));
+        assert!(report.contains("GPU: Test GPU\n"));
+        assert!(report.contains("Backtrace:\n0: rustcraft::main"));
+        assert!(report.ends_with("Recent log:\nfirst line\nsecond line\n"));
+    }
+
+    #[test]
+    fn missing_details_are_unknown() {
+        let report = format_report("chunk missing", None, "", None, &[]);
+
+        assert!(report.contains("Location: Unknown\n"));
+        assert!(report.contains("GPU: Unknown\n"));
+    }
+}
@@ -0,0 +1,408 @@
+use crate::config::ClientConfig;
+use crate::game::blocks::states::BlockStates;
+use crate::systems::chunk::ChunkSystem;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::block_id;
+use std::collections::{HashMap, HashSet};
+
+/// How often, in seconds, the blocks around the player are searched for ambient sounds
+const SCAN_INTERVAL: f32 = 0.5;
+
+/// How far past the range, in blocks, a playing sound carries on before it's stopped, so standing at the
+/// edge of the range doesn't start and stop it over and over
+const STOP_MARGIN: f32 = 2.0;
+
+/// A looping sound a block plays while the player is near it
+#[derive(Debug, Clone, Copy)]
+pub struct AmbientSound {
+    pub path: &'static str,
+    /// How loud the sound is at the block, 1.0 is normal
+    pub volume: f32,
+}
+
+/// The ambient sound of each block that has one, by block identifier as ids depend on the order of the block states file
+#[derive(Resource)]
+pub struct BlockAmbientSounds {
+    sounds: HashMap<String, AmbientSound>,
+}
+
+impl Default for BlockAmbientSounds {
+    fn default() -> Self {
+        let mut sounds = BlockAmbientSounds {
+            sounds: HashMap::new(),
+        };
+        sounds.register(
+            "mcv3::Water",
+            AmbientSound {
+                path: "sounds/ambient/water.ogg",
+                volume: 0.6,
+            },
+        );
+        sounds.register(
+            "mcv3::Portal",
+            AmbientSound {
+                path: "sounds/ambient/portal.ogg",
+                volume: 0.8,
+            },
+        );
+        sounds
+    }
+}
+
+impl BlockAmbientSounds {
+    pub fn register(&mut self, identifier: &str, sound: AmbientSound) {
+        self.sounds.insert(identifier.to_string(), sound);
+    }
+
+    /// The sound of each block id that has one, so scanning chunks doesn't look up identifiers block by block
+    pub fn by_block_id(&self, blocks: &BlockStates) -> HashMap<u32, AmbientSound> {
+        blocks
+            .states
+            .iter()
+            .enumerate()
+            .filter_map(|(id, block)| {
+                self.sounds
+                    .get(&block.identifier)
+                    .map(|sound| (id as u32, *sound))
+            })
+            .collect()
+    }
+}
+
+/// A block's ambient sound that's playing
+pub struct AmbientSource {
+    sound: AmbientSound,
+    sink: Handle<AudioSink>,
+}
+
+/// The ambient sounds playing, by the position of the block playing them
+#[derive(Resource, Default)]
+pub struct AmbientSources {
+    playing: HashMap<Vector3<i32>, AmbientSource>,
+    /// The sounding blocks found by the latest scan
+    nearby: Vec<(Vector3<i32>, AmbientSound)>,
+    since_scan: f32,
+}
+
+/// The sources to start and stop to match the blocks around the player
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AmbientChanges {
+    pub start: Vec<Vector3<i32>>,
+    pub stop: Vec<Vector3<i32>>,
+}
+
+/// Picks the closest sounding blocks within range, up to the cap, starting those that aren't playing yet.
+/// Playing sources that left the range or were pushed out by closer blocks are stopped
+pub fn ambient_changes(
+    playing: &HashSet<Vector3<i32>>,
+    nearby: &[Vector3<i32>],
+    listener: Vec3,
+    range: f32,
+    cap: usize,
+) -> AmbientChanges {
+    let mut candidates: Vec<(f32, Vector3<i32>)> = nearby
+        .iter()
+        .map(|pos| (listener.distance(block_centre(*pos)), *pos))
+        .filter(|(distance, pos)| {
+            *distance <= range || (playing.contains(pos) && *distance <= range + STOP_MARGIN)
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+    candidates.truncate(cap);
+
+    let wanted: HashSet<Vector3<i32>> = candidates.iter().map(|(_, pos)| *pos).collect();
+
+    AmbientChanges {
+        start: candidates
+            .iter()
+            .map(|(_, pos)| *pos)
+            .filter(|pos| !playing.contains(pos))
+            .collect(),
+        stop: playing
+            .iter()
+            .filter(|pos| !wanted.contains(pos))
+            .copied()
+            .collect(),
+    }
+}
+
+fn block_centre(position: Vector3<i32>) -> Vec3 {
+    Vec3::new(
+        position.x as f32 + 0.5,
+        position.y as f32 + 0.5,
+        position.z as f32 + 0.5,
+    )
+}
+
+/// Every block within range of the listener that has an ambient sound
+fn scan_nearby(
+    chunks: &ChunkSystem,
+    blocks: &BlockStates,
+    registry: &BlockAmbientSounds,
+    listener: Vec3,
+    range: f32,
+) -> Vec<(Vector3<i32>, AmbientSound)> {
+    let sounds = registry.by_block_id(blocks);
+    if sounds.is_empty() {
+        return Vec::new();
+    }
+
+    let reach = range + STOP_MARGIN;
+    let chunk_of = |v: f32| (v / chunks.chunk_size as f32).floor() as i32;
+    let min = Vector3::new(
+        chunk_of(listener.x - reach),
+        chunk_of(listener.y - reach),
+        chunk_of(listener.z - reach),
+    );
+    let max = Vector3::new(
+        chunk_of(listener.x + reach),
+        chunk_of(listener.y + reach),
+        chunk_of(listener.z + reach),
+    );
+
+    let mut nearby = Vec::new();
+    for cx in min.x..=max.x {
+        for cy in min.y..=max.y {
+            for cz in min.z..=max.z {
+                let chunk = match chunks.chunks.get(&Vector3::new(cx, cy, cz)) {
+                    Some(val) if !val.is_empty() => val,
+                    _ => continue,
+                };
+
+                let size = chunk.world.size();
+                let origin = chunk.position * size as i32;
+                for x in 0..size {
+                    for y in 0..size {
+                        for z in 0..size {
+                            let sound = match sounds.get(&block_id(chunk.world.get(x, y, z))) {
+                                Some(val) => val,
+                                None => continue,
+                            };
+
+                            let position = origin + Vector3::new(x as i32, y as i32, z as i32);
+                            if listener.distance(block_centre(position)) <= reach {
+                                nearby.push((position, *sound));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    nearby
+}
+
+/// Finds the blocks with ambient sounds around the camera. Blocks only change when chunks do,
+/// so this doesn't need to run every frame
+pub fn scan_ambient_blocks(
+    mut sources: ResMut<AmbientSources>,
+    camera: Query<&Transform, With<Camera>>,
+    chunks: Res<ChunkSystem>,
+    blocks: Res<BlockStates>,
+    registry: Res<BlockAmbientSounds>,
+    config: Res<ClientConfig>,
+    time: Res<Time>,
+) {
+    sources.since_scan += time.delta_seconds();
+    if sources.since_scan < SCAN_INTERVAL {
+        return;
+    }
+
+    let listener = match camera.get_single() {
+        Ok(val) => val.translation,
+        Err(_) => return,
+    };
+
+    sources.since_scan = 0.0;
+    sources.nearby = scan_nearby(
+        &chunks,
+        &blocks,
+        &registry,
+        listener,
+        config.ambient_sound_range,
+    );
+}
+
+/// Loops the ambient sounds of the closest blocks that have one, quieter the further they are from the camera
+pub fn update_ambient_sounds(
+    mut sources: ResMut<AmbientSources>,
+    camera: Query<&Transform, With<Camera>>,
+    config: Res<ClientConfig>,
+    assets: Res<AssetServer>,
+    audio: Res<Audio>,
+    sinks: Res<Assets<AudioSink>>,
+) {
+    let listener = match camera.get_single() {
+        Ok(val) => val.translation,
+        Err(_) => return,
+    };
+    let range = config.ambient_sound_range;
+
+    let playing: HashSet<Vector3<i32>> = sources.playing.keys().copied().collect();
+    let nearby: Vec<Vector3<i32>> = sources.nearby.iter().map(|(pos, _)| *pos).collect();
+    let changes = ambient_changes(
+        &playing,
+        &nearby,
+        listener,
+        range,
+        config.max_ambient_sounds,
+    );
+    for position in changes.stop {
+        if let Some(source) = sources.playing.remove(&position) {
+            if let Some(sink) = sinks.get(&source.sink) {
+                sink.stop();
+            }
+        }
+    }
+
+    for position in changes.start {
+        let sound = match sources.nearby.iter().find(|(pos, _)| *pos == position) {
+            Some((_, val)) => *val,
+            None => continue,
+        };
+
+        // Starts silent, the volume is set below along with every other source
+        let sink = audio.play_with_settings(
+            assets.load(sound.path),
+            PlaybackSettings::LOOP.with_volume(0.0),
+        );
+        sources.playing.insert(
+            position,
+            AmbientSource {
+                sound,
+                sink: sinks.get_handle(sink),
+            },
+        );
+    }
+
+    for (position, source) in sources.playing.iter() {
+        let distance = listener.distance(block_centre(*position));
+        let volume = source.sound.volume * (1.0 - distance / (range + STOP_MARGIN));
+        if let Some(sink) = sinks.get(&source.sink) {
+            sink.set_volume(volume.max(0.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkEdgeFaces;
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use rc_networking::constants::CHUNK_SIZE;
+
+    const RANGE: f32 = 16.0;
+    const WOOD: u32 = 4;
+
+    /// Listening from the middle of the block at the origin
+    const LISTENER: Vec3 = Vec3::new(0.5, 0.5, 0.5);
+
+    /// A block along the x axis, its distance from the listener
+    fn at(distance: i32) -> Vector3<i32> {
+        Vector3::new(distance, 0, 0)
+    }
+
+    fn changes(playing: &[Vector3<i32>], nearby: &[Vector3<i32>], cap: usize) -> AmbientChanges {
+        let playing = playing.iter().copied().collect();
+        ambient_changes(&playing, nearby, LISTENER, RANGE, cap)
+    }
+
+    #[test]
+    fn blocks_entering_range_start() {
+        assert_eq!(
+            changes(&[], &[at(5), at(30)], 8),
+            AmbientChanges {
+                start: vec![at(5)],
+                stop: vec![],
+            }
+        );
+
+        // Already playing sources are left alone
+        assert_eq!(changes(&[at(5)], &[at(5)], 8), AmbientChanges::default());
+    }
+
+    #[test]
+    fn blocks_leaving_range_stop() {
+        assert_eq!(
+            changes(&[at(5), at(30)], &[at(5), at(30)], 8),
+            AmbientChanges {
+                start: vec![],
+                stop: vec![at(30)],
+            }
+        );
+
+        // Blocks that are no longer there stop too
+        assert_eq!(
+            changes(&[at(5)], &[], 8),
+            AmbientChanges {
+                start: vec![],
+                stop: vec![at(5)],
+            }
+        );
+    }
+
+    #[test]
+    fn sounds_carry_on_just_past_the_range() {
+        // Playing sources keep going within the margin
+        assert_eq!(changes(&[at(17)], &[at(17)], 8), AmbientChanges::default());
+        assert_eq!(changes(&[at(19)], &[at(19)], 8).stop, vec![at(19)]);
+
+        // But a source isn't started there
+        assert_eq!(changes(&[], &[at(17)], 8), AmbientChanges::default());
+    }
+
+    #[test]
+    fn closest_sources_win_the_cap() {
+        assert_eq!(
+            changes(&[at(12)], &[at(12), at(3), at(8)], 2),
+            AmbientChanges {
+                start: vec![at(3), at(8)],
+                stop: vec![at(12)],
+            }
+        );
+        assert_eq!(changes(&[], &[at(3)], 0), AmbientChanges::default());
+    }
+
+    fn registry() -> BlockAmbientSounds {
+        let mut registry = BlockAmbientSounds::default();
+        registry.register(
+            "mcv3::Wood",
+            AmbientSound {
+                path: "sounds/ambient/creak.ogg",
+                volume: 0.5,
+            },
+        );
+        registry
+    }
+
+    #[test]
+    fn sounds_are_looked_up_by_block_id() {
+        let sounds = registry().by_block_id(&BlockStates::shipped());
+
+        // The shipped blocks have no water or portals, only the registered wood
+        assert_eq!(sounds.len(), 1);
+        assert_eq!(sounds[&WOOD].path, "sounds/ambient/creak.ogg");
+    }
+
+    #[test]
+    fn scanning_finds_sounding_blocks_in_reach() {
+        let mut data = RawChunkData::new(CHUNK_SIZE);
+        data.set(1, 2, 3, WOOD);
+        data.set(4, 4, 4, 6);
+
+        let mut chunks = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        chunks.chunks.insert(
+            Vector3::new(1, 0, 0),
+            test_chunk(Vector3::new(1, 0, 0), data),
+        );
+        let blocks = BlockStates::shipped();
+
+        let nearby = scan_nearby(&chunks, &blocks, &registry(), LISTENER, RANGE);
+        let positions: Vec<_> = nearby.iter().map(|(pos, _)| *pos).collect();
+        assert_eq!(positions, vec![Vector3::new(CHUNK_SIZE as i32 + 1, 2, 3)]);
+
+        let far = Vec3::new(-100.0, 0.0, 0.0);
+        assert!(scan_nearby(&chunks, &blocks, &registry(), far, RANGE).is_empty());
+    }
+}
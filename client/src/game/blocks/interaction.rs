@@ -0,0 +1,141 @@
+use crate::game::blocks::states::BlockStates;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::{block_id, block_state, pack_block};
+use std::collections::HashMap;
+
+/// The block state bit a door uses to store whether it's open, above the rotation bits
+pub const OPEN_STATE_BIT: u8 = 0b100;
+
+/// What using a block did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockUse {
+    /// The block doesn't react to being used, so the player places a block against it instead
+    Pass,
+    /// The block reacted without changing itself
+    Handled,
+    /// The block changed to a new value, which is sent to the server like any other edit
+    Changed(u32),
+}
+
+/// A block that does something when right clicked, rather than having a block placed against it
+pub trait InteractableBlock: Send + Sync {
+    fn on_use(&self, block: u32, position: Vector3<i32>, player: &Transform) -> BlockUse;
+}
+
+/// Flips a bit in the block state when used, such as opening and closing a door
+pub struct ToggleState(pub u8);
+
+impl InteractableBlock for ToggleState {
+    fn on_use(&self, block: u32, _position: Vector3<i32>, _player: &Transform) -> BlockUse {
+        BlockUse::Changed(pack_block(block_id(block), block_state(block) ^ self.0))
+    }
+}
+
+/// The behaviour of each interactive block, by block identifier as ids depend on the order of the block states file
+#[derive(Resource)]
+pub struct BlockInteractions {
+    handlers: HashMap<String, Box<dyn InteractableBlock>>,
+}
+
+impl Default for BlockInteractions {
+    fn default() -> Self {
+        let mut interactions = BlockInteractions {
+            handlers: HashMap::new(),
+        };
+        interactions.register("mcv3::Door", ToggleState(OPEN_STATE_BIT));
+        interactions
+    }
+}
+
+impl BlockInteractions {
+    pub fn register(&mut self, identifier: &str, handler: impl InteractableBlock + 'static) {
+        self.handlers
+            .insert(identifier.to_string(), Box::new(handler));
+    }
+
+    /// Uses the block, passing if it has no behaviour of its own
+    pub fn on_use(
+        &self,
+        blocks: &BlockStates,
+        block: u32,
+        position: Vector3<i32>,
+        player: &Transform,
+    ) -> BlockUse {
+        match self
+            .handlers
+            .get(&blocks.get_block(block as usize).identifier)
+        {
+            Some(handler) => handler.on_use(block, position, player),
+            None => BlockUse::Pass,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u32 = 6;
+    const WOOD: u32 = 4;
+
+    /// Uses wood as a door, as the shipped block states don't have one
+    fn interactions() -> BlockInteractions {
+        let mut interactions = BlockInteractions::default();
+        interactions.register("mcv3::Wood", ToggleState(OPEN_STATE_BIT));
+        interactions
+    }
+
+    fn use_block(interactions: &BlockInteractions, block: u32) -> BlockUse {
+        interactions.on_use(
+            &BlockStates::shipped(),
+            block,
+            Vector3::new(1, 2, 3),
+            &Transform::default(),
+        )
+    }
+
+    #[test]
+    fn doors_open_and_close() {
+        let interactions = interactions();
+        let closed = pack_block(WOOD, 0b10);
+        let open = pack_block(WOOD, 0b110);
+
+        assert_eq!(use_block(&interactions, closed), BlockUse::Changed(open));
+        assert_eq!(use_block(&interactions, open), BlockUse::Changed(closed));
+    }
+
+    #[test]
+    fn other_blocks_fall_through_to_placing() {
+        assert_eq!(use_block(&interactions(), STONE), BlockUse::Pass);
+        assert_eq!(
+            use_block(&interactions(), pack_block(STONE, OPEN_STATE_BIT)),
+            BlockUse::Pass
+        );
+    }
+
+    #[test]
+    fn handlers_can_react_without_changing_the_block() {
+        struct Bell;
+
+        impl InteractableBlock for Bell {
+            fn on_use(
+                &self,
+                _block: u32,
+                _position: Vector3<i32>,
+                _player: &Transform,
+            ) -> BlockUse {
+                BlockUse::Handled
+            }
+        }
+
+        let mut interactions = interactions();
+        interactions.register("mcv3::Stone", Bell);
+
+        assert_eq!(use_block(&interactions, STONE), BlockUse::Handled);
+        assert_eq!(
+            use_block(&interactions, WOOD),
+            BlockUse::Changed(pack_block(WOOD, OPEN_STATE_BIT))
+        );
+    }
+}
@@ -1,18 +1,14 @@
 use crate::game::blocks::loading::BlockStatesFile;
 use crate::game::blocks::states::BlockStates;
 use crate::game::blocks::Block;
-use crate::game::viewable_direction::ViewableDirectionBitMap;
 
 use crate::systems::asset::AssetService;
 use crate::systems::chunk::ChunkSystem;
-use crate::systems::physics::aabb::Aabb;
 use crate::systems::ui::loading::LoadingData;
 
 use crate::systems::chunk::builder::{RerenderChunkFlag, RerenderChunkFlagContext};
-use crate::systems::chunk::mesh::face::Face;
 use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
 use bevy::prelude::*;
-use nalgebra::Vector3;
 
 #[derive(Default)]
 pub struct BlockStateAssetLoader;
@@ -50,7 +46,7 @@ pub fn track_blockstate_changes(
     atlas: Res<AssetService>,
     chunks: ResMut<ChunkSystem>,
     mut commands: Commands,
-    mut loading: ResMut<LoadingData>,
+    loading: Option<ResMut<LoadingData>>,
     mut rerender_chunks: EventWriter<RerenderChunkFlag>,
 ) {
     for event in events.iter() {
@@ -73,7 +69,10 @@ pub fn track_blockstate_changes(
     if states.recalculate {
         println!("Recalculating!~");
         // Copy data over to blockstates, with full amount of data like normals and looking up texture atlas indexes
-        let (_, asset) = assets.iter().next().unwrap();
+        let asset = match assets.iter().next() {
+            Some((_, val)) => val,
+            None => return,
+        };
 
         let mut new_states = Vec::with_capacity(asset.states.len());
 
@@ -86,52 +85,16 @@ pub fn track_blockstate_changes(
             .unwrap();
 
         for block in &asset.states {
-            let mut new_block = Block {
-                identifier: block.identifier.clone(),
-                translucent: block.translucent,
-                full: block.full,
-                draw_betweens: block.draw_betweens,
-                faces: Vec::with_capacity(block.faces.len()),
-                bounding_boxes: (&block.colliders)
-                    .iter()
-                    .map(|v| Aabb::new(v.bottom_left, v.size))
-                    .collect::<Vec<Aabb>>(),
-                emission: block.emission,
-            };
-
-            for face in &block.faces {
-                // Lookup atlas index, or display glitch texture
-                let texture = *atlas
+            // Lookup atlas index, or display glitch texture
+            new_states.push(Block::from_deserialised(block, |texture| {
+                *atlas
                     .texture_atlas
                     .as_ref()
                     .unwrap()
                     .index
-                    .get(&face.texture)
-                    .unwrap_or(&error_texture);
-
-                let direction = ViewableDirectionBitMap::from_code(face.direction).unwrap();
-
-                let normal = match direction {
-                    ViewableDirectionBitMap::Top => Vector3::new(0.0, 1.0, 0.0),
-                    ViewableDirectionBitMap::Bottom => Vector3::new(0.0, -1.0, 0.0),
-                    ViewableDirectionBitMap::Left => Vector3::new(0.0, 0.0, -1.0),
-                    ViewableDirectionBitMap::Right => Vector3::new(0.0, 0.0, 1.0),
-                    ViewableDirectionBitMap::Front => Vector3::new(-1.0, 0.0, 0.0),
-                    ViewableDirectionBitMap::Back => Vector3::new(1.0, 0.0, 0.0),
-                };
-
-                new_block.faces.push(Face {
-                    top_left: face.top_left,
-                    top_right: face.top_right,
-                    bottom_left: face.bottom_left,
-                    texture,
-                    normal,
-                    edge: face.edge,
-                    direction,
-                })
-            }
-
-            new_states.push(new_block);
+                    .get(texture)
+                    .unwrap_or(&error_texture)
+            }));
         }
 
         states.states = new_states;
@@ -147,6 +110,8 @@ pub fn track_blockstate_changes(
             });
         }
 
-        loading.block_states = true;
+        if let Some(mut loading) = loading {
+            loading.block_states = true;
+        }
     }
 }
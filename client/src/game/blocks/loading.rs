@@ -17,6 +17,16 @@ pub struct DeserialisedBlock {
     pub faces: Vec<DeserialisedFace>,
     pub colliders: Vec<DeserialisedAabb>,
     pub emission: [u8; 4],
+    #[serde(default = "default_hardness")]
+    pub hardness: f32,
+    /// Whether the block is turned to face the player when placed
+    #[serde(default)]
+    pub rotatable: bool,
+}
+
+/// Blocks that don't say how hard they are take half a second to mine
+fn default_hardness() -> f32 {
+    0.5
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
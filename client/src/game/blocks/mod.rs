@@ -1,17 +1,17 @@
+pub mod interaction;
 pub mod loader;
 pub mod loading;
 pub mod states;
 
+use crate::game::blocks::interaction::BlockInteractions;
 use crate::game::blocks::states::BlockStates;
-use crate::game::viewable_direction::{AxisAlignedDirection, ViewableDirection};
+use crate::game::viewable_direction::{ViewableDirection, ViewableDirectionBitMap};
 use crate::systems::asset::atlas::index::TextureAtlasIndex;
 
 use crate::game::blocks::loader::{track_blockstate_changes, BlockStateAssetLoader};
-use crate::game::blocks::loading::BlockStatesFile;
-use crate::state::AppState;
-use crate::systems::chunk::data::LightingColor;
+use crate::game::blocks::loading::{BlockStatesFile, DeserialisedBlock};
 use crate::systems::chunk::mesh::draw_kit::DrawKit;
-use crate::systems::chunk::mesh::face::Face;
+use crate::systems::chunk::mesh::face::{Face, FaceLighting};
 use crate::systems::physics::aabb::Aabb;
 use bevy::prelude::*;
 use nalgebra::Vector3;
@@ -25,9 +25,9 @@ impl Plugin for BlockStatesPlugin {
             .init_asset_loader::<BlockStateAssetLoader>()
             .add_startup_system(create_block_states)
             .insert_resource(BlockStates::new())
-            .add_system_set(
-                SystemSet::on_update(AppState::Loading).with_system(track_blockstate_changes),
-            );
+            .init_resource::<BlockInteractions>()
+            // Runs outside of loading too so block states follow resource pack changes
+            .add_system(track_blockstate_changes);
     }
 }
 
@@ -43,31 +43,223 @@ pub struct Block {
     pub draw_betweens: bool,
     pub faces: Vec<Face>,
     pub bounding_boxes: Vec<Aabb>,
+    /// The color of light the block gives off followed by how far it reaches, 0 for blocks that don't glow
     pub emission: [u8; 4],
+    /// How long, in seconds, the block takes to mine in survival
+    pub hardness: f32,
+    /// Whether the block is turned to face the player when placed, other blocks are always placed unrotated
+    pub rotatable: bool,
 }
 
 impl Block {
+    /// Builds a block from its definition in the block states file, looking up each face's texture in the atlas
+    pub fn from_deserialised(
+        block: &DeserialisedBlock,
+        texture_index: impl Fn(&str) -> TextureAtlasIndex,
+    ) -> Block {
+        let mut new_block = Block {
+            identifier: block.identifier.clone(),
+            translucent: block.translucent,
+            full: block.full,
+            draw_betweens: block.draw_betweens,
+            faces: Vec::with_capacity(block.faces.len()),
+            bounding_boxes: (&block.colliders)
+                .iter()
+                .map(|v| Aabb::new(v.bottom_left, v.size))
+                .collect::<Vec<Aabb>>(),
+            emission: block.emission,
+            hardness: block.hardness,
+            rotatable: block.rotatable,
+        };
+
+        for face in &block.faces {
+            let texture = texture_index(&face.texture);
+
+            let direction = ViewableDirectionBitMap::from_code(face.direction).unwrap();
+
+            let normal = match direction {
+                ViewableDirectionBitMap::Top => Vector3::new(0.0, 1.0, 0.0),
+                ViewableDirectionBitMap::Bottom => Vector3::new(0.0, -1.0, 0.0),
+                ViewableDirectionBitMap::Left => Vector3::new(-1.0, 0.0, 0.0),
+                ViewableDirectionBitMap::Right => Vector3::new(1.0, 0.0, 0.0),
+                ViewableDirectionBitMap::Front => Vector3::new(0.0, 0.0, -1.0),
+                ViewableDirectionBitMap::Back => Vector3::new(0.0, 0.0, 1.0),
+            };
+
+            new_block.faces.push(Face {
+                top_left: face.top_left,
+                top_right: face.top_right,
+                bottom_left: face.bottom_left,
+                texture,
+                normal,
+                edge: face.edge,
+                direction,
+            })
+        }
+
+        new_block
+    }
+
     pub fn draw(
         &self,
         pos: Vector3<f32>,
+        state: u8,
         visible_map: ViewableDirection,
-        light_color: [LightingColor; 6],
-        mut kit: &mut DrawKit,
+        light: &FaceLighting,
+        kit: &mut DrawKit,
     ) {
-        for face in &self.faces {
+        self.visible_faces(state, visible_map, |_, face| {
+            kit.draw_face(pos, face, light.for_face(face));
+        });
+    }
+
+    /// Goes through the faces that aren't culled, rotated to match the block's orientation, along with where
+    /// they are in the block's list of faces
+    pub fn visible_faces(
+        &self,
+        state: u8,
+        visible_map: ViewableDirection,
+        mut visit: impl FnMut(usize, &Face),
+    ) {
+        let rotation = block_rotation(state);
+
+        for (index, face) in self.faces.iter().enumerate() {
+            // Rotate the face to match the blocks orientation
+            let rotated;
+            let face = if rotation == 0 {
+                face
+            } else {
+                rotated = face.rotate_y(rotation);
+                &rotated
+            };
+
             if !visible_map.has_flag(face.direction) && face.edge {
                 // Not visible from that direction and marked as an edge face, so cull
                 continue;
             }
 
-            // Get lighting color
-            let color = light_color[AxisAlignedDirection::from(face.direction) as usize];
+            visit(index, face);
+        }
+    }
 
-            kit.draw_face(pos, face, color);
+    /// The boxes outlining this block when it's targeted, matching its shape and orientation
+    pub fn outline_boxes(&self, state: u8) -> Vec<Aabb> {
+        // Full blocks and blocks without a shape use the whole block
+        if self.full || self.bounding_boxes.is_empty() {
+            return vec![Aabb::new(Vector3::zeros(), Vector3::new(1.0, 1.0, 1.0))];
         }
+
+        let rotation = block_rotation(state);
+
+        self.bounding_boxes
+            .iter()
+            .map(|v| v.rotate_y(rotation))
+            .collect()
     }
 }
 
+/// The number of clockwise 90 degree turns around the Y axis stored in the low bits of a block state
+pub fn block_rotation(state: u8) -> u8 {
+    state & 0b11
+}
+
 pub trait BlockGenerator {
     fn generate(texture_mapping: HashMap<String, TextureAtlasIndex>) -> Block;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::viewable_direction::BLOCK_SIDES;
+
+    fn block(full: bool, bounding_boxes: Vec<Aabb>) -> Block {
+        Block {
+            identifier: "mcv3::Test".to_string(),
+            translucent: false,
+            full,
+            draw_betweens: false,
+            faces: Vec::new(),
+            bounding_boxes,
+            emission: [0; 4],
+            hardness: 0.0,
+            rotatable: false,
+        }
+    }
+
+    fn unit_cube() -> Aabb {
+        Aabb::new(Vector3::zeros(), Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn slab_outline_is_half_height() {
+        let slab = block(
+            false,
+            vec![Aabb::new(Vector3::zeros(), Vector3::new(1.0, 0.5, 1.0))],
+        );
+
+        // Turning a slab that covers the whole block across doesn't change it
+        for state in 0..4 {
+            assert_eq!(
+                slab.outline_boxes(state),
+                vec![Aabb::new(Vector3::zeros(), Vector3::new(1.0, 0.5, 1.0))]
+            );
+        }
+    }
+
+    #[test]
+    fn outline_turns_with_the_block() {
+        // The half of a block with the lower z, which moves to the far x side after a turn
+        let half = block(
+            false,
+            vec![Aabb::new(Vector3::zeros(), Vector3::new(1.0, 1.0, 0.5))],
+        );
+
+        assert_eq!(
+            half.outline_boxes(1),
+            vec![Aabb::new(
+                Vector3::new(0.5, 0.0, 0.0),
+                Vector3::new(0.5, 1.0, 1.0)
+            )]
+        );
+    }
+
+    #[test]
+    fn full_blocks_outline_the_unit_cube() {
+        let shaped = block(
+            true,
+            vec![Aabb::new(Vector3::zeros(), Vector3::new(1.0, 0.5, 1.0))],
+        );
+        assert_eq!(shaped.outline_boxes(0), vec![unit_cube()]);
+
+        // Blocks without a shape, like long grass, still get an outline
+        assert_eq!(block(false, Vec::new()).outline_boxes(2), vec![unit_cube()]);
+    }
+
+    #[test]
+    fn side_faces_point_out_of_their_side() {
+        let states = BlockStates::shipped();
+
+        for block in &states.states {
+            for face in block.faces.iter().filter(|v| v.edge) {
+                let side =
+                    BLOCK_SIDES[AxisAlignedDirection::from(face.direction) as usize].cast::<f32>();
+                assert_eq!(
+                    face.normal, side,
+                    "{} {:?}",
+                    block.identifier, face.direction
+                );
+
+                // Every corner lies on that side of the block, at 1 on positive sides and 0 on negative ones
+                for corner in [face.top_left, face.top_right, face.bottom_left] {
+                    assert_eq!(
+                        corner.dot(&side),
+                        side.sum().max(0.0),
+                        "{} {:?}",
+                        block.identifier,
+                        face.direction
+                    );
+                }
+            }
+        }
+    }
+}
@@ -3,6 +3,7 @@ use crate::game::blocks::Block;
 use bevy::prelude::Resource;
 use bevy::prelude::{warn, Handle};
 use bevy::reflect::TypeUuid;
+use rc_networking::constants::block_id;
 
 #[derive(Debug, Clone, TypeUuid, Resource)]
 #[uuid = "97103fab-1e50-36b7-0c33-0938a62b0809"]
@@ -23,7 +24,9 @@ impl BlockStates {
     }
 
     // Possibly remove, keeping it because it was in old version and I might need it
+    /// Looks up a block by its stored value. Any block state packed into the value is ignored
     pub fn get_block(&self, i: usize) -> &Block {
+        let i = block_id(i as u32) as usize;
         if let Some(val) = self.states.get(i) {
             val
         } else {
@@ -32,3 +35,24 @@ impl BlockStates {
         }
     }
 }
+
+#[cfg(test)]
+impl BlockStates {
+    /// The block states the game ships with, with every face using the same texture as there's no atlas in tests
+    pub fn shipped() -> BlockStates {
+        use crate::systems::asset::atlas::index::TextureAtlasIndex;
+
+        let file = serde_json::from_str::<BlockStatesFile>(include_str!(
+            "../../../assets/game/block_states.blocks"
+        ))
+        .unwrap();
+
+        let mut states = BlockStates::new();
+        states.states = file
+            .states
+            .iter()
+            .map(|v| Block::from_deserialised(v, |_| TextureAtlasIndex::new(0.0, 1.0, 0.0, 1.0)))
+            .collect();
+        states
+    }
+}
@@ -0,0 +1,306 @@
+use crate::config::ClientConfig;
+use crate::game::blocks::states::BlockStates;
+use crate::game::interaction::prediction::PredictedEdits;
+use crate::game::interaction::schematic::{
+    apply_edits, check_edits, Schematic, SchematicPlacement, MAX_SCHEMATIC_BLOCKS,
+};
+use crate::game::interaction::undo::BlockEditHistory;
+use crate::helpers::from_bevy_vec3;
+use crate::systems::chunk::builder::RerenderChunkFlag;
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::physics::raycasts::do_raycast;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::types::SendPacket;
+
+/// Key that sets the first corner of the selection to the targeted block
+pub const FIRST_CORNER_KEY: KeyCode = KeyCode::LBracket;
+
+/// Key that sets the second corner of the selection to the targeted block
+pub const SECOND_CORNER_KEY: KeyCode = KeyCode::RBracket;
+
+/// Keys that copy the selection and paste the clipboard, while control is held
+pub const COPY_KEY: KeyCode = KeyCode::C;
+pub const PASTE_KEY: KeyCode = KeyCode::V;
+
+/// The blocks copied from the world, pasted with the same rotation as schematics
+#[derive(Resource, Default)]
+pub struct Clipboard {
+    /// Opposite corners of the selection, both included
+    pub corners: [Option<Vector3<i32>>; 2],
+    pub contents: Option<Schematic>,
+}
+
+/// How many blocks are in the cuboid between two corners, both included
+pub fn selection_volume(a: Vector3<i32>, b: Vector3<i32>) -> usize {
+    (0..3)
+        .map(|i| (a[i] - b[i]).unsigned_abs() as usize + 1)
+        .product()
+}
+
+/// Copies the blocks between two corners, to be pasted with the first corner at the targeted position. As with
+/// schematics, air in the clipboard leaves the world as it is. None if part of the selection isn't loaded
+pub fn copy_region(chunks: &ChunkSystem, a: Vector3<i32>, b: Vector3<i32>) -> Option<Schematic> {
+    let min = a.inf(&b);
+    let max = a.sup(&b);
+    let size = max - min + Vector3::new(1, 1, 1);
+
+    let mut blocks = Vec::with_capacity(selection_volume(a, b));
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                blocks.push(chunks.get_block(Vector3::new(x, y, z))?);
+            }
+        }
+    }
+
+    let origin = a - min;
+    Some(Schematic {
+        size: [size.x as usize, size.y as usize, size.z as usize],
+        origin: [origin.x, origin.y, origin.z],
+        blocks,
+    })
+}
+
+/// Selects, copies and pastes regions of blocks against the targeted block
+pub fn clipboard_tool(
+    keys: Res<Input<KeyCode>>,
+    camera: Query<&Transform, With<Camera>>,
+    mut chunks: ResMut<ChunkSystem>,
+    blocks: Res<BlockStates>,
+    config: Res<ClientConfig>,
+    (mut clipboard, placement): (ResMut<Clipboard>, Res<SchematicPlacement>),
+    (mut history, mut predicted, mut networking, mut rerender_chunks): (
+        ResMut<BlockEditHistory>,
+        ResMut<PredictedEdits>,
+        EventWriter<SendPacket>,
+        EventWriter<RerenderChunkFlag>,
+    ),
+) {
+    let control = keys.pressed(KeyCode::LControl);
+    let corner = keys.any_just_pressed([FIRST_CORNER_KEY, SECOND_CORNER_KEY]);
+    let copy = control && keys.just_pressed(COPY_KEY);
+    let paste = control && keys.just_pressed(PASTE_KEY);
+    if !corner && !copy && !paste {
+        return;
+    }
+
+    let camera = match camera.get_single() {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+    let eye = from_bevy_vec3(camera.translation);
+    let look = from_bevy_vec3(camera.rotation * Vec3::new(0.0, 0.0, -1.0));
+    let ray = do_raycast(eye, look, config.max_reach, &chunks, &blocks);
+
+    if corner {
+        let ray = match &ray {
+            Some(val) => val,
+            None => return,
+        };
+        let index = if keys.just_pressed(FIRST_CORNER_KEY) {
+            0
+        } else {
+            1
+        };
+        clipboard.corners[index] = Some(ray.block);
+        info!("Selection corner {} set to {:?}", index + 1, ray.block);
+    }
+
+    if copy {
+        let (a, b) = match clipboard.corners {
+            [Some(a), Some(b)] => (a, b),
+            _ => {
+                warn!("Select two corners before copying");
+                return;
+            }
+        };
+
+        let volume = selection_volume(a, b);
+        if volume > MAX_SCHEMATIC_BLOCKS {
+            warn!(
+                "Can't copy {} blocks, the most at once is {}",
+                volume, MAX_SCHEMATIC_BLOCKS
+            );
+            return;
+        }
+
+        match copy_region(&chunks, a, b) {
+            Some(val) => {
+                clipboard.contents = Some(val);
+                info!("Copied {} blocks", volume);
+            }
+            None => warn!("Can't copy a selection that isn't completely loaded"),
+        }
+    }
+
+    if paste {
+        let contents = match &clipboard.contents {
+            Some(val) => val,
+            None => {
+                warn!("Nothing has been copied to paste");
+                return;
+            }
+        };
+
+        // Pasted against the targeted face, like placing a single block
+        let target = match ray {
+            Some(ray) => ray.block + ray.normal,
+            None => return,
+        };
+
+        let edits = match contents
+            .edits(&blocks, target, placement.rotation)
+            .and_then(|edits| check_edits(&edits, eye, config.max_reach, &chunks).map(|_| edits))
+        {
+            Ok(val) => val,
+            Err(err) => {
+                warn!("Can't paste: {:?}", err);
+                return;
+            }
+        };
+
+        apply_edits(
+            &edits,
+            &mut chunks,
+            &mut history,
+            &mut predicted,
+            &mut networking,
+            &mut rerender_chunks,
+        );
+        info!("Pasted {} blocks", edits.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkEdgeFaces;
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use rc_networking::constants::{pack_block, CHUNK_SIZE};
+
+    const DIRT: u32 = 1;
+    const WOOD: u32 = 4;
+    const STONE: u32 = 6;
+
+    /// A single loaded chunk at the origin holding the given blocks
+    fn chunks(blocks: &[([usize; 3], u32)]) -> ChunkSystem {
+        let mut world = RawChunkData::new(CHUNK_SIZE);
+        for ([x, y, z], block) in blocks {
+            world.set(*x, *y, *z, *block);
+        }
+
+        let mut chunks = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        chunks
+            .chunks
+            .insert(Vector3::zeros(), test_chunk(Vector3::zeros(), world));
+        chunks
+    }
+
+    #[test]
+    fn selection_volume_includes_both_corners() {
+        assert_eq!(
+            selection_volume(Vector3::new(3, 4, 5), Vector3::new(3, 4, 5)),
+            1
+        );
+        assert_eq!(
+            selection_volume(Vector3::new(0, 0, 0), Vector3::new(1, 2, 3)),
+            24
+        );
+        // Whichever way round the corners are
+        assert_eq!(
+            selection_volume(Vector3::new(1, 2, 3), Vector3::new(0, 0, 0)),
+            24
+        );
+        assert_eq!(
+            selection_volume(Vector3::new(-2, 5, 1), Vector3::new(2, 5, -1)),
+            15
+        );
+    }
+
+    #[test]
+    fn selections_over_the_limit_are_too_large() {
+        let corner = Vector3::new(0, 0, 0);
+
+        assert_eq!(
+            selection_volume(corner, Vector3::new(31, 31, 31)),
+            MAX_SCHEMATIC_BLOCKS
+        );
+        assert!(selection_volume(corner, Vector3::new(32, 31, 31)) > MAX_SCHEMATIC_BLOCKS);
+    }
+
+    #[test]
+    fn copying_keeps_blocks_relative_to_the_first_corner() {
+        let chunks = chunks(&[([1, 1, 1], STONE), ([2, 1, 2], DIRT), ([2, 2, 1], WOOD)]);
+
+        // Selected from the far corner, so that's what lines up with the pasted position
+        let copied = copy_region(&chunks, Vector3::new(2, 2, 2), Vector3::new(1, 1, 1)).unwrap();
+        assert_eq!(copied.size, [2, 2, 2]);
+        assert_eq!(copied.origin, [1, 1, 1]);
+        assert_eq!(copied.blocks, vec![STONE, 0, 0, 0, 0, DIRT, WOOD, 0]);
+    }
+
+    #[test]
+    fn unloaded_selections_cant_be_copied() {
+        let chunks = chunks(&[]);
+
+        assert!(copy_region(&chunks, Vector3::new(-1, 0, 0), Vector3::new(1, 1, 1)).is_none());
+    }
+
+    #[test]
+    fn pasting_places_the_copied_blocks_at_the_target() {
+        let chunks = chunks(&[([1, 1, 1], STONE), ([2, 1, 1], DIRT), ([1, 1, 2], WOOD)]);
+        let copied = copy_region(&chunks, Vector3::new(1, 1, 1), Vector3::new(2, 1, 2)).unwrap();
+
+        // Air in the selection is left out
+        let edits = copied
+            .edits(&BlockStates::shipped(), Vector3::new(10, 5, 10), 0)
+            .unwrap();
+        assert_eq!(
+            edits,
+            vec![
+                (Vector3::new(10, 5, 10), STONE),
+                (Vector3::new(10, 5, 11), WOOD),
+                (Vector3::new(11, 5, 10), DIRT),
+            ]
+        );
+    }
+
+    #[test]
+    fn pasting_turns_the_copy_around_the_target() {
+        let chunks = chunks(&[([1, 1, 1], STONE), ([2, 1, 1], DIRT), ([1, 1, 2], WOOD)]);
+        let copied = copy_region(&chunks, Vector3::new(1, 1, 1), Vector3::new(2, 1, 2)).unwrap();
+
+        let edits = copied
+            .edits(&BlockStates::shipped(), Vector3::new(10, 5, 10), 1)
+            .unwrap();
+        assert_eq!(
+            edits,
+            vec![
+                (Vector3::new(10, 5, 10), STONE),
+                (Vector3::new(9, 5, 10), pack_block(WOOD, 1)),
+                (Vector3::new(10, 5, 11), DIRT),
+            ]
+        );
+    }
+
+    #[test]
+    fn pasting_turned_leaves_blocks_that_cant_rotate_unturned() {
+        let chunks = chunks(&[
+            ([1, 1, 1], STONE),
+            ([2, 1, 1], STONE),
+            ([1, 1, 2], STONE),
+            ([2, 1, 2], STONE),
+        ]);
+        let copied = copy_region(&chunks, Vector3::new(1, 1, 1), Vector3::new(2, 1, 2)).unwrap();
+
+        for rotation in 1..4 {
+            let edits = copied
+                .edits(&BlockStates::shipped(), Vector3::new(10, 5, 10), rotation)
+                .unwrap();
+
+            assert_eq!(edits.len(), 4);
+            assert!(edits.iter().all(|(_, block)| *block == STONE));
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::game::blocks::states::BlockStates;
+use crate::game::interaction::timing::BreakProgress;
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::physics::aabb::Aabb;
+use bevy::prelude::*;
+use bevy_prototype_debug_lines::DebugLines;
+use nalgebra::Vector3;
+use rc_networking::constants::{block_state, UserId};
+use rc_networking::protocol::serverbound::block_break_progress::BlockBreakProgress;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+
+/// How many steps the cracks in a block being mined grow in. The server is told about each step
+pub const CRACK_STAGES: u8 = 10;
+
+const CRACK_COLOR: Color = Color::rgb(0.3, 0.25, 0.2);
+
+/// Which step the cracks are at for how far through mining a block the player is
+pub fn crack_stage(progress: f32) -> u8 {
+    (progress.clamp(0.0, 1.0) * CRACK_STAGES as f32).floor() as u8
+}
+
+/// The block and crack stage the server was last told about
+#[derive(Default)]
+pub struct ReportedBreak(Option<(Vector3<i32>, u8)>);
+
+impl ReportedBreak {
+    /// Tells the server how far the block being mined has got whenever the cracks grow, or that mining stopped.
+    /// This is sent as mining happens so the server always hears about it before the block is broken
+    pub fn report(&mut self, mining: &BreakProgress, networking: &mut EventWriter<SendPacket>) {
+        let progress = mining.progress();
+        let stage = progress.map(|(target, progress)| (target, crack_stage(progress)));
+        if stage == self.0 {
+            return;
+        }
+
+        let packet = match (progress, self.0) {
+            (Some((target, progress)), _) => {
+                BlockBreakProgress::new(target.x, target.y, target.z, progress)
+            }
+            (None, Some((target, _))) => BlockBreakProgress::new(target.x, target.y, target.z, 0.0),
+            (None, None) => return,
+        };
+        networking.send(SendPacket(Protocol::BlockBreakProgress(packet), UserId(0)));
+        self.0 = stage;
+    }
+}
+
+/// Draws cracks growing out from the middle of the block being mined, in the shape of its outline
+pub fn draw_break_cracks(
+    mining: Res<BreakProgress>,
+    chunks: Res<ChunkSystem>,
+    blocks: Res<BlockStates>,
+    mut lines: ResMut<DebugLines>,
+) {
+    let (target, progress) = match mining.progress() {
+        Some(val) => val,
+        None => return,
+    };
+
+    let stage = crack_stage(progress);
+    if stage == 0 {
+        return;
+    }
+
+    let stored_block = match chunks.get_block(target) {
+        Some(val) => val,
+        None => return,
+    };
+
+    let cracks = blocks
+        .get_block(stored_block as usize)
+        .outline_boxes(block_state(stored_block))
+        .iter()
+        .map(|v| v.scale(stage as f32 / CRACK_STAGES as f32))
+        .collect::<Vec<Aabb>>();
+    for crack in &cracks {
+        crack.draw_colored(target.cast::<f32>(), &mut lines, 0.0, CRACK_COLOR);
+    }
+}
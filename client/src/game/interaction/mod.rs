@@ -1,19 +1,34 @@
-use crate::helpers::{from_bevy_vec3, global_to_local_position};
+use crate::config::ClientConfig;
+use crate::game::interaction::mining::ReportedBreak;
+use crate::game::interaction::prediction::PredictedEdits;
+use crate::game::interaction::timing::{
+    breaks_instantly, BreakProgress, InteractionCooldown, SwingAnimation,
+};
+use crate::game::interaction::undo::BlockEditHistory;
+use crate::helpers::{from_bevy_vec3, global_to_local_position, within_reach};
 use crate::systems::asset::AssetService;
 use crate::systems::chunk::ChunkSystem;
+use crate::systems::networking::join::WorldInfo;
 use crate::systems::physics::raycasts::do_raycast;
 use bevy::prelude::*;
 use bevy_prototype_debug_lines::DebugLines;
 
+use crate::game::blocks::interaction::{BlockInteractions, BlockUse};
 use crate::game::blocks::states::BlockStates;
 use crate::game::inventory::Inventory;
+use crate::game::item::states::ItemStates;
 use crate::systems::chunk::builder::{RerenderChunkFlag, RerenderChunkFlagContext};
 use crate::systems::physics::aabb::Aabb;
-use rc_networking::constants::{UserId, CHUNK_SIZE};
-use rc_networking::protocol::clientbound::block_update::BlockUpdate;
-use rc_networking::protocol::Protocol;
+use rc_networking::constants::{block_id, block_state, pack_block, RawChunkData};
 use rc_networking::types::SendPacket;
 
+pub mod clipboard;
+pub mod mining;
+pub mod prediction;
+pub mod schematic;
+pub mod timing;
+pub mod undo;
+
 pub fn mouse_interaction(
     mouse_button_input: Res<Input<MouseButton>>,
     mut commands: Commands,
@@ -21,43 +36,133 @@ pub fn mouse_interaction(
     mut chunks: ResMut<ChunkSystem>,
     mut assets: ResMut<AssetService>,
     mut networking: EventWriter<SendPacket>,
-    inventory: Res<Inventory>,
+    mut inventory: ResMut<Inventory>,
+    items: Res<ItemStates>,
     mut lines: ResMut<DebugLines>,
     blocks: Res<BlockStates>,
     mut rerender_chunks: EventWriter<RerenderChunkFlag>,
     mut meshes: ResMut<Assets<Mesh>>,
+    mut cooldown: Local<InteractionCooldown>,
+    (mut swing, mut history, mut predicted, interactions, mut mining, mut reported, world_info): (
+        ResMut<SwingAnimation>,
+        ResMut<BlockEditHistory>,
+        ResMut<PredictedEdits>,
+        Res<BlockInteractions>,
+        ResMut<BreakProgress>,
+        Local<ReportedBreak>,
+        Option<Res<WorldInfo>>,
+    ),
+    config: Res<ClientConfig>,
+    time: Res<Time>,
 ) {
+    cooldown.tick(time.delta_seconds());
+    swing.tick(time.delta_seconds());
+
+    // Clicking again can act straight away, holding the button waits for the cooldown
+    if mouse_button_input.any_just_pressed([MouseButton::Left, MouseButton::Right]) {
+        cooldown.reset();
+    }
+
     let camera_pos = camera.get_single().unwrap();
 
     let look = camera_pos.rotation * Vec3::new(0.0, 0.0, -1.0);
 
+    let eye = from_bevy_vec3(camera_pos.translation);
+
     let cast = do_raycast(
-        from_bevy_vec3(camera_pos.translation),
+        eye,
         from_bevy_vec3(look),
-        15.0,
+        config.max_reach,
         &chunks,
         &blocks,
     );
 
-    if cast.is_none() {
-        return;
-    }
+    // Nothing is outlined or interacted with out of reach, and looking away stops mining
+    let ray = match cast {
+        Some(ray) if within_reach(eye, ray.block, config.max_reach) => ray,
+        _ => {
+            mining.reset();
+            reported.report(&mining, &mut networking);
+            return;
+        }
+    };
+
+    let breaking = mouse_button_input.pressed(MouseButton::Left);
+    let placing = !breaking && mouse_button_input.pressed(MouseButton::Right);
 
-    let ray = cast.unwrap();
+    // Creative players break blocks on the cooldown alone, anyone else mines them for the block's hardness
+    let instant_break = breaks_instantly(world_info.map(|v| v.game_mode), &config);
+    let acting = (placing || (breaking && instant_break))
+        && cooldown.try_act(ray.block, config.interaction_cooldown);
+
+    if !breaking {
+        mining.reset();
+    }
 
     // Locate chunk
-    let (chunk_loc, inner_loc) = global_to_local_position(ray.block);
+    let (chunk_loc, inner_loc) = global_to_local_position(ray.block, chunks.chunk_size);
 
     // Try find chunk
     if let Some(mut chunk) = chunks.chunks.get_mut(&chunk_loc) {
         // Highlight selected block
-        let block = blocks.get_block(chunk.world[inner_loc.x][inner_loc.y][inner_loc.z] as usize);
+        let stored_block = chunk.get_block(inner_loc);
+        let block = blocks.get_block(stored_block as usize);
 
-        Aabb::draw_lines(&block.bounding_boxes, ray.block.cast::<f32>(), &mut lines);
+        // Draw slightly outside the block so the outline doesn't z-fight with its faces
+        let outline = block
+            .outline_boxes(block_state(stored_block))
+            .iter()
+            .map(|v| v.inflate(config.outline_inflation))
+            .collect();
+        Aabb::draw_lines(&outline, ray.block.cast::<f32>(), &mut lines);
+
+        // Pick block, air is never hit by the raycast so there's always a block here
+        if mouse_button_input.just_pressed(MouseButton::Middle) {
+            if let Some(item) = items.get_by_block(block_id(stored_block)) {
+                inventory.pick_block(item);
+            }
+        }
+
+        // Interactive blocks are used rather than having a block placed against them
+        if placing && acting {
+            match interactions.on_use(&blocks, stored_block, ray.block, camera_pos) {
+                BlockUse::Pass => {}
+                BlockUse::Handled => {
+                    swing.start();
+                    return;
+                }
+                BlockUse::Changed(block) => {
+                    swing.start();
+                    history.record(ray.block, stored_block);
+                    chunk.set_block(inner_loc, block);
+
+                    rerender_chunks.send(RerenderChunkFlag {
+                        chunk: chunk_loc,
+                        context: RerenderChunkFlagContext::Surrounding,
+                    });
+
+                    predicted.send(&mut networking, ray.block, block);
+                    return;
+                }
+            }
+        }
+
+        let broken = if instant_break {
+            breaking && acting
+        } else {
+            breaking
+                && mining.tick(ray.block, time.delta_seconds(), block.hardness)
+                && cooldown.try_act(ray.block, config.interaction_cooldown)
+        };
+        reported.report(&mining, &mut networking);
+
+        if broken {
+            mining.reset();
+            swing.start();
+            history.record(ray.block, stored_block);
 
-        if mouse_button_input.just_pressed(MouseButton::Left) {
             // Found chunk! Update block
-            chunk.world[inner_loc.x][inner_loc.y][inner_loc.z] = 0;
+            chunk.set_block(inner_loc, 0);
 
             // Rerender
             rerender_chunks.send(RerenderChunkFlag {
@@ -67,29 +172,30 @@ pub fn mouse_interaction(
 
             info!(
                 "Destroyed [{}, {}, {}]",
-                ray.block.x as usize % CHUNK_SIZE,
-                ray.block.y as usize % CHUNK_SIZE,
-                ray.block.z as usize % CHUNK_SIZE
+                inner_loc.x, inner_loc.y, inner_loc.z
             );
 
             // Send network update
-            networking.send(SendPacket(
-                Protocol::BlockUpdate(BlockUpdate::new(0, ray.block.x, ray.block.y, ray.block.z)),
-                UserId(0),
-            ))
+            predicted.send(&mut networking, ray.block, 0);
         }
     }
-    if mouse_button_input.just_pressed(MouseButton::Right) {
-        if let Some(block_type) = inventory.selected_block_id() {
+    if placing && acting {
+        if let Some(block_id) = inventory.selected_block_id() {
+            swing.start();
+
             let pos = ray.block + ray.normal;
 
+            let block_type = placed_block(&blocks, block_id, look);
+
             // Locate chunk
-            let (chunk_loc, inner_loc) = global_to_local_position(pos);
+            let (chunk_loc, inner_loc) = global_to_local_position(pos, chunks.chunk_size);
 
             // Try find chunk
             if let Some(mut chunk) = chunks.chunks.get_mut(&chunk_loc) {
+                history.record(pos, chunk.get_block(inner_loc));
+
                 // Found chunk! Update block
-                chunk.world[inner_loc.x][inner_loc.y][inner_loc.z] = block_type;
+                chunk.set_block(inner_loc, block_type);
 
                 // Rerender
                 rerender_chunks.send(RerenderChunkFlag {
@@ -102,11 +208,13 @@ pub fn mouse_interaction(
                     ray.block.x, ray.block.y, ray.block.z
                 );
             } else {
+                history.record(pos, 0);
+
                 // Create chunk data
-                let mut chunk = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+                let mut chunk = RawChunkData::default();
 
                 // Set block
-                chunk[inner_loc.x][inner_loc.y][inner_loc.z] = block_type;
+                chunk.set(inner_loc.x, inner_loc.y, inner_loc.z, block_type);
 
                 // Create chunk
                 chunks.create_chunk(
@@ -120,10 +228,64 @@ pub fn mouse_interaction(
             }
 
             // Send network update
-            networking.send(SendPacket(
-                Protocol::BlockUpdate(BlockUpdate::new(block_type, pos.x, pos.y, pos.z)),
-                UserId(0),
-            ))
+            predicted.send(&mut networking, pos, block_type);
+        }
+    }
+}
+
+/// The block to store when placing `block_id`, facing the player if it's a block that can be rotated
+fn placed_block(blocks: &BlockStates, block_id: u32, look: Vec3) -> u32 {
+    let rotation = if blocks.get_block(block_id as usize).rotatable {
+        facing_rotation(look)
+    } else {
+        0
+    };
+
+    pack_block(block_id, rotation)
+}
+
+/// Gets the block rotation that makes a blocks front face point back along the look direction
+fn facing_rotation(look: Vec3) -> u8 {
+    if look.x.abs() > look.z.abs() {
+        if look.x > 0.0 {
+            3
+        } else {
+            1
+        }
+    } else if look.z > 0.0 {
+        0
+    } else {
+        2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u32 = 6;
+    const WOOD: u32 = 4;
+
+    #[test]
+    fn rotatable_blocks_face_the_player() {
+        let blocks = BlockStates::shipped();
+
+        assert_eq!(
+            placed_block(&blocks, WOOD, Vec3::new(1.0, 0.0, 0.2)),
+            pack_block(WOOD, 3)
+        );
+        assert_eq!(
+            placed_block(&blocks, WOOD, Vec3::new(0.0, -0.5, -1.0)),
+            pack_block(WOOD, 2)
+        );
+    }
+
+    #[test]
+    fn other_blocks_are_placed_unrotated() {
+        let blocks = BlockStates::shipped();
+
+        for look in [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z] {
+            assert_eq!(placed_block(&blocks, STONE, look), STONE);
         }
     }
 }
@@ -0,0 +1,300 @@
+use crate::helpers::global_to_local_position;
+use crate::systems::chunk::builder::{RerenderChunkFlag, RerenderChunkFlagContext};
+use crate::systems::chunk::ChunkSystem;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::UserId;
+use rc_networking::protocol::serverbound::block_edit::BlockEdit;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::{ReceivePacket, SendPacket};
+use std::collections::HashMap;
+
+/// The most edits remembered while waiting for answers, as nothing answers them without a server
+pub const MAX_PENDING_EDITS: usize = 4096;
+
+/// Whether sequence number `a` was sent after `b`. Sequence numbers wrap around, so anything up to half the range
+/// ahead of `b` counts as after it
+pub fn sequence_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+/// Block edits applied locally that the server hasn't answered yet, by sequence number
+#[derive(Resource, Default)]
+pub struct PredictedEdits {
+    next_sequence: u32,
+    pending: HashMap<u32, Vector3<i32>>,
+}
+
+impl PredictedEdits {
+    /// Sends a block edit that has already been applied locally, remembering it until the server answers
+    pub fn send(
+        &mut self,
+        networking: &mut EventWriter<SendPacket>,
+        position: Vector3<i32>,
+        block: u32,
+    ) {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        self.pending.insert(sequence, position);
+
+        if self.pending.len() > MAX_PENDING_EDITS {
+            // The oldest is the furthest behind the next sequence number, which may have wrapped past it
+            let next = self.next_sequence;
+            if let Some(oldest) = self
+                .pending
+                .keys()
+                .copied()
+                .max_by_key(|v| next.wrapping_sub(*v))
+            {
+                self.pending.remove(&oldest);
+            }
+        }
+
+        networking.send(SendPacket(
+            Protocol::BlockEdit(BlockEdit::new(
+                sequence, block, position.x, position.y, position.z,
+            )),
+            UserId(0),
+        ));
+    }
+
+    /// Forgets an answered edit, giving back its position if the block should be set to what the server has.
+    /// A newer edit to the same position is still waiting to be answered, so that one decides what's shown instead
+    pub fn resolve(&mut self, sequence: u32, accepted: bool) -> Option<Vector3<i32>> {
+        let position = self.pending.remove(&sequence)?;
+
+        if accepted
+            || self.pending.iter().any(|(pending_sequence, pending)| {
+                *pending == position && sequence_after(*pending_sequence, sequence)
+            })
+        {
+            return None;
+        }
+
+        Some(position)
+    }
+}
+
+/// Puts back the servers block wherever it rejected one of our edits
+pub fn reconcile_block_edits(
+    mut event_reader: EventReader<ReceivePacket>,
+    mut predicted: ResMut<PredictedEdits>,
+    mut chunks: ResMut<ChunkSystem>,
+    mut rerender_chunks: EventWriter<RerenderChunkFlag>,
+) {
+    for event in event_reader.iter() {
+        let ack = match &event.0 {
+            Protocol::BlockEditAck(val) => val,
+            _ => continue,
+        };
+
+        let position = match predicted.resolve(ack.sequence, ack.accepted) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        warn!("Server rejected block edit at {:?}", position);
+
+        let (chunk_loc, inner_loc) = global_to_local_position(position, chunks.chunk_size);
+        if let Some(chunk) = chunks.chunks.get_mut(&chunk_loc) {
+            chunk.set_block(inner_loc, ack.id);
+
+            rerender_chunks.send(RerenderChunkFlag {
+                chunk: chunk_loc,
+                context: RerenderChunkFlagContext::Surrounding,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkEdgeFaces;
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::CHUNK_SIZE;
+    use rc_networking::protocol::clientbound::block_edit_ack::BlockEditAck;
+
+    const STONE: u32 = 6;
+    const DIRT: u32 = 1;
+
+    /// A world of one chunk at the origin with stone at 1, 2, 3
+    fn world() -> World {
+        let mut data = RawChunkData::new(CHUNK_SIZE);
+        data.set(1, 2, 3, STONE);
+
+        let mut chunks = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        chunks
+            .chunks
+            .insert(Vector3::zeros(), test_chunk(Vector3::zeros(), data));
+
+        let mut world = World::new();
+        world.insert_resource(chunks);
+        world.init_resource::<PredictedEdits>();
+        world.init_resource::<Events<SendPacket>>();
+        world.init_resource::<Events<ReceivePacket>>();
+        world.init_resource::<Events<RerenderChunkFlag>>();
+        world
+    }
+
+    fn block(world: &World) -> u32 {
+        world.resource::<ChunkSystem>().chunks[&Vector3::zeros()].get_block(Vector3::new(1, 2, 3))
+    }
+
+    /// Changes the block at 1, 2, 3 locally and sends the edit, as interaction does
+    fn predict(world: &mut World, block: u32) {
+        world
+            .resource_mut::<ChunkSystem>()
+            .chunks
+            .get_mut(&Vector3::zeros())
+            .unwrap()
+            .set_block(Vector3::new(1, 2, 3), block);
+
+        SystemStage::single_threaded()
+            .with_system(
+                move |mut predicted: ResMut<PredictedEdits>,
+                      mut networking: EventWriter<SendPacket>| {
+                    predicted.send(&mut networking, Vector3::new(1, 2, 3), block);
+                },
+            )
+            .run(world);
+    }
+
+    fn answer(world: &mut World, ack: BlockEditAck) {
+        world.resource_mut::<Events<RerenderChunkFlag>>().clear();
+        let mut packets = world.resource_mut::<Events<ReceivePacket>>();
+        packets.clear();
+        packets.send(ReceivePacket(Protocol::BlockEditAck(ack), UserId(0)));
+
+        SystemStage::single_threaded()
+            .with_system(reconcile_block_edits)
+            .run(world);
+    }
+
+    fn rerendered(world: &World) -> usize {
+        world
+            .resource::<Events<RerenderChunkFlag>>()
+            .iter_current_update_events()
+            .count()
+    }
+
+    #[test]
+    fn edits_are_sent_in_sequence() {
+        let mut world = world();
+        predict(&mut world, 0);
+        predict(&mut world, DIRT);
+
+        let sent: Vec<_> = world
+            .resource::<Events<SendPacket>>()
+            .iter_current_update_events()
+            .map(|v| v.0.clone())
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                Protocol::BlockEdit(BlockEdit::new(0, 0, 1, 2, 3)),
+                Protocol::BlockEdit(BlockEdit::new(1, DIRT, 1, 2, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepted_edits_are_kept() {
+        let mut world = world();
+        predict(&mut world, 0);
+
+        answer(&mut world, BlockEditAck::new(0, true, 0));
+
+        assert_eq!(block(&world), 0);
+        assert_eq!(rerendered(&world), 0);
+    }
+
+    #[test]
+    fn rejected_edits_are_reverted() {
+        let mut world = world();
+        predict(&mut world, 0);
+
+        answer(&mut world, BlockEditAck::new(0, false, STONE));
+
+        assert_eq!(block(&world), STONE);
+        assert_eq!(rerendered(&world), 1);
+
+        // An edit is only answered once
+        answer(&mut world, BlockEditAck::new(0, false, DIRT));
+        assert_eq!(block(&world), STONE);
+        assert_eq!(rerendered(&world), 0);
+    }
+
+    #[test]
+    fn newer_edits_decide_what_is_shown() {
+        let mut world = world();
+        predict(&mut world, 0);
+        predict(&mut world, DIRT);
+
+        // The first edit's rejection would undo the second, which is still waiting
+        answer(&mut world, BlockEditAck::new(0, false, STONE));
+        assert_eq!(block(&world), DIRT);
+
+        answer(&mut world, BlockEditAck::new(1, false, STONE));
+        assert_eq!(block(&world), STONE);
+    }
+
+    #[test]
+    fn oldest_edits_are_forgotten_past_the_limit() {
+        let mut world = world();
+        for _ in 0..=MAX_PENDING_EDITS {
+            predict(&mut world, 0);
+        }
+
+        let mut predicted = world.resource_mut::<PredictedEdits>();
+        assert_eq!(predicted.resolve(0, false), None);
+        assert_eq!(
+            predicted.resolve(MAX_PENDING_EDITS as u32, false),
+            Some(Vector3::new(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        assert!(sequence_after(1, 0));
+        assert!(!sequence_after(0, 1));
+        assert!(!sequence_after(5, 5));
+
+        assert!(sequence_after(0, u32::MAX));
+        assert!(sequence_after(10, u32::MAX - 10));
+        assert!(!sequence_after(u32::MAX, 0));
+    }
+
+    #[test]
+    fn newer_edits_decide_what_is_shown_after_wrapping() {
+        let mut world = world();
+        world.resource_mut::<PredictedEdits>().next_sequence = u32::MAX;
+        predict(&mut world, 0);
+        predict(&mut world, DIRT);
+
+        // The second edit was sent as 0, after the first at the top of the range
+        answer(&mut world, BlockEditAck::new(u32::MAX, false, STONE));
+        assert_eq!(block(&world), DIRT);
+
+        answer(&mut world, BlockEditAck::new(0, false, STONE));
+        assert_eq!(block(&world), STONE);
+    }
+
+    #[test]
+    fn oldest_edits_are_forgotten_after_wrapping() {
+        let mut world = world();
+        let first = u32::MAX - 10;
+        world.resource_mut::<PredictedEdits>().next_sequence = first;
+        for _ in 0..=MAX_PENDING_EDITS {
+            predict(&mut world, 0);
+        }
+
+        let predicted = world.resource::<PredictedEdits>();
+        assert_eq!(predicted.pending.len(), MAX_PENDING_EDITS);
+        assert!(!predicted.pending.contains_key(&first));
+        assert!(predicted.pending.contains_key(&(first + 1)));
+        assert!(predicted.pending.contains_key(&0));
+    }
+}
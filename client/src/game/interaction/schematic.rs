@@ -0,0 +1,380 @@
+use crate::config::ClientConfig;
+use crate::game::blocks::block_rotation;
+use crate::game::blocks::states::BlockStates;
+use crate::game::interaction::prediction::PredictedEdits;
+use crate::game::interaction::undo::BlockEditHistory;
+use crate::helpers::{from_bevy_vec3, global_to_local_position, within_reach};
+use crate::systems::chunk::builder::{RerenderChunkFlag, RerenderChunkFlagContext};
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::physics::raycasts::do_raycast;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::{block_id, block_state, pack_block};
+use rc_networking::types::SendPacket;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufReader;
+
+/// Key that places the loaded schematic against the targeted block
+pub const PLACE_SCHEMATIC_KEY: KeyCode = KeyCode::P;
+
+/// Key that turns the schematic a quarter turn clockwise before placing
+pub const ROTATE_SCHEMATIC_KEY: KeyCode = KeyCode::R;
+
+/// The most blocks a schematic can have, so one placement can't flood the server with edits
+pub const MAX_SCHEMATIC_BLOCKS: usize = 32 * 32 * 32;
+
+/// A structure saved as a grid of blocks
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Schematic {
+    /// The width, height and depth of the grid
+    pub size: [usize; 3],
+    /// The position in the grid that lines up with the targeted position when placed
+    pub origin: [i32; 3],
+    /// The blocks of the grid, ordered by x then y then z. Air is left as it is in the world
+    pub blocks: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SchematicError {
+    /// The grid doesn't have as many blocks as its size says
+    WrongSize,
+    TooLarge,
+    /// A block would be placed out of reach or in a chunk that isn't loaded, where the server would reject it
+    Protected(Vector3<i32>),
+}
+
+impl Schematic {
+    pub fn load(path: &str) -> Option<Schematic> {
+        let file = File::open(path).ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    /// The blocks to place with the origin at a position, after turning the schematic clockwise around the Y axis
+    pub fn edits(
+        &self,
+        blocks: &BlockStates,
+        position: Vector3<i32>,
+        rotation: u8,
+    ) -> Result<Vec<(Vector3<i32>, u32)>, SchematicError> {
+        let [width, height, depth] = self.size;
+
+        if width * height * depth != self.blocks.len() {
+            return Err(SchematicError::WrongSize);
+        }
+        if self.blocks.len() > MAX_SCHEMATIC_BLOCKS {
+            return Err(SchematicError::TooLarge);
+        }
+
+        let origin = Vector3::new(self.origin[0], self.origin[1], self.origin[2]);
+        let mut edits = Vec::new();
+
+        for x in 0..width {
+            for y in 0..height {
+                for z in 0..depth {
+                    let block = self.blocks[(x * height + y) * depth + z];
+                    if block_id(block) == 0 {
+                        continue;
+                    }
+
+                    let offset = rotate_offset(
+                        Vector3::new(x as i32, y as i32, z as i32) - origin,
+                        rotation,
+                    );
+                    edits.push((position + offset, rotate_block(blocks, block, rotation)));
+                }
+            }
+        }
+
+        Ok(edits)
+    }
+}
+
+/// Turns a position clockwise around the Y axis by a number of 90 degree steps, the same way block faces turn
+pub fn rotate_offset(mut offset: Vector3<i32>, rotation: u8) -> Vector3<i32> {
+    for _ in 0..(rotation % 4) {
+        offset = Vector3::new(-offset.z, offset.y, offset.x);
+    }
+    offset
+}
+
+/// Turns a block to match its schematic being turned, keeping the rest of its state. Blocks that can't be rotated
+/// are left as they are, as they would be when placed by hand
+pub fn rotate_block(blocks: &BlockStates, block: u32, rotation: u8) -> u32 {
+    if !blocks.get_block(block as usize).rotatable {
+        return block;
+    }
+
+    let state = block_state(block);
+    let turned = (block_rotation(state) + rotation) % 4;
+    pack_block(block_id(block), (state & !0b11) | turned)
+}
+
+/// Checks every edit can be made, so a placement is made whole or not at all rather than leaving half a structure
+pub fn check_edits(
+    edits: &[(Vector3<i32>, u32)],
+    eye: Vector3<f32>,
+    max_reach: f32,
+    chunks: &ChunkSystem,
+) -> Result<(), SchematicError> {
+    match edits.iter().find(|(pos, _)| {
+        let (chunk_loc, _) = global_to_local_position(*pos, chunks.chunk_size);
+        !within_reach(eye, *pos, max_reach) || !chunks.chunks.contains_key(&chunk_loc)
+    }) {
+        Some((pos, _)) => Err(SchematicError::Protected(*pos)),
+        None => Ok(()),
+    }
+}
+
+/// Makes checked edits locally and sends each to the server to be validated, rebuilding each changed chunk once
+pub fn apply_edits(
+    edits: &[(Vector3<i32>, u32)],
+    chunks: &mut ChunkSystem,
+    history: &mut BlockEditHistory,
+    predicted: &mut PredictedEdits,
+    networking: &mut EventWriter<SendPacket>,
+    rerender_chunks: &mut EventWriter<RerenderChunkFlag>,
+) {
+    let mut changed_chunks = HashSet::new();
+
+    for (pos, block) in edits {
+        let (chunk_loc, inner_loc) = global_to_local_position(*pos, chunks.chunk_size);
+        let chunk = chunks.chunks.get_mut(&chunk_loc).unwrap();
+
+        history.record(*pos, chunk.get_block(inner_loc));
+        chunk.set_block(inner_loc, *block);
+        changed_chunks.insert(chunk_loc);
+
+        predicted.send(networking, *pos, *block);
+    }
+
+    for chunk in changed_chunks {
+        rerender_chunks.send(RerenderChunkFlag {
+            chunk,
+            context: RerenderChunkFlagContext::Surrounding,
+        });
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SchematicPlacement {
+    /// How many quarter turns clockwise the schematic is placed with
+    pub rotation: u8,
+}
+
+pub fn place_schematic(
+    keys: Res<Input<KeyCode>>,
+    mut placement: ResMut<SchematicPlacement>,
+    camera: Query<&Transform, With<Camera>>,
+    mut chunks: ResMut<ChunkSystem>,
+    blocks: Res<BlockStates>,
+    config: Res<ClientConfig>,
+    mut history: ResMut<BlockEditHistory>,
+    mut predicted: ResMut<PredictedEdits>,
+    mut rerender_chunks: EventWriter<RerenderChunkFlag>,
+    mut networking: EventWriter<SendPacket>,
+) {
+    if keys.just_pressed(ROTATE_SCHEMATIC_KEY) {
+        placement.rotation = (placement.rotation + 1) % 4;
+        info!(
+            "Schematic rotation: {} degrees",
+            placement.rotation as u32 * 90
+        );
+    }
+
+    if !keys.just_pressed(PLACE_SCHEMATIC_KEY) {
+        return;
+    }
+
+    let schematic = match Schematic::load(&config.schematic_file) {
+        Some(val) => val,
+        None => {
+            warn!("Couldn't load schematic {}", config.schematic_file);
+            return;
+        }
+    };
+
+    let camera = match camera.get_single() {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+    let eye = from_bevy_vec3(camera.translation);
+    let look = from_bevy_vec3(camera.rotation * Vec3::new(0.0, 0.0, -1.0));
+
+    // Place against the targeted face, like placing a single block
+    let target = match do_raycast(eye, look, config.max_reach, &chunks, &blocks) {
+        Some(ray) => ray.block + ray.normal,
+        None => return,
+    };
+
+    let edits = match schematic.edits(&blocks, target, placement.rotation) {
+        Ok(val) => val,
+        Err(err) => {
+            warn!("Can't place schematic: {:?}", err);
+            return;
+        }
+    };
+
+    if let Err(err) = check_edits(&edits, eye, config.max_reach, &chunks) {
+        warn!("Can't place schematic: {:?}", err);
+        return;
+    }
+
+    apply_edits(
+        &edits,
+        &mut chunks,
+        &mut history,
+        &mut predicted,
+        &mut networking,
+        &mut rerender_chunks,
+    );
+
+    info!("Placed schematic of {} blocks", edits.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkEdgeFaces;
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use rc_networking::constants::CHUNK_SIZE;
+
+    const DIRT: u32 = 1;
+    const WOOD: u32 = 4;
+    const STONE: u32 = 6;
+
+    /// Two blocks wide and deep with a gap of air, ordered x then y then z
+    fn schematic(origin: [i32; 3]) -> Schematic {
+        Schematic {
+            size: [2, 1, 2],
+            origin,
+            blocks: vec![STONE, 0, DIRT, WOOD],
+        }
+    }
+
+    #[test]
+    fn quarter_turns_go_clockwise() {
+        assert_eq!(
+            rotate_offset(Vector3::new(1, 2, 0), 1),
+            Vector3::new(0, 2, 1)
+        );
+        assert_eq!(
+            rotate_offset(Vector3::new(0, 2, 1), 1),
+            Vector3::new(-1, 2, 0)
+        );
+        assert_eq!(
+            rotate_offset(Vector3::new(1, 2, 0), 2),
+            Vector3::new(-1, 2, 0)
+        );
+
+        // A full turn comes back around
+        let offset = Vector3::new(3, -1, 2);
+        assert_eq!(rotate_offset(offset, 4), offset);
+        assert_eq!(rotate_offset(offset, 5), rotate_offset(offset, 1));
+    }
+
+    #[test]
+    fn turning_a_block_keeps_the_rest_of_its_state() {
+        let blocks = BlockStates::shipped();
+        let block = pack_block(WOOD, 0b1011);
+
+        assert_eq!(rotate_block(&blocks, block, 0), block);
+        assert_eq!(rotate_block(&blocks, block, 1), pack_block(WOOD, 0b1000));
+        assert_eq!(rotate_block(&blocks, block, 2), pack_block(WOOD, 0b1001));
+    }
+
+    #[test]
+    fn blocks_that_cant_rotate_are_left_unturned() {
+        let blocks = BlockStates::shipped();
+
+        for rotation in 0..4 {
+            assert_eq!(rotate_block(&blocks, STONE, rotation), STONE);
+            assert_eq!(
+                rotate_block(&blocks, pack_block(DIRT, 0b1000), rotation),
+                pack_block(DIRT, 0b1000)
+            );
+        }
+    }
+
+    #[test]
+    fn edits_skip_air() {
+        let edits = schematic([0, 0, 0])
+            .edits(&BlockStates::shipped(), Vector3::new(10, 5, 10), 0)
+            .unwrap();
+
+        assert_eq!(
+            edits,
+            vec![
+                (Vector3::new(10, 5, 10), STONE),
+                (Vector3::new(11, 5, 10), DIRT),
+                (Vector3::new(11, 5, 11), WOOD),
+            ]
+        );
+    }
+
+    #[test]
+    fn edits_are_turned_around_the_origin() {
+        let edits = schematic([1, 0, 0])
+            .edits(&BlockStates::shipped(), Vector3::new(10, 5, 10), 1)
+            .unwrap();
+
+        // The grid and the facing of each block that can rotate turn together
+        assert_eq!(
+            edits,
+            vec![
+                (Vector3::new(10, 5, 9), STONE),
+                (Vector3::new(10, 5, 10), DIRT),
+                (Vector3::new(9, 5, 10), pack_block(WOOD, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_or_large_schematics_are_refused() {
+        let mut wrong_size = schematic([0, 0, 0]);
+        wrong_size.blocks.pop();
+        assert_eq!(
+            wrong_size.edits(&BlockStates::shipped(), Vector3::zeros(), 0),
+            Err(SchematicError::WrongSize)
+        );
+
+        let too_large = Schematic {
+            size: [33, 32, 32],
+            origin: [0, 0, 0],
+            blocks: vec![STONE; 33 * 32 * 32],
+        };
+        assert_eq!(
+            too_large.edits(&BlockStates::shipped(), Vector3::zeros(), 0),
+            Err(SchematicError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn edits_out_of_reach_or_unloaded_are_refused() {
+        let mut chunks = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        chunks.chunks.insert(
+            Vector3::zeros(),
+            test_chunk(Vector3::zeros(), RawChunkData::new(CHUNK_SIZE)),
+        );
+        let eye = Vector3::new(0.5, 0.5, 0.5);
+
+        let nearby = [(Vector3::new(2, 1, 1), STONE)];
+        assert_eq!(check_edits(&nearby, eye, 5.0, &chunks), Ok(()));
+
+        let far = [
+            (Vector3::new(2, 1, 1), STONE),
+            (Vector3::new(9, 1, 1), STONE),
+        ];
+        assert_eq!(
+            check_edits(&far, eye, 5.0, &chunks),
+            Err(SchematicError::Protected(Vector3::new(9, 1, 1)))
+        );
+
+        let unloaded = Vector3::new(-1, 0, 0);
+        assert_eq!(
+            check_edits(&[(unloaded, STONE)], eye, 5.0, &chunks),
+            Err(SchematicError::Protected(unloaded))
+        );
+    }
+}
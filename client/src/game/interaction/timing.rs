@@ -0,0 +1,230 @@
+use crate::config::ClientConfig;
+use nalgebra::Vector3;
+use rc_networking::constants::GameMode;
+
+/// How long a swing of the arm takes, in seconds
+pub const SWING_SECONDS: f32 = 0.3;
+
+/// Stops holding down a mouse button from placing or breaking a block every frame
+#[derive(Default)]
+pub struct InteractionCooldown {
+    remaining: f32,
+    /// The block the cooldown was started on, looking at a different block ends the cooldown
+    target: Option<Vector3<i32>>,
+}
+
+impl InteractionCooldown {
+    pub fn tick(&mut self, delta: f32) {
+        self.remaining = (self.remaining - delta).max(0.0);
+    }
+
+    /// Ends the cooldown, for when the button is pressed again rather than held
+    pub fn reset(&mut self) {
+        self.remaining = 0.0;
+    }
+
+    /// Checks if an action can be made on a block, starting the cooldown if so
+    pub fn try_act(&mut self, target: Vector3<i32>, cooldown: f32) -> bool {
+        if self.target != Some(target) {
+            self.target = Some(target);
+            self.remaining = 0.0;
+        }
+
+        if self.remaining > 0.0 {
+            return false;
+        }
+
+        self.remaining = cooldown;
+        true
+    }
+}
+
+/// How long the block being mined has been held down for, in survival blocks take their hardness in seconds to break
+#[derive(bevy::prelude::Resource, Default)]
+pub struct BreakProgress {
+    elapsed: f32,
+    hardness: f32,
+    /// The block being mined, looking at a different block starts again
+    target: Option<Vector3<i32>>,
+}
+
+impl BreakProgress {
+    /// Keeps mining a block, returning true once it has been mined for long enough to break
+    pub fn tick(&mut self, target: Vector3<i32>, delta: f32, hardness: f32) -> bool {
+        if self.target != Some(target) {
+            self.target = Some(target);
+            self.elapsed = 0.0;
+        }
+
+        self.hardness = hardness;
+        self.elapsed += delta;
+        self.elapsed >= hardness
+    }
+
+    /// The block being mined and how far through mining it the player is, from 0 to 1
+    pub fn progress(&self) -> Option<(Vector3<i32>, f32)> {
+        let fraction = if self.hardness <= 0.0 {
+            1.0
+        } else {
+            (self.elapsed / self.hardness).min(1.0)
+        };
+        self.target.map(|target| (target, fraction))
+    }
+
+    /// Stops mining, for when the button is let go or the block broke
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.target = None;
+    }
+}
+
+/// Whether blocks break as soon as they're hit rather than being mined, without a server the player is in creative
+pub fn breaks_instantly(game_mode: Option<GameMode>, config: &ClientConfig) -> bool {
+    config.creative_instant_break && game_mode.map_or(true, |v| v.instant_break())
+}
+
+/// Times the arm swing when placing or breaking blocks, for the viewmodel to animate with
+#[derive(bevy::prelude::Resource, Default)]
+pub struct SwingAnimation {
+    elapsed: Option<f32>,
+}
+
+impl SwingAnimation {
+    /// Starts a new swing, restarting any swing in progress
+    pub fn start(&mut self) {
+        self.elapsed = Some(0.0);
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        if let Some(elapsed) = self.elapsed {
+            let elapsed = elapsed + delta;
+            self.elapsed = if elapsed >= SWING_SECONDS {
+                None
+            } else {
+                Some(elapsed)
+            };
+        }
+    }
+
+    /// How far through the swing the arm is, from 0 to 1, or None when not swinging
+    pub fn progress(&self) -> Option<f32> {
+        self.elapsed.map(|v| v / SWING_SECONDS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cooldown_gates_repeated_actions() {
+        let mut cooldown = InteractionCooldown::default();
+        let block = Vector3::new(1, 2, 3);
+
+        assert!(cooldown.try_act(block, 0.2));
+        assert!(!cooldown.try_act(block, 0.2));
+
+        cooldown.tick(0.1);
+        assert!(!cooldown.try_act(block, 0.2));
+
+        cooldown.tick(0.1);
+        assert!(cooldown.try_act(block, 0.2));
+    }
+
+    #[test]
+    fn cooldown_ends_on_a_new_target_or_press() {
+        let mut cooldown = InteractionCooldown::default();
+
+        assert!(cooldown.try_act(Vector3::new(0, 0, 0), 0.2));
+        assert!(cooldown.try_act(Vector3::new(0, 1, 0), 0.2));
+
+        cooldown.reset();
+        assert!(cooldown.try_act(Vector3::new(0, 1, 0), 0.2));
+    }
+
+    #[test]
+    fn swing_completes_after_its_length() {
+        let mut swing = SwingAnimation::default();
+        assert_eq!(swing.progress(), None);
+
+        swing.start();
+        assert_eq!(swing.progress(), Some(0.0));
+
+        swing.tick(SWING_SECONDS / 2.0);
+        assert!((swing.progress().unwrap() - 0.5).abs() < 1e-5);
+
+        swing.tick(SWING_SECONDS / 2.0);
+        assert_eq!(swing.progress(), None);
+    }
+
+    #[test]
+    fn starting_again_restarts_the_swing() {
+        let mut swing = SwingAnimation::default();
+        swing.start();
+        swing.tick(SWING_SECONDS * 0.75);
+
+        swing.start();
+        assert_eq!(swing.progress(), Some(0.0));
+    }
+
+    #[test]
+    fn break_progress_restarts_on_a_new_block() {
+        let mut progress = BreakProgress::default();
+        let block = Vector3::new(4, 5, 6);
+
+        assert!(!progress.tick(block, 0.5, 1.0));
+        assert_eq!(progress.progress(), Some((block, 0.5)));
+        assert!(progress.tick(block, 0.5, 1.0));
+
+        assert!(!progress.tick(Vector3::new(4, 6, 6), 0.5, 1.0));
+        assert_eq!(progress.progress(), Some((Vector3::new(4, 6, 6), 0.5)));
+    }
+
+    #[test]
+    fn only_creative_breaks_instantly() {
+        let config = ClientConfig::default();
+
+        assert!(breaks_instantly(Some(GameMode::Creative), &config));
+        assert!(!breaks_instantly(Some(GameMode::Survival), &config));
+        assert!(!breaks_instantly(Some(GameMode::Spectator), &config));
+
+        // Without a server there's no game mode, and the player is in creative
+        assert!(breaks_instantly(None, &config));
+    }
+
+    #[test]
+    fn instant_breaking_can_be_turned_off() {
+        let config = ClientConfig {
+            creative_instant_break: false,
+            ..Default::default()
+        };
+
+        assert!(!breaks_instantly(Some(GameMode::Creative), &config));
+        assert!(!breaks_instantly(None, &config));
+    }
+
+    #[test]
+    fn mining_takes_the_block_hardness() {
+        let mut progress = BreakProgress::default();
+        let block = Vector3::new(4, 5, 6);
+
+        for _ in 0..3 {
+            assert!(!progress.tick(block, 0.25, 1.0));
+        }
+        assert!(progress.tick(block, 0.25, 1.0));
+        assert_eq!(progress.progress(), Some((block, 1.0)));
+
+        progress.reset();
+        assert_eq!(progress.progress(), None);
+        assert!(!progress.tick(block, 0.25, 1.0));
+    }
+
+    #[test]
+    fn blocks_without_hardness_break_on_the_first_tick() {
+        let mut progress = BreakProgress::default();
+        let block = Vector3::new(4, 5, 6);
+
+        assert!(progress.tick(block, 0.0, 0.0));
+        assert_eq!(progress.progress(), Some((block, 1.0)));
+    }
+}
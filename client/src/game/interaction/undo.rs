@@ -0,0 +1,216 @@
+use crate::helpers::global_to_local_position;
+use crate::systems::chunk::builder::{RerenderChunkFlag, RerenderChunkFlagContext};
+use crate::systems::chunk::ChunkSystem;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::Client;
+use std::collections::VecDeque;
+
+/// How many block edits can be undone
+pub const UNDO_HISTORY_SIZE: usize = 64;
+
+/// A block that was changed by the player, and what it was before
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockEdit {
+    pub position: Vector3<i32>,
+    pub previous: u32,
+}
+
+/// The latest block edits made by the player, newest last
+#[derive(Resource, Default)]
+pub struct BlockEditHistory {
+    edits: VecDeque<BlockEdit>,
+    /// Set while connected to a server, where edits can't be undone so aren't kept
+    paused: bool,
+}
+
+impl BlockEditHistory {
+    pub fn record(&mut self, position: Vector3<i32>, previous: u32) {
+        if self.paused {
+            return;
+        }
+        if self.edits.len() == UNDO_HISTORY_SIZE {
+            self.edits.pop_front();
+        }
+        self.edits.push_back(BlockEdit { position, previous });
+    }
+
+    pub fn pop(&mut self) -> Option<BlockEdit> {
+        self.edits.pop_back()
+    }
+}
+
+/// Stops recording edits while connected to a server, forgetting any made before connecting
+pub fn pause_history_while_connected(
+    client: Option<Res<Client>>,
+    mut history: ResMut<BlockEditHistory>,
+) {
+    let paused = client.is_some();
+    if history.paused != paused {
+        history.edits.clear();
+        history.paused = paused;
+    }
+}
+
+/// Puts back the block changed by the last edit when Ctrl+Z is pressed.
+/// Only works without a server, as a server holds the real world and wouldn't know about the undo
+pub fn undo_block_edit(
+    keys: Res<Input<KeyCode>>,
+    mut history: ResMut<BlockEditHistory>,
+    mut chunks: ResMut<ChunkSystem>,
+    mut rerender_chunks: EventWriter<RerenderChunkFlag>,
+    client: Option<Res<Client>>,
+) {
+    if !(keys.pressed(KeyCode::LControl) && keys.just_pressed(KeyCode::Z)) {
+        return;
+    }
+
+    if client.is_some() {
+        info!("Undo is only available in single player");
+        return;
+    }
+
+    let edit = match history.pop() {
+        Some(val) => val,
+        None => return,
+    };
+
+    let (chunk_loc, inner_loc) = global_to_local_position(edit.position, chunks.chunk_size);
+
+    if let Some(chunk) = chunks.chunks.get_mut(&chunk_loc) {
+        chunk.set_block(inner_loc, edit.previous);
+
+        rerender_chunks.send(RerenderChunkFlag {
+            chunk: chunk_loc,
+            context: RerenderChunkFlagContext::Surrounding,
+        });
+
+        info!(
+            "Undid edit at [{}, {}, {}]",
+            edit.position.x, edit.position.y, edit.position.z
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkEdgeFaces;
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use crate::systems::networking::connect;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::CHUNK_SIZE;
+
+    const DIRT: u32 = 1;
+    const STONE: u32 = 6;
+
+    #[test]
+    fn undo_takes_the_latest_edit() {
+        let mut history = BlockEditHistory::default();
+        history.record(Vector3::new(1, 2, 3), DIRT);
+        history.record(Vector3::new(4, 5, 6), STONE);
+
+        assert_eq!(
+            history.pop(),
+            Some(BlockEdit {
+                position: Vector3::new(4, 5, 6),
+                previous: STONE
+            })
+        );
+        assert_eq!(history.pop().map(|v| v.previous), Some(DIRT));
+        assert_eq!(history.pop(), None);
+    }
+
+    #[test]
+    fn history_forgets_the_oldest_edits() {
+        let mut history = BlockEditHistory::default();
+        for x in 0..(UNDO_HISTORY_SIZE + 10) as i32 {
+            history.record(Vector3::new(x, 0, 0), DIRT);
+        }
+
+        let mut undone = Vec::new();
+        while let Some(edit) = history.pop() {
+            undone.push(edit.position.x);
+        }
+        assert_eq!(undone.len(), UNDO_HISTORY_SIZE);
+        assert_eq!(undone.last(), Some(&10));
+    }
+
+    /// A world with stone at 2, 3, 4 in the chunk at 1, 0, 0 recorded as placed over dirt, with Ctrl+Z pressed
+    fn world() -> World {
+        let mut chunks = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        let mut data = RawChunkData::new(CHUNK_SIZE);
+        data.set(2, 3, 4, STONE);
+        chunks.chunks.insert(
+            Vector3::new(1, 0, 0),
+            test_chunk(Vector3::new(1, 0, 0), data),
+        );
+
+        let mut history = BlockEditHistory::default();
+        history.record(Vector3::new(CHUNK_SIZE as i32 + 2, 3, 4), DIRT);
+
+        let mut keys = Input::<KeyCode>::default();
+        keys.press(KeyCode::LControl);
+        keys.press(KeyCode::Z);
+
+        let mut world = World::new();
+        world.insert_resource(chunks);
+        world.insert_resource(history);
+        world.insert_resource(keys);
+        world.init_resource::<Events<RerenderChunkFlag>>();
+        world
+    }
+
+    fn connect_to_server(world: &mut World) {
+        SystemStage::single_threaded()
+            .with_system(|mut commands: Commands| {
+                connect(&mut commands, ([127, 0, 0, 1], 4000).into(), &[], "alice").unwrap();
+            })
+            .run(world);
+        assert!(world.get_resource::<Client>().is_some());
+    }
+
+    #[test]
+    fn undo_restores_the_previous_block() {
+        let mut world = world();
+
+        SystemStage::single_threaded()
+            .with_system(undo_block_edit)
+            .run(&mut world);
+
+        let chunk = &world.resource::<ChunkSystem>().chunks[&Vector3::new(1, 0, 0)];
+        assert_eq!(chunk.get_block(Vector3::new(2, 3, 4)), DIRT);
+        assert_eq!(world.resource::<Events<RerenderChunkFlag>>().len(), 1);
+    }
+
+    #[test]
+    fn undo_does_nothing_while_connected() {
+        let mut world = world();
+        connect_to_server(&mut world);
+
+        SystemStage::single_threaded()
+            .with_system(undo_block_edit)
+            .run(&mut world);
+
+        let chunk = &world.resource::<ChunkSystem>().chunks[&Vector3::new(1, 0, 0)];
+        assert_eq!(chunk.get_block(Vector3::new(2, 3, 4)), STONE);
+        assert!(world.resource::<Events<RerenderChunkFlag>>().is_empty());
+    }
+
+    #[test]
+    fn edits_arent_recorded_while_connected() {
+        let mut world = world();
+        connect_to_server(&mut world);
+
+        SystemStage::single_threaded()
+            .with_system(pause_history_while_connected)
+            .run(&mut world);
+
+        let mut history = world.resource_mut::<BlockEditHistory>();
+        history.record(Vector3::new(1, 2, 3), STONE);
+
+        // The edit from before connecting is forgotten too
+        assert_eq!(history.pop(), None);
+    }
+}
@@ -104,6 +104,35 @@ pub fn setup_hotbar_ui(
         });
 
     inventory.hotbar_selected_image = hotbar_selected_image;
+    inventory.hotbar_icons = hotbar_icons;
+}
+
+/// Keeps the hotbar icons matching the items in each slot
+pub fn update_hotbar_icons(
+    inventory: Res<Inventory>,
+    asset_server: Res<AssetServer>,
+    mut icons: Query<(&mut Style, &mut UiImage)>,
+) {
+    if !inventory.is_changed() {
+        return;
+    }
+
+    for (slot, icon) in inventory.hotbar_icons.iter().enumerate() {
+        let (mut style, mut image) = match icon.and_then(|v| icons.get_mut(v).ok()) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        match &inventory.hotbar[slot] {
+            Some(stack) => {
+                style.display = Display::Flex;
+                *image = asset_server
+                    .load(&format!("ui/icons/{}.png", stack.item.icon))
+                    .into();
+            }
+            None => style.display = Display::None,
+        }
+    }
 }
 
 pub fn update_hotbar(
@@ -113,14 +142,14 @@ pub fn update_hotbar(
 ) {
     let (changed, hotbar_index) = get_hotbar_keypresses(&keys);
 
-    if !changed
-        || hotbar_index == inventory.hotbar_slot
-        || inventory.hotbar_selected_image.is_none()
-    {
-        return;
+    if changed && hotbar_index != inventory.hotbar_slot {
+        inventory.hotbar_slot = hotbar_index;
     }
 
-    inventory.hotbar_slot = hotbar_index;
+    // The slot can also be changed by picking a block
+    if !inventory.is_changed() || inventory.hotbar_selected_image.is_none() {
+        return;
+    }
 
     let mut style = style
         .get_mut(*inventory.hotbar_selected_image.as_ref().unwrap())
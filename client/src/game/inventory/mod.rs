@@ -1,5 +1,5 @@
-use crate::game::inventory::hotbar::{setup_hotbar_ui, update_hotbar};
-use crate::game::item::ItemStack;
+use crate::game::inventory::hotbar::{setup_hotbar_ui, update_hotbar, update_hotbar_icons};
+use crate::game::item::{ItemStack, ItemType};
 use crate::state::AppState;
 use bevy::app::{App, Plugin};
 use bevy::prelude::*;
@@ -12,6 +12,7 @@ impl Plugin for InventoryPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Inventory::default())
             .add_system(update_hotbar)
+            .add_system(update_hotbar_icons)
             .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(setup_hotbar_ui));
     }
 }
@@ -21,6 +22,8 @@ pub struct Inventory {
     pub hotbar: [Option<ItemStack>; 10],
     pub hotbar_slot: u8,
     pub hotbar_selected_image: Option<Entity>,
+    /// The images showing the icon of each hotbar slot
+    pub hotbar_icons: [Option<Entity>; 10],
 }
 
 impl Inventory {
@@ -31,6 +34,44 @@ impl Inventory {
             None
         }
     }
+
+    /// Finds the slot a picked block goes in. A slot already holding the block is reused,
+    /// otherwise the selected slot if it's empty, then the first empty slot, and if the hotbar is full the selected slot is replaced
+    pub fn pick_block_slot(&self, block_id: u32) -> u8 {
+        if let Some(slot) = self
+            .hotbar
+            .iter()
+            .position(|v| matches!(v, Some(stack) if stack.item.block_state == Some(block_id)))
+        {
+            return slot as u8;
+        }
+
+        if self.hotbar[self.hotbar_slot as usize].is_none() {
+            return self.hotbar_slot;
+        }
+
+        self.hotbar
+            .iter()
+            .position(|v| v.is_none())
+            .map_or(self.hotbar_slot, |v| v as u8)
+    }
+
+    /// Selects the slot holding an item, adding it to the hotbar if it isn't there
+    pub fn pick_block(&mut self, item: &ItemType) {
+        let block_id = match item.block_state {
+            Some(val) => val,
+            None => return,
+        };
+
+        let slot = self.pick_block_slot(block_id);
+        let holds_block = self.hotbar[slot as usize]
+            .as_ref()
+            .map_or(false, |stack| stack.item.block_state == Some(block_id));
+        if !holds_block {
+            self.hotbar[slot as usize] = Some(ItemStack::new(item.clone(), 1));
+        }
+        self.hotbar_slot = slot;
+    }
 }
 
 impl Default for Inventory {
@@ -39,6 +80,73 @@ impl Default for Inventory {
             hotbar: [None, None, None, None, None, None, None, None, None, None],
             hotbar_slot: 0,
             hotbar_selected_image: None,
+            hotbar_icons: [None; 10],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRT: u32 = 1;
+    const STONE: u32 = 6;
+
+    fn item(block: u32) -> ItemType {
+        ItemType {
+            name: format!("Block {}", block),
+            icon: String::new(),
+            block_state: Some(block),
         }
     }
+
+    fn stack(block: u32) -> Option<ItemStack> {
+        Some(ItemStack::new(item(block), 1))
+    }
+
+    #[test]
+    fn picks_the_slot_already_holding_the_block() {
+        let mut inventory = Inventory::default();
+        inventory.hotbar[0] = stack(DIRT);
+        inventory.hotbar[4] = stack(STONE);
+
+        assert_eq!(inventory.pick_block_slot(STONE), 4);
+
+        inventory.pick_block(&item(STONE));
+        assert_eq!(inventory.hotbar_slot, 4);
+        assert_eq!(inventory.hotbar.iter().flatten().count(), 2);
+    }
+
+    #[test]
+    fn missing_block_goes_in_the_selected_slot_if_empty() {
+        let mut inventory = Inventory::default();
+        inventory.hotbar[0] = stack(DIRT);
+        inventory.hotbar_slot = 3;
+
+        inventory.pick_block(&item(STONE));
+        assert_eq!(inventory.hotbar_slot, 3);
+        assert_eq!(inventory.selected_block_id(), Some(STONE));
+    }
+
+    #[test]
+    fn missing_block_goes_in_the_first_empty_slot() {
+        let mut inventory = Inventory::default();
+        inventory.hotbar[0] = stack(DIRT);
+        inventory.hotbar[1] = stack(DIRT);
+
+        inventory.pick_block(&item(STONE));
+        assert_eq!(inventory.hotbar_slot, 2);
+        assert_eq!(inventory.selected_block_id(), Some(STONE));
+    }
+
+    #[test]
+    fn full_hotbar_replaces_the_selected_slot() {
+        let mut inventory = Inventory::default();
+        inventory.hotbar = std::array::from_fn(|_| stack(DIRT));
+        inventory.hotbar_slot = 7;
+
+        inventory.pick_block(&item(STONE));
+        assert_eq!(inventory.hotbar_slot, 7);
+        assert_eq!(inventory.selected_block_id(), Some(STONE));
+    }
 }
@@ -38,4 +38,9 @@ impl ItemStates {
             ],
         }
     }
+
+    /// Finds the item that places a block
+    pub fn get_by_block(&self, block: u32) -> Option<&ItemType> {
+        self.states.iter().find(|v| v.block_state == Some(block))
+    }
 }
@@ -1,8 +1,11 @@
+pub mod ambient_sound;
 pub mod blocks;
 pub mod entity;
 pub mod interaction;
 pub mod inventory;
 pub mod item;
 pub mod player;
+pub mod sound;
+pub mod spawn;
 pub mod viewable_direction;
 pub mod world;
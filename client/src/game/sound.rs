@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+use rc_networking::protocol::clientbound::play_sound::Sound;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::ReceivePacket;
+
+/// How far away, in blocks, a sound can still be heard. Sounds get quieter the further away they are
+pub const HEARING_DISTANCE: f32 = 32.0;
+
+/// The file each sound is played from
+fn sound_path(sound: Sound) -> &'static str {
+    match sound {
+        Sound::BlockPlace => "sounds/block_place.ogg",
+        Sound::BlockBreak => "sounds/block_break.ogg",
+        Sound::BlockUse => "sounds/block_use.ogg",
+    }
+}
+
+/// Plays the sounds sent by the server, quieter the further they are from the camera
+pub fn play_sounds(
+    mut packets: EventReader<ReceivePacket>,
+    camera: Query<&Transform, With<Camera>>,
+    assets: Res<AssetServer>,
+    audio: Res<Audio>,
+) {
+    let camera = match camera.get_single() {
+        Ok(val) => val.translation,
+        Err(_) => return,
+    };
+
+    for packet in packets.iter() {
+        if let Protocol::PlaySound(sound) = &packet.0 {
+            let distance = camera.distance(Vec3::new(sound.x, sound.y, sound.z));
+            let volume = sound.volume * (1.0 - distance / HEARING_DISTANCE);
+            if volume <= 0.0 {
+                continue;
+            }
+
+            audio.play_with_settings(
+                assets.load(sound_path(sound.sound_id)),
+                PlaybackSettings::ONCE
+                    .with_volume(volume)
+                    .with_speed(sound.pitch),
+            );
+        }
+    }
+}
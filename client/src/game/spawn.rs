@@ -0,0 +1,48 @@
+use crate::game::player::Player;
+use crate::systems::physics::PhysicsObject;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::UserId;
+use rc_networking::protocol::serverbound::spawn_point::{SpawnAction, SpawnPoint};
+use rc_networking::protocol::Protocol;
+use rc_networking::types::{ReceivePacket, SendPacket};
+
+/// Key that teleports the player back to their spawn point, or sets it when Ctrl is held
+pub const SPAWN_POINT_KEY: KeyCode = KeyCode::Home;
+
+pub fn spawn_point_keys(keys: Res<Input<KeyCode>>, mut send_packets: EventWriter<SendPacket>) {
+    if !keys.just_pressed(SPAWN_POINT_KEY) {
+        return;
+    }
+
+    let action = if keys.pressed(KeyCode::LControl) {
+        info!("Setting spawn point");
+        SpawnAction::Set
+    } else {
+        SpawnAction::Teleport
+    };
+
+    send_packets.send(SendPacket(
+        Protocol::SpawnPoint(SpawnPoint::new(action)),
+        UserId(0),
+    ));
+}
+
+/// Moves the player when the server teleports them
+pub fn teleport_player(
+    mut packets: EventReader<ReceivePacket>,
+    mut player: Query<&mut PhysicsObject, With<Player>>,
+) {
+    for packet in packets.iter() {
+        if let Protocol::Teleport(teleport) = &packet.0 {
+            let destination = Vector3::new(teleport.x, teleport.y, teleport.z);
+            info!("Teleported to {:?}", destination);
+
+            if let Ok(mut physics) = player.get_single_mut() {
+                physics.position = destination;
+                physics.previous_position = destination;
+                physics.velocity = Vector3::zeros();
+            }
+        }
+    }
+}
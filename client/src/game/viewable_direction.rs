@@ -4,6 +4,7 @@ use crate::systems::asset::atlas::index::Rotate;
 use crate::systems::chunk::data::{ChunkData, RawChunkData};
 use bevy::prelude::warn;
 use nalgebra::Vector3;
+use rc_networking::constants::block_id;
 use serde::{Deserialize, Serialize};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -88,6 +89,23 @@ impl ViewableDirectionBitMap {
     }
 }
 
+impl ViewableDirectionBitMap {
+    /// Rotates a direction clockwise around the Y axis by a number of 90 degree steps
+    pub fn rotate_y(&self, steps: u8) -> ViewableDirectionBitMap {
+        let mut direction = *self;
+        for _ in 0..(steps % 4) {
+            direction = match direction {
+                ViewableDirectionBitMap::Front => ViewableDirectionBitMap::Right,
+                ViewableDirectionBitMap::Right => ViewableDirectionBitMap::Back,
+                ViewableDirectionBitMap::Back => ViewableDirectionBitMap::Left,
+                ViewableDirectionBitMap::Left => ViewableDirectionBitMap::Front,
+                other => other,
+            };
+        }
+        direction
+    }
+}
+
 impl ViewableDirection {
     pub fn has_flag(&self, flag: ViewableDirectionBitMap) -> bool {
         let target: u8 = flag as u8;
@@ -141,10 +159,9 @@ pub fn calculate_viewable(
     let world = &chunk.world;
 
     let mut direction: u8 = 0;
+    let last = world.size() - 1;
 
-    if pos[1] != world[0].len() - 1
-        && should_draw_betweens(block_states, world, pos, [0, 1, 0], block)
-    {
+    if pos[1] != last && should_draw_betweens(block_states, world, pos, [0, 1, 0], block) {
         direction += ViewableDirectionBitMap::Top as u8;
     }
 
@@ -152,8 +169,7 @@ pub fn calculate_viewable(
         direction += ViewableDirectionBitMap::Bottom as u8;
     }
 
-    if pos[0] != world.len() - 1 && should_draw_betweens(block_states, world, pos, [1, 0, 0], block)
-    {
+    if pos[0] != last && should_draw_betweens(block_states, world, pos, [1, 0, 0], block) {
         direction += ViewableDirectionBitMap::Right as u8;
     }
 
@@ -161,9 +177,7 @@ pub fn calculate_viewable(
         direction += ViewableDirectionBitMap::Left as u8;
     }
 
-    if pos[2] != world[0][0].len() - 1
-        && should_draw_betweens(block_states, world, pos, [0, 0, 1], block)
-    {
+    if pos[2] != last && should_draw_betweens(block_states, world, pos, [0, 0, 1], block) {
         direction += ViewableDirectionBitMap::Back as u8;
     }
 
@@ -181,22 +195,91 @@ fn should_draw_betweens(
     offset: [isize; 3],
     src_block: &Block,
 ) -> bool {
-    let block_id = world[(pos[0] as isize + offset[0]) as usize]
-        [(pos[1] as isize + offset[1]) as usize][(pos[2] as isize + offset[2]) as usize];
+    let block = world.get(
+        (pos[0] as isize + offset[0]) as usize,
+        (pos[1] as isize + offset[1]) as usize,
+        (pos[2] as isize + offset[2]) as usize,
+    );
 
-    if block_id == 0 {
+    if block_id(block) == 0 {
         return true;
     }
 
-    let block = block_states.get_block(block_id as usize);
+    should_draw_face(src_block, block_states.get_block(block as usize))
+}
 
+/// Decides if the face of a block that borders a neighbouring block should be drawn
+pub fn should_draw_face(block: &Block, neighbour: &Block) -> bool {
     // If its the same block we don't want borders drawn between them, or if they're both waterlogged
-    if (block.translucent) && block.identifier == src_block.identifier {
-        return block.draw_betweens;
+    if neighbour.translucent && neighbour.identifier == block.identifier {
+        return neighbour.draw_betweens;
     }
-    if !block.full {
+    // Faces against partial blocks or a different translucent block, like water against glass, are kept
+    if !neighbour.full {
         return true;
     }
 
-    block.translucent
+    neighbour.translucent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(identifier: &str, translucent: bool, full: bool, draw_betweens: bool) -> Block {
+        Block {
+            identifier: identifier.to_string(),
+            translucent,
+            full,
+            draw_betweens,
+            faces: Vec::new(),
+            bounding_boxes: Vec::new(),
+            emission: [0; 4],
+            hardness: 0.0,
+            rotatable: false,
+        }
+    }
+
+    fn water() -> Block {
+        block("mcv3::Water", true, true, false)
+    }
+
+    fn glass() -> Block {
+        block("mcv3::Glass", true, true, false)
+    }
+
+    fn stone() -> Block {
+        block("mcv3::Stone", false, true, false)
+    }
+
+    #[test]
+    fn water_against_water_is_hidden() {
+        assert!(!should_draw_face(&water(), &water()));
+    }
+
+    #[test]
+    fn water_against_glass_is_drawn() {
+        assert!(should_draw_face(&water(), &glass()));
+        assert!(should_draw_face(&glass(), &water()));
+    }
+
+    #[test]
+    fn water_against_solid_is_hidden() {
+        assert!(!should_draw_face(&water(), &stone()));
+        // The solid block still shows through the water
+        assert!(should_draw_face(&stone(), &water()));
+    }
+
+    #[test]
+    fn translucent_blocks_drawing_betweens_keep_their_faces() {
+        let leaves = block("mcv3::Leaves", true, true, true);
+        assert!(should_draw_face(&leaves, &leaves));
+    }
+
+    #[test]
+    fn faces_against_partial_blocks_are_drawn() {
+        let long_grass = block("mcv3::LongGrass", true, false, true);
+        assert!(should_draw_face(&stone(), &long_grass));
+        assert!(should_draw_face(&water(), &long_grass));
+    }
 }
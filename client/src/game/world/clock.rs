@@ -0,0 +1,198 @@
+use crate::config::ClientConfig;
+use crate::systems::asset::material::chunk::{ChunkMaterial, RenderClockUniform};
+use crate::systems::asset::AssetService;
+use crate::systems::camera::panorama::PanoramaCapture;
+use bevy::prelude::*;
+
+/// How far the clock can drift from the server before it's snapped rather than eased back
+const MAX_CLOCK_DRIFT: f32 = 2.0;
+
+/// How much of the drift from the server is corrected each second
+const CLOCK_CORRECTION_RATE: f32 = 0.5;
+
+/// A single time shared by everything animated, so animated textures, water and the day cycle stay in step.
+/// Anything that should hold still while a panorama is captured steps with this rather than bevy's time
+#[derive(Resource, Default, Debug, Clone)]
+pub struct RenderClock {
+    /// Seconds since the clock started, or since the start of the server's clock when connected
+    pub seconds: f32,
+    /// Whole ticks since the clock started
+    pub tick: u64,
+    /// How many seconds the clock moved on this frame, 0 while paused. Fades and blends step by this
+    pub delta: f32,
+    /// Seconds the clock has run for on this client, without the server's corrections. Positions received for
+    /// remote entities are timed against this
+    pub elapsed: f64,
+    /// Stopped while a panorama is captured, so every face shows the same moment
+    pub paused: bool,
+    /// How far the clock is behind the last time received from the server, corrected over time
+    drift: f32,
+}
+
+impl RenderClock {
+    pub fn advance(&mut self, delta: f32, tick_rate: u32) {
+        self.paused = false;
+        self.delta = delta;
+        self.elapsed += delta as f64;
+
+        // Ease towards the server time instead of jumping, so animations don't stutter
+        let correction =
+            (self.drift * CLOCK_CORRECTION_RATE * delta).clamp(-self.drift.abs(), self.drift.abs());
+        self.drift -= correction;
+
+        self.seconds += delta + correction;
+        self.tick = (self.seconds.max(0.0) * tick_rate.max(1) as f32) as u64;
+    }
+
+    /// Holds the clock where it is until it's next advanced
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.delta = 0.0;
+    }
+
+    /// Brings the clock in line with the time sent by the server. Ignored while paused, the next time sent
+    /// after resuming brings it back in line
+    pub fn reconcile(&mut self, server_seconds: f32, tick_rate: u32) {
+        if self.paused {
+            return;
+        }
+
+        let drift = server_seconds - self.seconds;
+
+        if drift.abs() > MAX_CLOCK_DRIFT {
+            self.seconds = server_seconds;
+            self.drift = 0.0;
+            self.tick = (self.seconds.max(0.0) * tick_rate.max(1) as f32) as u64;
+        } else {
+            self.drift = drift;
+        }
+    }
+
+    pub fn uniform(&self) -> RenderClockUniform {
+        RenderClockUniform {
+            seconds: self.seconds,
+            tick: self.tick as u32,
+        }
+    }
+}
+
+pub fn advance_render_clock(
+    mut clock: ResMut<RenderClock>,
+    time: Res<Time>,
+    config: Res<ClientConfig>,
+    panorama: Res<PanoramaCapture>,
+) {
+    if panorama.active {
+        clock.pause();
+        return;
+    }
+    clock.advance(time.delta_seconds(), config.clock_tick_rate);
+}
+
+/// Passes the clock to the chunk shader. Modifying a material causes its bind group to be rebuilt, so the clock is
+/// only passed on when its tick changes. Shader animations step with the tick, so nothing is missed in between
+pub fn upload_render_clock(
+    clock: Res<RenderClock>,
+    service: Res<AssetService>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    let uniform = clock.uniform();
+
+    for handle in [
+        &service.opaque_texture_atlas_material,
+        &service.translucent_texture_atlas_material,
+    ] {
+        let uploaded = materials
+            .get(handle)
+            .map_or(true, |v| v.clock.tick == uniform.tick);
+        if !uploaded {
+            materials.get_mut(handle).unwrap().clock = uniform;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK_RATE: u32 = 20;
+
+    #[test]
+    fn clock_advances_seconds_and_ticks() {
+        let mut clock = RenderClock::default();
+
+        clock.advance(0.5, TICK_RATE);
+        assert_eq!(clock.seconds, 0.5);
+        assert_eq!(clock.tick, 10);
+
+        clock.advance(0.25, TICK_RATE);
+        assert_eq!(clock.seconds, 0.75);
+        assert_eq!(clock.tick, 15);
+    }
+
+    #[test]
+    fn small_drift_from_server_is_eased_out() {
+        let mut clock = RenderClock::default();
+        clock.advance(10.0, TICK_RATE);
+
+        // The server is a second ahead, which is corrected over time rather than all at once
+        clock.reconcile(11.0, TICK_RATE);
+        assert_eq!(clock.seconds, 10.0);
+
+        clock.advance(0.1, TICK_RATE);
+        assert!(clock.seconds > 10.1 && clock.seconds < 11.1);
+
+        for _ in 0..600 {
+            clock.advance(0.1, TICK_RATE);
+        }
+        // Caught up with the server, which has moved on by the same time
+        assert!((clock.seconds - (11.0 + 60.1)).abs() < 0.01);
+    }
+
+    #[test]
+    fn large_drift_from_server_is_snapped() {
+        let mut clock = RenderClock::default();
+        clock.advance(1.0, TICK_RATE);
+
+        clock.reconcile(100.0, TICK_RATE);
+        assert_eq!(clock.seconds, 100.0);
+        assert_eq!(clock.tick, 2000);
+
+        // And runs on from there without correcting any further
+        clock.advance(1.0, TICK_RATE);
+        assert_eq!(clock.seconds, 101.0);
+    }
+
+    #[test]
+    fn clock_is_eased_back_when_ahead_of_server() {
+        let mut clock = RenderClock::default();
+        clock.advance(10.0, TICK_RATE);
+
+        clock.reconcile(9.0, TICK_RATE);
+        clock.advance(0.1, TICK_RATE);
+
+        // Still moves forward, just slower than real time
+        assert!(clock.seconds > 10.0 && clock.seconds < 10.1);
+    }
+
+    #[test]
+    fn paused_clock_holds_still() {
+        let mut clock = RenderClock::default();
+        clock.advance(1.0, TICK_RATE);
+
+        clock.pause();
+        clock.reconcile(100.0, TICK_RATE);
+
+        assert!(clock.paused);
+        assert_eq!(clock.delta, 0.0);
+        assert_eq!(clock.elapsed, 1.0);
+        assert_eq!(clock.seconds, 1.0);
+
+        // Picks up from where it stopped
+        clock.advance(0.5, TICK_RATE);
+        assert!(!clock.paused);
+        assert_eq!(clock.delta, 0.5);
+        assert_eq!(clock.elapsed, 1.5);
+        assert_eq!(clock.seconds, 1.5);
+    }
+}
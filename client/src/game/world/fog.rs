@@ -0,0 +1,187 @@
+use crate::config::ClientConfig;
+use crate::game::world::clock::RenderClock;
+use crate::systems::asset::material::chunk::{ChunkMaterial, FogUniform};
+use crate::systems::asset::AssetService;
+use crate::systems::networking::join::WorldInfo;
+use bevy::prelude::*;
+use rc_networking::constants::Dimension;
+
+/// About how long, in seconds, the fog takes to change colour when moving somewhere else
+const FOG_TRANSITION_SECONDS: f32 = 3.0;
+
+/// Below this height the player is counted as being underground
+const UNDERGROUND_HEIGHT: f32 = 24.0;
+
+/// The places that each have their own fog colour
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FogEnvironment {
+    Surface,
+    Underground,
+    Nether,
+}
+
+impl FogEnvironment {
+    /// Where the player is, from the dimension they're in and how high up they are
+    pub fn at(dimension: Dimension, height: f32) -> FogEnvironment {
+        match dimension {
+            Dimension::Nether => FogEnvironment::Nether,
+            Dimension::Overworld if height < UNDERGROUND_HEIGHT => FogEnvironment::Underground,
+            Dimension::Overworld => FogEnvironment::Surface,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            FogEnvironment::Surface => Color::rgb(0.7137, 0.7803, 0.8784),
+            FogEnvironment::Underground => Color::rgb(0.05, 0.05, 0.08),
+            FogEnvironment::Nether => Color::rgb(0.35, 0.08, 0.05),
+        }
+    }
+}
+
+/// Moves a colour part of the way to another, so after `FOG_TRANSITION_SECONDS` it has nearly arrived whatever the frame rate.
+/// Once it's too close to see the difference it jumps the rest of the way, so the fog stops changing
+pub fn blend_fog(current: Color, target: Color, delta: f32) -> Color {
+    // Cover 95% of the distance over the transition
+    let t = 1.0 - 0.05_f32.powf(delta / FOG_TRANSITION_SECONDS);
+
+    let [r, g, b, a] = current.as_rgba_f32();
+    let [tr, tg, tb, ta] = target.as_rgba_f32();
+
+    let remaining = [tr - r, tg - g, tb - b, ta - a];
+    if remaining.iter().all(|v| v.abs() < 0.5 / 255.0) {
+        return target;
+    }
+
+    Color::rgba(
+        r + (tr - r) * t,
+        g + (tg - g) * t,
+        b + (tb - b) * t,
+        a + (ta - a) * t,
+    )
+}
+
+/// The colour distant chunks fade into, which can differ from the sky behind them
+#[derive(Resource)]
+pub struct Fog {
+    pub color: Color,
+    pub environment: FogEnvironment,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog {
+            color: FogEnvironment::Surface.color(),
+            environment: FogEnvironment::Surface,
+        }
+    }
+}
+
+pub fn update_fog(
+    mut fog: ResMut<Fog>,
+    camera: Query<&Transform, With<Camera>>,
+    world_info: Option<Res<WorldInfo>>,
+    clock: Res<RenderClock>,
+) {
+    let height = match camera.get_single() {
+        Ok(val) => val.translation.y,
+        Err(_) => return,
+    };
+
+    let dimension = world_info.map_or(Dimension::Overworld, |v| v.dimension);
+    fog.environment = FogEnvironment::at(dimension, height);
+
+    fog.color = blend_fog(fog.color, fog.environment.color(), clock.delta);
+}
+
+/// Passes the fog to the chunk shader. Modifying a material causes its bind group to be rebuilt, so this only
+/// happens while the fog is changing
+pub fn upload_fog(
+    fog: Res<Fog>,
+    config: Res<ClientConfig>,
+    service: Res<AssetService>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    let uniform = FogUniform {
+        color: Vec4::from(fog.color.as_linear_rgba_f32()),
+        start: config.fog_start,
+        end: config.fog_end,
+    };
+
+    for handle in [
+        &service.opaque_texture_atlas_material,
+        &service.translucent_texture_atlas_material,
+    ] {
+        let uploaded = materials.get(handle).map_or(true, |v| v.fog == uniform);
+        if !uploaded {
+            materials.get_mut(handle).unwrap().fog = uniform;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Color, b: Color) -> bool {
+        a.as_rgba_f32()
+            .iter()
+            .zip(b.as_rgba_f32().iter())
+            .all(|(a, b)| (a - b).abs() < 0.001)
+    }
+
+    #[test]
+    fn environment_changes_when_going_underground() {
+        assert_eq!(
+            FogEnvironment::at(Dimension::Overworld, UNDERGROUND_HEIGHT + 1.0),
+            FogEnvironment::Surface
+        );
+        assert_eq!(
+            FogEnvironment::at(Dimension::Overworld, UNDERGROUND_HEIGHT - 1.0),
+            FogEnvironment::Underground
+        );
+        // The nether has its own fog however high up the player is
+        assert_eq!(
+            FogEnvironment::at(Dimension::Nether, UNDERGROUND_HEIGHT + 1.0),
+            FogEnvironment::Nether
+        );
+    }
+
+    #[test]
+    fn fog_blends_across_boundary() {
+        let surface = FogEnvironment::Surface.color();
+        let underground = FogEnvironment::Underground.color();
+
+        // Nothing changes without time passing
+        assert!(close(blend_fog(surface, underground, 0.0), surface));
+
+        // Part of the way over after a frame, moving towards the new colour without overshooting it
+        let frame = blend_fog(surface, underground, 1.0 / 60.0);
+        assert!(!close(frame, surface));
+        assert!(frame.r() < surface.r() && frame.r() > underground.r());
+
+        // Nearly there after the transition time, however it's split into frames
+        let mut stepped = surface;
+        for _ in 0..(FOG_TRANSITION_SECONDS * 60.0) as usize {
+            stepped = blend_fog(stepped, underground, 1.0 / 60.0);
+        }
+        let whole = blend_fog(surface, underground, FOG_TRANSITION_SECONDS);
+
+        assert!(close(stepped, whole));
+        assert!((whole.r() - underground.r()).abs() <= (surface.r() - underground.r()) * 0.051);
+    }
+
+    #[test]
+    fn fog_settles_on_target() {
+        let surface = FogEnvironment::Surface.color();
+        let underground = FogEnvironment::Underground.color();
+
+        let mut color = surface;
+        for _ in 0..(FOG_TRANSITION_SECONDS * 60.0 * 4.0) as usize {
+            color = blend_fog(color, underground, 1.0 / 60.0);
+        }
+
+        // Exactly on the target rather than creeping towards it forever
+        assert_eq!(color.as_rgba_f32(), underground.as_rgba_f32());
+    }
+}
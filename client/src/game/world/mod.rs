@@ -1,15 +1,36 @@
+use crate::game::world::clock::{advance_render_clock, upload_render_clock, RenderClock};
+use crate::game::world::fog::{update_fog, upload_fog, Fog};
+use crate::game::world::sky::{select_skybox, setup_skybox, update_skybox};
 use crate::game::world::sun::{setup_sun, update_sun};
 use crate::state::AppState;
 use bevy::app::App;
-use bevy::prelude::{Plugin, SystemSet};
+use bevy::prelude::{IntoSystemDescriptor, Plugin, SystemSet};
 
+pub mod clock;
+pub mod fog;
+pub mod sky;
 pub mod sun;
 
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(setup_sun))
-            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(update_sun));
+        app.insert_resource(RenderClock::default())
+            .add_system(advance_render_clock)
+            .add_system(upload_render_clock.after(advance_render_clock))
+            .insert_resource(Fog::default())
+            .add_system(update_fog.after(advance_render_clock))
+            .add_system(upload_fog.after(update_fog))
+            .add_system_set(
+                SystemSet::on_enter(AppState::InGame)
+                    .with_system(setup_sun)
+                    .with_system(setup_skybox),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(update_sun)
+                    .with_system(select_skybox.before(update_skybox))
+                    .with_system(update_skybox.after(advance_render_clock)),
+            );
     }
 }
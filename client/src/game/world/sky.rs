@@ -0,0 +1,251 @@
+use crate::config::ClientConfig;
+use crate::game::world::clock::RenderClock;
+use crate::systems::networking::join::WorldInfo;
+use bevy::prelude::*;
+use rc_networking::constants::Dimension;
+use std::collections::HashMap;
+
+/// How long it takes to cross-fade from one skybox to another
+const SKYBOX_FADE_SECONDS: f32 = 2.0;
+
+/// How far away the skybox is drawn from the camera
+const SKYBOX_RADIUS: f32 = 900.0;
+
+/// The skyboxes that can be drawn behind the world
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SkyboxKind {
+    Overworld,
+    Nether,
+}
+
+impl SkyboxKind {
+    pub const ALL: [SkyboxKind; 2] = [SkyboxKind::Overworld, SkyboxKind::Nether];
+
+    /// The skybox shown in a dimension
+    pub fn for_dimension(dimension: Dimension) -> SkyboxKind {
+        match dimension {
+            Dimension::Overworld => SkyboxKind::Overworld,
+            Dimension::Nether => SkyboxKind::Nether,
+        }
+    }
+
+    pub fn texture(&self) -> &'static str {
+        match self {
+            SkyboxKind::Overworld => "textures/world/sky_overworld.png",
+            SkyboxKind::Nether => "textures/world/sky_nether.png",
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Skybox {
+    /// The skybox that should be shown, changing it fades over to it. Follows the player's dimension
+    pub selected: SkyboxKind,
+    /// The skybox being faded to or currently shown
+    current: SkyboxKind,
+    /// How long the current fade has been running for
+    fade_elapsed: f32,
+    textures: HashMap<SkyboxKind, Handle<Image>>,
+    // The back skybox holds the skybox being faded out, while the front fades in on top of it
+    front: Entity,
+    front_material: Handle<StandardMaterial>,
+    back: Entity,
+    back_material: Handle<StandardMaterial>,
+}
+
+/// The weight of the new skybox during a cross-fade, going from 0 when the fade starts to 1 when it's complete
+pub fn crossfade_weight(elapsed: f32, duration: f32) -> f32 {
+    if duration <= 0.0 {
+        return 1.0;
+    }
+    (elapsed / duration).clamp(0.0, 1.0)
+}
+
+pub fn setup_skybox(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    assets: Res<AssetServer>,
+    world_info: Option<Res<WorldInfo>>,
+) {
+    let textures = SkyboxKind::ALL
+        .iter()
+        .map(|kind| (*kind, assets.load(kind.texture())))
+        .collect::<HashMap<SkyboxKind, Handle<Image>>>();
+
+    // Start on the player's dimension rather than fading over to it as they join
+    let current = world_info.map_or(SkyboxKind::Overworld, |v| {
+        SkyboxKind::for_dimension(v.dimension)
+    });
+
+    let front_material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        base_color_texture: Some(textures.get(&current).unwrap().clone()),
+        unlit: true,
+        alpha_mode: AlphaMode::Blend,
+        cull_mode: None,
+        ..default()
+    });
+    let back_material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        base_color_texture: Some(textures.get(&current).unwrap().clone()),
+        unlit: true,
+        alpha_mode: AlphaMode::Opaque,
+        cull_mode: None,
+        ..default()
+    });
+
+    let front = commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                radius: SKYBOX_RADIUS,
+                sectors: 32,
+                stacks: 16,
+            })),
+            material: front_material.clone(),
+            ..default()
+        })
+        .id();
+
+    let back = commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                radius: SKYBOX_RADIUS + 10.0,
+                sectors: 32,
+                stacks: 16,
+            })),
+            material: back_material.clone(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .id();
+
+    commands.insert_resource(Skybox {
+        selected: current,
+        current,
+        fade_elapsed: SKYBOX_FADE_SECONDS,
+        textures,
+        front,
+        front_material,
+        back,
+        back_material,
+    });
+}
+
+/// Selects the skybox for the dimension the player is in
+pub fn select_skybox(world_info: Option<Res<WorldInfo>>, mut skybox: ResMut<Skybox>) {
+    let world_info = match world_info {
+        Some(val) => val,
+        None => return,
+    };
+
+    let selected = SkyboxKind::for_dimension(world_info.dimension);
+    if skybox.selected != selected {
+        skybox.selected = selected;
+    }
+}
+
+pub fn update_skybox(
+    mut skybox: ResMut<Skybox>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    camera: Query<&Transform, (With<Camera>, Without<Handle<StandardMaterial>>)>,
+    mut spheres: Query<(&mut Transform, &mut Visibility), With<Handle<StandardMaterial>>>,
+    config: Res<ClientConfig>,
+    clock: Res<RenderClock>,
+) {
+    // Start a new fade when the selected skybox changes
+    if skybox.selected != skybox.current {
+        let previous = skybox.textures.get(&skybox.current).cloned();
+        let next = skybox.textures.get(&skybox.selected).cloned();
+
+        if let Some(material) = materials.get_mut(&skybox.back_material) {
+            material.base_color_texture = previous;
+        }
+        if let Some(material) = materials.get_mut(&skybox.front_material) {
+            material.base_color_texture = next;
+        }
+
+        skybox.current = skybox.selected;
+        skybox.fade_elapsed = 0.0;
+    }
+
+    skybox.fade_elapsed += clock.delta;
+    // Reduced motion switches straight to the new skybox
+    let weight = crossfade_weight(
+        skybox.fade_elapsed,
+        SKYBOX_FADE_SECONDS * config.motion_scale(),
+    );
+
+    // Only touch the material while fading as modifying it causes it to be re-uploaded
+    let faded = materials
+        .get(&skybox.front_material)
+        .map_or(true, |v| v.base_color.a() == weight);
+    if !faded {
+        materials
+            .get_mut(&skybox.front_material)
+            .unwrap()
+            .base_color
+            .set_a(weight);
+    }
+
+    // Keep the skybox centered on the camera
+    let camera = match camera.get_single() {
+        Ok(val) => val.translation,
+        Err(_) => return,
+    };
+
+    if let Ok((mut transform, _)) = spheres.get_mut(skybox.front) {
+        transform.translation = camera;
+    }
+    if let Ok((mut transform, mut visibility)) = spheres.get_mut(skybox.back) {
+        transform.translation = camera;
+        visibility.is_visible = weight < 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crossfade_weight_during_switch() {
+        // Only the old skybox shows as the switch starts, and the new one fades in evenly over the fade
+        assert_eq!(crossfade_weight(0.0, SKYBOX_FADE_SECONDS), 0.0);
+        assert_eq!(
+            crossfade_weight(SKYBOX_FADE_SECONDS * 0.25, SKYBOX_FADE_SECONDS),
+            0.25
+        );
+        assert_eq!(
+            crossfade_weight(SKYBOX_FADE_SECONDS * 0.5, SKYBOX_FADE_SECONDS),
+            0.5
+        );
+
+        // Then stays on the new skybox once the fade is over
+        assert_eq!(
+            crossfade_weight(SKYBOX_FADE_SECONDS, SKYBOX_FADE_SECONDS),
+            1.0
+        );
+        assert_eq!(
+            crossfade_weight(SKYBOX_FADE_SECONDS * 3.0, SKYBOX_FADE_SECONDS),
+            1.0
+        );
+    }
+
+    #[test]
+    fn crossfade_without_duration_switches_straight_away() {
+        // Reduced motion scales the fade to nothing
+        assert_eq!(crossfade_weight(0.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn skybox_follows_dimension() {
+        assert_eq!(
+            SkyboxKind::for_dimension(Dimension::Overworld),
+            SkyboxKind::Overworld
+        );
+        assert_eq!(
+            SkyboxKind::for_dimension(Dimension::Nether),
+            SkyboxKind::Nether
+        );
+    }
+}
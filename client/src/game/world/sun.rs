@@ -1,67 +1,138 @@
-use crate::game::player::Player;
+use crate::config::ClientConfig;
+use crate::game::world::clock::RenderClock;
 use bevy::prelude::shape::Quad;
 use bevy::prelude::*;
-use bevy::render::primitives::Plane;
-use bevy::utils::Instant;
-use nalgebra::Vector3;
 use std::f32::consts::PI;
-use std::time::{SystemTime, UNIX_EPOCH};
-use zip::DateTime;
+
+/// How far from the camera the sun and moon are drawn, past the furthest chunks but in front of the skybox
+const CELESTIAL_DISTANCE: f32 = 800.0;
+
+/// How many phases the moon goes through, one a day starting from full
+pub const MOON_PHASES: usize = 8;
+
+/// How much light is left on the dark side of the moon
+const MOON_SHADOW_BRIGHTNESS: f32 = 0.1;
+
+const DAY_ILLUMINANCE: f32 = 50000.0;
+const NIGHT_ILLUMINANCE: f32 = 5000.0;
 
 #[derive(Resource)]
 pub struct SunData {
     sun_sprite: Entity,
     moon_sprite: Entity,
     directional_light: Entity,
+    moon_material: Handle<StandardMaterial>,
+    moon_texture: Handle<Image>,
+    /// The moon texture shaded for each phase, made once the moon texture has loaded
+    moon_phases: Vec<Handle<Image>>,
+    shown_phase: Option<usize>,
+}
+
+/// How far through the current day it is, from 0 at sunrise through noon at 0.25 to 1 at the next sunrise
+pub fn day_fraction(seconds: f32, day_length: f32) -> f32 {
+    if day_length <= 0.0 {
+        return 0.25;
+    }
+    (seconds / day_length).rem_euclid(1.0)
+}
+
+/// The direction from the player to the sun, rising in the south and setting in the north. The moon is opposite
+pub fn sun_direction(day_fraction: f32) -> Vec3 {
+    let angle = day_fraction * 2.0 * PI;
+    Vec3::new(0.0, angle.sin(), angle.cos())
+}
+
+/// The phase the moon is in, from 0 for a full moon through 4 for a new moon, advancing each day
+pub fn moon_phase(seconds: f32, day_length: f32) -> usize {
+    if day_length <= 0.0 {
+        return 0;
+    }
+    ((seconds / day_length).floor() as i64).rem_euclid(MOON_PHASES as i64) as usize
+}
+
+/// Whether a point on the moon is lit in a phase. The point goes from -1 to 1 across the moon's disc,
+/// the shadow creeps in from the right as it wanes and leaves to the left as it waxes
+pub fn moon_lit(phase: usize, x: f32, y: f32) -> bool {
+    let half_width = (1.0 - y * y).max(0.0).sqrt();
+    let angle = phase as f32 / MOON_PHASES as f32 * 2.0 * PI;
+    let terminator = half_width * angle.cos();
+
+    if phase * 2 <= MOON_PHASES {
+        x <= terminator
+    } else {
+        x >= -terminator
+    }
+}
+
+/// Darkens the unlit part of the moon texture for a phase, None if the texture isn't 8 bit RGBA
+fn shade_moon(moon: &Image, phase: usize) -> Option<Image> {
+    let size = moon.texture_descriptor.size;
+    let (width, height) = (size.width as usize, size.height as usize);
+    if moon.data.len() != width * height * 4 {
+        return None;
+    }
+
+    let mut image = moon.clone();
+    for py in 0..height {
+        for px in 0..width {
+            let x = (px as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+            let y = (py as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+            if moon_lit(phase, x, y) {
+                continue;
+            }
+
+            let pixel = (py * width + px) * 4;
+            for channel in &mut image.data[pixel..pixel + 4] {
+                *channel = (*channel as f32 * MOON_SHADOW_BRIGHTNESS) as u8;
+            }
+        }
+    }
+
+    Some(image)
+}
+
+fn celestial_quad(
+    texture: Handle<Image>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) -> PbrBundle {
+    PbrBundle {
+        mesh: meshes.add(Mesh::from(Quad::new(Vec2::new(150.0, 150.0)))),
+        material: materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            base_color_texture: Some(texture),
+            unlit: true,
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        }),
+        ..default()
+    }
 }
 
 pub fn setup_sun(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut assets: Res<AssetServer>,
+    assets: Res<AssetServer>,
 ) {
-    // TODO: Spawn this attached to the camera so it moves around with it
     let sun_sprite = commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(Quad::new(Vec2::new(150.0, 150.0)))),
-            material: materials.add(StandardMaterial {
-                base_color: Color::WHITE,
-                base_color_texture: Some(assets.load("textures/world/sun.png")),
-                unlit: true,
-                alpha_mode: AlphaMode::Blend,
-                ..default()
-            }),
-            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
-            global_transform: Default::default(),
-            visibility: Default::default(),
-            computed_visibility: Default::default(),
-        })
-        .with_children(|c| {})
+        .spawn(celestial_quad(
+            assets.load("textures/world/sun.png"),
+            &mut meshes,
+            &mut materials,
+        ))
         .id();
 
-    let moon_sprite = commands
-        .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(Quad::new(Vec2::new(150.0, 150.0)))),
-            material: materials.add(StandardMaterial {
-                base_color: Color::WHITE,
-                base_color_texture: Some(assets.load("textures/world/moon.png")),
-                unlit: true,
-                alpha_mode: AlphaMode::Blend,
-                ..default()
-            }),
-            transform: Transform::from_translation(Vec3::new(PI / 2.0, 0.0, 0.0)),
-            global_transform: Default::default(),
-            visibility: Default::default(),
-            computed_visibility: Default::default(),
-        })
-        .id();
+    let moon_texture = assets.load("textures/world/moon.png");
+    let moon_bundle = celestial_quad(moon_texture.clone(), &mut meshes, &mut materials);
+    let moon_material = moon_bundle.material.clone();
+    let moon_sprite = commands.spawn(moon_bundle).id();
 
     let directional_light = commands
         .spawn(DirectionalLightBundle {
             directional_light: DirectionalLight {
                 color: Color::rgb(1., 1., 1.),
-                illuminance: 50000.0,
+                illuminance: DAY_ILLUMINANCE,
                 shadow_projection: OrthographicProjection {
                     left: -40.0,
                     right: 40.0,
@@ -88,54 +159,169 @@ pub fn setup_sun(
         sun_sprite,
         moon_sprite,
         directional_light,
+        moon_material,
+        moon_texture,
+        moon_phases: Vec::new(),
+        shown_phase: None,
     });
 }
 
-pub fn update_sun(mut sundata: ResMut<SunData>, mut query: Query<&mut Transform>) {
-    let day_len_ms = 1000 * 60;
+pub fn update_sun(
+    mut sundata: ResMut<SunData>,
+    clock: Res<RenderClock>,
+    config: Res<ClientConfig>,
+    camera: Query<&Transform, (With<Camera>, Without<DirectionalLight>)>,
+    mut transforms: Query<&mut Transform, Without<Camera>>,
+    mut visibilities: Query<&mut Visibility>,
+    mut lights: Query<&mut DirectionalLight>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let camera = match camera.get_single() {
+        Ok(val) => val.translation,
+        Err(_) => return,
+    };
+
+    let sun = sun_direction(day_fraction(clock.seconds, config.day_length));
+
+    // Keep the sun and moon centered on the camera facing back at it, so they're always behind the terrain
+    for (entity, direction) in [(sundata.sun_sprite, sun), (sundata.moon_sprite, -sun)] {
+        if let Ok(mut transform) = transforms.get_mut(entity) {
+            let position = camera + direction * CELESTIAL_DISTANCE;
+            *transform =
+                Transform::from_translation(position).looking_at(position + direction, Vec3::X);
+        }
+        if let Ok(mut visibility) = visibilities.get_mut(entity) {
+            visibility.is_visible = config.sun_and_moon;
+        }
+    }
+
+    // The light shines from whichever of the sun or moon is up
+    let (light_direction, illuminance) = if sun.y >= 0.0 {
+        (sun, DAY_ILLUMINANCE)
+    } else {
+        (-sun, NIGHT_ILLUMINANCE)
+    };
+    if let Ok(mut transform) = transforms.get_mut(sundata.directional_light) {
+        *transform = Transform::default().looking_at(-light_direction, Vec3::X);
+    }
+    if let Ok(mut light) = lights.get_mut(sundata.directional_light) {
+        light.illuminance = illuminance;
+    }
 
-    let sun_distance = 600.0;
+    // Shade a copy of the moon for each phase once its texture is ready
+    if sundata.moon_phases.is_empty() {
+        let phases = match images.get(&sundata.moon_texture) {
+            Some(moon) => (0..MOON_PHASES)
+                .map(|phase| shade_moon(moon, phase))
+                .collect::<Option<Vec<Image>>>(),
+            None => return,
+        };
 
-    let mut time = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis()
-        % day_len_ms;
+        match phases {
+            Some(phases) => {
+                sundata.moon_phases = phases.into_iter().map(|v| images.add(v)).collect();
+            }
+            None => {
+                warn!("Moon texture isn't 8 bit RGBA, it won't show phases");
+                sundata.moon_phases = vec![sundata.moon_texture.clone(); MOON_PHASES];
+            }
+        }
+    }
 
-    let mut day_progress = (time as f32 / day_len_ms as f32);
+    let phase = moon_phase(clock.seconds, config.day_length);
+    if sundata.shown_phase != Some(phase) {
+        if let Some(material) = materials.get_mut(&sundata.moon_material) {
+            material.base_color_texture = Some(sundata.moon_phases[phase].clone());
+            sundata.shown_phase = Some(phase);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
 
-    day_progress = 0.05;
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} isn't {:?}", a, b);
+    }
 
-    let rotation_amount = day_progress * 2.0 * PI + (PI / 4.0);
-    let x = (rotation_amount.cos() - rotation_amount.sin()) * sun_distance;
-    let y = (rotation_amount.cos() + rotation_amount.sin()) * sun_distance;
+    #[test]
+    fn day_fraction_wraps_each_day() {
+        assert_eq!(day_fraction(0.0, 1200.0), 0.0);
+        assert_eq!(day_fraction(300.0, 1200.0), 0.25);
+        assert_eq!(day_fraction(1500.0, 1200.0), 0.25);
+        assert_eq!(day_fraction(-300.0, 1200.0), 0.75);
+        // Without a day length it stays noon
+        assert_eq!(day_fraction(500.0, 0.0), 0.25);
+    }
 
-    let mut transform = query.get_mut(sundata.sun_sprite).unwrap();
-    transform.translation = Vec3::new(0.0, y, x);
+    #[test]
+    fn sun_rises_peaks_and_sets() {
+        assert_near(sun_direction(0.0), Vec3::Z);
+        assert_near(sun_direction(0.25), Vec3::Y);
+        assert_near(sun_direction(0.5), -Vec3::Z);
+        assert_near(sun_direction(0.75), -Vec3::Y);
+    }
 
-    transform.rotation = Quat::from_axis_angle(
-        Vec3::new(1.0, 0.0, 0.0),
-        (day_progress * -PI * 2.0) + (PI / 2.0),
-    );
+    #[test]
+    fn moon_phase_advances_each_day() {
+        assert_eq!(moon_phase(0.0, 1200.0), 0);
+        assert_eq!(moon_phase(1199.0, 1200.0), 0);
+        assert_eq!(moon_phase(1200.0, 1200.0), 1);
+        assert_eq!(moon_phase(1200.0 * 4.5, 1200.0), 4);
+        // Back to full after every phase
+        assert_eq!(moon_phase(1200.0 * MOON_PHASES as f32, 1200.0), 0);
+        assert_eq!(moon_phase(-1.0, 1200.0), MOON_PHASES - 1);
+        assert_eq!(moon_phase(5000.0, 0.0), 0);
+    }
 
-    let rotation_amount = day_progress * 2.0 * PI + (PI / 4.0) + PI;
-    let x = (rotation_amount.cos() - rotation_amount.sin()) * sun_distance;
-    let y = (rotation_amount.cos() + rotation_amount.sin()) * sun_distance;
+    #[test]
+    fn moon_is_lit_by_phase() {
+        // Full
+        assert!(moon_lit(0, -0.5, 0.0));
+        assert!(moon_lit(0, 0.5, 0.0));
+        // Waning half, the right side is in shadow
+        assert!(moon_lit(2, -0.5, 0.0));
+        assert!(!moon_lit(2, 0.5, 0.0));
+        // New
+        assert!(!moon_lit(4, -0.5, 0.0));
+        assert!(!moon_lit(4, 0.5, 0.0));
+        // Waxing half, lit from the right
+        assert!(!moon_lit(6, -0.5, 0.0));
+        assert!(moon_lit(6, 0.5, 0.0));
+    }
+
+    fn white_moon() -> Image {
+        Image::new(
+            Extent3d {
+                width: 2,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            vec![255; 8],
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
 
-    let mut transform = query.get_mut(sundata.moon_sprite).unwrap();
-    transform.translation = Vec3::new(0.0, y, x);
+    #[test]
+    fn moon_texture_is_shaded_for_its_phase() {
+        let full = shade_moon(&white_moon(), 0).unwrap();
+        assert_eq!(full.data, vec![255; 8]);
 
-    transform.rotation = Quat::from_axis_angle(
-        Vec3::new(1.0, 0.0, 0.0),
-        (day_progress * -PI * 2.0) + (PI / 2.0) + PI,
-    );
+        let half = shade_moon(&white_moon(), 2).unwrap();
+        assert_eq!(half.data, vec![255, 255, 255, 255, 25, 25, 25, 25]);
 
-    // Update directional light
-    let rot = Quat::from_axis_angle(
-        Vec3::new(1.0, 0.0, 0.0),
-        (day_progress * -PI * 2.0) + (PI / 2.0) + PI,
-    );
+        let new = shade_moon(&white_moon(), 4).unwrap();
+        assert_eq!(new.data, vec![25; 8]);
+    }
 
-    let mut transform = query.get_mut(sundata.directional_light).unwrap();
-    transform.rotation = rot;
+    #[test]
+    fn only_rgba_moons_are_shaded() {
+        let mut moon = white_moon();
+        moon.data.truncate(6);
+        assert!(shade_moon(&moon, 2).is_none());
+    }
 }
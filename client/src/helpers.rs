@@ -1,7 +1,6 @@
 use crate::systems::chunk::data::LightingColor;
 use bevy::prelude::Vec3;
 use nalgebra::{Point3, Vector3};
-use rc_networking::constants::CHUNK_SIZE;
 use std::ops::Add;
 
 /// Formats a u32 with American comma placement.
@@ -27,6 +26,23 @@ pub fn format_u32(mut count: u32) -> String {
     msg
 }
 
+/// Checks if a block is close enough to a position to be reached, measured to the center of the block
+/// the same way the server checks edits.
+///
+/// # Example
+/// ```rust
+/// use nalgebra::Vector3;
+/// use rc_client::helpers::within_reach;
+/// assert!(within_reach(Vector3::new(0.5, 0.5, 0.5), Vector3::new(4, 0, 0), 4.0));
+/// assert!(!within_reach(Vector3::new(0.5, 0.5, 0.5), Vector3::new(5, 0, 0), 4.0));
+/// ```
+#[inline]
+pub fn within_reach(position: Vector3<f32>, block: Vector3<i32>, reach: f32) -> bool {
+    let center = block.cast::<f32>() + Vector3::new(0.5, 0.5, 0.5);
+
+    (center - position).magnitude() <= reach
+}
+
 pub trait Lerp {
     fn lerp(self, b: Self, t: f32) -> Self;
 }
@@ -96,10 +112,10 @@ impl Clamp for f32 {
     }
 }
 
-/// Converts global coordinate axis to a local one inside the data
+/// Converts global coordinate axis to a local one inside the data of a chunk `size` blocks wide
 #[inline]
-pub fn get_chunk_coords(i: i32) -> usize {
-    ((i % CHUNK_SIZE as i32) + CHUNK_SIZE as i32) as usize % CHUNK_SIZE
+pub fn get_chunk_coords(i: i32, size: usize) -> usize {
+    i.rem_euclid(size as i32) as usize
 }
 
 #[inline]
@@ -112,53 +128,43 @@ pub fn to_bevy_vec3(vector: Vector3<f32>) -> Vec3 {
     Vec3::new(vector.x, vector.y, vector.z)
 }
 
+/// Splits a global block position into the chunk it's in and its position within that chunk, for chunks `size`
+/// blocks wide
 #[inline]
-pub fn global_to_local_position(vector: Vector3<i32>) -> (Vector3<i32>, Vector3<usize>) {
+pub fn global_to_local_position(
+    vector: Vector3<i32>,
+    size: usize,
+) -> (Vector3<i32>, Vector3<usize>) {
+    let size = size as i32;
+
     // Locate block
-    let inner_loc = Vector3::new(
-        ((vector.x as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-        ((vector.y as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-        ((vector.z as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-    );
+    let inner_loc = vector.map(|v| v.rem_euclid(size) as usize);
 
     // Locate chunk
-    let chunk_loc = Vector3::new(
-        (vector.x as f32 / CHUNK_SIZE as f32).floor() as i32,
-        (vector.y as f32 / CHUNK_SIZE as f32).floor() as i32,
-        (vector.z as f32 / CHUNK_SIZE as f32).floor() as i32,
-    );
+    let chunk_loc = vector.map(|v| v.div_euclid(size));
 
     (chunk_loc, inner_loc)
 }
 
+/// Splits a position in the world into the chunk it's in and the position of the block it's in within that chunk,
+/// for chunks `size` blocks wide
 #[inline]
-pub fn global_f32_to_local_position(vector: Vector3<f32>) -> (Vector3<i32>, Vector3<usize>) {
-    // Locate block
-    let inner_loc = Vector3::new(
-        ((vector.x as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-        ((vector.y as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-        ((vector.z as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-    );
-
-    // Locate chunk
-    let chunk_loc = Vector3::new(
-        (vector.x / CHUNK_SIZE as f32).floor() as i32,
-        (vector.y / CHUNK_SIZE as f32).floor() as i32,
-        (vector.z / CHUNK_SIZE as f32).floor() as i32,
-    );
-
-    (chunk_loc, inner_loc)
+pub fn global_f32_to_local_position(
+    vector: Vector3<f32>,
+    size: usize,
+) -> (Vector3<i32>, Vector3<usize>) {
+    global_to_local_position(vector.map(|v| v.floor() as i32), size)
 }
 
 #[inline]
-/// Returns true when a position moved by a direction is still within the 0-15 chunk boundaries
-pub fn check_chunk_boundaries(pos: Vector3<usize>, dir: Vector3<i32>) -> bool {
+/// Returns true when a position moved by a direction is still within a chunk `size` blocks wide
+pub fn check_chunk_boundaries(pos: Vector3<usize>, dir: Vector3<i32>, size: usize) -> bool {
     match (dir.x, dir.y, dir.z) {
-        (1, 0, 0) => pos.x < CHUNK_SIZE - 1,
+        (1, 0, 0) => pos.x < size - 1,
         (-1, 0, 0) => pos.x > 0,
-        (0, 1, 0) => pos.y < CHUNK_SIZE - 1,
+        (0, 1, 0) => pos.y < size - 1,
         (0, -1, 0) => pos.y > 0,
-        (0, 0, 1) => pos.z < CHUNK_SIZE - 1,
+        (0, 0, 1) => pos.z < size - 1,
         (0, 0, -1) => pos.z > 0,
 
         _ => panic!("Invalid direction"),
@@ -176,3 +182,51 @@ pub enum TextureSubdivisionMethod {
     Bottom,
     Full,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reach_includes_its_boundary() {
+        let position = Vector3::new(0.5, 0.5, 0.5);
+
+        // The block's center is exactly 5 away
+        assert!(within_reach(position, Vector3::new(3, 4, 0), 5.0));
+        assert!(within_reach(position, Vector3::new(-5, 0, 0), 5.0));
+    }
+
+    #[test]
+    fn blocks_beyond_reach_are_out_of_reach() {
+        let position = Vector3::new(0.5, 0.5, 0.5);
+
+        assert!(!within_reach(position, Vector3::new(3, 4, 0), 4.99));
+        assert!(!within_reach(position, Vector3::new(0, -6, 0), 5.0));
+    }
+
+    #[test]
+    fn reach_is_measured_to_the_block_center() {
+        // The near face of the block is within reach, but its center isn't
+        let position = Vector3::new(0.0, 0.5, 0.5);
+        assert!(!within_reach(position, Vector3::new(4, 0, 0), 4.0));
+        assert!(within_reach(position, Vector3::new(4, 0, 0), 4.5));
+    }
+
+    #[test]
+    fn positions_split_into_chunk_and_block() {
+        assert_eq!(
+            global_to_local_position(Vector3::new(17, 0, -1), 16),
+            (Vector3::new(1, 0, -1), Vector3::new(1, 0, 15))
+        );
+
+        // Sizes other than powers of two, on both sides of 0
+        assert_eq!(
+            global_to_local_position(Vector3::new(24, -1, -25), 24),
+            (Vector3::new(1, -1, -2), Vector3::new(0, 23, 23))
+        );
+        assert_eq!(
+            global_f32_to_local_position(Vector3::new(23.9, -0.5, -24.5), 24),
+            (Vector3::new(0, -1, -2), Vector3::new(23, 23, 23))
+        );
+    }
+}
@@ -1,5 +1,8 @@
+pub mod config;
+pub mod crash;
 pub mod error;
 pub mod game;
 pub mod helpers;
+pub mod logging;
 pub mod state;
 pub mod systems;
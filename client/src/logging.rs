@@ -0,0 +1,216 @@
+use crate::config::ClientConfig;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::prelude::*;
+
+/// The same filters bevy's LogPlugin is given for stdout
+const STDOUT_FILTER: &str = "debug,wgpu=error,rustcraft=debug,naga=error,bevy_app=info";
+
+const LOG_FILE_NAME: &str = "latest.log";
+
+/// How many rotated log files are kept alongside the latest one
+const KEPT_LOG_FILES: usize = 4;
+
+/// How many of the latest log lines are kept in memory for crash reports
+const RECENT_LINE_COUNT: usize = 100;
+
+static RECENT_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// The latest lines that were logged, oldest first
+pub fn recent_log_lines() -> Vec<String> {
+    match RECENT_LINES.lock() {
+        Ok(lines) => lines.iter().cloned().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Whether writing more to a log file would take it over its size limit
+pub fn needs_rotation(written: u64, incoming: u64, max_size: u64) -> bool {
+    written > 0 && written + incoming > max_size
+}
+
+/// A log file that's moved aside and started again once it gets too big
+pub struct RotatingFile {
+    directory: PathBuf,
+    max_size: u64,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    pub fn new(directory: &Path, max_size: u64) -> io::Result<RotatingFile> {
+        fs::create_dir_all(directory)?;
+
+        let path = directory.join(LOG_FILE_NAME);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+
+        Ok(RotatingFile {
+            directory: directory.to_path_buf(),
+            max_size,
+            file,
+            written,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        self.directory.join(format!("latest.{}.log", index))
+    }
+
+    /// Shifts every log file along by one, dropping the oldest, and starts a new file
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let _ = fs::remove_file(self.rotated_path(KEPT_LOG_FILES));
+        for i in (1..KEPT_LOG_FILES).rev() {
+            let _ = fs::rename(self.rotated_path(i), self.rotated_path(i + 1));
+        }
+        fs::rename(self.directory.join(LOG_FILE_NAME), self.rotated_path(1))?;
+
+        self.file = File::create(self.directory.join(LOG_FILE_NAME))?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if needs_rotation(self.written, buf.len() as u64, self.max_size) {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Hands log lines to a background thread to write, so logging never waits on the disk
+#[derive(Clone)]
+struct BackgroundWriter(Arc<Mutex<Sender<Vec<u8>>>>);
+
+impl Write for BackgroundWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = self.0.lock().unwrap().send(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Keeps the latest log lines for crash reports
+struct RecentLinesWriter;
+
+impl Write for RecentLinesWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Ok(mut lines) = RECENT_LINES.lock() {
+            if lines.len() == RECENT_LINE_COUNT {
+                lines.pop_front();
+            }
+            lines.push_back(String::from_utf8_lossy(buf).trim_end().to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Opens the log file and starts the thread that writes to it
+fn start_file_writer(config: &ClientConfig) -> Option<BackgroundWriter> {
+    let mut file = match RotatingFile::new(Path::new(&config.log_directory), config.log_file_size) {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("Failed to open log file, logging to stdout only: {:?}", err);
+            return None;
+        }
+    };
+
+    let (sender, receiver) = channel::<Vec<u8>>();
+    thread::spawn(move || {
+        for line in receiver {
+            let _ = file.write_all(&line);
+        }
+        let _ = file.flush();
+    });
+
+    Some(BackgroundWriter(Arc::new(Mutex::new(sender))))
+}
+
+/// Sets up logging to stdout, to memory for crash reports and, if enabled, to a rotating file.
+/// This replaces bevy's LogPlugin, which can't log anywhere but stdout
+pub fn setup_logging(config: &ClientConfig) {
+    let file_writer = if config.log_to_file {
+        start_file_writer(config)
+    } else {
+        None
+    };
+
+    let file_filter = format!("{},wgpu=error,naga=error", config.log_level);
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(EnvFilter::new(STDOUT_FILTER)))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(|| RecentLinesWriter)
+                .with_filter(EnvFilter::new(STDOUT_FILTER)),
+        )
+        .with(file_writer.map(|writer| {
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .with_filter(EnvFilter::new(file_filter))
+        }))
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_once_the_size_would_be_passed() {
+        assert!(!needs_rotation(0, 10, 100));
+        assert!(!needs_rotation(90, 10, 100));
+        assert!(needs_rotation(91, 10, 100));
+
+        // A line bigger than the limit still goes into an empty file rather than rotating forever
+        assert!(!needs_rotation(0, 500, 100));
+    }
+
+    #[test]
+    fn full_log_file_is_moved_aside() {
+        let directory = std::env::temp_dir().join(format!("rustcraft-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+
+        let mut file = RotatingFile::new(&directory, 10).unwrap();
+        file.write_all(b"first\n").unwrap();
+        file.write_all(b"second\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(directory.join("latest.1.log")).unwrap(),
+            "first\n"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join(LOG_FILE_NAME)).unwrap(),
+            "second\n"
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
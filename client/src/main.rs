@@ -1,14 +1,33 @@
+pub mod config;
+pub mod crash;
 pub mod error;
 pub mod game;
 pub mod helpers;
+pub mod logging;
 pub mod state;
 pub mod systems;
 
+use crate::config::load_config;
+use crate::crash::{install_panic_hook, record_gpu_info};
+use crate::game::ambient_sound::{
+    scan_ambient_blocks, update_ambient_sounds, AmbientSources, BlockAmbientSounds,
+};
 use crate::game::blocks::BlockStatesPlugin;
+use crate::game::interaction::clipboard::{clipboard_tool, Clipboard};
+use crate::game::interaction::mining::draw_break_cracks;
 use crate::game::interaction::mouse_interaction;
+use crate::game::interaction::prediction::{reconcile_block_edits, PredictedEdits};
+use crate::game::interaction::schematic::{place_schematic, SchematicPlacement};
+use crate::game::interaction::timing::{BreakProgress, SwingAnimation};
+use crate::game::interaction::undo::{
+    pause_history_while_connected, undo_block_edit, BlockEditHistory,
+};
 use crate::game::inventory::InventoryPlugin;
 use crate::game::item::states::ItemStates;
+use crate::game::sound::play_sounds;
+use crate::game::spawn::{spawn_point_keys, teleport_player};
 use crate::game::world::WorldPlugin;
+use crate::logging::setup_logging;
 use crate::state::AppState;
 use crate::systems::asset::atlas::resource_packs::ResourcePacks;
 use crate::systems::asset::atlas::ResourcePackData;
@@ -16,28 +35,38 @@ use crate::systems::asset::material::chunk::ChunkMaterial;
 use crate::systems::asset::parsing::json::JsonAssetLoader;
 use crate::systems::asset::parsing::pack::ResourcePackAssetLoader;
 use crate::systems::asset::AssetPlugin;
+use crate::systems::camera::display::{
+    monitor_selection, window_mode, window_resolution, MIN_RESOLUTION,
+};
+use crate::systems::camera::graphics::supported_msaa_samples;
 use crate::systems::camera::CameraPlugin;
 use crate::systems::chunk::ChunkPlugin;
+use crate::systems::dev_tools::DevToolsPlugin;
 use crate::systems::input::InputPlugin;
 use crate::systems::networking::NetworkingPlugin;
 use crate::systems::physics::PhysicsPlugin;
+use crate::systems::shutdown::ShutdownPlugin;
 use crate::systems::ui::UIPlugin;
-use bevy::log::{Level, LogPlugin};
+use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy::window::WindowResizeConstraints;
-use bevy_inspector_egui::{InspectorPlugin, WorldInspectorPlugin};
 use bevy_prototype_debug_lines::DebugLinesPlugin;
+use rc_networking::dispatch::{PacketHandlerAppExt, PacketKind};
 
 #[rustfmt::skip]
 fn main() {
-    
-    App::new()
-        .add_plugins(
-            DefaultPlugins
-            .set(LogPlugin {
-                filter: "wgpu=error,rustcraft=debug,naga=error,bevy_app=info".into(),
-                level: Level::DEBUG,
-            })
+    let config = load_config();
+    setup_logging(&config);
+    install_panic_hook();
+
+    let resolution = window_resolution(config.resolution);
+    // Centred on the chosen monitor, otherwise the platform decides where the window goes
+    let position = if config.monitor.is_some() {
+        WindowPosition::Centered
+    } else {
+        WindowPosition::Automatic
+    };
+    let plugins = DefaultPlugins
             .set(bevy::prelude::AssetPlugin {
                 watch_for_changes: true,
                 ..default()
@@ -46,33 +75,70 @@ fn main() {
             .set(WindowPlugin {
                 window: WindowDescriptor {
                     title: "app".to_string(),
-                    width: 1280.,
-                    height: 720.,
-                    position: WindowPosition::Automatic,
+                    width: resolution[0] as f32,
+                    height: resolution[1] as f32,
+                    position,
+                    monitor: monitor_selection(config.monitor),
+                    mode: window_mode(config.display_mode),
                     resize_constraints: WindowResizeConstraints {
-                        min_width: 256.0,
-                        min_height: 256.0,
+                        min_width: MIN_RESOLUTION as f32,
+                        min_height: MIN_RESOLUTION as f32,
                         max_width: 1920.0*8.0,
                         max_height: 1080.0*8.0,
                     },
                     ..default()
                 },
+                // Closing the window goes through the shutdown sequence instead
+                close_when_requested: false,
                 ..default()
-            }))
-        .add_plugin(WorldInspectorPlugin::new())
+            });
+
+    App::new()
+        // Logging is set up with our own subscriber instead
+        .add_plugins(plugins.disable::<LogPlugin>())
+        .add_plugin(DevToolsPlugin)
+
+        .insert_resource(Msaa { samples: supported_msaa_samples(config.msaa_samples) })
+        .insert_resource(config)
+        .add_startup_system(record_gpu_info)
         
         // add the app state 
         .add_state(AppState::Preloading)
         
         .add_plugin(DebugLinesPlugin::default())
-        
-        .insert_resource(Msaa { samples: 1 })
 
         // Networking
         .add_plugin(NetworkingPlugin)
         
         // Interaction
+        .insert_resource(SwingAnimation::default())
+        .insert_resource(BlockEditHistory::default())
+        .insert_resource(PredictedEdits::default())
+        .add_system(reconcile_block_edits)
+        .handle_packets(&[PacketKind::BlockEditAck])
+        .insert_resource(BreakProgress::default())
+        .add_system(
+            pause_history_while_connected
+                .before(mouse_interaction)
+                .before(place_schematic)
+                .before(clipboard_tool),
+        )
         .add_system(mouse_interaction)
+        .add_system(draw_break_cracks.after(mouse_interaction))
+        .add_system(undo_block_edit)
+        .insert_resource(SchematicPlacement::default())
+        .add_system(place_schematic)
+        .insert_resource(Clipboard::default())
+        .add_system(clipboard_tool)
+        .add_system(spawn_point_keys)
+        .add_system(teleport_player)
+        .handle_packets(&[PacketKind::Teleport])
+        .add_system(play_sounds)
+        .handle_packets(&[PacketKind::PlaySound])
+        .init_resource::<BlockAmbientSounds>()
+        .init_resource::<AmbientSources>()
+        .add_system(scan_ambient_blocks.before(update_ambient_sounds))
+        .add_system(update_ambient_sounds)
         
         // Chunk loading.rs
         .add_plugin(ChunkPlugin)
@@ -87,6 +153,8 @@ fn main() {
 
         .add_plugin(UIPlugin)
 
+        .add_plugin(ShutdownPlugin)
+
         .add_plugin(InventoryPlugin)
         
         .insert_resource(ItemStates::new())
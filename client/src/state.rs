@@ -5,4 +5,6 @@ pub enum AppState {
     MainMenu,
     Connecting,
     InGame,
+    /// Shown over the game after losing the connection, so it can be resumed by reconnecting
+    Disconnected,
 }
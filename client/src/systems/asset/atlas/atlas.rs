@@ -3,11 +3,128 @@ use crate::systems::asset::atlas::resource_packs::ResourcePack;
 use bevy::prelude::*;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
 use fnv::{FnvBuildHasher, FnvHashMap};
+use image::imageops::FilterType;
 use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
 use std::collections::HashMap;
+use thiserror::Error;
 
-pub const ATLAS_WIDTH: u32 = 4096 / 8;
-pub const ATLAS_HEIGHT: u32 = 4096 / 8;
+/// The smallest an atlas is on each side, so small packs don't end up with a sliver of an image
+pub const MIN_ATLAS_SIZE: u32 = 64;
+
+/// The size of an atlas image, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AtlasError {
+    #[error("texture `{0}` is wider than the largest atlas")]
+    TextureTooWide(String),
+    #[error("the textures need an atlas {0} pixels high, more than the largest atlas")]
+    TooManyTextures(u32),
+}
+
+/// How high an atlas of a width has to be to fit the textures, packing them into rows the same way the atlas is built.
+/// The textures have to be sorted tallest first, as each row is as tall as its first texture
+pub fn packed_height(textures: &[(String, DynamicImage)], width: u32) -> Result<u32, AtlasError> {
+    let mut height = 0;
+    let mut row_width = 0;
+    let mut row_height = 0;
+
+    for (name, texture) in textures {
+        if texture.width() > width {
+            return Err(AtlasError::TextureTooWide(name.clone()));
+        }
+
+        if row_width == 0 || row_width + texture.width() > width {
+            height += row_height;
+            row_width = 0;
+            row_height = texture.height();
+        }
+        row_width += texture.width();
+    }
+
+    Ok(height + row_height)
+}
+
+/// Picks the smallest square-ish atlas that fits the textures, no larger than the max size on either side.
+/// Sides are powers of two, and the width grows first so rows are as long as they can be
+pub fn choose_atlas_size(
+    textures: &[(String, DynamicImage)],
+    max_size: u32,
+) -> Result<AtlasSize, AtlasError> {
+    let max_size = max_size.max(MIN_ATLAS_SIZE);
+    let mut width = MIN_ATLAS_SIZE;
+
+    loop {
+        let needed = match packed_height(textures, width) {
+            Ok(val) => val,
+            // Too wide for this width, but a wider atlas might fit it
+            Err(_) if width < max_size => {
+                width = (width * 2).min(max_size);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+
+        let height = needed.next_power_of_two().max(MIN_ATLAS_SIZE);
+        if height <= width {
+            return Ok(AtlasSize { width, height });
+        }
+        if width >= max_size {
+            return if height <= max_size {
+                Ok(AtlasSize { width, height })
+            } else {
+                Err(AtlasError::TooManyTextures(needed))
+            };
+        }
+
+        width = (width * 2).min(max_size);
+    }
+}
+
+/// The largest an atlas can be on either side, the setting capped by what the graphics card can hold
+pub fn atlas_size_limit(max_atlas_size: u32, max_texture_dimension: u32) -> u32 {
+    max_atlas_size.min(max_texture_dimension)
+}
+
+/// The size a texture is scaled down to so neither side is over the max, keeping its aspect ratio.
+/// None if it already fits, or the max is 0 for no limit
+pub fn downscaled_size(width: u32, height: u32, max_size: u32) -> Option<(u32, u32)> {
+    if max_size == 0 || (width <= max_size && height <= max_size) {
+        return None;
+    }
+
+    let scale = max_size as f32 / width.max(height) as f32;
+    Some((
+        ((width as f32 * scale).round() as u32).clamp(1, max_size),
+        ((height as f32 * scale).round() as u32).clamp(1, max_size),
+    ))
+}
+
+/// Scales down textures larger than the max size, so high resolution packs still fit in the atlas at a lower quality
+/// rather than being left out
+pub fn downscale_textures(
+    textures: &mut HashMap<String, DynamicImage, FnvBuildHasher>,
+    max_size: u32,
+) {
+    for (name, texture) in textures.iter_mut() {
+        if let Some((width, height)) = downscaled_size(texture.width(), texture.height(), max_size)
+        {
+            warn!(
+                "Texture {} is {}x{}, scaling it down to {}x{}",
+                name,
+                texture.width(),
+                texture.height(),
+                width,
+                height
+            );
+            *texture = texture.resize_exact(width, height, FilterType::Triangle);
+        }
+    }
+}
 
 pub struct TextureAtlas {
     image: Handle<Image>,
@@ -20,6 +137,8 @@ impl TextureAtlas {
         _resource_pack: &ResourcePack,
         textures: &mut HashMap<String, DynamicImage, FnvBuildHasher>,
         assets: &mut ResMut<Assets<Image>>,
+        max_size: u32,
+        max_texture_size: u32,
     ) -> TextureAtlas {
         let mut atlas_index: HashMap<String, TextureAtlasIndex, FnvBuildHasher> =
             FnvHashMap::default();
@@ -27,15 +146,8 @@ impl TextureAtlas {
 
         // If reading cache didnt work then remake it
         if atlas_img.is_none() {
-            let mut textures = sort_textures(textures);
-
-            // Add error texture
-            textures.push((
-                String::from("game/error"),
-                DynamicImage::ImageRgba8(gen_invalid_texture()),
-            ));
-
-            let atlas = generate_atlas(textures, &mut atlas_index);
+            downscale_textures(textures, max_texture_size);
+            let atlas = pack_textures(textures, &mut atlas_index, max_size);
 
             // if settings.atlas_cache_writing {
             //     write_cached_atlas(
@@ -86,12 +198,43 @@ impl TextureAtlas {
     }
 }
 
+/// Packs textures, along with the error texture, into a single atlas image, adding where each ended up to the index.
+/// The atlas is no larger than the max size on either side, textures that don't fit are left out
+pub fn pack_textures(
+    textures: &mut HashMap<String, DynamicImage, FnvBuildHasher>,
+    atlas_index: &mut HashMap<String, TextureAtlasIndex, FnvBuildHasher>,
+    max_size: u32,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let mut textures = sort_textures(textures);
+
+    // Add error texture, kept in height order as each row is only as tall as its first texture
+    textures.push((
+        String::from("game/error"),
+        DynamicImage::ImageRgba8(gen_invalid_texture()),
+    ));
+    textures.sort_by(|(_, a), (_, b)| b.height().cmp(&a.height()));
+
+    let size = match choose_atlas_size(&textures, max_size) {
+        Ok(val) => val,
+        Err(err) => {
+            error!("Atlas too small! Not all textures could fit in: {}", err);
+            AtlasSize {
+                width: max_size,
+                height: max_size,
+            }
+        }
+    };
+
+    generate_atlas(textures, atlas_index, size)
+}
+
 fn generate_atlas(
     textures: Vec<(String, DynamicImage)>,
     atlas_index: &mut HashMap<String, TextureAtlasIndex, FnvBuildHasher>,
+    size: AtlasSize,
 ) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
     let mut atlas: ImageBuffer<Rgba<u8>, Vec<u8>> =
-        image::ImageBuffer::new(ATLAS_WIDTH, ATLAS_HEIGHT);
+        image::ImageBuffer::new(size.width, size.height);
 
     // Stores the ID of the lowest texture id on this row
     let mut texture_id = 0;
@@ -127,17 +270,17 @@ fn generate_atlas(
                 let (name, img) = textures.get(relative_texture_index + texture_id).unwrap();
                 let width = img.width();
 
-                if (row_width + width) <= ATLAS_WIDTH {
+                if (row_width + width) <= size.width {
                     texture_numbers_x.push(row_width + width - 1);
 
                     // Generate a list of locations that our textures exist inside of the src atlas texture. These are in the form 1/(X POS) because this is how it's expected in the shaders.
                     atlas_index.insert(
                         name.split('.').next().unwrap().to_string(),
                         TextureAtlasIndex::new(
-                            (row_width as f32) / ATLAS_WIDTH as f32,
-                            ((row_width + width) as f32) / ATLAS_WIDTH as f32,
-                            ((current_y + row_height - img.height()) as f32) / ATLAS_HEIGHT as f32,
-                            ((current_y + row_height) as f32) / ATLAS_HEIGHT as f32,
+                            (row_width as f32) / size.width as f32,
+                            ((row_width + width) as f32) / size.width as f32,
+                            ((current_y + row_height - img.height()) as f32) / size.height as f32,
+                            ((current_y + row_height) as f32) / size.height as f32,
                         ),
                     );
                 } else {
@@ -151,7 +294,7 @@ fn generate_atlas(
             // Update y
             current_y += row_height;
 
-            if current_y > ATLAS_HEIGHT {
+            if current_y > size.height {
                 error!("Atlas too small! Not all textures could fit in");
                 break;
             }
@@ -260,3 +403,168 @@ fn sort_textures(
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// A texture where every pixel is different, so each pixel in the atlas can only have come from one place
+    fn texture(index: usize, width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+            Rgba([index as u8, x as u8, y as u8, 255])
+        }))
+    }
+
+    proptest! {
+        /// Failing sets are shrunk, so the textures reported are the fewest and smallest that break packing
+        #[test]
+        fn textures_map_back_to_their_pixels(
+            sizes in prop::collection::vec((1u32..48, 1u32..48), 1..24)
+        ) {
+            let mut textures: HashMap<String, DynamicImage, FnvBuildHasher> = sizes
+                .iter()
+                .enumerate()
+                .map(|(i, (width, height))| {
+                    (format!("game/texture_{}", i), texture(i, *width, *height))
+                })
+                .collect();
+            let mut index = FnvHashMap::default();
+
+            let atlas = pack_textures(&mut textures, &mut index, 4096);
+            let (atlas_width, atlas_height) = (atlas.width() as f32, atlas.height() as f32);
+
+            for (i, (width, height)) in sizes.iter().enumerate() {
+                let name = format!("game/texture_{}", i);
+                let rect = index[&name];
+
+                let left = (rect.u_min * atlas_width).round() as u32;
+                let top = (rect.v_min * atlas_height).round() as u32;
+                let right = (rect.u_max * atlas_width).round() as u32;
+                let bottom = (rect.v_max * atlas_height).round() as u32;
+                prop_assert_eq!((right - left, bottom - top), (*width, *height), "size of {}", name);
+
+                for (x, y, pixel) in texture(i, *width, *height).pixels() {
+                    let packed = *atlas.get_pixel(left + x, top + y);
+                    prop_assert_eq!(packed, pixel, "pixel {},{} of {}", x, y, name);
+                }
+            }
+        }
+    }
+
+    fn textures(count: usize, width: u32, height: u32) -> Vec<(String, DynamicImage)> {
+        (0..count)
+            .map(|i| (format!("game/texture_{}", i), texture(i, width, height)))
+            .collect()
+    }
+
+    #[test]
+    fn atlas_is_capped_by_the_graphics_card() {
+        assert_eq!(atlas_size_limit(4096, 2048), 2048);
+        assert_eq!(atlas_size_limit(4096, 16384), 4096);
+    }
+
+    #[test]
+    fn rows_are_packed_to_the_width() {
+        assert_eq!(packed_height(&textures(10, 16, 16), 64), Ok(48));
+        assert_eq!(packed_height(&textures(10, 16, 16), 160), Ok(16));
+        assert_eq!(packed_height(&[], 64), Ok(0));
+    }
+
+    #[test]
+    fn small_packs_get_the_smallest_atlas() {
+        assert_eq!(
+            choose_atlas_size(&textures(3, 16, 16), 4096),
+            Ok(AtlasSize {
+                width: MIN_ATLAS_SIZE,
+                height: MIN_ATLAS_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn atlas_widens_before_growing_taller() {
+        // 16 textures a row at 256 wide, so 7 rows
+        assert_eq!(
+            choose_atlas_size(&textures(100, 16, 16), 4096),
+            Ok(AtlasSize {
+                width: 256,
+                height: 128
+            })
+        );
+    }
+
+    #[test]
+    fn wide_textures_widen_the_atlas() {
+        assert_eq!(
+            choose_atlas_size(&textures(1, 200, 16), 256),
+            Ok(AtlasSize {
+                width: 256,
+                height: MIN_ATLAS_SIZE
+            })
+        );
+        assert_eq!(
+            choose_atlas_size(&textures(1, 300, 16), 256),
+            Err(AtlasError::TextureTooWide("game/texture_0".to_string()))
+        );
+    }
+
+    #[test]
+    fn packs_larger_than_the_limit_are_reported() {
+        // 8 textures a row at the limit, so 13 rows
+        assert_eq!(
+            choose_atlas_size(&textures(100, 16, 16), 128),
+            Err(AtlasError::TooManyTextures(208))
+        );
+    }
+
+    #[test]
+    fn oversized_textures_are_scaled_to_the_cap() {
+        assert_eq!(downscaled_size(1024, 1024, 512), Some((512, 512)));
+        // Keeping their aspect ratio
+        assert_eq!(downscaled_size(2048, 512, 512), Some((512, 128)));
+        assert_eq!(downscaled_size(300, 900, 512), Some((171, 512)));
+        // Never scaled to nothing
+        assert_eq!(downscaled_size(4096, 1, 512), Some((512, 1)));
+    }
+
+    #[test]
+    fn textures_that_fit_are_left_alone() {
+        assert_eq!(downscaled_size(512, 512, 512), None);
+        assert_eq!(downscaled_size(16, 16, 512), None);
+        // 0 turns the cap off
+        assert_eq!(downscaled_size(8192, 8192, 0), None);
+    }
+
+    #[test]
+    fn downscaled_textures_are_packed_with_correct_uvs() {
+        let mut textures: HashMap<String, DynamicImage, FnvBuildHasher> = [
+            ("game/large".to_string(), texture(0, 1024, 512)),
+            ("game/small".to_string(), texture(1, 16, 16)),
+        ]
+        .into_iter()
+        .collect();
+
+        downscale_textures(&mut textures, 64);
+        assert_eq!(textures["game/large"].dimensions(), (64, 32));
+        assert_eq!(textures["game/small"].dimensions(), (16, 16));
+
+        let expected = textures.clone();
+        let mut index = FnvHashMap::default();
+        let atlas = pack_textures(&mut textures, &mut index, 256);
+        let (atlas_width, atlas_height) = (atlas.width() as f32, atlas.height() as f32);
+
+        for (name, image) in &expected {
+            let rect = index[name];
+            let left = (rect.u_min * atlas_width).round() as u32;
+            let top = (rect.v_min * atlas_height).round() as u32;
+            let right = (rect.u_max * atlas_width).round() as u32;
+            let bottom = (rect.v_max * atlas_height).round() as u32;
+            assert_eq!((right - left, bottom - top), image.dimensions(), "{}", name);
+
+            for (x, y, pixel) in image.pixels() {
+                assert_eq!(*atlas.get_pixel(left + x, top + y), pixel, "{}", name);
+            }
+        }
+    }
+}
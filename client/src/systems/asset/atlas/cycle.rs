@@ -0,0 +1,121 @@
+use crate::systems::asset::atlas::resource_packs::{next_pack_index, ResourcePacks};
+use crate::systems::asset::atlas::AtlasLoadingStage;
+use crate::systems::asset::AssetService;
+use crate::systems::ui::theme::UiTheme;
+use bevy::prelude::*;
+
+/// Debug key that switches to the next resource pack
+pub const CYCLE_PACK_KEY: KeyCode = KeyCode::F7;
+
+/// How long the name of the new pack is shown for
+const OVERLAY_SECONDS: f32 = 3.0;
+
+/// Moves on to the next of the packs, wrapping back round to the first, and starts rebuilding the atlas from it
+fn select_next_pack(service: &mut AssetService, stage: &mut AtlasLoadingStage, count: usize) {
+    service.selected_pack = next_pack_index(service.selected_pack, count);
+    service.reload_pack = true;
+    *stage = AtlasLoadingStage::AwaitingIndex;
+}
+
+pub fn cycle_resource_pack(
+    keys: Res<Input<KeyCode>>,
+    mut service: ResMut<AssetService>,
+    packs: Res<Assets<ResourcePacks>>,
+    mut stage: ResMut<AtlasLoadingStage>,
+    existing: Query<Entity, With<PackOverlayText>>,
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    theme: Res<UiTheme>,
+) {
+    if !keys.just_pressed(CYCLE_PACK_KEY) || *stage != AtlasLoadingStage::Done {
+        return;
+    }
+
+    let packs = match packs.get(&service.resource_packs) {
+        Some(val) => val,
+        None => return,
+    };
+
+    select_next_pack(&mut service, &mut stage, packs.len());
+
+    let name = packs.get(service.selected_pack).name.clone();
+    info!("Switching to resource pack {}", name);
+
+    // Replace the name of the previous pack if it's still showing
+    for entity in existing.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    commands.spawn((
+        TextBundle::from_section(
+            name,
+            TextStyle {
+                font: assets.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 24.0,
+                color: theme.overlay_text,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        PackOverlayText(OVERLAY_SECONDS),
+    ));
+}
+
+/// Shows the name of the pack that was just switched to, holding how much longer it's shown for
+#[derive(Component)]
+pub struct PackOverlayText(f32);
+
+/// Removes the pack name once it's been shown for long enough
+pub fn update_pack_overlay(
+    mut overlays: Query<(Entity, &mut PackOverlayText)>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    for (entity, mut overlay) in overlays.iter_mut() {
+        overlay.0 -= time.delta_seconds();
+        if overlay.0 <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_index_wraps_past_the_end() {
+        assert_eq!(next_pack_index(0, 3), 1);
+        assert_eq!(next_pack_index(1, 3), 2);
+        assert_eq!(next_pack_index(2, 3), 0);
+        assert_eq!(next_pack_index(0, 1), 0);
+        assert_eq!(next_pack_index(4, 0), 0);
+    }
+
+    #[test]
+    fn cycling_rebuilds_the_atlas() {
+        let mut service = AssetService {
+            resource_packs: Handle::default(),
+            texture_atlas: None,
+            pack: None,
+            opaque_texture_atlas_material: Handle::default(),
+            translucent_texture_atlas_material: Handle::default(),
+            selected_pack: 2,
+            reload_pack: false,
+        };
+        let mut stage = AtlasLoadingStage::Done;
+
+        select_next_pack(&mut service, &mut stage, 3);
+
+        assert_eq!(service.selected_pack, 0);
+        assert!(service.reload_pack);
+        assert_eq!(stage, AtlasLoadingStage::AwaitingIndex);
+    }
+}
@@ -1,11 +1,14 @@
-use crate::systems::asset::atlas::atlas::TextureAtlas;
+use crate::config::ClientConfig;
+use crate::systems::asset::atlas::atlas::{atlas_size_limit, TextureAtlas};
 use crate::systems::asset::atlas::resource_packs::ResourcePacks;
 use crate::systems::asset::material::chunk::ChunkMaterial;
 use crate::systems::asset::AssetService;
 
 use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
+use bevy::render::renderer::RenderDevice;
 
+use crate::game::blocks::states::BlockStates;
 use crate::systems::ui::loading::LoadingData;
 use fnv::FnvBuildHasher;
 use image::{DynamicImage, GenericImage};
@@ -13,6 +16,7 @@ use std::collections::HashMap;
 use std::ffi::OsString;
 
 pub mod atlas;
+pub mod cycle;
 pub mod index;
 pub mod resource_packs;
 
@@ -46,13 +50,14 @@ pub fn load_resource_zips(
     if *stage != AtlasLoadingStage::AwaitingIndex || packs.len() == 0 {
         return;
     }
-    if !packs.is_changed() {
+    if !packs.is_changed() && !service.reload_pack {
         return;
     }
+    service.reload_pack = false;
 
     let packs = packs.get(&service.resource_packs).unwrap();
 
-    let pack = packs.get_default();
+    let pack = packs.get(service.selected_pack);
 
     if !pack.path.extension().unwrap().eq(&OsString::from("pack")) {
         error!(
@@ -74,7 +79,10 @@ pub fn build_texture_atlas(
     mut stage: ResMut<AtlasLoadingStage>,
     mut images: ResMut<Assets<Image>>,
     mut materials: ResMut<Assets<ChunkMaterial>>,
-    mut loading: ResMut<LoadingData>,
+    loading: Option<ResMut<LoadingData>>,
+    mut states: ResMut<BlockStates>,
+    config: Res<ClientConfig>,
+    device: Res<RenderDevice>,
 ) {
     if *stage != AtlasLoadingStage::AwaitingPack
         || data.len() == 0
@@ -84,7 +92,10 @@ pub fn build_texture_atlas(
     }
 
     // Fetch the resources required to build the texture atlas
-    let pack = packs.get(&service.resource_packs).unwrap().get_default();
+    let pack = packs
+        .get(&service.resource_packs)
+        .unwrap()
+        .get(service.selected_pack);
     let textures = data.get_mut(service.pack.as_ref().unwrap());
 
     let textures = match textures {
@@ -92,8 +103,23 @@ pub fn build_texture_atlas(
         Some(val) => val,
     };
 
-    // Build the texture atlas
-    let atlas = TextureAtlas::new(pack, &mut textures.images, &mut images);
+    // Free the atlas being replaced
+    if let Some(previous) = &service.texture_atlas {
+        images.remove(previous.get_image());
+    }
+
+    // Build the texture atlas, no larger than the graphics card can hold
+    let max_size = atlas_size_limit(
+        config.max_atlas_size,
+        device.limits().max_texture_dimension_2d,
+    );
+    let atlas = TextureAtlas::new(
+        pack,
+        &mut textures.images,
+        &mut images,
+        max_size,
+        config.max_texture_size,
+    );
 
     info!("Generated texture atlas");
     service.texture_atlas = Some(atlas);
@@ -107,6 +133,8 @@ pub fn build_texture_atlas(
                 images.get_handle(service.texture_atlas.as_ref().unwrap().get_image()),
             ),
             alpha_mode: AlphaMode::Opaque,
+            clock: Default::default(),
+            fog: Default::default(),
         },
     );
 
@@ -118,9 +146,17 @@ pub fn build_texture_atlas(
                 images.get_handle(service.texture_atlas.as_ref().unwrap().get_image()),
             ),
             alpha_mode: AlphaMode::Blend,
+            clock: Default::default(),
+            fog: Default::default(),
         },
     );
 
     *stage = AtlasLoadingStage::Done;
-    loading.texture_atlas = true;
+
+    // Block states hold atlas indexes, so have to be rebuilt along with the atlas
+    states.recalculate = true;
+
+    if let Some(mut loading) = loading {
+        loading.texture_atlas = true;
+    }
 }
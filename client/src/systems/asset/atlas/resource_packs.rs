@@ -18,6 +18,15 @@ impl ResourcePacks {
     pub fn get_default(&self) -> &ResourcePack {
         self.list.get(0).unwrap()
     }
+
+    /// Gets a pack by its index in the list, falling back to the default pack
+    pub fn get(&self, index: usize) -> &ResourcePack {
+        self.list.get(index).unwrap_or_else(|| self.get_default())
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
 }
 
 impl Default for ResourcePacks {
@@ -38,3 +47,11 @@ pub struct ResourcePack {
     pub name: String,
     pub path: PathBuf,
 }
+
+/// The index of the pack after the current one, wrapping back to the first pack after the last
+pub fn next_pack_index(current: usize, count: usize) -> usize {
+    if count == 0 {
+        return 0;
+    }
+    (current + 1) % count
+}
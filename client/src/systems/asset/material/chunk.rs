@@ -1,8 +1,14 @@
-use crate::systems::chunk::builder::ATTRIBUTE_LIGHTING_COLOR;
+use crate::systems::chunk::builder::{ATTRIBUTE_LIGHTING_COLOR, ATTRIBUTE_TEXTURE_RECT};
+use crate::systems::chunk::mesh::vertex::{
+    ATTRIBUTE_PACKED_LIGHTING, ATTRIBUTE_PACKED_NORMAL, ATTRIBUTE_PACKED_POSITION,
+    ATTRIBUTE_PACKED_TEXTURE_RECT, ATTRIBUTE_PACKED_UV, PACKED_VERTICES,
+};
 use bevy::pbr::{MaterialPipeline, MaterialPipelineKey};
 use bevy::prelude::*;
 use bevy::render::mesh::MeshVertexBufferLayout;
-use bevy::render::render_resource::{RenderPipelineDescriptor, SpecializedMeshPipelineError};
+use bevy::render::render_resource::{
+    RenderPipelineDescriptor, ShaderType, SpecializedMeshPipelineError,
+};
 use bevy::{
     reflect::TypeUuid,
     render::render_resource::{AsBindGroup, ShaderRef},
@@ -18,6 +24,26 @@ pub struct ChunkMaterial {
     #[sampler(2)]
     pub color_texture: Option<Handle<Image>>,
     pub alpha_mode: AlphaMode,
+    #[uniform(3)]
+    pub clock: RenderClockUniform,
+    #[uniform(4)]
+    pub fog: FogUniform,
+}
+
+/// The render clock as seen by shaders
+#[derive(ShaderType, Debug, Clone, Copy, Default)]
+pub struct RenderClockUniform {
+    pub seconds: f32,
+    pub tick: u32,
+}
+
+/// How chunks fade into the distance. There's no fog when the end isn't past the start
+#[derive(ShaderType, Debug, Clone, Copy, Default, PartialEq)]
+pub struct FogUniform {
+    pub color: Vec4,
+    /// The distances, in blocks, where fog starts and where it completely covers chunks
+    pub start: f32,
+    pub end: f32,
 }
 
 impl Material for ChunkMaterial {
@@ -38,12 +64,29 @@ impl Material for ChunkMaterial {
         layout: &MeshVertexBufferLayout,
         key: MaterialPipelineKey<Self>,
     ) -> Result<(), SpecializedMeshPipelineError> {
-        let vertex_layout = layout.get_layout(&[
-            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
-            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
-            Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
-            ATTRIBUTE_LIGHTING_COLOR.at_shader_location(3),
-        ])?;
+        let vertex_layout = if PACKED_VERTICES {
+            // The shader unpacks these itself
+            descriptor
+                .vertex
+                .shader_defs
+                .push(String::from("PACKED_VERTICES"));
+
+            layout.get_layout(&[
+                ATTRIBUTE_PACKED_POSITION.at_shader_location(0),
+                ATTRIBUTE_PACKED_NORMAL.at_shader_location(1),
+                ATTRIBUTE_PACKED_UV.at_shader_location(2),
+                ATTRIBUTE_PACKED_LIGHTING.at_shader_location(3),
+                ATTRIBUTE_PACKED_TEXTURE_RECT.at_shader_location(4),
+            ])?
+        } else {
+            layout.get_layout(&[
+                Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+                Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+                Mesh::ATTRIBUTE_UV_0.at_shader_location(2),
+                ATTRIBUTE_LIGHTING_COLOR.at_shader_location(3),
+                ATTRIBUTE_TEXTURE_RECT.at_shader_location(4),
+            ])?
+        };
         descriptor.vertex.buffers = vec![vertex_layout];
         Ok(())
     }
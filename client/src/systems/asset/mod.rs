@@ -1,5 +1,6 @@
 use crate::state::AppState;
 use crate::systems::asset::atlas::atlas::TextureAtlas;
+use crate::systems::asset::atlas::cycle::{cycle_resource_pack, update_pack_overlay};
 use crate::systems::asset::atlas::resource_packs::ResourcePacks;
 use crate::systems::asset::atlas::{
     build_texture_atlas, load_resource_zips, AtlasLoadingStage, ResourcePackData,
@@ -18,8 +19,12 @@ impl Plugin for AssetPlugin {
         app.insert_resource(AtlasLoadingStage::AwaitingIndex)
             .add_startup_system(create_asset_service)
             .add_system(load_resource_zips)
+            // Runs outside of loading too so packs can be swapped in game
+            .add_system(build_texture_atlas)
             .add_system_set(
-                SystemSet::on_update(AppState::Loading).with_system(build_texture_atlas),
+                SystemSet::on_update(AppState::InGame)
+                    .with_system(cycle_resource_pack)
+                    .with_system(update_pack_overlay),
             );
     }
 }
@@ -31,6 +36,10 @@ pub struct AssetService {
     pack: Option<Handle<ResourcePackData>>,
     pub opaque_texture_atlas_material: Handle<ChunkMaterial>,
     pub translucent_texture_atlas_material: Handle<ChunkMaterial>,
+    /// The index of the resource pack in use
+    pub selected_pack: usize,
+    /// Set to load the selected pack again and rebuild the atlas from it
+    pub reload_pack: bool,
 }
 
 impl AssetService {
@@ -39,11 +48,15 @@ impl AssetService {
             color: Color::GRAY,
             color_texture: None,
             alpha_mode: Default::default(),
+            clock: Default::default(),
+            fog: Default::default(),
         });
         let translucent_texture_atlas_material = materials.add(ChunkMaterial {
             color: Color::GRAY,
             color_texture: None,
             alpha_mode: Default::default(),
+            clock: Default::default(),
+            fog: Default::default(),
         });
 
         AssetService {
@@ -52,6 +65,8 @@ impl AssetService {
             pack: None,
             opaque_texture_atlas_material,
             translucent_texture_atlas_material,
+            selected_pack: 0,
+            reload_pack: false,
         }
     }
 }
@@ -0,0 +1,157 @@
+use crate::config::ClientConfig;
+use crate::game::player::Player;
+use crate::systems::camera::panorama::PanoramaCapture;
+use crate::systems::input::spectator::SpectatorMode;
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+/// How far, in blocks, the player moves for each step, a full bob being two steps
+const STRIDE_LENGTH: f32 = 1.4;
+
+/// How far the camera moves up and to the side, in blocks, at an intensity of 1
+const BOB_HEIGHT: f32 = 0.06;
+const BOB_SWAY: f32 = 0.04;
+
+/// How quickly bobbing fades in and out as the player starts and stops walking, per second
+const BOB_FADE_RATE: f32 = 6.0;
+
+/// The highest intensity allowed, so the camera can't be thrown around
+pub const MAX_BOB_INTENSITY: f32 = 2.0;
+
+/// How far the bob phase moves on after walking a distance, in radians
+pub fn advance_bob_phase(phase: f32, distance: f32) -> f32 {
+    (phase + distance / STRIDE_LENGTH * PI) % (2.0 * PI)
+}
+
+/// The camera's offset to the right and up for a point in the bob.
+/// The camera rises on each step, so it's up twice for every sway from side to side
+pub fn bob_offset(phase: f32, intensity: f32) -> Vec2 {
+    let intensity = intensity.clamp(0.0, MAX_BOB_INTENSITY);
+    Vec2::new(
+        phase.sin() * BOB_SWAY * intensity,
+        -(phase * 2.0).cos() * 0.5 * BOB_HEIGHT * intensity,
+    )
+}
+
+/// The walking bob applied on top of the player's position, only moving the camera and never the player
+#[derive(Resource, Default)]
+pub struct ViewBob {
+    phase: f32,
+    /// How much of the bob is applied, fading between 0 when still and 1 when walking
+    amount: f32,
+    last_position: Option<Vec3>,
+}
+
+pub fn update_view_bob(
+    mut bob: ResMut<ViewBob>,
+    config: Res<ClientConfig>,
+    spectator: Res<SpectatorMode>,
+    panorama: Res<PanoramaCapture>,
+    player: Query<&Transform, With<Player>>,
+    mut camera: Query<&mut Transform, (With<Camera>, Without<Player>)>,
+    time: Res<Time>,
+) {
+    // The camera moves on its own while spectating or capturing a panorama
+    if spectator.active || panorama.active {
+        bob.last_position = None;
+        return;
+    }
+
+    let (player, mut camera) = match (player.get_single(), camera.get_single_mut()) {
+        (Ok(player), Ok(camera)) => (player, camera),
+        _ => return,
+    };
+
+    let movement = player.translation - bob.last_position.unwrap_or(player.translation);
+    bob.last_position = Some(player.translation);
+
+    // The player flies, so only bob when walking along the ground
+    let walking_distance = Vec2::new(movement.x, movement.z).length();
+    let walking = config.view_bobbing && walking_distance > 0.0 && movement.y == 0.0;
+
+    bob.phase = advance_bob_phase(bob.phase, walking_distance);
+
+    let target = if walking { 1.0 } else { 0.0 };
+    let fade = (BOB_FADE_RATE * time.delta_seconds()).min(1.0);
+    bob.amount += (target - bob.amount) * fade;
+
+    let intensity = config.view_bobbing_intensity * config.motion_scale();
+    let offset = bob_offset(bob.phase, intensity * bob.amount);
+    camera.translation = player.translation + player.right() * offset.x + player.up() * offset.y;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_follows_distance_walked() {
+        // Long enough at walking speed to take one step
+        let speed = 4.0;
+        let time = STRIDE_LENGTH / speed;
+        assert!((advance_bob_phase(0.0, speed * time) - PI).abs() < 1e-5);
+
+        // Twice as fast for a quarter of the time is half a step
+        assert!((advance_bob_phase(0.0, speed * 2.0 * time / 4.0) - PI / 2.0).abs() < 1e-5);
+
+        // Two steps make a full bob, coming back around
+        assert!((advance_bob_phase(0.5, STRIDE_LENGTH * 2.0) - 0.5).abs() < 1e-5);
+        assert_eq!(advance_bob_phase(1.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn offset_rises_on_each_step() {
+        let low = bob_offset(0.0, 1.0);
+        assert!(low.abs_diff_eq(Vec2::new(0.0, -0.5 * BOB_HEIGHT), 1e-5));
+
+        // Swayed fully to one side between steps, at the top of the bob
+        let high = bob_offset(PI / 2.0, 1.0);
+        assert!(high.abs_diff_eq(Vec2::new(BOB_SWAY, 0.5 * BOB_HEIGHT), 1e-5));
+
+        let other_side = bob_offset(PI * 1.5, 1.0);
+        assert!(other_side.abs_diff_eq(Vec2::new(-BOB_SWAY, 0.5 * BOB_HEIGHT), 1e-5));
+    }
+
+    #[test]
+    fn offset_scales_with_intensity() {
+        let phase = PI / 2.0;
+
+        assert_eq!(bob_offset(phase, 0.0), Vec2::ZERO);
+        assert!(bob_offset(phase, 0.5).abs_diff_eq(bob_offset(phase, 1.0) * 0.5, 1e-5));
+        assert_eq!(
+            bob_offset(phase, MAX_BOB_INTENSITY * 10.0),
+            bob_offset(phase, MAX_BOB_INTENSITY)
+        );
+    }
+
+    /// Where the camera ends up mid bob, relative to the player
+    fn camera_offset(reduced_motion: bool) -> Vec3 {
+        let mut world = World::new();
+        world.insert_resource(ViewBob {
+            phase: PI / 2.0,
+            amount: 1.0,
+            last_position: None,
+        });
+        world.insert_resource(ClientConfig {
+            reduced_motion,
+            ..default()
+        });
+        world.init_resource::<SpectatorMode>();
+        world.init_resource::<PanoramaCapture>();
+        world.init_resource::<Time>();
+        world.spawn((Transform::from_xyz(5.0, 20.0, 5.0), Player::new()));
+        let camera = world.spawn((Transform::default(), Camera::default())).id();
+
+        SystemStage::single_threaded()
+            .with_system(update_view_bob)
+            .run(&mut world);
+
+        world.get::<Transform>(camera).unwrap().translation - Vec3::new(5.0, 20.0, 5.0)
+    }
+
+    #[test]
+    fn reduced_motion_keeps_the_camera_on_the_player() {
+        assert_ne!(camera_offset(false), Vec3::ZERO);
+        assert_eq!(camera_offset(true), Vec3::ZERO);
+    }
+}
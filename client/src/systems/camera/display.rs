@@ -0,0 +1,205 @@
+use crate::config::{ClientConfig, DisplayMode};
+use bevy::prelude::*;
+use bevy::window::{MonitorSelection, WindowMode};
+use bevy::winit::WinitWindows;
+
+/// Key that switches to the next display mode
+pub const DISPLAY_MODE_KEY: KeyCode = KeyCode::F11;
+
+/// The smallest the window can be on each side, matching its resize constraints
+pub const MIN_RESOLUTION: u32 = 256;
+
+/// The window size used when the one in the settings is too small
+pub const FALLBACK_RESOLUTION: [u32; 2] = [1280, 720];
+
+pub fn window_mode(mode: DisplayMode) -> WindowMode {
+    match mode {
+        DisplayMode::Windowed => WindowMode::Windowed,
+        DisplayMode::Borderless => WindowMode::BorderlessFullscreen,
+        // Sized so the monitor switches to the resolution in the settings rather than its largest
+        DisplayMode::Fullscreen => WindowMode::SizedFullscreen,
+    }
+}
+
+pub fn monitor_selection(monitor: Option<usize>) -> MonitorSelection {
+    monitor.map_or(MonitorSelection::Primary, MonitorSelection::Index)
+}
+
+/// The resolution in the settings, or the fallback if it's smaller than the window can be
+pub fn window_resolution(resolution: [u32; 2]) -> [u32; 2] {
+    if resolution.iter().any(|v| *v < MIN_RESOLUTION) {
+        warn!(
+            "Resolution {}x{} is too small, using {}x{}",
+            resolution[0], resolution[1], FALLBACK_RESOLUTION[0], FALLBACK_RESOLUTION[1]
+        );
+        return FALLBACK_RESOLUTION;
+    }
+    resolution
+}
+
+/// The resolution to use in exclusive fullscreen: the one wanted if the monitor supports it, otherwise the supported
+/// one closest in size. None if the monitor doesn't list what it supports, leaving it to the platform to pick
+pub fn fullscreen_resolution(wanted: [u32; 2], supported: &[[u32; 2]]) -> Option<[u32; 2]> {
+    if supported.contains(&wanted) {
+        return Some(wanted);
+    }
+
+    supported
+        .iter()
+        .copied()
+        .min_by_key(|[width, height]| width.abs_diff(wanted[0]) + height.abs_diff(wanted[1]))
+}
+
+/// The settings the window was last set up with
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AppliedDisplay {
+    mode: DisplayMode,
+    monitor: Option<usize>,
+    resolution: [u32; 2],
+}
+
+impl AppliedDisplay {
+    fn from_config(config: &ClientConfig) -> AppliedDisplay {
+        AppliedDisplay {
+            mode: config.display_mode,
+            monitor: config.monitor,
+            resolution: config.resolution,
+        }
+    }
+}
+
+pub fn cycle_display_mode(keys: Res<Input<KeyCode>>, mut config: ResMut<ClientConfig>) {
+    if !keys.just_pressed(DISPLAY_MODE_KEY) {
+        return;
+    }
+
+    config.display_mode = config.display_mode.next();
+    info!("Display mode: {:?}", config.display_mode);
+}
+
+/// Moves, resizes and changes the mode of the window when the display settings change. The window is created with
+/// the settings at startup, so this only acts on changes after that. The renderer reconfigures its surface to match
+/// the window on its own
+pub fn apply_display_settings(
+    config: Res<ClientConfig>,
+    mut windows: ResMut<Windows>,
+    winit_windows: NonSend<WinitWindows>,
+    mut applied: Local<Option<AppliedDisplay>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    let wanted = AppliedDisplay::from_config(&config);
+    let previous = match *applied {
+        Some(val) => val,
+        None => {
+            *applied = Some(wanted);
+            return;
+        }
+    };
+    if previous == wanted {
+        return;
+    }
+    *applied = Some(wanted);
+
+    let window = match windows.get_primary_mut() {
+        Some(val) => val,
+        None => return,
+    };
+
+    let mut resolution = window_resolution(wanted.resolution);
+
+    if wanted.mode == DisplayMode::Fullscreen {
+        // The modes of the monitor the window is going to, not the one it's on now
+        let monitor = winit_windows
+            .get_window(window.id())
+            .and_then(|v| match wanted.monitor {
+                Some(index) => v.available_monitors().nth(index),
+                None => v.primary_monitor(),
+            });
+        let supported = monitor.map_or(Vec::new(), |v| {
+            v.video_modes()
+                .map(|mode| [mode.size().width, mode.size().height])
+                .collect()
+        });
+
+        if let Some(val) = fullscreen_resolution(resolution, &supported) {
+            if val != resolution {
+                warn!(
+                    "The monitor doesn't support {}x{} in fullscreen, using {}x{}",
+                    resolution[0], resolution[1], val[0], val[1]
+                );
+            }
+            resolution = val;
+        }
+    }
+
+    // Moved first, as fullscreen covers whichever monitor the window is on
+    if wanted.monitor != previous.monitor {
+        window.center_window(monitor_selection(wanted.monitor));
+    }
+    window.set_resolution(resolution[0] as f32, resolution[1] as f32);
+    window.set_mode(window_mode(wanted.mode));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_resolution_keeps_usable_sizes() {
+        assert_eq!(window_resolution([1920, 1080]), [1920, 1080]);
+        assert_eq!(
+            window_resolution([MIN_RESOLUTION, MIN_RESOLUTION]),
+            [MIN_RESOLUTION, MIN_RESOLUTION]
+        );
+    }
+
+    #[test]
+    fn window_resolution_falls_back_when_too_small() {
+        assert_eq!(window_resolution([100, 1080]), FALLBACK_RESOLUTION);
+        assert_eq!(window_resolution([1920, 0]), FALLBACK_RESOLUTION);
+    }
+
+    #[test]
+    fn fullscreen_uses_wanted_resolution_when_supported() {
+        let supported = [[1280, 720], [1920, 1080], [2560, 1440]];
+        assert_eq!(
+            fullscreen_resolution([1920, 1080], &supported),
+            Some([1920, 1080])
+        );
+    }
+
+    #[test]
+    fn fullscreen_falls_back_to_closest_supported() {
+        let supported = [[1280, 720], [1920, 1080], [2560, 1440]];
+
+        assert_eq!(
+            fullscreen_resolution([1900, 1000], &supported),
+            Some([1920, 1080])
+        );
+        assert_eq!(
+            fullscreen_resolution([4000, 3000], &supported),
+            Some([2560, 1440])
+        );
+        assert_eq!(
+            fullscreen_resolution([640, 480], &supported),
+            Some([1280, 720])
+        );
+    }
+
+    #[test]
+    fn fullscreen_leaves_it_to_the_platform_without_modes() {
+        assert_eq!(fullscreen_resolution([1920, 1080], &[]), None);
+    }
+
+    #[test]
+    fn monitor_defaults_to_primary() {
+        assert!(matches!(monitor_selection(None), MonitorSelection::Primary));
+        assert!(matches!(
+            monitor_selection(Some(1)),
+            MonitorSelection::Index(1)
+        ));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::config::ClientConfig;
+use bevy::core_pipeline::bloom::BloomSettings;
+use bevy::prelude::*;
+
+/// Key that switches to the next graphics preset
+pub const GRAPHICS_PRESET_KEY: KeyCode = KeyCode::F10;
+
+/// The renderer only supports either no multisampling or 4 samples
+pub fn supported_msaa_samples(samples: u32) -> u32 {
+    if samples >= 4 {
+        4
+    } else {
+        1
+    }
+}
+
+pub fn cycle_graphics_preset(keys: Res<Input<KeyCode>>, mut config: ResMut<ClientConfig>) {
+    if !keys.just_pressed(GRAPHICS_PRESET_KEY) {
+        return;
+    }
+
+    let preset = config.graphics_preset().next();
+    config.apply_preset(preset);
+    info!("Graphics preset: {:?}", preset);
+}
+
+/// Rebuilds the render pipelines with the new sample count when it's changed, and turns bloom on or off.
+/// Bloom needs the camera to draw in HDR, so that's switched along with it
+pub fn apply_graphics_settings(
+    mut commands: Commands,
+    config: Res<ClientConfig>,
+    mut msaa: ResMut<Msaa>,
+    mut cameras: Query<(Entity, &mut Camera, Option<&BloomSettings>), With<Camera3d>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    let samples = supported_msaa_samples(config.msaa_samples);
+    if msaa.samples != samples {
+        msaa.samples = samples;
+    }
+
+    for (entity, mut camera, bloom) in cameras.iter_mut() {
+        if camera.hdr != config.bloom {
+            camera.hdr = config.bloom;
+        }
+
+        match (config.bloom, bloom.is_some()) {
+            (true, false) => {
+                commands.entity(entity).insert(BloomSettings::default());
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<BloomSettings>();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    fn apply(world: &mut World) {
+        SystemStage::single_threaded()
+            .with_system(apply_graphics_settings)
+            .run(world);
+    }
+
+    #[test]
+    fn bloom_draws_the_camera_in_hdr() {
+        let mut world = World::new();
+        world.insert_resource(Msaa::default());
+        world.insert_resource(ClientConfig {
+            bloom: true,
+            msaa_samples: 4,
+            ..ClientConfig::default()
+        });
+        let camera = world.spawn(Camera3dBundle::default()).id();
+
+        apply(&mut world);
+        assert!(world.get::<Camera>(camera).unwrap().hdr);
+        assert!(world.get::<BloomSettings>(camera).is_some());
+        assert_eq!(world.resource::<Msaa>().samples, 4);
+
+        world.resource_mut::<ClientConfig>().bloom = false;
+        apply(&mut world);
+        assert!(!world.get::<Camera>(camera).unwrap().hdr);
+        assert!(world.get::<BloomSettings>(camera).is_none());
+    }
+}
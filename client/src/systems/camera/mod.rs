@@ -1,5 +1,12 @@
+use crate::config::ClientConfig;
 use crate::game::entity::Entity;
 use crate::game::player::Player;
+use crate::systems::camera::bobbing::{update_view_bob, ViewBob};
+use crate::systems::camera::display::{apply_display_settings, cycle_display_mode};
+use crate::systems::camera::graphics::{apply_graphics_settings, cycle_graphics_preset};
+use crate::systems::camera::panorama::{update_panorama_capture, PanoramaCapture, PanoramaPlugin};
+use crate::systems::camera::viewmodel::{spawn_viewmodel, update_viewmodel};
+use crate::systems::input::spectator::SpectatorMode;
 use crate::systems::physics::aabb::Aabb;
 use crate::systems::physics::PhysicsObject;
 use bevy::core_pipeline::clear_color::ClearColorConfig;
@@ -7,23 +14,37 @@ use bevy::core_pipeline::core_3d::Camera3dDepthLoadOp;
 use bevy::prelude::*;
 use nalgebra::Vector3;
 
+pub mod bobbing;
+pub mod display;
+pub mod graphics;
+pub mod panorama;
+pub mod viewmodel;
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup_camera)
-            .add_system(camera_player_sync);
+        app.add_plugin(PanoramaPlugin)
+            .add_startup_system(setup_camera)
+            .insert_resource(ViewBob::default())
+            .add_system(camera_player_sync)
+            .add_system(update_view_bob.after(camera_player_sync))
+            .add_system(update_panorama_capture.after(camera_player_sync))
+            .add_system(update_viewmodel)
+            .add_system(cycle_graphics_preset)
+            .add_system(apply_graphics_settings.after(cycle_graphics_preset))
+            .add_system(cycle_display_mode)
+            .add_system(apply_display_settings.after(cycle_display_mode));
     }
 }
 
-fn setup_camera(mut commands: Commands) {
-    let player_physics = PhysicsObject::new(
-        Vector3::new(0.0, 40.0, 0.0),
-        vec![Aabb::new(
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector3::new(1.0, 1.0, 1.0),
-        )],
-    );
+fn setup_camera(
+    mut commands: Commands,
+    config: Res<ClientConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let player_physics = PhysicsObject::new(Vector3::new(0.0, 40.0, 0.0), vec![Aabb::player()]);
     let start_transform = Transform::from_translation(Vec3::new(
         player_physics.position.x,
         player_physics.position.y,
@@ -31,15 +52,25 @@ fn setup_camera(mut commands: Commands) {
     ));
 
     // Spawn camera
-    commands.spawn(Camera3dBundle {
-        transform: start_transform,
-        camera_3d: Camera3d {
-            /// The clear color operation to perform for the main 3d pass.
-            clear_color: ClearColorConfig::Custom(Color::rgba(0.7137, 0.7803, 0.8784, 1.0)),
-            depth_load_op: Camera3dDepthLoadOp::Clear(0.0),
-        },
-        ..default()
-    });
+    commands
+        .spawn(Camera3dBundle {
+            transform: start_transform,
+            camera_3d: Camera3d {
+                /// The clear color operation to perform for the main 3d pass.
+                clear_color: ClearColorConfig::Custom(Color::rgba(0.7137, 0.7803, 0.8784, 1.0)),
+                depth_load_op: Camera3dDepthLoadOp::Clear(0.0),
+            },
+            projection: Projection::Perspective(PerspectiveProjection {
+                near: config.near_plane,
+                // The skybox has to fit within the far plane
+                far: config.far_plane,
+                ..default()
+            }),
+            ..default()
+        })
+        // Lets the UI be hidden while capturing a panorama
+        .insert(UiCameraConfig::default())
+        .with_children(|parent| spawn_viewmodel(parent, &mut meshes, &mut materials));
 
     // Spawn player
     // Todo: Move this elsewhere
@@ -55,7 +86,13 @@ fn camera_player_sync(
         Query<&mut Transform, (With<Transform>, &Camera)>,
         Query<&Transform, (With<Player>, Changed<Transform>)>,
     )>,
+    spectator: Res<SpectatorMode>,
+    panorama: Res<PanoramaCapture>,
 ) {
+    // The camera moves on its own while spectating or capturing a panorama
+    if spectator.active || panorama.active {
+        return;
+    }
     if query.p0().is_empty() {
         return;
     }
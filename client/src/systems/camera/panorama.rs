@@ -0,0 +1,505 @@
+use crate::config::ClientConfig;
+use crate::helpers::global_f32_to_local_position;
+use crate::systems::chunk::ChunkSystem;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{
+    Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+    ImageDataLayout, MapMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::{RenderApp, RenderStage};
+use nalgebra::Vector3;
+use std::f32::consts::FRAC_PI_2;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Key that starts a panorama capture
+pub const PANORAMA_KEY: KeyCode = KeyCode::F9;
+
+/// How many frames each face is held for once the world has finished loading
+const FRAMES_PER_FACE: u32 = 10;
+
+/// The six faces of the panorama cubemap, in the order they're captured.
+/// Each is the direction the camera looks in and the direction of up on screen
+pub const PANORAMA_FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::NEG_Z, Vec3::Y),
+    (Vec3::X, Vec3::Y),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+];
+
+/// The camera rotation for a face of the panorama
+pub fn panorama_rotation(face: usize) -> Quat {
+    let (forward, up) = PANORAMA_FACES[face];
+    Transform::IDENTITY.looking_at(forward, up).rotation
+}
+
+/// Where a face of the panorama is saved
+pub fn panorama_path(directory: &Path, face: usize) -> PathBuf {
+    directory.join(format!("panorama_{}.png", face))
+}
+
+/// Rows copied out of a texture have to be padded to a multiple of 256 bytes
+pub fn padded_row_size(row: usize) -> usize {
+    RenderDevice::align_copy_bytes_per_row(row)
+}
+
+/// Strips the padding from the end of each row copied out of a texture
+pub fn unpad_rows(data: &[u8], row: usize, padded_row: usize) -> Vec<u8> {
+    data.chunks(padded_row)
+        .flat_map(|v| &v[..row])
+        .copied()
+        .collect()
+}
+
+/// The square image each face is drawn into before being read back
+fn panorama_image(size: u32) -> Image {
+    let size = Extent3d {
+        width: size,
+        height: size,
+        depth_or_array_layers: 1,
+    };
+
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("panorama"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+        },
+        ..default()
+    };
+    image.resize(size);
+    image
+}
+
+/// The camera before capturing started, restored afterwards
+struct PreviousCamera {
+    rotation: Quat,
+    fov: f32,
+    target: RenderTarget,
+}
+
+/// Steps the camera through the faces of a cubemap, drawing each into an image that's read back and saved.
+/// Simulation is paused while capturing so every face shows the same moment
+#[derive(Resource, Default)]
+pub struct PanoramaCapture {
+    pub active: bool,
+    face: usize,
+    held_frames: u32,
+    /// When the current face started waiting for the world to load
+    waiting_since: Option<Instant>,
+    /// Where the faces of this capture are saved
+    directory: PathBuf,
+    previous: Option<PreviousCamera>,
+}
+
+/// A face of the panorama read back from the graphics card, as rows of RGBA pixels
+struct PanoramaFace {
+    face: usize,
+    size: u32,
+    pixels: Vec<u8>,
+}
+
+/// Asks the render world to read back the face drawn this frame. Copied into the render world whenever it changes
+#[derive(Resource, Clone)]
+pub struct PanoramaReadback {
+    image: Option<Handle<Image>>,
+    face: Option<usize>,
+    sender: Sender<PanoramaFace>,
+}
+
+impl ExtractResource for PanoramaReadback {
+    type Source = PanoramaReadback;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+/// The faces the render world has read back, waiting to be saved
+#[derive(Resource)]
+pub struct PanoramaFaces(Mutex<Receiver<PanoramaFace>>);
+
+/// A face being copied out of the image in the render world
+struct PendingReadback {
+    face: usize,
+    size: u32,
+    padded_row: usize,
+    buffer: Buffer,
+    /// Set once the copy has finished, to whether it succeeded
+    mapped: Arc<Mutex<Option<bool>>>,
+}
+
+#[derive(Resource, Default)]
+struct PendingReadbacks(Vec<PendingReadback>);
+
+pub struct PanoramaPlugin;
+
+impl Plugin for PanoramaPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = channel();
+
+        app.insert_resource(PanoramaCapture::default())
+            .insert_resource(PanoramaReadback {
+                image: None,
+                face: None,
+                sender,
+            })
+            .insert_resource(PanoramaFaces(Mutex::new(receiver)))
+            .add_plugin(ExtractResourcePlugin::<PanoramaReadback>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<PendingReadbacks>()
+                .add_system_to_stage(RenderStage::Cleanup, read_back_panorama);
+        }
+    }
+}
+
+/// Whether every chunk requested within `radius` chunks of `center` has arrived and is being drawn, with no
+/// meshes left to build. Chunks further out are left out, they can't be seen and may never arrive
+fn world_loaded(chunks: &ChunkSystem, center: Vector3<i32>, radius: i32) -> bool {
+    chunks.pending_builds == 0
+        && chunks
+            .requested_chunks
+            .iter()
+            .filter(|v| (**v - center).cast::<f32>().magnitude() <= radius as f32)
+            .all(|v| chunks.chunks.get(v).map_or(false, |chunk| chunk.meshed))
+}
+
+/// Saves a face of the panorama, returning whether it was written
+fn save_face(directory: &Path, face: &PanoramaFace) -> bool {
+    let path = panorama_path(directory, face.face);
+    let result = std::fs::create_dir_all(directory)
+        .map_err(image::ImageError::from)
+        .and_then(|_| {
+            image::save_buffer(
+                &path,
+                &face.pixels,
+                face.size,
+                face.size,
+                image::ColorType::Rgba8,
+            )
+        });
+
+    match result {
+        Ok(_) => {
+            info!("Saved panorama face to {:?}", path);
+            true
+        }
+        Err(err) => {
+            error!("Failed to save panorama face {:?}: {}", path, err);
+            false
+        }
+    }
+}
+
+pub fn update_panorama_capture(
+    keys: Res<Input<KeyCode>>,
+    config: Res<ClientConfig>,
+    mut capture: ResMut<PanoramaCapture>,
+    mut readback: ResMut<PanoramaReadback>,
+    faces: Res<PanoramaFaces>,
+    mut images: ResMut<Assets<Image>>,
+    chunks: Res<ChunkSystem>,
+    mut camera: Query<(
+        &mut Transform,
+        &mut Projection,
+        &mut Camera,
+        &mut UiCameraConfig,
+    )>,
+) {
+    let (mut transform, mut projection, mut camera, mut ui) = match camera.get_single_mut() {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+
+    if !capture.active {
+        if keys.just_pressed(PANORAMA_KEY) {
+            let fov = match &*projection {
+                Projection::Perspective(perspective) => perspective.fov,
+                Projection::Orthographic(_) => return,
+            };
+
+            let timestamp = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |v| v.as_secs());
+
+            info!("Starting panorama capture");
+            capture.active = true;
+            capture.face = 0;
+            capture.held_frames = 0;
+            capture.waiting_since = Some(Instant::now());
+            capture.directory =
+                Path::new(&config.panorama_directory).join(format!("panorama-{}", timestamp));
+            capture.previous = Some(PreviousCamera {
+                rotation: transform.rotation,
+                fov,
+                target: camera.target.clone(),
+            });
+
+            // Draw into an image rather than the window, without the UI on top
+            let image = images.add(panorama_image(config.panorama_resolution));
+            camera.target = RenderTarget::Image(image.clone());
+            readback.image = Some(image);
+            ui.show_ui = false;
+        }
+        return;
+    }
+
+    // Each face covers exactly a quarter turn
+    if let Projection::Perspective(perspective) = &mut *projection {
+        perspective.fov = FRAC_PI_2;
+    }
+    transform.rotation = panorama_rotation(capture.face);
+
+    // Don't start holding the face until the chunks in view have loaded so nothing pops in, giving up on them
+    // after a while so a chunk that never arrives can't stall the capture
+    if let Some(started) = capture.waiting_since {
+        let (center, _) = global_f32_to_local_position(
+            Vector3::new(
+                transform.translation.x,
+                transform.translation.y,
+                transform.translation.z,
+            ),
+            chunks.chunk_size,
+        );
+        let timeout = Duration::from_secs_f32(config.panorama_timeout);
+
+        if world_loaded(&chunks, center, config.render_distance) {
+            info!("Panorama face {} ready", capture.face);
+        } else if started.elapsed() >= timeout {
+            warn!(
+                "Chunks still loading after {:?}, capturing panorama face {} anyway",
+                timeout, capture.face
+            );
+        } else {
+            return;
+        }
+        capture.waiting_since = None;
+    }
+
+    if capture.held_frames < FRAMES_PER_FACE {
+        capture.held_frames += 1;
+        return;
+    }
+
+    // The face is drawn in the frame it's asked for, then arrives a frame or two later
+    if readback.face != Some(capture.face) {
+        readback.face = Some(capture.face);
+    }
+
+    let face = match faces
+        .0
+        .lock()
+        .unwrap()
+        .try_iter()
+        .find(|v| v.face == capture.face)
+    {
+        Some(val) => val,
+        None => return,
+    };
+
+    let saved = save_face(&capture.directory, &face);
+    readback.face = None;
+    capture.face += 1;
+    capture.held_frames = 0;
+    capture.waiting_since = Some(Instant::now());
+
+    if !saved || capture.face == PANORAMA_FACES.len() {
+        if saved {
+            info!("Finished panorama capture");
+        }
+        capture.active = false;
+        readback.image = None;
+
+        if let Some(previous) = capture.previous.take() {
+            transform.rotation = previous.rotation;
+            camera.target = previous.target;
+            ui.show_ui = true;
+            if let Projection::Perspective(perspective) = &mut *projection {
+                perspective.fov = previous.fov;
+            }
+        }
+    }
+}
+
+/// Starts copying the face drawn this frame out of the image, and sends on the faces that have finished copying.
+/// Runs after the frame has been drawn
+fn read_back_panorama(
+    readback: Option<Res<PanoramaReadback>>,
+    mut pending: ResMut<PendingReadbacks>,
+    images: Res<RenderAssets<Image>>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+) {
+    let readback = match readback {
+        Some(val) => val,
+        None => return,
+    };
+
+    if let (Some(face), Some(handle)) = (readback.face, &readback.image) {
+        let image = images.get(handle);
+
+        if let (Some(image), false) = (image, pending.0.iter().any(|v| v.face == face)) {
+            let size = image.size.x as u32;
+            let row = size as usize * 4;
+            let padded_row = padded_row_size(row);
+
+            let buffer = device.create_buffer(&BufferDescriptor {
+                label: Some("panorama_readback"),
+                size: (padded_row * size as usize) as u64,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("panorama_readback"),
+            });
+            encoder.copy_texture_to_buffer(
+                image.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(padded_row as u32),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+            );
+            queue.submit([encoder.finish()]);
+
+            // Mapped once the copy has been carried out, which is checked on later frames
+            let mapped = Arc::new(Mutex::new(None));
+            let callback_mapped = mapped.clone();
+            device.map_buffer(&buffer.slice(..), MapMode::Read, move |result| {
+                *callback_mapped.lock().unwrap() = Some(result.is_ok());
+            });
+
+            pending.0.push(PendingReadback {
+                face,
+                size,
+                padded_row,
+                buffer,
+                mapped,
+            });
+        }
+    }
+
+    pending.0.retain(|v| {
+        let mapped = *v.mapped.lock().unwrap();
+        match mapped {
+            Some(true) => {
+                let pixels = {
+                    let data = v.buffer.slice(..).get_mapped_range();
+                    unpad_rows(&data, v.size as usize * 4, v.padded_row)
+                };
+                v.buffer.unmap();
+
+                let _ = readback.sender.send(PanoramaFace {
+                    face: v.face,
+                    size: v.size,
+                    pixels,
+                });
+                false
+            }
+            Some(false) => {
+                error!("Failed to read back panorama face {}", v.face);
+                false
+            }
+            None => true,
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faces_look_along_each_axis() {
+        for (face, (forward, up)) in PANORAMA_FACES.iter().enumerate() {
+            let rotation = panorama_rotation(face);
+
+            // The camera looks down its negative z axis, with its y axis up on screen
+            assert!(
+                (rotation * Vec3::NEG_Z).abs_diff_eq(*forward, 1e-5),
+                "face {}",
+                face
+            );
+            assert!((rotation * Vec3::Y).abs_diff_eq(*up, 1e-5), "face {}", face);
+        }
+    }
+
+    #[test]
+    fn faces_are_saved_by_index() {
+        let directory = Path::new("panoramas/panorama-1");
+
+        for face in 0..PANORAMA_FACES.len() {
+            assert_eq!(
+                panorama_path(directory, face),
+                directory.join(format!("panorama_{}.png", face))
+            );
+        }
+    }
+
+    #[test]
+    fn rows_are_padded_and_unpadded() {
+        assert_eq!(padded_row_size(256), 256);
+        assert_eq!(padded_row_size(12), 256);
+        assert_eq!(padded_row_size(1028), 1280);
+
+        // Two rows of three pixels, each padded out to 256 bytes
+        let mut data = vec![0; 512];
+        data[..12].copy_from_slice(&[1; 12]);
+        data[256..268].copy_from_slice(&[2; 12]);
+
+        let pixels = unpad_rows(&data, 12, 256);
+        assert_eq!(pixels.len(), 24);
+        assert!(pixels[..12].iter().all(|v| *v == 1));
+        assert!(pixels[12..].iter().all(|v| *v == 2));
+    }
+
+    #[test]
+    fn capture_waits_for_chunks_to_be_drawn() {
+        use crate::config::ChunkEdgeFaces;
+        use crate::systems::chunk::data::{test_chunk, RawChunkData};
+        use rc_networking::constants::CHUNK_SIZE;
+
+        let mut chunks = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        chunks.requested_chunks = vec![Vector3::zeros(), Vector3::new(10, 0, 0)];
+        chunks.chunks.insert(
+            Vector3::zeros(),
+            test_chunk(Vector3::zeros(), RawChunkData::new(CHUNK_SIZE)),
+        );
+
+        // Arrived but not meshed yet, so it isn't on screen
+        assert!(!world_loaded(&chunks, Vector3::zeros(), 4));
+
+        // The chunk out of range is never waited on
+        chunks.chunks.get_mut(&Vector3::zeros()).unwrap().meshed = true;
+        assert!(world_loaded(&chunks, Vector3::zeros(), 4));
+
+        chunks.pending_builds = 1;
+        assert!(!world_loaded(&chunks, Vector3::zeros(), 4));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::game::interaction::timing::SwingAnimation;
+use crate::systems::camera::panorama::PanoramaCapture;
+use crate::systems::input::spectator::SpectatorMode;
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+/// Where the arm sits in front of the camera when it isn't swinging, down and to the right of the crosshair
+const REST_POSITION: Vec3 = Vec3::new(0.35, -0.3, -0.5);
+
+/// How far the arm tips down and reaches forward, in radians and blocks, at the middle of a swing
+const SWING_ANGLE: f32 = 0.9;
+const SWING_REACH: f32 = 0.12;
+
+/// The player's arm, drawn in front of the camera
+#[derive(Component)]
+pub struct Viewmodel;
+
+/// Where the arm is for a point in its swing. It tips down and forward and comes back, resting when not swinging
+pub fn viewmodel_transform(progress: Option<f32>) -> Transform {
+    let amount = progress.map_or(0.0, |v| (v.clamp(0.0, 1.0) * PI).sin());
+
+    Transform::from_translation(REST_POSITION + Vec3::new(0.0, 0.0, -SWING_REACH * amount))
+        .with_rotation(Quat::from_rotation_x(-SWING_ANGLE * amount))
+}
+
+/// Adds the arm to the camera, so it follows it around
+pub fn spawn_viewmodel(
+    parent: &mut ChildBuilder,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    parent
+        .spawn(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Box::new(0.12, 0.12, 0.4))),
+            // Unlit, as the chunks bring their own lighting and nothing lights the rest of the scene
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgb(0.8, 0.6, 0.45),
+                unlit: true,
+                ..default()
+            }),
+            transform: viewmodel_transform(None),
+            ..default()
+        })
+        .insert(Viewmodel);
+}
+
+/// Swings the arm when placing or breaking blocks. It's hidden while the camera is away from the player
+pub fn update_viewmodel(
+    swing: Res<SwingAnimation>,
+    spectator: Res<SpectatorMode>,
+    panorama: Res<PanoramaCapture>,
+    mut viewmodel: Query<(&mut Transform, &mut Visibility), With<Viewmodel>>,
+) {
+    let (mut transform, mut visibility) = match viewmodel.get_single_mut() {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+
+    let visible = !spectator.active && !panorama.active;
+    if visibility.is_visible != visible {
+        visibility.is_visible = visible;
+    }
+
+    let wanted = viewmodel_transform(swing.progress());
+    if *transform != wanted {
+        *transform = wanted;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arm_rests_outside_of_a_swing() {
+        let rest = viewmodel_transform(None);
+        assert_eq!(rest.translation, REST_POSITION);
+        assert_eq!(rest.rotation, Quat::IDENTITY);
+
+        // Back where it started at either end of a swing
+        for progress in [0.0, 1.0] {
+            let transform = viewmodel_transform(Some(progress));
+            assert!(transform.translation.abs_diff_eq(REST_POSITION, 1e-5));
+            assert!(transform.rotation.abs_diff_eq(Quat::IDENTITY, 1e-5));
+        }
+    }
+
+    #[test]
+    fn arm_reaches_furthest_halfway_through() {
+        let middle = viewmodel_transform(Some(0.5));
+        let quarter = viewmodel_transform(Some(0.25));
+
+        assert!(middle.translation.z < quarter.translation.z);
+        assert!(quarter.translation.z < REST_POSITION.z);
+        assert!(middle
+            .rotation
+            .abs_diff_eq(Quat::from_rotation_x(-SWING_ANGLE), 1e-5));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::systems::chunk::data::ChunkData;
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::physics::aabb::Aabb;
+use crate::systems::ui::theme::{Indicator, UiTheme};
+use bevy::prelude::*;
+use bevy_prototype_debug_lines::DebugLines;
+use nalgebra::Vector3;
+
+/// Key that shows and hides the outlines of loaded chunks
+pub const CHUNK_BOUNDARIES_KEY: KeyCode = KeyCode::F4;
+
+#[derive(Resource, Default)]
+pub struct ChunkBoundaries {
+    pub shown: bool,
+}
+
+/// What a chunk's outline shows about its mesh
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChunkMeshState {
+    /// The mesh is up to date
+    Meshed,
+    /// Every block is air, so there's no mesh
+    Empty,
+    /// The mesh is waiting to be built or rebuilt
+    Dirty,
+}
+
+impl ChunkMeshState {
+    /// A chunk waiting on a rebuild is dirty even if it's empty, as it may not be once rebuilt
+    pub fn of(chunk: &ChunkData) -> ChunkMeshState {
+        if chunk.dirty {
+            ChunkMeshState::Dirty
+        } else if chunk.is_empty() {
+            ChunkMeshState::Empty
+        } else {
+            ChunkMeshState::Meshed
+        }
+    }
+
+    pub fn indicator(&self) -> Indicator {
+        match self {
+            ChunkMeshState::Meshed => Indicator::Good,
+            ChunkMeshState::Empty => Indicator::Neutral,
+            ChunkMeshState::Dirty => Indicator::Bad,
+        }
+    }
+}
+
+pub fn toggle_chunk_boundaries(keys: Res<Input<KeyCode>>, mut boundaries: ResMut<ChunkBoundaries>) {
+    if keys.just_pressed(CHUNK_BOUNDARIES_KEY) {
+        boundaries.shown = !boundaries.shown;
+    }
+}
+
+pub fn draw_chunk_boundaries(
+    boundaries: Res<ChunkBoundaries>,
+    chunks: Res<ChunkSystem>,
+    mut lines: ResMut<DebugLines>,
+    theme: Res<UiTheme>,
+) {
+    if !boundaries.shown {
+        return;
+    }
+
+    let size = chunks.chunk_size as f32;
+    let outline = Aabb::new(Vector3::zeros(), Vector3::new(size, size, size));
+
+    for (position, chunk) in chunks.chunks.iter() {
+        let state = ChunkMeshState::of(chunk);
+        let corner = position.cast::<f32>() * size;
+        let color = theme.indicator(state.indicator());
+
+        outline.draw_colored(corner, &mut lines, 0.0, color);
+
+        // Cross through dirty chunks, so they can be told apart without relying on color
+        if state == ChunkMeshState::Dirty && theme.colorblind_mode.is_enabled() {
+            let corner = Vec3::new(corner.x, corner.y, corner.z);
+            let far = corner + Vec3::splat(size);
+            lines.line_colored(corner, far, 0.0, color);
+            lines.line_colored(
+                Vec3::new(far.x, corner.y, corner.z),
+                Vec3::new(corner.x, far.y, far.z),
+                0.0,
+                color,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use rc_networking::constants::CHUNK_SIZE;
+
+    fn chunk(stone: bool) -> ChunkData {
+        let mut world = RawChunkData::new(CHUNK_SIZE);
+        if stone {
+            world.set(0, 0, 0, 6);
+        }
+
+        test_chunk(Vector3::zeros(), world)
+    }
+
+    #[test]
+    fn new_chunks_wait_for_a_mesh() {
+        assert_eq!(ChunkMeshState::of(&chunk(true)), ChunkMeshState::Dirty);
+        assert_eq!(ChunkMeshState::of(&chunk(false)), ChunkMeshState::Dirty);
+    }
+
+    #[test]
+    fn built_chunks_are_meshed_or_empty() {
+        let mut stone = chunk(true);
+        stone.dirty = false;
+        assert_eq!(ChunkMeshState::of(&stone), ChunkMeshState::Meshed);
+
+        let mut air = chunk(false);
+        air.dirty = false;
+        assert_eq!(ChunkMeshState::of(&air), ChunkMeshState::Empty);
+    }
+
+    #[test]
+    fn emptied_chunks_stay_dirty_until_rebuilt() {
+        let mut chunk = chunk(true);
+        chunk.dirty = false;
+
+        chunk.set_block(Vector3::zeros(), 0);
+        chunk.dirty = true;
+        assert_eq!(ChunkMeshState::of(&chunk), ChunkMeshState::Dirty);
+
+        chunk.dirty = false;
+        assert_eq!(ChunkMeshState::of(&chunk), ChunkMeshState::Empty);
+    }
+
+    #[test]
+    fn each_state_has_its_own_color() {
+        let theme = UiTheme::default();
+        let meshed = theme.indicator(ChunkMeshState::Meshed.indicator());
+        let empty = theme.indicator(ChunkMeshState::Empty.indicator());
+        let dirty = theme.indicator(ChunkMeshState::Dirty.indicator());
+
+        assert_ne!(meshed, empty);
+        assert_ne!(meshed, dirty);
+        assert_ne!(empty, dirty);
+    }
+}
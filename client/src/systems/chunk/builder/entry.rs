@@ -1,50 +1,34 @@
 use bevy::prelude::Entity;
 use nalgebra::Vector3;
-use std::sync::atomic::{AtomicI32, Ordering};
 
-// Approximate player position used to load closer chunks
-pub static PLAYER_POS: [AtomicI32; 3] = [AtomicI32::new(0), AtomicI32::new(0), AtomicI32::new(0)];
-
-#[derive(Eq)]
 pub struct MeshBuildEntry {
     pub entity: Entity,
     pub chunk: Vector3<i32>,
+    /// How soon this chunk should be built, lower values are built first
+    pub priority: f32,
 }
 
 impl PartialEq<Self> for MeshBuildEntry {
     fn eq(&self, other: &Self) -> bool {
-        self.chunk == other.chunk
+        self.cmp(other) == std::cmp::Ordering::Equal
     }
 }
 
+impl Eq for MeshBuildEntry {}
+
 impl PartialOrd<Self> for MeshBuildEntry {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let player = Vector3::new(
-            PLAYER_POS[0].load(Ordering::Relaxed),
-            PLAYER_POS[1].load(Ordering::Relaxed),
-            PLAYER_POS[2].load(Ordering::Relaxed),
-        )
-        .cast::<f32>();
-
-        Some(
-            (self.chunk.cast::<f32>() - player)
-                .magnitude()
-                .total_cmp(&(other.chunk.cast::<f32>() - player).magnitude()),
-        )
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for MeshBuildEntry {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let player = Vector3::new(
-            PLAYER_POS[0].load(Ordering::Relaxed),
-            PLAYER_POS[1].load(Ordering::Relaxed),
-            PLAYER_POS[2].load(Ordering::Relaxed),
-        )
-        .cast::<f32>();
-
-        (self.chunk.cast::<f32>() - player)
-            .magnitude()
-            .total_cmp(&(other.chunk.cast::<f32>() - player).magnitude())
+        // Reversed so the binary heap pops the lowest priority value first. Ties are broken by the chunk so only
+        // entries for the same chunk are equal
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then_with(|| other.chunk.as_slice().cmp(self.chunk.as_slice()))
     }
 }
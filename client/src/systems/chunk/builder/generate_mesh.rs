@@ -1,34 +1,87 @@
-use crate::game::viewable_direction::{AxisAlignedDirection, ViewableDirection, BLOCK_SIDES};
+use crate::game::viewable_direction::{ViewableDirection, BLOCK_SIDES};
 
-use crate::systems::chunk::data::ChunkData;
+use crate::systems::chunk::data::{ChunkData, RawChunkData, ViewableMap};
 
 use crate::systems::chunk::ChunkSystem;
 use bevy::ecs::component::Component;
 
 use crate::game::blocks::states::BlockStates;
-use crate::helpers::{check_chunk_boundaries, global_to_local_position};
+use crate::helpers::global_to_local_position;
+use crate::systems::chunk::builder::greedy::GreedyMesher;
 use crate::systems::chunk::mesh::draw_kit::DrawKit;
+use crate::systems::chunk::mesh::face::FaceLighting;
 use crate::systems::chunk::nearby_cache::NearbyChunkCache;
 use fnv::FnvHashMap;
 use nalgebra::Vector3;
-use rc_networking::constants::CHUNK_SIZE;
+use rc_networking::constants::{block_id, block_state};
+
+/// Counts the visible sides of the opaque and translucent blocks in a chunk. Full blocks draw a face per visible
+/// side, so this is a close guess at how many faces the mesh will have
+pub fn count_visible_faces(
+    chunk: &RawChunkData,
+    viewable: &ViewableMap,
+    block_states: &BlockStates,
+) -> (usize, usize) {
+    let mut opaque = 0;
+    let mut translucent = 0;
+
+    let size = chunk.size();
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let block = chunk.get(x, y, z);
+                let sides = viewable.get(x, y, z).0.count_ones() as usize;
+                if sides == 0 || block_id(block) == 0 {
+                    continue;
+                }
+
+                if block_states.get_block(block as usize).translucent {
+                    translucent += sides;
+                } else {
+                    opaque += sides;
+                }
+            }
+        }
+    }
+
+    (opaque, translucent)
+}
+
+/// How many faces to make room for from a count of visible sides, 0 scale turns reserving off
+pub fn reserved_faces(visible_sides: usize, scale: f32) -> usize {
+    (visible_sides as f32 * scale.max(0.0)).ceil() as usize
+}
 
 #[derive(Component)]
 pub struct UpdateChunkMesh {
     pub chunk: Vector3<i32>,
     pub opaque: DrawKit,
     pub translucent: DrawKit,
-    pub viewable_map: Option<[[[ViewableDirection; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>,
+    pub viewable_map: Option<ViewableMap>,
 }
 
 impl ChunkData {
+    /// Builds the chunk's meshes. With a greedy light tolerance, matching faces next to each other are merged into
+    /// one quad, otherwise every face is drawn by itself
     pub fn build_mesh(
         &self,
         chunks: &ChunkSystem,
         block_states: &BlockStates,
         edge_faces: bool,
+        reserve_scale: f32,
+        greedy: Option<u8>,
         cache: &NearbyChunkCache,
     ) -> UpdateChunkMesh {
+        // Nothing to draw, so skip working out which faces are visible
+        if self.is_empty() {
+            return UpdateChunkMesh {
+                chunk: self.position,
+                opaque: DrawKit::new(),
+                translucent: DrawKit::new(),
+                viewable_map: None,
+            };
+        }
+
         // Get adjacent chunks
         let mut map = FnvHashMap::default();
 
@@ -59,52 +112,86 @@ impl ChunkData {
 
         let viewable = self.generate_viewable_map(block_states, map, edge_faces);
 
-        let mut opaque = DrawKit::new();
-        let mut translucent = DrawKit::new();
+        // Create the buffers to add the mesh data into, sized up front so dense chunks don't reallocate as they're drawn
+        let (opaque_sides, translucent_sides) =
+            count_visible_faces(&self.world, &viewable, block_states);
+        let mut opaque = DrawKit::with_capacity(reserved_faces(opaque_sides, reserve_scale));
+        let mut translucent =
+            DrawKit::with_capacity(reserved_faces(translucent_sides, reserve_scale));
 
-        // Create the buffers to add the mesh data into
-        let chunk = self.world;
+        let size = self.world.size();
+        let mut greedy = greedy.map(|tolerance| GreedyMesher::new(size, tolerance));
 
-        for x in 0..chunk.len() {
-            for z in 0..chunk[0][0].len() {
-                for y in 0..chunk[0].len() {
-                    let viewable = viewable[x][y][z].0;
+        for x in 0..size {
+            for z in 0..size {
+                for y in 0..size {
+                    let viewable = viewable.get(x, y, z).0;
+                    let stored_block = self.world.get(x, y, z);
 
                     // Isn't air and is visible from at least one side
-                    if chunk[x][y][z] != 0 && viewable != 0 {
-                        let block = block_states.get_block(chunk[x][y][z] as usize);
+                    if block_id(stored_block) != 0 && viewable != 0 {
+                        let block = block_states.get_block(stored_block as usize);
 
-                        let mut light_color = [self.light_levels[x][y][z]; 6];
+                        let mut light = FaceLighting::new(self.light_levels.get(x, y, z));
 
                         for (i, side) in BLOCK_SIDES.iter().enumerate() {
                             let (chunk_pos, local_pos) = global_to_local_position(
                                 Vector3::new(x, y, z).cast::<i32>()
                                     + side
-                                    + (self.position * CHUNK_SIZE as i32),
+                                    + (self.position * size as i32),
+                                size,
                             );
 
-                            light_color[i] = if let Some(chunk) = cache.get_chunk(chunk_pos) {
-                                chunk.light_levels[local_pos.x][local_pos.y][local_pos.z]
+                            light.sides[i] = if let Some(chunk) = cache.get_chunk(chunk_pos) {
+                                chunk
+                                    .light_levels
+                                    .get(local_pos.x, local_pos.y, local_pos.z)
                             } else {
                                 [0; 4]
                             }
                         }
 
-                        block.draw(
-                            Vector3::new(x as f32, y as f32, z as f32),
-                            ViewableDirection(viewable),
-                            light_color,
-                            if block.translucent {
-                                &mut translucent
-                            } else {
-                                &mut opaque
-                            },
-                        );
+                        let position = Vector3::new(x as f32, y as f32, z as f32);
+                        let kit = if block.translucent {
+                            &mut translucent
+                        } else {
+                            &mut opaque
+                        };
+
+                        match greedy.as_mut() {
+                            Some(greedy) => block.visible_faces(
+                                block_state(stored_block),
+                                ViewableDirection(viewable),
+                                |index, face| {
+                                    let color = light.for_face(face);
+                                    if !greedy.add(
+                                        Vector3::new(x, y, z),
+                                        stored_block,
+                                        index,
+                                        face,
+                                        color,
+                                    ) {
+                                        kit.draw_face(position, face, color);
+                                    }
+                                },
+                            ),
+                            None => block.draw(
+                                position,
+                                block_state(stored_block),
+                                ViewableDirection(viewable),
+                                &light,
+                                kit,
+                            ),
+                        }
                     }
                 }
             }
         }
 
+        if let Some(greedy) = greedy {
+            greedy.draw(block_states, &mut opaque, &mut translucent);
+        }
+
         // Check top faces
         UpdateChunkMesh {
             chunk: self.position,
@@ -114,3 +201,407 @@ impl ChunkData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ChunkEdgeFaces;
+    use crate::game::blocks::states::BlockStates;
+    use crate::systems::chunk::builder::generate_mesh::{count_visible_faces, reserved_faces};
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use crate::systems::chunk::nearby_cache::NearbyChunkCache;
+    use crate::systems::chunk::ChunkSystem;
+    use bevy::prelude::Vec3A;
+    use nalgebra::Vector3;
+    use rc_networking::constants::CHUNK_SIZE;
+
+    const DIRT: u32 = 1;
+    const STONE: u32 = 6;
+
+    fn insert_chunk(system: &mut ChunkSystem, position: Vector3<i32>, stone: &[[usize; 3]]) {
+        insert_sized_chunk(system, position, CHUNK_SIZE, stone);
+    }
+
+    fn insert_sized_chunk(
+        system: &mut ChunkSystem,
+        position: Vector3<i32>,
+        size: usize,
+        stone: &[[usize; 3]],
+    ) {
+        let mut world = RawChunkData::new(size);
+        for [x, y, z] in stone {
+            world.set(*x, *y, *z, STONE);
+        }
+
+        system.chunks.insert(position, test_chunk(position, world));
+    }
+
+    /// How many faces the chunk's mesh has, drawing them one by one
+    fn faces(system: &ChunkSystem, states: &BlockStates, position: Vector3<i32>) -> usize {
+        let cache = NearbyChunkCache::from_service(system, position);
+        let mesh = system.chunks.get(&position).unwrap().build_mesh(
+            system,
+            states,
+            system.edge_faces.draws_unloaded_faces(),
+            1.0,
+            None,
+            &cache,
+        );
+
+        mesh.opaque.indices.len() / 6
+    }
+
+    #[test]
+    fn corner_blocks_get_a_small_aabb() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(
+            &mut system,
+            Vector3::new(0, 0, 0),
+            &[[0, 0, 0], [1, 0, 0], [0, 1, 1]],
+        );
+
+        let cache = NearbyChunkCache::from_service(&system, Vector3::new(0, 0, 0));
+        let mesh = system
+            .chunks
+            .get(&Vector3::new(0, 0, 0))
+            .unwrap()
+            .build_mesh(&system, &states, true, 1.0, None, &cache);
+
+        let aabb = mesh.opaque.aabb();
+        assert_eq!(aabb.min(), Vec3A::ZERO);
+        assert_eq!(aabb.max(), Vec3A::new(2.0, 2.0, 2.0));
+        assert!(mesh.translucent.is_empty());
+    }
+
+    #[test]
+    fn block_at_section_boundary_meshes_in_both_sections() {
+        let states = BlockStates::shipped();
+        let top = CHUNK_SIZE - 1;
+
+        // Alone at the top of the lower section, with air above it in the upper section
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &[[8, top, 8]]);
+        insert_chunk(&mut system, Vector3::new(0, 1, 0), &[]);
+
+        assert_eq!(faces(&system, &states, Vector3::new(0, 0, 0)), 6);
+        assert_eq!(faces(&system, &states, Vector3::new(0, 1, 0)), 0);
+
+        // A block directly above it in the upper section hides the faces between them in both sections
+        insert_chunk(&mut system, Vector3::new(0, 1, 0), &[[8, 0, 8]]);
+
+        assert_eq!(faces(&system, &states, Vector3::new(0, 0, 0)), 5);
+        assert_eq!(faces(&system, &states, Vector3::new(0, 1, 0)), 5);
+    }
+
+    #[test]
+    fn edge_faces_against_an_unloaded_chunk() {
+        let states = BlockStates::shipped();
+        let edge = CHUNK_SIZE - 1;
+
+        // Alone against the edge of the chunk, with nothing loaded next to it
+        let count = |edge_faces| {
+            let mut system = ChunkSystem::new(edge_faces, 1.0, None);
+            insert_chunk(&mut system, Vector3::new(0, 0, 0), &[[edge, 8, 8]]);
+            faces(&system, &states, Vector3::new(0, 0, 0))
+        };
+        assert_eq!(count(ChunkEdgeFaces::Always), 6);
+        assert_eq!(count(ChunkEdgeFaces::Never), 5);
+        assert_eq!(count(ChunkEdgeFaces::UntilLoaded), 6);
+
+        // Only the last rebuilds the chunk once its neighbour loads
+        assert!(!ChunkEdgeFaces::Always.remesh_neighbours());
+        assert!(!ChunkEdgeFaces::Never.remesh_neighbours());
+        assert!(ChunkEdgeFaces::UntilLoaded.remesh_neighbours());
+
+        // Which then hides the face against the neighbour's block
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::UntilLoaded, 1.0, None);
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &[[edge, 8, 8]]);
+        insert_chunk(&mut system, Vector3::new(1, 0, 0), &[[0, 8, 8]]);
+        assert_eq!(faces(&system, &states, Vector3::new(0, 0, 0)), 5);
+    }
+
+    #[test]
+    fn all_air_chunk_takes_the_fast_path() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &[]);
+
+        let chunk = system.chunks.get(&Vector3::new(0, 0, 0)).unwrap();
+        assert!(chunk.is_empty());
+
+        let cache = NearbyChunkCache::from_service(&system, Vector3::new(0, 0, 0));
+        let mesh = chunk.build_mesh(&system, &states, true, 1.0, None, &cache);
+
+        // The visible faces are never worked out, and nothing is reserved for them
+        assert!(mesh.viewable_map.is_none());
+        assert!(mesh.opaque.positions.is_empty() && mesh.translucent.positions.is_empty());
+        assert_eq!(mesh.opaque.positions.capacity(), 0);
+    }
+
+    #[test]
+    fn emptied_chunk_takes_the_fast_path() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &[[8, 8, 8]]);
+
+        let chunk = system.chunks.get_mut(&Vector3::new(0, 0, 0)).unwrap();
+        assert!(!chunk.is_empty());
+        chunk.set_block(Vector3::new(8, 8, 8), 0);
+        assert!(chunk.is_empty());
+
+        let cache = NearbyChunkCache::from_service(&system, Vector3::new(0, 0, 0));
+        let mesh = system
+            .chunks
+            .get(&Vector3::new(0, 0, 0))
+            .unwrap()
+            .build_mesh(&system, &states, true, 1.0, None, &cache);
+        assert!(mesh.viewable_map.is_none());
+        assert!(mesh.opaque.positions.is_empty());
+    }
+
+    #[test]
+    fn reserved_faces_scale_the_visible_sides() {
+        assert_eq!(reserved_faces(100, 1.0), 100);
+        assert_eq!(reserved_faces(100, 1.5), 150);
+        assert_eq!(reserved_faces(3, 0.5), 2);
+
+        // 0 or less turns reserving off
+        assert_eq!(reserved_faces(100, 0.0), 0);
+        assert_eq!(reserved_faces(100, -1.0), 0);
+    }
+
+    #[test]
+    fn reserving_leaves_the_mesh_unchanged() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+
+        // Every other block is stone, so each one shows all of its sides
+        let mut stone = vec![];
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    if (x + y + z) % 2 == 0 {
+                        stone.push([x, y, z]);
+                    }
+                }
+            }
+        }
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &stone);
+
+        let chunk = system.chunks.get(&Vector3::new(0, 0, 0)).unwrap();
+        let cache = NearbyChunkCache::from_service(&system, Vector3::new(0, 0, 0));
+        let build = |scale| chunk.build_mesh(&system, &states, true, scale, None, &cache);
+
+        let unreserved = build(0.0);
+        assert_eq!(unreserved.opaque.indices.len() / 6, stone.len() * 6);
+
+        for scale in [1.0, 2.0] {
+            let reserved = build(scale);
+            for (a, b) in [
+                (&unreserved.opaque, &reserved.opaque),
+                (&unreserved.translucent, &reserved.translucent),
+            ] {
+                assert_eq!(a.positions, b.positions);
+                assert_eq!(a.indices, b.indices);
+                assert_eq!(a.normals, b.normals);
+                assert_eq!(a.uv_coordinates, b.uv_coordinates);
+                assert_eq!(a.texture_rects, b.texture_rects);
+                assert_eq!(a.lighting, b.lighting);
+            }
+            assert_eq!(unreserved.viewable_map, reserved.viewable_map);
+        }
+    }
+
+    #[test]
+    fn full_blocks_draw_a_face_per_visible_side() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(
+            &mut system,
+            Vector3::new(0, 0, 0),
+            &[[4, 4, 4], [5, 4, 4], [4, 5, 4], [10, 10, 10]],
+        );
+
+        let chunk = system.chunks.get(&Vector3::new(0, 0, 0)).unwrap();
+        let cache = NearbyChunkCache::from_service(&system, Vector3::new(0, 0, 0));
+        let mesh = chunk.build_mesh(&system, &states, true, 1.0, None, &cache);
+
+        // So the guess is exact, and nothing had to grow past what was reserved
+        let (opaque, translucent) =
+            count_visible_faces(&chunk.world, &mesh.viewable_map.unwrap(), &states);
+        assert_eq!((opaque, translucent), (4 * 6 - 4, 0));
+        assert_eq!(mesh.opaque.indices.len() / 6, opaque);
+        assert!(mesh.opaque.positions.capacity() >= reserved_faces(opaque, 1.0) * 4);
+        assert_eq!(mesh.opaque.positions.len(), reserved_faces(opaque, 1.0) * 4);
+    }
+
+    /// How many quads the chunk at the origin is drawn with, with or without greedy meshing
+    fn quads(system: &ChunkSystem, states: &BlockStates, greedy: Option<u8>) -> usize {
+        let cache = NearbyChunkCache::from_service(system, Vector3::new(0, 0, 0));
+        let mesh = system
+            .chunks
+            .get(&Vector3::new(0, 0, 0))
+            .unwrap()
+            .build_mesh(system, states, true, 1.0, greedy, &cache);
+
+        assert!(mesh.translucent.is_empty());
+        mesh.opaque.indices.len() / 6
+    }
+
+    /// A single layer of stone across the whole chunk
+    fn floor() -> Vec<[usize; 3]> {
+        let mut blocks = vec![];
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                blocks.push([x, 4, z]);
+            }
+        }
+        blocks
+    }
+
+    #[test]
+    fn greedy_meshing_merges_a_flat_layer() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &floor());
+
+        // Top, bottom and a strip round each edge, drawn face by face
+        let sides = CHUNK_SIZE * CHUNK_SIZE * 2 + CHUNK_SIZE * 4;
+        assert_eq!(quads(&system, &states, None), sides);
+        // Against one quad for each direction
+        assert_eq!(quads(&system, &states, Some(0)), 6);
+    }
+
+    #[test]
+    fn greedy_meshing_keeps_block_types_apart() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &floor());
+
+        // The far half of the layer is dirt
+        let chunk = system.chunks.get_mut(&Vector3::new(0, 0, 0)).unwrap();
+        for x in CHUNK_SIZE / 2..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.set_block(Vector3::new(x, 4, z), DIRT);
+            }
+        }
+
+        // Top and bottom split in two, as do the two edges running across both halves
+        assert_eq!(quads(&system, &states, Some(0)), 10);
+    }
+
+    #[test]
+    fn greedy_meshing_respects_culled_faces() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+
+        // A solid chunk only shows its outside
+        let mut solid = vec![];
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    solid.push([x, y, z]);
+                }
+            }
+        }
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &solid);
+
+        assert_eq!(quads(&system, &states, None), CHUNK_SIZE * CHUNK_SIZE * 6);
+        assert_eq!(quads(&system, &states, Some(0)), 6);
+
+        // Neighbours hide the faces against them either way
+        for position in [Vector3::new(1, 0, 0), Vector3::new(0, 1, 0)] {
+            insert_chunk(&mut system, position, &solid);
+        }
+        assert_eq!(quads(&system, &states, None), CHUNK_SIZE * CHUNK_SIZE * 4);
+        assert_eq!(quads(&system, &states, Some(0)), 4);
+    }
+
+    #[test]
+    fn chunks_of_other_sizes_mesh_at_their_size() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        system.chunk_size = 24;
+
+        let mut solid = vec![];
+        for x in 0..24 {
+            for y in 0..24 {
+                for z in 0..24 {
+                    solid.push([x, y, z]);
+                }
+            }
+        }
+        insert_sized_chunk(&mut system, Vector3::new(0, 0, 0), 24, &solid);
+
+        assert_eq!(quads(&system, &states, None), 24 * 24 * 6);
+        assert_eq!(quads(&system, &states, Some(0)), 6);
+
+        // The neighbour's near side is found at the same size, hiding the faces against it
+        insert_sized_chunk(&mut system, Vector3::new(1, 0, 0), 24, &solid);
+        assert_eq!(quads(&system, &states, None), 24 * 24 * 5);
+    }
+
+    /// The light of each vertex on the top faces of the chunk's mesh
+    fn top_face_light(
+        system: &ChunkSystem,
+        states: &BlockStates,
+        position: Vector3<i32>,
+        greedy: Option<u8>,
+    ) -> Vec<[f32; 4]> {
+        let cache = NearbyChunkCache::from_service(system, position);
+        let mesh = system
+            .chunks
+            .get(&position)
+            .unwrap()
+            .build_mesh(system, states, true, 1.0, greedy, &cache);
+
+        mesh.opaque
+            .normals
+            .iter()
+            .zip(&mesh.opaque.lighting)
+            .filter(|(normal, _)| **normal == [0.0, 1.0, 0.0])
+            .map(|(_, light)| *light)
+            .collect()
+    }
+
+    #[test]
+    fn top_face_is_lit_by_the_cell_above() {
+        let states = BlockStates::shipped();
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &[[8, 8, 8]]);
+
+        let chunk = system.chunks.get_mut(&Vector3::new(0, 0, 0)).unwrap();
+        chunk.light_levels.set(8, 8, 8, [0, 0, 0, 255]);
+        chunk.light_levels.set(8, 9, 8, [255, 102, 51, 255]);
+
+        for greedy in [None, Some(0)] {
+            let light = top_face_light(&system, &states, Vector3::new(0, 0, 0), greedy);
+            assert_eq!(light, vec![[1.0, 0.4, 0.2, 1.0]; 4]);
+        }
+    }
+
+    #[test]
+    fn top_face_is_lit_across_the_chunk_border() {
+        let states = BlockStates::shipped();
+        let top = CHUNK_SIZE - 1;
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        insert_chunk(&mut system, Vector3::new(0, 0, 0), &[[8, top, 8]]);
+        insert_chunk(&mut system, Vector3::new(0, 1, 0), &[]);
+
+        system
+            .chunks
+            .get_mut(&Vector3::new(0, 0, 0))
+            .unwrap()
+            .light_levels
+            .set(8, top, 8, [0, 0, 0, 255]);
+        system
+            .chunks
+            .get_mut(&Vector3::new(0, 1, 0))
+            .unwrap()
+            .light_levels
+            .set(8, 0, 8, [51, 255, 102, 255]);
+
+        let light = top_face_light(&system, &states, Vector3::new(0, 0, 0), None);
+        assert_eq!(light, vec![[0.2, 1.0, 0.4, 1.0]; 4]);
+    }
+}
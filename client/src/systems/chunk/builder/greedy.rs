@@ -0,0 +1,346 @@
+use crate::game::blocks::block_rotation;
+use crate::game::blocks::states::BlockStates;
+use crate::systems::chunk::data::LightingColor;
+use crate::systems::chunk::mesh::draw_kit::DrawKit;
+use crate::systems::chunk::mesh::face::Face;
+use crate::systems::chunk::mesh::vertex::MAX_UV_REPEATS;
+use nalgebra::Vector3;
+use rc_networking::constants::block_state;
+use std::collections::BTreeMap;
+
+/// The side of a block a face covers
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct FacePlane {
+    /// The axis the face points along, 0 for x, 1 for y and 2 for z
+    axis: usize,
+    /// Whether the face is on the far side of the block along the axis
+    far: bool,
+    /// Whether the face points towards the far side
+    forwards: bool,
+}
+
+impl FacePlane {
+    /// The side of the block a face covers completely, or None if it only covers part of a side or is inside the
+    /// block. Only these faces line up with their neighbours well enough to be merged
+    fn of(face: &Face) -> Option<FacePlane> {
+        let bottom_right = face.top_right + face.bottom_left - face.top_left;
+        let corners = [
+            face.top_left,
+            face.top_right,
+            face.bottom_left,
+            bottom_right,
+        ];
+
+        // Every corner is a corner of the block, and the sides leaving the top left are a block long
+        if corners
+            .iter()
+            .any(|corner| corner.iter().any(|v| *v != 0.0 && *v != 1.0))
+            || (face.top_right - face.top_left).norm() != 1.0
+            || (face.bottom_left - face.top_left).norm() != 1.0
+        {
+            return None;
+        }
+
+        let axis = (0..3).find(|axis| {
+            corners
+                .iter()
+                .all(|corner| corner[*axis] == face.top_left[*axis])
+        })?;
+
+        Some(FacePlane {
+            axis,
+            far: face.top_left[axis] == 1.0,
+            forwards: face.normal[axis] > 0.0,
+        })
+    }
+
+    /// The two axes running across the side
+    fn across(&self) -> (usize, usize) {
+        ((self.axis + 1) % 3, (self.axis + 2) % 3)
+    }
+}
+
+/// A face waiting to be merged with the matching faces around it
+#[derive(Copy, Clone, Debug)]
+struct PendingFace {
+    block: u32,
+    /// Where the face is in the block's list of faces
+    face: usize,
+    color: LightingColor,
+}
+
+impl PendingFace {
+    /// Faces of the same block and state can be merged when each channel of their light is within the tolerance
+    fn matches(&self, other: &PendingFace, tolerance: u8) -> bool {
+        self.block == other.block
+            && self.face == other.face
+            && self
+                .color
+                .iter()
+                .zip(other.color.iter())
+                .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+    }
+}
+
+/// Collects the faces covering whole sides of blocks in a chunk, then draws each rectangle of matching faces in
+/// a layer as one quad with the texture repeated across it
+pub struct GreedyMesher {
+    size: usize,
+    tolerance: u8,
+    /// A grid across the chunk for each side and layer of blocks, holding the faces waiting to be merged
+    slices: BTreeMap<(FacePlane, usize), Vec<Option<PendingFace>>>,
+}
+
+impl GreedyMesher {
+    pub fn new(size: usize, tolerance: u8) -> GreedyMesher {
+        GreedyMesher {
+            size,
+            tolerance,
+            slices: BTreeMap::new(),
+        }
+    }
+
+    /// Holds onto a face to be merged. Returns false if it can't be merged, and should be drawn by itself
+    pub fn add(
+        &mut self,
+        position: Vector3<usize>,
+        block: u32,
+        face_index: usize,
+        face: &Face,
+        color: LightingColor,
+    ) -> bool {
+        let plane = match FacePlane::of(face) {
+            Some(val) => val,
+            None => return false,
+        };
+        let (a, b) = plane.across();
+        let size = self.size;
+
+        let slice = self
+            .slices
+            .entry((plane, position[plane.axis]))
+            .or_insert_with(|| vec![None; size * size]);
+        let cell = &mut slice[position[a] * size + position[b]];
+
+        // Another face of the block, such as an overlay, already covers this side
+        if cell.is_some() {
+            return false;
+        }
+
+        *cell = Some(PendingFace {
+            block,
+            face: face_index,
+            color,
+        });
+        true
+    }
+
+    /// Draws every face that was held onto, merging each into the largest rectangle of matching faces it can
+    pub fn draw(self, block_states: &BlockStates, opaque: &mut DrawKit, translucent: &mut DrawKit) {
+        let size = self.size;
+        let tolerance = self.tolerance;
+
+        for ((plane, depth), mut slice) in self.slices {
+            let (a, b) = plane.across();
+
+            for u in 0..size {
+                for v in 0..size {
+                    let first = match slice[u * size + v] {
+                        Some(val) => val,
+                        None => continue,
+                    };
+                    let matches = |cell: &Option<PendingFace>| {
+                        cell.map_or(false, |cell| first.matches(&cell, tolerance))
+                    };
+
+                    // Grow along the column first, then across as long as the whole column matches. The texture
+                    // repeats once per block, so neither side can be longer than a packed coordinate can repeat it
+                    let mut height = 1;
+                    while height < MAX_UV_REPEATS
+                        && v + height < size
+                        && matches(&slice[u * size + v + height])
+                    {
+                        height += 1;
+                    }
+                    let mut width = 1;
+                    while width < MAX_UV_REPEATS
+                        && u + width < size
+                        && (v..v + height).all(|v| matches(&slice[(u + width) * size + v]))
+                    {
+                        width += 1;
+                    }
+
+                    for u in u..u + width {
+                        for v in v..v + height {
+                            slice[u * size + v] = None;
+                        }
+                    }
+
+                    let block = block_states.get_block(first.block as usize);
+                    let face =
+                        block.faces[first.face].rotate_y(block_rotation(block_state(first.block)));
+
+                    let mut position = Vector3::zeros();
+                    position[plane.axis] = depth as f32;
+                    position[a] = u as f32;
+                    position[b] = v as f32;
+
+                    // Stretch the face across the rectangle
+                    let stretch = |mut corner: Vector3<f32>| {
+                        corner[a] *= width as f32;
+                        corner[b] *= height as f32;
+                        position + corner
+                    };
+                    let top_left = stretch(face.top_left);
+                    let top_right = stretch(face.top_right);
+                    let bottom_left = stretch(face.bottom_left);
+
+                    let kit = if block.translucent {
+                        &mut *translucent
+                    } else {
+                        &mut *opaque
+                    };
+                    kit.draw_quad(
+                        [top_left, top_right, bottom_left],
+                        face.normal,
+                        face.texture,
+                        [
+                            (bottom_left - top_left).norm(),
+                            (top_right - top_left).norm(),
+                        ],
+                        first.color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u32 = 6;
+
+    /// Adds the top face of stone across a square layer of the mesher, lit by `color` at each position
+    fn fill_top_faces(
+        mesher: &mut GreedyMesher,
+        states: &BlockStates,
+        size: usize,
+        color: impl Fn(usize, usize) -> LightingColor,
+    ) {
+        let block = states.get_block(STONE as usize);
+        let index = block
+            .faces
+            .iter()
+            .position(|v| v.normal == Vector3::new(0.0, 1.0, 0.0))
+            .unwrap();
+
+        for x in 0..size {
+            for z in 0..size {
+                let added = mesher.add(
+                    Vector3::new(x, 0, z),
+                    STONE,
+                    index,
+                    &block.faces[index],
+                    color(x, z),
+                );
+                assert!(added);
+            }
+        }
+    }
+
+    /// Draws the held faces, which are all opaque
+    fn draw(mesher: GreedyMesher, states: &BlockStates) -> DrawKit {
+        let mut opaque = DrawKit::new();
+        let mut translucent = DrawKit::new();
+        mesher.draw(states, &mut opaque, &mut translucent);
+
+        assert!(translucent.is_empty());
+        opaque
+    }
+
+    #[test]
+    fn matching_layer_merges_into_one_quad() {
+        let states = BlockStates::shipped();
+        let mut mesher = GreedyMesher::new(16, 0);
+        fill_top_faces(&mut mesher, &states, 16, |_, _| [255, 255, 255, 255]);
+
+        let kit = draw(mesher, &states);
+        assert_eq!(kit.indices.len() / 6, 1);
+        assert!(kit.uv_coordinates.contains(&[16.0, 16.0]));
+    }
+
+    #[test]
+    fn merges_stop_at_the_most_a_texture_can_repeat() {
+        let states = BlockStates::shipped();
+        let size = MAX_UV_REPEATS + 16;
+        let mut mesher = GreedyMesher::new(size, 0);
+        fill_top_faces(&mut mesher, &states, size, |_, _| [255, 255, 255, 255]);
+
+        // A full quad in one corner, with the rest split along the limit
+        let kit = draw(mesher, &states);
+        assert_eq!(kit.indices.len() / 6, 4);
+        assert!(kit
+            .uv_coordinates
+            .iter()
+            .flatten()
+            .all(|v| *v <= MAX_UV_REPEATS as f32));
+    }
+
+    /// A layer lit in two halves, the second a little brighter on each channel
+    fn two_tone(x: usize, _: usize) -> LightingColor {
+        if x < 8 {
+            [100, 100, 100, 255]
+        } else {
+            [103, 97, 103, 255]
+        }
+    }
+
+    #[test]
+    fn faces_within_light_tolerance_merge() {
+        let states = BlockStates::shipped();
+        let mut mesher = GreedyMesher::new(16, 3);
+        fill_top_faces(&mut mesher, &states, 16, two_tone);
+
+        assert_eq!(draw(mesher, &states).indices.len() / 6, 1);
+    }
+
+    #[test]
+    fn faces_beyond_light_tolerance_stay_apart() {
+        let states = BlockStates::shipped();
+        let mut mesher = GreedyMesher::new(16, 2);
+        fill_top_faces(&mut mesher, &states, 16, two_tone);
+
+        // One quad for each half, each keeping its own light
+        let kit = draw(mesher, &states);
+        assert_eq!(kit.indices.len() / 6, 2);
+    }
+
+    #[test]
+    fn faces_only_match_the_same_block_and_face() {
+        let face = PendingFace {
+            block: STONE,
+            face: 0,
+            color: [10, 10, 10, 255],
+        };
+
+        assert!(face.matches(&face, 0));
+        assert!(!face.matches(&PendingFace { block: 1, ..face }, 255));
+        assert!(!face.matches(&PendingFace { face: 1, ..face }, 255));
+        assert!(face.matches(
+            &PendingFace {
+                color: [15, 5, 10, 255],
+                ..face
+            },
+            5
+        ));
+        assert!(!face.matches(
+            &PendingFace {
+                color: [16, 10, 10, 255],
+                ..face
+            },
+            5
+        ));
+    }
+}
@@ -2,12 +2,11 @@ use crate::game::blocks::states::BlockStates;
 use crate::game::blocks::Block;
 use crate::game::viewable_direction::BLOCK_SIDES;
 use crate::helpers::{get_chunk_coords, global_to_local_position};
-use crate::systems::chunk::data::{ChunkData, LightingColor, RawLightingData};
+use crate::systems::chunk::data::{ChunkData, ChunkGrid, LightingColor, RawLightingData};
 use crate::systems::chunk::nearby_cache::NearbyChunkCache;
 use bevy::prelude::system_adapter::new;
 use bevy::prelude::Entity;
 use nalgebra::{max, Vector3, Vector4};
-use rc_networking::constants::CHUNK_SIZE;
 use std::collections::VecDeque;
 use std::mem;
 use std::time::Instant;
@@ -25,24 +24,28 @@ impl ChunkData {
         cache: &NearbyChunkCache,
     ) -> LightingUpdateData {
         let start = Instant::now();
+        let size = self.world.size();
 
         let mut lights = get_lights(self.position, states, cache);
 
         if lights.len() == 0 {
             return LightingUpdateData {
-                data: [[[[0 as u8; 4]; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+                data: RawLightingData::new(size, [0; 4]),
             };
         }
 
         let lights_len = lights.len();
 
-        // Rolling average of chunk lighting data
-        let mut data = [[[[0 as u32; 5]; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        // The brightest level of each color channel reaching each block, so lights of different colors
+        // each keep their own color rather than being averaged together
+        let mut out = RawLightingData::new(size, [0; 4]);
 
         // Propagate lighting
         for (light_pos, color) in lights {
-            // a 32 block wide area around the light that tracks what blocks its visited
-            let mut visited = [[[false; CHUNK_SIZE * 2]; CHUNK_SIZE * 2]; CHUNK_SIZE * 2];
+            // a 32 block wide area around the light that tracks what blocks its visited, as far as any light reaches
+            // whatever size the chunks are
+            let mut visited =
+                [[[false; MAX_LIGHT_VALUE * 2]; MAX_LIGHT_VALUE * 2]; MAX_LIGHT_VALUE * 2];
 
             let mut point = VecDeque::with_capacity(1000);
 
@@ -57,11 +60,11 @@ impl ChunkData {
             while !point.is_empty() {
                 let (pos, strength) = point.pop_front().unwrap();
 
-                let (chunk_pos, block_pos) = global_to_local_position(pos);
+                let (chunk_pos, block_pos) = global_to_local_position(pos, size);
 
-                if visited[(pos.x - light_pos.x + CHUNK_SIZE as i32) as usize]
-                    [(pos.y - light_pos.y + CHUNK_SIZE as i32) as usize]
-                    [(pos.z - light_pos.z + CHUNK_SIZE as i32) as usize]
+                if visited[(pos.x - light_pos.x + MAX_LIGHT_VALUE as i32) as usize]
+                    [(pos.y - light_pos.y + MAX_LIGHT_VALUE as i32) as usize]
+                    [(pos.z - light_pos.z + MAX_LIGHT_VALUE as i32) as usize]
                 {
                     continue;
                 }
@@ -70,7 +73,7 @@ impl ChunkData {
                     assert_eq!(v.position, chunk_pos);
 
                     if !states
-                        .get_block(v.world[block_pos.x][block_pos.y][block_pos.z] as usize)
+                        .get_block(v.get_block(block_pos) as usize)
                         .translucent
                     {
                         // Collision, bail
@@ -79,18 +82,17 @@ impl ChunkData {
                 }
 
                 if chunk_pos == self.position {
-                    let current_color =
-                        &mut data[block_pos.x as usize][block_pos.y as usize][block_pos.z as usize];
-                    current_color[0] += strength as u32 * color[0] as u32;
-                    current_color[1] += strength as u32 * color[1] as u32;
-                    current_color[2] += strength as u32 * color[2] as u32;
-                    current_color[3] += strength as u32;
-                    current_color[4] = current_color[4].max(strength as u32);
+                    let current_color = out.get_mut(block_pos.x, block_pos.y, block_pos.z);
+                    let level = channel_levels(color, strength);
+                    for channel in 0..3 {
+                        current_color[channel] = current_color[channel].max(level[channel]);
+                    }
+                    current_color[3] = current_color[3].max(strength);
                 }
 
-                visited[(pos.x - light_pos.x + CHUNK_SIZE as i32) as usize]
-                    [(pos.y - light_pos.y + CHUNK_SIZE as i32) as usize]
-                    [(pos.z - light_pos.z + CHUNK_SIZE as i32) as usize] = true;
+                visited[(pos.x - light_pos.x + MAX_LIGHT_VALUE as i32) as usize]
+                    [(pos.y - light_pos.y + MAX_LIGHT_VALUE as i32) as usize]
+                    [(pos.z - light_pos.z + MAX_LIGHT_VALUE as i32) as usize] = true;
 
                 if strength == 1 {
                     continue;
@@ -99,9 +101,9 @@ impl ChunkData {
                 for side in &BLOCK_SIDES {
                     let new_pos = pos + side;
 
-                    if visited[(new_pos.x - light_pos.x + CHUNK_SIZE as i32) as usize]
-                        [(new_pos.y - light_pos.y + CHUNK_SIZE as i32) as usize]
-                        [(new_pos.z - light_pos.z + CHUNK_SIZE as i32) as usize]
+                    if visited[(new_pos.x - light_pos.x + MAX_LIGHT_VALUE as i32) as usize]
+                        [(new_pos.y - light_pos.y + MAX_LIGHT_VALUE as i32) as usize]
+                        [(new_pos.z - light_pos.z + MAX_LIGHT_VALUE as i32) as usize]
                     {
                         continue;
                     }
@@ -111,39 +113,6 @@ impl ChunkData {
             }
         }
 
-        // Combine strengths
-        let mut out = [[[[0 as u8; 4]; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-
-        for x in 0..CHUNK_SIZE {
-            for y in 0..CHUNK_SIZE {
-                for z in 0..CHUNK_SIZE {
-                    let color = &data[x][y][z];
-
-                    // If there's no lighting data for this block ignore it
-                    if color[3] == 0 {
-                        continue;
-                    }
-
-                    let out_color = &mut out[x][y][z];
-
-                    // Get the proportional color
-                    out_color[0] = (color[0] / color[3]) as u8;
-                    out_color[1] = (color[1] / color[3]) as u8;
-                    out_color[2] = (color[2] / color[3]) as u8;
-
-                    // Light falloff
-                    out_color[0] =
-                        (out_color[0] as u32 * color[4] as u32 / MAX_LIGHT_VALUE as u32) as u8;
-                    out_color[1] =
-                        (out_color[1] as u32 * color[4] as u32 / MAX_LIGHT_VALUE as u32) as u8;
-                    out_color[2] =
-                        (out_color[2] as u32 * color[4] as u32 / MAX_LIGHT_VALUE as u32) as u8;
-
-                    out_color[3] = color[4] as u8;
-                }
-            }
-        }
-
         println!(
             "Took {}ns to render {:?} with {} lights with flood fill",
             start.elapsed().as_nanos(),
@@ -160,16 +129,17 @@ impl ChunkData {
         cache: &NearbyChunkCache,
     ) -> LightingUpdateData {
         let start = Instant::now();
+        let size = self.world.size();
 
-        let mut out = [[[[0 as u8; 4]; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-        let mut collision = [[[false; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        let mut out = RawLightingData::new(size, [0; 4]);
+        let mut collision = ChunkGrid::new(size, false);
 
-        for x in 0..CHUNK_SIZE {
-            for y in 0..CHUNK_SIZE {
-                for z in 0..CHUNK_SIZE {
-                    let block = states.get_block(self.world[x][y][z] as usize);
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let block = states.get_block(self.world.get(x, y, z) as usize);
 
-                    collision[x][y][z] = !block.translucent;
+                    collision.set(x, y, z, !block.translucent);
 
                     if block.emission[3] == 0 {
                         continue;
@@ -177,8 +147,8 @@ impl ChunkData {
 
                     println!("Found emitter at {} {} {}", x, y, z);
 
-                    out[x][y][z] = block.emission;
-                    collision[x][y][z] = true;
+                    out.set(x, y, z, block.emission);
+                    collision.set(x, y, z, true);
                 }
             }
         }
@@ -187,10 +157,10 @@ impl ChunkData {
         while (changed) {
             changed = false;
             //println!("pass");
-            for x in 0..CHUNK_SIZE {
-                for y in 0..CHUNK_SIZE {
-                    for z in 0..CHUNK_SIZE {
-                        if collision[x][y][z] {
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        if collision.get(x, y, z) {
                             continue;
                         }
 
@@ -199,38 +169,35 @@ impl ChunkData {
                         let mut max_strength: u8 = 0;
                         for side in &BLOCK_SIDES {
                             let (chunk_pos, block_pos) = global_to_local_position(
-                                side + Vector3::new(x, y, z).cast::<i32>() + (self.position * 16),
+                                side + Vector3::new(x, y, z).cast::<i32>()
+                                    + (self.position * size as i32),
+                                size,
                             );
+                            let own = out.get(block_pos.x, block_pos.y, block_pos.z);
 
                             // If its this chunk then read this chunks lighting data
                             if chunk_pos == self.position {
                                 avg += Vector4::new(
-                                    out[block_pos.x][block_pos.y][block_pos.z][0] as u32
-                                        * out[block_pos.x][block_pos.y][block_pos.z][3] as u32,
-                                    out[block_pos.x][block_pos.y][block_pos.z][1] as u32
-                                        * out[block_pos.x][block_pos.y][block_pos.z][3] as u32,
-                                    out[block_pos.x][block_pos.y][block_pos.z][2] as u32
-                                        * out[block_pos.x][block_pos.y][block_pos.z][3] as u32,
-                                    out[block_pos.x][block_pos.y][block_pos.z][3] as u32,
+                                    own[0] as u32 * own[3] as u32,
+                                    own[1] as u32 * own[3] as u32,
+                                    own[2] as u32 * own[3] as u32,
+                                    own[3] as u32,
                                 );
-                                max_strength =
-                                    max_strength.max(out[block_pos.x][block_pos.y][block_pos.z][3]);
+                                max_strength = max_strength.max(own[3]);
                                 //println!("{:?} {} X {} {} {}", avg, max_strength, x, y, z);
                                 continue;
                             }
 
                             if let Some(v) = cache.get_chunk(chunk_pos) {
+                                let theirs =
+                                    v.light_levels.get(block_pos.x, block_pos.y, block_pos.z);
                                 avg += Vector4::new(
-                                    v.light_levels[block_pos.x][block_pos.y][block_pos.z][0] as u32
-                                        * out[block_pos.x][block_pos.y][block_pos.z][3] as u32,
-                                    v.light_levels[block_pos.x][block_pos.y][block_pos.z][1] as u32
-                                        * out[block_pos.x][block_pos.y][block_pos.z][3] as u32,
-                                    v.light_levels[block_pos.x][block_pos.y][block_pos.z][2] as u32
-                                        * out[block_pos.x][block_pos.y][block_pos.z][3] as u32,
-                                    v.light_levels[block_pos.x][block_pos.y][block_pos.z][3] as u32,
+                                    theirs[0] as u32 * own[3] as u32,
+                                    theirs[1] as u32 * own[3] as u32,
+                                    theirs[2] as u32 * own[3] as u32,
+                                    theirs[3] as u32,
                                 );
-                                max_strength = max_strength
-                                    .max(v.light_levels[block_pos.x][block_pos.y][block_pos.z][3]);
+                                max_strength = max_strength.max(theirs[3]);
                             }
                         }
 
@@ -245,12 +212,10 @@ impl ChunkData {
 
                         //println!("bb {:?} {}", avg, max_strength);
 
-                        if out[x][y][z]
-                            != [avg.x as u8, avg.y as u8, avg.z as u8, max_strength.min(16)]
-                        {
-                            //println!("modify {:?}", out[x][y][z]);
-                            out[x][y][z] =
-                                [avg.x as u8, avg.y as u8, avg.z as u8, max_strength.min(16)];
+                        let color = [avg.x as u8, avg.y as u8, avg.z as u8, max_strength.min(16)];
+                        if out.get(x, y, z) != color {
+                            //println!("modify {:?}", out.get(x, y, z));
+                            out.set(x, y, z, color);
 
                             //println!("chabgwed");
                             changed = true;
@@ -262,10 +227,10 @@ impl ChunkData {
             //println!("{:?}", out);
         }
 
-        for x in 0..CHUNK_SIZE {
-            for y in 0..CHUNK_SIZE {
-                for z in 0..CHUNK_SIZE {
-                    let out_color = &mut out[x][y][z];
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let out_color = out.get_mut(x, y, z);
 
                     // If there's no lighting data for this block ignore it
                     if out_color[3] == 0 {
@@ -295,6 +260,15 @@ impl ChunkData {
     }
 }
 
+/// How bright each color channel of a light is at a given strength, the full emission color at full strength
+/// fading linearly to nothing. Emission is RGB followed by the strength the light starts at
+pub fn channel_levels(emission: LightingColor, strength: u8) -> [u8; 3] {
+    [0, 1, 2].map(|channel| {
+        (emission[channel] as u32 * strength.min(MAX_LIGHT_VALUE as u8) as u32
+            / MAX_LIGHT_VALUE as u32) as u8
+    })
+}
+
 // Gets all the lights in this chunk and the surrounding chunks
 fn get_lights(
     chunk_pos: Vector3<i32>,
@@ -308,10 +282,11 @@ fn get_lights(
             for chunk_z in (chunk_pos.z - 1)..=(chunk_pos.z + 1) {
                 // Get chunk
                 if let Some(chunk) = cache.get_chunk(Vector3::new(chunk_x, chunk_y, chunk_z)) {
-                    for x in 0..CHUNK_SIZE {
-                        for y in 0..CHUNK_SIZE {
-                            for z in 0..CHUNK_SIZE {
-                                let block = states.get_block(chunk.world[x][y][z] as usize);
+                    let size = chunk.world.size();
+                    for x in 0..size {
+                        for y in 0..size {
+                            for z in 0..size {
+                                let block = states.get_block(chunk.world.get(x, y, z) as usize);
 
                                 if block.emission[3] == 0 {
                                     continue;
@@ -319,7 +294,7 @@ fn get_lights(
 
                                 lights.push((
                                     Vector3::new(x, y, z).cast::<i32>()
-                                        + (CHUNK_SIZE as i32 * chunk.position),
+                                        + (size as i32 * chunk.position),
                                     block.emission,
                                 ));
                             }
@@ -332,3 +307,129 @@ fn get_lights(
 
     lights
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkEdgeFaces;
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use crate::systems::chunk::ChunkSystem;
+
+    const RED_LAMP: u32 = 6;
+    const GREEN_LAMP: u32 = 1;
+    const GRASS: u32 = 2;
+
+    /// Block states where stone is a red lamp and dirt a green one, both starting at strength 8
+    fn states() -> BlockStates {
+        let mut states = BlockStates::shipped();
+        states.states[RED_LAMP as usize].emission = [255, 0, 0, 8];
+        states.states[GREEN_LAMP as usize].emission = [0, 255, 0, 8];
+        states
+    }
+
+    fn light(lamps: &[([usize; 3], u32)]) -> RawLightingData {
+        light_sized(CHUNK_SIZE, lamps)
+    }
+
+    fn light_sized(size: usize, lamps: &[([usize; 3], u32)]) -> RawLightingData {
+        let mut data = RawChunkData::new(size);
+        for ([x, y, z], lamp) in lamps {
+            data.set(*x, *y, *z, *lamp);
+        }
+
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        system
+            .chunks
+            .insert(Vector3::zeros(), test_chunk(Vector3::zeros(), data));
+
+        let cache = NearbyChunkCache::from_service(&system, Vector3::zeros());
+        system.chunks[&Vector3::zeros()]
+            .build_lighting(&states(), &cache)
+            .data
+    }
+
+    #[test]
+    fn levels_fade_with_strength() {
+        let emission = [255, 128, 0, 16];
+
+        assert_eq!(channel_levels(emission, 16), [255, 128, 0]);
+        assert_eq!(channel_levels(emission, 8), [127, 64, 0]);
+        assert_eq!(channel_levels(emission, 0), [0, 0, 0]);
+        assert_eq!(channel_levels(emission, 20), [255, 128, 0]);
+    }
+
+    #[test]
+    fn red_light_only_raises_red() {
+        let light = light(&[([8, 8, 8], RED_LAMP)]);
+
+        // One block away is strength 7, falling by one each block
+        assert_eq!(light.get(9, 8, 8), [111, 0, 0, 7]);
+        assert_eq!(light.get(8, 6, 8), [95, 0, 0, 6]);
+        assert_eq!(light.get(8, 8, 13), [47, 0, 0, 3]);
+        assert_eq!(light.get(9, 9, 9), [79, 0, 0, 5]);
+
+        // Light runs out after 7 blocks
+        assert_eq!(light.get(8, 8, 15), [15, 0, 0, 1]);
+        assert_eq!(light.get(8, 8, 0), [0, 0, 0, 0]);
+
+        for cell in light.cells() {
+            assert_eq!((cell[1], cell[2]), (0, 0));
+        }
+    }
+
+    #[test]
+    fn colors_are_kept_apart_rather_than_averaged() {
+        let light = light(&[([4, 8, 8], RED_LAMP), ([12, 8, 8], GREEN_LAMP)]);
+
+        // Halfway between, each lamp reaches at strength 4
+        assert_eq!(light.get(8, 8, 8), [63, 63, 0, 4]);
+
+        // Nearer the red lamp, red is brighter but green is still its own level
+        assert_eq!(light.get(6, 8, 8), [95, 31, 0, 6]);
+    }
+
+    #[test]
+    fn solid_blocks_stop_light() {
+        let mut walled = vec![([8, 8, 8], RED_LAMP)];
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                walled.push(([10, y, z], GRASS));
+            }
+        }
+        let light = light(&walled);
+
+        assert_eq!(light.get(9, 8, 8), [111, 0, 0, 7]);
+        assert_eq!(light.get(11, 8, 8), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn chunks_of_other_sizes_are_lit_at_their_size() {
+        let light = light_sized(24, &[([20, 8, 8], RED_LAMP)]);
+
+        assert_eq!(light.size(), 24);
+        assert_eq!(light.get(23, 8, 8), [79, 0, 0, 5]);
+        assert_eq!(light.get(20, 8, 1), [15, 0, 0, 1]);
+        assert_eq!(light.get(12, 8, 8), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn lamps_light_the_section_above() {
+        let mut below = RawChunkData::new(CHUNK_SIZE);
+        below.set(8, CHUNK_SIZE - 1, 8, RED_LAMP);
+
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        for (position, data) in [(0, below), (1, RawChunkData::new(CHUNK_SIZE))] {
+            let position = Vector3::new(0, position, 0);
+            system.chunks.insert(position, test_chunk(position, data));
+        }
+
+        let above = Vector3::new(0, 1, 0);
+        let cache = NearbyChunkCache::from_service(&system, above);
+        let light = system.chunks[&above].build_lighting(&states(), &cache).data;
+
+        // The lamp sits just under the section, so its light carries on up through the boundary
+        assert_eq!(light.get(8, 0, 8), [111, 0, 0, 7]);
+        assert_eq!(light.get(8, 2, 8), [79, 0, 0, 5]);
+        assert_eq!(light.get(8, 7, 8), [0, 0, 0, 0]);
+    }
+}
@@ -1,13 +1,15 @@
 mod entry;
 mod generate_mesh;
+mod greedy;
 mod lighting;
 
 use crate::game::blocks::states::BlockStates;
 use crate::helpers::from_bevy_vec3;
-use crate::systems::chunk::builder::entry::{MeshBuildEntry, PLAYER_POS};
+use crate::systems::chunk::builder::entry::MeshBuildEntry;
 use crate::systems::chunk::builder::generate_mesh::UpdateChunkMesh;
 use crate::systems::chunk::builder::lighting::LightingUpdateData;
 use crate::systems::chunk::nearby_cache::NearbyChunkCache;
+use crate::systems::chunk::priority::chunk_priority;
 use crate::systems::chunk::ChunkSystem;
 use bevy::prelude::*;
 use bevy::render::mesh::{Indices, MeshVertexAttribute, PrimitiveTopology, VertexAttributeValues};
@@ -15,10 +17,12 @@ use bevy::render::render_resource::VertexFormat;
 use nalgebra::Vector3;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use std::collections::BinaryHeap;
-use std::sync::atomic::Ordering;
 
 pub const ATTRIBUTE_LIGHTING_COLOR: MeshVertexAttribute =
     MeshVertexAttribute::new("Lighting", 988540917, VertexFormat::Float32x4);
+/// The part of the atlas a face is textured with, which its texture coordinates repeat across
+pub const ATTRIBUTE_TEXTURE_RECT: MeshVertexAttribute =
+    MeshVertexAttribute::new("TextureRect", 988540923, VertexFormat::Float32x4);
 
 pub struct RerenderChunkFlag {
     pub chunk: Vector3<i32>,
@@ -38,6 +42,33 @@ pub enum RerenderChunkFlagContext {
 pub struct MeshBuilderCache {
     // A priority list of chunks to build
     chunks: BinaryHeap<MeshBuildEntry>,
+    // Where the camera was last frame, used to tell if the camera is moving
+    last_camera_position: Option<Vec3>,
+    // The chunk the camera was in when the queue was last prioritised
+    prioritised_chunk: Option<Vector3<i32>>,
+}
+
+impl MeshBuilderCache {
+    /// Updates the priorities of the chunks waiting to be built once the camera has moved into another chunk,
+    /// returning whether it did. Within a chunk the order barely changes, so it isn't worth rebuilding the queue
+    /// every frame
+    fn prioritise(&mut self, camera_chunk: Vector3<f32>, forward: Option<Vector3<f32>>) -> bool {
+        let current = camera_chunk.map(|v| v.floor() as i32);
+        if self.prioritised_chunk == Some(current) {
+            return false;
+        }
+        self.prioritised_chunk = Some(current);
+
+        let queued = std::mem::take(&mut self.chunks);
+        self.chunks = queued
+            .into_iter()
+            .map(|mut entry| {
+                entry.priority = chunk_priority(entry.chunk, camera_chunk, forward);
+                entry
+            })
+            .collect();
+        true
+    }
 }
 
 pub fn mesh_builder(
@@ -49,11 +80,17 @@ pub fn mesh_builder(
     mut builder_data: Local<MeshBuilderCache>,
     mut commands: Commands,
 ) {
-    // Update player location
-    let pos = from_bevy_vec3(camera.single().translation);
-    PLAYER_POS[0].store(pos.x as i32, Ordering::Relaxed);
-    PLAYER_POS[1].store(pos.y as i32, Ordering::Relaxed);
-    PLAYER_POS[2].store(pos.z as i32, Ordering::Relaxed);
+    // Prioritise chunks in view while the camera is moving, otherwise just build the closest chunks first
+    let camera = camera.single();
+    let camera_chunk = from_bevy_vec3(camera.translation) / chunks.chunk_size as f32;
+    let forward = if builder_data.last_camera_position != Some(camera.translation) {
+        Some(from_bevy_vec3(camera.rotation * Vec3::new(0.0, 0.0, -1.0)))
+    } else {
+        None
+    };
+    builder_data.last_camera_position = Some(camera.translation);
+
+    builder_data.prioritise(camera_chunk, forward);
 
     let mut rerender_chunks = Vec::new();
 
@@ -88,13 +125,16 @@ pub fn mesh_builder(
     // Loop over all new chunks to render and add them to the list if the chunk exists and if its not already being rerendered
     for pos in rerender_chunks {
         // The chunk data exists
-        if let Some(data) = chunks.chunks.get(&pos) {
+        if let Some(data) = chunks.chunks.get_mut(&pos) {
+            data.dirty = true;
+
             // And if this chunk isn't already scheduled for rebuild
             if !builder_data.chunks.iter().any(|v| v.chunk == pos) {
                 // Put entry into rebuild table
                 builder_data.chunks.push(MeshBuildEntry {
                     entity: data.entity.clone(),
                     chunk: pos,
+                    priority: chunk_priority(pos, camera_chunk, forward),
                 });
             }
         }
@@ -148,7 +188,14 @@ pub fn mesh_builder(
                 let cache = NearbyChunkCache::from_service(&chunks, chunk.position);
                 // Generate mesh & gpu buffers
                 Some((
-                    chunk.build_mesh(&chunks, &block_states, true, &cache),
+                    chunk.build_mesh(
+                        &chunks,
+                        &block_states,
+                        chunks.edge_faces.draws_unloaded_faces(),
+                        chunks.mesh_reserve_scale,
+                        chunks.greedy_meshing,
+                        &cache,
+                    ),
                     &chunk.opaque_mesh,
                     &chunk.translucent_mesh,
                 ))
@@ -159,11 +206,109 @@ pub fn mesh_builder(
         })
         .collect::<Vec<Option<(UpdateChunkMesh, &Handle<Mesh>, &Handle<Mesh>)>>>();
 
+    let mut built = Vec::with_capacity(updates.len());
+
     for update in updates {
         if let Some((val, opaque_mesh, translucent_mesh)) = update {
+            built.push(val.chunk);
+
+            // Fit the culling bounds to the new meshes, and don't draw meshes with nothing in them.
+            // The translucent mesh is a child of the opaque one so that stays visible while either has something.
+            // Chunks are spawned hidden, so this is also where a new chunk is first drawn
+            if let Some(chunk) = chunks.chunks.get(&val.chunk) {
+                commands.entity(chunk.entity).insert((
+                    val.opaque.aabb(),
+                    Visibility {
+                        is_visible: !val.opaque.is_empty() || !val.translucent.is_empty(),
+                    },
+                ));
+                commands.entity(chunk.translucent_entity).insert((
+                    val.translucent.aabb(),
+                    Visibility {
+                        is_visible: !val.translucent.is_empty(),
+                    },
+                ));
+            }
+
             val.opaque.apply_mesh(meshes.get_mut(opaque_mesh).unwrap());
             val.translucent
                 .apply_mesh(meshes.get_mut(translucent_mesh).unwrap());
         }
     }
+
+    for pos in built {
+        if let Some(chunk) = chunks.chunks.get_mut(&pos) {
+            chunk.dirty = false;
+            chunk.meshed = true;
+        }
+    }
+
+    chunks.pending_builds = builder_data.chunks.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(chunks: &[Vector3<i32>]) -> MeshBuilderCache {
+        let mut cache = MeshBuilderCache::default();
+        for chunk in chunks {
+            cache.chunks.push(MeshBuildEntry {
+                entity: Entity::from_raw(0),
+                chunk: *chunk,
+                priority: 0.0,
+            });
+        }
+        cache
+    }
+
+    fn build_order(mut cache: MeshBuilderCache) -> Vec<Vector3<i32>> {
+        let mut order = Vec::new();
+        while let Some(entry) = cache.chunks.pop() {
+            order.push(entry.chunk);
+        }
+        order
+    }
+
+    #[test]
+    fn queue_is_prioritised_when_entering_a_chunk() {
+        let near = Vector3::new(1, 0, 0);
+        let far = Vector3::new(8, 0, 0);
+        let mut cache = queue(&[far, near]);
+
+        assert!(cache.prioritise(Vector3::new(0.5, 0.5, 0.5), None));
+        // Moving within the same chunk leaves the queue alone
+        assert!(!cache.prioritise(Vector3::new(0.9, 0.1, 0.5), None));
+
+        assert_eq!(build_order(cache), vec![near, far]);
+    }
+
+    #[test]
+    fn queue_is_prioritised_again_in_a_new_chunk() {
+        let near = Vector3::new(1, 0, 0);
+        let far = Vector3::new(8, 0, 0);
+        let mut cache = queue(&[far, near]);
+        cache.prioritise(Vector3::new(0.5, 0.5, 0.5), None);
+
+        assert!(cache.prioritise(Vector3::new(8.5, 0.5, 0.5), None));
+        assert_eq!(build_order(cache), vec![far, near]);
+    }
+
+    #[test]
+    fn entries_with_the_same_priority_are_ordered_by_chunk() {
+        let a = Vector3::new(0, 0, 1);
+        let b = Vector3::new(0, 1, 0);
+        let c = Vector3::new(1, 0, 0);
+
+        assert_eq!(build_order(queue(&[c, a, b])), vec![a, b, c]);
+        assert_eq!(build_order(queue(&[b, c, a])), vec![a, b, c]);
+
+        let entry = |chunk| MeshBuildEntry {
+            entity: Entity::from_raw(0),
+            chunk,
+            priority: 0.0,
+        };
+        assert!(entry(a) != entry(b));
+        assert!(entry(a) == entry(a));
+    }
 }
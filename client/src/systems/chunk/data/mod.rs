@@ -3,7 +3,8 @@ use bevy::ecs::component::Component;
 use bevy::prelude::{Entity, Handle, Mesh};
 
 use nalgebra::Vector3;
-use rc_networking::constants::CHUNK_SIZE;
+use rc_networking::constants::block_id;
+use rc_networking::protocol::clientbound::fill_region::ChunkSpan;
 
 pub mod viewable;
 
@@ -12,39 +13,150 @@ pub struct ChunkData {
     pub position: Vector3<i32>,
 
     pub entity: Entity,
+    pub translucent_entity: Entity,
 
     pub opaque_mesh: Handle<Mesh>,
     pub translucent_mesh: Handle<Mesh>,
 
     pub world: RawChunkData,
 
-    pub viewable_map: Option<[[[ViewableDirection; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>,
+    pub viewable_map: Option<ViewableMap>,
 
     // Stores the lighting intensity and color map
     pub light_levels: RawLightingData,
+
+    /// Whether every block is air, so there's nothing to mesh
+    empty: bool,
+
+    /// Whether the mesh is out of date and waiting to be rebuilt
+    pub dirty: bool,
+    /// Whether a mesh has been built since the chunk loaded. Until then its meshes are empty placeholders
+    /// and the chunk is left out of rendering
+    pub meshed: bool,
 }
 
 impl ChunkData {
     pub fn new(
         data: RawChunkData,
         entity: Entity,
+        translucent_entity: Entity,
         position: Vector3<i32>,
         opaque_mesh: Handle<Mesh>,
         translucent_mesh: Handle<Mesh>,
     ) -> ChunkData {
         ChunkData {
+            empty: is_all_air(&data),
+            light_levels: RawLightingData::new(data.size(), [255, 255, 255, 255]),
+            dirty: true,
+            meshed: false,
             world: data,
             viewable_map: None,
             position,
-            light_levels: [[[[255, 255, 255, 255]; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
             entity,
+            translucent_entity,
             opaque_mesh,
             translucent_mesh,
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.empty
+    }
+
+    pub fn get_block(&self, position: Vector3<usize>) -> u32 {
+        self.world.get(position.x, position.y, position.z)
+    }
+
+    /// Sets a block within the chunk, keeping track of whether the chunk is empty
+    pub fn set_block(&mut self, position: Vector3<usize>, block: u32) {
+        self.world.set(position.x, position.y, position.z, block);
+
+        self.empty = if block_id(block) == 0 {
+            self.empty || is_all_air(&self.world)
+        } else {
+            false
+        };
+    }
+
+    /// Fills part of the chunk with one block, keeping track of whether the chunk is empty
+    pub fn fill(&mut self, span: &ChunkSpan, block: u32) {
+        span.fill(&mut self.world, block);
+
+        self.empty = if block_id(block) == 0 {
+            self.empty || is_all_air(&self.world)
+        } else {
+            false
+        };
+    }
+}
+
+/// A chunk with no entities or meshes behind it, for tests that only need its blocks
+#[cfg(test)]
+pub fn test_chunk(position: Vector3<i32>, data: RawChunkData) -> ChunkData {
+    ChunkData::new(
+        data,
+        Entity::from_raw(0),
+        Entity::from_raw(1),
+        position,
+        Handle::default(),
+        Handle::default(),
+    )
+}
+
+pub fn is_all_air(data: &RawChunkData) -> bool {
+    data.blocks().iter().all(|v| block_id(*v) == 0)
+}
+
+pub use rc_networking::constants::RawChunkData;
+
+/// A value for every block in a chunk, stored flat in the same x, y, z order as the chunk's blocks so it's
+/// as big as whatever size of chunk it's for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkGrid<T> {
+    size: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Copy> ChunkGrid<T> {
+    /// A grid for a chunk `size` blocks wide with every block set to `value`
+    pub fn new(size: usize, value: T) -> ChunkGrid<T> {
+        ChunkGrid {
+            size,
+            cells: vec![value; size * size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        debug_assert!(x < self.size && y < self.size && z < self.size);
+        (x * self.size + y) * self.size + z
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> T {
+        self.cells[self.index(x, y, z)]
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize, z: usize) -> &mut T {
+        let index = self.index(x, y, z);
+        &mut self.cells[index]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: T) {
+        *self.get_mut(x, y, z) = value;
+    }
+
+    /// Every value in the grid, in x, y, z order
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
 }
 
-pub type RawChunkData = [[[u32; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-pub type RawLightingData = [[[LightingColor; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+pub type RawLightingData = ChunkGrid<LightingColor>;
+
+/// Which sides of each block in a chunk can be seen
+pub type ViewableMap = ChunkGrid<ViewableDirection>;
 
 pub type LightingColor = [u8; 4];
@@ -1,16 +1,16 @@
 use crate::game::blocks::states::BlockStates;
 use crate::game::viewable_direction::{
-    calculate_viewable, ViewableDirection, ViewableDirectionBitMap,
+    calculate_viewable, should_draw_face, ViewableDirection, ViewableDirectionBitMap,
 };
-use crate::systems::chunk::data::ChunkData;
+use crate::systems::chunk::data::{ChunkData, ViewableMap};
 use fnv::FnvBuildHasher;
 use nalgebra::Vector3;
-use rc_networking::constants::CHUNK_SIZE;
 use std::collections::HashMap;
 
 impl<'a> ChunkData {
     /*
-       This is a complex function that needs to take the xyz of the block position and the direction to create a value
+       This is a complex function that needs to take the xyz of the block position and the direction to create a value.
+       The examples are for a chunk 16 blocks wide, 15 is the last block along an axis
 
        15, 15, 15  - 1, 0, 0   = 0, 15, 15
        15, 15, 15  - 0, 0, 1   = 15, 15, 0
@@ -37,8 +37,10 @@ impl<'a> ChunkData {
         block_states: &BlockStates,
         adjacent_chunks: HashMap<Vector3<i32>, Option<&ChunkData>, FnvBuildHasher>,
         chunk_edge_faces: bool,
-    ) -> [[[ViewableDirection; 16]; 16]; 16] {
-        let mut data = [[[ViewableDirection(0); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+    ) -> ViewableMap {
+        let size = self.world.size();
+        let last = size - 1;
+        let mut data = ViewableMap::new(size, ViewableDirection(0));
 
         let directions: [Vector3<i32>; 6] = [
             Vector3::new(1, 0, 0),
@@ -49,20 +51,20 @@ impl<'a> ChunkData {
             Vector3::new(0, 0, -1),
         ];
 
-        for x in 0..self.world.len() {
-            for z in 0..self.world[0][0].len() {
-                for y in 0..self.world[0].len() {
-                    let block = block_states.get_block(self.world[x][y][z] as usize);
+        for x in 0..size {
+            for z in 0..size {
+                for y in 0..size {
+                    let block = block_states.get_block(self.world.get(x, y, z) as usize);
 
                     let mut viewable = calculate_viewable(block_states, &self, &block, [x, y, z]);
 
                     for direction in directions.iter() {
                         // Calculates if block is bordering on this direction
-                        if (direction.x == 1 && x == 15)
+                        if (direction.x == 1 && x == last)
                             || (direction.x == -1 && x == 0)
-                            || (direction.y == 1 && y == 15)
+                            || (direction.y == 1 && y == last)
                             || (direction.y == -1 && y == 0)
-                            || (direction.z == 1 && z == 15)
+                            || (direction.z == 1 && z == last)
                             || (direction.z == -1 && z == 0)
                         {
                             // Make it so we get the block on the other chunk closest to our block
@@ -72,35 +74,34 @@ impl<'a> ChunkData {
                                 } else if direction.x == 1 {
                                     0
                                 } else {
-                                    15
+                                    last
                                 },
                                 if direction.y == 0 {
                                     y
                                 } else if direction.y == 1 {
                                     0
                                 } else {
-                                    15
+                                    last
                                 },
                                 if direction.z == 0 {
                                     z
                                 } else if direction.z == 1 {
                                     0
                                 } else {
-                                    15
+                                    last
                                 },
                             );
 
                             // Checks if the block in an adjacent chunk is transparent
                             if let Some(Some(chunk)) = adjacent_chunks.get(&direction) {
-                                let block = {
-                                    let block_id =
-                                        chunk.world[block_pos.x][block_pos.y][block_pos.z];
+                                let neighbour = {
+                                    let block_id = chunk.get_block(block_pos);
 
                                     block_states.get_block(block_id as usize)
                                 };
 
-                                // Check if face visible
-                                if block.translucent {
+                                // Check if face visible, using the same culling rules as within the chunk
+                                if should_draw_face(block, neighbour) {
                                     viewable.add_flag(ViewableDirectionBitMap::from(direction));
                                 }
                             } else if chunk_edge_faces {
@@ -109,7 +110,7 @@ impl<'a> ChunkData {
                         }
                     }
 
-                    data[x][y][z] = viewable;
+                    data.set(x, y, z, viewable);
                 }
             }
         }
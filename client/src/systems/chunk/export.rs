@@ -0,0 +1,223 @@
+use crate::game::blocks::states::BlockStates;
+use crate::helpers::{from_bevy_vec3, global_to_local_position};
+use crate::systems::chunk::mesh::vertex::UnpackedVertices;
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::physics::raycasts::do_raycast;
+use bevy::prelude::*;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Key that exports the mesh of the targeted chunk
+pub const MESH_EXPORT_KEY: KeyCode = KeyCode::F8;
+
+const EXPORT_DIRECTORY: &str = "mesh-exports";
+
+/// How far away a block can be targeted for export
+const EXPORT_REACH: f32 = 64.0;
+
+/// Appends a mesh to an OBJ file as its own object. Lighting is written as vertex colours after each position,
+/// an extension most tools (such as Blender and MeshLab) read.
+/// `first_vertex` is how many vertices are already in the file, as OBJ indices count across every object
+pub fn write_obj_object(out: &mut String, name: &str, mesh: &Mesh, first_vertex: usize) -> usize {
+    let vertices = match UnpackedVertices::from_mesh(mesh) {
+        Some(val) => val,
+        None => return 0,
+    };
+    let positions = &vertices.positions;
+    let normals = vertices.normals.as_ref();
+    let uvs = vertices.atlas_uvs();
+    let uvs = uvs.as_ref();
+    let lighting = vertices.lighting.as_ref();
+
+    let _ = writeln!(out, "o {}", name);
+
+    for (i, [x, y, z]) in positions.iter().enumerate() {
+        match lighting.and_then(|v| v.get(i)) {
+            Some([r, g, b, _]) => {
+                let _ = writeln!(out, "v {} {} {} {} {} {}", x, y, z, r, g, b);
+            }
+            None => {
+                let _ = writeln!(out, "v {} {} {}", x, y, z);
+            }
+        }
+    }
+    for [u, v] in uvs.into_iter().flatten() {
+        // OBJ texture coordinates start from the bottom rather than the top
+        let _ = writeln!(out, "vt {} {}", u, 1.0 - v);
+    }
+    for [x, y, z] in normals.into_iter().flatten() {
+        let _ = writeln!(out, "vn {} {} {}", x, y, z);
+    }
+
+    // OBJ indices start at 1
+    let vertex = |i: usize| {
+        let i = first_vertex + i + 1;
+        match (uvs.is_some(), normals.is_some()) {
+            (true, true) => format!("{}/{}/{}", i, i, i),
+            (true, false) => format!("{}/{}", i, i),
+            (false, true) => format!("{}//{}", i, i),
+            (false, false) => format!("{}", i),
+        }
+    };
+
+    if let Some(indices) = mesh.indices() {
+        let indices = indices.iter().collect::<Vec<usize>>();
+        for triangle in indices.chunks_exact(3) {
+            let _ = writeln!(
+                out,
+                "f {} {} {}",
+                vertex(triangle[0]),
+                vertex(triangle[1]),
+                vertex(triangle[2])
+            );
+        }
+    }
+
+    positions.len()
+}
+
+/// Writes the meshes of the chunk being looked at to an OBJ file, for inspecting what the mesher built
+pub fn export_chunk_mesh(
+    keys: Res<Input<KeyCode>>,
+    camera: Query<&Transform, With<Camera>>,
+    chunks: Res<ChunkSystem>,
+    blocks: Res<BlockStates>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    if !keys.just_pressed(MESH_EXPORT_KEY) {
+        return;
+    }
+
+    let camera = match camera.get_single() {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+
+    // Export the targeted chunk, or the one the camera is in when looking at nothing
+    let look = camera.rotation * Vec3::new(0.0, 0.0, -1.0);
+    let target = match do_raycast(
+        from_bevy_vec3(camera.translation),
+        from_bevy_vec3(look),
+        EXPORT_REACH,
+        &chunks,
+        &blocks,
+    ) {
+        Some(ray) => ray.block,
+        None => from_bevy_vec3(camera.translation).map(|v| v.floor() as i32),
+    };
+    let (chunk_loc, _) = global_to_local_position(target, chunks.chunk_size);
+
+    let chunk = match chunks.chunks.get(&chunk_loc) {
+        Some(val) => val,
+        None => {
+            warn!("No chunk loaded at {:?} to export", chunk_loc);
+            return;
+        }
+    };
+
+    let mut obj = String::new();
+    let _ = writeln!(
+        obj,
+        "# Chunk [{}, {}, {}]",
+        chunk_loc.x, chunk_loc.y, chunk_loc.z
+    );
+
+    let mut vertices = 0;
+    for (name, handle) in [
+        ("opaque", &chunk.opaque_mesh),
+        ("translucent", &chunk.translucent_mesh),
+    ] {
+        if let Some(mesh) = meshes.get(handle) {
+            vertices += write_obj_object(&mut obj, name, mesh, vertices);
+        }
+    }
+
+    let path = Path::new(EXPORT_DIRECTORY).join(format!(
+        "chunk_{}_{}_{}.obj",
+        chunk_loc.x, chunk_loc.y, chunk_loc.z
+    ));
+
+    match fs::create_dir_all(EXPORT_DIRECTORY).and_then(|_| fs::write(&path, obj)) {
+        Ok(_) => info!("Exported {} vertices to {:?}", vertices, path),
+        Err(err) => error!("Failed to export chunk mesh: {:?}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::chunk::builder::{ATTRIBUTE_LIGHTING_COLOR, ATTRIBUTE_TEXTURE_RECT};
+    use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+
+    /// A single triangle, textured from a quarter of the atlas
+    fn triangle() -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 3]);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+        );
+        mesh.insert_attribute(
+            ATTRIBUTE_TEXTURE_RECT,
+            VertexAttributeValues::Float32x4(vec![[0.5, 0.25, 0.75, 0.5]; 3]),
+        );
+        mesh.insert_attribute(
+            ATTRIBUTE_LIGHTING_COLOR,
+            VertexAttributeValues::Float32x4(vec![[1.0, 0.5, 0.0, 1.0]; 3]),
+        );
+        mesh.set_indices(Some(Indices::U32(vec![0, 1, 2])));
+        mesh
+    }
+
+    #[test]
+    fn writes_a_known_mesh() {
+        let mut out = String::new();
+        assert_eq!(write_obj_object(&mut out, "opaque", &triangle(), 0), 3);
+
+        assert_eq!(
+            out,
+            "o opaque\n\
+             v 0 0 0 1 0.5 0\n\
+             v 1 0 0 1 0.5 0\n\
+             v 0 1 0 1 0.5 0\n\
+             vt 0.5 0.75\n\
+             vt 0.75 0.75\n\
+             vt 0.5 0.5\n\
+             vn 0 0 1\n\
+             vn 0 0 1\n\
+             vn 0 0 1\n\
+             f 1/1/1 2/2/2 3/3/3\n"
+        );
+    }
+
+    #[test]
+    fn later_objects_count_on_from_earlier_vertices() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        );
+        mesh.set_indices(Some(Indices::U32(vec![0, 1, 2])));
+
+        let mut out = String::new();
+        write_obj_object(&mut out, "translucent", &mesh, 3);
+
+        // Only positions, so the faces reference nothing else
+        assert!(out.ends_with("f 4 5 6\n"));
+        assert!(!out.contains("vt") && !out.contains("vn"));
+    }
+
+    #[test]
+    fn meshes_without_positions_write_nothing() {
+        let mut out = String::new();
+        let mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+        assert_eq!(write_obj_object(&mut out, "opaque", &mesh, 0), 0);
+        assert!(out.is_empty());
+    }
+}
@@ -1,8 +1,15 @@
-use crate::systems::chunk::builder::ATTRIBUTE_LIGHTING_COLOR;
+use crate::systems::asset::atlas::index::TextureAtlasIndex;
+use crate::systems::chunk::builder::{ATTRIBUTE_LIGHTING_COLOR, ATTRIBUTE_TEXTURE_RECT};
 use crate::systems::chunk::data::LightingColor;
 use crate::systems::chunk::mesh::face::Face;
-use bevy::prelude::Mesh;
+use crate::systems::chunk::mesh::vertex::{
+    pack_lighting, pack_normal, pack_position, pack_texture_rect, pack_uv,
+    ATTRIBUTE_PACKED_LIGHTING, ATTRIBUTE_PACKED_NORMAL, ATTRIBUTE_PACKED_POSITION,
+    ATTRIBUTE_PACKED_TEXTURE_RECT, ATTRIBUTE_PACKED_UV, PACKED_VERTICES,
+};
+use bevy::prelude::{Mesh, Vec3};
 use bevy::render::mesh::{Indices, MeshVertexAttribute, VertexAttributeValues};
+use bevy::render::primitives::Aabb;
 use nalgebra::Vector3;
 
 /// Stores all objects allowing for more ergonomic drawing of objects
@@ -10,7 +17,10 @@ pub struct DrawKit {
     pub positions: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
     pub normals: Vec<[f32; 3]>,
+    /// How far across the face's texture each vertex is, going past 1 where the texture repeats
     pub uv_coordinates: Vec<[f32; 2]>,
+    /// The part of the atlas the face is textured with, as u and v at the start followed by u and v at the end
+    pub texture_rects: Vec<[f32; 4]>,
     pub lighting: Vec<[f32; 4]>,
 }
 
@@ -21,23 +31,59 @@ impl DrawKit {
             indices: vec![],
             normals: vec![],
             uv_coordinates: vec![],
+            texture_rects: vec![],
             lighting: vec![],
         }
     }
 
+    /// Makes room for a number of faces up front, so drawing them doesn't reallocate
+    pub fn with_capacity(faces: usize) -> DrawKit {
+        DrawKit {
+            positions: Vec::with_capacity(faces * 4),
+            indices: Vec::with_capacity(faces * 6),
+            normals: Vec::with_capacity(faces * 4),
+            uv_coordinates: Vec::with_capacity(faces * 4),
+            texture_rects: Vec::with_capacity(faces * 4),
+            lighting: Vec::with_capacity(faces * 4),
+        }
+    }
+
+    /// Whether nothing has been drawn
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
     pub fn draw_face(&mut self, position: Vector3<f32>, face: &Face, color: LightingColor) {
-        let center = (face.top_right + face.bottom_left) / 2.0;
+        self.draw_quad(
+            [
+                position + face.top_left,
+                position + face.top_right,
+                position + face.bottom_left,
+            ],
+            face.normal,
+            face.texture,
+            [1.0, 1.0],
+            color,
+        );
+    }
+
+    /// Draws a quad from three of its corners, with the top left opposite the bottom right that's left out.
+    /// The texture repeats the given number of times from the top left to the bottom left and to the top right
+    pub fn draw_quad(
+        &mut self,
+        [top_left, top_right, bottom_left]: [Vector3<f32>; 3],
+        normal: Vector3<f32>,
+        texture: TextureAtlasIndex,
+        [repeat_u, repeat_v]: [f32; 2],
+        color: LightingColor,
+    ) {
+        let center = (top_right + bottom_left) / 2.0;
 
-        let bottom_right = center + (center - face.top_left);
+        let bottom_right = center + (center - top_left);
 
         let indices_index = self.positions.len() as u32;
 
-        let mut pos = [
-            position + face.top_left,
-            position + face.top_right,
-            position + face.bottom_left,
-            position + bottom_right,
-        ];
+        let pos = [top_left, top_right, bottom_left, bottom_right];
 
         let color = [
             color[0] as f32 / 255.0,
@@ -46,21 +92,19 @@ impl DrawKit {
             color[3] as f32 / 255.0,
         ];
 
+        let rect = [texture.u_min, texture.v_min, texture.u_max, texture.v_max];
+
         for pos in pos {
             self.positions.push([pos.x, pos.y, pos.z]);
-            self.normals
-                .push([face.normal.x, face.normal.y, face.normal.z]);
+            self.normals.push([normal.x, normal.y, normal.z]);
+            self.texture_rects.push(rect);
             self.lighting.push(color);
         }
 
-        self.uv_coordinates
-            .push([face.texture.u_min, face.texture.v_max]);
-        self.uv_coordinates
-            .push([face.texture.u_min, face.texture.v_min]);
-        self.uv_coordinates
-            .push([face.texture.u_max, face.texture.v_max]);
-        self.uv_coordinates
-            .push([face.texture.u_max, face.texture.v_min]);
+        self.uv_coordinates.push([0.0, repeat_v]);
+        self.uv_coordinates.push([0.0, 0.0]);
+        self.uv_coordinates.push([repeat_u, repeat_v]);
+        self.uv_coordinates.push([repeat_u, 0.0]);
 
         self.indices.push(indices_index + 1);
         self.indices.push(indices_index + 0);
@@ -70,11 +114,71 @@ impl DrawKit {
         self.indices.push(indices_index + 2);
     }
 
+    /// Calculates the tightest bounds that fit around every vertex that has been drawn
+    pub fn aabb(&self) -> Aabb {
+        if self.positions.is_empty() {
+            return Aabb::default();
+        }
+
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+
+        for position in &self.positions {
+            let position = Vec3::from(*position);
+            min = min.min(position);
+            max = max.max(position);
+        }
+
+        Aabb::from_min_max(min, max)
+    }
+
     pub fn apply_mesh(self, mesh: &mut Mesh) {
         mesh.set_indices(Some(Indices::U32(self.indices)));
+
+        if PACKED_VERTICES {
+            mesh.insert_attribute(
+                ATTRIBUTE_PACKED_POSITION,
+                VertexAttributeValues::Uint16x4(
+                    self.positions.into_iter().map(pack_position).collect(),
+                ),
+            );
+            mesh.insert_attribute(
+                ATTRIBUTE_PACKED_NORMAL,
+                VertexAttributeValues::Snorm8x4(
+                    self.normals.into_iter().map(pack_normal).collect(),
+                ),
+            );
+            mesh.insert_attribute(
+                ATTRIBUTE_PACKED_UV,
+                VertexAttributeValues::Uint16x2(
+                    self.uv_coordinates.into_iter().map(pack_uv).collect(),
+                ),
+            );
+            mesh.insert_attribute(
+                ATTRIBUTE_PACKED_TEXTURE_RECT,
+                VertexAttributeValues::Unorm16x4(
+                    self.texture_rects
+                        .into_iter()
+                        .map(pack_texture_rect)
+                        .collect(),
+                ),
+            );
+            mesh.insert_attribute(
+                ATTRIBUTE_PACKED_LIGHTING,
+                VertexAttributeValues::Unorm8x4(
+                    self.lighting.into_iter().map(pack_lighting).collect(),
+                ),
+            );
+            return;
+        }
+
         mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
         mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
         mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uv_coordinates);
+        mesh.insert_attribute(
+            ATTRIBUTE_TEXTURE_RECT,
+            VertexAttributeValues::Float32x4(self.texture_rects),
+        );
         mesh.insert_attribute(
             ATTRIBUTE_LIGHTING_COLOR,
             VertexAttributeValues::Float32x4(self.lighting),
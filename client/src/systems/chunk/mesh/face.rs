@@ -1,5 +1,6 @@
-use crate::game::viewable_direction::ViewableDirectionBitMap;
+use crate::game::viewable_direction::{AxisAlignedDirection, ViewableDirectionBitMap};
 use crate::systems::asset::atlas::index::TextureAtlasIndex;
+use crate::systems::chunk::data::LightingColor;
 use nalgebra::Vector3;
 
 #[derive(Debug, Clone)]
@@ -13,3 +14,136 @@ pub struct Face {
     pub edge: bool,
     pub direction: ViewableDirectionBitMap,
 }
+
+impl Face {
+    /// Rotates the face clockwise around the center of the block by a number of 90 degree steps
+    pub fn rotate_y(&self, steps: u8) -> Face {
+        let rotate_point = |mut point: Vector3<f32>| {
+            for _ in 0..(steps % 4) {
+                point = Vector3::new(1.0 - point.z, point.y, point.x);
+            }
+            point
+        };
+        let rotate_normal = |mut normal: Vector3<f32>| {
+            for _ in 0..(steps % 4) {
+                normal = Vector3::new(-normal.z, normal.y, normal.x);
+            }
+            normal
+        };
+
+        Face {
+            top_left: rotate_point(self.top_left),
+            top_right: rotate_point(self.top_right),
+            bottom_left: rotate_point(self.bottom_left),
+            texture: self.texture,
+            normal: rotate_normal(self.normal),
+            edge: self.edge,
+            direction: self.direction.rotate_y(steps),
+        }
+    }
+}
+
+/// The light around a block, for lighting each face by the cell it faces
+#[derive(Debug, Clone, Copy)]
+pub struct FaceLighting {
+    /// The light in the block's own cell
+    pub own: LightingColor,
+    /// The light in each neighbouring cell, in the order of `BLOCK_SIDES`
+    pub sides: [LightingColor; 6],
+}
+
+impl FaceLighting {
+    pub fn new(own: LightingColor) -> FaceLighting {
+        FaceLighting {
+            own,
+            sides: [own; 6],
+        }
+    }
+
+    /// Faces on a side of the block face into the neighbour on that side. Faces inside the block, such as long
+    /// grass, face into the block's own cell
+    pub fn for_face(&self, face: &Face) -> LightingColor {
+        if face.edge {
+            self.sides[AxisAlignedDirection::from(face.direction) as usize]
+        } else {
+            self.own
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::blocks::block_rotation;
+    use rc_networking::constants::{block_state, pack_block};
+
+    /// The face covering the front (-z) side of a block
+    fn front_face() -> Face {
+        Face {
+            top_left: Vector3::new(0.0, 1.0, 0.0),
+            top_right: Vector3::new(1.0, 1.0, 0.0),
+            bottom_left: Vector3::new(0.0, 0.0, 0.0),
+            texture: TextureAtlasIndex::new(0.0, 1.0, 0.0, 1.0),
+            normal: Vector3::new(0.0, 0.0, -1.0),
+            edge: true,
+            direction: ViewableDirectionBitMap::Front,
+        }
+    }
+
+    #[test]
+    fn quarter_turn_moves_front_face_to_the_right() {
+        let face = front_face().rotate_y(1);
+
+        assert_eq!(face.top_left, Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(face.top_right, Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(face.bottom_left, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(face.normal, Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(face.direction, ViewableDirectionBitMap::Right);
+    }
+
+    #[test]
+    fn four_turns_leave_the_face_unchanged() {
+        let original = front_face();
+        let face = original.rotate_y(4);
+
+        assert_eq!(face.top_left, original.top_left);
+        assert_eq!(face.top_right, original.top_right);
+        assert_eq!(face.bottom_left, original.bottom_left);
+        assert_eq!(face.normal, original.normal);
+        assert_eq!(face.direction, original.direction);
+    }
+
+    #[test]
+    fn rotation_comes_from_the_packed_state() {
+        // The low two bits turn the block, the rest of the state is left for other uses
+        let block = pack_block(6, 0b110);
+        assert_eq!(block_rotation(block_state(block)), 2);
+
+        let face = front_face().rotate_y(block_rotation(block_state(block)));
+        assert_eq!(face.normal, Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(face.direction, ViewableDirectionBitMap::Back);
+    }
+
+    #[test]
+    fn edge_faces_take_the_light_of_the_cell_they_face() {
+        let mut light = FaceLighting::new([10, 10, 10, 255]);
+        light.sides[AxisAlignedDirection::Front as usize] = [200, 150, 100, 255];
+        light.sides[AxisAlignedDirection::Back as usize] = [50, 50, 50, 255];
+
+        assert_eq!(light.for_face(&front_face()), [200, 150, 100, 255]);
+        // Turned to face the back, it takes the light behind the block instead
+        assert_eq!(light.for_face(&front_face().rotate_y(2)), [50, 50, 50, 255]);
+    }
+
+    #[test]
+    fn inner_faces_take_the_light_of_their_own_cell() {
+        let mut light = FaceLighting::new([10, 10, 10, 255]);
+        light.sides = [[200, 200, 200, 255]; 6];
+
+        let inner = Face {
+            edge: false,
+            ..front_face()
+        };
+        assert_eq!(light.for_face(&inner), [10, 10, 10, 255]);
+    }
+}
@@ -1,2 +1,3 @@
 pub mod draw_kit;
 pub mod face;
+pub mod vertex;
@@ -0,0 +1,291 @@
+use crate::systems::chunk::builder::{ATTRIBUTE_LIGHTING_COLOR, ATTRIBUTE_TEXTURE_RECT};
+use bevy::prelude::Mesh;
+use bevy::render::mesh::{MeshVertexAttribute, VertexAttributeValues};
+use bevy::render::render_resource::VertexFormat;
+
+/// Chunk vertices are packed into 28 bytes rather than 64 unless the `unpacked-vertices` feature is enabled: 20 for
+/// the position, normal, texture coordinates and lighting, and 8 for the texture rect. Positions and texture
+/// coordinates are fixed point, normals are signed bytes, texture rects are 16 bit and lighting is a byte per channel
+pub const PACKED_VERTICES: bool = cfg!(not(feature = "unpacked-vertices"));
+
+/// Fixed point position, with the last component left as padding
+pub const ATTRIBUTE_PACKED_POSITION: MeshVertexAttribute =
+    MeshVertexAttribute::new("PackedPosition", 988540918, VertexFormat::Uint16x4);
+pub const ATTRIBUTE_PACKED_NORMAL: MeshVertexAttribute =
+    MeshVertexAttribute::new("PackedNormal", 988540919, VertexFormat::Snorm8x4);
+pub const ATTRIBUTE_PACKED_UV: MeshVertexAttribute =
+    MeshVertexAttribute::new("PackedUv", 988540920, VertexFormat::Uint16x2);
+pub const ATTRIBUTE_PACKED_LIGHTING: MeshVertexAttribute =
+    MeshVertexAttribute::new("PackedLighting", 988540921, VertexFormat::Unorm8x4);
+pub const ATTRIBUTE_PACKED_TEXTURE_RECT: MeshVertexAttribute =
+    MeshVertexAttribute::new("PackedTextureRect", 988540922, VertexFormat::Unorm16x4);
+
+/// Steps per block in a packed position. Must match the shader
+pub const POSITION_SCALE: f32 = 256.0;
+/// Packed positions are shifted by this many blocks so faces reaching just outside the chunk stay positive.
+/// Must match the shader
+pub const POSITION_OFFSET: f32 = 16.0;
+/// Steps per texture repeat in a packed texture coordinate, leaving room for a face repeated 64 times.
+/// Must match the shader
+pub const UV_SCALE: f32 = 512.0;
+/// The most times a texture can repeat across a face and still fit in a packed texture coordinate
+pub const MAX_UV_REPEATS: usize = 64;
+
+const _: () = assert!(UV_SCALE as usize * MAX_UV_REPEATS <= u16::MAX as usize);
+
+pub fn pack_position(position: [f32; 3]) -> [u16; 4] {
+    let pack = |v: f32| {
+        ((v + POSITION_OFFSET) * POSITION_SCALE)
+            .round()
+            .clamp(0.0, u16::MAX as f32) as u16
+    };
+    [pack(position[0]), pack(position[1]), pack(position[2]), 0]
+}
+
+pub fn unpack_position(packed: [u16; 4]) -> [f32; 3] {
+    let unpack = |v: u16| v as f32 / POSITION_SCALE - POSITION_OFFSET;
+    [unpack(packed[0]), unpack(packed[1]), unpack(packed[2])]
+}
+
+pub fn pack_normal(normal: [f32; 3]) -> [i8; 4] {
+    let pack = |v: f32| (v.clamp(-1.0, 1.0) * i8::MAX as f32).round() as i8;
+    [pack(normal[0]), pack(normal[1]), pack(normal[2]), 0]
+}
+
+pub fn unpack_normal(packed: [i8; 4]) -> [f32; 3] {
+    let unpack = |v: i8| (v as f32 / i8::MAX as f32).max(-1.0);
+    [unpack(packed[0]), unpack(packed[1]), unpack(packed[2])]
+}
+
+pub fn pack_uv(uv: [f32; 2]) -> [u16; 2] {
+    let pack = |v: f32| (v * UV_SCALE).round().clamp(0.0, u16::MAX as f32) as u16;
+    [pack(uv[0]), pack(uv[1])]
+}
+
+pub fn unpack_uv(packed: [u16; 2]) -> [f32; 2] {
+    [packed[0] as f32 / UV_SCALE, packed[1] as f32 / UV_SCALE]
+}
+
+pub fn pack_texture_rect(rect: [f32; 4]) -> [u16; 4] {
+    rect.map(|v| (v.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16)
+}
+
+pub fn unpack_texture_rect(packed: [u16; 4]) -> [f32; 4] {
+    packed.map(|v| v as f32 / u16::MAX as f32)
+}
+
+pub fn pack_lighting(lighting: [f32; 4]) -> [u8; 4] {
+    lighting.map(|v| (v.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8)
+}
+
+pub fn unpack_lighting(packed: [u8; 4]) -> [f32; 4] {
+    packed.map(|v| v as f32 / u8::MAX as f32)
+}
+
+/// The vertices of a chunk mesh as full floats, whichever format the mesh was built with
+pub struct UnpackedVertices {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    /// How far across the face's texture each vertex is, counting up by one each time the texture repeats
+    pub uvs: Option<Vec<[f32; 2]>>,
+    /// The part of the atlas each vertex's face is textured with, as the corners the texture starts and ends at
+    pub texture_rects: Option<Vec<[f32; 4]>>,
+    pub lighting: Option<Vec<[f32; 4]>>,
+}
+
+impl UnpackedVertices {
+    /// Where each vertex lands in the atlas. Faces merged by greedy meshing repeat their texture in the shader,
+    /// which the atlas can't show, so they come out stretched across the atlas instead
+    pub fn atlas_uvs(&self) -> Option<Vec<[f32; 2]>> {
+        let uvs = self.uvs.as_ref()?;
+        let rects = self.texture_rects.as_ref()?;
+
+        Some(
+            uvs.iter()
+                .zip(rects)
+                .map(|([u, v], [u_min, v_min, u_max, v_max])| {
+                    [u_min + u * (u_max - u_min), v_min + v * (v_max - v_min)]
+                })
+                .collect(),
+        )
+    }
+
+    pub fn from_mesh(mesh: &Mesh) -> Option<UnpackedVertices> {
+        if let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        {
+            return Some(UnpackedVertices {
+                positions: positions.clone(),
+                normals: match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+                    Some(VertexAttributeValues::Float32x3(val)) => Some(val.clone()),
+                    _ => None,
+                },
+                uvs: match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+                    Some(VertexAttributeValues::Float32x2(val)) => Some(val.clone()),
+                    _ => None,
+                },
+                texture_rects: match mesh.attribute(ATTRIBUTE_TEXTURE_RECT) {
+                    Some(VertexAttributeValues::Float32x4(val)) => Some(val.clone()),
+                    _ => None,
+                },
+                lighting: match mesh.attribute(ATTRIBUTE_LIGHTING_COLOR) {
+                    Some(VertexAttributeValues::Float32x4(val)) => Some(val.clone()),
+                    _ => None,
+                },
+            });
+        }
+
+        let positions = match mesh.attribute(ATTRIBUTE_PACKED_POSITION) {
+            Some(VertexAttributeValues::Uint16x4(val)) => val,
+            _ => return None,
+        };
+
+        Some(UnpackedVertices {
+            positions: positions.iter().map(|v| unpack_position(*v)).collect(),
+            normals: match mesh.attribute(ATTRIBUTE_PACKED_NORMAL) {
+                Some(VertexAttributeValues::Snorm8x4(val)) => {
+                    Some(val.iter().map(|v| unpack_normal(*v)).collect())
+                }
+                _ => None,
+            },
+            uvs: match mesh.attribute(ATTRIBUTE_PACKED_UV) {
+                Some(VertexAttributeValues::Uint16x2(val)) => {
+                    Some(val.iter().map(|v| unpack_uv(*v)).collect())
+                }
+                _ => None,
+            },
+            texture_rects: match mesh.attribute(ATTRIBUTE_PACKED_TEXTURE_RECT) {
+                Some(VertexAttributeValues::Unorm16x4(val)) => {
+                    Some(val.iter().map(|v| unpack_texture_rect(*v)).collect())
+                }
+                _ => None,
+            },
+            lighting: match mesh.attribute(ATTRIBUTE_PACKED_LIGHTING) {
+                Some(VertexAttributeValues::Unorm8x4(val)) => {
+                    Some(val.iter().map(|v| unpack_lighting(*v)).collect())
+                }
+                _ => None,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::chunk::mesh::draw_kit::DrawKit;
+    use bevy::render::mesh::PrimitiveTopology;
+
+    fn assert_close<const N: usize>(a: [f32; N], b: [f32; N], tolerance: f32) {
+        assert!(
+            a.iter()
+                .zip(b.iter())
+                .all(|(a, b)| (a - b).abs() <= tolerance),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        for position in [
+            [0.0, 0.0, 0.0],
+            [16.0, 16.0, 16.0],
+            [0.25, 7.5, 15.9],
+            [-1.0, 17.0, 3.3],
+        ] {
+            let unpacked = unpack_position(pack_position(position));
+            assert_close(unpacked, position, 0.5 / POSITION_SCALE);
+        }
+
+        // Whole and half blocks are exact
+        assert_eq!(
+            unpack_position(pack_position([3.0, 8.5, 12.0])),
+            [3.0, 8.5, 12.0]
+        );
+    }
+
+    #[test]
+    fn normals_round_trip() {
+        let diagonal = 1.0 / 3f32.sqrt();
+        for normal in [
+            [0.0, 1.0, 0.0],
+            [-1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.0],
+            [diagonal, -diagonal, diagonal],
+        ] {
+            assert_close(unpack_normal(pack_normal(normal)), normal, 0.5 / 127.0);
+        }
+    }
+
+    #[test]
+    fn uvs_round_trip() {
+        for uv in [
+            [0.0, 0.0],
+            [1.0, 1.0],
+            [0.3, 0.7],
+            [16.0, MAX_UV_REPEATS as f32],
+        ] {
+            assert_close(unpack_uv(pack_uv(uv)), uv, 0.5 / UV_SCALE);
+        }
+    }
+
+    #[test]
+    fn texture_rects_and_lighting_round_trip() {
+        let rect = [0.125, 0.5, 0.1875, 0.5625];
+        assert_close(
+            unpack_texture_rect(pack_texture_rect(rect)),
+            rect,
+            0.5 / u16::MAX as f32,
+        );
+
+        let lighting = [1.0, 0.5, 0.2, 1.0];
+        assert_close(
+            unpack_lighting(pack_lighting(lighting)),
+            lighting,
+            0.5 / u8::MAX as f32,
+        );
+    }
+
+    /// Two vertices with values that fall between the steps of the packed format
+    fn kit() -> DrawKit {
+        let mut kit = DrawKit::new();
+        kit.positions = vec![[1.0, 2.5, 3.3], [15.0, 0.1, 16.0]];
+        kit.normals = vec![[0.0, 1.0, 0.0], [0.0, 0.0, -1.0]];
+        kit.uv_coordinates = vec![[0.0, 4.0], [2.3, 0.0]];
+        kit.texture_rects = vec![[0.25, 0.5, 0.375, 0.625]; 2];
+        kit.lighting = vec![[1.0, 0.2, 0.6, 1.0]; 2];
+        kit.indices = vec![0, 1, 0];
+        kit
+    }
+
+    #[test]
+    fn mesh_round_trips() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        kit().apply_mesh(&mut mesh);
+        let vertices = UnpackedVertices::from_mesh(&mesh).unwrap();
+        let kit = kit();
+
+        for (a, b) in vertices.positions.iter().zip(&kit.positions) {
+            assert_close(*a, *b, 0.5 / POSITION_SCALE);
+        }
+        for (a, b) in vertices.normals.unwrap().iter().zip(&kit.normals) {
+            assert_close(*a, *b, 0.5 / 127.0);
+        }
+        for (a, b) in vertices.uvs.unwrap().iter().zip(&kit.uv_coordinates) {
+            assert_close(*a, *b, 0.5 / UV_SCALE);
+        }
+        for (a, b) in vertices
+            .texture_rects
+            .unwrap()
+            .iter()
+            .zip(&kit.texture_rects)
+        {
+            assert_close(*a, *b, 0.5 / u16::MAX as f32);
+        }
+        for (a, b) in vertices.lighting.unwrap().iter().zip(&kit.lighting) {
+            assert_close(*a, *b, 0.5 / u8::MAX as f32);
+        }
+    }
+}
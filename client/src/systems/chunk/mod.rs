@@ -1,6 +1,15 @@
+use crate::config::{ChunkEdgeFaces, ClientConfig};
+use crate::helpers::global_to_local_position;
+use crate::state::AppState;
+use crate::systems::asset::material::chunk::ChunkMaterial;
 use crate::systems::asset::AssetService;
+use crate::systems::chunk::boundaries::{
+    draw_chunk_boundaries, toggle_chunk_boundaries, ChunkBoundaries,
+};
 use crate::systems::chunk::builder::{mesh_builder, RerenderChunkFlag, RerenderChunkFlagContext};
 use crate::systems::chunk::data::{ChunkData, RawChunkData};
+use crate::systems::chunk::export::export_chunk_mesh;
+use crate::systems::chunk::preload::{preload_spawn_chunks, reset_world_info};
 use crate::systems::chunk::request::request_chunks;
 use bevy::prelude::*;
 use bevy::render::mesh::PrimitiveTopology;
@@ -10,21 +19,44 @@ use nalgebra::Vector3;
 use rc_networking::constants::CHUNK_SIZE;
 use std::collections::HashMap;
 
+pub mod boundaries;
 pub mod builder;
 pub mod data;
+pub mod export;
 pub mod lookup;
 pub mod mesh;
 pub mod nearby_cache;
+pub mod preload;
+pub mod priority;
 mod request;
 
 pub struct ChunkPlugin;
 
 impl Plugin for ChunkPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(ChunkSystem::new())
+        let (edge_faces, mesh_reserve_scale, greedy) = app
+            .world
+            .get_resource::<ClientConfig>()
+            .map_or((ChunkEdgeFaces::UntilLoaded, 1.0, Some(0)), |v| {
+                (
+                    v.chunk_edge_faces,
+                    v.mesh_reserve_scale,
+                    v.greedy_meshing.then_some(v.greedy_light_tolerance),
+                )
+            });
+
+        app.insert_resource(ChunkSystem::new(edge_faces, mesh_reserve_scale, greedy))
             .add_system(mesh_builder)
             .add_event::<RerenderChunkFlag>()
-            .add_system(request_chunks);
+            .add_system(request_chunks)
+            .add_system_set(SystemSet::on_enter(AppState::Connecting).with_system(reset_world_info))
+            .add_system_set(
+                SystemSet::on_update(AppState::Connecting).with_system(preload_spawn_chunks),
+            )
+            .add_system(export_chunk_mesh)
+            .insert_resource(ChunkBoundaries::default())
+            .add_system(toggle_chunk_boundaries)
+            .add_system(draw_chunk_boundaries.after(toggle_chunk_boundaries));
     }
 }
 
@@ -32,14 +64,57 @@ impl Plugin for ChunkPlugin {
 pub struct ChunkSystem {
     pub chunks: HashMap<Vector3<i32>, ChunkData, FnvBuildHasher>,
     pub requested_chunks: Vec<Vector3<i32>>,
+    /// How many chunks are waiting for their mesh to be built
+    pub pending_builds: usize,
+    pub edge_faces: ChunkEdgeFaces,
+    /// How much room is made in a chunk's mesh buffers for each visible block side before meshing
+    pub mesh_reserve_scale: f32,
+    /// Merges matching faces into larger quads when set, treating light that differs by up to this much in each
+    /// channel as matching. Faces are drawn one by one when it's None
+    pub greedy_meshing: Option<u8>,
+    /// How many blocks wide each chunk is. The server says when we join, chunks placed before then are CHUNK_SIZE
+    pub chunk_size: usize,
 }
 
 impl ChunkSystem {
-    pub fn new() -> ChunkSystem {
+    pub fn new(
+        edge_faces: ChunkEdgeFaces,
+        mesh_reserve_scale: f32,
+        greedy_meshing: Option<u8>,
+    ) -> ChunkSystem {
         ChunkSystem {
             chunks: FnvHashMap::default(),
             requested_chunks: vec![],
+            pending_builds: 0,
+            edge_faces,
+            mesh_reserve_scale,
+            greedy_meshing,
+            chunk_size: CHUNK_SIZE,
+        }
+    }
+
+    /// Changes how wide chunks are. Chunks already loaded at the old size wouldn't line up with the new ones, so
+    /// they're unloaded to be requested again
+    pub fn set_chunk_size(&mut self, size: usize, commands: &mut Commands) {
+        if size == self.chunk_size {
+            return;
         }
+
+        for chunk in self.chunks.values() {
+            commands.entity(chunk.entity).despawn_recursive();
+        }
+        self.chunks.clear();
+        self.requested_chunks.clear();
+        self.chunk_size = size;
+    }
+
+    /// Gets the block at a global position, or None if its chunk isn't loaded
+    pub fn get_block(&self, position: Vector3<i32>) -> Option<u32> {
+        let (chunk_loc, inner_loc) = global_to_local_position(position, self.chunk_size);
+
+        self.chunks
+            .get(&chunk_loc)
+            .map(|chunk| chunk.get_block(inner_loc))
     }
 
     /// Creates a new chunk from data
@@ -55,42 +130,170 @@ impl ChunkSystem {
         let opaque = meshes.add(Mesh::new(PrimitiveTopology::TriangleList));
         let translucent = meshes.add(Mesh::new(PrimitiveTopology::TriangleList));
 
-        let entity = commands
-            .spawn(asset_service.opaque_texture_atlas_material.clone())
-            .insert(Transform::from_translation(Vec3::new(
-                (position.x * CHUNK_SIZE as i32) as f32,
-                (position.y * CHUNK_SIZE as i32) as f32,
-                (position.z * CHUNK_SIZE as i32) as f32,
-            )))
-            .insert(GlobalTransform::default())
-            .insert(Visibility::default())
-            .insert(ComputedVisibility::default())
-            .insert(Aabb::from_min_max(
-                Vec3::new(0.0, 0.0, 0.0),
-                Vec3::new(16.0, 16.0, 16.0),
-            ))
-            .insert(opaque.clone())
-            .with_children(|c| {
-                c.spawn(asset_service.translucent_texture_atlas_material.clone())
-                    .insert(Transform::default())
-                    .insert(GlobalTransform::default())
-                    .insert(Visibility::default())
-                    .insert(ComputedVisibility::default())
-                    .insert(Aabb::from_min_max(
-                        Vec3::new(0.0, 0.0, 0.0),
-                        Vec3::new(16.0, 16.0, 16.0),
-                    ))
-                    .insert(translucent.clone());
-            })
-            .id();
+        let (entity, translucent_entity) = spawn_chunk_entities(
+            commands,
+            position,
+            data.size(),
+            &asset_service.opaque_texture_atlas_material,
+            &asset_service.translucent_texture_atlas_material,
+            &opaque,
+            &translucent,
+        );
 
-        let chunk = ChunkData::new(data, entity, position, opaque, translucent);
+        let chunk = ChunkData::new(
+            data,
+            entity,
+            translucent_entity,
+            position,
+            opaque,
+            translucent,
+        );
 
         self.chunks.insert(position, chunk);
 
+        // Rebuilding the neighbours replaces the faces they drew against this chunk while it was missing
         rerender_chunk.send(RerenderChunkFlag {
             chunk: position,
-            context: RerenderChunkFlagContext::Surrounding,
+            context: if self.edge_faces.remesh_neighbours() {
+                RerenderChunkFlagContext::Surrounding
+            } else {
+                RerenderChunkFlagContext::None
+            },
         });
     }
 }
+
+/// Spawns the entities of a chunk `size` blocks wide, the opaque one with the translucent one as its child. Both start
+/// hidden, and are first drawn once the mesh builder has built their meshes
+fn spawn_chunk_entities(
+    commands: &mut Commands,
+    position: Vector3<i32>,
+    size: usize,
+    opaque_material: &Handle<ChunkMaterial>,
+    translucent_material: &Handle<ChunkMaterial>,
+    opaque: &Handle<Mesh>,
+    translucent: &Handle<Mesh>,
+) -> (Entity, Entity) {
+    let mut translucent_entity = None;
+
+    let entity = commands
+        .spawn(opaque_material.clone())
+        .insert(Transform::from_translation(Vec3::new(
+            (position.x * size as i32) as f32,
+            (position.y * size as i32) as f32,
+            (position.z * size as i32) as f32,
+        )))
+        .insert(GlobalTransform::default())
+        // Hidden until the mesh builder has built its mesh, so the placeholder isn't drawn
+        .insert(Visibility { is_visible: false })
+        .insert(ComputedVisibility::default())
+        // The bounds are fitted to the mesh each time it's built
+        .insert(Aabb::default())
+        .insert(opaque.clone())
+        .with_children(|c| {
+            translucent_entity = Some(
+                c.spawn(translucent_material.clone())
+                    .insert(Transform::default())
+                    .insert(GlobalTransform::default())
+                    .insert(Visibility { is_visible: false })
+                    .insert(ComputedVisibility::default())
+                    .insert(Aabb::default())
+                    .insert(translucent.clone())
+                    .id(),
+            );
+        })
+        .id();
+
+    (entity, translucent_entity.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::CommandQueue;
+
+    #[test]
+    fn new_chunks_are_hidden_until_meshed() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+
+        let (entity, translucent_entity) = spawn_chunk_entities(
+            &mut commands,
+            Vector3::new(1, 2, -3),
+            CHUNK_SIZE,
+            &Handle::default(),
+            &Handle::default(),
+            &Handle::default(),
+            &Handle::default(),
+        );
+        queue.apply(&mut world);
+
+        // Neither placeholder mesh is drawn
+        let visible = |entity| world.get::<Visibility>(entity).unwrap().is_visible;
+        assert!(!visible(entity));
+        assert!(!visible(translucent_entity));
+        assert_eq!(
+            world.get::<Parent>(translucent_entity).unwrap().get(),
+            entity
+        );
+        assert_eq!(
+            world.get::<Transform>(entity).unwrap().translation,
+            Vec3::new(16.0, 32.0, -48.0)
+        );
+
+        let chunk = ChunkData::new(
+            RawChunkData::new(CHUNK_SIZE),
+            entity,
+            translucent_entity,
+            Vector3::new(1, 2, -3),
+            Handle::default(),
+            Handle::default(),
+        );
+        assert!(!chunk.meshed);
+        assert!(chunk.dirty);
+    }
+
+    #[test]
+    fn changing_chunk_size_unloads_chunks() {
+        let mut world = World::new();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+
+        let mut system = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        let (entity, translucent_entity) = spawn_chunk_entities(
+            &mut commands,
+            Vector3::zeros(),
+            CHUNK_SIZE,
+            &Handle::default(),
+            &Handle::default(),
+            &Handle::default(),
+            &Handle::default(),
+        );
+        system.chunks.insert(
+            Vector3::zeros(),
+            ChunkData::new(
+                RawChunkData::new(CHUNK_SIZE),
+                entity,
+                translucent_entity,
+                Vector3::zeros(),
+                Handle::default(),
+                Handle::default(),
+            ),
+        );
+        system.requested_chunks.push(Vector3::new(0, 1, 0));
+
+        // Joining at the same size keeps what's loaded
+        system.set_chunk_size(CHUNK_SIZE, &mut commands);
+        assert_eq!(system.chunks.len(), 1);
+
+        system.set_chunk_size(24, &mut commands);
+        queue.apply(&mut world);
+
+        assert_eq!(system.chunk_size, 24);
+        assert!(system.chunks.is_empty());
+        assert!(system.requested_chunks.is_empty());
+        assert!(world.get_entity(entity).is_none());
+        assert!(world.get_entity(translucent_entity).is_none());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::config::ClientConfig;
+use crate::helpers::global_f32_to_local_position;
+use crate::state::AppState;
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::networking::join::WorldInfo;
+use crate::systems::ui::loading::LoadingData;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::in_world_column;
+use std::time::{Duration, Instant};
+
+/// Where loading the chunks around spawn has got to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreloadStatus {
+    Loading {
+        loaded: usize,
+        total: usize,
+    },
+    Done,
+    /// Took too long, the player is let in with whatever has loaded
+    TimedOut,
+}
+
+pub fn preload_status(
+    loaded: usize,
+    total: usize,
+    elapsed: Duration,
+    timeout: Duration,
+) -> PreloadStatus {
+    if loaded >= total {
+        PreloadStatus::Done
+    } else if elapsed >= timeout {
+        PreloadStatus::TimedOut
+    } else {
+        PreloadStatus::Loading { loaded, total }
+    }
+}
+
+/// The chunks `chunk_size` blocks wide within a radius of a position that make up a world column `column_sections`
+/// high
+pub fn preload_area(
+    position: Vector3<f32>,
+    radius: i32,
+    column_sections: i32,
+    chunk_size: usize,
+) -> Vec<Vector3<i32>> {
+    let (center, _) = global_f32_to_local_position(position, chunk_size);
+    let mut area = Vec::new();
+
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            for z in -radius..=radius {
+                let chunk = center + Vector3::new(x, y, z);
+
+                if !in_world_column(chunk.y, column_sections) {
+                    continue;
+                }
+
+                if Vector3::new(x, y, z).cast::<f32>().magnitude() > radius as f32 {
+                    continue;
+                }
+
+                area.push(chunk);
+            }
+        }
+    }
+
+    area
+}
+
+/// How many of the chunks in an area are being drawn. A chunk that's only waiting to be rebuilt, as a neighbour
+/// arrived, still counts as it already has a mesh on screen
+pub fn drawn_chunks(chunks: &ChunkSystem, area: &[Vector3<i32>]) -> usize {
+    area.iter()
+        .filter(|v| chunks.chunks.get(v).map_or(false, |chunk| chunk.meshed))
+        .count()
+}
+
+/// The chunks being waited on, around where the server told us we spawn
+#[derive(Default)]
+pub struct WorldPreload {
+    started: Option<Instant>,
+    area: Vec<Vector3<i32>>,
+}
+
+/// Forgets the world from any previous connection, so the preload waits for the server to say where we spawn
+pub fn reset_world_info(mut commands: Commands) {
+    commands.remove_resource::<WorldInfo>();
+}
+
+/// Holds the player on the connecting screen until the chunks around spawn are loaded and meshed,
+/// so they don't spawn into empty terrain
+pub fn preload_spawn_chunks(
+    world_info: Option<Res<WorldInfo>>,
+    chunks: Res<ChunkSystem>,
+    config: Res<ClientConfig>,
+    mut preload: Local<WorldPreload>,
+    mut loading: ResMut<LoadingData>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    let world_info = match world_info {
+        Some(val) => val,
+        None => return,
+    };
+
+    // Joining again replaces the world info, so the preload starts over around the new spawn
+    if world_info.is_changed() || preload.started.is_none() {
+        *preload = WorldPreload {
+            started: Some(Instant::now()),
+            area: preload_area(
+                world_info.spawn,
+                config.preload_radius.max(0),
+                world_info.column_sections,
+                chunks.chunk_size,
+            ),
+        };
+    }
+
+    let loaded = drawn_chunks(&chunks, &preload.area);
+    let total = preload.area.len();
+
+    loading.preloaded_chunks = loaded;
+    loading.preload_chunks = total;
+
+    let elapsed = preload.started.map_or(Duration::ZERO, |v| v.elapsed());
+    match preload_status(
+        loaded,
+        total,
+        elapsed,
+        Duration::from_secs_f32(config.preload_timeout),
+    ) {
+        PreloadStatus::Loading { .. } => return,
+        PreloadStatus::Done => info!("Preloaded {} chunks in {:?}", total, elapsed),
+        PreloadStatus::TimedOut => warn!(
+            "Preloading timed out with {} of {} chunks loaded",
+            loaded, total
+        ),
+    }
+
+    preload.started = None;
+    let _ = app_state.set(AppState::InGame);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc_networking::constants::CHUNK_SIZE;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// A position in the middle of a chunk
+    fn in_chunk(x: i32, y: i32, z: i32) -> Vector3<f32> {
+        (Vector3::new(x, y, z).cast::<f32>() + Vector3::repeat(0.5)) * CHUNK_SIZE as f32
+    }
+
+    #[test]
+    fn preloading_waits_for_every_chunk() {
+        assert_eq!(
+            preload_status(3, 7, Duration::from_secs(1), TIMEOUT),
+            PreloadStatus::Loading {
+                loaded: 3,
+                total: 7
+            }
+        );
+        assert_eq!(
+            preload_status(7, 7, Duration::from_secs(1), TIMEOUT),
+            PreloadStatus::Done
+        );
+    }
+
+    #[test]
+    fn preloading_times_out() {
+        assert_eq!(
+            preload_status(6, 7, TIMEOUT, TIMEOUT),
+            PreloadStatus::TimedOut
+        );
+        assert_eq!(
+            preload_status(0, 7, Duration::from_secs(60), TIMEOUT),
+            PreloadStatus::TimedOut
+        );
+    }
+
+    #[test]
+    fn finishing_late_isnt_a_timeout() {
+        assert_eq!(
+            preload_status(7, 7, Duration::from_secs(60), TIMEOUT),
+            PreloadStatus::Done
+        );
+        // Nothing to load is done straight away
+        assert_eq!(
+            preload_status(0, 0, Duration::ZERO, TIMEOUT),
+            PreloadStatus::Done
+        );
+    }
+
+    #[test]
+    fn preload_area_is_a_sphere_of_chunks() {
+        let center = Vector3::new(1, 4, 1);
+
+        assert_eq!(
+            preload_area(in_chunk(1, 4, 1), 0, 16, CHUNK_SIZE),
+            vec![center]
+        );
+
+        let area = preload_area(in_chunk(1, 4, 1), 1, 16, CHUNK_SIZE);
+        assert_eq!(area.len(), 7);
+        assert!(area.contains(&center));
+        assert!(area.contains(&Vector3::new(1, 5, 1)));
+        // Corners are further away than the radius
+        assert!(!area.contains(&Vector3::new(2, 5, 2)));
+    }
+
+    #[test]
+    fn preload_area_is_centered_at_the_servers_chunk_size() {
+        let spawn = Vector3::new(30.0, 70.0, 30.0);
+
+        assert_eq!(preload_area(spawn, 0, 16, 16), vec![Vector3::new(1, 4, 1)]);
+        assert_eq!(preload_area(spawn, 0, 16, 24), vec![Vector3::new(1, 2, 1)]);
+    }
+
+    #[test]
+    fn preload_area_stays_in_the_world_column() {
+        let bottom = preload_area(in_chunk(0, 0, 0), 1, 16, CHUNK_SIZE);
+        assert_eq!(bottom.len(), 6);
+        assert!(bottom.iter().all(|v| v.y >= 0));
+
+        let top = preload_area(in_chunk(0, 3, 0), 1, 4, CHUNK_SIZE);
+        assert_eq!(top.len(), 6);
+        assert!(top.iter().all(|v| v.y < 4));
+    }
+
+    #[test]
+    fn only_meshed_chunks_count_as_preloaded() {
+        use crate::config::ChunkEdgeFaces;
+        use crate::systems::chunk::data::{test_chunk, RawChunkData};
+
+        let mut chunks = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        for (x, meshed, dirty) in [(0, false, true), (1, true, false), (2, true, true)] {
+            let mut chunk = test_chunk(Vector3::new(x, 0, 0), RawChunkData::new(CHUNK_SIZE));
+            chunk.meshed = meshed;
+            chunk.dirty = dirty;
+            chunks.chunks.insert(chunk.position, chunk);
+        }
+
+        // The first has never been drawn, the third is drawn while waiting on a rebuild, and the fourth hasn't arrived
+        let area = (0..4).map(|x| Vector3::new(x, 0, 0)).collect::<Vec<_>>();
+        assert_eq!(drawn_chunks(&chunks, &area), 2);
+    }
+}
@@ -0,0 +1,94 @@
+use nalgebra::Vector3;
+
+/// How much a chunk being away from the view direction counts against it, relative to its distance
+const ANGLE_WEIGHT: f32 = 1.5;
+
+/// Scores how soon a chunk should be loaded, chunks with lower scores are loaded first.
+///
+/// When a view direction is given, chunks in front of the camera are favoured over those behind it so the view fills in first. Otherwise only the distance to the camera is used.
+pub fn chunk_priority(
+    chunk: Vector3<i32>,
+    camera_chunk: Vector3<f32>,
+    forward: Option<Vector3<f32>>,
+) -> f32 {
+    let offset = chunk.cast::<f32>() - camera_chunk;
+    let distance = offset.magnitude();
+
+    match forward {
+        Some(forward) if distance > 0.0 && forward.magnitude() > 0.0 => {
+            // 1 when directly in front of the camera, -1 when directly behind
+            let alignment = offset.dot(&forward) / (distance * forward.magnitude());
+
+            distance * (1.0 + (1.0 - alignment) * 0.5 * ANGLE_WEIGHT)
+        }
+        _ => distance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sorts chunks into the order they'd be loaded in
+    fn load_order(
+        chunks: &[Vector3<i32>],
+        camera_chunk: Vector3<f32>,
+        forward: Option<Vector3<f32>>,
+    ) -> Vec<Vector3<i32>> {
+        let mut chunks = chunks.to_vec();
+        chunks.sort_by(|a, b| {
+            chunk_priority(*a, camera_chunk, forward)
+                .partial_cmp(&chunk_priority(*b, camera_chunk, forward))
+                .unwrap()
+        });
+        chunks
+    }
+
+    #[test]
+    fn chunks_in_front_load_before_those_behind() {
+        let front = Vector3::new(0, 0, -2);
+        let side = Vector3::new(2, 0, 0);
+        let behind = Vector3::new(0, 0, 2);
+
+        let order = load_order(
+            &[behind, side, front],
+            Vector3::zeros(),
+            Some(Vector3::new(0.0, 0.0, -1.0)),
+        );
+        assert_eq!(order, vec![front, side, behind]);
+    }
+
+    #[test]
+    fn close_chunks_behind_still_beat_distant_ones_in_front() {
+        let near_behind = Vector3::new(0, 0, 1);
+        let far_front = Vector3::new(0, 0, -6);
+
+        let order = load_order(
+            &[far_front, near_behind],
+            Vector3::zeros(),
+            Some(Vector3::new(0.0, 0.0, -1.0)),
+        );
+        assert_eq!(order, vec![near_behind, far_front]);
+    }
+
+    #[test]
+    fn without_a_direction_only_distance_counts() {
+        let near = Vector3::new(0, 0, 1);
+        let far = Vector3::new(0, 0, -3);
+
+        assert_eq!(chunk_priority(near, Vector3::zeros(), None), 1.0);
+        assert_eq!(
+            load_order(&[far, near], Vector3::zeros(), None),
+            vec![near, far]
+        );
+    }
+
+    #[test]
+    fn camera_chunk_comes_first() {
+        let forward = Some(Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            chunk_priority(Vector3::zeros(), Vector3::zeros(), forward),
+            0.0
+        );
+    }
+}
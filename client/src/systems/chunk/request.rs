@@ -1,10 +1,13 @@
+use crate::config::ClientConfig;
 use crate::game::player::Player;
-use crate::helpers::{global_f32_to_local_position, global_to_local_position};
+use crate::helpers::{from_bevy_vec3, global_f32_to_local_position, global_to_local_position};
+use crate::systems::chunk::priority::chunk_priority;
 use crate::systems::chunk::ChunkSystem;
+use crate::systems::networking::join::WorldInfo;
 use crate::systems::physics::PhysicsObject;
-use bevy::prelude::{EventWriter, Query, ResMut, With};
+use bevy::prelude::{Camera, EventWriter, Local, Query, Res, ResMut, Transform, Vec3, With};
 use nalgebra::Vector3;
-use rc_networking::constants::UserId;
+use rc_networking::constants::{in_world_column, UserId};
 use rc_networking::protocol::serverbound::request_chunk::RequestChunk;
 use rc_networking::protocol::Protocol;
 use rc_networking::types::SendPacket;
@@ -12,19 +15,34 @@ use rc_networking::types::SendPacket;
 /// Requests chunks when we move between chunks
 pub fn request_chunks(
     player: Query<&PhysicsObject, With<Player>>,
+    camera: Query<&Transform, With<Camera>>,
     mut system: ResMut<ChunkSystem>,
     mut chunk_requests: EventWriter<SendPacket>,
+    config: Res<ClientConfig>,
+    world_info: Option<Res<WorldInfo>>,
+    mut previous_chunk: Local<Option<Vector3<i32>>>,
 ) {
+    // The server tells us how high the world is when we join, chunks can't be requested until then
+    let world_info = match world_info {
+        Some(val) => val,
+        None => return,
+    };
+
     let object = player.single();
     // Get current chunk
-    let (current_chunk, _) = global_f32_to_local_position(object.position);
-    let (previous_chunk, _) = global_f32_to_local_position(object.previous_position);
+    let (current_chunk, _) = global_f32_to_local_position(object.position, system.chunk_size);
 
-    if current_chunk == previous_chunk {
+    // Physics ticks don't line up with frames, so compare against the chunk we last requested around
+    // rather than where the player was on the previous tick.
+    // Changing the render distance or joining a world loads the chunks it now covers without having to move
+    if *previous_chunk == Some(current_chunk) && !config.is_changed() && !world_info.is_changed() {
         return;
     }
+    *previous_chunk = Some(current_chunk);
+
+    let render_distance = config.render_distance.max(1);
 
-    let render_distance = 5;
+    let mut requests = Vec::new();
 
     // Load new chunks
     for x in -render_distance..render_distance {
@@ -32,6 +50,11 @@ pub fn request_chunks(
             for z in -render_distance..render_distance {
                 let potential_chunk = Vector3::new(x, y, z) + current_chunk;
 
+                // Only request sections that make up the world column, the server ignores the rest
+                if !in_world_column(potential_chunk.y, world_info.column_sections) {
+                    continue;
+                }
+
                 if (potential_chunk - current_chunk).cast::<f32>().magnitude()
                     > render_distance as f32
                 {
@@ -45,17 +68,36 @@ pub fn request_chunks(
                     continue;
                 }
 
-                system.requested_chunks.push(potential_chunk);
-                chunk_requests.send(SendPacket(
-                    Protocol::RequestChunk(RequestChunk::new(
-                        potential_chunk.x,
-                        potential_chunk.y,
-                        potential_chunk.z,
-                    )),
-                    UserId(0),
-                ));
+                requests.push(potential_chunk);
             }
         }
     }
+
+    // Request the chunks in view first, the player has just moved so use the direction they're looking in
+    let forward = camera
+        .get_single()
+        .ok()
+        .map(|v| from_bevy_vec3(v.rotation * Vec3::new(0.0, 0.0, -1.0)));
+    let camera_chunk = object.position / system.chunk_size as f32;
+
+    requests.sort_by(|a, b| {
+        chunk_priority(*a, camera_chunk, forward).total_cmp(&chunk_priority(
+            *b,
+            camera_chunk,
+            forward,
+        ))
+    });
+
+    for potential_chunk in requests {
+        system.requested_chunks.push(potential_chunk);
+        chunk_requests.send(SendPacket(
+            Protocol::RequestChunk(RequestChunk::new(
+                potential_chunk.x,
+                potential_chunk.y,
+                potential_chunk.z,
+            )),
+            UserId(0),
+        ));
+    }
     println!("Moved chunk");
 }
@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+/// Tools for debugging the game, only included when built with the `dev-tools` feature
+pub struct DevToolsPlugin;
+
+impl Plugin for DevToolsPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "dev-tools")]
+        app.add_plugin(bevy_inspector_egui::WorldInspectorPlugin::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Without the feature the inspector isn't a dependency at all, so the plugin can't add anything
+    #[cfg(not(feature = "dev-tools"))]
+    #[test]
+    fn default_build_adds_no_tools() {
+        let mut app = App::new();
+        let components = app.world.components().len();
+
+        app.add_plugin(DevToolsPlugin);
+
+        assert_eq!(app.world.components().len(), components);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::ClientConfig;
 use crate::game::player::Player;
 use crate::systems::input::InputSystem;
 use bevy::input::mouse::MouseMotion;
@@ -5,27 +6,133 @@ use bevy::prelude::*;
 
 const MOUSE_SENSITIVITY: f32 = 0.00022;
 
+/// The most look smoothing allowed, any closer to 1 and the camera would barely follow the mouse
+pub const MAX_LOOK_SMOOTHING: f32 = 0.95;
+
+/// Eases the camera's movement towards what the mouse did this frame. 0 passes the mouse straight through,
+/// closer to 1 is smoother but lags further behind. Smoothing is per 60th of a second so it feels the same at any frame rate
+pub fn smooth_look(previous: Vec2, raw: Vec2, smoothing: f32, delta: f32) -> Vec2 {
+    let smoothing = smoothing.clamp(0.0, MAX_LOOK_SMOOTHING);
+    if smoothing == 0.0 {
+        return raw;
+    }
+
+    let weight = 1.0 - smoothing.powf(delta * 60.0);
+    let smoothed = previous + (raw - previous) * weight;
+
+    // Settle once the movement is too small to see, rather than creeping forever
+    if smoothed.length_squared() < 1e-6 {
+        Vec2::ZERO
+    } else {
+        smoothed
+    }
+}
+
 pub fn update_input_look(
-    service: Res<InputSystem>,
+    mut service: ResMut<InputSystem>,
     mut mouse: EventReader<MouseMotion>,
     mut player: Query<(&mut Transform, &mut Player)>,
     windows: Res<Windows>,
+    config: Res<ClientConfig>,
+    time: Res<Time>,
 ) {
     if !service.captured {
         return;
     }
 
+    let moves = mouse.iter().map(|v| v.delta).collect::<Vec<Vec2>>();
+
+    // The cursor jumps when it's captured, which would otherwise spin the camera
+    if !service.accept_motion(!moves.is_empty()) {
+        return;
+    }
+
     let window = windows.get_primary().unwrap();
 
     let window_scale = window.height().min(window.width());
 
     let (mut transform, mut player) = player.single_mut();
 
-    for motion in mouse.iter() {
-        player.pitch -= (MOUSE_SENSITIVITY * motion.delta.y * window_scale).to_radians();
-        player.yaw -= (MOUSE_SENSITIVITY * motion.delta.x * window_scale).to_radians();
+    let raw = moves.into_iter().sum::<Vec2>();
+    let motion = smooth_look(
+        service.smoothed_look,
+        raw,
+        config.look_smoothing,
+        time.delta_seconds(),
+    );
+    service.smoothed_look = motion;
+
+    // Smoothing carries on easing the camera after the mouse stops
+    if motion == Vec2::ZERO {
+        return;
+    }
+
+    player.pitch -= (MOUSE_SENSITIVITY * motion.y * window_scale).to_radians();
+    player.yaw -= (MOUSE_SENSITIVITY * motion.x * window_scale).to_radians();
+
+    transform.rotation =
+        Quat::from_axis_angle(Vec3::Y, player.yaw) * Quat::from_axis_angle(Vec3::X, player.pitch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f32 = 1.0 / 60.0;
+    const STEP: Vec2 = Vec2::new(10.0, -4.0);
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 1e-3, "{:?} isn't {:?}", a, b);
+    }
+
+    #[test]
+    fn no_smoothing_passes_the_mouse_through() {
+        assert_eq!(smooth_look(Vec2::ZERO, STEP, 0.0, FRAME), STEP);
+        assert_eq!(smooth_look(STEP, Vec2::ZERO, 0.0, FRAME), Vec2::ZERO);
+        assert_eq!(smooth_look(Vec2::ZERO, STEP, -1.0, FRAME), STEP);
+    }
+
+    #[test]
+    fn step_input_is_eased_towards() {
+        let first = smooth_look(Vec2::ZERO, STEP, 0.5, FRAME);
+        assert_near(first, STEP * 0.5);
+
+        let second = smooth_look(first, STEP, 0.5, FRAME);
+        assert_near(second, STEP * 0.75);
+
+        let mut motion = second;
+        for _ in 0..30 {
+            motion = smooth_look(motion, STEP, 0.5, FRAME);
+        }
+        assert_near(motion, STEP);
+    }
+
+    #[test]
+    fn smoothing_feels_the_same_at_any_frame_rate() {
+        let once = smooth_look(Vec2::ZERO, STEP, 0.8, FRAME);
+        let half = smooth_look(Vec2::ZERO, STEP, 0.8, FRAME / 2.0);
+        let twice = smooth_look(half, STEP, 0.8, FRAME / 2.0);
+
+        assert_near(once, twice);
+    }
+
+    #[test]
+    fn smoothing_is_capped() {
+        assert_eq!(
+            smooth_look(Vec2::ZERO, STEP, 1.0, FRAME),
+            smooth_look(Vec2::ZERO, STEP, MAX_LOOK_SMOOTHING, FRAME)
+        );
+        assert_ne!(smooth_look(Vec2::ZERO, STEP, 1.0, FRAME), Vec2::ZERO);
+    }
 
-        transform.rotation = Quat::from_axis_angle(Vec3::Y, player.yaw)
-            * Quat::from_axis_angle(Vec3::X, player.pitch);
+    #[test]
+    fn movement_settles_after_the_mouse_stops() {
+        let mut motion = STEP;
+        let mut frames = 0;
+        while motion != Vec2::ZERO {
+            motion = smooth_look(motion, Vec2::ZERO, 0.5, FRAME);
+            frames += 1;
+            assert!(frames < 100, "never settled");
+        }
     }
 }
@@ -1,34 +1,88 @@
 mod look;
-mod movement;
+pub mod movement;
+pub mod spectator;
 
+use crate::config::ClientConfig;
 use crate::state::AppState;
 use crate::systems::chunk::builder::{RerenderChunkFlag, RerenderChunkFlagContext};
 use crate::systems::input::look::update_input_look;
-use crate::systems::input::movement::update_input_movement;
+use crate::systems::input::spectator::{
+    snap_camera_on_spectator_exit, toggle_spectator, update_spectator_movement, SpectatorMode,
+};
+use crate::systems::shutdown::ShutdownSequence;
 use bevy::app::{App, Plugin};
 use bevy::prelude::*;
-use bevy::window::{CursorGrabMode, Windows};
+use bevy::window::{CursorGrabMode, WindowFocused, Windows};
 use nalgebra::Vector3;
 
 pub struct InputPlugin;
 
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(InputSystem { captured: false })
-            .add_system_set(
-                SystemSet::on_update(AppState::InGame)
-                    .with_system(update_input_look)
-                    .with_system(update_input_movement)
-                    .with_system(grab_mouse),
-            )
-            .add_system(test)
-            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(grab_mouse_on_play));
+        app.insert_resource(InputSystem {
+            captured: false,
+            discard_next_motion: false,
+            smoothed_look: Vec2::ZERO,
+        })
+        .insert_resource(SpectatorMode::default())
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(update_input_look)
+                .with_system(toggle_spectator)
+                .with_system(snap_camera_on_spectator_exit.after(toggle_spectator))
+                .with_system(update_spectator_movement.after(update_input_look))
+                .with_system(grab_mouse)
+                .with_system(recapture_on_focus),
+        )
+        .add_system(test)
+        .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(grab_mouse_on_play))
+        .add_system_set(SystemSet::on_resume(AppState::InGame).with_system(grab_mouse_on_play))
+        .add_system_set(
+            SystemSet::on_enter(AppState::Disconnected).with_system(release_mouse_on_disconnect),
+        );
     }
 }
 
 #[derive(Resource)]
 pub struct InputSystem {
     captured: bool,
+    /// Skips the next mouse movement after capturing, as the cursor jumping into place isn't the player looking
+    discard_next_motion: bool,
+    /// The mouse movement last applied to the camera, when look smoothing is on
+    smoothed_look: Vec2,
+}
+
+impl InputSystem {
+    fn capture(&mut self, window: &mut Window) {
+        window.set_cursor_visibility(false);
+        window.set_cursor_grab_mode(CursorGrabMode::Confined);
+        self.captured = true;
+        self.discard_next_motion = true;
+        self.smoothed_look = Vec2::ZERO;
+    }
+
+    fn release(&mut self, window: &mut Window) {
+        window.set_cursor_visibility(true);
+        window.set_cursor_grab_mode(CursorGrabMode::None);
+        self.captured = false;
+    }
+
+    /// Whether a frame's mouse movement should be looked with. The first movement after capturing is the
+    /// cursor jumping into place, so it's skipped
+    pub fn accept_motion(&mut self, moved: bool) -> bool {
+        if self.discard_next_motion {
+            if moved {
+                self.discard_next_motion = false;
+            }
+            return false;
+        }
+        true
+    }
+}
+
+/// Whether the mouse should be captured again when the window regains focus
+pub fn should_recapture(enabled: bool, in_game: bool, menu_open: bool) -> bool {
+    enabled && in_game && !menu_open
 }
 
 // This system grabs the mouse when the left mouse button is pressed
@@ -40,23 +94,50 @@ fn grab_mouse(
     mut service: ResMut<InputSystem>,
 ) {
     let window = windows.get_primary_mut().unwrap();
-    if mouse.just_pressed(MouseButton::Left) {
-        window.set_cursor_visibility(false);
-        window.set_cursor_grab_mode(CursorGrabMode::Confined);
-        service.captured = true;
+    if mouse.just_pressed(MouseButton::Left) && !service.captured {
+        service.capture(window);
     }
     if key.just_pressed(KeyCode::Escape) {
-        window.set_cursor_visibility(true);
-        window.set_cursor_grab_mode(CursorGrabMode::None);
-        service.captured = false;
+        service.release(window);
+    }
+}
+
+// Releases the mouse when the window loses focus, and grabs it again on return if the player was playing
+fn recapture_on_focus(
+    mut windows: ResMut<Windows>,
+    mut focused: EventReader<WindowFocused>,
+    mut service: ResMut<InputSystem>,
+    state: Res<State<AppState>>,
+    shutdown: Res<ShutdownSequence>,
+    config: Res<ClientConfig>,
+) {
+    let window = windows.get_primary_mut().unwrap();
+
+    for event in focused.iter() {
+        if event.id != window.id() {
+            continue;
+        }
+
+        if !event.focused {
+            service.release(window);
+        } else if should_recapture(
+            config.capture_on_focus,
+            *state.current() == AppState::InGame,
+            shutdown.dialog_open(),
+        ) {
+            service.capture(window);
+        }
     }
 }
 
 fn grab_mouse_on_play(mut windows: ResMut<Windows>, mut service: ResMut<InputSystem>) {
     let window = windows.get_primary_mut().unwrap();
-    window.set_cursor_visibility(false);
-    window.set_cursor_grab_mode(CursorGrabMode::Confined);
-    service.captured = true;
+    service.capture(window);
+}
+
+fn release_mouse_on_disconnect(mut windows: ResMut<Windows>, mut service: ResMut<InputSystem>) {
+    let window = windows.get_primary_mut().unwrap();
+    service.release(window);
 }
 
 fn test(mut rerender_chunk: EventWriter<RerenderChunkFlag>, keys: Res<Input<KeyCode>>) {
@@ -67,3 +148,45 @@ fn test(mut rerender_chunk: EventWriter<RerenderChunkFlag>, keys: Res<Input<KeyC
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn just_captured() -> InputSystem {
+        InputSystem {
+            captured: true,
+            discard_next_motion: true,
+            smoothed_look: Vec2::ZERO,
+        }
+    }
+
+    #[test]
+    fn recaptures_only_in_gameplay() {
+        assert!(should_recapture(true, true, false));
+
+        assert!(!should_recapture(false, true, false));
+        assert!(!should_recapture(true, false, false));
+        assert!(!should_recapture(true, true, true));
+    }
+
+    #[test]
+    fn first_motion_after_capture_is_discarded() {
+        let mut service = just_captured();
+
+        assert!(!service.accept_motion(true));
+        assert!(service.accept_motion(true));
+        assert!(service.accept_motion(true));
+    }
+
+    #[test]
+    fn discard_waits_for_the_mouse_to_move() {
+        let mut service = just_captured();
+
+        // Frames without movement don't use up the discard
+        assert!(!service.accept_motion(false));
+        assert!(!service.accept_motion(false));
+        assert!(!service.accept_motion(true));
+        assert!(service.accept_motion(true));
+    }
+}
@@ -1,48 +1,216 @@
+use crate::config::{ClientConfig, MovementMode};
+use crate::game::blocks::states::BlockStates;
 use crate::game::player::Player;
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::input::spectator::SpectatorMode;
 use crate::systems::input::InputSystem;
-use crate::systems::physics::PhysicsObject;
+use crate::systems::networking::join::WorldInfo;
+use crate::systems::physics::simulate::VELOCITY_DAMPING;
+use crate::systems::physics::{PhysicsObject, PHYSICS_TICK_RATE};
 use bevy::prelude::*;
 use nalgebra::Vector3;
+use rc_networking::constants::block_id;
+
+/// How far the player's feet are below their position
+const EYE_HEIGHT: f32 = 1.6;
+
+/// How far ahead of the player a ledge is looked for
+const AUTO_JUMP_DISTANCE: f32 = 0.6;
+
+/// How close, in blocks, the player's feet have to be to the top of the block under them to be standing on it
+const GROUND_TOLERANCE: f32 = 0.05;
+
+/// Whether walking from a position in a direction runs into a ledge that can be stepped up.
+/// The player has to be standing on a block, and the ledge has to be a single block high with room for the player
+/// on top and nothing above the player's head
+pub fn should_auto_jump(
+    feet: Vector3<f32>,
+    direction: Vector3<f32>,
+    is_solid: impl Fn(Vector3<i32>) -> bool,
+) -> bool {
+    let horizontal = Vector3::new(direction.x, 0.0, direction.z);
+    if horizontal.norm() == 0.0 {
+        return false;
+    }
+
+    // The block the player's feet are in, measured from the ground they're standing on
+    let ground = feet.y.round();
+    let standing =
+        |v: Vector3<f32>| Vector3::new(v.x.floor() as i32, ground as i32, v.z.floor() as i32);
+    let up = Vector3::new(0, 1, 0);
+
+    let grounded = (feet.y - ground).abs() < GROUND_TOLERANCE && is_solid(standing(feet) - up);
+    if !grounded {
+        return false;
+    }
+
+    let ledge = standing(feet + horizontal.normalize() * AUTO_JUMP_DISTANCE);
+
+    is_solid(ledge)
+        && !is_solid(ledge + up)
+        && !is_solid(ledge + up * 2)
+        && !is_solid(standing(feet) + up * 2)
+}
+
+/// The upwards velocity that carries the player up by `rise` blocks and no further, as the velocity dies away
+/// over the following physics ticks
+pub fn step_up_velocity(rise: f32) -> f32 {
+    rise * (1.0 - VELOCITY_DAMPING) * PHYSICS_TICK_RATE as f32
+}
 
 pub fn update_input_movement(
     service: Res<InputSystem>,
     mut player: Query<(&mut PhysicsObject, &Player)>,
     keys: Res<Input<KeyCode>>,
+    config: Res<ClientConfig>,
+    spectator: Res<SpectatorMode>,
+    chunks: Res<ChunkSystem>,
+    blocks: Res<BlockStates>,
+    world_info: Option<Res<WorldInfo>>,
 ) {
-    if !service.captured {
+    // The player is left in place while spectating
+    if !service.captured || spectator.active {
         return;
     }
 
     let (mut player_physics, player) = player.single_mut();
 
-    let forward = -Vector3::new(player.yaw.sin(), 0.0, player.yaw.cos());
+    let mode = if keys.pressed(KeyCode::LControl) {
+        MovementMode::Sprint
+    } else {
+        MovementMode::Walk
+    };
+    let speed = config.movement_speed(mode);
+    let fly_speed = config.movement_speed(MovementMode::Fly);
+
+    let forward = -Vector3::new(player.yaw.sin(), 0.0, player.yaw.cos()) * speed;
 
     let right = forward.cross(&Vector3::new(0.0, 1.0, 0.0));
 
-    if keys.pressed(KeyCode::Space) {
-        player_physics.position.y += 0.2;
+    // Survival players can't fly. Before joining a world there's no game mode, so flying is allowed
+    let can_fly = world_info.map_or(true, |v| v.game_mode.can_fly());
+    if can_fly && keys.pressed(KeyCode::Space) {
+        player_physics.position.y += 0.2 * fly_speed;
     }
-    if keys.pressed(KeyCode::LShift) {
-        player_physics.position.y -= 0.2;
+    if can_fly && keys.pressed(KeyCode::LShift) {
+        player_physics.position.y -= 0.2 * fly_speed;
     }
+
+    let mut walking = Vector3::zeros();
     if keys.pressed(KeyCode::W) {
         // W is being held down
-        player_physics.position += forward * 0.02;
-        player_physics.velocity += forward * 2.1;
+        walking += forward;
     }
     if keys.pressed(KeyCode::S) {
-        // W is being held down
-        player_physics.position -= forward * 0.02;
-        player_physics.velocity -= forward * 2.1;
+        walking -= forward;
     }
     if keys.pressed(KeyCode::A) {
-        // W is being held down
-        player_physics.position -= right * 0.02;
-        player_physics.velocity -= right * 2.1;
+        walking -= right;
     }
     if keys.pressed(KeyCode::D) {
-        // W is being held down
-        player_physics.position += right * 0.02;
-        player_physics.velocity += right * 2.1;
+        walking += right;
+    }
+    player_physics.position += walking * 0.02;
+    player_physics.velocity += walking * 2.1;
+
+    if config.auto_jump {
+        let feet = player_physics.position - Vector3::new(0.0, EYE_HEIGHT, 0.0);
+        let is_solid = |pos| {
+            chunks.get_block(pos).map_or(false, |v| {
+                block_id(v) != 0 && !blocks.get_block(v as usize).bounding_boxes.is_empty()
+            })
+        };
+
+        // Lifted onto the ledge over the next few ticks rather than teleported on top of it
+        if should_auto_jump(feet, walking, is_solid) {
+            player_physics.velocity.y = step_up_velocity(feet.y.round() + 1.0 - feet.y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Walks east from the middle of a block, with the given blocks solid
+    fn auto_jumps_from(feet: Vector3<f32>, solid: &[[i32; 3]]) -> bool {
+        let solid = solid
+            .iter()
+            .map(|[x, y, z]| Vector3::new(*x, *y, *z))
+            .collect::<HashSet<_>>();
+
+        should_auto_jump(feet, Vector3::new(1.0, 0.0, 0.0), |pos| {
+            solid.contains(&pos)
+        })
+    }
+
+    /// Walks east standing on the ground, with the given blocks solid as well
+    fn auto_jumps(solid: &[[i32; 3]]) -> bool {
+        let mut solid = solid.to_vec();
+        solid.push([0, 0, 0]);
+        auto_jumps_from(Vector3::new(0.5, 1.0, 0.5), &solid)
+    }
+
+    #[test]
+    fn jumps_onto_a_single_block_ledge() {
+        assert!(auto_jumps(&[[1, 1, 0]]));
+    }
+
+    #[test]
+    fn does_not_jump_into_a_two_block_wall() {
+        assert!(!auto_jumps(&[[1, 1, 0], [1, 2, 0]]));
+    }
+
+    #[test]
+    fn does_not_jump_without_headroom() {
+        // A ceiling above the player
+        assert!(!auto_jumps(&[[1, 1, 0], [0, 3, 0]]));
+        // No room for the player on top of the ledge
+        assert!(!auto_jumps(&[[1, 1, 0], [1, 3, 0]]));
+    }
+
+    #[test]
+    fn does_not_jump_in_mid_air() {
+        // Nothing underfoot
+        assert!(!auto_jumps_from(Vector3::new(0.5, 1.0, 0.5), &[[1, 1, 0]]));
+        // Already on the way up, so it doesn't jump again every tick
+        assert!(!auto_jumps_from(
+            Vector3::new(0.5, 1.4, 0.5),
+            &[[0, 0, 0], [1, 1, 0]]
+        ));
+    }
+
+    #[test]
+    fn jumps_when_nearly_on_the_ground() {
+        assert!(auto_jumps_from(
+            Vector3::new(0.5, 0.98, 0.5),
+            &[[0, 0, 0], [1, 1, 0]]
+        ));
+    }
+
+    #[test]
+    fn step_up_comes_to_rest_on_the_ledge() {
+        let delta = (1.0 / PHYSICS_TICK_RATE) as f32;
+        let mut velocity = step_up_velocity(1.0);
+        let mut risen = 0.0;
+
+        // Moved and damped the same way the physics tick does
+        for _ in 0..600 {
+            risen += velocity * delta;
+            velocity *= VELOCITY_DAMPING;
+        }
+
+        assert!((risen - 1.0).abs() < 1e-3, "{}", risen);
+    }
+
+    #[test]
+    fn does_not_jump_without_a_ledge() {
+        assert!(!auto_jumps(&[]));
+        assert!(!should_auto_jump(
+            Vector3::new(0.5, 1.0, 0.5),
+            Vector3::new(0.0, 1.0, 0.0),
+            |_| true
+        ));
     }
 }
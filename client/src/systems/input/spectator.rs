@@ -0,0 +1,146 @@
+use crate::config::{ClientConfig, MovementMode};
+use crate::game::player::Player;
+use crate::systems::input::InputSystem;
+use bevy::prelude::*;
+
+/// Key that toggles spectator mode
+pub const SPECTATOR_KEY: KeyCode = KeyCode::F6;
+
+/// How far the camera moves per frame at a fly speed of 1
+const SPECTATOR_SPEED: f32 = 0.4;
+
+/// While active the camera is detached from the player and flies through blocks, and the player stays where it was left
+#[derive(Resource, Default)]
+pub struct SpectatorMode {
+    pub active: bool,
+}
+
+pub fn toggle_spectator(keys: Res<Input<KeyCode>>, mut spectator: ResMut<SpectatorMode>) {
+    if !keys.just_pressed(SPECTATOR_KEY) {
+        return;
+    }
+
+    spectator.active = !spectator.active;
+    info!("Spectator mode: {}", spectator.active);
+}
+
+/// Snaps the camera back to the authoritative player when spectating stops, whether from the key or the game mode changing
+pub fn snap_camera_on_spectator_exit(
+    spectator: Res<SpectatorMode>,
+    mut was_active: Local<bool>,
+    mut query: ParamSet<(
+        Query<&mut Transform, With<Camera>>,
+        Query<&Transform, With<Player>>,
+    )>,
+) {
+    let stopped = *was_active && !spectator.active;
+    *was_active = spectator.active;
+
+    if !stopped {
+        return;
+    }
+
+    let player = match query.p1().get_single() {
+        Ok(val) => *val,
+        Err(_) => return,
+    };
+    if let Ok(mut camera) = query.p0().get_single_mut() {
+        *camera = player;
+    }
+}
+
+pub fn update_spectator_movement(
+    service: Res<InputSystem>,
+    spectator: Res<SpectatorMode>,
+    keys: Res<Input<KeyCode>>,
+    config: Res<ClientConfig>,
+    mut query: ParamSet<(
+        Query<&mut Transform, With<Camera>>,
+        Query<(&Transform, &Player)>,
+    )>,
+) {
+    if !spectator.active {
+        return;
+    }
+
+    // Looking still rotates the player, so copy it over to the camera
+    let (rotation, yaw) = match query.p1().get_single() {
+        Ok((transform, player)) => (transform.rotation, player.yaw),
+        Err(_) => return,
+    };
+
+    let mut cameras = query.p0();
+    let mut camera = match cameras.get_single_mut() {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+    camera.rotation = rotation;
+
+    if !service.captured {
+        return;
+    }
+
+    let mode = if keys.pressed(KeyCode::LControl) {
+        MovementMode::Sprint
+    } else {
+        MovementMode::Fly
+    };
+    let speed = SPECTATOR_SPEED * config.movement_speed(mode);
+
+    let forward = -Vec3::new(yaw.sin(), 0.0, yaw.cos());
+    let right = forward.cross(Vec3::Y);
+
+    let mut movement = Vec3::ZERO;
+    if keys.pressed(KeyCode::W) {
+        movement += forward;
+    }
+    if keys.pressed(KeyCode::S) {
+        movement -= forward;
+    }
+    if keys.pressed(KeyCode::A) {
+        movement -= right;
+    }
+    if keys.pressed(KeyCode::D) {
+        movement += right;
+    }
+    if keys.pressed(KeyCode::Space) {
+        movement += Vec3::Y;
+    }
+    if keys.pressed(KeyCode::LShift) {
+        movement -= Vec3::Y;
+    }
+
+    if movement != Vec3::ZERO {
+        camera.translation += movement.normalize() * speed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    #[test]
+    fn camera_snaps_back_to_the_player_when_spectating_stops() {
+        let mut world = World::new();
+        world.insert_resource(SpectatorMode { active: true });
+        let player = Transform::from_xyz(1.0, 70.0, 2.0);
+        world.spawn((player, Player::new()));
+        let camera = world
+            .spawn((Transform::from_xyz(50.0, 90.0, -20.0), Camera::default()))
+            .id();
+
+        let mut stage = SystemStage::single_threaded().with_system(snap_camera_on_spectator_exit);
+
+        // Left where it flew to while spectating
+        stage.run(&mut world);
+        assert_eq!(
+            world.get::<Transform>(camera).unwrap().translation,
+            Vec3::new(50.0, 90.0, -20.0)
+        );
+
+        world.resource_mut::<SpectatorMode>().active = false;
+        stage.run(&mut world);
+        assert_eq!(*world.get::<Transform>(camera).unwrap(), player);
+    }
+}
@@ -1,7 +1,9 @@
 pub mod asset;
 pub mod camera;
 pub mod chunk;
+pub mod dev_tools;
 pub mod input;
 pub mod networking;
 pub mod physics;
+pub mod shutdown;
 pub mod ui;
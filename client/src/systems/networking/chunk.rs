@@ -6,7 +6,7 @@ use bevy::prelude::*;
 
 use crate::systems::chunk::builder::{RerenderChunkFlag, RerenderChunkFlagContext};
 use nalgebra::Vector3;
-use rc_networking::constants::CHUNK_SIZE;
+use rc_networking::constants::RawChunkData;
 use rc_networking::protocol::Protocol;
 use rc_networking::types::ReceivePacket;
 
@@ -23,9 +23,19 @@ pub fn network_chunk_sync(
             Protocol::PartialChunkUpdate(update) => {
                 let location = Vector3::new(update.x, update.y, update.z);
 
+                // Chunks are placed at the size the server gave when we joined, any other size wouldn't line up
+                if !update.data.is_valid() || update.data.size() != chunk_service.chunk_size {
+                    warn!(
+                        "Ignoring chunk at {:?} with size {}",
+                        location,
+                        update.data.size()
+                    );
+                    continue;
+                }
+
                 chunk_service.create_chunk(
                     location,
-                    update.data,
+                    update.data.clone(),
                     &mut commands,
                     &asset_service,
                     &mut rerender_chunks,
@@ -36,12 +46,13 @@ pub fn network_chunk_sync(
                 let location = Vector3::new(update.x, update.y, update.z);
 
                 // Locate chunk
-                let (chunk_loc, inner_loc) = global_to_local_position(location);
+                let (chunk_loc, inner_loc) =
+                    global_to_local_position(location, chunk_service.chunk_size);
 
                 // Try find chunk
                 if let Some(mut chunk) = chunk_service.chunks.get_mut(&chunk_loc) {
                     // Found chunk! Update block
-                    chunk.world[inner_loc.x][inner_loc.y][inner_loc.z] = update.id;
+                    chunk.set_block(inner_loc, update.id);
 
                     // Rerender
                     rerender_chunks.send(RerenderChunkFlag {
@@ -50,10 +61,10 @@ pub fn network_chunk_sync(
                     });
                 } else {
                     // Create chunk data
-                    let mut chunk = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+                    let mut chunk = RawChunkData::new(chunk_service.chunk_size);
 
                     // Set block
-                    chunk[inner_loc.x][inner_loc.y][inner_loc.z] = update.id;
+                    chunk.set(inner_loc.x, inner_loc.y, inner_loc.z, update.id);
 
                     // Create chunk
                     chunk_service.create_chunk(
@@ -66,6 +77,69 @@ pub fn network_chunk_sync(
                     );
                 }
             }
+            Protocol::BatchBlockUpdate(batch) => {
+                let chunk_loc = Vector3::from(batch.chunk);
+                let size = chunk_service.chunk_size;
+
+                // Positions within the chunk, skipping any update that isn't actually in it
+                let blocks = batch.updates.iter().filter_map(|update| {
+                    let (update_chunk, inner_loc) =
+                        global_to_local_position(Vector3::new(update.x, update.y, update.z), size);
+                    if update_chunk == chunk_loc {
+                        Some((inner_loc, update.id))
+                    } else {
+                        warn!("Ignoring block update outside of its batch's chunk");
+                        None
+                    }
+                });
+
+                if let Some(chunk) = chunk_service.chunks.get_mut(&chunk_loc) {
+                    for (inner_loc, id) in blocks {
+                        chunk.set_block(inner_loc, id);
+                    }
+
+                    // Rerender once for the whole batch
+                    rerender_chunks.send(RerenderChunkFlag {
+                        chunk: chunk_loc,
+                        context: RerenderChunkFlagContext::Surrounding,
+                    });
+                } else {
+                    let mut chunk = RawChunkData::new(size);
+                    for (inner_loc, id) in blocks {
+                        chunk.set(inner_loc.x, inner_loc.y, inner_loc.z, id);
+                    }
+
+                    chunk_service.create_chunk(
+                        chunk_loc,
+                        chunk,
+                        &mut commands,
+                        &asset_service,
+                        &mut rerender_chunks,
+                        &mut meshes,
+                    );
+                }
+            }
+            Protocol::FillRegion(fill) => {
+                if !fill.is_valid() {
+                    warn!("Ignoring fill of {} blocks", fill.volume());
+                    continue;
+                }
+
+                // Chunks that aren't loaded get the filled blocks from the server when they're requested
+                let block = fill.block();
+                for span in fill.chunk_spans(chunk_service.chunk_size) {
+                    let chunk_loc = Vector3::from(span.chunk);
+                    if let Some(chunk) = chunk_service.chunks.get_mut(&chunk_loc) {
+                        chunk.fill(&span, block);
+
+                        // Neighbours are rebuilt too for the faces against the fill, each chunk only builds once
+                        rerender_chunks.send(RerenderChunkFlag {
+                            chunk: chunk_loc,
+                            context: RerenderChunkFlagContext::Surrounding,
+                        });
+                    }
+                }
+            }
             _ => {}
         }
     }
@@ -0,0 +1,141 @@
+use crate::config::ClientConfig;
+use crate::state::AppState;
+use crate::systems::networking::replay::replaying;
+use crate::systems::networking::NetworkingSystem;
+use crate::systems::shutdown::ShutdownSequence;
+use bevy::prelude::*;
+use rc_networking::protocol::clientbound::disconnect::DisconnectReason;
+use rc_networking::{Client, NetworkingError, UnreadablePackets};
+
+/// Why the connection to the server was lost, shown on the disconnect screen
+#[derive(Resource)]
+pub struct Disconnected(pub NetworkingError);
+
+/// Why the server said it was about to disconnect us, shown instead of the connection's own reason
+#[derive(Resource)]
+pub struct ServerDisconnectReason(pub DisconnectReason);
+
+/// Shows the disconnect screen over the game once the connection to the server is lost
+pub fn detect_disconnect(
+    client: Option<Res<Client>>,
+    config: Res<ClientConfig>,
+    shutdown: Res<ShutdownSequence>,
+    mut system: ResMut<NetworkingSystem>,
+    mut unreadable: ResMut<UnreadablePackets>,
+    server_reason: Option<Res<ServerDisconnectReason>>,
+    mut commands: Commands,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    // Replays have no connection to lose, and quitting disconnects on purpose
+    if replaying(&config) || shutdown.in_progress() {
+        return;
+    }
+
+    let reason = match &client {
+        Some(client) => match client.disconnected() {
+            Some(reason) => reason.to_string(),
+            None => return,
+        },
+        // The client is dropped when its socket fails
+        None => String::from("the connection was lost"),
+    };
+    let reason = server_reason.map_or(reason, |v| v.0.to_string());
+    commands.remove_resource::<ServerDisconnectReason>();
+    warn!("Disconnected from the server: {}", reason);
+
+    // The server spawns its entities again when we reconnect
+    for (_, entity) in system.entity_mapping.drain() {
+        commands.entity(entity).despawn();
+    }
+
+    // Dropping the connection over packets that couldn't be read says what was wrong with them
+    let error = unreadable
+        .error
+        .take()
+        .unwrap_or(NetworkingError::Disconnected { reason });
+    *unreadable = UnreadablePackets::default();
+
+    commands.remove_resource::<Client>();
+    commands.insert_resource(Disconnected(error));
+    let _ = app_state.push(AppState::Disconnected);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc_networking::constants::EntityId;
+
+    /// In game with the connection already gone
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_state(AppState::InGame)
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(detect_disconnect))
+            .init_resource::<ClientConfig>()
+            .init_resource::<ShutdownSequence>()
+            .init_resource::<NetworkingSystem>()
+            .init_resource::<UnreadablePackets>();
+        app
+    }
+
+    fn reason(app: &App) -> Option<String> {
+        app.world
+            .get_resource::<Disconnected>()
+            .map(|v| v.0.to_string())
+    }
+
+    #[test]
+    fn losing_the_connection_opens_the_disconnect_screen() {
+        let mut app = app();
+        let entity = app.world.spawn_empty().id();
+        app.world
+            .resource_mut::<NetworkingSystem>()
+            .entity_mapping
+            .insert(EntityId(1), entity);
+
+        app.update();
+
+        assert_eq!(
+            *app.world.resource::<State<AppState>>().current(),
+            AppState::Disconnected
+        );
+        assert_eq!(
+            reason(&app).as_deref(),
+            Some("Disconnected from the server: the connection was lost")
+        );
+        // Spawned again by the server when reconnecting
+        assert!(app.world.get_entity(entity).is_none());
+        assert!(app
+            .world
+            .resource::<NetworkingSystem>()
+            .entity_mapping
+            .is_empty());
+    }
+
+    #[test]
+    fn server_reason_is_shown_over_the_connection_reason() {
+        let mut app = app();
+        app.insert_resource(ServerDisconnectReason(DisconnectReason::Idle));
+
+        app.update();
+
+        assert_eq!(
+            reason(&app).as_deref(),
+            Some("Disconnected from the server: you were idle for too long")
+        );
+        assert!(app.world.get_resource::<ServerDisconnectReason>().is_none());
+    }
+
+    #[test]
+    fn replays_stay_in_game() {
+        let mut app = app();
+        app.world.resource_mut::<ClientConfig>().play_replay = Some(String::from("replay.bin"));
+
+        app.update();
+
+        assert_eq!(
+            *app.world.resource::<State<AppState>>().current(),
+            AppState::InGame
+        );
+        assert!(reason(&app).is_none());
+    }
+}
@@ -0,0 +1,291 @@
+use crate::config::ClientConfig;
+use crate::game::world::clock::RenderClock;
+use crate::systems::physics::PhysicsObject;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::Client;
+use std::collections::VecDeque;
+
+/// How often, in seconds, the round trip time is sampled, so the smoothing doesn't depend on the frame rate
+const LATENCY_SAMPLE_INTERVAL: f32 = 0.1;
+
+/// How many deviations of jitter are buffered on top of the time between updates
+const JITTER_MARGIN: f32 = 4.0;
+
+/// The most positions kept per entity, enough for the longest delay at the server's tick rate
+const MAX_SNAPSHOTS: usize = 32;
+
+/// How long remote entities are drawn behind the latest position received, so there's always a later
+/// position to move towards. Adapts to the connection as only jitter needs buffering, a steady latency doesn't
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct InterpolationDelay {
+    /// The smoothed round trip time, in seconds
+    rtt: f32,
+    /// The smoothed deviation of the round trip time from its average
+    jitter: f32,
+    sampled: bool,
+}
+
+impl InterpolationDelay {
+    /// Folds in a round trip time in seconds, smoothed the same way TCP estimates its retransmit timeout
+    pub fn sample(&mut self, rtt: f32) {
+        if !self.sampled {
+            self.rtt = rtt;
+            self.jitter = rtt / 2.0;
+            self.sampled = true;
+            return;
+        }
+
+        self.jitter = self.jitter * 0.75 + (rtt - self.rtt).abs() * 0.25;
+        self.rtt = self.rtt * 0.875 + rtt * 0.125;
+    }
+
+    /// The delay to draw remote entities at, one update interval plus room for jitter, within the limits
+    pub fn delay(&self, update_interval: f32, min: f32, max: f32) -> f32 {
+        (update_interval + self.jitter * JITTER_MARGIN).clamp(min, max.max(min))
+    }
+}
+
+/// The positions received for an entity moved by the server, and when they arrived
+#[derive(Component, Default)]
+pub struct RemoteSnapshots {
+    snapshots: VecDeque<(f64, Vector3<f32>)>,
+}
+
+impl RemoteSnapshots {
+    pub fn push(&mut self, time: f64, position: Vector3<f32>) {
+        if self.snapshots.len() == MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((time, position));
+    }
+
+    /// Where the entity was at a time, between the positions either side of it.
+    /// Holds at the latest position rather than guessing past it
+    pub fn position_at(&self, time: f64) -> Option<Vector3<f32>> {
+        let next = self.snapshots.iter().position(|(t, _)| *t >= time);
+
+        match next {
+            Some(0) => self.snapshots.front().map(|(_, v)| *v),
+            Some(i) => {
+                let (from_time, from) = self.snapshots[i - 1];
+                let (to_time, to) = self.snapshots[i];
+                let alpha = ((time - from_time) / (to_time - from_time)) as f32;
+                Some(from.lerp(&to, alpha.clamp(0.0, 1.0)))
+            }
+            None => self.snapshots.back().map(|(_, v)| *v),
+        }
+    }
+
+    /// Drops positions that can no longer be interpolated from, keeping the one just before the time
+    pub fn prune(&mut self, time: f64) {
+        while self.snapshots.len() > 1 && self.snapshots[1].0 <= time {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+pub fn sample_latency(
+    client: Option<Res<Client>>,
+    mut delay: ResMut<InterpolationDelay>,
+    mut since_sample: Local<f32>,
+    time: Res<Time>,
+) {
+    let client = match client {
+        Some(val) => val,
+        None => return,
+    };
+
+    *since_sample += time.delta_seconds();
+    if *since_sample < LATENCY_SAMPLE_INTERVAL || !client.is_connected() {
+        return;
+    }
+    *since_sample = 0.0;
+
+    // Renet reports the round trip in milliseconds
+    delay.sample(client.network_info().rtt / 1000.0);
+}
+
+/// Draws entities moved by the server a short delay behind the latest position, moving smoothly between updates
+pub fn interpolate_remote_entities(
+    mut entities: Query<(&mut RemoteSnapshots, &mut PhysicsObject)>,
+    delay: Res<InterpolationDelay>,
+    config: Res<ClientConfig>,
+    clock: Res<RenderClock>,
+) {
+    let update_interval = 1.0 / config.clock_tick_rate.max(1) as f32;
+    // Remote entities hold still while the clock is paused, and carry on from there afterwards
+    let render_time = clock.elapsed
+        - delay.delay(
+            update_interval,
+            config.interpolation_delay_min,
+            config.interpolation_delay_max,
+        ) as f64;
+
+    for (mut snapshots, mut object) in entities.iter_mut() {
+        if let Some(position) = snapshots.position_at(render_time) {
+            object.position = position;
+            object.previous_position = position;
+        }
+        snapshots.prune(render_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: f32 = 0.05;
+
+    fn sampled(rtts: &[f32]) -> InterpolationDelay {
+        let mut delay = InterpolationDelay::default();
+        for rtt in rtts {
+            delay.sample(*rtt);
+        }
+        delay
+    }
+
+    #[test]
+    fn first_sample_assumes_some_jitter() {
+        let delay = sampled(&[0.1]);
+
+        // Half the round trip of jitter, buffered four times over
+        assert!((delay.delay(TICK, 0.0, 1.0) - (TICK + 0.2)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn steady_latency_settles_to_one_tick() {
+        let delay = sampled(&[0.2; 60]);
+
+        // However high the latency, it doesn't need buffering if it doesn't vary
+        assert!((delay.delay(TICK, 0.0, 1.0) - TICK).abs() < 1e-3);
+    }
+
+    #[test]
+    fn jittery_latency_buffers_more() {
+        let steady = sampled(&[0.1; 20]);
+        let jittery = sampled(
+            &(0..20)
+                .map(|i| if i % 2 == 0 { 0.05 } else { 0.15 })
+                .collect::<Vec<_>>(),
+        );
+
+        assert!(jittery.delay(TICK, 0.0, 1.0) > steady.delay(TICK, 0.0, 1.0) + 0.1);
+    }
+
+    #[test]
+    fn delay_recovers_once_the_jitter_stops() {
+        let mut delay = sampled(&[0.05, 0.3, 0.05, 0.3]);
+        let jittery = delay.delay(TICK, 0.0, 1.0);
+
+        for _ in 0..30 {
+            delay.sample(0.1);
+        }
+        assert!(delay.delay(TICK, 0.0, 1.0) < jittery / 4.0);
+    }
+
+    #[test]
+    fn delay_is_clamped() {
+        let steady = sampled(&[0.1; 60]);
+        assert_eq!(steady.delay(TICK, 0.08, 0.3), 0.08);
+
+        let jittery = sampled(&[0.05, 1.0, 0.05, 1.0]);
+        assert_eq!(jittery.delay(TICK, 0.08, 0.3), 0.3);
+
+        // A max below the min is ignored
+        assert_eq!(jittery.delay(TICK, 0.08, 0.01), 0.08);
+    }
+
+    fn snapshots(positions: &[(f64, f32)]) -> RemoteSnapshots {
+        let mut snapshots = RemoteSnapshots::default();
+        for (time, x) in positions {
+            snapshots.push(*time, Vector3::new(*x, 0.0, 0.0));
+        }
+        snapshots
+    }
+
+    fn x_at(snapshots: &RemoteSnapshots, time: f64) -> Option<f32> {
+        snapshots.position_at(time).map(|v| v.x)
+    }
+
+    #[test]
+    fn positions_are_interpolated_between_snapshots() {
+        let snapshots = snapshots(&[(1.0, 0.0), (2.0, 10.0), (3.0, 30.0)]);
+
+        assert_eq!(x_at(&snapshots, 1.5), Some(5.0));
+        assert_eq!(x_at(&snapshots, 2.5), Some(20.0));
+        assert_eq!(x_at(&snapshots, 2.0), Some(10.0));
+    }
+
+    #[test]
+    fn positions_hold_outside_the_snapshots() {
+        let snapshots = snapshots(&[(1.0, 0.0), (2.0, 10.0)]);
+
+        assert_eq!(x_at(&snapshots, 0.5), Some(0.0));
+        // No guessing past the latest position
+        assert_eq!(x_at(&snapshots, 5.0), Some(10.0));
+        assert_eq!(x_at(&RemoteSnapshots::default(), 1.0), None);
+    }
+
+    #[test]
+    fn pruning_keeps_the_snapshot_before_the_time() {
+        let mut snapshots = snapshots(&[(1.0, 0.0), (2.0, 10.0), (3.0, 30.0)]);
+
+        snapshots.prune(2.5);
+        assert_eq!(snapshots.snapshots.len(), 2);
+        assert_eq!(x_at(&snapshots, 2.5), Some(20.0));
+
+        // The latest is always kept
+        snapshots.prune(10.0);
+        assert_eq!(x_at(&snapshots, 10.0), Some(30.0));
+    }
+
+    #[test]
+    fn only_recent_snapshots_are_kept() {
+        let mut snapshots = RemoteSnapshots::default();
+        for i in 0..MAX_SNAPSHOTS + 5 {
+            snapshots.push(i as f64, Vector3::new(i as f32, 0.0, 0.0));
+        }
+
+        assert_eq!(snapshots.snapshots.len(), MAX_SNAPSHOTS);
+        assert_eq!(x_at(&snapshots, 0.0), Some(5.0));
+    }
+
+    #[test]
+    fn remote_entities_hold_still_while_the_clock_is_paused() {
+        use bevy::ecs::schedule::{Stage, SystemStage};
+
+        let mut world = World::new();
+        world.init_resource::<InterpolationDelay>();
+        world.insert_resource(ClientConfig {
+            interpolation_delay_min: 0.1,
+            interpolation_delay_max: 0.1,
+            ..default()
+        });
+        world.init_resource::<RenderClock>();
+
+        let entity = world
+            .spawn((
+                snapshots(&[(0.0, 0.0), (1.0, 10.0)]),
+                PhysicsObject::new(Vector3::zeros(), Vec::new()),
+            ))
+            .id();
+
+        let mut stage = SystemStage::single_threaded().with_system(interpolate_remote_entities);
+        let mut x_after = |world: &mut World, advance: Option<f32>| {
+            let mut clock = world.resource_mut::<RenderClock>();
+            match advance {
+                Some(delta) => clock.advance(delta, 20),
+                None => clock.pause(),
+            }
+            stage.run(world);
+            world.get::<PhysicsObject>(entity).unwrap().position.x
+        };
+
+        // Drawn the delay behind the clock
+        assert!((x_after(&mut world, Some(0.6)) - 5.0).abs() < 1e-4);
+        assert!((x_after(&mut world, None) - 5.0).abs() < 1e-4);
+        assert!((x_after(&mut world, None) - 5.0).abs() < 1e-4);
+        assert!((x_after(&mut world, Some(0.2)) - 7.0).abs() < 1e-4);
+    }
+}
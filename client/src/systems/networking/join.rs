@@ -0,0 +1,270 @@
+use crate::config::ClientConfig;
+use crate::game::player::Player;
+use crate::game::world::clock::RenderClock;
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::input::spectator::SpectatorMode;
+use crate::systems::physics::PhysicsObject;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::{Dimension, GameMode, MAX_CHUNK_SIZE};
+use rc_networking::protocol::Protocol;
+use rc_networking::types::ReceivePacket;
+use rc_networking::{Client, ClientCompression, NetworkingError, UnreadablePackets};
+
+/// What the server told us about the world when joining
+#[derive(Resource, Debug, Clone)]
+pub struct WorldInfo {
+    pub seed: u64,
+    pub spawn: Vector3<f32>,
+    pub game_mode: GameMode,
+    /// How many chunk sections high a column of the world is, the server doesn't send chunks outside of it
+    pub column_sections: i32,
+    pub dimension: Dimension,
+}
+
+/// Sets up the world from the server's join packet, and keeps the clock in step with it afterwards
+pub fn join_world(
+    mut packets: EventReader<ReceivePacket>,
+    mut commands: Commands,
+    mut clock: ResMut<RenderClock>,
+    mut player: Query<&mut PhysicsObject, With<Player>>,
+    config: Res<ClientConfig>,
+    mut world_info: Option<ResMut<WorldInfo>>,
+    mut spectator: ResMut<SpectatorMode>,
+    mut chunks: ResMut<ChunkSystem>,
+    mut client: Option<ResMut<Client>>,
+    mut unreadable: ResMut<UnreadablePackets>,
+    mut compression: ResMut<ClientCompression>,
+) {
+    for packet in packets.iter() {
+        match &packet.0 {
+            Protocol::JoinWorld(join) => {
+                // None of the world could be placed, so leave with the reason rather than dropping every chunk
+                let chunk_size = join.chunk_size as usize;
+                if !(1..=MAX_CHUNK_SIZE).contains(&chunk_size) {
+                    let error = NetworkingError::UnsupportedChunkSize {
+                        size: chunk_size,
+                        max: MAX_CHUNK_SIZE,
+                    };
+                    error!("Can't join the world: {}", error);
+
+                    if let Some(client) = &mut client {
+                        unreadable.error = Some(error);
+                        client.disconnect();
+                    }
+                    continue;
+                }
+
+                let spawn = Vector3::new(join.spawn_pos[0], join.spawn_pos[1], join.spawn_pos[2]);
+                info!(
+                    "Joined {:?} world with seed {} in {:?} at {:?}",
+                    join.dimension, join.seed, join.game_mode, spawn
+                );
+
+                clock.reconcile(join.time_of_day, config.clock_tick_rate);
+
+                // Everything from here on is placed at the server's chunk size
+                chunks.set_chunk_size(chunk_size, &mut commands);
+
+                if let Ok(mut physics) = player.get_single_mut() {
+                    physics.position = spawn;
+                    physics.previous_position = spawn;
+                    physics.velocity = Vector3::zeros();
+                }
+
+                spectator.active = join.game_mode == GameMode::Spectator;
+
+                // Our packets are compressed the same way as the server's from now on
+                compression.0 = join.compression;
+
+                commands.insert_resource(WorldInfo {
+                    seed: join.seed,
+                    spawn,
+                    game_mode: join.game_mode,
+                    column_sections: join.column_sections,
+                    dimension: join.dimension,
+                });
+            }
+            Protocol::SetGameMode(packet) => {
+                info!("Game mode changed to {:?}", packet.game_mode);
+
+                // Spectators fly through blocks with the camera detached from the player
+                spectator.active = packet.game_mode == GameMode::Spectator;
+
+                if let Some(info) = &mut world_info {
+                    info.game_mode = packet.game_mode;
+                }
+            }
+            Protocol::TimeSync(sync) => {
+                clock.reconcile(sync.seconds, config.clock_tick_rate);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkEdgeFaces;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::compression::Compression;
+    use rc_networking::constants::{UserId, CHUNK_SIZE};
+    use rc_networking::protocol::clientbound::join_world::JoinWorld;
+    use rc_networking::protocol::clientbound::set_game_mode::SetGameMode;
+    use rc_networking::protocol::clientbound::time_sync::TimeSync;
+
+    fn join(game_mode: GameMode, chunk_size: u32) -> Protocol {
+        Protocol::JoinWorld(JoinWorld::new(
+            42,
+            [1.0, 70.0, 2.0],
+            120.0,
+            game_mode,
+            16,
+            Dimension::Overworld,
+            chunk_size,
+        ))
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Events<ReceivePacket>>();
+        world.init_resource::<RenderClock>();
+        world.init_resource::<ClientConfig>();
+        world.init_resource::<SpectatorMode>();
+        world.init_resource::<UnreadablePackets>();
+        world.init_resource::<ClientCompression>();
+        world.insert_resource(ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None));
+        world.spawn((
+            PhysicsObject::new(Vector3::zeros(), Vec::new()),
+            Player::new(),
+        ));
+        world
+    }
+
+    /// Receives the packets and runs the join system on them
+    fn receive(world: &mut World, packets: Vec<Protocol>) {
+        for packet in packets {
+            world
+                .resource_mut::<Events<ReceivePacket>>()
+                .send(ReceivePacket(packet, UserId(0)));
+        }
+
+        SystemStage::single_threaded()
+            .with_system(join_world)
+            .run(world);
+    }
+
+    #[test]
+    fn join_sets_up_the_world() {
+        let mut world = world();
+        receive(
+            &mut world,
+            vec![join(GameMode::Creative, CHUNK_SIZE as u32)],
+        );
+
+        let info = world.resource::<WorldInfo>();
+        assert_eq!(info.seed, 42);
+        assert_eq!(info.spawn, Vector3::new(1.0, 70.0, 2.0));
+        assert_eq!(info.game_mode, GameMode::Creative);
+        assert_eq!(info.column_sections, 16);
+        assert_eq!(info.dimension, Dimension::Overworld);
+
+        assert_eq!(world.resource::<RenderClock>().seconds, 120.0);
+        assert!(!world.resource::<SpectatorMode>().active);
+
+        let position = world.query::<&PhysicsObject>().single(&world).position;
+        assert_eq!(position, Vector3::new(1.0, 70.0, 2.0));
+    }
+
+    #[test]
+    fn spectators_join_detached() {
+        let mut world = world();
+        receive(
+            &mut world,
+            vec![join(GameMode::Spectator, CHUNK_SIZE as u32)],
+        );
+
+        assert!(world.resource::<SpectatorMode>().active);
+    }
+
+    #[test]
+    fn time_follows_time_sync_after_joining() {
+        let mut world = world();
+        receive(
+            &mut world,
+            vec![
+                join(GameMode::Survival, CHUNK_SIZE as u32),
+                Protocol::TimeSync(TimeSync::new(500.0)),
+            ],
+        );
+
+        assert_eq!(world.resource::<RenderClock>().seconds, 500.0);
+    }
+
+    #[test]
+    fn chunks_are_placed_at_the_servers_size() {
+        let mut world = world();
+        receive(&mut world, vec![join(GameMode::Survival, 24)]);
+
+        assert!(world.get_resource::<WorldInfo>().is_some());
+        assert_eq!(world.resource::<ChunkSystem>().chunk_size, 24);
+    }
+
+    #[test]
+    fn unsupported_chunk_sizes_are_not_joined() {
+        for size in [0, MAX_CHUNK_SIZE as u32 + 1] {
+            let mut world = world();
+            receive(&mut world, vec![join(GameMode::Survival, size)]);
+
+            assert!(world.get_resource::<WorldInfo>().is_none());
+            assert_eq!(world.resource::<RenderClock>().seconds, 0.0);
+            assert_eq!(world.resource::<ChunkSystem>().chunk_size, CHUNK_SIZE);
+        }
+    }
+
+    #[test]
+    fn packets_to_the_server_use_the_agreed_compression() {
+        let mut world = world();
+        assert_eq!(world.resource::<ClientCompression>().0, Compression::None);
+
+        let mut packet = JoinWorld::new(
+            42,
+            [1.0, 70.0, 2.0],
+            120.0,
+            GameMode::Survival,
+            16,
+            Dimension::Overworld,
+            CHUNK_SIZE as u32,
+        );
+        packet.compression = Compression::Max;
+        receive(&mut world, vec![Protocol::JoinWorld(packet)]);
+
+        assert_eq!(world.resource::<ClientCompression>().0, Compression::Max);
+    }
+
+    #[test]
+    fn game_mode_changes_detach_and_reattach_spectators() {
+        let mut world = world();
+        receive(
+            &mut world,
+            vec![join(GameMode::Survival, CHUNK_SIZE as u32)],
+        );
+        receive(
+            &mut world,
+            vec![Protocol::SetGameMode(SetGameMode::new(GameMode::Spectator))],
+        );
+
+        assert!(world.resource::<SpectatorMode>().active);
+        assert_eq!(world.resource::<WorldInfo>().game_mode, GameMode::Spectator);
+
+        receive(
+            &mut world,
+            vec![Protocol::SetGameMode(SetGameMode::new(GameMode::Creative))],
+        );
+
+        assert!(!world.resource::<SpectatorMode>().active);
+        assert_eq!(world.resource::<WorldInfo>().game_mode, GameMode::Creative);
+    }
+}
@@ -1,3 +1,6 @@
+use crate::game::player::Player;
+use crate::systems::input::spectator::SpectatorMode;
+use crate::systems::physics::PhysicsObject;
 use bevy::prelude::*;
 use rc_networking::constants::UserId;
 
@@ -14,29 +17,29 @@ pub struct LastNetworkTranslationSync(pub Vec3);
 pub struct LastNetworkRotationSync(pub Quat);
 
 pub fn network_location_sync(
-    query: Query<&Transform, (With<Camera>, Changed<Transform>)>,
+    query: Query<(&Transform, &PhysicsObject), (With<Player>, Changed<Transform>)>,
     mut translation: ResMut<LastNetworkTranslationSync>,
     mut rotation: ResMut<LastNetworkRotationSync>,
     mut networking: EventWriter<SendPacket>,
+    spectator: Res<SpectatorMode>,
 ) {
-    if query.is_empty() {
+    // Spectating shouldn't move the player on the server
+    if query.is_empty() || spectator.active {
         return;
     }
 
-    let transform = query.single();
+    let (transform, physics) = query.single();
 
-    let translation_diff = transform.translation.distance(translation.0);
+    // The server is sent where the latest tick left the player, not where they're drawn between ticks
+    let position = Vec3::new(physics.position.x, physics.position.y, physics.position.z);
+    let translation_diff = position.distance(translation.0);
 
     if translation_diff > MIN_LOCATION_CHANGE_SYNC {
         networking.send(SendPacket(
-            Protocol::PlayerMove(PlayerMove::new(
-                transform.translation.x,
-                transform.translation.y,
-                transform.translation.z,
-            )),
+            Protocol::PlayerMove(PlayerMove::new(position.x, position.y, position.z)),
             UserId(0),
         ));
-        translation.0 = transform.translation;
+        translation.0 = position;
     }
 
     let rotation_diff = (transform.rotation.x - rotation.0.x).abs()
@@ -57,3 +60,78 @@ pub fn network_location_sync(
         rotation.0 = transform.rotation;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use nalgebra::Vector3;
+
+    /// Runs the sync once for a player who has moved away from where the server last saw them, returning how many
+    /// moves were sent
+    fn moves_sent(spectating: bool) -> usize {
+        let mut world = World::new();
+        world.insert_resource(LastNetworkTranslationSync(Vec3::ZERO));
+        world.insert_resource(LastNetworkRotationSync(Quat::IDENTITY));
+        world.insert_resource(SpectatorMode { active: spectating });
+        world.init_resource::<Events<SendPacket>>();
+        world.spawn((
+            Transform::default(),
+            PhysicsObject::new(Vector3::new(4.0, 10.0, 4.0), Vec::new()),
+            Player::new(),
+        ));
+
+        SystemStage::single_threaded()
+            .with_system(network_location_sync)
+            .run(&mut world);
+
+        world
+            .resource::<Events<SendPacket>>()
+            .iter_current_update_events()
+            .filter(|packet| matches!(packet.0, Protocol::PlayerMove(_)))
+            .count()
+    }
+
+    #[test]
+    fn moving_player_is_synced() {
+        assert_eq!(moves_sent(false), 1);
+    }
+
+    #[test]
+    fn spectating_suppresses_player_moves() {
+        assert_eq!(moves_sent(true), 0);
+    }
+
+    #[test]
+    fn server_is_sent_the_tick_position_not_the_drawn_one() {
+        let mut world = World::new();
+        world.insert_resource(LastNetworkTranslationSync(Vec3::ZERO));
+        world.insert_resource(LastNetworkRotationSync(Quat::IDENTITY));
+        world.insert_resource(SpectatorMode { active: false });
+        world.init_resource::<Events<SendPacket>>();
+
+        // Drawn halfway between the previous and latest tick
+        let mut physics = PhysicsObject::new(Vector3::new(4.0, 10.0, 4.0), Vec::new());
+        physics.previous_position = Vector3::new(0.0, 10.0, 0.0);
+        world.spawn((Transform::from_xyz(2.0, 10.0, 2.0), physics, Player::new()));
+
+        SystemStage::single_threaded()
+            .with_system(network_location_sync)
+            .run(&mut world);
+
+        let moves: Vec<_> = world
+            .resource::<Events<SendPacket>>()
+            .iter_current_update_events()
+            .filter_map(|packet| match packet.0 {
+                Protocol::PlayerMove(val) => Some(val),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(moves, vec![PlayerMove::new(4.0, 10.0, 4.0)]);
+        assert_eq!(
+            world.resource::<LastNetworkTranslationSync>().0,
+            Vec3::new(4.0, 10.0, 4.0)
+        );
+    }
+}
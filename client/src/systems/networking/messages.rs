@@ -1,41 +1,45 @@
 use crate::game::entity::Entity;
+use crate::game::world::clock::RenderClock;
 
+use crate::systems::networking::disconnect::ServerDisconnectReason;
+use crate::systems::networking::interpolation::RemoteSnapshots;
 use crate::systems::networking::NetworkingSystem;
 use crate::systems::physics::PhysicsObject;
 use bevy::prelude::*;
 
 use nalgebra::Vector3;
 
-use crate::state::AppState;
+use crate::game::inventory::Inventory;
+use crate::game::item::states::ItemStates;
+use crate::game::item::ItemStack;
 use crate::systems::physics::aabb::Aabb;
+use rc_networking::constants::UserId;
 use rc_networking::protocol::Protocol;
-use rc_networking::types::ReceivePacket;
+use rc_networking::types::{ReceivePacket, SendPacket};
 
 pub fn messages_update(
     mut event_reader: EventReader<ReceivePacket>,
     mut transforms: Query<&mut Transform>,
-    mut physics_objects: Query<&mut PhysicsObject>,
+    mut remote: Query<&mut RemoteSnapshots>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut system: ResMut<NetworkingSystem>,
-    mut app_state: ResMut<State<AppState>>,
+    mut inventory: ResMut<Inventory>,
+    items: Res<ItemStates>,
+    mut send_packets: EventWriter<SendPacket>,
+    clock: Res<RenderClock>,
 ) {
-    // If we've received packets we're connected. Change this in the future to be a dedicated packet
-    if *app_state.current() == AppState::Connecting {
-        app_state.set(AppState::InGame).unwrap();
-    }
     for event in event_reader.iter() {
         match &event.0 {
             Protocol::EntityMoved(update) => {
-                if let Some(Ok(mut transform)) = system
+                // Drawn once the interpolation delay catches up with it
+                if let Some(Ok(mut snapshots)) = system
                     .entity_mapping
                     .get(&update.entity)
-                    .map(|v| physics_objects.get_mut(*v))
+                    .map(|v| remote.get_mut(*v))
                 {
-                    transform.position.x = update.x;
-                    transform.position.y = update.y;
-                    transform.position.z = update.z;
+                    snapshots.push(clock.elapsed, Vector3::new(update.x, update.y, update.z));
                 } else {
                     error!("Move event received before entity created");
                 }
@@ -52,6 +56,10 @@ pub fn messages_update(
                 }
             }
             Protocol::SpawnEntity(entity) => {
+                let position = Vector3::new(entity.loc[0], entity.loc[1], entity.loc[2]);
+                let mut snapshots = RemoteSnapshots::default();
+                snapshots.push(clock.elapsed, position);
+
                 let entity_id = commands
                     .spawn(Transform::from_rotation(Quat::from_xyzw(
                         entity.rot[0],
@@ -59,14 +67,9 @@ pub fn messages_update(
                         entity.rot[2],
                         entity.rot[3],
                     )))
-                    .insert(PhysicsObject::new(
-                        Vector3::new(entity.loc[0], entity.loc[1], entity.loc[2]),
-                        vec![Aabb::new(
-                            Vector3::new(0.0, 1.0, 0.0),
-                            Vector3::new(1.0, 1.0, 1.0),
-                        )],
-                    ))
+                    .insert(PhysicsObject::new(position, vec![Aabb::player()]))
                     .insert(Entity)
+                    .insert(snapshots)
                     .insert(PbrBundle {
                         mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
                         material: materials.add(Color::rgb(0.3, 0.8, 0.3).into()),
@@ -78,15 +81,43 @@ pub fn messages_update(
 
                 info!("Entity spawned {:?}!", entity.id);
             }
-            Protocol::PartialChunkUpdate(_) => {}
             Protocol::DespawnEntity(packet) => {
                 if let Some(entity) = system.entity_mapping.remove(&packet.entity) {
                     commands.entity(entity).despawn();
                 }
             }
-            other => {
-                info!("Other {:?}", other);
+            Protocol::InventoryUpdate(packet) => {
+                let slot = match inventory.hotbar.get_mut(packet.slot as usize) {
+                    Some(val) => val,
+                    None => {
+                        warn!("Inventory update for invalid slot {}", packet.slot);
+                        continue;
+                    }
+                };
+
+                *slot = if packet.amount == 0 {
+                    None
+                } else if let Some(item) = items.get_by_block(packet.item) {
+                    Some(ItemStack::new(item.clone(), packet.amount))
+                } else {
+                    warn!("Inventory update for unknown item {}", packet.item);
+                    None
+                };
+            }
+            // Answered straight away, so any interval the server picks works
+            Protocol::KeepAlive(packet) => {
+                send_packets.send(SendPacket(Protocol::KeepAlive(*packet), UserId(0)));
+            }
+            Protocol::ChatSent(packet) => {
+                info!("[Chat] {}", packet.message);
+            }
+            // The connection is dropped shortly after, when the reason is shown
+            Protocol::Disconnect(packet) => {
+                warn!("The server is disconnecting us: {}", packet.reason);
+                commands.insert_resource(ServerDisconnectReason(packet.reason));
             }
+            // Anything else is handled by another system, or reported as unhandled
+            _ => {}
         }
     }
 }
@@ -1,19 +1,32 @@
 use crate::systems::networking::chunk::network_chunk_sync;
+use crate::systems::networking::disconnect::detect_disconnect;
 use crate::systems::networking::events::authorization::AuthorizationEvent;
 use crate::systems::networking::events::connection::ConnectionEvent;
 use crate::systems::networking::events::disconnect::DisconnectionEvent;
+use crate::systems::networking::interpolation::{
+    interpolate_remote_entities, sample_latency, InterpolationDelay,
+};
+use crate::systems::networking::join::join_world;
 use crate::systems::networking::location_sync::{
     network_location_sync, LastNetworkRotationSync, LastNetworkTranslationSync,
 };
 use crate::systems::networking::messages::messages_update;
+use crate::systems::networking::replay::{
+    finish_replay, play_replay, record_replay, replaying, start_replay,
+};
 
 use bevy::prelude::*;
 use bevy::prelude::{info, Entity, SystemSet, Vec3};
 
+use rc_networking::compression::{compression_user_data, Compression};
 use rc_networking::constants::EntityId;
+use rc_networking::dispatch::{PacketHandlerAppExt, PacketKind};
+use rc_networking::player_name::write_name_user_data;
 
+use crate::config::ClientConfig;
 use crate::state::AppState;
-use rc_networking::renet::ClientAuthentication;
+use crate::systems::physics::sync::physics_sync;
+use rc_networking::renet::{ClientAuthentication, RenetClient};
 use rc_networking::*;
 
 use rc_networking::types::{ReceivePacket, SendPacket};
@@ -22,9 +35,13 @@ use std::net::{SocketAddr, UdpSocket};
 use std::time::SystemTime;
 
 mod chunk;
+pub mod disconnect;
 mod events;
+pub mod interpolation;
+pub mod join;
 mod location_sync;
 mod messages;
+pub mod replay;
 
 pub struct NetworkingPlugin;
 
@@ -33,44 +50,136 @@ impl Plugin for NetworkingPlugin {
         app.add_plugin(RenetClientPlugin)
             // Once the game is in the Main Menu connect to server as we have no main screen yet
             .add_system_set(
-                SystemSet::on_enter(AppState::Connecting).with_system(connect_to_server),
+                SystemSet::on_enter(AppState::Connecting)
+                    .with_system(connect_to_server)
+                    .with_system(start_replay),
             )
+            .add_system(play_replay.before(messages_update))
+            .add_system(record_replay)
+            .add_system_to_stage(CoreStage::PostUpdate, finish_replay)
             .add_system(messages_update)
+            .handle_packets(&[
+                PacketKind::EntityMoved,
+                PacketKind::EntityRotated,
+                PacketKind::SpawnEntity,
+                PacketKind::DespawnEntity,
+                PacketKind::InventoryUpdate,
+                PacketKind::KeepAlive,
+                PacketKind::ChatSent,
+                PacketKind::Disconnect,
+            ])
+            .add_system(join_world)
+            .handle_packets(&[
+                PacketKind::JoinWorld,
+                PacketKind::TimeSync,
+                PacketKind::SetGameMode,
+            ])
             .add_system(network_location_sync)
+            .insert_resource(InterpolationDelay::default())
+            .add_system(sample_latency)
+            .add_system(
+                interpolate_remote_entities
+                    .after(messages_update)
+                    .before(physics_sync),
+            )
+            .add_system_set(SystemSet::on_update(AppState::InGame).with_system(detect_disconnect))
             .add_event::<ReceivePacket>()
             .add_event::<SendPacket>()
             .add_event::<ConnectionEvent>()
             .add_event::<DisconnectionEvent>()
             .add_event::<AuthorizationEvent>()
             .add_system(network_chunk_sync)
+            .handle_packets(&[
+                PacketKind::PartialChunkUpdate,
+                PacketKind::BlockUpdate,
+                PacketKind::FillRegion,
+                PacketKind::BatchBlockUpdate,
+            ])
             .insert_resource(LastNetworkTranslationSync(Vec3::default()))
             .insert_resource(LastNetworkRotationSync(Quat::default()))
             .insert_resource(NetworkingSystem::default());
     }
 }
 
-pub fn connect_to_server(mut commands: Commands) {
+/// Why connecting to the server last failed, shown on the main menu
+#[derive(Resource)]
+pub struct ConnectionFailure(pub NetworkingError);
+
+/// The address of the server last connected to, reused when reconnecting
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastServer(pub SocketAddr);
+
+/// The server connected to when there's no previous server to go back to
+pub fn server_address(last: Option<&LastServer>) -> SocketAddr {
+    last.map_or(([127, 0, 0, 1], 25568).into(), |v| v.0)
+}
+
+fn create_client(
+    server_addr: SocketAddr,
+    compression: &[Compression],
+    name: &str,
+) -> Result<RenetClient, NetworkingError> {
     let bind_addr: SocketAddr = ([127, 0, 0, 1], 0).into();
-    let server_addr: SocketAddr = ([127, 0, 0, 1], 25568).into();
     let current_time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
-    let socket = UdpSocket::bind(bind_addr).unwrap();
+    let socket = UdpSocket::bind(bind_addr)?;
     let user_id = current_time.as_millis() as u64;
-    let client = rc_networking::renet::RenetClient::new(
+    let mut user_data = compression_user_data(compression);
+    write_name_user_data(&mut user_data, name);
+    let client = RenetClient::new(
         current_time,
         socket,
         user_id,
         get_renet_connection_config(),
         ClientAuthentication::Secure {
-            connect_token: get_simple_connect_token(user_id, vec![server_addr]),
+            connect_token: get_simple_connect_token(user_id, vec![server_addr], &user_data),
         },
-    )
-    .unwrap();
+    )?;
 
-    commands.insert_resource(Client(client));
+    Ok(client)
+}
+
+/// Starts connecting to a server as the named player, remembering it so it can be reconnected to
+pub fn connect(
+    commands: &mut Commands,
+    server_addr: SocketAddr,
+    compression: &[Compression],
+    name: &str,
+) -> Result<(), NetworkingError> {
+    let client = create_client(server_addr, compression, name)?;
 
+    commands.remove_resource::<ConnectionFailure>();
+    commands.insert_resource(Client(client));
+    commands.insert_resource(LastServer(server_addr));
     info!("Connecting to server on {}", server_addr);
+
+    Ok(())
+}
+
+pub fn connect_to_server(
+    mut commands: Commands,
+    config: Res<ClientConfig>,
+    last_server: Option<Res<LastServer>>,
+    mut app_state: ResMut<State<AppState>>,
+) {
+    // Replays stand in for the server
+    if replaying(&config) {
+        return;
+    }
+
+    let server_addr = server_address(last_server.as_deref());
+
+    if let Err(err) = connect(
+        &mut commands,
+        server_addr,
+        &config.compression,
+        &config.player_name,
+    ) {
+        error!("Failed to connect to {}: {}", server_addr, err);
+        commands.insert_resource(ConnectionFailure(err));
+        let _ = app_state.set(AppState::MainMenu);
+    }
 }
 
 #[derive(Resource)]
@@ -85,3 +194,34 @@ impl Default for NetworkingSystem {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    #[test]
+    fn reconnecting_reuses_the_last_server() {
+        let last = LastServer(([192, 168, 1, 20], 4000).into());
+
+        assert_eq!(server_address(Some(&last)), last.0);
+        assert_eq!(server_address(None), ([127, 0, 0, 1], 25568).into());
+    }
+
+    #[test]
+    fn connecting_remembers_the_server() {
+        let server_addr: SocketAddr = ([127, 0, 0, 1], 4000).into();
+
+        let mut world = World::new();
+        world.insert_resource(ConnectionFailure(NetworkingError::Timeout));
+        SystemStage::single_threaded()
+            .with_system(move |mut commands: Commands| {
+                connect(&mut commands, server_addr, &[], "alice").unwrap();
+            })
+            .run(&mut world);
+
+        assert_eq!(*world.resource::<LastServer>(), LastServer(server_addr));
+        assert!(world.get_resource::<Client>().is_some());
+        assert!(world.get_resource::<ConnectionFailure>().is_none());
+    }
+}
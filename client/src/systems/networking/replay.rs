@@ -0,0 +1,318 @@
+use crate::config::ClientConfig;
+use crate::game::world::clock::RenderClock;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use rc_networking::constants::UserId;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::ReceivePacket;
+use rc_networking::{PROTOCOL_ID, PROTOCOL_VERSION};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime};
+use thiserror::Error;
+
+const REPLAY_MAGIC: [u8; 8] = *b"RCREPLAY";
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("failed to access replay file")]
+    Io(#[from] io::Error),
+    #[error("invalid replay data")]
+    Invalid(#[from] bincode::Error),
+    #[error("not a replay file")]
+    NotAReplay,
+    #[error("replay was recorded with protocol {found}, expected {expected}")]
+    IncompatibleProtocol { expected: u32, found: u32 },
+}
+
+/// Written at the start of every replay so replays from other protocols are rejected rather than misread
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ReplayHeader {
+    pub magic: [u8; 8],
+    pub protocol_id: u64,
+    pub protocol_version: u32,
+}
+
+impl ReplayHeader {
+    pub fn current() -> ReplayHeader {
+        ReplayHeader {
+            magic: REPLAY_MAGIC,
+            protocol_id: PROTOCOL_ID,
+            protocol_version: PROTOCOL_VERSION,
+        }
+    }
+
+    pub fn check(&self) -> Result<(), ReplayError> {
+        if self.magic != REPLAY_MAGIC || self.protocol_id != PROTOCOL_ID {
+            return Err(ReplayError::NotAReplay);
+        }
+        if self.protocol_version != PROTOCOL_VERSION {
+            return Err(ReplayError::IncompatibleProtocol {
+                expected: PROTOCOL_VERSION,
+                found: self.protocol_version,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A packet received from the server, and when it was received in seconds since recording started
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayRecord {
+    pub time: f32,
+    pub packet: Protocol,
+}
+
+pub fn write_replay_header(writer: &mut impl Write) -> Result<(), ReplayError> {
+    bincode::serialize_into(writer, &ReplayHeader::current())?;
+    Ok(())
+}
+
+pub fn write_replay_record(
+    writer: &mut impl Write,
+    record: &ReplayRecord,
+) -> Result<(), ReplayError> {
+    bincode::serialize_into(writer, record)?;
+    Ok(())
+}
+
+/// Reads a whole replay, checking it was recorded with this protocol
+pub fn read_replay(bytes: &[u8]) -> Result<Vec<ReplayRecord>, ReplayError> {
+    let mut reader = bytes;
+
+    let header: ReplayHeader = bincode::deserialize_from(&mut reader)?;
+    header.check()?;
+
+    let mut records = Vec::new();
+    while !reader.is_empty() {
+        records.push(bincode::deserialize_from(&mut reader)?);
+    }
+    Ok(records)
+}
+
+/// Writes every packet received from the server to a replay file
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+/// Feeds the packets of a replay back in as if they came from the server
+#[derive(Resource)]
+pub struct ReplayPlayer {
+    records: VecDeque<ReplayRecord>,
+    elapsed: f32,
+}
+
+/// Whether the client is playing a replay rather than connecting to a server
+pub fn replaying(config: &ClientConfig) -> bool {
+    config.play_replay.is_some()
+}
+
+/// Starts recording or playing back a replay when connecting, depending on the config
+pub fn start_replay(mut commands: Commands, config: Res<ClientConfig>) {
+    if let Some(path) = &config.play_replay {
+        match fs::read(path)
+            .map_err(ReplayError::from)
+            .and_then(|v| read_replay(&v))
+        {
+            Ok(records) => {
+                info!("Playing replay {} with {} packets", path, records.len());
+                commands.insert_resource(ReplayPlayer {
+                    records: records.into(),
+                    elapsed: 0.0,
+                });
+            }
+            Err(err) => error!("Failed to load replay {}: {}", path, err),
+        }
+        return;
+    }
+
+    if !config.record_replay {
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |v| v.as_secs());
+    let path = Path::new(&config.replay_directory).join(format!("replay-{}.bin", timestamp));
+
+    let writer = fs::create_dir_all(&config.replay_directory)
+        .and_then(|_| File::create(&path))
+        .map_err(ReplayError::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write_replay_header(&mut writer)?;
+            Ok(writer)
+        });
+
+    match writer {
+        Ok(writer) => {
+            info!("Recording replay to {:?}", path);
+            commands.insert_resource(ReplayRecorder {
+                writer,
+                started: Instant::now(),
+            });
+        }
+        Err(err) => error!("Failed to start recording replay: {}", err),
+    }
+}
+
+pub fn record_replay(
+    recorder: Option<ResMut<ReplayRecorder>>,
+    mut packets: EventReader<ReceivePacket>,
+    mut commands: Commands,
+) {
+    let mut recorder = match recorder {
+        Some(val) => val,
+        None => return,
+    };
+
+    let time = recorder.started.elapsed().as_secs_f32();
+
+    for packet in packets.iter() {
+        let record = ReplayRecord {
+            time,
+            packet: packet.0.clone(),
+        };
+
+        if let Err(err) = write_replay_record(&mut recorder.writer, &record) {
+            error!("Failed to record replay, stopping: {}", err);
+            commands.remove_resource::<ReplayRecorder>();
+            return;
+        }
+    }
+}
+
+/// Sends each packet of the replay once the time it was received at has passed
+pub fn play_replay(
+    player: Option<ResMut<ReplayPlayer>>,
+    mut packets: EventWriter<ReceivePacket>,
+    clock: Res<RenderClock>,
+) {
+    let mut player = match player {
+        Some(val) => val,
+        None => return,
+    };
+
+    // Waits along with the rest of the world while the clock is paused
+    player.elapsed += clock.delta;
+
+    while player
+        .records
+        .front()
+        .map_or(false, |v| v.time <= player.elapsed)
+    {
+        let record = player.records.pop_front().unwrap();
+        packets.send(ReceivePacket(record.packet, UserId(0)));
+
+        if player.records.is_empty() {
+            info!("Replay finished");
+        }
+    }
+}
+
+/// Flushes the replay being recorded so it's complete when the game closes
+pub fn finish_replay(recorder: Option<ResMut<ReplayRecorder>>, mut exit: EventReader<AppExit>) {
+    if exit.iter().count() == 0 {
+        return;
+    }
+    if let Some(mut recorder) = recorder {
+        let _ = recorder.writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc_networking::protocol::clientbound::block_update::BlockUpdate;
+    use rc_networking::protocol::clientbound::keep_alive::KeepAlive;
+
+    fn records() -> Vec<ReplayRecord> {
+        vec![
+            ReplayRecord {
+                time: 0.0,
+                packet: Protocol::KeepAlive(KeepAlive::new(1)),
+            },
+            ReplayRecord {
+                time: 0.5,
+                packet: Protocol::BlockUpdate(BlockUpdate::new(6, 1, -2, 3)),
+            },
+            ReplayRecord {
+                time: 1.25,
+                packet: Protocol::KeepAlive(KeepAlive::new(2)),
+            },
+        ]
+    }
+
+    /// A replay as it would be written to a file, with the given header
+    fn replay_bytes(header: &ReplayHeader) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bincode::serialize_into(&mut bytes, header).unwrap();
+        for record in records() {
+            write_replay_record(&mut bytes, &record).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn recorded_stream_round_trips() {
+        let mut bytes = Vec::new();
+        write_replay_header(&mut bytes).unwrap();
+        for record in records() {
+            write_replay_record(&mut bytes, &record).unwrap();
+        }
+
+        assert_eq!(read_replay(&bytes).unwrap(), records());
+    }
+
+    #[test]
+    fn empty_replay_has_no_records() {
+        let mut bytes = Vec::new();
+        write_replay_header(&mut bytes).unwrap();
+
+        assert!(read_replay(&bytes).unwrap().is_empty());
+    }
+
+    #[test]
+    fn other_protocol_versions_are_rejected() {
+        let bytes = replay_bytes(&ReplayHeader {
+            protocol_version: PROTOCOL_VERSION + 1,
+            ..ReplayHeader::current()
+        });
+
+        assert!(matches!(
+            read_replay(&bytes),
+            Err(ReplayError::IncompatibleProtocol { expected, found })
+                if expected == PROTOCOL_VERSION && found == PROTOCOL_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn other_files_are_not_replays() {
+        let bytes = replay_bytes(&ReplayHeader {
+            magic: *b"NOTREPLY",
+            ..ReplayHeader::current()
+        });
+        assert!(matches!(read_replay(&bytes), Err(ReplayError::NotAReplay)));
+
+        let bytes = replay_bytes(&ReplayHeader {
+            protocol_id: PROTOCOL_ID + 1,
+            ..ReplayHeader::current()
+        });
+        assert!(matches!(read_replay(&bytes), Err(ReplayError::NotAReplay)));
+    }
+
+    #[test]
+    fn truncated_replay_is_invalid() {
+        let bytes = replay_bytes(&ReplayHeader::current());
+
+        assert!(matches!(
+            read_replay(&bytes[..bytes.len() - 1]),
+            Err(ReplayError::Invalid(_))
+        ));
+    }
+}
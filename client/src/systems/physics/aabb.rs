@@ -1,9 +1,10 @@
-use bevy::prelude::{ResMut, Vec3};
+use bevy::prelude::{Color, ResMut, Vec3};
 use bevy_prototype_debug_lines::DebugLines;
 use nalgebra::Vector3;
+use rc_networking::constants::{PLAYER_COLLIDER_OFFSET, PLAYER_COLLIDER_SIZE};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize, Serialize)]
 pub struct Aabb {
     bottom_left: Vector3<f32>,
     size: Vector3<f32>,
@@ -14,6 +15,45 @@ impl Aabb {
         Aabb { bottom_left, size }
     }
 
+    /// The box a player collides with, the same one the server keeps placed blocks out of
+    pub fn player() -> Aabb {
+        Aabb::new(
+            Vector3::from(PLAYER_COLLIDER_OFFSET),
+            Vector3::from(PLAYER_COLLIDER_SIZE),
+        )
+    }
+
+    /// Grows the box outwards by an amount on every side
+    pub fn inflate(&self, amount: f32) -> Aabb {
+        Aabb::new(
+            self.bottom_left - Vector3::new(amount, amount, amount),
+            self.size + Vector3::new(amount, amount, amount) * 2.0,
+        )
+    }
+
+    /// Shrinks or grows the box by a factor, keeping it centred in the same place
+    pub fn scale(&self, factor: f32) -> Aabb {
+        let size = self.size * factor;
+        Aabb::new(self.bottom_left + (self.size - size) / 2.0, size)
+    }
+
+    /// Rotates the box clockwise around the center of a block by a number of 90 degree steps
+    pub fn rotate_y(&self, steps: u8) -> Aabb {
+        let mut aabb = *self;
+        for _ in 0..(steps % 4) {
+            // Points move from (x, z) to (1 - z, x), so the far z edge becomes the new minimum x
+            aabb = Aabb::new(
+                Vector3::new(
+                    1.0 - (aabb.bottom_left.z + aabb.size.z),
+                    aabb.bottom_left.y,
+                    aabb.bottom_left.x,
+                ),
+                Vector3::new(aabb.size.z, aabb.size.y, aabb.size.x),
+            );
+        }
+        aabb
+    }
+
     pub fn draw_lines(boxes: &Vec<Aabb>, position: Vector3<f32>, lines: &mut ResMut<DebugLines>) {
         for val in boxes {
             val.draw(position, lines, 0.0);
@@ -21,42 +61,58 @@ impl Aabb {
     }
 
     pub fn draw(&self, position: Vector3<f32>, lines: &mut ResMut<DebugLines>, duration: f32) {
+        self.draw_colored(position, lines, duration, Color::WHITE);
+    }
+
+    pub fn draw_colored(
+        &self,
+        position: Vector3<f32>,
+        lines: &mut ResMut<DebugLines>,
+        duration: f32,
+        color: Color,
+    ) {
         let base = self.bottom_left + position;
 
         // Bottom ring
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x, base.y, base.z),
             Vec3::new(base.x + self.size.x, base.y, base.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x, base.y, base.z),
             Vec3::new(base.x, base.y, base.z + self.size.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x + self.size.x, base.y, base.z + self.size.z),
             Vec3::new(base.x + self.size.x, base.y, base.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x + self.size.x, base.y, base.z + self.size.z),
             Vec3::new(base.x, base.y, base.z + self.size.z),
             duration,
+            color,
         );
 
         // Top ring
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x, base.y + self.size.y, base.z),
             Vec3::new(base.x + self.size.x, base.y + self.size.y, base.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x, base.y + self.size.y, base.z),
             Vec3::new(base.x, base.y + self.size.y, base.z + self.size.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(
                 base.x + self.size.x,
                 base.y + self.size.y,
@@ -64,8 +120,9 @@ impl Aabb {
             ),
             Vec3::new(base.x + self.size.x, base.y + self.size.y, base.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(
                 base.x + self.size.x,
                 base.y + self.size.y,
@@ -73,25 +130,29 @@ impl Aabb {
             ),
             Vec3::new(base.x, base.y + self.size.y, base.z + self.size.z),
             duration,
+            color,
         );
 
         // Vertical ring
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x, base.y, base.z),
             Vec3::new(base.x, base.y + self.size.y, base.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x, base.y, base.z + self.size.z),
             Vec3::new(base.x, base.y + self.size.y, base.z + self.size.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x + self.size.x, base.y, base.z),
             Vec3::new(base.x + self.size.x, base.y + self.size.y, base.z),
             duration,
+            color,
         );
-        lines.line(
+        lines.line_colored(
             Vec3::new(base.x + self.size.x, base.y, base.z + self.size.z),
             Vec3::new(
                 base.x + self.size.x,
@@ -99,6 +160,7 @@ impl Aabb {
                 base.z + self.size.z,
             ),
             duration,
+            color,
         );
     }
 
@@ -136,3 +198,32 @@ impl Aabb {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        (a - b).norm() < 1e-6
+    }
+
+    #[test]
+    fn inflated_box_grows_on_every_side() {
+        let outline = Aabb::new(Vector3::zeros(), Vector3::new(1.0, 0.5, 1.0)).inflate(0.002);
+
+        assert!(close(
+            outline.bottom_left,
+            Vector3::new(-0.002, -0.002, -0.002)
+        ));
+        assert!(close(
+            outline.bottom_left + outline.size,
+            Vector3::new(1.002, 0.502, 1.002)
+        ));
+    }
+
+    #[test]
+    fn inflating_by_nothing_keeps_the_box() {
+        let aabb = Aabb::new(Vector3::new(0.25, 0.0, 0.25), Vector3::new(0.5, 1.0, 0.5));
+        assert_eq!(aabb.inflate(0.0), aabb);
+    }
+}
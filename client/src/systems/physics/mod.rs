@@ -1,20 +1,37 @@
+use crate::systems::input::movement::update_input_movement;
 use crate::systems::physics::aabb::Aabb;
 use crate::systems::physics::simulate::physics_tick;
 use crate::systems::physics::sync::physics_sync;
 use bevy::ecs::component::Component;
-use bevy::prelude::{App, Plugin};
+use bevy::prelude::{App, IntoSystemDescriptor, Plugin, SystemSet};
+use bevy::time::FixedTimestep;
 use nalgebra::Vector3;
 
 pub mod aabb;
 pub mod raycasts;
 mod simulate;
-mod sync;
+pub mod sync;
+
+/// How many times a second physics and player movement are simulated, whatever the frame rate
+pub const PHYSICS_TICK_RATE: f64 = 60.0;
+
+/// The label of the physics timestep, used to find how far the frame is between ticks
+pub const PHYSICS_TIMESTEP: &str = "physics";
 
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(physics_tick).add_system(physics_sync);
+        app.add_system_set(
+            SystemSet::new()
+                .with_run_criteria(
+                    FixedTimestep::steps_per_second(PHYSICS_TICK_RATE).with_label(PHYSICS_TIMESTEP),
+                )
+                .with_system(physics_tick)
+                // Moves from where the tick left the player, so the move is interpolated along with the rest
+                .with_system(update_input_movement.after(physics_tick)),
+        )
+        .add_system(physics_sync.after(update_input_movement));
     }
 }
 
@@ -6,6 +6,7 @@ use crate::game::blocks::states::BlockStates;
 use crate::systems::chunk::data::ChunkData;
 
 use nalgebra::Vector3;
+use rc_networking::constants::block_id;
 
 pub struct RaycastResult {
     pub block: Vector3<i32>,
@@ -69,7 +70,7 @@ pub fn do_raycast(
     let mut last_position = block;
 
     while distance < max_distance {
-        let (chunk_pos, local_pos) = global_to_local_position(block);
+        let (chunk_pos, local_pos) = global_to_local_position(block, chunks.chunk_size);
 
         // Use last chunk if its the same chunk, otherwise fetch new chunk
         last_chunk = if let Some(c) = &last_chunk.filter(|v| v.position == chunk_pos) {
@@ -80,9 +81,9 @@ pub fn do_raycast(
 
         // Check if block is solid
         if let Some(chunk_data) = last_chunk {
-            let block_id = chunk_data.world[local_pos.x][local_pos.y][local_pos.z];
-            if block_id != 0 {
-                let collided_block = blocks.get_block(block_id as usize);
+            let stored_block = chunk_data.get_block(local_pos);
+            if block_id(stored_block) != 0 {
+                let collided_block = blocks.get_block(stored_block as usize);
 
                 // Don't bother real aabb check if we know its a full block
                 if collided_block.full {
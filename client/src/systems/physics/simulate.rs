@@ -1,12 +1,23 @@
-use crate::systems::physics::PhysicsObject;
+use crate::game::world::clock::RenderClock;
+use crate::systems::physics::{PhysicsObject, PHYSICS_TICK_RATE};
 use bevy::prelude::*;
 use nalgebra::Vector3;
 
-pub fn physics_tick(mut query: Query<&mut PhysicsObject>, time: Res<Time>) {
+/// How much of an object's velocity it keeps from one physics tick to the next
+pub const VELOCITY_DAMPING: f32 = 0.92;
+
+pub fn physics_tick(mut query: Query<&mut PhysicsObject>, clock: Res<RenderClock>) {
+    // Freeze the world so every face of the panorama matches
+    if clock.paused {
+        return;
+    }
+
+    let delta = (1.0 / PHYSICS_TICK_RATE) as f32;
+
     for mut object in query.iter_mut() {
         object.previous_position = object.position;
-        object.position = object.position + (object.velocity * time.delta_seconds());
-        object.velocity *= 0.92;
+        object.position = object.position + (object.velocity * delta);
+        object.velocity *= VELOCITY_DAMPING;
 
         // Stop when going slow enough to save computation
         if object.position.norm() < 0.1 {
@@ -1,9 +1,93 @@
-use crate::systems::physics::PhysicsObject;
+use crate::systems::physics::{PhysicsObject, PHYSICS_TIMESTEP};
 use bevy::prelude::*;
+use bevy::time::FixedTimesteps;
+use nalgebra::Vector3;
+
+/// Where an object is drawn between two ticks, with an alpha of 0 at the previous tick and 1 at the latest
+pub fn interpolate_position(
+    previous: Vector3<f32>,
+    current: Vector3<f32>,
+    alpha: f32,
+) -> Vector3<f32> {
+    previous.lerp(&current, alpha.clamp(0.0, 1.0))
+}
+
+/// How far the frame is through the current physics tick
+pub fn tick_alpha(timesteps: &FixedTimesteps) -> f32 {
+    timesteps
+        .get(PHYSICS_TIMESTEP)
+        .map_or(1.0, |v| v.overstep_percentage() as f32)
+}
+
+/// Moves each transform to where its PhysicsObject is at this point between ticks.
+/// Only the drawn position is interpolated, the PhysicsObject keeps the position of the latest tick
+pub fn physics_sync(
+    mut query: Query<(&mut Transform, &PhysicsObject)>,
+    timesteps: Res<FixedTimesteps>,
+) {
+    let alpha = tick_alpha(&timesteps);
 
-/// Syncs all of the changed PhysicsObjects to the transform
-pub fn physics_sync(mut query: Query<(&mut Transform, &PhysicsObject), Changed<PhysicsObject>>) {
     for (mut transform, object) in query.iter_mut() {
-        transform.translation = Vec3::new(object.position.x, object.position.y, object.position.z);
+        let position = interpolate_position(object.previous_position, object.position, alpha);
+        let translation = Vec3::new(position.x, position.y, position.z);
+
+        // Leave still objects unchanged so they aren't picked up by change detection
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+
+    #[test]
+    fn positions_are_interpolated_between_ticks() {
+        let previous = Vector3::new(0.0, 10.0, -4.0);
+        let current = Vector3::new(2.0, 10.0, 4.0);
+
+        assert_eq!(interpolate_position(previous, current, 0.0), previous);
+        assert_eq!(
+            interpolate_position(previous, current, 0.25),
+            Vector3::new(0.5, 10.0, -2.0)
+        );
+        assert_eq!(interpolate_position(previous, current, 1.0), current);
+    }
+
+    #[test]
+    fn alpha_is_kept_between_the_ticks() {
+        let previous = Vector3::new(0.0, 10.0, -4.0);
+        let current = Vector3::new(2.0, 10.0, 4.0);
+
+        assert_eq!(interpolate_position(previous, current, -0.5), previous);
+        assert_eq!(interpolate_position(previous, current, 1.5), current);
+    }
+
+    #[test]
+    fn latest_tick_is_drawn_without_a_timestep() {
+        assert_eq!(tick_alpha(&FixedTimesteps::default()), 1.0);
+
+        let mut world = World::new();
+        world.init_resource::<FixedTimesteps>();
+        let mut object = PhysicsObject::new(Vector3::new(2.0, 10.0, 4.0), Vec::new());
+        object.previous_position = Vector3::new(0.0, 10.0, -4.0);
+        let entity = world.spawn((Transform::default(), object)).id();
+
+        SystemStage::single_threaded()
+            .with_system(physics_sync)
+            .run(&mut world);
+
+        // Only what's drawn moves, the physics keeps both ticks
+        let entity = world.entity(entity);
+        assert_eq!(
+            entity.get::<Transform>().unwrap().translation,
+            Vec3::new(2.0, 10.0, 4.0)
+        );
+        assert_eq!(
+            entity.get::<PhysicsObject>().unwrap().previous_position,
+            Vector3::new(0.0, 10.0, -4.0)
+        );
     }
 }
@@ -0,0 +1,246 @@
+use crate::state::AppState;
+use crate::systems::ui::focus::Focusable;
+use crate::systems::ui::theme::UiTheme;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
+use rc_networking::Client;
+
+pub struct ShutdownPlugin;
+
+impl Plugin for ShutdownPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ShutdownSequence::default())
+            .add_system(request_quit)
+            .add_system(quit_dialog_buttons)
+            .add_system(run_shutdown);
+    }
+}
+
+/// The steps taken, in order, to leave the game cleanly
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShutdownStep {
+    /// Tell the server we're leaving
+    Disconnect,
+    /// Wait a frame so the disconnect is sent before the socket closes
+    Flush,
+    /// Save the world if it's being hosted locally
+    Save,
+    Exit,
+}
+
+impl ShutdownStep {
+    pub const ORDER: [ShutdownStep; 4] = [
+        ShutdownStep::Disconnect,
+        ShutdownStep::Flush,
+        ShutdownStep::Save,
+        ShutdownStep::Exit,
+    ];
+}
+
+#[derive(Resource, Default)]
+pub struct ShutdownSequence {
+    /// The index of the next step in [`ShutdownStep::ORDER`], or None if not shutting down
+    next_step: Option<usize>,
+    /// The confirm dialog, if it's open
+    dialog: Option<Entity>,
+}
+
+impl ShutdownSequence {
+    pub fn start(&mut self) {
+        if self.next_step.is_none() {
+            self.next_step = Some(0);
+        }
+    }
+
+    /// Whether the game is shutting down
+    pub fn in_progress(&self) -> bool {
+        self.next_step.is_some()
+    }
+
+    /// Whether the confirm dialog is open
+    pub fn dialog_open(&self) -> bool {
+        self.dialog.is_some()
+    }
+
+    /// Takes the next step to run
+    pub fn advance(&mut self) -> Option<ShutdownStep> {
+        let index = self.next_step?;
+        let step = ShutdownStep::ORDER.get(index).copied();
+        self.next_step = Some(index + 1);
+        step
+    }
+}
+
+#[derive(Component)]
+pub enum QuitDialogButton {
+    Quit,
+    Cancel,
+}
+
+/// Closing the window asks before quitting when in game, as quitting leaves the server
+fn request_quit(
+    mut close_requests: EventReader<WindowCloseRequested>,
+    mut sequence: ResMut<ShutdownSequence>,
+    state: Res<State<AppState>>,
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    theme: Res<UiTheme>,
+) {
+    if close_requests.iter().count() == 0 {
+        return;
+    }
+
+    if *state.current() != AppState::InGame {
+        sequence.start();
+        return;
+    }
+
+    if sequence.dialog.is_some() {
+        return;
+    }
+
+    let button = |c: &mut ChildBuilder, text: &str, action: QuitDialogButton, order: u32| {
+        c.spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(150.0), Val::Px(65.0)),
+                    margin: UiRect::all(Val::Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: theme.button.into(),
+                ..default()
+            },
+            Focusable::new(order),
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font: assets.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 40.0,
+                    color: theme.text,
+                },
+            ));
+        });
+    };
+
+    let dialog = commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            background_color: theme.menu_background.into(),
+            ..default()
+        })
+        .with_children(|c| {
+            c.spawn(TextBundle::from_section(
+                "Quit game?",
+                TextStyle {
+                    font: assets.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 40.0,
+                    color: theme.text,
+                },
+            ));
+            button(c, "Quit", QuitDialogButton::Quit, 0);
+            button(c, "Cancel", QuitDialogButton::Cancel, 1);
+        })
+        .id();
+
+    sequence.dialog = Some(dialog);
+}
+
+fn quit_dialog_buttons(
+    buttons: Query<(&Interaction, &QuitDialogButton), Changed<Interaction>>,
+    mut sequence: ResMut<ShutdownSequence>,
+    mut commands: Commands,
+) {
+    for (interaction, button) in buttons.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        if let Some(dialog) = sequence.dialog.take() {
+            commands.entity(dialog).despawn_recursive();
+        }
+
+        if let QuitDialogButton::Quit = button {
+            sequence.start();
+        }
+    }
+}
+
+/// Runs one shutdown step a frame so each has finished before the next
+fn run_shutdown(
+    mut sequence: ResMut<ShutdownSequence>,
+    client: Option<ResMut<Client>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let step = match sequence.advance() {
+        Some(val) => val,
+        None => return,
+    };
+    info!("Shutdown: {:?}", step);
+
+    match step {
+        ShutdownStep::Disconnect => {
+            if let Some(mut client) = client {
+                client.disconnect();
+            }
+        }
+        // The disconnect is sent along with the rest of this frames packets
+        ShutdownStep::Flush => {}
+        // Worlds are only saved by the server at the moment, which saves on its own shutdown
+        ShutdownStep::Save => {}
+        ShutdownStep::Exit => exit.send(AppExit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_runs_until_started() {
+        let mut sequence = ShutdownSequence::default();
+
+        assert!(!sequence.in_progress());
+        assert_eq!(sequence.advance(), None);
+    }
+
+    #[test]
+    fn steps_run_in_order_once() {
+        let mut sequence = ShutdownSequence::default();
+        sequence.start();
+
+        let steps = std::iter::from_fn(|| sequence.advance()).collect::<Vec<ShutdownStep>>();
+        assert_eq!(
+            steps,
+            vec![
+                ShutdownStep::Disconnect,
+                ShutdownStep::Flush,
+                ShutdownStep::Save,
+                ShutdownStep::Exit,
+            ]
+        );
+        assert_eq!(sequence.advance(), None);
+    }
+
+    #[test]
+    fn starting_again_doesnt_restart() {
+        let mut sequence = ShutdownSequence::default();
+        sequence.start();
+        assert_eq!(sequence.advance(), Some(ShutdownStep::Disconnect));
+
+        // Closing the window again while shutting down carries on from where it was
+        sequence.start();
+        assert_eq!(sequence.advance(), Some(ShutdownStep::Flush));
+    }
+}
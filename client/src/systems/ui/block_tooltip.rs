@@ -0,0 +1,258 @@
+use crate::config::ClientConfig;
+use crate::game::blocks::states::BlockStates;
+use crate::game::blocks::Block;
+use crate::helpers::{from_bevy_vec3, global_to_local_position, within_reach};
+use crate::systems::chunk::ChunkSystem;
+use crate::systems::physics::raycasts::do_raycast;
+use crate::systems::ui::frame_graph::DebugScreen;
+use crate::systems::ui::theme::UiTheme;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::{block_id, block_state};
+
+#[derive(Component)]
+pub struct BlockTooltip;
+
+/// The block the tooltip was last written for, so the text is only rebuilt when the target changes
+#[derive(Default, PartialEq)]
+pub struct TooltipTarget(Option<(Vector3<i32>, u32, bool)>);
+
+/// The text shown for a block, with its id and state while the debug screen is open
+pub fn tooltip_text(block: &Block, stored: u32, debug: bool) -> String {
+    // Identifiers are namespaced, such as "mcv3::Dirt"
+    let name = block
+        .identifier
+        .rsplit("::")
+        .next()
+        .unwrap_or(&block.identifier);
+
+    if debug {
+        format!(
+            "{} (id {}, state {})",
+            name,
+            block_id(stored),
+            block_state(stored)
+        )
+    } else {
+        name.to_string()
+    }
+}
+
+pub fn setup_block_tooltip(mut commands: Commands, assets: Res<AssetServer>, theme: Res<UiTheme>) {
+    // Just below the crosshair
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: assets.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 18.0,
+                        color: theme.overlay_text,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::top(Val::Px(60.0)),
+                    ..default()
+                }),
+                BlockTooltip,
+            ));
+        });
+}
+
+/// Shows the name of the block the player is looking at, only while it's in reach
+pub fn update_block_tooltip(
+    camera: Query<&Transform, With<Camera>>,
+    chunks: Res<ChunkSystem>,
+    blocks: Res<BlockStates>,
+    config: Res<ClientConfig>,
+    debug: Res<DebugScreen>,
+    mut target: Local<TooltipTarget>,
+    mut tooltip: Query<(&mut Text, &mut Visibility), With<BlockTooltip>>,
+) {
+    let camera = match camera.get_single() {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+    let eye = from_bevy_vec3(camera.translation);
+    let look = from_bevy_vec3(camera.rotation * Vec3::new(0.0, 0.0, -1.0));
+
+    let targeted = if config.block_tooltip {
+        do_raycast(eye, look, config.max_reach, &chunks, &blocks)
+            .filter(|ray| within_reach(eye, ray.block, config.max_reach))
+            .and_then(|ray| {
+                let (chunk_loc, inner_loc) = global_to_local_position(ray.block, chunks.chunk_size);
+                chunks
+                    .chunks
+                    .get(&chunk_loc)
+                    .map(|chunk| (ray.block, chunk.get_block(inner_loc), debug.is_shown()))
+            })
+    } else {
+        None
+    };
+
+    let new_target = TooltipTarget(targeted);
+    if *target == new_target {
+        return;
+    }
+    *target = new_target;
+
+    for (mut text, mut visibility) in tooltip.iter_mut() {
+        match targeted {
+            Some((_, stored, debug)) => {
+                text.sections[0].value =
+                    tooltip_text(blocks.get_block(stored as usize), stored, debug);
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChunkEdgeFaces;
+    use crate::systems::chunk::data::{test_chunk, RawChunkData};
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::{pack_block, CHUNK_SIZE};
+
+    const DIRT: u32 = 1;
+    const STONE: u32 = 6;
+
+    /// A camera looking down -z at stone 5 blocks away, and dirt 5 blocks away from one block to the right
+    fn world() -> World {
+        let mut data = RawChunkData::new(CHUNK_SIZE);
+        data.set(8, 8, 10, STONE);
+        data.set(9, 8, 10, DIRT);
+
+        let mut chunks = ChunkSystem::new(ChunkEdgeFaces::Always, 1.0, None);
+        chunks
+            .chunks
+            .insert(Vector3::zeros(), test_chunk(Vector3::zeros(), data));
+
+        let mut world = World::new();
+        world.insert_resource(chunks);
+        world.insert_resource(BlockStates::shipped());
+        world.init_resource::<ClientConfig>();
+        world.init_resource::<DebugScreen>();
+        world.spawn((Transform::from_xyz(8.5, 8.5, 15.5), Camera::default()));
+        world.spawn((
+            Text::from_section("", TextStyle::default()),
+            Visibility::default(),
+            BlockTooltip,
+        ));
+        world
+    }
+
+    /// The tooltip's text, if it's shown
+    fn shown(world: &mut World) -> Option<String> {
+        let (text, visibility) = world
+            .query_filtered::<(&Text, &Visibility), With<BlockTooltip>>()
+            .single(world);
+        visibility
+            .is_visible
+            .then(|| text.sections[0].value.clone())
+    }
+
+    fn move_camera(world: &mut World, x: f32) {
+        world
+            .query_filtered::<&mut Transform, With<Camera>>()
+            .single_mut(world)
+            .translation
+            .x = x;
+    }
+
+    #[test]
+    fn names_drop_their_namespace() {
+        let blocks = BlockStates::shipped();
+        let stone = pack_block(STONE, 2);
+
+        assert_eq!(
+            tooltip_text(blocks.get_block(stone as usize), stone, false),
+            "Stone"
+        );
+        assert_eq!(
+            tooltip_text(blocks.get_block(stone as usize), stone, true),
+            "Stone (id 6, state 2)"
+        );
+    }
+
+    #[test]
+    fn tooltip_follows_the_target() {
+        let mut world = world();
+        let mut stage = SystemStage::single_threaded().with_system(update_block_tooltip);
+
+        stage.run(&mut world);
+        assert_eq!(shown(&mut world).as_deref(), Some("Stone"));
+
+        move_camera(&mut world, 9.5);
+        stage.run(&mut world);
+        assert_eq!(shown(&mut world).as_deref(), Some("Dirt"));
+
+        // The block changing under the crosshair updates it too
+        world
+            .resource_mut::<ChunkSystem>()
+            .chunks
+            .get_mut(&Vector3::zeros())
+            .unwrap()
+            .set_block(Vector3::new(9, 8, 10), STONE);
+        stage.run(&mut world);
+        assert_eq!(shown(&mut world).as_deref(), Some("Stone"));
+
+        // Looking past both blocks hides it
+        move_camera(&mut world, 12.5);
+        stage.run(&mut world);
+        assert_eq!(shown(&mut world), None);
+    }
+
+    #[test]
+    fn text_is_only_rebuilt_when_the_target_changes() {
+        let mut world = world();
+        let mut stage = SystemStage::single_threaded().with_system(update_block_tooltip);
+        stage.run(&mut world);
+
+        world
+            .query_filtered::<&mut Text, With<BlockTooltip>>()
+            .single_mut(&mut world)
+            .sections[0]
+            .value = "Unchanged".to_string();
+        stage.run(&mut world);
+
+        assert_eq!(shown(&mut world).as_deref(), Some("Unchanged"));
+    }
+
+    #[test]
+    fn blocks_out_of_reach_have_no_tooltip() {
+        let mut world = world();
+        world.resource_mut::<ClientConfig>().max_reach = 4.0;
+
+        SystemStage::single_threaded()
+            .with_system(update_block_tooltip)
+            .run(&mut world);
+
+        assert_eq!(shown(&mut world), None);
+    }
+
+    #[test]
+    fn tooltip_can_be_turned_off() {
+        let mut world = world();
+        world.resource_mut::<ClientConfig>().block_tooltip = false;
+
+        SystemStage::single_threaded()
+            .with_system(update_block_tooltip)
+            .run(&mut world);
+
+        assert_eq!(shown(&mut world), None);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::config::ClientConfig;
+use crate::state::AppState;
+use crate::systems::networking::disconnect::Disconnected;
+use crate::systems::networking::{connect, server_address, LastServer};
+use crate::systems::ui::focus::Focusable;
+use crate::systems::ui::theme::UiTheme;
+use bevy::prelude::*;
+
+#[derive(Resource)]
+pub struct DisconnectScreenData {
+    ui: Entity,
+    reason: Entity,
+}
+
+#[derive(Component, Clone, Copy)]
+pub enum DisconnectButton {
+    /// Connects to the same server again and carries on playing
+    Reconnect,
+    MainMenu,
+}
+
+pub fn setup_disconnect_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<UiTheme>,
+    disconnected: Option<Res<Disconnected>>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let mut reason = None;
+
+    let button = |c: &mut ChildBuilder, text: &str, action: DisconnectButton, order: u32| {
+        c.spawn((
+            ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(250.0), Val::Px(65.0)),
+                    margin: UiRect::all(Val::Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: theme.button.into(),
+                ..default()
+            },
+            Focusable::new(order),
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 40.0,
+                    color: theme.text,
+                },
+            ));
+        });
+    };
+
+    let ui = commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            background_color: theme.menu_background.into(),
+            ..default()
+        })
+        .with_children(|c| {
+            reason = Some(
+                c.spawn(
+                    TextBundle::from_section(
+                        disconnected.map_or(String::new(), |v| v.0.to_string()),
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 24.0,
+                            color: theme.text,
+                        },
+                    )
+                    .with_style(Style {
+                        margin: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    }),
+                )
+                .id(),
+            );
+            button(c, "Reconnect", DisconnectButton::Reconnect, 0);
+            button(c, "Main Menu", DisconnectButton::MainMenu, 1);
+        })
+        .id();
+
+    commands.insert_resource(DisconnectScreenData {
+        ui,
+        reason: reason.unwrap(),
+    });
+}
+
+pub fn destroy_disconnect_screen(mut commands: Commands, screen: Res<DisconnectScreenData>) {
+    commands.entity(screen.ui).despawn_recursive();
+    commands.remove_resource::<DisconnectScreenData>();
+    commands.remove_resource::<Disconnected>();
+}
+
+/// Shows why reconnecting failed in place of the original reason
+pub fn update_disconnect_reason(
+    disconnected: Option<Res<Disconnected>>,
+    screen: Option<Res<DisconnectScreenData>>,
+    mut texts: Query<&mut Text>,
+) {
+    let (disconnected, screen) = match (disconnected, screen) {
+        (Some(disconnected), Some(screen)) if disconnected.is_changed() => (disconnected, screen),
+        _ => return,
+    };
+
+    if let Ok(mut text) = texts.get_mut(screen.reason) {
+        text.sections[0].value = disconnected.0.to_string();
+    }
+}
+
+pub fn disconnect_buttons(
+    mut buttons: Query<
+        (&Interaction, &mut BackgroundColor, &DisconnectButton),
+        Changed<Interaction>,
+    >,
+    mut commands: Commands,
+    last_server: Option<Res<LastServer>>,
+    mut app_state: ResMut<State<AppState>>,
+    theme: Res<UiTheme>,
+    config: Res<ClientConfig>,
+) {
+    for (interaction, mut color, button) in &mut buttons {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = theme.button_pressed.into();
+
+                match button {
+                    DisconnectButton::Reconnect => {
+                        let server_addr = server_address(last_server.as_deref());
+                        match connect(
+                            &mut commands,
+                            server_addr,
+                            &config.compression,
+                            &config.player_name,
+                        ) {
+                            // The game underneath carries on while the connection is made
+                            Ok(()) => {
+                                let _ = app_state.pop();
+                            }
+                            Err(err) => {
+                                error!("Failed to reconnect to {}: {}", server_addr, err);
+                                commands.insert_resource(Disconnected(err));
+                            }
+                        }
+                    }
+                    DisconnectButton::MainMenu => {
+                        let _ = app_state.replace(AppState::MainMenu);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+                *color = theme.button_hovered.into();
+            }
+            Interaction::None => {
+                *color = theme.button.into();
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use crate::systems::ui::theme::UiTheme;
+use bevy::prelude::*;
+
+/// Marks a button that can be focused with the keyboard. Buttons are visited in order of `order`
+#[derive(Component)]
+pub struct Focusable {
+    pub order: u32,
+    pub disabled: bool,
+}
+
+impl Focusable {
+    pub fn new(order: u32) -> Focusable {
+        Focusable {
+            order,
+            disabled: false,
+        }
+    }
+}
+
+/// The button on the current menu that has keyboard focus
+#[derive(Resource, Default)]
+pub struct MenuFocus {
+    pub focused: Option<Entity>,
+}
+
+/// Finds the next enabled item after the current one, wrapping around at the ends.
+/// With nothing focused yet, the first enabled item going forwards or last going backwards is chosen
+pub fn next_focus(current: Option<usize>, enabled: &[bool], forwards: bool) -> Option<usize> {
+    let len = enabled.len();
+    if len == 0 {
+        return None;
+    }
+
+    let mut index = match current {
+        Some(val) => val,
+        None if forwards => len - 1,
+        None => 0,
+    };
+
+    for _ in 0..len {
+        index = if forwards {
+            (index + 1) % len
+        } else {
+            (index + len - 1) % len
+        };
+
+        if enabled[index] {
+            return Some(index);
+        }
+    }
+    None
+}
+
+pub fn menu_keyboard_navigation(
+    keys: Res<Input<KeyCode>>,
+    mut focus: ResMut<MenuFocus>,
+    mut buttons: Query<(Entity, &Focusable, &mut Interaction)>,
+) {
+    let forwards = keys.any_just_pressed([KeyCode::Down, KeyCode::Tab]);
+    let backwards = keys.just_pressed(KeyCode::Up);
+
+    if forwards || backwards {
+        let mut items = buttons
+            .iter()
+            .map(|(entity, focusable, _)| (focusable.order, entity, !focusable.disabled))
+            .collect::<Vec<(u32, Entity, bool)>>();
+        items.sort_by_key(|(order, _, _)| *order);
+
+        let enabled = items.iter().map(|v| v.2).collect::<Vec<bool>>();
+        let current = focus
+            .focused
+            .and_then(|focused| items.iter().position(|v| v.1 == focused));
+
+        focus.focused = next_focus(current, &enabled, forwards).map(|i| items[i].1);
+    }
+
+    // Activate the focused button as if it were clicked
+    if keys.any_just_pressed([KeyCode::Return, KeyCode::NumpadEnter]) {
+        if let Some(Ok((_, focusable, mut interaction))) = focus.focused.map(|v| buttons.get_mut(v))
+        {
+            if !focusable.disabled {
+                *interaction = Interaction::Clicked;
+            }
+        }
+    }
+}
+
+/// Highlights the focused button, unless the mouse is already hovering or clicking a button
+pub fn highlight_focus(
+    focus: Res<MenuFocus>,
+    theme: Res<UiTheme>,
+    mut buttons: Query<(Entity, &Interaction, &mut BackgroundColor), With<Focusable>>,
+) {
+    if !focus.is_changed() {
+        return;
+    }
+
+    for (entity, interaction, mut color) in buttons.iter_mut() {
+        if *interaction != Interaction::None {
+            continue;
+        }
+        *color = if Some(entity) == focus.focused {
+            theme.button_hovered.into()
+        } else {
+            theme.button.into()
+        };
+    }
+}
+
+/// Clears focus when leaving a menu, as its buttons are gone
+pub fn clear_focus(mut focus: ResMut<MenuFocus>) {
+    focus.focused = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focus_moves_through_items_in_order() {
+        let enabled = [true, true, true];
+
+        assert_eq!(next_focus(None, &enabled, true), Some(0));
+        assert_eq!(next_focus(Some(0), &enabled, true), Some(1));
+        assert_eq!(next_focus(Some(1), &enabled, true), Some(2));
+
+        assert_eq!(next_focus(None, &enabled, false), Some(2));
+        assert_eq!(next_focus(Some(2), &enabled, false), Some(1));
+    }
+
+    #[test]
+    fn focus_wraps_at_the_ends() {
+        let enabled = [true, true, true];
+
+        assert_eq!(next_focus(Some(2), &enabled, true), Some(0));
+        assert_eq!(next_focus(Some(0), &enabled, false), Some(2));
+    }
+
+    #[test]
+    fn disabled_items_are_skipped() {
+        let enabled = [false, true, false, true, false];
+
+        assert_eq!(next_focus(None, &enabled, true), Some(1));
+        assert_eq!(next_focus(Some(1), &enabled, true), Some(3));
+        assert_eq!(next_focus(Some(3), &enabled, true), Some(1));
+        assert_eq!(next_focus(Some(1), &enabled, false), Some(3));
+    }
+
+    #[test]
+    fn nothing_to_focus() {
+        assert_eq!(next_focus(None, &[], true), None);
+        assert_eq!(next_focus(None, &[false, false], true), None);
+        assert_eq!(next_focus(Some(0), &[false, false], false), None);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::config::{ClientConfig, ScreenCorner};
+use crate::systems::ui::frame_graph::FrameTimes;
+use crate::systems::ui::theme::UiTheme;
+use bevy::prelude::*;
+
+/// Key that shows and hides the fps counter, separately from the debug screen
+pub const FPS_OVERLAY_KEY: KeyCode = KeyCode::F2;
+
+/// How many of the latest frames the counter averages over, so it follows changes quickly without flickering
+const FPS_FRAMES: usize = 60;
+
+/// The gap, in pixels, between the counter and the edges of the window
+const CORNER_MARGIN: f32 = 10.0;
+
+#[derive(Component)]
+pub struct FpsOverlay;
+
+/// Where the top left of something the size of `size` goes to sit in a corner of the window, in pixels from the
+/// window's top left. Kept on screen even when the window is smaller than it
+pub fn corner_position(corner: ScreenCorner, window: Vec2, size: Vec2, margin: f32) -> Vec2 {
+    let right = (window.x - size.x - margin).max(0.0);
+    let bottom = (window.y - size.y - margin).max(0.0);
+    let left = margin.min(right);
+    let top = margin.min(bottom);
+
+    match corner {
+        ScreenCorner::TopLeft => Vec2::new(left, top),
+        ScreenCorner::TopRight => Vec2::new(right, top),
+        ScreenCorner::BottomLeft => Vec2::new(left, bottom),
+        ScreenCorner::BottomRight => Vec2::new(right, bottom),
+    }
+}
+
+pub fn setup_fps_overlay(mut commands: Commands, assets: Res<AssetServer>, theme: Res<UiTheme>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: assets.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 18.0,
+                color: theme.overlay_text,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        }),
+        FpsOverlay,
+    ));
+}
+
+pub fn toggle_fps_overlay(keys: Res<Input<KeyCode>>, mut config: ResMut<ClientConfig>) {
+    if keys.just_pressed(FPS_OVERLAY_KEY) {
+        config.fps_overlay = !config.fps_overlay;
+    }
+}
+
+/// Shows the fps in the configured corner, moving it along when the window is resized
+pub fn update_fps_overlay(
+    config: Res<ClientConfig>,
+    frames: Res<FrameTimes>,
+    windows: Res<Windows>,
+    mut overlay: Query<(&mut Text, &mut Style, &mut Visibility, &Node), With<FpsOverlay>>,
+) {
+    let window = match windows.get_primary() {
+        Some(val) => Vec2::new(val.width(), val.height()),
+        None => return,
+    };
+
+    for (mut text, mut style, mut visibility, node) in overlay.iter_mut() {
+        visibility.is_visible = config.fps_overlay;
+        if !config.fps_overlay {
+            continue;
+        }
+
+        text.sections[0].value = format!("{:.0} fps", frames.recent_fps(FPS_FRAMES));
+
+        let position = corner_position(
+            config.fps_overlay_corner,
+            window,
+            node.size(),
+            CORNER_MARGIN,
+        );
+        style.position = UiRect {
+            left: Val::Px(position.x),
+            top: Val::Px(position.y),
+            ..default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Vec2 = Vec2::new(1280.0, 720.0);
+    const TEXT: Vec2 = Vec2::new(60.0, 20.0);
+
+    #[test]
+    fn counter_sits_in_each_corner() {
+        let position = |corner| corner_position(corner, WINDOW, TEXT, CORNER_MARGIN);
+
+        assert_eq!(position(ScreenCorner::TopLeft), Vec2::new(10.0, 10.0));
+        assert_eq!(position(ScreenCorner::TopRight), Vec2::new(1210.0, 10.0));
+        assert_eq!(position(ScreenCorner::BottomLeft), Vec2::new(10.0, 690.0));
+        assert_eq!(
+            position(ScreenCorner::BottomRight),
+            Vec2::new(1210.0, 690.0)
+        );
+    }
+
+    #[test]
+    fn counter_follows_the_window_size() {
+        let small = Vec2::new(640.0, 480.0);
+
+        assert_eq!(
+            corner_position(ScreenCorner::BottomRight, small, TEXT, CORNER_MARGIN),
+            Vec2::new(570.0, 450.0)
+        );
+    }
+
+    #[test]
+    fn counter_stays_on_screen_in_tiny_windows() {
+        let tiny = Vec2::new(50.0, 25.0);
+
+        for corner in [
+            ScreenCorner::TopLeft,
+            ScreenCorner::TopRight,
+            ScreenCorner::BottomLeft,
+            ScreenCorner::BottomRight,
+        ] {
+            assert_eq!(
+                corner_position(corner, tiny, TEXT, CORNER_MARGIN),
+                Vec2::ZERO
+            );
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use crate::systems::ui::theme::{Indicator, UiTheme};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// Key that shows and hides the debug screen
+pub const DEBUG_SCREEN_KEY: KeyCode = KeyCode::F3;
+
+/// How many frame times are kept, enough for the 0.1% low to be more than a single frame
+const FRAME_HISTORY: usize = 1000;
+
+/// How many of the latest frames are drawn in the graph
+const GRAPH_FRAMES: usize = 120;
+
+/// Frames that take longer than this, in seconds, are highlighted as hitches
+const FRAME_BUDGET: f32 = 1.0 / 60.0;
+
+/// The graph is tall enough to show frames up to twice the budget, longer frames are cut off
+const GRAPH_HEIGHT: f32 = 64.0;
+const BAR_WIDTH: f32 = 2.0;
+
+const BAR_ALPHA: f32 = 0.8;
+const HITCH_ALPHA: f32 = 0.9;
+
+/// The average fps over the slowest fraction of frames, so 0.01 gives the 1% low.
+/// Averaging hides the single hitches this is meant to show, so a high average can still have a low 1% low
+pub fn low_percentile_fps(frame_times: &[f32], fraction: f32) -> f32 {
+    if frame_times.is_empty() {
+        return 0.0;
+    }
+
+    let mut sorted = frame_times.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+
+    // Always include at least the slowest frame
+    let count = ((sorted.len() as f32 * fraction).ceil() as usize).clamp(1, sorted.len());
+    let average = sorted[..count].iter().sum::<f32>() / count as f32;
+
+    if average > 0.0 {
+        1.0 / average
+    } else {
+        0.0
+    }
+}
+
+/// The time taken by each of the latest frames, oldest first
+#[derive(Resource, Default)]
+pub struct FrameTimes {
+    times: VecDeque<f32>,
+}
+
+impl FrameTimes {
+    pub fn push(&mut self, frame_time: f32) {
+        if self.times.len() == FRAME_HISTORY {
+            self.times.pop_front();
+        }
+        self.times.push_back(frame_time);
+    }
+
+    pub fn average_fps(&self) -> f32 {
+        let total = self.times.iter().sum::<f32>();
+        if total > 0.0 {
+            self.times.len() as f32 / total
+        } else {
+            0.0
+        }
+    }
+
+    /// The average fps over just the latest frames, to follow changes faster than the whole history does
+    pub fn recent_fps(&self, count: usize) -> f32 {
+        let recent = self.times.iter().rev().take(count);
+        let total = recent.clone().sum::<f32>();
+        if total > 0.0 {
+            recent.count() as f32 / total
+        } else {
+            0.0
+        }
+    }
+
+    pub fn low_fps(&self, fraction: f32) -> f32 {
+        let times = self.times.iter().copied().collect::<Vec<f32>>();
+        low_percentile_fps(&times, fraction)
+    }
+
+    /// The frame time `age` frames ago, where 0 is the latest frame
+    fn get(&self, age: usize) -> Option<f32> {
+        self.times
+            .len()
+            .checked_sub(age + 1)
+            .and_then(|i| self.times.get(i).copied())
+    }
+}
+
+/// The debug screen, while it's shown
+#[derive(Resource, Default)]
+pub struct DebugScreen {
+    ui: Option<Entity>,
+}
+
+impl DebugScreen {
+    pub fn is_shown(&self) -> bool {
+        self.ui.is_some()
+    }
+}
+
+/// A bar of the graph, holding how many frames ago the frame it shows was
+#[derive(Component)]
+pub struct FrameBar(usize);
+
+#[derive(Component)]
+pub struct FrameStatsText;
+
+pub fn record_frame_time(mut frames: ResMut<FrameTimes>, time: Res<Time>) {
+    frames.push(time.delta_seconds());
+}
+
+pub fn toggle_debug_screen(
+    keys: Res<Input<KeyCode>>,
+    mut screen: ResMut<DebugScreen>,
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    theme: Res<UiTheme>,
+) {
+    if !keys.just_pressed(DEBUG_SCREEN_KEY) {
+        return;
+    }
+
+    if let Some(ui) = screen.ui.take() {
+        commands.entity(ui).despawn_recursive();
+        return;
+    }
+
+    let ui = commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|c| {
+            c.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: assets.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 18.0,
+                        color: theme.overlay_text,
+                    },
+                ),
+                FrameStatsText,
+            ));
+
+            // The latest frame is drawn on the right
+            c.spawn(NodeBundle {
+                style: Style {
+                    size: Size::new(
+                        Val::Px(GRAPH_FRAMES as f32 * BAR_WIDTH),
+                        Val::Px(GRAPH_HEIGHT),
+                    ),
+                    align_items: AlignItems::FlexEnd,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+                ..default()
+            })
+            .with_children(|graph| {
+                for age in (0..GRAPH_FRAMES).rev() {
+                    graph.spawn((
+                        NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(BAR_WIDTH), Val::Px(0.0)),
+                                ..default()
+                            },
+                            background_color: theme
+                                .indicator(Indicator::Good)
+                                .with_a(BAR_ALPHA)
+                                .into(),
+                            ..default()
+                        },
+                        FrameBar(age),
+                    ));
+                }
+
+                // Marks the frame budget, so hitches stand out by height and not just color
+                if theme.colorblind_mode.is_enabled() {
+                    graph.spawn(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                bottom: Val::Px(GRAPH_HEIGHT / 2.0),
+                                left: Val::Px(0.0),
+                                ..default()
+                            },
+                            size: Size::new(Val::Percent(100.0), Val::Px(1.0)),
+                            ..default()
+                        },
+                        background_color: theme.overlay_text.into(),
+                        ..default()
+                    });
+                }
+            });
+        })
+        .id();
+
+    screen.ui = Some(ui);
+}
+
+pub fn update_frame_graph(
+    screen: Res<DebugScreen>,
+    frames: Res<FrameTimes>,
+    mut bars: Query<(&FrameBar, &mut Style, &mut BackgroundColor)>,
+    mut text: Query<&mut Text, With<FrameStatsText>>,
+    theme: Res<UiTheme>,
+) {
+    if screen.ui.is_none() {
+        return;
+    }
+
+    for (bar, mut style, mut color) in bars.iter_mut() {
+        let frame_time = frames.get(bar.0).unwrap_or(0.0);
+        let height = (frame_time / (FRAME_BUDGET * 2.0)).min(1.0) * GRAPH_HEIGHT;
+
+        style.size.height = Val::Px(height);
+        *color = if frame_time > FRAME_BUDGET {
+            theme.indicator(Indicator::Bad).with_a(HITCH_ALPHA).into()
+        } else {
+            theme.indicator(Indicator::Good).with_a(BAR_ALPHA).into()
+        };
+    }
+
+    for mut text in text.iter_mut() {
+        text.sections[0].value = format!(
+            "{:.0} fps  1% low {:.0}  0.1% low {:.0}",
+            frames.average_fps(),
+            frames.low_fps(0.01),
+            frames.low_fps(0.001)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A window of frames at 100 fps, with the given number of slower frames mixed in
+    fn window(slow: usize, slow_time: f32) -> Vec<f32> {
+        let mut times = vec![0.01; FRAME_HISTORY - slow];
+        for i in 0..slow {
+            times.insert(i * 90, slow_time);
+        }
+        times
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn lows_average_the_slowest_frames() {
+        let times = window(5, 0.05);
+
+        // The 1% low is the slowest 10 frames, half of them hitches
+        assert_close(low_percentile_fps(&times, 0.01), 1.0 / 0.03);
+        // The 0.1% low is just the slowest frame
+        assert_close(low_percentile_fps(&times, 0.001), 20.0);
+    }
+
+    #[test]
+    fn lows_match_the_average_without_hitches() {
+        let times = window(0, 0.0);
+
+        assert_close(low_percentile_fps(&times, 0.01), 100.0);
+        assert_close(low_percentile_fps(&times, 0.001), 100.0);
+    }
+
+    #[test]
+    fn small_windows_use_the_slowest_frame() {
+        let times = [0.01, 0.02, 0.04, 0.01];
+
+        assert_close(low_percentile_fps(&times, 0.01), 25.0);
+        assert_eq!(low_percentile_fps(&[], 0.01), 0.0);
+    }
+
+    #[test]
+    fn history_keeps_the_latest_frames() {
+        let mut frames = FrameTimes::default();
+        for time in window(10, 0.1) {
+            frames.push(time);
+        }
+        // Pushes out the oldest frame, the first hitch
+        frames.push(0.01);
+
+        assert_eq!(frames.times.len(), FRAME_HISTORY);
+        assert_eq!(frames.get(0), Some(0.01));
+        assert_close(frames.low_fps(0.01), 1.0 / 0.091);
+        assert_close(frames.recent_fps(10), 100.0);
+    }
+
+    #[test]
+    fn recent_fps_only_counts_the_latest_frames() {
+        let mut frames = FrameTimes::default();
+        assert_eq!(frames.recent_fps(60), 0.0);
+
+        for _ in 0..100 {
+            frames.push(0.01);
+        }
+        for _ in 0..4 {
+            frames.push(0.05);
+        }
+
+        assert_close(frames.recent_fps(4), 20.0);
+        // Half slow frames and half fast ones
+        assert_close(frames.recent_fps(8), 1.0 / 0.03);
+        // Asking for more frames than there are averages what there is
+        assert_close(frames.recent_fps(FRAME_HISTORY), 104.0 / 1.2);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::config::{ClientConfig, PresetSettings};
+use crate::systems::camera::graphics::{supported_msaa_samples, GRAPHICS_PRESET_KEY};
+use crate::systems::ui::theme::UiTheme;
+use bevy::prelude::*;
+
+/// How long, in seconds, the panel stays up after the graphics settings change
+const PANEL_SECONDS: f64 = 4.0;
+
+/// The gap, in pixels, between the panel and the top right of the window
+const PANEL_MARGIN: f32 = 10.0;
+
+#[derive(Component)]
+pub struct GraphicsPanel;
+
+/// The preset settings the panel last showed, and until when it's shown
+#[derive(Default)]
+pub struct GraphicsPanelState {
+    shown: Option<PresetSettings>,
+    hide_at: f64,
+}
+
+/// The preset in use, or custom once any of its settings have been changed, followed by each setting it covers
+pub fn graphics_panel_text(config: &ClientConfig) -> String {
+    let on_off = |on: bool| if on { "On" } else { "Off" };
+    let msaa = match supported_msaa_samples(config.msaa_samples) {
+        1 => String::from("Off"),
+        samples => format!("{}x", samples),
+    };
+
+    format!(
+        "Graphics: {:?} ({:?} to change)\nRender distance: {} chunks\nMSAA: {}\nBloom: {}\nFog: {} to {} blocks",
+        config.graphics_preset(),
+        GRAPHICS_PRESET_KEY,
+        config.render_distance,
+        msaa,
+        on_off(config.bloom),
+        config.fog_start,
+        config.fog_end,
+    )
+}
+
+pub fn setup_graphics_panel(mut commands: Commands, assets: Res<AssetServer>, theme: Res<UiTheme>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: assets.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 18.0,
+                color: theme.overlay_text,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                right: Val::Px(PANEL_MARGIN),
+                top: Val::Px(PANEL_MARGIN),
+                ..default()
+            },
+            ..default()
+        }),
+        GraphicsPanel,
+    ));
+}
+
+/// Shows the graphics settings for a few seconds whenever a preset is switched to or one of its settings changes
+pub fn update_graphics_panel(
+    config: Res<ClientConfig>,
+    time: Res<Time>,
+    mut state: Local<GraphicsPanelState>,
+    mut panel: Query<(&mut Text, &mut Visibility), With<GraphicsPanel>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let settings = config.preset_settings();
+
+    // The settings loaded at startup aren't a change
+    match state.shown {
+        None => state.shown = Some(settings),
+        Some(shown) if shown != settings => {
+            state.shown = Some(settings);
+            state.hide_at = now + PANEL_SECONDS;
+        }
+        _ => {}
+    }
+
+    for (mut text, mut visibility) in panel.iter_mut() {
+        visibility.is_visible = now < state.hide_at;
+        if visibility.is_visible {
+            text.sections[0].value = graphics_panel_text(&config);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GraphicsPreset;
+
+    #[test]
+    fn panel_lists_the_preset_and_its_settings() {
+        let mut config = ClientConfig::default();
+        config.apply_preset(GraphicsPreset::High);
+
+        assert_eq!(
+            graphics_panel_text(&config),
+            "Graphics: High (F10 to change)\nRender distance: 8 chunks\nMSAA: 4x\nBloom: On\nFog: 96 to 128 blocks"
+        );
+    }
+
+    #[test]
+    fn panel_shows_custom_settings() {
+        let mut config = ClientConfig::default();
+        config.apply_preset(GraphicsPreset::Low);
+        config.render_distance = 6;
+
+        let text = graphics_panel_text(&config);
+        assert!(text.starts_with("Graphics: Custom"));
+        assert!(text.contains("Render distance: 6 chunks\nMSAA: Off\nBloom: Off"));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::state::AppState;
+use crate::systems::ui::theme::UiTheme;
 use bevy::prelude::*;
 
 pub fn set_loading(mut app_state: ResMut<State<AppState>>) {
@@ -9,10 +10,17 @@ pub fn set_loading(mut app_state: ResMut<State<AppState>>) {
 pub struct LoadingData {
     pub texture_atlas: bool,
     pub block_states: bool,
+    /// How many of the chunks around spawn have loaded and been meshed, out of how many are preloaded when joining
+    pub preloaded_chunks: usize,
+    pub preload_chunks: usize,
     pub ui: Option<Entity>,
 }
 
-pub fn setup_loading_ui(mut commands: Commands, mut data: ResMut<LoadingData>) {
+pub fn setup_loading_ui(
+    mut commands: Commands,
+    mut data: ResMut<LoadingData>,
+    theme: Res<UiTheme>,
+) {
     let ui = commands
         .spawn(NodeBundle {
             style: Style {
@@ -22,7 +30,7 @@ pub fn setup_loading_ui(mut commands: Commands, mut data: ResMut<LoadingData>) {
                 position_type: PositionType::Absolute,
                 ..default()
             },
-            background_color: Color::rgb(0.361, 0.42, 0.753).into(),
+            background_color: theme.loading_background.into(),
             ..default()
         })
         .id();
@@ -30,11 +38,11 @@ pub fn setup_loading_ui(mut commands: Commands, mut data: ResMut<LoadingData>) {
     data.ui = Some(ui);
 }
 
-pub fn remove_loading_ui(mut commands: Commands, data: ResMut<LoadingData>) {
-    if let Some(ui) = data.ui {
+/// The loading data is kept after the loading screen, as preloading the world when joining reports to it
+pub fn remove_loading_ui(mut commands: Commands, mut data: ResMut<LoadingData>) {
+    if let Some(ui) = data.ui.take() {
         commands.entity(ui).despawn();
     }
-    commands.remove_resource::<LoadingData>();
 }
 
 pub fn check_loading(data: Res<LoadingData>, mut app_state: ResMut<State<AppState>>) {
@@ -1,4 +1,7 @@
 use crate::state::AppState;
+use crate::systems::networking::ConnectionFailure;
+use crate::systems::ui::focus::Focusable;
+use crate::systems::ui::theme::UiTheme;
 use bevy::prelude::*;
 
 #[derive(Resource)]
@@ -6,7 +9,12 @@ pub struct MainMenuData {
     ui: Entity,
 }
 
-pub fn setup_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+pub fn setup_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    theme: Res<UiTheme>,
+    failure: Option<Res<ConnectionFailure>>,
+) {
     let entity = commands
         .spawn(NodeBundle {
             style: Style {
@@ -16,28 +24,49 @@ pub fn setup_main_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
                 position_type: PositionType::Absolute,
                 ..default()
             },
-            background_color: Color::rgba(0.1, 0.1, 0.1, 0.00).into(),
+            background_color: theme.menu_background.into(),
             ..default()
         })
         .with_children(|c| {
-            c.spawn(ButtonBundle {
-                style: Style {
-                    size: Size::new(Val::Px(150.0), Val::Px(65.0)),
-                    margin: UiRect::all(Val::Auto),
-                    justify_content: JustifyContent::Center,
-                    align_items: AlignItems::Center,
+            // Explain why the last attempt to connect failed
+            if let Some(failure) = &failure {
+                c.spawn(
+                    TextBundle::from_section(
+                        failure.0.to_string(),
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 24.0,
+                            color: theme.text,
+                        },
+                    )
+                    .with_style(Style {
+                        margin: UiRect::all(Val::Auto),
+                        ..default()
+                    }),
+                );
+            }
+
+            c.spawn((
+                ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(150.0), Val::Px(65.0)),
+                        margin: UiRect::all(Val::Auto),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: theme.button.into(),
                     ..default()
                 },
-                background_color: NORMAL_BUTTON.into(),
-                ..default()
-            })
+                Focusable::new(0),
+            ))
             .with_children(|parent| {
                 parent.spawn(TextBundle::from_section(
                     "Connect",
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                         font_size: 40.0,
-                        color: Color::rgb(0.9, 0.9, 0.9),
+                        color: theme.text,
                     },
                 ));
             });
@@ -52,29 +81,19 @@ pub fn destroy_main_menu(mut commands: Commands, menu: Res<MainMenuData>) {
     commands.remove_resource::<MainMenuData>();
 }
 
-const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
-const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
-const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
-
 pub fn button_system(
     mut interaction_query: Query<
         (&Interaction, &mut BackgroundColor),
         (Changed<Interaction>, With<Button>),
     >,
     mut app_state: ResMut<State<AppState>>,
+    theme: Res<UiTheme>,
 ) {
     for (interaction, mut color) in &mut interaction_query {
-        match *interaction {
-            Interaction::Clicked => {
-                *color = PRESSED_BUTTON.into();
-                app_state.set(AppState::Connecting).unwrap();
-            }
-            Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
-            }
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
-            }
+        *color = theme.button_color(*interaction).into();
+
+        if *interaction == Interaction::Clicked {
+            app_state.set(AppState::Connecting).unwrap();
         }
     }
 }
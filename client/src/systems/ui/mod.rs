@@ -1,22 +1,98 @@
+pub mod block_tooltip;
+pub mod disconnect;
+pub mod focus;
+pub mod fps_overlay;
+pub mod frame_graph;
+pub mod graphics_panel;
 pub mod loading;
 pub mod main_menu;
+pub mod theme;
 
 use crate::state::AppState;
+use crate::systems::ui::block_tooltip::{setup_block_tooltip, update_block_tooltip};
+use crate::systems::ui::disconnect::{
+    destroy_disconnect_screen, disconnect_buttons, setup_disconnect_screen,
+    update_disconnect_reason,
+};
+use crate::systems::ui::focus::{
+    clear_focus, highlight_focus, menu_keyboard_navigation, MenuFocus,
+};
+use crate::systems::ui::fps_overlay::{setup_fps_overlay, toggle_fps_overlay, update_fps_overlay};
+use crate::systems::ui::frame_graph::{
+    record_frame_time, toggle_debug_screen, update_frame_graph, DebugScreen, FrameTimes,
+};
+use crate::systems::ui::graphics_panel::{setup_graphics_panel, update_graphics_panel};
 use crate::systems::ui::loading::{
     check_loading, remove_loading_ui, set_loading, setup_loading_ui, LoadingData,
 };
 use crate::systems::ui::main_menu::{button_system, destroy_main_menu, setup_main_menu};
+use crate::systems::ui::theme::load_theme;
 use bevy::prelude::*;
 
 pub struct UIPlugin;
 
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(setup_ui))
+        app.insert_resource(load_theme())
+            .insert_resource(MenuFocus::default())
+            // Debug screen
+            .insert_resource(FrameTimes::default())
+            .insert_resource(DebugScreen::default())
+            .add_system(record_frame_time)
+            .add_system(toggle_debug_screen)
+            .add_system(
+                update_frame_graph
+                    .after(record_frame_time)
+                    .after(toggle_debug_screen),
+            )
+            // Fps counter
+            .add_startup_system(setup_fps_overlay)
+            .add_system(toggle_fps_overlay)
+            .add_system(
+                update_fps_overlay
+                    .after(record_frame_time)
+                    .after(toggle_fps_overlay),
+            )
+            // Graphics settings, shown when they change
+            .add_startup_system(setup_graphics_panel)
+            .add_system(update_graphics_panel)
+            .add_system_set(
+                SystemSet::on_enter(AppState::InGame)
+                    .with_system(setup_ui)
+                    .with_system(setup_block_tooltip),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::InGame).with_system(update_block_tooltip),
+            )
             // Main menu
             .add_system_set(SystemSet::on_enter(AppState::MainMenu).with_system(setup_main_menu))
-            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(button_system))
-            .add_system_set(SystemSet::on_exit(AppState::MainMenu).with_system(destroy_main_menu))
+            .add_system_set(
+                SystemSet::on_update(AppState::MainMenu)
+                    .with_system(button_system)
+                    .with_system(menu_keyboard_navigation.before(button_system))
+                    .with_system(highlight_focus.after(menu_keyboard_navigation)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu)
+                    .with_system(destroy_main_menu)
+                    .with_system(clear_focus),
+            )
+            // Disconnect screen
+            .add_system_set(
+                SystemSet::on_enter(AppState::Disconnected).with_system(setup_disconnect_screen),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Disconnected)
+                    .with_system(disconnect_buttons)
+                    .with_system(update_disconnect_reason)
+                    .with_system(menu_keyboard_navigation.before(disconnect_buttons))
+                    .with_system(highlight_focus.after(menu_keyboard_navigation)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Disconnected)
+                    .with_system(destroy_disconnect_screen)
+                    .with_system(clear_focus),
+            )
             // Loading
             .add_startup_system(setup_loading_ui)
             .insert_resource(LoadingData::default())
@@ -0,0 +1,276 @@
+use bevy::prelude::{Color, Interaction, Resource};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+const THEME_PATH: &str = "ui_theme.json";
+
+/// The colors used across the UI, so menus can be reskinned without editing code
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Resource)]
+#[serde(default)]
+pub struct UiTheme {
+    #[serde(with = "rgba")]
+    pub loading_background: Color,
+    #[serde(with = "rgba")]
+    pub menu_background: Color,
+    #[serde(with = "rgba")]
+    pub button: Color,
+    #[serde(with = "rgba")]
+    pub button_hovered: Color,
+    #[serde(with = "rgba")]
+    pub button_pressed: Color,
+    #[serde(with = "rgba")]
+    pub text: Color,
+    #[serde(with = "rgba")]
+    pub overlay_text: Color,
+    /// The colors of status indicators, such as frame times in the debug screen and chunk outlines
+    #[serde(with = "rgba")]
+    pub indicator_good: Color,
+    #[serde(with = "rgba")]
+    pub indicator_neutral: Color,
+    #[serde(with = "rgba")]
+    pub indicator_bad: Color,
+    /// Swaps the indicator colors for ones that can be told apart with a color vision deficiency,
+    /// and marks indicators with shapes where they'd otherwise only differ by color
+    pub colorblind_mode: ColorblindMode,
+}
+
+/// What a status indicator is showing, which picks its color
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Indicator {
+    Good,
+    Neutral,
+    Bad,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ColorblindMode {
+    #[default]
+    Off,
+    /// Red-blind
+    Protanopia,
+    /// Green-blind
+    Deuteranopia,
+    /// Blue-blind
+    Tritanopia,
+}
+
+impl ColorblindMode {
+    pub fn is_enabled(&self) -> bool {
+        *self != ColorblindMode::Off
+    }
+
+    /// The color an indicator is replaced with, None when the theme's own colors are used.
+    /// Red and green are swapped for orange and blue, or for red and teal when blue and yellow are hard to tell apart
+    pub fn indicator(&self, indicator: Indicator) -> Option<Color> {
+        let color = match (self, indicator) {
+            (ColorblindMode::Off, _) => return None,
+            (_, Indicator::Neutral) => Color::rgb(0.6, 0.6, 0.6),
+            (ColorblindMode::Protanopia | ColorblindMode::Deuteranopia, Indicator::Good) => {
+                Color::rgb(0.0, 0.45, 0.7)
+            }
+            (ColorblindMode::Protanopia | ColorblindMode::Deuteranopia, Indicator::Bad) => {
+                Color::rgb(0.9, 0.6, 0.0)
+            }
+            (ColorblindMode::Tritanopia, Indicator::Good) => Color::rgb(0.0, 0.6, 0.6),
+            (ColorblindMode::Tritanopia, Indicator::Bad) => Color::rgb(0.85, 0.2, 0.45),
+        };
+        Some(color)
+    }
+}
+
+impl UiTheme {
+    /// The color to draw an indicator in, taking the colorblind mode into account
+    pub fn indicator(&self, indicator: Indicator) -> Color {
+        self.colorblind_mode
+            .indicator(indicator)
+            .unwrap_or(match indicator {
+                Indicator::Good => self.indicator_good,
+                Indicator::Neutral => self.indicator_neutral,
+                Indicator::Bad => self.indicator_bad,
+            })
+    }
+}
+
+impl Default for UiTheme {
+    fn default() -> Self {
+        UiTheme {
+            loading_background: Color::rgb(0.361, 0.42, 0.753),
+            menu_background: Color::rgba(0.1, 0.1, 0.1, 0.0),
+            button: Color::rgb(0.15, 0.15, 0.15),
+            button_hovered: Color::rgb(0.25, 0.25, 0.25),
+            button_pressed: Color::rgb(0.35, 0.75, 0.35),
+            text: Color::rgb(0.9, 0.9, 0.9),
+            overlay_text: Color::WHITE,
+            indicator_good: Color::rgb(0.35, 0.8, 0.35),
+            indicator_neutral: Color::rgb(0.5, 0.5, 0.5),
+            indicator_bad: Color::rgb(0.9, 0.3, 0.2),
+            colorblind_mode: ColorblindMode::Off,
+        }
+    }
+}
+
+impl UiTheme {
+    /// The background of a button as it's interacted with
+    pub fn button_color(&self, interaction: Interaction) -> Color {
+        match interaction {
+            Interaction::Clicked => self.button_pressed,
+            Interaction::Hovered => self.button_hovered,
+            Interaction::None => self.button,
+        }
+    }
+}
+
+pub fn load_theme() -> UiTheme {
+    if !Path::new(THEME_PATH).exists() {
+        if let Ok(file) = File::create(THEME_PATH) {
+            let mut writer = BufWriter::new(file);
+            let _ = serde_json::to_writer_pretty(&mut writer, &UiTheme::default());
+        }
+    }
+
+    let theme = if let Ok(file) = File::open(THEME_PATH) {
+        let reader = BufReader::new(file);
+
+        serde_json::from_reader(reader).ok()
+    } else {
+        None
+    };
+
+    theme.unwrap_or(UiTheme::default())
+}
+
+/// Stores colors as [r, g, b, a] so they're easy to edit by hand
+mod rgba {
+    use bevy::prelude::Color;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        color.as_rgba_f32().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let [r, g, b, a] = <[f32; 4]>::deserialize(deserializer)?;
+        Ok(Color::rgba(r, g, b, a))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loaded_theme_overrides_the_defaults() {
+        let theme: UiTheme = serde_json::from_str(
+            r#"{ "button": [1.0, 0.0, 0.0, 1.0], "text": [0.0, 0.0, 0.0, 1.0] }"#,
+        )
+        .unwrap();
+        let default = UiTheme::default();
+
+        assert_eq!(
+            theme.button_color(Interaction::None),
+            Color::rgba(1.0, 0.0, 0.0, 1.0)
+        );
+        assert_eq!(theme.text, Color::rgba(0.0, 0.0, 0.0, 1.0));
+
+        // Colors left out of the file keep their defaults
+        assert_eq!(
+            theme.button_color(Interaction::Hovered),
+            default.button_color(Interaction::Hovered)
+        );
+        assert_eq!(theme.loading_background, default.loading_background);
+    }
+
+    #[test]
+    fn theme_saves_and_loads_the_same() {
+        let theme = UiTheme {
+            button_pressed: Color::rgba(0.2, 0.4, 0.6, 0.8),
+            ..UiTheme::default()
+        };
+
+        let json = serde_json::to_string(&theme).unwrap();
+        assert_eq!(serde_json::from_str::<UiTheme>(&json).unwrap(), theme);
+    }
+
+    const INDICATORS: [Indicator; 3] = [Indicator::Good, Indicator::Neutral, Indicator::Bad];
+
+    fn theme(colorblind_mode: ColorblindMode) -> UiTheme {
+        UiTheme {
+            colorblind_mode,
+            ..UiTheme::default()
+        }
+    }
+
+    #[test]
+    fn indicators_use_the_theme_colors_by_default() {
+        let theme = theme(ColorblindMode::Off);
+
+        assert!(!theme.colorblind_mode.is_enabled());
+        assert_eq!(theme.indicator(Indicator::Good), theme.indicator_good);
+        assert_eq!(theme.indicator(Indicator::Neutral), theme.indicator_neutral);
+        assert_eq!(theme.indicator(Indicator::Bad), theme.indicator_bad);
+    }
+
+    #[test]
+    fn colorblind_modes_remap_indicators() {
+        let red_green = [ColorblindMode::Protanopia, ColorblindMode::Deuteranopia];
+        for mode in red_green {
+            let theme = theme(mode);
+            assert_eq!(theme.indicator(Indicator::Good), Color::rgb(0.0, 0.45, 0.7));
+            assert_eq!(
+                theme.indicator(Indicator::Neutral),
+                Color::rgb(0.6, 0.6, 0.6)
+            );
+            assert_eq!(theme.indicator(Indicator::Bad), Color::rgb(0.9, 0.6, 0.0));
+        }
+
+        let theme = theme(ColorblindMode::Tritanopia);
+        assert_eq!(theme.indicator(Indicator::Good), Color::rgb(0.0, 0.6, 0.6));
+        assert_eq!(
+            theme.indicator(Indicator::Neutral),
+            Color::rgb(0.6, 0.6, 0.6)
+        );
+        assert_eq!(theme.indicator(Indicator::Bad), Color::rgb(0.85, 0.2, 0.45));
+    }
+
+    #[test]
+    fn colorblind_modes_override_custom_theme_colors() {
+        let theme = UiTheme {
+            indicator_good: Color::rgb(0.1, 0.2, 0.3),
+            colorblind_mode: ColorblindMode::Tritanopia,
+            ..UiTheme::default()
+        };
+
+        assert_eq!(theme.indicator(Indicator::Good), Color::rgb(0.0, 0.6, 0.6));
+    }
+
+    #[test]
+    fn every_mode_keeps_indicators_distinct() {
+        let modes = [
+            ColorblindMode::Off,
+            ColorblindMode::Protanopia,
+            ColorblindMode::Deuteranopia,
+            ColorblindMode::Tritanopia,
+        ];
+        for mode in modes {
+            let theme = theme(mode);
+            for (i, a) in INDICATORS.iter().enumerate() {
+                for b in &INDICATORS[i + 1..] {
+                    assert_ne!(theme.indicator(*a), theme.indicator(*b), "{:?}", mode);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn colorblind_mode_is_saved_with_the_theme() {
+        let json = serde_json::to_string(&theme(ColorblindMode::Deuteranopia)).unwrap();
+        let loaded: UiTheme = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.colorblind_mode, ColorblindMode::Deuteranopia);
+
+        // Themes saved before colorblind modes load with it off
+        let loaded: UiTheme = serde_json::from_str("{}").unwrap();
+        assert_eq!(loaded.colorblind_mode, ColorblindMode::Off);
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{NetworkingError, MAX_MESSAGE_SIZE};
+use bevy::prelude::Resource;
+use renet::NETCODE_USER_DATA_BYTES;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Packets smaller than this, in bytes, are sent as they are as compressing them saves next to nothing
+pub const COMPRESSION_THRESHOLD: usize = 512;
+
+/// The bytes at the start of the user data a client connects with that hold the compression it supports. The rest are
+/// left for the player's name
+pub const COMPRESSION_USER_DATA_BYTES: usize = 32;
+
+/// How far a compressed packet can expand, so a small packet can't claim an enormous size
+const MAX_DECOMPRESSED_SIZE: usize = MAX_MESSAGE_SIZE * 16;
+
+/// How hard large packets are compressed, agreed on per connection when connecting
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Compression {
+    /// Sent as they are, for slow machines or fast local networks
+    #[default]
+    None,
+    /// Cheap to compress, for most connections
+    Fast,
+    /// The smallest packets at the most CPU, for slow connections
+    Max,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 3] = [Compression::None, Compression::Fast, Compression::Max];
+
+    /// The zstd level packets are compressed at. Max stops short of zstd's ultra levels, which need far more
+    /// memory on both ends for little gain on packets this size
+    fn level(&self) -> Option<i32> {
+        match self {
+            Compression::None => None,
+            Compression::Fast => Some(1),
+            Compression::Max => Some(19),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Fast => 1,
+            Compression::Max => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Compression> {
+        Compression::ALL.into_iter().find(|v| v.to_byte() == byte)
+    }
+}
+
+/// The first option the client prefers that the server also supports, or no compression if there isn't one
+pub fn negotiate_compression(client: &[Compression], server: &[Compression]) -> Compression {
+    client
+        .iter()
+        .find(|v| server.contains(v))
+        .copied()
+        .unwrap_or(Compression::None)
+}
+
+/// Writes the compression a client supports, most preferred first, into the user data it connects with
+pub fn compression_user_data(preferences: &[Compression]) -> [u8; NETCODE_USER_DATA_BYTES] {
+    let mut data = [0; NETCODE_USER_DATA_BYTES];
+    let count = preferences.len().min(COMPRESSION_USER_DATA_BYTES - 1);
+
+    data[0] = count as u8;
+    for (i, compression) in preferences[..count].iter().enumerate() {
+        data[i + 1] = compression.to_byte();
+    }
+    data
+}
+
+/// Reads the compression a client supports back out of its user data, skipping anything unrecognised
+pub fn read_compression_user_data(data: &[u8; NETCODE_USER_DATA_BYTES]) -> Vec<Compression> {
+    let count = (data[0] as usize).min(COMPRESSION_USER_DATA_BYTES - 1);
+    data[1..=count]
+        .iter()
+        .filter_map(|v| Compression::from_byte(*v))
+        .collect()
+}
+
+/// The compression the server supports, and what was agreed with each connected client
+#[derive(Resource, Debug)]
+pub struct ConnectionCompression {
+    supported: Vec<Compression>,
+    connections: HashMap<u64, Compression>,
+}
+
+impl ConnectionCompression {
+    pub fn new(supported: Vec<Compression>) -> ConnectionCompression {
+        ConnectionCompression {
+            supported,
+            connections: HashMap::new(),
+        }
+    }
+
+    /// Agrees on the compression for a client that's connected, returning what was chosen
+    pub fn connect(
+        &mut self,
+        client_id: u64,
+        user_data: &[u8; NETCODE_USER_DATA_BYTES],
+    ) -> Compression {
+        let compression =
+            negotiate_compression(&read_compression_user_data(user_data), &self.supported);
+        self.connections.insert(client_id, compression);
+        compression
+    }
+
+    pub fn disconnect(&mut self, client_id: u64) {
+        self.connections.remove(&client_id);
+    }
+
+    pub fn get(&self, client_id: u64) -> Compression {
+        self.connections
+            .get(&client_id)
+            .copied()
+            .unwrap_or(Compression::None)
+    }
+}
+
+impl Default for ConnectionCompression {
+    fn default() -> Self {
+        ConnectionCompression::new(Compression::ALL.to_vec())
+    }
+}
+
+/// Puts a packet in a frame, compressing it if it's large enough. The first byte says how the rest is stored
+pub fn compress_packet(
+    bytes: Vec<u8>,
+    compression: Compression,
+) -> Result<Vec<u8>, NetworkingError> {
+    let level = match compression.level() {
+        Some(level) if bytes.len() >= COMPRESSION_THRESHOLD => level,
+        _ => {
+            let mut framed = Vec::with_capacity(bytes.len() + 1);
+            framed.push(Compression::None.to_byte());
+            framed.extend_from_slice(&bytes);
+            return Ok(framed);
+        }
+    };
+
+    let mut encoder = zstd::Encoder::new(vec![compression.to_byte()], level)?;
+    encoder.write_all(&bytes)?;
+    Ok(encoder.finish()?)
+}
+
+/// Takes a packet back out of its frame, decompressing it if needed
+pub fn decompress_packet(framed: &[u8]) -> Result<Vec<u8>, NetworkingError> {
+    let (flag, body) = framed
+        .split_first()
+        .ok_or(NetworkingError::UnknownCompression)?;
+
+    match Compression::from_byte(*flag) {
+        Some(Compression::None) => Ok(body.to_vec()),
+        Some(_) => {
+            let mut bytes = Vec::new();
+            zstd::Decoder::new(body)?
+                .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+                .read_to_end(&mut bytes)?;
+
+            if bytes.len() > MAX_DECOMPRESSED_SIZE {
+                return Err(NetworkingError::PacketTooLarge {
+                    size: bytes.len(),
+                    max: MAX_DECOMPRESSED_SIZE,
+                });
+            }
+            Ok(bytes)
+        }
+        None => Err(NetworkingError::UnknownCompression),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiation_picks_the_clients_favourite_the_server_supports() {
+        use Compression::*;
+
+        assert_eq!(
+            negotiate_compression(&[Max, Fast, None], &[Fast, None]),
+            Fast
+        );
+        assert_eq!(negotiate_compression(&[Fast, Max], &[Max, Fast]), Fast);
+        assert_eq!(negotiate_compression(&[Max], &Compression::ALL), Max);
+    }
+
+    #[test]
+    fn negotiation_falls_back_to_none() {
+        use Compression::*;
+
+        assert_eq!(negotiate_compression(&[Max], &[Fast]), None);
+        assert_eq!(negotiate_compression(&[], &Compression::ALL), None);
+        assert_eq!(negotiate_compression(&[Fast, Max], &[]), None);
+    }
+
+    #[test]
+    fn preferences_survive_the_user_data() {
+        let preferences = vec![Compression::Max, Compression::None, Compression::Fast];
+        let data = compression_user_data(&preferences);
+
+        assert_eq!(read_compression_user_data(&data), preferences);
+        assert!(read_compression_user_data(&[0; NETCODE_USER_DATA_BYTES]).is_empty());
+    }
+
+    #[test]
+    fn unknown_preferences_are_skipped() {
+        let mut data = compression_user_data(&[Compression::Fast, Compression::Max]);
+        data[1] = 200;
+        assert_eq!(read_compression_user_data(&data), vec![Compression::Max]);
+
+        // A count past the end of the compression bytes is cut short rather than read into the player's name
+        let mut data = [0; NETCODE_USER_DATA_BYTES];
+        data[0] = u8::MAX;
+        assert_eq!(
+            read_compression_user_data(&data).len(),
+            COMPRESSION_USER_DATA_BYTES - 1
+        );
+    }
+
+    #[test]
+    fn each_connection_keeps_what_it_agreed() {
+        let mut compression = ConnectionCompression::new(vec![Compression::Fast]);
+
+        let fast = compression.connect(1, &compression_user_data(&[Compression::Fast]));
+        let max = compression.connect(2, &compression_user_data(&[Compression::Max]));
+        assert_eq!(fast, Compression::Fast);
+        assert_eq!(max, Compression::None);
+
+        assert_eq!(compression.get(1), Compression::Fast);
+        assert_eq!(compression.get(2), Compression::None);
+
+        compression.disconnect(1);
+        assert_eq!(compression.get(1), Compression::None);
+    }
+
+    #[test]
+    fn large_packets_are_compressed_and_read_back() {
+        let bytes = vec![7; COMPRESSION_THRESHOLD * 4];
+
+        for compression in Compression::ALL {
+            let framed = compress_packet(bytes.clone(), compression).unwrap();
+            assert_eq!(framed[0], compression.to_byte());
+            assert_eq!(decompress_packet(&framed).unwrap(), bytes);
+
+            if compression != Compression::None {
+                assert!(framed.len() < bytes.len());
+            }
+        }
+    }
+
+    #[test]
+    fn compressed_packets_are_zstd_frames() {
+        let bytes = vec![7; COMPRESSION_THRESHOLD * 4];
+        let fast = compress_packet(bytes.clone(), Compression::Fast).unwrap();
+        let max = compress_packet(bytes.clone(), Compression::Max).unwrap();
+
+        // Both can be read by any zstd decoder once the flag byte is taken off
+        assert_eq!(zstd::decode_all(&fast[1..]).unwrap(), bytes);
+        assert_eq!(zstd::decode_all(&max[1..]).unwrap(), bytes);
+        assert!(max.len() <= fast.len());
+    }
+
+    #[test]
+    fn small_packets_are_sent_as_they_are() {
+        let bytes = vec![7; COMPRESSION_THRESHOLD - 1];
+        let framed = compress_packet(bytes.clone(), Compression::Max).unwrap();
+
+        assert_eq!(framed[0], Compression::None.to_byte());
+        assert_eq!(&framed[1..], &bytes[..]);
+        assert_eq!(decompress_packet(&framed).unwrap(), bytes);
+    }
+
+    #[test]
+    fn unknown_frames_are_refused() {
+        assert!(matches!(
+            decompress_packet(&[]),
+            Err(NetworkingError::UnknownCompression)
+        ));
+        assert!(matches!(
+            decompress_packet(&[9, 1, 2, 3]),
+            Err(NetworkingError::UnknownCompression)
+        ));
+    }
+
+    #[test]
+    fn packets_cant_expand_past_the_limit() {
+        let framed = compress_packet(vec![0; MAX_DECOMPRESSED_SIZE + 1], Compression::Max).unwrap();
+
+        assert!(matches!(
+            decompress_packet(&framed),
+            Err(NetworkingError::PacketTooLarge { .. })
+        ));
+    }
+}
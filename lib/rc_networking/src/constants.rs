@@ -1,12 +1,267 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
-use serde::{Serialize, Deserialize};
 
 pub const CHUNK_SIZE: usize = 16;
 
-pub type RawChunkData = [[[u32; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+/// The widest chunks a client accepts from a server. Lighting and meshing a chunk take buffers that grow with the
+/// cube of its size, so a server can't have clients allocate without bound
+pub const MAX_CHUNK_SIZE: usize = 64;
+
+/// The default number of chunk sections stacked on top of each other to make up a column of the world, starting from section 0
+pub const DEFAULT_COLUMN_SECTIONS: i32 = 16;
+
+/// Whether a chunk section is part of a world column that's `column_sections` high
+#[inline]
+pub fn in_world_column(section: i32, column_sections: i32) -> bool {
+    (0..column_sections).contains(&section)
+}
+
+/// The lowest corner and size of a player's collision box, relative to the position sent for them. The client
+/// collides with it and the server keeps placed blocks out of it, so both take it from here
+pub const PLAYER_COLLIDER_OFFSET: [f32; 3] = [0.0, 1.0, 0.0];
+pub const PLAYER_COLLIDER_SIZE: [f32; 3] = [1.0, 1.0, 1.0];
+
+/// The blocks of a chunk, stored flat in x, y, z order. The chunk's size is kept with its blocks rather than
+/// fixed at CHUNK_SIZE, so a server can use chunks of any size up to MAX_CHUNK_SIZE and tell clients when they join
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct RawChunkData {
+    size: usize,
+    blocks: Vec<u32>,
+}
+
+impl RawChunkData {
+    /// A chunk of air, with `size` blocks along each side
+    pub fn new(size: usize) -> RawChunkData {
+        RawChunkData {
+            size,
+            blocks: vec![0; size * size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether there are as many blocks as the size says, which isn't the case for chunks from a broken peer.
+    /// A size too large to count the blocks of is never valid
+    pub fn is_valid(&self) -> bool {
+        self.size
+            .checked_mul(self.size)
+            .and_then(|v| v.checked_mul(self.size))
+            == Some(self.blocks.len())
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        debug_assert!(x < self.size && y < self.size && z < self.size);
+        (x * self.size + y) * self.size + z
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.blocks[self.index(x, y, z)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: u32) {
+        let index = self.index(x, y, z);
+        self.blocks[index] = block;
+    }
+
+    /// Every block in the chunk, in x, y, z order
+    pub fn blocks(&self) -> &[u32] {
+        &self.blocks
+    }
+}
+
+impl Default for RawChunkData {
+    fn default() -> Self {
+        RawChunkData::new(CHUNK_SIZE)
+    }
+}
+
+impl From<&[[[u32; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]> for RawChunkData {
+    fn from(world: &[[[u32; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]) -> Self {
+        RawChunkData {
+            size: CHUNK_SIZE,
+            blocks: world.iter().flatten().flatten().copied().collect(),
+        }
+    }
+}
+
+/// How many of the low bits of a stored block are used for the block id. The remaining high bits hold the block's state
+pub const BLOCK_ID_BITS: u32 = 24;
+pub const BLOCK_ID_MASK: u32 = (1 << BLOCK_ID_BITS) - 1;
+
+/// Packs a block id and its state (orientation, variant etc) into the single value stored in chunk data.
+/// A state of 0 leaves the id untouched, so plain ids are still valid blocks.
+#[inline]
+pub fn pack_block(id: u32, state: u8) -> u32 {
+    (id & BLOCK_ID_MASK) | ((state as u32) << BLOCK_ID_BITS)
+}
+
+/// Extracts the block id from a stored block
+#[inline]
+pub fn block_id(block: u32) -> u32 {
+    block & BLOCK_ID_MASK
+}
+
+/// Extracts the block state from a stored block
+#[inline]
+pub fn block_state(block: u32) -> u8 {
+    (block >> BLOCK_ID_BITS) as u8
+}
 
 #[derive(fmt::Debug, Hash, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
 pub struct UserId(pub u64);
 
 #[derive(fmt::Debug, Hash, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
-pub struct EntityId(pub u64);
\ No newline at end of file
+pub struct EntityId(pub u64);
+
+/// How a player interacts with the world
+#[derive(fmt::Debug, Hash, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum GameMode {
+    Survival,
+    Creative,
+    Spectator,
+}
+
+impl GameMode {
+    /// Whether the player can move up and down freely
+    pub fn can_fly(&self) -> bool {
+        *self != GameMode::Survival
+    }
+
+    /// Whether blocks break as soon as they're hit
+    pub fn instant_break(&self) -> bool {
+        *self == GameMode::Creative
+    }
+
+    /// Whether the player is stopped by blocks, spectators pass straight through them
+    pub fn collides(&self) -> bool {
+        *self != GameMode::Spectator
+    }
+
+    /// Whether the player can place and break blocks
+    pub fn can_edit_blocks(&self) -> bool {
+        *self != GameMode::Spectator
+    }
+
+    /// Whether blocks the player breaks drop items
+    pub fn gets_drops(&self) -> bool {
+        *self == GameMode::Survival
+    }
+}
+
+/// The kind of world a player is in, which sets how its sky and fog look
+#[derive(fmt::Debug, Hash, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Dimension {
+    Overworld,
+    Nether,
+}
+
+impl std::str::FromStr for GameMode {
+    type Err = ();
+
+    /// Parses a game mode from its name, as typed into commands
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "survival" => Ok(GameMode::Survival),
+            "creative" => Ok(GameMode::Creative),
+            "spectator" => Ok(GameMode::Spectator),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_are_stored_in_x_y_z_order() {
+        let mut chunk = RawChunkData::new(4);
+        chunk.set(1, 2, 3, 7);
+
+        assert_eq!(chunk.get(1, 2, 3), 7);
+        // (1 * 4 + 2) * 4 + 3
+        assert_eq!(chunk.blocks()[27], 7);
+        assert_eq!(chunk.blocks().iter().filter(|v| **v != 0).count(), 1);
+    }
+
+    #[test]
+    fn chunk_from_array_matches_indexing() {
+        let mut world = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        world[3][9][14] = 5;
+
+        let chunk = RawChunkData::from(&world);
+        assert_eq!(chunk.size(), CHUNK_SIZE);
+        assert!(chunk.is_valid());
+        assert_eq!(chunk.get(3, 9, 14), 5);
+    }
+
+    #[test]
+    fn chunk_with_wrong_block_count_is_invalid() {
+        let chunk = RawChunkData {
+            size: 4,
+            blocks: vec![0; 63],
+        };
+        assert!(!chunk.is_valid());
+    }
+
+    #[test]
+    fn chunk_with_overflowing_size_is_invalid() {
+        // The cube of this wraps round to 0 in 64 bits, matching the empty buffer if it weren't checked
+        let chunk = RawChunkData {
+            size: 1 << 22,
+            blocks: Vec::new(),
+        };
+        assert!(!chunk.is_valid());
+
+        let chunk = RawChunkData {
+            size: usize::MAX,
+            blocks: vec![0; 1],
+        };
+        assert!(!chunk.is_valid());
+    }
+
+    #[test]
+    fn packed_blocks_keep_id_and_state() {
+        let block = pack_block(6, 3);
+        assert_eq!(block_id(block), 6);
+        assert_eq!(block_state(block), 3);
+        assert_eq!(pack_block(6, 0), 6);
+    }
+
+    #[test]
+    fn game_modes_imply_their_capabilities() {
+        // (fly, instant break, collide, edit blocks, drops)
+        let capabilities = |mode: GameMode| {
+            (
+                mode.can_fly(),
+                mode.instant_break(),
+                mode.collides(),
+                mode.can_edit_blocks(),
+                mode.gets_drops(),
+            )
+        };
+
+        assert_eq!(
+            capabilities(GameMode::Survival),
+            (false, false, true, true, true)
+        );
+        assert_eq!(
+            capabilities(GameMode::Creative),
+            (true, true, true, true, false)
+        );
+        assert_eq!(
+            capabilities(GameMode::Spectator),
+            (true, false, false, false, false)
+        );
+    }
+
+    #[test]
+    fn game_modes_parse_from_their_names() {
+        assert_eq!("survival".parse(), Ok(GameMode::Survival));
+        assert_eq!("Creative".parse(), Ok(GameMode::Creative));
+        assert_eq!("SPECTATOR".parse(), Ok(GameMode::Spectator));
+        assert_eq!("adventure".parse::<GameMode>(), Err(()));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::protocol::Protocol;
+use crate::types::ReceivePacket;
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// Which packet a `Protocol` is, without its contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketKind {
+    PlayerMove,
+    EntityMoved,
+    PlayerRotate,
+    EntityRotated,
+    DespawnEntity,
+    BlockUpdate,
+    ChatSent,
+    PartialChunkUpdate,
+    SpawnEntity,
+    RequestChunk,
+    InventoryUpdate,
+    JoinWorld,
+    TimeSync,
+    KeepAlive,
+    SetGameMode,
+    SpawnPoint,
+    Teleport,
+    BlockEdit,
+    BlockEditAck,
+    PlaySound,
+    Disconnect,
+    FillRegion,
+    BatchBlockUpdate,
+    BlockBreakProgress,
+}
+
+impl Protocol {
+    pub fn kind(&self) -> PacketKind {
+        match self {
+            Protocol::PlayerMove(_) => PacketKind::PlayerMove,
+            Protocol::EntityMoved(_) => PacketKind::EntityMoved,
+            Protocol::PlayerRotate(_) => PacketKind::PlayerRotate,
+            Protocol::EntityRotated(_) => PacketKind::EntityRotated,
+            Protocol::DespawnEntity(_) => PacketKind::DespawnEntity,
+            Protocol::BlockUpdate(_) => PacketKind::BlockUpdate,
+            Protocol::ChatSent(_) => PacketKind::ChatSent,
+            Protocol::PartialChunkUpdate(_) => PacketKind::PartialChunkUpdate,
+            Protocol::SpawnEntity(_) => PacketKind::SpawnEntity,
+            Protocol::RequestChunk(_) => PacketKind::RequestChunk,
+            Protocol::InventoryUpdate(_) => PacketKind::InventoryUpdate,
+            Protocol::JoinWorld(_) => PacketKind::JoinWorld,
+            Protocol::TimeSync(_) => PacketKind::TimeSync,
+            Protocol::KeepAlive(_) => PacketKind::KeepAlive,
+            Protocol::SetGameMode(_) => PacketKind::SetGameMode,
+            Protocol::SpawnPoint(_) => PacketKind::SpawnPoint,
+            Protocol::Teleport(_) => PacketKind::Teleport,
+            Protocol::BlockEdit(_) => PacketKind::BlockEdit,
+            Protocol::BlockEditAck(_) => PacketKind::BlockEditAck,
+            Protocol::PlaySound(_) => PacketKind::PlaySound,
+            Protocol::Disconnect(_) => PacketKind::Disconnect,
+            Protocol::FillRegion(_) => PacketKind::FillRegion,
+            Protocol::BatchBlockUpdate(_) => PacketKind::BatchBlockUpdate,
+            Protocol::BlockBreakProgress(_) => PacketKind::BlockBreakProgress,
+        }
+    }
+}
+
+/// The packets something has registered to handle. A received packet nothing handles is logged the first time
+/// it arrives, so a new packet that hasn't been hooked up is noticed rather than silently dropped
+#[derive(Resource, Default)]
+pub struct PacketHandlers {
+    handled: HashSet<PacketKind>,
+    /// Unhandled packets that have already been logged
+    reported: HashSet<PacketKind>,
+}
+
+impl PacketHandlers {
+    pub fn register(&mut self, kinds: &[PacketKind]) {
+        self.handled.extend(kinds);
+    }
+
+    pub fn is_handled(&self, kind: PacketKind) -> bool {
+        self.handled.contains(&kind)
+    }
+
+    /// Checks a received packet has a handler, returning true the first time one without a handler arrives
+    pub fn report_unhandled(&mut self, kind: PacketKind) -> bool {
+        !self.is_handled(kind) && self.reported.insert(kind)
+    }
+}
+
+pub trait PacketHandlerAppExt {
+    /// Registers the packets the systems being added handle
+    fn handle_packets(&mut self, kinds: &[PacketKind]) -> &mut Self;
+}
+
+impl PacketHandlerAppExt for App {
+    fn handle_packets(&mut self, kinds: &[PacketKind]) -> &mut Self {
+        self.init_resource::<PacketHandlers>();
+        self.world.resource_mut::<PacketHandlers>().register(kinds);
+        self
+    }
+}
+
+pub fn report_unhandled_packets(
+    mut packets: EventReader<ReceivePacket>,
+    mut handlers: ResMut<PacketHandlers>,
+) {
+    for packet in packets.iter() {
+        if handlers.report_unhandled(packet.0.kind()) {
+            warn!(
+                "Received {:?} from {:?} but nothing handles it",
+                packet.0.kind(),
+                packet.1
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::UserId;
+    use crate::protocol::clientbound::block_edit_ack::BlockEditAck;
+    use crate::protocol::clientbound::chat::ChatSent;
+    use crate::protocol::serverbound::player_move::PlayerMove;
+    use bevy::ecs::event::Events;
+
+    fn chat() -> Protocol {
+        Protocol::ChatSent(ChatSent {
+            message: "hello".to_string(),
+        })
+    }
+
+    #[test]
+    fn packets_know_their_kind() {
+        assert_eq!(chat().kind(), PacketKind::ChatSent);
+        assert_eq!(
+            Protocol::PlayerMove(PlayerMove::new(1.0, 2.0, 3.0)).kind(),
+            PacketKind::PlayerMove
+        );
+        assert_eq!(
+            Protocol::BlockEditAck(BlockEditAck::new(0, true, 1)).kind(),
+            PacketKind::BlockEditAck
+        );
+    }
+
+    #[test]
+    fn registered_packets_are_handled() {
+        let mut app = App::new();
+        app.handle_packets(&[PacketKind::ChatSent])
+            .handle_packets(&[PacketKind::PlayerMove, PacketKind::BlockEdit]);
+
+        let handlers = app.world.resource::<PacketHandlers>();
+        assert!(handlers.is_handled(PacketKind::ChatSent));
+        assert!(handlers.is_handled(PacketKind::PlayerMove));
+        assert!(handlers.is_handled(PacketKind::BlockEdit));
+        assert!(!handlers.is_handled(PacketKind::Teleport));
+    }
+
+    #[test]
+    fn unhandled_packets_are_reported_once() {
+        let mut handlers = PacketHandlers::default();
+        handlers.register(&[PacketKind::ChatSent]);
+
+        assert!(!handlers.report_unhandled(PacketKind::ChatSent));
+        assert!(handlers.report_unhandled(PacketKind::Teleport));
+        assert!(!handlers.report_unhandled(PacketKind::Teleport));
+        assert!(handlers.report_unhandled(PacketKind::PlaySound));
+    }
+
+    #[test]
+    fn received_packets_without_a_handler_are_reported() {
+        let mut app = App::new();
+        app.add_event::<ReceivePacket>()
+            .handle_packets(&[PacketKind::ChatSent])
+            .add_system(report_unhandled_packets);
+
+        for packet in [
+            chat(),
+            Protocol::PlayerMove(PlayerMove::new(1.0, 2.0, 3.0)),
+            Protocol::PlayerMove(PlayerMove::new(4.0, 5.0, 6.0)),
+        ] {
+            app.world
+                .resource_mut::<Events<ReceivePacket>>()
+                .send(ReceivePacket(packet, UserId(1)));
+        }
+        app.update();
+
+        let handlers = app.world.resource::<PacketHandlers>();
+        assert_eq!(handlers.reported, HashSet::from([PacketKind::PlayerMove]));
+    }
+}
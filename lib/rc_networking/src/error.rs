@@ -0,0 +1,142 @@
+use std::io;
+use thiserror::Error;
+
+/// Everything that can go wrong with a connection, worded so it can be shown to the player as is
+#[derive(Error, Debug)]
+pub enum NetworkingError {
+    #[error("The server refused the connection. Check the address and that the server is running")]
+    ConnectionRefused,
+    #[error("The connection timed out. The server may be down or unreachable")]
+    Timeout,
+    #[error("The server is on a different version (protocol {theirs}, this game uses {ours}). Update whichever is older")]
+    VersionMismatch { ours: u32, theirs: u32 },
+    #[error("A packet of {size} bytes was larger than the {max} bytes allowed")]
+    PacketTooLarge { size: usize, max: usize },
+    #[error("A network error occurred: {0}")]
+    Io(io::Error),
+    #[error("Received a packet that couldn't be read: {0}")]
+    Serialization(bincode::Error),
+    #[error("Received a packet compressed in a way this game can't read")]
+    UnknownCompression,
+    #[error("The server uses chunks {size} blocks wide, this game supports chunks 1 to {max} blocks wide")]
+    UnsupportedChunkSize { size: usize, max: usize },
+    #[error("Disconnected from the server: {reason}")]
+    Disconnected { reason: String },
+}
+
+impl From<io::Error> for NetworkingError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::ConnectionRefused => NetworkingError::ConnectionRefused,
+            io::ErrorKind::TimedOut => NetworkingError::Timeout,
+            _ => NetworkingError::Io(err),
+        }
+    }
+}
+
+impl From<bincode::Error> for NetworkingError {
+    fn from(err: bincode::Error) -> Self {
+        match *err {
+            // Reading or writing failed rather than the data being wrong
+            bincode::ErrorKind::Io(err) => NetworkingError::from(err),
+            _ => NetworkingError::Serialization(err),
+        }
+    }
+}
+
+impl From<renet::RenetError> for NetworkingError {
+    fn from(err: renet::RenetError) -> Self {
+        match err {
+            renet::RenetError::IO(err) => NetworkingError::from(err),
+            other => NetworkingError::Disconnected {
+                reason: other.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_can_be_shown_to_players() {
+        assert_eq!(
+            NetworkingError::VersionMismatch { ours: 16, theirs: 15 }.to_string(),
+            "The server is on a different version (protocol 15, this game uses 16). Update whichever is older"
+        );
+        assert_eq!(
+            NetworkingError::PacketTooLarge {
+                size: 300,
+                max: 256
+            }
+            .to_string(),
+            "A packet of 300 bytes was larger than the 256 bytes allowed"
+        );
+        assert_eq!(
+            NetworkingError::UnsupportedChunkSize { size: 128, max: 64 }.to_string(),
+            "The server uses chunks 128 blocks wide, this game supports chunks 1 to 64 blocks wide"
+        );
+        assert_eq!(
+            NetworkingError::Disconnected {
+                reason: String::from("Server closed")
+            }
+            .to_string(),
+            "Disconnected from the server: Server closed"
+        );
+        assert_eq!(
+            NetworkingError::from(io::Error::new(io::ErrorKind::Other, "broken pipe")).to_string(),
+            "A network error occurred: broken pipe"
+        );
+
+        // Usable wherever a standard error is expected
+        let err: Box<dyn std::error::Error> = Box::new(NetworkingError::Timeout);
+        assert_eq!(
+            err.to_string(),
+            "The connection timed out. The server may be down or unreachable"
+        );
+    }
+
+    #[test]
+    fn io_errors_map_to_their_causes() {
+        let from_kind = |kind| NetworkingError::from(io::Error::from(kind));
+
+        assert!(matches!(
+            from_kind(io::ErrorKind::ConnectionRefused),
+            NetworkingError::ConnectionRefused
+        ));
+        assert!(matches!(
+            from_kind(io::ErrorKind::TimedOut),
+            NetworkingError::Timeout
+        ));
+        assert!(matches!(
+            from_kind(io::ErrorKind::AddrInUse),
+            NetworkingError::Io(_)
+        ));
+        assert!(matches!(
+            NetworkingError::from(renet::RenetError::IO(io::Error::from(
+                io::ErrorKind::TimedOut
+            ))),
+            NetworkingError::Timeout
+        ));
+    }
+
+    #[test]
+    fn bincode_errors_map_to_their_causes() {
+        // A bool can only be 0 or 1, so the data itself is wrong
+        let invalid = bincode::deserialize::<bool>(&[2]).unwrap_err();
+        assert!(matches!(
+            NetworkingError::from(invalid),
+            NetworkingError::Serialization(_)
+        ));
+
+        // Failing to read is an IO error, whatever bincode was doing at the time
+        let io = Box::new(bincode::ErrorKind::Io(io::Error::from(
+            io::ErrorKind::ConnectionRefused,
+        )));
+        assert!(matches!(
+            NetworkingError::from(io),
+            NetworkingError::ConnectionRefused
+        ));
+    }
+}
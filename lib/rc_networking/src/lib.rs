@@ -1,27 +1,168 @@
+pub mod compression;
 pub mod constants;
+pub mod dispatch;
+pub mod error;
+pub mod player_name;
 pub mod protocol;
 pub mod types;
 
 use bevy::ecs::schedule::ShouldRun;
 use bevy::prelude::{Res, Resource};
+use compression::{compress_packet, decompress_packet, Compression};
+use constants::UserId;
 use protocol::Protocol;
-use renet::{BlockChannelConfig, ChannelConfig, ConnectToken, RenetConnectionConfig};
+use renet::{
+    BlockChannelConfig, ChannelConfig, ConnectToken, RenetConnectionConfig, NETCODE_USER_DATA_BYTES,
+};
 use std::net::SocketAddr;
 use std::time::{Duration, SystemTime};
+use types::SendPacket;
 
 pub use client::*;
+pub use error::NetworkingError;
 pub use renet;
 pub use server::*;
 
 pub const PROTOCOL_ID: u64 = 4302467916224429941;
 
+/// Changed whenever a packet is added or changes shape, so anything stored from an older protocol can be recognised
+pub const PROTOCOL_VERSION: u32 = 16;
+
 // current private key is SHA256 hash of format!("{}{}", PROTOCOL_ID, "RustCraft");
 pub const PRIVATE_KEY: [u8; 32] = [
     0x2e, 0x7c, 0x89, 0x9c, 0xf6, 0x46, 0x8d, 0x19, 0x4b, 0x38, 0x14, 0xfd, 0xea, 0xa8, 0x7a, 0xce,
     0xf2, 0xc7, 0x2d, 0x99, 0x2b, 0x1b, 0xe2, 0x5d, 0x29, 0x2d, 0xd3, 0x26, 0x52, 0x71, 0x8a, 0x1b,
 ];
 
+/// The sizes of the buffers kept for each connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionBuffers {
+    /// How many sent packets are remembered for acks, 256 by default
+    pub sent_packets: usize,
+    /// How many received packets are remembered for acks, 256 by default
+    pub received_packets: usize,
+    /// How many messages can be waiting to be sent on the block channel, 1024 by default
+    pub send_queue: usize,
+}
+
+impl ConnectionBuffers {
+    /// The smallest buffers that still leave room for a full chunk to be sent
+    pub const MINIMUM: ConnectionBuffers = ConnectionBuffers {
+        sent_packets: 64,
+        received_packets: 64,
+        send_queue: 256,
+    };
+
+    /// Raises any buffer below its minimum up to it
+    pub fn validated(self) -> ConnectionBuffers {
+        ConnectionBuffers {
+            sent_packets: self.sent_packets.max(Self::MINIMUM.sent_packets),
+            received_packets: self.received_packets.max(Self::MINIMUM.received_packets),
+            send_queue: self.send_queue.max(Self::MINIMUM.send_queue),
+        }
+    }
+}
+
+impl Default for ConnectionBuffers {
+    fn default() -> Self {
+        ConnectionBuffers {
+            sent_packets: 256,
+            received_packets: 256,
+            send_queue: 1024,
+        }
+    }
+}
+
+/// How often the server checks a client is still there, and how long it waits for an answer before dropping them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepAliveSettings {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepAliveError {
+    /// Clients would be dropped before they were ever asked to answer
+    TimeoutNotAfterInterval,
+    ZeroInterval,
+}
+
+impl std::fmt::Display for KeepAliveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeepAliveError::TimeoutNotAfterInterval => {
+                write!(f, "the keep-alive timeout must be longer than the interval")
+            }
+            KeepAliveError::ZeroInterval => write!(f, "the keep-alive interval can't be zero"),
+        }
+    }
+}
+
+impl KeepAliveSettings {
+    pub fn validate(&self) -> Result<(), KeepAliveError> {
+        if self.interval.is_zero() {
+            return Err(KeepAliveError::ZeroInterval);
+        }
+        if self.timeout <= self.interval {
+            return Err(KeepAliveError::TimeoutNotAfterInterval);
+        }
+        Ok(())
+    }
+}
+
+impl Default for KeepAliveSettings {
+    fn default() -> Self {
+        KeepAliveSettings {
+            interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(15),
+        }
+    }
+}
+
+/// The largest packet that can be sent, large enough for a full chunk
+pub const MAX_MESSAGE_SIZE: usize = 256 * 1024;
+
+/// Serializes and compresses a packet, refusing packets too big to send even once compressed
+fn serialize_packet(
+    protocol: &Protocol,
+    compression: Compression,
+) -> Result<Vec<u8>, NetworkingError> {
+    let bytes = compress_packet(bincode::serialize(protocol)?, compression)?;
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(NetworkingError::PacketTooLarge {
+            size: bytes.len(),
+            max: MAX_MESSAGE_SIZE,
+        });
+    }
+    Ok(bytes)
+}
+
+/// Serializes the packets waiting to be sent, logging and skipping any that can't be sent so one bad
+/// packet doesn't hold up the rest of the queue
+fn serialize_outgoing<'a>(
+    packets: impl Iterator<Item = &'a SendPacket>,
+    compression: impl Fn(UserId) -> Compression,
+) -> impl Iterator<Item = (&'a SendPacket, Vec<u8>)> {
+    packets.filter_map(move |v| match serialize_packet(&v.0, compression(v.1)) {
+        Ok(ser) => Some((v, ser)),
+        Err(err) => {
+            bevy::log::error!("Skipping {:?} packet to {:?}: {}", v.0.kind(), v.1, err);
+            None
+        }
+    })
+}
+
+fn deserialize_packet(bytes: &[u8]) -> Result<Protocol, NetworkingError> {
+    Ok(bincode::deserialize(&decompress_packet(bytes)?)?)
+}
+
 pub fn get_renet_connection_config() -> RenetConnectionConfig {
+    connection_config(ConnectionBuffers::default())
+}
+
+pub fn connection_config(buffers: ConnectionBuffers) -> RenetConnectionConfig {
+    let buffers = buffers.validated();
+
     let channels_config = vec![
         ChannelConfig::Reliable(Default::default()),
         ChannelConfig::Unreliable(Default::default()),
@@ -31,15 +172,15 @@ pub fn get_renet_connection_config() -> RenetConnectionConfig {
             resend_time: Duration::from_millis(300),
             sent_packet_buffer_size: 256,
             packet_budget: 8 * 1024,
-            max_message_size: 256 * 1024,
-            message_send_queue_size: 1024,
+            max_message_size: MAX_MESSAGE_SIZE,
+            message_send_queue_size: buffers.send_queue,
         }),
     ];
 
     let config = RenetConnectionConfig {
         max_packet_size: 16 * 1024,
-        sent_packets_buffer_size: 256,
-        received_packets_buffer_size: 256,
+        sent_packets_buffer_size: buffers.sent_packets,
+        received_packets_buffer_size: buffers.received_packets,
         reassembly_buffer_size: 256,
         rtt_smoothing_factor: 0.005,
         packet_loss_smoothing_factor: 0.1,
@@ -52,7 +193,12 @@ pub fn get_renet_connection_config() -> RenetConnectionConfig {
     config
 }
 
-pub fn get_simple_connect_token(client_id: u64, addresses: Vec<SocketAddr>) -> ConnectToken {
+/// A token to connect with, carrying user data such as the compression the client supports to the server
+pub fn get_simple_connect_token(
+    client_id: u64,
+    addresses: Vec<SocketAddr>,
+    user_data: &[u8; NETCODE_USER_DATA_BYTES],
+) -> ConnectToken {
     let current_time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
@@ -64,7 +210,7 @@ pub fn get_simple_connect_token(client_id: u64, addresses: Vec<SocketAddr>) -> C
         client_id,
         10,
         addresses,
-        None,
+        Some(user_data),
         &PRIVATE_KEY,
     )
     .unwrap()
@@ -92,13 +238,27 @@ fn get_channel(protocol: &Protocol) -> Channel {
         Protocol::PlayerMove(_)
         | Protocol::EntityMoved(_)
         | Protocol::PlayerRotate(_)
-        | Protocol::EntityRotated(_) => Channel::Unreliable,
+        | Protocol::EntityRotated(_)
+        | Protocol::TimeSync(_)
+        | Protocol::KeepAlive(_)
+        | Protocol::PlaySound(_) => Channel::Unreliable,
 
         Protocol::BlockUpdate(_)
         | Protocol::ChatSent(_)
         | Protocol::DespawnEntity(_)
         | Protocol::SpawnEntity(_)
-        | Protocol::RequestChunk(_) => Channel::Reliable,
+        | Protocol::RequestChunk(_)
+        | Protocol::InventoryUpdate(_)
+        | Protocol::JoinWorld(_)
+        | Protocol::SetGameMode(_)
+        | Protocol::SpawnPoint(_)
+        | Protocol::Teleport(_)
+        | Protocol::BlockEdit(_)
+        | Protocol::BlockEditAck(_)
+        | Protocol::Disconnect(_)
+        | Protocol::FillRegion(_)
+        | Protocol::BatchBlockUpdate(_)
+        | Protocol::BlockBreakProgress(_) => Channel::Reliable,
 
         Protocol::PartialChunkUpdate(_) => Channel::Block,
     }
@@ -113,6 +273,7 @@ fn has_resource<T: Resource>(resource: Option<Res<T>>) -> ShouldRun {
 
 mod client {
     use crate::constants::UserId;
+    use crate::dispatch::{report_unhandled_packets, PacketHandlers};
     use crate::types::{ReceivePacket, SendPacket};
     use crate::*;
     use bevy::app::AppExit;
@@ -127,6 +288,10 @@ mod client {
             use bevy::prelude::CoreStage::*;
 
             app.add_event::<RenetError>()
+                .init_resource::<PacketHandlers>()
+                .init_resource::<UnreadablePackets>()
+                .init_resource::<ClientCompression>()
+                .add_system_to_stage(PostUpdate, report_unhandled_packets)
                 .add_system_to_stage(
                     PreUpdate,
                     update_system.with_run_criteria(has_resource::<Client>),
@@ -174,24 +339,85 @@ mod client {
         }
     }
 
-    fn read_packets_system(mut client: ResMut<Client>, mut recv: EventWriter<ReceivePacket>) {
-        fn send(client: &mut Client, recv: &mut EventWriter<ReceivePacket>, channel: Channel) {
+    /// The most packets in a row that can fail to be read before the connection is assumed to be broken
+    pub const MAX_UNREADABLE_PACKETS: u32 = 8;
+
+    /// Packets from the server that couldn't be read. One bad packet is skipped, but many in a row means
+    /// the connection is broken so it's dropped with the error kept to tell the player why
+    #[derive(Resource, Default)]
+    pub struct UnreadablePackets {
+        in_a_row: u32,
+        pub error: Option<NetworkingError>,
+    }
+
+    impl UnreadablePackets {
+        /// Reads a packet, skipping it if it can't be read
+        pub fn read(&mut self, bytes: &[u8]) -> Option<Protocol> {
+            match deserialize_packet(bytes) {
+                Ok(protocol) => {
+                    self.in_a_row = 0;
+                    Some(protocol)
+                }
+                Err(err) => {
+                    error!("Skipping packet: {}", err);
+
+                    self.in_a_row += 1;
+                    if self.in_a_row >= MAX_UNREADABLE_PACKETS && self.error.is_none() {
+                        self.error = Some(err);
+                    }
+                    None
+                }
+            }
+        }
+
+        /// Whether so many packets couldn't be read that the connection should be dropped
+        pub fn is_broken(&self) -> bool {
+            self.error.is_some()
+        }
+    }
+
+    fn read_packets_system(
+        mut client: ResMut<Client>,
+        mut recv: EventWriter<ReceivePacket>,
+        mut unreadable: ResMut<UnreadablePackets>,
+    ) {
+        fn send(
+            client: &mut Client,
+            recv: &mut EventWriter<ReceivePacket>,
+            unreadable: &mut UnreadablePackets,
+            channel: Channel,
+        ) {
             while let Some(bytes) = client.receive_message(channel) {
-                let protocol: Protocol = bincode::deserialize(&bytes).unwrap();
-                recv.send(ReceivePacket(protocol, UserId(client.client_id())));
+                if let Some(protocol) = unreadable.read(&bytes) {
+                    recv.send(ReceivePacket(protocol, UserId(client.client_id())));
+                }
             }
         }
-        send(&mut client, &mut recv, Channel::Unreliable);
-        send(&mut client, &mut recv, Channel::Reliable);
-        send(&mut client, &mut recv, Channel::Block);
+        send(&mut client, &mut recv, &mut unreadable, Channel::Unreliable);
+        send(&mut client, &mut recv, &mut unreadable, Channel::Reliable);
+        send(&mut client, &mut recv, &mut unreadable, Channel::Block);
+
+        if unreadable.is_broken() && client.is_connected() {
+            error!("Too many packets couldn't be read, disconnecting");
+            client.disconnect();
+        }
     }
 
-    fn write_packets_system(mut client: ResMut<Client>, mut to_send: EventReader<SendPacket>) {
-        to_send.iter().for_each(|v: &SendPacket| {
-            let ser = bincode::serialize(&v.0).unwrap();
-            let channel = get_channel(&v.0);
-            client.send_message(channel, ser);
-        })
+    /// How packets to the server are compressed. Nothing is compressed until the server says what was agreed on
+    /// when we join
+    #[derive(Resource, Default, Debug, Copy, Clone, PartialEq, Eq)]
+    pub struct ClientCompression(pub Compression);
+
+    /// Sends every queued packet. One that can't be serialized is skipped rather than holding up the rest,
+    /// only a failing socket ends the connection
+    fn write_packets_system(
+        mut client: ResMut<Client>,
+        mut to_send: EventReader<SendPacket>,
+        compression: Res<ClientCompression>,
+    ) {
+        for (v, ser) in serialize_outgoing(to_send.iter(), |_| compression.0) {
+            client.send_message(get_channel(&v.0), ser);
+        }
     }
 
     fn detect_shutdown_system(mut client: ResMut<Client>, mut bevy_shutdown: EventReader<AppExit>) {
@@ -201,10 +427,20 @@ mod client {
         }
     }
 
-    fn send_packets_system(mut client: ResMut<Client>, mut renet_error: EventWriter<RenetError>) {
+    fn send_packets_system(
+        mut client: ResMut<Client>,
+        mut renet_error: EventWriter<RenetError>,
+        mut commands: Commands,
+    ) {
         if let Err(e) = client.send_packets() {
-            error!("Renet Send: {}", e);
-            renet_error.send(e);
+            if let RenetError::IO(err) = e {
+                // Same as when updating, a socket that can't send won't recover
+                commands.remove_resource::<Client>();
+                error!("IO Error sending to server {:?}. Terminating.", err);
+            } else {
+                error!("Renet Send: {}", e);
+                renet_error.send(e);
+            }
         }
     }
 
@@ -227,7 +463,9 @@ mod client {
 }
 
 pub mod server {
+    use crate::compression::ConnectionCompression;
     use crate::constants::UserId;
+    use crate::dispatch::{report_unhandled_packets, PacketHandlers};
     use crate::types::{ReceivePacket, SendPacket};
     use crate::*;
     use bevy::app::AppExit;
@@ -242,6 +480,9 @@ pub mod server {
             use bevy::prelude::CoreStage::*;
             app.add_event::<RenetError>()
                 .add_event::<ServerEvent>()
+                .init_resource::<PacketHandlers>()
+                .init_resource::<ConnectionCompression>()
+                .add_system_to_stage(PostUpdate, report_unhandled_packets)
                 .add_system_to_stage(
                     PreUpdate,
                     update_system.with_run_criteria(has_resource::<Server>),
@@ -270,6 +511,7 @@ pub mod server {
         mut server: ResMut<Server>,
         mut renet_error: EventWriter<RenetError>,
         mut server_events: EventWriter<ServerEvent>,
+        mut compression: ResMut<ConnectionCompression>,
         time: Res<Time>,
     ) {
         if let Err(e) = server.update(time.delta()) {
@@ -278,7 +520,16 @@ pub mod server {
         }
 
         while let Some(event) = server.get_event() {
-            info!("{:?}", event);
+            match &event {
+                ServerEvent::ClientConnected(id, user_data) => {
+                    let chosen = compression.connect(*id, user_data);
+                    info!("{:?} connected, compressing with {:?}", UserId(*id), chosen);
+                }
+                ServerEvent::ClientDisconnected(id) => {
+                    compression.disconnect(*id);
+                    info!("{:?}", event);
+                }
+            }
             server_events.send(event);
         }
     }
@@ -292,8 +543,10 @@ pub mod server {
                 channel: Channel,
             ) {
                 while let Some(bytes) = server.receive_message(user_id, channel) {
-                    let protocol: Protocol = bincode::deserialize(&bytes).unwrap();
-                    recv.send(ReceivePacket(protocol, UserId(user_id)));
+                    match deserialize_packet(&bytes) {
+                        Ok(protocol) => recv.send(ReceivePacket(protocol, UserId(user_id))),
+                        Err(err) => error!("From {:?}: {}", UserId(user_id), err),
+                    }
                 }
             }
             send(&mut server, &mut recv, user_id, Channel::Unreliable);
@@ -302,12 +555,14 @@ pub mod server {
         })
     }
 
-    fn write_packets_system(mut server: ResMut<Server>, mut to_send: EventReader<SendPacket>) {
-        to_send.iter().for_each(|v| {
-            let ser = bincode::serialize(&v.0).unwrap();
-            let channel = get_channel(&v.0);
-            server.send_message(v.1 .0, channel, ser);
-        })
+    fn write_packets_system(
+        mut server: ResMut<Server>,
+        mut to_send: EventReader<SendPacket>,
+        compression: Res<ConnectionCompression>,
+    ) {
+        for (v, ser) in serialize_outgoing(to_send.iter(), |user| compression.get(user.0)) {
+            server.send_message(v.1 .0, get_channel(&v.0), ser);
+        }
     }
 
     fn detect_shutdown_system(mut server: ResMut<Server>, mut bevy_shutdown: EventReader<AppExit>) {
@@ -341,3 +596,209 @@ pub mod server {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The send queue of the block channel, the only channel with one set
+    fn send_queue(config: &RenetConnectionConfig) -> Option<usize> {
+        config
+            .send_channels_config
+            .iter()
+            .find_map(|channel| match channel {
+                ChannelConfig::Block(val) => Some(val.message_send_queue_size),
+                _ => None,
+            })
+    }
+
+    #[test]
+    fn configured_buffers_are_used() {
+        let config = connection_config(ConnectionBuffers {
+            sent_packets: 512,
+            received_packets: 128,
+            send_queue: 4096,
+        });
+
+        assert_eq!(config.sent_packets_buffer_size, 512);
+        assert_eq!(config.received_packets_buffer_size, 128);
+        assert_eq!(send_queue(&config), Some(4096));
+    }
+
+    #[test]
+    fn default_config_uses_default_buffers() {
+        let config = get_renet_connection_config();
+        let defaults = ConnectionBuffers::default();
+
+        assert_eq!(config.sent_packets_buffer_size, defaults.sent_packets);
+        assert_eq!(
+            config.received_packets_buffer_size,
+            defaults.received_packets
+        );
+        assert_eq!(send_queue(&config), Some(defaults.send_queue));
+    }
+
+    #[test]
+    fn small_buffers_are_raised_to_the_minimum() {
+        let config = connection_config(ConnectionBuffers {
+            sent_packets: 1,
+            received_packets: 0,
+            send_queue: 1024,
+        });
+
+        assert_eq!(
+            config.sent_packets_buffer_size,
+            ConnectionBuffers::MINIMUM.sent_packets
+        );
+        assert_eq!(
+            config.received_packets_buffer_size,
+            ConnectionBuffers::MINIMUM.received_packets
+        );
+        // Sizes above the minimum are left alone
+        assert_eq!(send_queue(&config), Some(1024));
+    }
+
+    #[test]
+    fn minimum_buffers_are_valid() {
+        assert_eq!(
+            ConnectionBuffers::MINIMUM.validated(),
+            ConnectionBuffers::MINIMUM
+        );
+        assert_eq!(
+            ConnectionBuffers::default().validated(),
+            ConnectionBuffers::default()
+        );
+    }
+
+    fn keep_alive(interval: u64, timeout: u64) -> KeepAliveSettings {
+        KeepAliveSettings {
+            interval: Duration::from_secs(interval),
+            timeout: Duration::from_secs(timeout),
+        }
+    }
+
+    #[test]
+    fn keep_alive_timeout_must_be_after_the_interval() {
+        assert_eq!(
+            keep_alive(10, 5).validate(),
+            Err(KeepAliveError::TimeoutNotAfterInterval)
+        );
+        assert_eq!(
+            keep_alive(10, 10).validate(),
+            Err(KeepAliveError::TimeoutNotAfterInterval)
+        );
+        assert_eq!(keep_alive(10, 11).validate(), Ok(()));
+    }
+
+    #[test]
+    fn keep_alive_interval_cant_be_zero() {
+        assert_eq!(
+            keep_alive(0, 5).validate(),
+            Err(KeepAliveError::ZeroInterval)
+        );
+        assert_eq!(KeepAliveSettings::default().validate(), Ok(()));
+    }
+
+    /// A frame that isn't compressed but whose packet has no valid variant
+    const CORRUPT_FRAME: [u8; 5] = [0, 0xFF, 0xFF, 0xFF, 0xFF];
+
+    fn readable_frame() -> (Protocol, Vec<u8>) {
+        let packet = Protocol::ChatSent(protocol::clientbound::chat::ChatSent {
+            message: "hello".to_string(),
+        });
+        let bytes = serialize_packet(&packet, Compression::None).unwrap();
+        (packet, bytes)
+    }
+
+    #[test]
+    fn corrupt_packets_are_skipped() {
+        let mut unreadable = UnreadablePackets::default();
+        let (packet, bytes) = readable_frame();
+
+        assert_eq!(unreadable.read(&CORRUPT_FRAME), None);
+        assert_eq!(unreadable.read(&[]), None);
+        assert!(!unreadable.is_broken());
+
+        assert_eq!(unreadable.read(&bytes), Some(packet));
+    }
+
+    #[test]
+    fn many_unreadable_packets_in_a_row_break_the_connection() {
+        let mut unreadable = UnreadablePackets::default();
+        for _ in 1..MAX_UNREADABLE_PACKETS {
+            unreadable.read(&CORRUPT_FRAME);
+        }
+        assert!(!unreadable.is_broken());
+
+        unreadable.read(&CORRUPT_FRAME);
+        assert!(unreadable.is_broken());
+        assert!(matches!(
+            unreadable.error,
+            Some(NetworkingError::Serialization(_))
+        ));
+
+        // The error that broke the connection is the one kept
+        unreadable.read(&[]);
+        assert!(matches!(
+            unreadable.error,
+            Some(NetworkingError::Serialization(_))
+        ));
+    }
+
+    #[test]
+    fn readable_packets_reset_the_count() {
+        let mut unreadable = UnreadablePackets::default();
+        let (_, bytes) = readable_frame();
+
+        for _ in 1..MAX_UNREADABLE_PACKETS {
+            unreadable.read(&CORRUPT_FRAME);
+        }
+        unreadable.read(&bytes);
+        for _ in 1..MAX_UNREADABLE_PACKETS {
+            unreadable.read(&CORRUPT_FRAME);
+        }
+
+        assert!(!unreadable.is_broken());
+    }
+
+    fn chat(message: String) -> Protocol {
+        Protocol::ChatSent(protocol::clientbound::chat::ChatSent { message })
+    }
+
+    #[test]
+    fn packets_that_cant_be_sent_dont_stop_the_rest() {
+        let (first, _) = readable_frame();
+        let last = chat("world".to_string());
+        let packets = vec![
+            SendPacket(first.clone(), UserId(1)),
+            // Too large to send uncompressed
+            SendPacket(chat("a".repeat(MAX_MESSAGE_SIZE)), UserId(1)),
+            SendPacket(last.clone(), UserId(2)),
+        ];
+
+        let sent: Vec<_> = serialize_outgoing(packets.iter(), |_| Compression::None)
+            .map(|(v, ser)| (v.1, deserialize_packet(&ser).unwrap()))
+            .collect();
+
+        assert_eq!(sent, vec![(UserId(1), first), (UserId(2), last)]);
+    }
+
+    #[test]
+    fn each_receiver_uses_their_own_compression() {
+        let message = chat("a".repeat(MAX_MESSAGE_SIZE));
+        let packets = vec![
+            SendPacket(message.clone(), UserId(1)),
+            SendPacket(message.clone(), UserId(2)),
+        ];
+
+        let sent: Vec<_> = serialize_outgoing(packets.iter(), |user| match user {
+            UserId(2) => Compression::Fast,
+            _ => Compression::None,
+        })
+        .map(|(v, ser)| (v.1, deserialize_packet(&ser).unwrap()))
+        .collect();
+
+        // Only the compressed copy is small enough to send
+        assert_eq!(sent, vec![(UserId(2), message)]);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::compression::COMPRESSION_USER_DATA_BYTES;
+use renet::NETCODE_USER_DATA_BYTES;
+
+/// The longest a player's name can be, in bytes
+pub const MAX_NAME_BYTES: usize = 32;
+
+/// Where the name's length is kept in the user data, with the name itself right after it
+const NAME_OFFSET: usize = COMPRESSION_USER_DATA_BYTES;
+
+/// Cleans up a name so it can be typed in commands and shown in chat. Control characters and whitespace are removed
+/// and it's cut down to MAX_NAME_BYTES, returning None if nothing is left
+pub fn sanitize_name(name: &str) -> Option<String> {
+    let chars = name
+        .chars()
+        .filter(|c| !c.is_control() && !c.is_whitespace());
+
+    let mut sanitized = String::new();
+    for c in chars {
+        if sanitized.len() + c.len_utf8() > MAX_NAME_BYTES {
+            break;
+        }
+        sanitized.push(c);
+    }
+
+    (!sanitized.is_empty()).then_some(sanitized)
+}
+
+/// Writes the name a player goes by into the user data they connect with, after the compression they support
+pub fn write_name_user_data(data: &mut [u8; NETCODE_USER_DATA_BYTES], name: &str) {
+    let name = sanitize_name(name).unwrap_or_default();
+
+    data[NAME_OFFSET] = name.len() as u8;
+    data[NAME_OFFSET + 1..][..name.len()].copy_from_slice(name.as_bytes());
+}
+
+/// Reads the name a player goes by back out of their user data, or None if they didn't send a usable one
+pub fn read_name_user_data(data: &[u8; NETCODE_USER_DATA_BYTES]) -> Option<String> {
+    let length = (data[NAME_OFFSET] as usize).min(MAX_NAME_BYTES);
+    let name = std::str::from_utf8(&data[NAME_OFFSET + 1..][..length]).ok()?;
+
+    // Names are checked again, as the client may not have
+    sanitize_name(name).filter(|v| v == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{compression_user_data, read_compression_user_data, Compression};
+
+    #[test]
+    fn names_are_cleaned_up() {
+        assert_eq!(sanitize_name("alice"), Some("alice".to_string()));
+        assert_eq!(sanitize_name(" al ice\n"), Some("alice".to_string()));
+        assert_eq!(sanitize_name(" \t\n"), None);
+        assert_eq!(sanitize_name(""), None);
+
+        // Long names are cut without splitting a character
+        let long = "é".repeat(MAX_NAME_BYTES);
+        assert_eq!(sanitize_name(&long), Some("é".repeat(MAX_NAME_BYTES / 2)));
+    }
+
+    #[test]
+    fn names_survive_the_user_data_with_compression() {
+        let preferences = vec![Compression::Max, Compression::Fast];
+        let mut data = compression_user_data(&preferences);
+        write_name_user_data(&mut data, "alice");
+
+        assert_eq!(read_name_user_data(&data), Some("alice".to_string()));
+        assert_eq!(read_compression_user_data(&data), preferences);
+    }
+
+    #[test]
+    fn missing_or_broken_names_are_ignored() {
+        let mut data = [0; NETCODE_USER_DATA_BYTES];
+        assert_eq!(read_name_user_data(&data), None);
+
+        write_name_user_data(&mut data, "   ");
+        assert_eq!(read_name_user_data(&data), None);
+
+        // Names that weren't cleaned up by the client aren't trusted
+        data[NAME_OFFSET] = 3;
+        data[NAME_OFFSET + 1..][..3].copy_from_slice(b"a b");
+        assert_eq!(read_name_user_data(&data), None);
+
+        data[NAME_OFFSET + 1..][..3].copy_from_slice(&[0xff, 0xfe, 0xfd]);
+        assert_eq!(read_name_user_data(&data), None);
+    }
+}
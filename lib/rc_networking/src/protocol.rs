@@ -1,13 +1,27 @@
+use crate::protocol::clientbound::batch_block_update::BatchBlockUpdate;
+use crate::protocol::clientbound::block_edit_ack::BlockEditAck;
 use crate::protocol::clientbound::block_update::BlockUpdate;
 use crate::protocol::clientbound::chat::ChatSent;
 use crate::protocol::clientbound::chunk_update::FullChunkUpdate;
 use crate::protocol::clientbound::despawn_entity::DespawnEntity;
+use crate::protocol::clientbound::disconnect::Disconnect;
 use crate::protocol::clientbound::entity_moved::EntityMoved;
 use crate::protocol::clientbound::entity_rotated::EntityRotated;
+use crate::protocol::clientbound::fill_region::FillRegion;
+use crate::protocol::clientbound::inventory_update::InventoryUpdate;
+use crate::protocol::clientbound::join_world::JoinWorld;
+use crate::protocol::clientbound::keep_alive::KeepAlive;
+use crate::protocol::clientbound::play_sound::PlaySound;
+use crate::protocol::clientbound::set_game_mode::SetGameMode;
 use crate::protocol::clientbound::spawn_entity::SpawnEntity;
+use crate::protocol::clientbound::teleport::Teleport;
+use crate::protocol::clientbound::time_sync::TimeSync;
+use crate::protocol::serverbound::block_break_progress::BlockBreakProgress;
+use crate::protocol::serverbound::block_edit::BlockEdit;
 use crate::protocol::serverbound::player_move::PlayerMove;
 use crate::protocol::serverbound::player_rotate::PlayerRotate;
 use crate::protocol::serverbound::request_chunk::RequestChunk;
+use crate::protocol::serverbound::spawn_point::SpawnPoint;
 use serde::{Deserialize, Serialize};
 
 pub mod clientbound;
@@ -26,4 +40,18 @@ pub enum Protocol {
     PartialChunkUpdate(FullChunkUpdate),
     SpawnEntity(SpawnEntity),
     RequestChunk(RequestChunk),
+    InventoryUpdate(InventoryUpdate),
+    JoinWorld(JoinWorld),
+    TimeSync(TimeSync),
+    KeepAlive(KeepAlive),
+    SetGameMode(SetGameMode),
+    SpawnPoint(SpawnPoint),
+    Teleport(Teleport),
+    BlockEdit(BlockEdit),
+    BlockEditAck(BlockEditAck),
+    PlaySound(PlaySound),
+    Disconnect(Disconnect),
+    FillRegion(FillRegion),
+    BatchBlockUpdate(BatchBlockUpdate),
+    BlockBreakProgress(BlockBreakProgress),
 }
@@ -0,0 +1,27 @@
+use crate::protocol::clientbound::block_update::BlockUpdate;
+use serde::{Deserialize, Serialize};
+
+/// The most updates sent in one batch, larger runs are split over several so no message gets near the size limit
+pub const MAX_BATCH_UPDATES: usize = 1024;
+
+/// Several block updates within one chunk, so the chunk is only rebuilt once for all of them
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[repr(C)]
+pub struct BatchBlockUpdate {
+    pub chunk: [i32; 3],
+    /// Positions are global, like a single block update
+    pub updates: Vec<BlockUpdate>,
+}
+
+impl BatchBlockUpdate {
+    /// Splits the updates to a chunk into as few batches as fit within the batch limit
+    pub fn split(chunk: [i32; 3], updates: &[BlockUpdate]) -> Vec<BatchBlockUpdate> {
+        updates
+            .chunks(MAX_BATCH_UPDATES)
+            .map(|updates| BatchBlockUpdate {
+                chunk,
+                updates: updates.to_vec(),
+            })
+            .collect()
+    }
+}
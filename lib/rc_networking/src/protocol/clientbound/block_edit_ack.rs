@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// Tells the client whether one of its block edits was accepted, and what the block really is now
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct BlockEditAck {
+    /// The sequence number of the edit being answered
+    pub sequence: u32,
+    pub accepted: bool,
+    /// The block the server has at the position, which the client should show if the edit was rejected
+    pub id: u32,
+}
+
+impl BlockEditAck {
+    pub fn new(sequence: u32, accepted: bool, id: u32) -> BlockEditAck {
+        BlockEditAck {
+            sequence,
+            accepted,
+            id,
+        }
+    }
+}
@@ -3,6 +3,7 @@ use serde::{Serialize, Deserialize};
 #[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
 #[repr(C)]
 pub struct BlockUpdate {
+    /// The packed block id and state, see [`crate::constants::pack_block`]
     pub id: u32,
     pub x: i32,
     pub y: i32,
@@ -9,7 +9,7 @@ pub const CHUNK_UPDATE_PARTIAL_CHUNKS: usize =
 
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 #[repr(C)]
 pub struct FullChunkUpdate {
     pub data: RawChunkData,
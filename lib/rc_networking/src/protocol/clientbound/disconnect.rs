@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// Why the server is about to disconnect a player
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Copy, Clone)]
+pub enum DisconnectReason {
+    /// Didn't move or do anything for longer than the server allows
+    Idle,
+}
+
+impl std::fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisconnectReason::Idle => write!(f, "you were idle for too long"),
+        }
+    }
+}
+
+/// Sent just before the server disconnects a player, so they can be told why
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Disconnect {
+    pub reason: DisconnectReason,
+}
+
+impl Disconnect {
+    pub fn new(reason: DisconnectReason) -> Disconnect {
+        Disconnect { reason }
+    }
+}
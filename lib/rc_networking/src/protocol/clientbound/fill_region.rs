@@ -0,0 +1,219 @@
+use crate::constants::{pack_block, RawChunkData, CHUNK_SIZE};
+use serde::{Deserialize, Serialize};
+
+/// The most blocks a single fill can change, so a mistyped corner can't stall the server and every client
+pub const MAX_FILL_VOLUME: u64 = 64 * 64 * 64;
+
+/// Fills a cuboid of the world with one block, sent in place of a block update for every block in it
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct FillRegion {
+    /// Opposite corners of the cuboid, both included. Every axis of min is at most the same axis of max
+    pub min: [i32; 3],
+    pub max: [i32; 3],
+    pub id: u32,
+    pub state: u8,
+}
+
+/// The part of a fill that lands in one chunk, as positions within the chunk with both ends included
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub struct ChunkSpan {
+    pub chunk: [i32; 3],
+    pub min: [usize; 3],
+    pub max: [usize; 3],
+}
+
+impl ChunkSpan {
+    /// Every position within the chunk the span covers
+    pub fn positions(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        (self.min[0]..=self.max[0]).flat_map(move |x| {
+            (self.min[1]..=self.max[1])
+                .flat_map(move |y| (self.min[2]..=self.max[2]).map(move |z| [x, y, z]))
+        })
+    }
+
+    pub fn fill(&self, world: &mut RawChunkData, block: u32) {
+        for [x, y, z] in self.positions() {
+            world.set(x, y, z, block);
+        }
+    }
+}
+
+impl FillRegion {
+    /// Fills between two corners, given in either order
+    pub fn new(a: [i32; 3], b: [i32; 3], id: u32, state: u8) -> FillRegion {
+        FillRegion {
+            min: [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+            max: [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+            id,
+            state,
+        }
+    }
+
+    /// The id and state packed together, as they're stored in chunk data
+    pub fn block(&self) -> u32 {
+        pack_block(self.id, self.state)
+    }
+
+    /// How many blocks the region covers, 0 if its corners are the wrong way round
+    pub fn volume(&self) -> u64 {
+        (0..3)
+            .map(|i| (self.max[i] as i64 - self.min[i] as i64 + 1).max(0) as u64)
+            .product()
+    }
+
+    /// Whether the region is the right way round and within the size limit
+    pub fn is_valid(&self) -> bool {
+        let volume = self.volume();
+        volume > 0 && volume <= MAX_FILL_VOLUME
+    }
+
+    /// The part of the region in each chunk `chunk_size` blocks wide it covers, so each chunk is written and rebuilt
+    /// once. Check the region is valid first, this visits every chunk however large it is
+    pub fn chunk_spans(&self, chunk_size: usize) -> Vec<ChunkSpan> {
+        let size = chunk_size as i32;
+        let chunk_min = self.min.map(|v| v.div_euclid(size));
+        let chunk_max = self.max.map(|v| v.div_euclid(size));
+
+        // Where the region starts and ends within a chunk along one axis
+        let span = |axis: usize, chunk: i32| {
+            let origin = chunk * size;
+            (
+                (self.min[axis] - origin).max(0) as usize,
+                (self.max[axis] - origin).min(size - 1) as usize,
+            )
+        };
+
+        let mut spans = Vec::new();
+        for cx in chunk_min[0]..=chunk_max[0] {
+            for cy in chunk_min[1]..=chunk_max[1] {
+                for cz in chunk_min[2]..=chunk_max[2] {
+                    let (x, y, z) = (span(0, cx), span(1, cy), span(2, cz));
+                    spans.push(ChunkSpan {
+                        chunk: [cx, cy, cz],
+                        min: [x.0, y.0, z.0],
+                        max: [x.1, y.1, z.1],
+                    });
+                }
+            }
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Crosses the chunk boundaries at 0 along x and 16 along z
+    fn across_chunks() -> FillRegion {
+        FillRegion::new([1, 5, 17], [-2, 5, 14], 6, 0)
+    }
+
+    /// The world positions a fill's spans cover, failing if any are covered twice
+    fn covered(fill: &FillRegion) -> HashSet<[i32; 3]> {
+        let size = CHUNK_SIZE as i32;
+        let mut covered = HashSet::new();
+        for span in fill.chunk_spans(CHUNK_SIZE) {
+            for [x, y, z] in span.positions() {
+                let position = [
+                    span.chunk[0] * size + x as i32,
+                    span.chunk[1] * size + y as i32,
+                    span.chunk[2] * size + z as i32,
+                ];
+                assert!(covered.insert(position), "{:?} covered twice", position);
+            }
+        }
+        covered
+    }
+
+    #[test]
+    fn corners_are_put_in_order() {
+        let fill = across_chunks();
+
+        assert_eq!(fill.min, [-2, 5, 14]);
+        assert_eq!(fill.max, [1, 5, 17]);
+        assert_eq!(fill.volume(), 16);
+    }
+
+    #[test]
+    fn fills_are_limited_in_size() {
+        assert!(FillRegion::new([0, 0, 0], [63, 63, 63], 6, 0).is_valid());
+        assert!(!FillRegion::new([0, 0, 0], [64, 63, 63], 6, 0).is_valid());
+
+        let backwards = FillRegion {
+            min: [5, 0, 0],
+            max: [4, 0, 0],
+            id: 6,
+            state: 0,
+        };
+        assert_eq!(backwards.volume(), 0);
+        assert!(!backwards.is_valid());
+    }
+
+    #[test]
+    fn spans_cover_exactly_the_region_across_chunks() {
+        let fill = across_chunks();
+
+        let chunks: HashSet<[i32; 3]> = fill
+            .chunk_spans(CHUNK_SIZE)
+            .iter()
+            .map(|v| v.chunk)
+            .collect();
+        assert_eq!(
+            chunks,
+            HashSet::from([[-1, 0, 0], [0, 0, 0], [-1, 0, 1], [0, 0, 1]])
+        );
+
+        let mut expected = HashSet::new();
+        for x in -2..=1 {
+            for z in 14..=17 {
+                expected.insert([x, 5, z]);
+            }
+        }
+        assert_eq!(covered(&fill), expected);
+    }
+
+    #[test]
+    fn single_block_fills_cover_one_chunk() {
+        let fill = FillRegion::new([-1, -1, -1], [-1, -1, -1], 6, 0);
+
+        assert_eq!(
+            fill.chunk_spans(CHUNK_SIZE),
+            vec![ChunkSpan {
+                chunk: [-1, -1, -1],
+                min: [15, 15, 15],
+                max: [15, 15, 15],
+            }]
+        );
+
+        // The same block is the last one of a chunk of another size
+        assert_eq!(
+            fill.chunk_spans(24),
+            vec![ChunkSpan {
+                chunk: [-1, -1, -1],
+                min: [23, 23, 23],
+                max: [23, 23, 23],
+            }]
+        );
+    }
+
+    #[test]
+    fn spans_only_write_their_blocks() {
+        let fill = FillRegion::new([0, 0, 0], [2, 3, 4], 6, 2);
+        let span = fill.chunk_spans(CHUNK_SIZE)[0];
+
+        let mut world = RawChunkData::new(CHUNK_SIZE);
+        span.fill(&mut world, fill.block());
+
+        let filled = world
+            .blocks()
+            .iter()
+            .filter(|v| **v == fill.block())
+            .count();
+        assert_eq!(filled as u64, fill.volume());
+        assert_eq!(world.get(2, 3, 4), fill.block());
+        assert_eq!(world.get(3, 3, 4), 0);
+    }
+}
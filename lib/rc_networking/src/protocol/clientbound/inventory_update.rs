@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+/// Sets the contents of a slot in the players inventory
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct InventoryUpdate {
+    pub slot: u8,
+    /// The block id of the item in the slot
+    pub item: u32,
+    /// How many items are in the slot, 0 if it's empty
+    pub amount: u32,
+}
+
+impl InventoryUpdate {
+    pub fn new(slot: u8, item: u32, amount: u32) -> InventoryUpdate {
+        InventoryUpdate { slot, item, amount }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::compression::Compression;
+use crate::constants::{Dimension, GameMode};
+use serde::{Deserialize, Serialize};
+
+/// Sent once a player has joined, before any chunks, so they know the world they're in
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct JoinWorld {
+    pub seed: u64,
+    /// Where the player starts
+    pub spawn_pos: [f32; 3],
+    /// How many seconds the world's clock has run for, further updates come from TimeSync
+    pub time_of_day: f32,
+    pub game_mode: GameMode,
+    /// How many chunk sections high a column of the world is, chunks outside of it aren't sent
+    pub column_sections: i32,
+    pub dimension: Dimension,
+    /// How many blocks along each side the server's chunks are, players place and mesh their chunks at this size
+    pub chunk_size: u32,
+    /// How the player compresses large packets, agreed on from what they connected with. Nothing is compressed
+    /// unless the server sets it
+    pub compression: Compression,
+}
+
+impl JoinWorld {
+    pub fn new(
+        seed: u64,
+        spawn_pos: [f32; 3],
+        time_of_day: f32,
+        game_mode: GameMode,
+        column_sections: i32,
+        dimension: Dimension,
+        chunk_size: u32,
+    ) -> JoinWorld {
+        JoinWorld {
+            seed,
+            spawn_pos,
+            time_of_day,
+            game_mode,
+            column_sections,
+            dimension,
+            chunk_size,
+            compression: Compression::None,
+        }
+    }
+}
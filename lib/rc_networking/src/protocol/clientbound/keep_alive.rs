@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Sent regularly by the server and sent straight back by the client, so connections that have gone quiet can be dropped
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct KeepAlive {
+    /// Counts up with each keep-alive sent
+    pub id: u64,
+}
+
+impl KeepAlive {
+    pub fn new(id: u64) -> KeepAlive {
+        KeepAlive { id }
+    }
+}
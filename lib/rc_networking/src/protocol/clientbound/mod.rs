@@ -1,7 +1,18 @@
+pub mod batch_block_update;
+pub mod block_edit_ack;
 pub mod block_update;
 pub mod chat;
 pub mod chunk_update;
+pub mod despawn_entity;
+pub mod disconnect;
 pub mod entity_moved;
 pub mod entity_rotated;
+pub mod fill_region;
+pub mod inventory_update;
+pub mod join_world;
+pub mod keep_alive;
+pub mod play_sound;
+pub mod set_game_mode;
 pub mod spawn_entity;
-pub mod despawn_entity;
+pub mod teleport;
+pub mod time_sync;
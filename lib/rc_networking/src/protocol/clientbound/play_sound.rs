@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// The sounds the server can ask clients to play
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Sound {
+    BlockPlace,
+    BlockBreak,
+    /// A block changing state in place, such as a door opening
+    BlockUse,
+}
+
+/// Plays a sound at a position in the world, sent to players close enough to hear it
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct PlaySound {
+    pub sound_id: Sound,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    /// How loud the sound is at its source, 1.0 is normal
+    pub volume: f32,
+    /// How fast the sound plays, 1.0 is normal
+    pub pitch: f32,
+}
+
+impl PlaySound {
+    pub fn new(sound_id: Sound, x: f32, y: f32, z: f32) -> PlaySound {
+        PlaySound {
+            sound_id,
+            x,
+            y,
+            z,
+            volume: 1.0,
+            pitch: 1.0,
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use crate::constants::GameMode;
+use serde::{Deserialize, Serialize};
+
+/// Sent when a player's game mode is changed after joining
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct SetGameMode {
+    pub game_mode: GameMode,
+}
+
+impl SetGameMode {
+    pub fn new(game_mode: GameMode) -> SetGameMode {
+        SetGameMode { game_mode }
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// Moves the player, sent once the chunk they're moved to has been sent to them
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Teleport {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Teleport {
+    pub fn new(x: f32, y: f32, z: f32) -> Teleport {
+        Teleport { x, y, z }
+    }
+}
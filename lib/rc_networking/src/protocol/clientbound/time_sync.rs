@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Sent regularly to keep the clients' clocks in step with the server's
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct TimeSync {
+    /// How many seconds the world's clock has run for
+    pub seconds: f32,
+}
+
+impl TimeSync {
+    pub fn new(seconds: f32) -> TimeSync {
+        TimeSync { seconds }
+    }
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// How far the player has got mining a block, sent each time the cracks in it grow. The block is then broken with a
+/// [`crate::protocol::serverbound::block_edit::BlockEdit`] once it's fully mined
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct BlockBreakProgress {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    /// From 0 to 1, where 0 means the player stopped mining the block before it broke
+    pub progress: f32,
+}
+
+impl BlockBreakProgress {
+    pub fn new(x: i32, y: i32, z: i32, progress: f32) -> BlockBreakProgress {
+        BlockBreakProgress {
+            x,
+            y,
+            z,
+            progress: progress.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Whether the player stopped mining the block
+    pub fn stopped(&self) -> bool {
+        self.progress <= 0.0
+    }
+}
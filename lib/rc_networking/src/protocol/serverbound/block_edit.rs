@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// A block changed by the player, which the client has already applied to its own world.
+/// The server answers each one with a [`crate::protocol::clientbound::block_edit_ack::BlockEditAck`]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct BlockEdit {
+    /// Counts up with each edit the client makes, so the answer can be matched to the edit
+    pub sequence: u32,
+    /// The packed block id and state, see [`crate::constants::pack_block`]
+    pub id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockEdit {
+    pub fn new(sequence: u32, id: u32, x: i32, y: i32, z: i32) -> BlockEdit {
+        BlockEdit {
+            sequence,
+            id,
+            x,
+            y,
+            z,
+        }
+    }
+}
@@ -1,3 +1,6 @@
+pub mod block_break_progress;
+pub mod block_edit;
 pub mod player_move;
 pub mod player_rotate;
 pub mod request_chunk;
+pub mod spawn_point;
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Copy, Clone)]
+pub enum SpawnAction {
+    /// Makes where the player is standing their spawn point
+    Set,
+    /// Sends the player back to their spawn point
+    Teleport,
+}
+
+/// Asks the server to set or return to the player's own spawn point
+#[derive(Serialize, Deserialize, PartialEq, Debug, Copy, Clone)]
+#[repr(C)]
+pub struct SpawnPoint {
+    pub action: SpawnAction,
+}
+
+impl SpawnPoint {
+    pub fn new(action: SpawnAction) -> SpawnPoint {
+        SpawnPoint { action }
+    }
+}
@@ -1,14 +1,100 @@
-use bevy::prelude::{Resource};
+use crate::game::chunk::GenerationMode;
+use crate::game::world::chunk_codec::{ChunkCompression, ChunkEncoding};
+use bevy::prelude::Resource;
+use rc_networking::compression::Compression;
+use rc_networking::constants::{
+    Dimension, GameMode, CHUNK_SIZE, DEFAULT_COLUMN_SECTIONS, MAX_CHUNK_SIZE,
+};
+use rc_networking::{ConnectionBuffers, KeepAliveSettings};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Resource)]
+#[serde(default)]
 pub struct ServerConfig {
     pub ip: String,
     pub port: u16,
     pub save_world: bool,
+    /// How chunks are compressed when they're saved. Chunks are loaded with whatever they were saved with
+    pub chunk_compression: ChunkCompression,
+    /// How hard chunks are compressed, 0 to 9 for Gzip and 1 to 22 for Zstd. Higher is smaller but slower to save
+    pub chunk_compression_level: i32,
+    /// How many chunk sections high a column of the world is
+    pub column_sections: i32,
+    /// How many blocks along each side chunks are, up to 64. Players are sent it when they join. Chunks saved at
+    /// another size are generated again, so change it before creating the world
+    pub chunk_size: usize,
+    /// How far away from a player, in blocks, they can edit blocks
+    pub max_reach: f32,
+    /// How many out of reach edits a player can make before being kicked, 0 to never kick
+    pub reach_kick_threshold: u32,
+    /// The shortest time, in seconds, between blocks broken by a player outside creative, as only creative players break blocks instantly. 0 to not check
+    pub min_break_interval: f32,
+    /// How often the world is saved, in seconds, 0 to only save on exit
+    pub autosave_interval: u64,
+    /// How many threads generate chunks, 0 to use one per core
+    pub generation_threads: usize,
+    /// How many chunks can be generating at once, further requests wait their turn
+    pub generation_queue_size: usize,
+    /// How many chunks a player can have waiting to be generated, further requests are dropped until some are sent.
+    /// Enough for a render distance of 16 with the default column height
+    pub max_queued_chunks: usize,
+    /// The seed the world is generated from, the same seed always generates the same world
+    pub seed: u64,
+    /// How new chunks are generated, Heightmap gives plain terrain for performance testing
+    pub generation_mode: GenerationMode,
+    /// The dimension the world is, which sets the sky and fog players see in it
+    pub dimension: Dimension,
+    /// The local port a JSON snapshot of the server's state is served on over HTTP, for monitoring. 0 turns it off
+    pub metrics_port: u16,
+    /// How many players can be connected at once
+    pub max_clients: usize,
+    /// How many sent and received packets are remembered per connection, at least 64
+    pub sent_packets_buffer_size: usize,
+    pub received_packets_buffer_size: usize,
+    /// How many chunks and other large messages can be queued to send to a player, at least 256
+    pub send_queue_size: usize,
+    /// The compression players can ask for on large packets such as chunks, leave out Max to save CPU.
+    /// Players asking for none of these are sent packets uncompressed
+    pub compression: Vec<Compression>,
+    /// How many chunks a player can be sent each tick, nearest first, 0 for no limit
+    pub chunks_per_tick: usize,
+    /// How often, in seconds, players are sent a keep-alive
+    pub keep_alive_interval: u64,
+    /// How long, in seconds, a player can go without answering a keep-alive before they're disconnected. Must be longer than the interval
+    pub keep_alive_timeout: u64,
+    /// How long, in seconds, a player can go without moving or doing anything before they're disconnected
+    /// to free their slot, 0 to never disconnect idle players. Use `/afk <player>` to exempt someone
+    pub idle_timeout: u64,
+    /// The game mode players join in, creative by default as there's no gravity for survival players yet
+    pub default_game_mode: GameMode,
+    /// Lets players set their own spawn point and teleport back to it, saved under the name they connect with
+    pub personal_spawns: bool,
+    /// How far from the world spawn, in blocks across the ground, only operators can place or break blocks. 0 turns it off
+    pub spawn_protection: f32,
+    /// The names players connect with who can edit blocks anywhere, including inside spawn protection
+    pub operators: Vec<String>,
+    /// How far away from a player, in chunks, entities are simulated. Further chunks are still sent but their entities are frozen
+    pub simulation_distance: i32,
+    /// How far, in blocks, an entity has to move from where it was last sent before players are sent its new position
+    pub entity_move_threshold: f32,
+    /// The most times a second an entity's position is sent, 0 for no limit
+    pub entity_move_rate: f32,
+    /// How often, in seconds, an entity's position is sent even when it hasn't moved, so players can't drift out of sync. 0 to never
+    pub entity_keyframe_interval: f32,
+    /// How far away, in blocks, players can hear sounds such as blocks being placed
+    pub sound_range: f32,
+    /// Saves each player's inventory with the world under the name they connect with, so they get it back when they rejoin
+    pub save_inventories: bool,
+    /// How many chat messages a player can send within the rate window, 0 for no limit
+    pub chat_rate_limit: usize,
+    /// The window chat messages are counted over, in seconds
+    pub chat_rate_window: u64,
+    /// The longest a chat message can be in characters, longer messages are cut short
+    pub max_chat_length: usize,
 }
 
 impl Default for ServerConfig {
@@ -17,6 +103,77 @@ impl Default for ServerConfig {
             ip: "127.0.0.1".to_string(),
             port: 25568,
             save_world: true,
+            chunk_compression: ChunkCompression::Zstd,
+            chunk_compression_level: 3,
+            column_sections: DEFAULT_COLUMN_SECTIONS,
+            chunk_size: CHUNK_SIZE,
+            max_reach: 16.0,
+            reach_kick_threshold: 10,
+            min_break_interval: 0.1,
+            autosave_interval: 300,
+            generation_threads: 0,
+            generation_queue_size: 64,
+            max_queued_chunks: 16384,
+            seed: 0,
+            generation_mode: GenerationMode::Normal,
+            dimension: Dimension::Overworld,
+            metrics_port: 0,
+            max_clients: 1024,
+            sent_packets_buffer_size: 256,
+            received_packets_buffer_size: 256,
+            send_queue_size: 1024,
+            compression: Compression::ALL.to_vec(),
+            chunks_per_tick: 16,
+            keep_alive_interval: 5,
+            keep_alive_timeout: 15,
+            idle_timeout: 0,
+            default_game_mode: GameMode::Creative,
+            personal_spawns: true,
+            spawn_protection: 0.0,
+            operators: Vec::new(),
+            simulation_distance: 4,
+            entity_move_threshold: 0.05,
+            entity_move_rate: 20.0,
+            entity_keyframe_interval: 5.0,
+            sound_range: 32.0,
+            save_inventories: true,
+            chat_rate_limit: 5,
+            chat_rate_window: 10,
+            max_chat_length: 256,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn connection_buffers(&self) -> ConnectionBuffers {
+        ConnectionBuffers {
+            sent_packets: self.sent_packets_buffer_size,
+            received_packets: self.received_packets_buffer_size,
+            send_queue: self.send_queue_size,
+        }
+    }
+
+    /// Whether a player is listed as an operator, by their name
+    pub fn is_operator(&self, name: &str) -> bool {
+        self.operators.iter().any(|v| v == name)
+    }
+
+    /// The size chunks are, kept between 1 and the most clients accept
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size.clamp(1, MAX_CHUNK_SIZE)
+    }
+
+    pub fn chunk_encoding(&self) -> ChunkEncoding {
+        ChunkEncoding {
+            compression: self.chunk_compression,
+            level: self.chunk_compression_level,
+        }
+    }
+
+    pub fn keep_alive(&self) -> KeepAliveSettings {
+        KeepAliveSettings {
+            interval: Duration::from_secs(self.keep_alive_interval),
+            timeout: Duration::from_secs(self.keep_alive_timeout),
         }
     }
 }
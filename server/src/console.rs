@@ -1 +1,47 @@
+use crate::events::console::ConsoleCommandEvent;
+use bevy::prelude::*;
+use std::io::BufRead;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Mutex;
+use std::thread;
 
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = channel();
+
+        // Read stdin on its own thread so waiting for input doesn't block the tick loop
+        thread::spawn(move || {
+            let stdin = std::io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        app.insert_resource(ConsoleInput(Mutex::new(receiver)))
+            .add_event::<ConsoleCommandEvent>()
+            .add_system_to_stage(CoreStage::PreUpdate, read_console);
+    }
+}
+
+#[derive(Resource)]
+struct ConsoleInput(Mutex<Receiver<String>>);
+
+fn read_console(input: Res<ConsoleInput>, mut commands: EventWriter<ConsoleCommandEvent>) {
+    let receiver = input.0.lock().unwrap();
+
+    while let Ok(line) = receiver.try_recv() {
+        if let Some(command) = ConsoleCommandEvent::parse(&line) {
+            info!("Console command: {}", line.trim());
+            commands.send(command);
+        }
+    }
+}
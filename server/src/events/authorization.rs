@@ -2,10 +2,12 @@ use rc_networking::constants::UserId;
 
 pub struct AuthorizationEvent {
     pub client: UserId,
+    /// The name the user connected with, if they sent one
+    pub name: Option<String>,
 }
 
 impl AuthorizationEvent {
-    pub fn new(client: UserId) -> AuthorizationEvent {
-        AuthorizationEvent { client }
+    pub fn new(client: UserId, name: Option<String>) -> AuthorizationEvent {
+        AuthorizationEvent { client, name }
     }
 }
@@ -0,0 +1,20 @@
+use nalgebra::Vector3;
+use rc_networking::constants::UserId;
+
+/// Sent once a player's block break has been accepted and written to the world
+pub struct BlockBrokenEvent {
+    pub user: UserId,
+    pub position: Vector3<i32>,
+    /// The block that was broken, with its state
+    pub block: u32,
+}
+
+impl BlockBrokenEvent {
+    pub fn new(user: UserId, position: Vector3<i32>, block: u32) -> BlockBrokenEvent {
+        BlockBrokenEvent {
+            user,
+            position,
+            block,
+        }
+    }
+}
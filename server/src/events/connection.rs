@@ -2,12 +2,15 @@ use rc_networking::constants::UserId;
 
 pub struct ConnectionEvent {
     pub user: UserId,
+    /// The name the user connected with, if they sent one
+    pub name: Option<String>,
 }
 
 impl ConnectionEvent {
-    pub fn new(user: UserId) -> ConnectionEvent {
+    pub fn new(user: UserId, name: Option<String>) -> ConnectionEvent {
         ConnectionEvent {
-            user
+            user,
+            name
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,17 @@
+/// A command typed into the server console, such as `/save`
+pub struct ConsoleCommandEvent {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl ConsoleCommandEvent {
+    /// Parses a line of console input, returning None if it's empty
+    pub fn parse(line: &str) -> Option<ConsoleCommandEvent> {
+        let mut parts = line.trim().trim_start_matches('/').split_whitespace();
+
+        let command = parts.next()?.to_lowercase();
+        let args = parts.map(|v| v.to_string()).collect();
+
+        Some(ConsoleCommandEvent { command, args })
+    }
+}
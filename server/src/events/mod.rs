@@ -1,3 +1,5 @@
 pub mod authorization;
+pub mod block_break;
+pub mod console;
 pub mod disconnect;
 pub mod connection;
\ No newline at end of file
@@ -1,64 +1,90 @@
+use crate::game::world::rng::chunk_rng;
 use bevy::ecs::prelude::Component;
 use nalgebra::Vector3;
 use noise::{NoiseFn, Perlin};
-use rc_networking::constants::CHUNK_SIZE;
-use serde::{Deserialize, Serialize};
+use rand_chacha::rand_core::RngCore;
+use rc_networking::constants::{RawChunkData, CHUNK_SIZE};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::ops::Mul;
 
-#[derive(Debug, Component, Serialize, Deserialize)]
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
 pub struct ChunkData {
     pub position: Vector3<i32>,
 
+    #[serde(deserialize_with = "deserialize_world")]
     pub world: RawChunkData,
 }
 
-pub type RawChunkData = [[[u32; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+/// Chunks were saved as nested arrays of CHUNK_SIZE blocks before they had a size, so those still load
+fn deserialize_world<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RawChunkData, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SavedWorld {
+        Sized(RawChunkData),
+        Nested(Box<[[[u32; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>),
+    }
+
+    Ok(match SavedWorld::deserialize(deserializer)? {
+        SavedWorld::Sized(world) => world,
+        SavedWorld::Nested(world) => RawChunkData::from(&*world),
+    })
+}
+
+/// How new chunks are generated
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum GenerationMode {
+    #[default]
+    Normal,
+    /// Just the ground shape filled with stone, so rendering and networking can be measured
+    /// without the terrain changing what's being measured
+    Heightmap,
+}
 
 impl ChunkData {
     pub fn new(position: Vector3<i32>, world: RawChunkData) -> ChunkData {
         ChunkData { position, world }
     }
 
-    pub fn blank(position: Vector3<i32>) -> ChunkData {
+    /// A chunk of air, with `size` blocks along each side
+    pub fn blank(position: Vector3<i32>, size: usize) -> ChunkData {
         ChunkData {
             position,
-            world: [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+            world: RawChunkData::new(size),
         }
     }
 
-    pub fn generate(position: Vector3<i32>) -> ChunkData {
-        let mut world = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+    pub fn generate(
+        position: Vector3<i32>,
+        size: usize,
+        seed: u64,
+        mode: GenerationMode,
+    ) -> ChunkData {
+        if mode == GenerationMode::Heightmap {
+            return Self::generate_heightmap(position, size, seed);
+        }
 
-        let ground_perlin = Perlin::new(0);
-        let grass_perlin = Perlin::new(1);
+        let mut world = RawChunkData::new(size);
 
-        for x in 0..CHUNK_SIZE {
-            for y in 0..CHUNK_SIZE {
-                for z in 0..CHUNK_SIZE {
-                    let absolute = Vector3::new(
-                        (position.x * 16) + x as i32,
-                        (position.y * 16) + y as i32,
-                        (position.z * 16) + z as i32,
-                    );
-
-                    let ground_level = 35
-                        + ground_perlin
-                            .get([absolute.x as f64 / 20.0, absolute.z as f64 / 20.0])
-                            .mul(3.0)
-                            .floor() as i32;
+        let ground_perlin = Perlin::new(seed as u32);
+        let mut rng = chunk_rng(seed, position);
+        let origin = position * size as i32;
+
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let absolute = origin + Vector3::new(x as i32, y as i32, z as i32);
+
+                    let ground_level = ground_level(&ground_perlin, absolute.x, absolute.z);
 
                     if absolute.y < ground_level - 3 {
-                        world[x][y][z] = 6;
+                        world.set(x, y, z, 6);
                     } else if absolute.y < ground_level {
-                        world[x][y][z] = 1;
+                        world.set(x, y, z, 1);
                     } else if absolute.y == ground_level {
-                        world[x][y][z] = 2;
-                    } else if absolute.y == ground_level + 1 {
-                        if grass_perlin.get([absolute.x as f64 / 2.0, absolute.z as f64 / 2.0])
-                            > 0.7
-                        {
-                            world[x][y][z] = 3;
-                        }
+                        world.set(x, y, z, 2);
+                    } else if absolute.y == ground_level + 1 && rng.next_u32() % 32 == 0 {
+                        // Scatter long grass over the surface
+                        world.set(x, y, z, 3);
                     }
                 }
             }
@@ -66,4 +92,143 @@ impl ChunkData {
 
         ChunkData { position, world }
     }
+
+    /// Generates the same ground shape as normal generation, but solid stone with nothing scattered on top
+    pub fn generate_heightmap(position: Vector3<i32>, size: usize, seed: u64) -> ChunkData {
+        let mut world = RawChunkData::new(size);
+
+        let ground_perlin = Perlin::new(seed as u32);
+        let origin = position * size as i32;
+
+        for x in 0..size {
+            for z in 0..size {
+                let ground_level =
+                    ground_level(&ground_perlin, origin.x + x as i32, origin.z + z as i32);
+
+                for y in 0..size {
+                    if origin.y + y as i32 <= ground_level {
+                        world.set(x, y, z, 6);
+                    }
+                }
+            }
+        }
+
+        ChunkData { position, world }
+    }
+}
+
+/// The height of the surface block of a column
+fn ground_level(ground_perlin: &Perlin, x: i32, z: i32) -> i32 {
+    35 + ground_perlin
+        .get([x as f64 / 20.0, z as f64 / 20.0])
+        .mul(3.0)
+        .floor() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u32 = 6;
+    const GRASS: u32 = 2;
+
+    /// The height of the highest block in a column of a chunk
+    fn top(chunk: &ChunkData, x: usize, z: usize) -> Option<usize> {
+        (0..chunk.world.size())
+            .rev()
+            .find(|y| chunk.world.get(x, *y, z) != 0)
+    }
+
+    #[test]
+    fn heightmap_columns_are_stone_up_to_the_surface() {
+        let position = Vector3::new(3, 2, -5);
+        let chunk = ChunkData::generate(position, CHUNK_SIZE, 1234, GenerationMode::Heightmap);
+
+        let surface = ground_level(&Perlin::new(1234), 3 * 16 + 7, -5 * 16 + 11);
+        assert!((32..48).contains(&surface));
+
+        let height = (surface - 32) as usize;
+        for y in 0..CHUNK_SIZE {
+            let expected = if y <= height { STONE } else { 0 };
+            assert_eq!(chunk.world.get(7, y, 11), expected);
+        }
+    }
+
+    #[test]
+    fn heightmap_surface_matches_normal_generation() {
+        let position = Vector3::new(-1, 2, 4);
+        let heightmap = ChunkData::generate(position, CHUNK_SIZE, 99, GenerationMode::Heightmap);
+        let normal = ChunkData::generate(position, CHUNK_SIZE, 99, GenerationMode::Normal);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let surface = top(&heightmap, x, z).unwrap();
+                assert_eq!(normal.world.get(x, surface, z), GRASS);
+            }
+        }
+    }
+
+    #[test]
+    fn heightmap_is_deterministic() {
+        let position = Vector3::new(10, 2, 10);
+        let first = ChunkData::generate(position, CHUNK_SIZE, 7, GenerationMode::Heightmap);
+        let second = ChunkData::generate(position, CHUNK_SIZE, 7, GenerationMode::Heightmap);
+
+        assert_eq!(first.world, second.world);
+    }
+
+    #[test]
+    fn heightmap_chunks_above_and_below_the_surface() {
+        let below = ChunkData::generate(
+            Vector3::new(0, 0, 0),
+            CHUNK_SIZE,
+            7,
+            GenerationMode::Heightmap,
+        );
+        let above = ChunkData::generate(
+            Vector3::new(0, 3, 0),
+            CHUNK_SIZE,
+            7,
+            GenerationMode::Heightmap,
+        );
+
+        assert!(below.world.blocks().iter().all(|v| *v == STONE));
+        assert!(above.world.blocks().iter().all(|v| *v == 0));
+    }
+
+    #[test]
+    fn chunks_generate_at_any_size() {
+        let small = ChunkData::generate(Vector3::new(1, 4, -2), 8, 99, GenerationMode::Heightmap);
+        let normal = ChunkData::generate(
+            Vector3::new(0, 2, -1),
+            CHUNK_SIZE,
+            99,
+            GenerationMode::Heightmap,
+        );
+        assert_eq!(small.world.size(), 8);
+
+        // Placed by their own size, the smaller chunk is the far half along x of the normal one's lower corner
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    assert_eq!(small.world.get(x, y, z), normal.world.get(x + 8, y, z));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_saved_as_nested_arrays_still_load() {
+        let chunk =
+            ChunkData::generate(Vector3::new(3, 2, 1), CHUNK_SIZE, 7, GenerationMode::Normal);
+        let mut nested = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        for (i, block) in chunk.world.blocks().iter().enumerate() {
+            nested[i / (CHUNK_SIZE * CHUNK_SIZE)][(i / CHUNK_SIZE) % CHUNK_SIZE][i % CHUNK_SIZE] =
+                *block;
+        }
+
+        let json = serde_json::json!({ "position": chunk.position, "world": nested });
+        let loaded: ChunkData = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.world, chunk.world);
+    }
 }
@@ -0,0 +1,162 @@
+use bevy::ecs::prelude::Resource;
+use nalgebra::Vector3;
+use rc_networking::constants::{block_id, block_state};
+use std::collections::HashMap;
+
+/// The outline a block collides with, before being turned by its state
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CollisionShape {
+    /// Nothing to collide with, like air and plants
+    Empty,
+    Full,
+    /// Half a block high, in the top half when the state's third bit is set
+    Slab,
+    /// A bottom half with a step filling the back half of the top, turned by the state's rotation
+    Stair,
+    /// A post in the middle of the block, taller than a block so it can't be jumped over
+    Fence,
+}
+
+impl CollisionShape {
+    /// The minimum and maximum corner of each box in the shape, relative to the block's corner
+    pub fn boxes(&self, state: u8) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+        match self {
+            CollisionShape::Empty => Vec::new(),
+            CollisionShape::Full => vec![(Vector3::zeros(), Vector3::new(1.0, 1.0, 1.0))],
+            CollisionShape::Slab => {
+                let bottom = if state & 0b100 != 0 { 0.5 } else { 0.0 };
+                vec![(
+                    Vector3::new(0.0, bottom, 0.0),
+                    Vector3::new(1.0, bottom + 0.5, 1.0),
+                )]
+            }
+            CollisionShape::Stair => {
+                // The step faces -z before turning, each turn being clockwise around the block's center
+                let (min, max) = (0..(state & 0b11)).fold(
+                    (Vector3::new(0.0, 0.5, 0.5), Vector3::new(1.0, 1.0, 1.0)),
+                    |(min, max), _| {
+                        (
+                            Vector3::new(1.0 - max.z, min.y, min.x),
+                            Vector3::new(1.0 - min.z, max.y, max.x),
+                        )
+                    },
+                );
+                vec![(Vector3::zeros(), Vector3::new(1.0, 0.5, 1.0)), (min, max)]
+            }
+            CollisionShape::Fence => vec![(
+                Vector3::new(0.375, 0.0, 0.375),
+                Vector3::new(0.625, 1.5, 0.625),
+            )],
+        }
+    }
+}
+
+/// The collision shape of each block, any block not registered being a full cube
+#[derive(Resource)]
+pub struct CollisionShapes {
+    shapes: HashMap<u32, CollisionShape>,
+}
+
+impl CollisionShapes {
+    pub fn register(&mut self, block_id: u32, shape: CollisionShape) {
+        self.shapes.insert(block_id, shape);
+    }
+
+    pub fn shape(&self, block: u32) -> CollisionShape {
+        self.shapes
+            .get(&block_id(block))
+            .copied()
+            .unwrap_or(CollisionShape::Full)
+    }
+
+    /// The boxes a packed block collides with, relative to its corner
+    pub fn boxes(&self, block: u32) -> Vec<(Vector3<f32>, Vector3<f32>)> {
+        self.shape(block).boxes(block_state(block))
+    }
+
+    /// Whether a packed block at a position would overlap the bounds. Touching faces don't count
+    pub fn block_overlaps(
+        &self,
+        block: u32,
+        position: Vector3<i32>,
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+    ) -> bool {
+        let corner = position.cast::<f32>();
+        self.boxes(block).iter().any(|(box_min, box_max)| {
+            (0..3).all(|i| corner[i] + box_min[i] < max[i] && corner[i] + box_max[i] > min[i])
+        })
+    }
+}
+
+impl Default for CollisionShapes {
+    fn default() -> Self {
+        let mut shapes = CollisionShapes {
+            shapes: HashMap::new(),
+        };
+
+        // Air and long grass, matching the block states file
+        shapes.register(0, CollisionShape::Empty);
+        shapes.register(3, CollisionShape::Empty);
+
+        shapes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::{player_bounds, PhysicsBody};
+    use rc_networking::constants::pack_block;
+
+    const STONE: u32 = 6;
+    const LONG_GRASS: u32 = 3;
+
+    #[test]
+    fn block_inside_player_overlaps() {
+        let shapes = CollisionShapes::default();
+        let (min, max) = player_bounds(Vector3::new(4.0, 10.0, 4.0));
+
+        // The player's box fills the block above their position
+        assert!(shapes.block_overlaps(STONE, Vector3::new(4, 11, 4), min, max));
+    }
+
+    #[test]
+    fn block_inside_entity_overlaps() {
+        let shapes = CollisionShapes::default();
+        let (min, max) = PhysicsBody::new(0.125, 0.25).bounds(Vector3::new(2.5, 5.0, 2.5));
+
+        assert!(shapes.block_overlaps(STONE, Vector3::new(2, 5, 2), min, max));
+    }
+
+    #[test]
+    fn block_in_clear_space_is_allowed() {
+        let shapes = CollisionShapes::default();
+        let (min, max) = player_bounds(Vector3::new(4.0, 10.0, 4.0));
+
+        assert!(!shapes.block_overlaps(STONE, Vector3::new(8, 11, 4), min, max));
+        // Right under the player's box, touching it
+        assert!(!shapes.block_overlaps(STONE, Vector3::new(4, 10, 4), min, max));
+        // Right next to it
+        assert!(!shapes.block_overlaps(STONE, Vector3::new(5, 11, 4), min, max));
+    }
+
+    #[test]
+    fn blocks_without_collision_never_overlap() {
+        let shapes = CollisionShapes::default();
+        let (min, max) = player_bounds(Vector3::new(4.0, 10.0, 4.0));
+
+        assert!(!shapes.block_overlaps(LONG_GRASS, Vector3::new(4, 11, 4), min, max));
+    }
+
+    #[test]
+    fn slab_only_overlaps_its_half() {
+        let mut shapes = CollisionShapes::default();
+        shapes.register(STONE, CollisionShape::Slab);
+
+        // A body standing on the bottom slab's top
+        let (min, max) = PhysicsBody::new(0.25, 0.25).bounds(Vector3::new(0.5, 0.5, 0.5));
+        assert!(!shapes.block_overlaps(STONE, Vector3::zeros(), min, max));
+        assert!(shapes.block_overlaps(pack_block(STONE, 0b100), Vector3::zeros(), min, max));
+    }
+}
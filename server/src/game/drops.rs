@@ -0,0 +1,163 @@
+use crate::game::inventory::{ItemStack, ITEM_BLOCKS};
+use bevy::ecs::prelude::Resource;
+use rc_networking::constants::{block_id, GameMode};
+use std::collections::HashMap;
+
+/// What breaking a block leaves behind
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlockDrop {
+    /// The block id of the item
+    pub item: u32,
+    pub amount: u32,
+    /// The item that has to be held to get the drop, the block drops nothing when broken with anything else
+    pub tool: Option<u32>,
+}
+
+impl BlockDrop {
+    /// Drops one of an item, whatever the block is broken with
+    pub fn new(item: u32) -> BlockDrop {
+        BlockDrop {
+            item,
+            amount: 1,
+            tool: None,
+        }
+    }
+
+    pub fn with_tool(mut self, tool: u32) -> BlockDrop {
+        self.tool = Some(tool);
+        self
+    }
+}
+
+/// The drop of each block, keyed by block id. Blocks without one drop nothing
+#[derive(Resource)]
+pub struct DropTable {
+    drops: HashMap<u32, BlockDrop>,
+}
+
+impl Default for DropTable {
+    fn default() -> Self {
+        let mut table = DropTable {
+            drops: HashMap::new(),
+        };
+
+        // Every block with an item drops itself
+        for block in ITEM_BLOCKS {
+            table.register(block, BlockDrop::new(block));
+        }
+
+        table
+    }
+}
+
+impl DropTable {
+    pub fn register(&mut self, block: u32, drop: BlockDrop) {
+        self.drops.insert(block, drop);
+    }
+
+    /// The items a block drops when a player in the game mode breaks it holding an item.
+    /// Creative players never get drops, and blocks needing a tool drop nothing without it
+    pub fn resolve(&self, block: u32, game_mode: GameMode, held: Option<u32>) -> Option<ItemStack> {
+        if !game_mode.gets_drops() {
+            return None;
+        }
+
+        let drop = self.drops.get(&block_id(block))?;
+        if drop.tool.map_or(false, |tool| held != Some(tool)) || drop.amount == 0 {
+            return None;
+        }
+
+        Some(ItemStack {
+            item: drop.item,
+            amount: drop.amount,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc_networking::constants::pack_block;
+
+    const STONE: u32 = 6;
+    const PICKAXE: u32 = 100;
+
+    fn stack(item: u32, amount: u32) -> Option<ItemStack> {
+        Some(ItemStack { item, amount })
+    }
+
+    #[test]
+    fn blocks_with_items_drop_themselves() {
+        let table = DropTable::default();
+
+        for block in ITEM_BLOCKS {
+            assert_eq!(
+                table.resolve(block, GameMode::Survival, None),
+                stack(block, 1)
+            );
+        }
+        // Air and blocks without a registered drop leave nothing
+        assert_eq!(table.resolve(0, GameMode::Survival, None), None);
+        assert_eq!(table.resolve(STONE, GameMode::Survival, None), None);
+    }
+
+    #[test]
+    fn drops_ignore_the_block_state() {
+        let table = DropTable::default();
+        let rotated = pack_block(4, 2);
+
+        assert_eq!(
+            table.resolve(rotated, GameMode::Survival, None),
+            stack(4, 1)
+        );
+    }
+
+    #[test]
+    fn creative_and_spectator_get_no_drops() {
+        let table = DropTable::default();
+
+        assert_eq!(table.resolve(2, GameMode::Creative, None), None);
+        assert_eq!(table.resolve(2, GameMode::Spectator, None), None);
+    }
+
+    #[test]
+    fn wrong_tool_gets_no_drop() {
+        let mut table = DropTable::default();
+        table.register(STONE, BlockDrop::new(1).with_tool(PICKAXE));
+
+        assert_eq!(table.resolve(STONE, GameMode::Survival, None), None);
+        assert_eq!(table.resolve(STONE, GameMode::Survival, Some(2)), None);
+        assert_eq!(
+            table.resolve(STONE, GameMode::Survival, Some(PICKAXE)),
+            stack(1, 1)
+        );
+        // The right tool still gets nothing in creative
+        assert_eq!(
+            table.resolve(STONE, GameMode::Creative, Some(PICKAXE)),
+            None
+        );
+    }
+
+    #[test]
+    fn registering_replaces_the_drop() {
+        let mut table = DropTable::default();
+        table.register(
+            2,
+            BlockDrop {
+                item: 1,
+                amount: 3,
+                tool: None,
+            },
+        );
+        table.register(
+            5,
+            BlockDrop {
+                amount: 0,
+                ..BlockDrop::new(5)
+            },
+        );
+
+        assert_eq!(table.resolve(2, GameMode::Survival, None), stack(1, 3));
+        assert_eq!(table.resolve(5, GameMode::Survival, None), None);
+    }
+}
@@ -1 +1,69 @@
+use bevy::ecs::prelude::Component;
+use nalgebra::Vector3;
+use rc_networking::constants::{PLAYER_COLLIDER_OFFSET, PLAYER_COLLIDER_SIZE};
 
+/// The bounds of a player at the position they sent, the same box their client collides with
+pub fn player_bounds(position: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let min = position + Vector3::from(PLAYER_COLLIDER_OFFSET);
+    (min, min + Vector3::from(PLAYER_COLLIDER_SIZE))
+}
+
+/// Gives a non-player entity physics, players move themselves so don't have this
+#[derive(Component)]
+pub struct PhysicsBody {
+    pub velocity: Vector3<f32>,
+    /// Half the width of the body on the x and z axis
+    pub half_width: f32,
+    pub height: f32,
+    pub on_ground: bool,
+}
+
+impl PhysicsBody {
+    pub fn new(half_width: f32, height: f32) -> PhysicsBody {
+        PhysicsBody {
+            velocity: Vector3::zeros(),
+            half_width,
+            height,
+            on_ground: false,
+        }
+    }
+
+    /// The bounds of the body when its feet are at the position
+    pub fn bounds(&self, position: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        (
+            position - Vector3::new(self.half_width, 0.0, self.half_width),
+            position + Vector3::new(self.half_width, self.height, self.half_width),
+        )
+    }
+}
+
+/// An item lying in the world, waiting to be picked up
+#[derive(Component)]
+pub struct DroppedItem {
+    /// The block id of the item
+    pub item: u32,
+    pub amount: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_bounds_match_the_client_collider() {
+        let (min, max) = player_bounds(Vector3::new(10.0, 20.0, 30.0));
+
+        // The client's box sits a block above its position, a block wide and high
+        assert_eq!(min, Vector3::new(10.0, 21.0, 30.0));
+        assert_eq!(max, Vector3::new(11.0, 22.0, 31.0));
+    }
+
+    #[test]
+    fn body_bounds_are_centred_on_its_feet() {
+        let body = PhysicsBody::new(0.25, 0.5);
+        let (min, max) = body.bounds(Vector3::new(1.0, 2.0, 3.0));
+
+        assert_eq!(min, Vector3::new(0.75, 2.0, 2.75));
+        assert_eq!(max, Vector3::new(1.25, 2.5, 3.25));
+    }
+}
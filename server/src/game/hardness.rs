@@ -0,0 +1,66 @@
+use bevy::ecs::prelude::Resource;
+use rc_networking::constants::block_id;
+use std::collections::HashMap;
+
+/// How long blocks that aren't registered take to mine, matching the client's default
+pub const DEFAULT_HARDNESS: f32 = 0.5;
+
+/// How long, in seconds, each block takes to mine outside creative, keyed by block id
+#[derive(Resource)]
+pub struct BlockHardness {
+    hardness: HashMap<u32, f32>,
+}
+
+impl Default for BlockHardness {
+    fn default() -> Self {
+        let mut hardness = BlockHardness {
+            hardness: HashMap::new(),
+        };
+
+        // Matching the block states file
+        hardness.register(0, 0.0);
+        hardness.register(1, 0.5);
+        hardness.register(2, 0.6);
+        hardness.register(3, 0.0);
+        hardness.register(4, 1.0);
+        hardness.register(5, 0.2);
+        hardness.register(6, 1.5);
+
+        hardness
+    }
+}
+
+impl BlockHardness {
+    pub fn register(&mut self, block_id: u32, hardness: f32) {
+        self.hardness.insert(block_id, hardness);
+    }
+
+    /// How long a packed block takes to mine, whatever its state
+    pub fn hardness(&self, block: u32) -> f32 {
+        self.hardness
+            .get(&block_id(block))
+            .copied()
+            .unwrap_or(DEFAULT_HARDNESS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc_networking::constants::pack_block;
+
+    const STONE: u32 = 6;
+
+    #[test]
+    fn hardness_ignores_the_block_state() {
+        let hardness = BlockHardness::default();
+
+        assert_eq!(hardness.hardness(STONE), 1.5);
+        assert_eq!(hardness.hardness(pack_block(STONE, 0b101)), 1.5);
+    }
+
+    #[test]
+    fn unknown_blocks_take_the_default_time() {
+        assert_eq!(BlockHardness::default().hardness(200), DEFAULT_HARDNESS);
+    }
+}
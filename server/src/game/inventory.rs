@@ -0,0 +1,177 @@
+use bevy::ecs::prelude::Component;
+use bevy::log::warn;
+use serde::{Deserialize, Serialize};
+
+/// How many slots a players inventory has, matching the clients hotbar
+pub const INVENTORY_SLOTS: usize = 10;
+
+/// The most items that fit in a single slot
+pub const MAX_STACK_SIZE: u32 = 64;
+
+/// The blocks that have an item, matching the clients item states
+pub const ITEM_BLOCKS: [u32; 5] = [2, 1, 3, 5, 4];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    /// The block id of the item
+    pub item: u32,
+    pub amount: u32,
+}
+
+/// The items a player holds, the server's copy is the one that counts
+#[derive(Component, Debug)]
+pub struct Inventory {
+    pub slots: [Option<ItemStack>; INVENTORY_SLOTS],
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Inventory {
+            slots: [None; INVENTORY_SLOTS],
+        }
+    }
+}
+
+impl Inventory {
+    /// The blocks players start with, the same ones the client fills its hotbar with
+    pub fn starter() -> Inventory {
+        let mut inventory = Inventory::default();
+        for (slot, item) in ITEM_BLOCKS.into_iter().enumerate() {
+            inventory.slots[slot] = Some(ItemStack { item, amount: 1 });
+        }
+        inventory
+    }
+
+    /// Rebuilds a saved inventory. Items that no longer exist are dropped, and extra slots are ignored
+    pub fn restore(slots: &[Option<ItemStack>]) -> Inventory {
+        let mut inventory = Inventory::default();
+        for (slot, stack) in slots.iter().take(INVENTORY_SLOTS).enumerate() {
+            inventory.slots[slot] = match stack {
+                Some(stack) if !ITEM_BLOCKS.contains(&stack.item) => {
+                    warn!("Dropping unknown item {} from saved inventory", stack.item);
+                    None
+                }
+                Some(stack) if stack.amount > 0 => Some(ItemStack {
+                    item: stack.item,
+                    amount: stack.amount.min(MAX_STACK_SIZE),
+                }),
+                _ => None,
+            };
+        }
+        inventory
+    }
+
+    /// Adds items, topping up existing stacks of the item before using empty slots.
+    /// Returns how many items didn't fit, and the slots that changed
+    pub fn add_item(&mut self, item: u32, amount: u32) -> (u32, Vec<usize>) {
+        let mut remaining = amount;
+        let mut changed = Vec::new();
+
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if remaining == 0 {
+                break;
+            }
+            if let Some(stack) = slot {
+                if stack.item == item && stack.amount < MAX_STACK_SIZE {
+                    let added = remaining.min(MAX_STACK_SIZE - stack.amount);
+                    stack.amount += added;
+                    remaining -= added;
+                    changed.push(i);
+                }
+            }
+        }
+
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if remaining == 0 {
+                break;
+            }
+            if slot.is_none() {
+                let added = remaining.min(MAX_STACK_SIZE);
+                *slot = Some(ItemStack {
+                    item,
+                    amount: added,
+                });
+                remaining -= added;
+                changed.push(i);
+            }
+        }
+
+        (remaining, changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRT: u32 = 1;
+    const STONE: u32 = 6;
+
+    fn stack(item: u32, amount: u32) -> Option<ItemStack> {
+        Some(ItemStack { item, amount })
+    }
+
+    #[test]
+    fn pickup_tops_up_a_matching_stack() {
+        let mut inventory = Inventory::default();
+        inventory.slots[0] = stack(DIRT, 10);
+        inventory.slots[3] = stack(STONE, 5);
+
+        assert_eq!(inventory.add_item(STONE, 20), (0, vec![3]));
+        assert_eq!(inventory.slots[3], stack(STONE, 25));
+        assert_eq!(inventory.slots[0], stack(DIRT, 10));
+    }
+
+    #[test]
+    fn overflow_moves_into_empty_slots() {
+        let mut inventory = Inventory::default();
+        inventory.slots[2] = stack(STONE, MAX_STACK_SIZE - 4);
+
+        // Fills the stack, then the first empty slot
+        assert_eq!(inventory.add_item(STONE, 10), (0, vec![2, 0]));
+        assert_eq!(inventory.slots[2], stack(STONE, MAX_STACK_SIZE));
+        assert_eq!(inventory.slots[0], stack(STONE, 6));
+    }
+
+    #[test]
+    fn items_that_dont_fit_are_left_over() {
+        let mut inventory = Inventory::default();
+        inventory.slots = [stack(DIRT, MAX_STACK_SIZE); INVENTORY_SLOTS];
+
+        assert_eq!(inventory.add_item(STONE, 3), (3, Vec::new()));
+        assert_eq!(inventory.add_item(DIRT, 3), (3, Vec::new()));
+
+        // One free space takes what it can
+        inventory.slots[5] = stack(DIRT, MAX_STACK_SIZE - 1);
+        assert_eq!(inventory.add_item(DIRT, 3), (2, vec![5]));
+    }
+
+    #[test]
+    fn starter_inventory_survives_a_restore() {
+        assert_eq!(
+            Inventory::restore(&Inventory::starter().slots).slots,
+            Inventory::starter().slots
+        );
+    }
+
+    #[test]
+    fn unknown_items_are_dropped_on_restore() {
+        let inventory = Inventory::restore(&[stack(STONE, 5), stack(DIRT, 5), stack(999, 1)]);
+
+        assert_eq!(inventory.slots[0], None);
+        assert_eq!(inventory.slots[1], stack(DIRT, 5));
+        assert_eq!(inventory.slots[2], None);
+    }
+
+    #[test]
+    fn restored_stacks_are_kept_within_limits() {
+        let mut slots = vec![stack(DIRT, 0), stack(DIRT, MAX_STACK_SIZE + 10)];
+        slots.resize(INVENTORY_SLOTS + 3, stack(DIRT, 1));
+
+        let inventory = Inventory::restore(&slots);
+
+        assert_eq!(inventory.slots[0], None);
+        assert_eq!(inventory.slots[1], stack(DIRT, MAX_STACK_SIZE));
+        assert_eq!(inventory.slots[INVENTORY_SLOTS - 1], stack(DIRT, 1));
+    }
+}
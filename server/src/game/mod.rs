@@ -1,5 +1,9 @@
 pub mod chunk;
+pub mod collision;
+pub mod drops;
 pub mod entity;
+pub mod hardness;
+pub mod inventory;
 pub mod player;
 pub mod transform;
 pub mod world;
@@ -0,0 +1,237 @@
+use crate::error::ServerError;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Starts every chunk file written with a codec, followed by a byte saying which codec.
+/// Files saved before chunks were compressed start straight away with their JSON
+const CHUNK_MAGIC: [u8; 4] = *b"RCCK";
+
+/// How chunk files are compressed on disk
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChunkCompression {
+    /// Plain JSON, for reading saves by hand
+    None,
+    Gzip,
+    /// Smaller and faster than gzip
+    Zstd,
+}
+
+impl ChunkCompression {
+    fn to_byte(self) -> u8 {
+        match self {
+            ChunkCompression::None => 0,
+            ChunkCompression::Gzip => 1,
+            ChunkCompression::Zstd => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<ChunkCompression> {
+        [
+            ChunkCompression::None,
+            ChunkCompression::Gzip,
+            ChunkCompression::Zstd,
+        ]
+        .into_iter()
+        .find(|v| v.to_byte() == byte)
+    }
+}
+
+/// The codec and level new chunk files are written with. Files are read with whatever codec their header names,
+/// so changing this doesn't stop older saves loading
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChunkEncoding {
+    pub compression: ChunkCompression,
+    /// Clamped to the levels the codec supports, 0 to 9 for gzip and 1 to 22 for zstd
+    pub level: i32,
+}
+
+/// Puts a saved chunk behind a header naming its codec, compressing it with that codec
+pub fn encode_chunk(bytes: &[u8], encoding: ChunkEncoding) -> Result<Vec<u8>, ServerError> {
+    let mut file = CHUNK_MAGIC.to_vec();
+    file.push(encoding.compression.to_byte());
+
+    match encoding.compression {
+        ChunkCompression::None => file.extend_from_slice(bytes),
+        ChunkCompression::Gzip => {
+            let level = flate2::Compression::new(encoding.level.clamp(0, 9) as u32);
+            let mut encoder = GzEncoder::new(file, level);
+            encoder.write_all(bytes)?;
+            file = encoder.finish()?;
+        }
+        ChunkCompression::Zstd => {
+            let mut encoder = zstd::Encoder::new(file, encoding.level.clamp(1, 22))?;
+            encoder.write_all(bytes)?;
+            file = encoder.finish()?;
+        }
+    }
+
+    Ok(file)
+}
+
+/// Reads a saved chunk back out of a chunk file, using the codec in its header. Files without a header are from
+/// before compression and are read as they are
+pub fn decode_chunk(file: &[u8]) -> Result<Vec<u8>, ServerError> {
+    let body = match file.strip_prefix(&CHUNK_MAGIC) {
+        Some(val) => val,
+        None => return Ok(file.to_vec()),
+    };
+
+    let (codec, body) = body.split_first().ok_or(ServerError::InvalidHeader {
+        expected: "a chunk codec".to_string(),
+        found: "the end of the file".to_string(),
+    })?;
+
+    let mut bytes = Vec::new();
+    match ChunkCompression::from_byte(*codec) {
+        Some(ChunkCompression::None) => bytes.extend_from_slice(body),
+        Some(ChunkCompression::Gzip) => {
+            GzDecoder::new(body).read_to_end(&mut bytes)?;
+        }
+        Some(ChunkCompression::Zstd) => {
+            zstd::Decoder::new(body)?.read_to_end(&mut bytes)?;
+        }
+        // Most likely written by a newer server
+        None => {
+            return Err(ServerError::InvalidHeader {
+                expected: "a chunk codec".to_string(),
+                found: format!("codec {}", codec),
+            })
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::chunk::{ChunkData, GenerationMode};
+    use crate::game::world::saved_chunk::SavedChunk;
+    use nalgebra::Vector3;
+    use rc_networking::constants::CHUNK_SIZE;
+
+    const CODECS: [ChunkCompression; 3] = [
+        ChunkCompression::None,
+        ChunkCompression::Gzip,
+        ChunkCompression::Zstd,
+    ];
+
+    /// A saved chunk as it's written before compression
+    fn saved_chunk() -> (ChunkData, Vec<u8>) {
+        let mut chunk = ChunkData::generate(
+            Vector3::new(1, 3, -2),
+            CHUNK_SIZE,
+            4321,
+            GenerationMode::Normal,
+        );
+        for (x, y, z) in [(4, 5, 6), (15, 0, 9)] {
+            let block = chunk.world.get(x, y, z);
+            chunk.world.set(x, y, z, block + 100);
+        }
+
+        let saved = SavedChunk::new(&chunk, 4321, GenerationMode::Normal);
+        (chunk, serde_json::to_vec(&saved).unwrap())
+    }
+
+    fn encoding(compression: ChunkCompression, level: i32) -> ChunkEncoding {
+        ChunkEncoding { compression, level }
+    }
+
+    #[test]
+    fn chunks_round_trip_through_each_codec() {
+        let (chunk, bytes) = saved_chunk();
+
+        for codec in CODECS {
+            let file = encode_chunk(&bytes, encoding(codec, 6)).unwrap();
+            let decoded = decode_chunk(&file).unwrap();
+            assert_eq!(decoded, bytes, "{:?}", codec);
+
+            let saved: SavedChunk = serde_json::from_slice(&decoded).unwrap();
+            let loaded = saved.into_chunk();
+            assert_eq!(loaded.position, chunk.position);
+            assert!(loaded.world == chunk.world);
+        }
+    }
+
+    #[test]
+    fn header_names_the_codec() {
+        let (_, bytes) = saved_chunk();
+
+        for codec in CODECS {
+            let file = encode_chunk(&bytes, encoding(codec, 6)).unwrap();
+            assert_eq!(file[..4], CHUNK_MAGIC);
+            assert_eq!(ChunkCompression::from_byte(file[4]), Some(codec));
+        }
+
+        // Stored as it is without compression
+        let file = encode_chunk(&bytes, encoding(ChunkCompression::None, 6)).unwrap();
+        assert_eq!(file[5..], bytes[..]);
+    }
+
+    #[test]
+    fn decoding_follows_the_header_not_the_setting() {
+        let (_, bytes) = saved_chunk();
+
+        // Whichever codec is set now, files written with the others still load
+        let files: Vec<_> = CODECS
+            .into_iter()
+            .map(|codec| encode_chunk(&bytes, encoding(codec, 6)).unwrap())
+            .collect();
+        assert_ne!(files[1], files[2]);
+        for file in files {
+            assert_eq!(decode_chunk(&file).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn files_without_a_header_are_read_as_they_are() {
+        let (_, bytes) = saved_chunk();
+        assert_eq!(decode_chunk(&bytes).unwrap(), bytes);
+    }
+
+    #[test]
+    fn unknown_codecs_are_an_invalid_header() {
+        let mut file = CHUNK_MAGIC.to_vec();
+        file.push(200);
+        file.extend_from_slice(b"{}");
+        assert!(matches!(
+            decode_chunk(&file),
+            Err(ServerError::InvalidHeader { .. })
+        ));
+
+        // Cut off before the codec
+        assert!(matches!(
+            decode_chunk(&CHUNK_MAGIC),
+            Err(ServerError::InvalidHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn levels_are_clamped_to_the_codec() {
+        let (_, bytes) = saved_chunk();
+
+        for codec in [ChunkCompression::Gzip, ChunkCompression::Zstd] {
+            for level in [-10, 0, 100] {
+                let file = encode_chunk(&bytes, encoding(codec, level)).unwrap();
+                assert_eq!(decode_chunk(&file).unwrap(), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn compression_shrinks_whole_chunks() {
+        let data = serde_json::to_vec(&SavedChunk::Full(ChunkData::blank(
+            Vector3::new(0, 0, 0),
+            CHUNK_SIZE,
+        )))
+        .unwrap();
+        let plain = encode_chunk(&data, encoding(ChunkCompression::None, 0)).unwrap();
+
+        for codec in [ChunkCompression::Gzip, ChunkCompression::Zstd] {
+            let file = encode_chunk(&data, encoding(codec, 6)).unwrap();
+            assert!(file.len() < plain.len(), "{:?}", codec);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::TransportSystem;
+use bevy::prelude::*;
+use rc_networking::protocol::clientbound::time_sync::TimeSync;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+
+/// How often, in seconds, clients are sent the time to correct their clocks
+const TIME_SYNC_INTERVAL: f32 = 5.0;
+
+/// The time the world has been running, which the day cycle and animations follow
+#[derive(Resource, Default)]
+pub struct WorldClock {
+    pub seconds: f32,
+    since_sync: f32,
+}
+
+pub fn advance_world_clock(
+    mut clock: ResMut<WorldClock>,
+    time: Res<Time>,
+    transport: Res<TransportSystem>,
+    mut send_packets: EventWriter<SendPacket>,
+) {
+    clock.seconds += time.delta_seconds();
+    clock.since_sync += time.delta_seconds();
+
+    if clock.since_sync < TIME_SYNC_INTERVAL {
+        return;
+    }
+    clock.since_sync = 0.0;
+
+    for user in transport.clients.keys() {
+        send_packets.send(SendPacket(
+            Protocol::TimeSync(TimeSync::new(clock.seconds)),
+            *user,
+        ));
+    }
+}
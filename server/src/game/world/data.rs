@@ -1,27 +1,39 @@
-use crate::game::chunk::ChunkData;
+use crate::game::chunk::{ChunkData, GenerationMode};
+use crate::game::world::chunk_codec::decode_chunk;
+use crate::game::world::saved_chunk::SavedChunk;
 
 use crate::error::ServerError;
+use crate::helpers::global_to_local_position;
 use bevy::ecs::entity::Entity;
 use bevy::ecs::prelude::Resource;
 use bevy::log::error;
 use nalgebra::Vector3;
 use rc_networking::constants::EntityId;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::fs::File;
-use std::io::BufReader;
 use std::sync::atomic::AtomicU64;
 
-pub const ENTITY_ID_COUNT: AtomicU64 = AtomicU64::new(0);
+pub static ENTITY_ID_COUNT: AtomicU64 = AtomicU64::new(0);
 
 #[derive(Resource)]
 pub struct WorldData {
     pub chunks: HashMap<Vector3<i32>, ChunkData>,
     pub entities: HashMap<EntityId, Entity>,
+    /// Chunks that have been edited since they were last saved
+    pub modified_chunks: HashSet<Vector3<i32>>,
+    /// How many blocks along each side chunks are
+    pub chunk_size: usize,
+    /// The seed new chunks are generated from
+    pub seed: u64,
+    pub generation_mode: GenerationMode,
 }
 
 impl WorldData {
-    pub fn load_spawn_chunks() -> Self {
+    pub fn load_spawn_chunks(
+        chunk_size: usize,
+        seed: u64,
+        generation_mode: GenerationMode,
+    ) -> Self {
         let mut chunks = HashMap::new();
 
         // Load spawn area
@@ -30,16 +42,10 @@ impl WorldData {
                 for z in -2..=2 {
                     let pos = Vector3::new(x, y, z);
 
-                    let chunk = match Self::try_load_chunk(pos) {
-                        Ok(Some(chunk)) => chunk,
-                        Ok(None) => ChunkData::generate(pos),
-                        Err(err) => {
-                            error!("Error reading chunk data: {:?}", err);
-                            ChunkData::generate(pos)
-                        }
-                    };
-
-                    chunks.insert(Vector3::new(x, y, z), chunk);
+                    chunks.insert(
+                        pos,
+                        Self::load_or_generate_chunk(pos, chunk_size, seed, generation_mode),
+                    );
                 }
             }
         }
@@ -47,6 +53,46 @@ impl WorldData {
         WorldData {
             chunks,
             entities: Default::default(),
+            modified_chunks: Default::default(),
+            chunk_size,
+            seed,
+            generation_mode,
+        }
+    }
+
+    /// Gets the block at a global position, or None if its chunk isn't loaded
+    pub fn get_block(&self, position: Vector3<i32>) -> Option<u32> {
+        let (chunk_loc, inner_loc) = global_to_local_position(position, self.chunk_size);
+
+        self.chunks
+            .get(&chunk_loc)
+            .map(|chunk| chunk.world.get(inner_loc.x, inner_loc.y, inner_loc.z))
+    }
+
+    /// Loads a chunk that was saved to disk, only generating it if it's never been saved or was saved at
+    /// another size
+    pub fn load_or_generate_chunk(
+        location: Vector3<i32>,
+        size: usize,
+        seed: u64,
+        mode: GenerationMode,
+    ) -> ChunkData {
+        match Self::try_load_chunk(location) {
+            Ok(Some(chunk)) if chunk.world.size() == size && chunk.world.is_valid() => chunk,
+            Ok(Some(chunk)) => {
+                error!(
+                    "Chunk {:?} was saved with a size of {}, not {}",
+                    location,
+                    chunk.world.size(),
+                    size
+                );
+                ChunkData::generate(location, size, seed, mode)
+            }
+            Ok(None) => ChunkData::generate(location, size, seed, mode),
+            Err(err) => {
+                error!("Error reading chunk data: {:?}", err);
+                ChunkData::generate(location, size, seed, mode)
+            }
         }
     }
 
@@ -59,11 +105,9 @@ impl WorldData {
             return Ok(None);
         }
 
-        let file = File::open(&path)?;
-        let mut reader = BufReader::new(file);
-
-        let chunk: ChunkData = serde_json::from_reader(&mut reader)?;
+        let bytes = decode_chunk(&fs::read(&path)?)?;
+        let chunk: SavedChunk = serde_json::from_slice(&bytes)?;
 
-        Ok(Some(chunk))
+        Ok(Some(chunk.into_chunk()))
     }
 }
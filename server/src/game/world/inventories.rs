@@ -0,0 +1,202 @@
+use crate::error::ServerError;
+use crate::game::inventory::{Inventory, ItemStack};
+use crate::{ServerConfig, TransportSystem, WorldData};
+use bevy::log::error;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+const INVENTORIES_PATH: &str = "./world/inventories.json";
+
+/// The inventories players had when last seen, by player name, so they get them back when they rejoin
+#[derive(Resource, Default, Debug, Clone)]
+pub struct SavedInventories {
+    pub inventories: HashMap<String, Vec<Option<ItemStack>>>,
+    /// Whether an inventory has changed since they were last saved
+    pub modified: bool,
+}
+
+impl SavedInventories {
+    pub fn load() -> SavedInventories {
+        Self::load_from(Path::new(INVENTORIES_PATH))
+    }
+
+    pub fn load_from(path: &Path) -> SavedInventories {
+        match Self::try_load(path) {
+            Ok(inventories) => SavedInventories {
+                inventories,
+                modified: false,
+            },
+            Err(err) => {
+                error!("Error reading inventories: {:?}", err);
+                SavedInventories::default()
+            }
+        }
+    }
+
+    fn try_load(path: &Path) -> Result<HashMap<String, Vec<Option<ItemStack>>>, ServerError> {
+        if !fs::try_exists(path)? {
+            return Ok(HashMap::new());
+        }
+
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn set(&mut self, player: &str, slots: &[Option<ItemStack>]) {
+        self.inventories.insert(player.to_string(), slots.to_vec());
+        self.modified = true;
+    }
+
+    pub fn get(&self, player: &str) -> Option<&[Option<ItemStack>]> {
+        self.inventories.get(player).map(|v| v.as_slice())
+    }
+
+    /// Copies out the inventories if they need saving
+    pub fn snapshot_modified(&mut self) -> Option<HashMap<String, Vec<Option<ItemStack>>>> {
+        if !self.modified {
+            return None;
+        }
+        self.modified = false;
+        Some(self.inventories.clone())
+    }
+}
+
+pub fn write_inventories(inventories: &HashMap<String, Vec<Option<ItemStack>>>) {
+    write_inventories_to(Path::new(INVENTORIES_PATH), inventories);
+}
+
+pub fn write_inventories_to(path: &Path, inventories: &HashMap<String, Vec<Option<ItemStack>>>) {
+    let result = fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))
+        .map_err(ServerError::from)
+        .and_then(|_| Ok(File::create(path)?))
+        .and_then(|file| Ok(serde_json::to_writer(BufWriter::new(file), inventories)?));
+
+    if let Err(err) = result {
+        error!("Failed to save inventories: {:?}", err);
+    }
+}
+
+/// Keeps a copy of each connected player's inventory whenever it changes, so it's saved with the world
+pub fn store_inventories(
+    mut saved: ResMut<SavedInventories>,
+    transport: Res<TransportSystem>,
+    world: Res<WorldData>,
+    inventories: Query<(&Inventory, ChangeTrackers<Inventory>)>,
+    config: Res<ServerConfig>,
+) {
+    if !config.save_inventories {
+        return;
+    }
+
+    for user in transport.clients.values() {
+        let name = match user.save_name() {
+            Some(val) => val,
+            None => continue,
+        };
+
+        if let Some((inventory, tracker)) = world
+            .entities
+            .get(&user.entity_id)
+            .and_then(|v| inventories.get(*v).ok())
+        {
+            if tracker.is_changed() {
+                saved.set(name, &inventory.slots);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::inventory::INVENTORY_SLOTS;
+    use crate::systems::authorization::testing;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::UserId;
+
+    #[test]
+    fn inventories_save_and_restore() {
+        let directory =
+            std::env::temp_dir().join(format!("rustcraft-inventories-{}", std::process::id()));
+        let path = directory.join("inventories.json");
+        let _ = fs::remove_dir_all(&directory);
+
+        let mut inventory = Inventory::starter();
+        inventory.slots[0] = Some(ItemStack {
+            item: 2,
+            amount: 40,
+        });
+        inventory.slots[INVENTORY_SLOTS - 1] = Some(ItemStack { item: 4, amount: 7 });
+
+        let mut saved = SavedInventories::default();
+        saved.set("alice", &inventory.slots);
+        write_inventories_to(&path, &saved.snapshot_modified().unwrap());
+
+        let loaded = SavedInventories::load_from(&path);
+        let _ = fs::remove_dir_all(&directory);
+
+        let restored = Inventory::restore(loaded.get("alice").unwrap());
+        assert_eq!(restored.slots, inventory.slots);
+        assert!(loaded.get("bob").is_none());
+    }
+
+    #[test]
+    fn missing_file_has_no_inventories() {
+        let path = std::env::temp_dir().join("rustcraft-inventories-missing.json");
+
+        assert!(SavedInventories::load_from(&path).inventories.is_empty());
+    }
+
+    fn inventory(world: &World, id: u64) -> [Option<ItemStack>; INVENTORY_SLOTS] {
+        let entity_id = world.resource::<TransportSystem>().clients[&UserId(id)].entity_id;
+        let entity = world.resource::<WorldData>().entities[&entity_id];
+        world.get::<Inventory>(entity).unwrap().slots
+    }
+
+    #[test]
+    fn each_player_gets_back_their_own_inventory() {
+        let alice = [Some(ItemStack {
+            item: 2,
+            amount: 40,
+        })];
+        let bob = [Some(ItemStack { item: 4, amount: 7 })];
+
+        let mut world = testing::world(ServerConfig::default(), &[1, 2, 3]);
+        {
+            let mut saved = world.resource_mut::<SavedInventories>();
+            saved.set("alice", &alice);
+            saved.set("bob", &bob);
+        }
+
+        testing::authorize(&mut world, 1, Some("alice"));
+        testing::authorize(&mut world, 2, Some("bob"));
+        testing::authorize(&mut world, 3, None);
+
+        assert_eq!(inventory(&world, 1), Inventory::restore(&alice).slots);
+        assert_eq!(inventory(&world, 2), Inventory::restore(&bob).slots);
+        // Players without a name start afresh rather than loading anyone's
+        assert_eq!(inventory(&world, 3), Inventory::starter().slots);
+    }
+
+    #[test]
+    fn only_named_players_are_saved() {
+        let mut world = testing::world(ServerConfig::default(), &[1, 2]);
+        testing::authorize(&mut world, 1, Some("alice"));
+        testing::authorize(&mut world, 2, None);
+
+        SystemStage::single_threaded()
+            .with_system(store_inventories)
+            .run(&mut world);
+
+        let saved = world.resource::<SavedInventories>();
+        assert_eq!(saved.inventories.len(), 1);
+        assert_eq!(
+            saved.get("alice").unwrap(),
+            Inventory::starter().slots.as_slice()
+        );
+    }
+}
@@ -1,43 +1,406 @@
+use crate::error::ServerError;
+use crate::events::console::ConsoleCommandEvent;
+use crate::game::chunk::{ChunkData, GenerationMode};
+use crate::game::inventory::ItemStack;
+use crate::game::world::chunk_codec::{encode_chunk, ChunkEncoding};
+use crate::game::world::clock::{advance_world_clock, WorldClock};
 use crate::game::world::data::WorldData;
+use crate::game::world::inventories::{store_inventories, write_inventories, SavedInventories};
+use crate::game::world::saved_chunk::SavedChunk;
+use crate::game::world::simulation::{update_simulated_area, SimulatedArea};
+use crate::game::world::spawns::{write_spawn_points, SpawnPoints};
 use crate::{App, AppExit, ServerConfig};
 use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+use nalgebra::Vector3;
+use std::collections::HashMap;
 use std::fs;
-use std::fs::File;
-use std::io::BufWriter;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+pub mod chunk_codec;
+pub mod clock;
 pub mod data;
+pub mod inventories;
+pub mod rng;
+pub mod saved_chunk;
+pub mod simulation;
+pub mod spawns;
 
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(save_world);
+        let interval = app
+            .world
+            .get_resource::<ServerConfig>()
+            .map_or(0, |v| v.autosave_interval);
+
+        app.insert_resource(AutosaveTimer::new(interval))
+            .insert_resource(WorldClock::default())
+            .insert_resource(SpawnPoints::load())
+            .insert_resource(SavedInventories::load())
+            .insert_resource(SimulatedArea::default())
+            .insert_resource(FailedSaves::default())
+            .add_system(update_simulated_area)
+            .add_system(store_inventories)
+            .add_system(advance_world_clock)
+            .add_system(retry_failed_saves)
+            .add_system(autosave_world)
+            .add_system(save_command)
+            .add_system(save_world);
+    }
+}
+
+/// Counts down to the next autosave, a zero interval disables autosaving
+#[derive(Resource)]
+pub struct AutosaveTimer(Option<Timer>);
+
+impl AutosaveTimer {
+    pub fn new(seconds: u64) -> AutosaveTimer {
+        if seconds == 0 {
+            return AutosaveTimer(None);
+        }
+        AutosaveTimer(Some(Timer::new(
+            Duration::from_secs(seconds),
+            TimerMode::Repeating,
+        )))
+    }
+
+    /// Advances the timer, returning true when a save is due
+    pub fn tick(&mut self, delta: Duration) -> bool {
+        match &mut self.0 {
+            Some(timer) => timer.tick(delta).just_finished(),
+            None => false,
+        }
+    }
+}
+
+/// Chunks a background save couldn't write. They're marked as modified again so the next save tries them again
+#[derive(Resource)]
+pub struct FailedSaves {
+    sender: Mutex<Sender<Vec<Vector3<i32>>>>,
+    receiver: Mutex<Receiver<Vec<Vector3<i32>>>>,
+}
+
+impl Default for FailedSaves {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+
+        FailedSaves {
+            sender: Mutex::new(sender),
+            receiver: Mutex::new(receiver),
+        }
+    }
+}
+
+fn retry_failed_saves(failed: Res<FailedSaves>, mut world: ResMut<WorldData>) {
+    for chunks in failed.receiver.lock().unwrap().try_iter() {
+        world.modified_chunks.extend(chunks);
+    }
+}
+
+/// Copies out every modified chunk so it can be written without holding onto the world
+fn snapshot_modified(world: &mut WorldData) -> Vec<ChunkData> {
+    world
+        .modified_chunks
+        .drain()
+        .filter_map(|pos| world.chunks.get(&pos).cloned())
+        .collect()
+}
+
+/// Writes chunks as the blocks changed from what the seed generates, so the diffing and compression happen off the tick loop.
+/// Returns the chunks that couldn't be written
+fn write_chunks(
+    chunks: Vec<ChunkData>,
+    seed: u64,
+    generation_mode: GenerationMode,
+    encoding: ChunkEncoding,
+) -> Vec<Vector3<i32>> {
+    write_chunks_to(
+        Path::new("./world/"),
+        chunks,
+        seed,
+        generation_mode,
+        encoding,
+    )
+}
+
+fn write_chunks_to(
+    directory: &Path,
+    chunks: Vec<ChunkData>,
+    seed: u64,
+    generation_mode: GenerationMode,
+    encoding: ChunkEncoding,
+) -> Vec<Vector3<i32>> {
+    let start = Instant::now();
+
+    if let Err(err) = fs::create_dir_all(directory) {
+        error!("Failed to create world directory: {:?}", err);
+        return chunks.iter().map(|v| v.position).collect();
+    }
+
+    let mut written = 0;
+    let mut failed = Vec::new();
+    for chunk in &chunks {
+        let pos = chunk.position;
+        let saved = SavedChunk::new(chunk, seed, generation_mode);
+
+        let file = serde_json::to_vec(&saved)
+            .map_err(ServerError::from)
+            .and_then(|v| encode_chunk(&v, encoding))
+            .and_then(|v| {
+                fs::write(
+                    directory.join(format!("{:08x}{:08x}{:08x}.chunk", pos.x, pos.y, pos.z)),
+                    v,
+                )
+                .map_err(ServerError::from)
+            });
+
+        match file {
+            Ok(_) => written += 1,
+            Err(err) => {
+                error!("Failed to save chunk {:?}: {:?}", pos, err);
+                failed.push(pos);
+            }
+        }
+    }
+
+    info!("Saved {} chunks in {:?}", written, start.elapsed());
+    failed
+}
+
+fn write_world(
+    chunks: Vec<ChunkData>,
+    seed: u64,
+    generation_mode: GenerationMode,
+    encoding: ChunkEncoding,
+    spawns: Option<HashMap<String, [f32; 3]>>,
+    inventories: Option<HashMap<String, Vec<Option<ItemStack>>>>,
+) -> Vec<Vector3<i32>> {
+    let failed = write_chunks(chunks, seed, generation_mode, encoding);
+    if let Some(spawns) = spawns {
+        write_spawn_points(&spawns);
+    }
+    if let Some(inventories) = inventories {
+        write_inventories(&inventories);
+    }
+    failed
+}
+
+/// Saves the world on a background task so the tick loop isn't held up
+fn save_in_background(
+    world: &mut WorldData,
+    spawns: &mut SpawnPoints,
+    inventories: &mut SavedInventories,
+    failed: &FailedSaves,
+    encoding: ChunkEncoding,
+) {
+    let chunks = snapshot_modified(world);
+    let (seed, generation_mode) = (world.seed, world.generation_mode);
+    let spawns = spawns.snapshot_modified();
+    let inventories = inventories.snapshot_modified();
+    let sender = failed.sender.lock().unwrap().clone();
+
+    IoTaskPool::get()
+        .spawn(async move {
+            let failed = write_world(chunks, seed, generation_mode, encoding, spawns, inventories);
+            if !failed.is_empty() {
+                let _ = sender.send(failed);
+            }
+        })
+        .detach();
+}
+
+fn autosave_world(
+    mut world: ResMut<WorldData>,
+    mut spawns: ResMut<SpawnPoints>,
+    mut inventories: ResMut<SavedInventories>,
+    mut timer: ResMut<AutosaveTimer>,
+    failed: Res<FailedSaves>,
+    config: Res<ServerConfig>,
+    time: Res<Time>,
+) {
+    if !timer.tick(time.delta()) || !config.save_world {
+        return;
+    }
+
+    info!("Autosaving world...");
+    save_in_background(
+        &mut world,
+        &mut spawns,
+        &mut inventories,
+        &failed,
+        config.chunk_encoding(),
+    );
+}
+
+fn save_command(
+    mut world: ResMut<WorldData>,
+    mut spawns: ResMut<SpawnPoints>,
+    mut inventories: ResMut<SavedInventories>,
+    mut timer: ResMut<AutosaveTimer>,
+    failed: Res<FailedSaves>,
+    config: Res<ServerConfig>,
+    mut commands: EventReader<ConsoleCommandEvent>,
+) {
+    for command in commands.iter() {
+        if command.command != "save" {
+            continue;
+        }
+
+        // The world is meant to be thrown away, so a save asked for by hand is refused rather than overwriting it
+        if !config.save_world {
+            warn!("Not saving, as save_world is turned off in the server settings");
+            continue;
+        }
+
+        info!("Saving world...");
+        save_in_background(
+            &mut world,
+            &mut spawns,
+            &mut inventories,
+            &failed,
+            config.chunk_encoding(),
+        );
+
+        // Push back the next autosave as the world was just saved
+        if let Some(timer) = &mut timer.0 {
+            timer.reset();
+        }
     }
 }
 
 fn save_world(
-    mut world: Res<WorldData>,
+    mut world: ResMut<WorldData>,
+    mut spawns: ResMut<SpawnPoints>,
+    mut inventories: ResMut<SavedInventories>,
     config: Res<ServerConfig>,
     mut bevy_shutdown: EventReader<AppExit>,
 ) {
     if bevy_shutdown.is_empty() || !config.save_world {
         return;
     }
+    bevy_shutdown.clear();
 
+    // Save on this thread, as the server is about to exit a background task may not finish
     info!("Saving world...");
-    fs::create_dir_all("./world/").unwrap();
+    let chunks = snapshot_modified(&mut world);
+    let failed = write_world(
+        chunks,
+        world.seed,
+        world.generation_mode,
+        config.chunk_encoding(),
+        spawns.snapshot_modified(),
+        inventories.snapshot_modified(),
+    );
+    world.modified_chunks.extend(failed);
+}
 
-    for (pos, chunk) in &world.chunks {
-        let file = File::create(format!(
-            "./world/{:08x}{:08x}{:08x}.chunk",
-            pos.x, pos.y, pos.z
-        ))
-        .unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::CHUNK_SIZE;
+    use std::collections::HashSet;
 
-        let mut writer = BufWriter::new(file);
+    fn chunks() -> Vec<ChunkData> {
+        (0..3)
+            .map(|x| {
+                ChunkData::generate(
+                    Vector3::new(x, 2, 0),
+                    CHUNK_SIZE,
+                    0,
+                    GenerationMode::Heightmap,
+                )
+            })
+            .collect()
+    }
 
-        serde_json::to_writer(&mut writer, chunk).unwrap();
+    #[test]
+    fn zero_interval_never_autosaves() {
+        let mut timer = AutosaveTimer::new(0);
+        assert!(!timer.tick(Duration::from_secs(1_000_000)));
     }
 
-    info!("Saved world.");
+    #[test]
+    fn autosave_is_due_every_interval() {
+        let mut timer = AutosaveTimer::new(300);
+
+        assert!(!timer.tick(Duration::from_secs(299)));
+        assert!(timer.tick(Duration::from_secs(1)));
+        assert!(!timer.tick(Duration::from_secs(150)));
+        assert!(timer.tick(Duration::from_secs(150)));
+    }
+
+    #[test]
+    fn resetting_pushes_back_the_next_autosave() {
+        let mut timer = AutosaveTimer::new(300);
+        assert!(!timer.tick(Duration::from_secs(200)));
+
+        // As the save command does after saving
+        if let Some(timer) = &mut timer.0 {
+            timer.reset();
+        }
+
+        assert!(!timer.tick(Duration::from_secs(200)));
+        assert!(timer.tick(Duration::from_secs(100)));
+    }
+
+    #[test]
+    fn written_chunks_are_not_retried() {
+        let directory =
+            std::env::temp_dir().join(format!("rustcraft-world-{}", std::process::id()));
+        let encoding = ServerConfig::default().chunk_encoding();
+
+        let failed = write_chunks_to(&directory, chunks(), 0, GenerationMode::Heightmap, encoding);
+        let saved = fs::read_dir(&directory).map_or(0, |v| v.count());
+        let _ = fs::remove_dir_all(&directory);
+
+        assert!(failed.is_empty());
+        assert_eq!(saved, 3);
+    }
+
+    #[test]
+    fn chunks_that_fail_to_write_are_saved_again() {
+        // A file where the world directory should be, so nothing can be written into it
+        let directory =
+            std::env::temp_dir().join(format!("rustcraft-world-file-{}", std::process::id()));
+        fs::write(&directory, b"").unwrap();
+        let encoding = ServerConfig::default().chunk_encoding();
+
+        let failed = write_chunks_to(&directory, chunks(), 0, GenerationMode::Heightmap, encoding);
+        let _ = fs::remove_file(&directory);
+        assert_eq!(failed.len(), 3);
+
+        let mut world = World::new();
+        world.insert_resource(FailedSaves::default());
+        world.insert_resource(WorldData {
+            chunks: HashMap::new(),
+            entities: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            chunk_size: CHUNK_SIZE,
+            seed: 0,
+            generation_mode: GenerationMode::Heightmap,
+        });
+
+        // As a background save reports back
+        let sender = world
+            .resource::<FailedSaves>()
+            .sender
+            .lock()
+            .unwrap()
+            .clone();
+        sender.send(failed).unwrap();
+
+        SystemStage::single_threaded()
+            .with_system(retry_failed_saves)
+            .run(&mut world);
+
+        let modified = &world.resource::<WorldData>().modified_chunks;
+        assert_eq!(modified.len(), 3);
+        assert!(modified.contains(&Vector3::new(2, 2, 0)));
+    }
 }
@@ -0,0 +1,64 @@
+use nalgebra::Vector3;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// Mixes the bits of a value so nearby inputs give unrelated outputs (the splitmix64 finaliser)
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    value ^ (value >> 31)
+}
+
+/// A random number generator for world features in one chunk.
+/// The same seed and chunk always give the same numbers, no matter the order chunks are generated in or on what thread,
+/// so features like ores and trees come out the same every time a world is generated
+pub fn chunk_rng(seed: u64, chunk: Vector3<i32>) -> ChaCha8Rng {
+    let mut hash = mix(seed);
+    for axis in [chunk.x, chunk.y, chunk.z] {
+        hash = mix(hash ^ axis as u32 as u64);
+    }
+    ChaCha8Rng::seed_from_u64(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::rand_core::RngCore;
+
+    /// The first few numbers from a chunk's generator
+    fn sequence(seed: u64, chunk: Vector3<i32>) -> Vec<u64> {
+        let mut rng = chunk_rng(seed, chunk);
+        (0..8).map(|_| rng.next_u64()).collect()
+    }
+
+    #[test]
+    fn same_seed_and_chunk_repeat() {
+        let chunk = Vector3::new(3, -1, 7);
+        assert_eq!(sequence(42, chunk), sequence(42, chunk));
+    }
+
+    #[test]
+    fn different_chunks_differ() {
+        let first = sequence(42, Vector3::new(0, 0, 0));
+
+        // Neighbours on each axis, and the same numbers on swapped axes
+        for chunk in [
+            Vector3::new(1, 0, 0),
+            Vector3::new(0, 1, 0),
+            Vector3::new(0, 0, 1),
+            Vector3::new(-1, 0, 0),
+        ] {
+            assert_ne!(first, sequence(42, chunk), "chunk {:?}", chunk);
+        }
+        assert_ne!(
+            sequence(42, Vector3::new(1, 2, 0)),
+            sequence(42, Vector3::new(2, 1, 0))
+        );
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let chunk = Vector3::new(3, -1, 7);
+        assert_ne!(sequence(1, chunk), sequence(2, chunk));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::game::chunk::{ChunkData, GenerationMode};
+use nalgebra::Vector3;
+use rc_networking::constants::{CHUNK_SIZE, MAX_CHUNK_SIZE};
+use serde::{Deserialize, Serialize};
+
+/// Past this many changed blocks a chunk of `size` is saved whole, as the diff would be bigger than the chunk
+pub fn max_diff_blocks(size: usize) -> usize {
+    size * size * size / 4
+}
+
+/// Diffs were saved before chunks had a size, when they were all CHUNK_SIZE
+fn default_chunk_size() -> usize {
+    CHUNK_SIZE
+}
+
+/// A block that differs from what the generator makes, by its position in the chunk
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangedBlock {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    pub block: u32,
+}
+
+/// A chunk as it's written to disk. Lightly edited chunks only store the blocks changed from the generator,
+/// and are generated again from the seed when loaded
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum SavedChunk {
+    Diff {
+        position: Vector3<i32>,
+        #[serde(default = "default_chunk_size")]
+        size: usize,
+        /// What the chunk was generated from, so changing the world's seed doesn't change saved chunks
+        seed: u64,
+        generation_mode: GenerationMode,
+        changes: Vec<ChangedBlock>,
+    },
+    /// Also how chunks were saved before diffs, so those still load
+    Full(ChunkData),
+}
+
+impl SavedChunk {
+    /// Stores only the changed blocks, unless so many changed that the whole chunk is smaller
+    pub fn new(chunk: &ChunkData, seed: u64, generation_mode: GenerationMode) -> SavedChunk {
+        let size = chunk.world.size();
+        let base = ChunkData::generate(chunk.position, size, seed, generation_mode);
+        let mut changes = Vec::new();
+
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let block = chunk.world.get(x, y, z);
+                    if block == base.world.get(x, y, z) {
+                        continue;
+                    }
+
+                    if changes.len() >= max_diff_blocks(size) {
+                        return SavedChunk::Full(chunk.clone());
+                    }
+
+                    changes.push(ChangedBlock {
+                        x: x as u8,
+                        y: y as u8,
+                        z: z as u8,
+                        block,
+                    });
+                }
+            }
+        }
+
+        SavedChunk::Diff {
+            position: chunk.position,
+            size,
+            seed,
+            generation_mode,
+            changes,
+        }
+    }
+
+    pub fn into_chunk(self) -> ChunkData {
+        match self {
+            SavedChunk::Diff {
+                position,
+                size,
+                seed,
+                generation_mode,
+                changes,
+            } => {
+                // Out of range sizes would have the generator allocate without bound
+                let size = size.clamp(1, MAX_CHUNK_SIZE);
+                let mut chunk = ChunkData::generate(position, size, seed, generation_mode);
+                for change in changes {
+                    let (x, y, z) = (change.x as usize, change.y as usize, change.z as usize);
+                    if x < size && y < size && z < size {
+                        chunk.world.set(x, y, z, change.block);
+                    }
+                }
+                chunk
+            }
+            SavedChunk::Full(chunk) => chunk,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 4321;
+
+    fn generated() -> ChunkData {
+        ChunkData::generate(
+            Vector3::new(2, 3, -1),
+            CHUNK_SIZE,
+            SEED,
+            GenerationMode::Normal,
+        )
+    }
+
+    /// Changes the first `count` blocks of the chunk to something the generator never makes
+    fn modified(count: usize) -> ChunkData {
+        let mut chunk = generated();
+        for i in 0..count {
+            let (x, y, z) = (i / 256, (i / 16) % 16, i % 16);
+            let block = chunk.world.get(x, y, z);
+            chunk.world.set(x, y, z, block + 100);
+        }
+        chunk
+    }
+
+    fn diff_len(saved: &SavedChunk) -> Option<usize> {
+        match saved {
+            SavedChunk::Diff { changes, .. } => Some(changes.len()),
+            SavedChunk::Full(_) => None,
+        }
+    }
+
+    #[test]
+    fn untouched_chunks_have_an_empty_diff() {
+        let chunk = generated();
+        let saved = SavedChunk::new(&chunk, SEED, GenerationMode::Normal);
+
+        assert_eq!(diff_len(&saved), Some(0));
+        assert!(saved.into_chunk().world == chunk.world);
+    }
+
+    #[test]
+    fn applying_the_diff_reproduces_the_chunk() {
+        let mut chunk = generated();
+        for (x, y, z) in [(1, 2, 3), (15, 0, 9), (0, 15, 15)] {
+            let block = chunk.world.get(x, y, z);
+            chunk.world.set(x, y, z, block + 100);
+        }
+
+        let saved = SavedChunk::new(&chunk, SEED, GenerationMode::Normal);
+        assert_eq!(diff_len(&saved), Some(3));
+
+        let loaded = saved.into_chunk();
+        assert_eq!(loaded.position, chunk.position);
+        assert!(loaded.world == chunk.world);
+    }
+
+    #[test]
+    fn heavily_modified_chunks_are_saved_whole() {
+        let saved = SavedChunk::new(
+            &modified(max_diff_blocks(CHUNK_SIZE)),
+            SEED,
+            GenerationMode::Normal,
+        );
+        assert_eq!(diff_len(&saved), Some(max_diff_blocks(CHUNK_SIZE)));
+
+        let chunk = modified(max_diff_blocks(CHUNK_SIZE) + 1);
+        let saved = SavedChunk::new(&chunk, SEED, GenerationMode::Normal);
+        assert_eq!(diff_len(&saved), None);
+        assert!(saved.into_chunk().world == chunk.world);
+    }
+
+    #[test]
+    fn diffs_are_against_the_chunks_own_generation() {
+        let chunk = ChunkData::generate(
+            Vector3::new(0, 2, 0),
+            CHUNK_SIZE,
+            SEED,
+            GenerationMode::Heightmap,
+        );
+        let saved = SavedChunk::new(&chunk, SEED, GenerationMode::Heightmap);
+
+        assert_eq!(diff_len(&saved), Some(0));
+        assert!(saved.into_chunk().world == chunk.world);
+    }
+
+    #[test]
+    fn changes_outside_the_chunk_are_ignored() {
+        let chunk = generated();
+        let saved = SavedChunk::Diff {
+            position: chunk.position,
+            size: CHUNK_SIZE,
+            seed: SEED,
+            generation_mode: GenerationMode::Normal,
+            changes: vec![ChangedBlock {
+                x: CHUNK_SIZE as u8,
+                y: 0,
+                z: 0,
+                block: 6,
+            }],
+        };
+
+        assert!(saved.into_chunk().world == chunk.world);
+    }
+
+    #[test]
+    fn saved_chunks_load_from_json() {
+        let chunk = modified(5);
+        let json =
+            serde_json::to_string(&SavedChunk::new(&chunk, SEED, GenerationMode::Normal)).unwrap();
+        let loaded: SavedChunk = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(diff_len(&loaded), Some(5));
+        assert!(loaded.into_chunk().world == chunk.world);
+    }
+
+    #[test]
+    fn chunks_saved_before_diffs_still_load() {
+        let chunk = modified(5);
+        let json = serde_json::to_string(&chunk).unwrap();
+        let loaded: SavedChunk = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(diff_len(&loaded), None);
+        assert!(loaded.into_chunk().world == chunk.world);
+    }
+
+    #[test]
+    fn diffs_keep_the_size_of_their_chunk() {
+        let mut chunk =
+            ChunkData::generate(Vector3::new(2, 5, -1), 8, SEED, GenerationMode::Normal);
+        chunk.world.set(7, 7, 7, 100);
+
+        let saved = SavedChunk::new(&chunk, SEED, GenerationMode::Normal);
+        assert_eq!(diff_len(&saved), Some(1));
+        assert!(saved.into_chunk().world == chunk.world);
+    }
+
+    #[test]
+    fn diffs_saved_before_sizes_are_chunk_size() {
+        let chunk = modified(5);
+        let mut json =
+            serde_json::to_value(SavedChunk::new(&chunk, SEED, GenerationMode::Normal)).unwrap();
+        json.as_object_mut().unwrap().remove("size");
+
+        let loaded: SavedChunk = serde_json::from_value(json).unwrap();
+        assert!(loaded.into_chunk().world == chunk.world);
+    }
+}
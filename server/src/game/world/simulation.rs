@@ -0,0 +1,136 @@
+use crate::game::entity::PhysicsBody;
+use crate::game::transform::Transform;
+use crate::helpers::global_to_local_position;
+use crate::{ServerConfig, TransportSystem, WorldData};
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::CHUNK_SIZE;
+
+/// The chunks players are in, chunks within the simulation distance of these are ticked.
+/// Chunks further away are still sent to players, but their entities are frozen until a player comes back
+#[derive(Resource)]
+pub struct SimulatedArea {
+    pub player_chunks: Vec<Vector3<i32>>,
+    /// How far, in chunks, from a player chunks are ticked
+    pub distance: i32,
+    /// How many blocks along each side the world's chunks are
+    pub chunk_size: usize,
+}
+
+impl Default for SimulatedArea {
+    fn default() -> Self {
+        SimulatedArea {
+            player_chunks: Vec::new(),
+            distance: 0,
+            chunk_size: CHUNK_SIZE,
+        }
+    }
+}
+
+impl SimulatedArea {
+    /// Whether a chunk is close enough to a player to be ticked
+    pub fn is_simulated(&self, chunk: Vector3<i32>) -> bool {
+        self.player_chunks.iter().any(|player| {
+            let offset = chunk - player;
+            offset.x.abs() <= self.distance
+                && offset.y.abs() <= self.distance
+                && offset.z.abs() <= self.distance
+        })
+    }
+
+    /// Whether the chunk a position is in is ticked
+    pub fn is_position_simulated(&self, position: Vector3<f32>) -> bool {
+        self.is_simulated(chunk_of(position, self.chunk_size))
+    }
+}
+
+/// The chunk a position is in, for chunks with `size` blocks along each side
+pub fn chunk_of(position: Vector3<f32>, size: usize) -> Vector3<i32> {
+    global_to_local_position(position.map(|v| v.floor() as i32), size).0
+}
+
+pub fn update_simulated_area(
+    mut area: ResMut<SimulatedArea>,
+    world: Res<WorldData>,
+    transport: Res<TransportSystem>,
+    players: Query<&Transform, Without<PhysicsBody>>,
+    config: Res<ServerConfig>,
+) {
+    area.distance = config.simulation_distance.max(0);
+    area.chunk_size = world.chunk_size;
+    area.player_chunks = transport
+        .clients
+        .values()
+        .filter_map(|user| world.entities.get(&user.entity_id))
+        .filter_map(|entity| players.get(*entity).ok())
+        .map(|transform| chunk_of(transform.position, world.chunk_size))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(players: &[Vector3<i32>]) -> SimulatedArea {
+        SimulatedArea {
+            player_chunks: players.to_vec(),
+            distance: 2,
+            chunk_size: CHUNK_SIZE,
+        }
+    }
+
+    #[test]
+    fn chunks_within_the_distance_are_simulated() {
+        let area = area(&[Vector3::zeros()]);
+
+        assert!(area.is_simulated(Vector3::zeros()));
+        assert!(area.is_simulated(Vector3::new(2, 0, 0)));
+        assert!(area.is_simulated(Vector3::new(-2, 2, -2)));
+        assert!(!area.is_simulated(Vector3::new(3, 0, 0)));
+        assert!(!area.is_simulated(Vector3::new(0, -3, 0)));
+        assert!(!area.is_simulated(Vector3::new(2, 2, 3)));
+    }
+
+    #[test]
+    fn simulation_follows_the_player() {
+        let far = Vector3::new(4, 0, 0);
+        let mut area = area(&[Vector3::zeros()]);
+        assert!(!area.is_simulated(far));
+
+        // Walking towards the chunk brings it into range, and its entities resume
+        area.player_chunks = vec![Vector3::new(2, 0, 0)];
+        assert!(area.is_simulated(far));
+        assert!(!area.is_simulated(Vector3::new(-1, 0, 0)));
+
+        // Walking away freezes it again
+        area.player_chunks = vec![Vector3::new(-1, 0, 0)];
+        assert!(!area.is_simulated(far));
+    }
+
+    #[test]
+    fn any_player_keeps_a_chunk_simulated() {
+        let area = area(&[Vector3::zeros(), Vector3::new(10, 0, 0)]);
+
+        assert!(area.is_simulated(Vector3::new(1, 0, 0)));
+        assert!(area.is_simulated(Vector3::new(9, 0, 0)));
+        assert!(!area.is_simulated(Vector3::new(5, 0, 0)));
+    }
+
+    #[test]
+    fn nothing_is_simulated_without_players() {
+        assert!(!area(&[]).is_simulated(Vector3::zeros()));
+    }
+
+    #[test]
+    fn positions_are_simulated_by_their_chunk() {
+        let area = area(&[Vector3::zeros()]);
+
+        assert_eq!(
+            chunk_of(Vector3::new(-0.5, 15.9, 16.0), CHUNK_SIZE),
+            Vector3::new(-1, 0, 1)
+        );
+        assert!(area.is_position_simulated(Vector3::new(47.9, 0.0, -32.0)));
+        assert!(!area.is_position_simulated(Vector3::new(48.0, 0.0, 0.0)));
+        assert!(!area.is_position_simulated(Vector3::new(0.0, 0.0, -32.1)));
+    }
+}
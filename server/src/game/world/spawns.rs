@@ -0,0 +1,123 @@
+use crate::error::ServerError;
+use bevy::log::error;
+use bevy::prelude::Resource;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+const SPAWNS_PATH: &str = "./world/spawns.json";
+
+/// The spawn points players have set for themselves, by player name
+#[derive(Resource, Default, Debug, Clone)]
+pub struct SpawnPoints {
+    pub points: HashMap<String, [f32; 3]>,
+    /// Whether a spawn point has changed since they were last saved
+    pub modified: bool,
+}
+
+impl SpawnPoints {
+    pub fn load() -> SpawnPoints {
+        Self::load_from(Path::new(SPAWNS_PATH))
+    }
+
+    pub fn load_from(path: &Path) -> SpawnPoints {
+        match Self::try_load(path) {
+            Ok(points) => SpawnPoints {
+                points,
+                modified: false,
+            },
+            Err(err) => {
+                error!("Error reading spawn points: {:?}", err);
+                SpawnPoints::default()
+            }
+        }
+    }
+
+    fn try_load(path: &Path) -> Result<HashMap<String, [f32; 3]>, ServerError> {
+        if !fs::try_exists(path)? {
+            return Ok(HashMap::new());
+        }
+
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn set(&mut self, player: &str, position: [f32; 3]) {
+        self.points.insert(player.to_string(), position);
+        self.modified = true;
+    }
+
+    pub fn get(&self, player: &str) -> Option<[f32; 3]> {
+        self.points.get(player).copied()
+    }
+
+    /// Copies out the spawn points if they need saving
+    pub fn snapshot_modified(&mut self) -> Option<HashMap<String, [f32; 3]>> {
+        if !self.modified {
+            return None;
+        }
+        self.modified = false;
+        Some(self.points.clone())
+    }
+}
+
+pub fn write_spawn_points(points: &HashMap<String, [f32; 3]>) {
+    write_spawn_points_to(Path::new(SPAWNS_PATH), points);
+}
+
+pub fn write_spawn_points_to(path: &Path, points: &HashMap<String, [f32; 3]>) {
+    let result = fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))
+        .map_err(ServerError::from)
+        .and_then(|_| Ok(File::create(path)?))
+        .and_then(|file| Ok(serde_json::to_writer(BufWriter::new(file), points)?));
+
+    if let Err(err) = result {
+        error!("Failed to save spawn points: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_points_save_and_restore() {
+        let directory =
+            std::env::temp_dir().join(format!("rustcraft-spawns-{}", std::process::id()));
+        let path = directory.join("spawns.json");
+        let _ = fs::remove_dir_all(&directory);
+
+        let mut spawns = SpawnPoints::default();
+        spawns.set("alice", [1.5, 70.0, -3.5]);
+        spawns.set("bob", [100.0, 64.0, 20.0]);
+        spawns.set("alice", [2.5, 71.0, -4.5]);
+        write_spawn_points_to(&path, &spawns.snapshot_modified().unwrap());
+
+        let restored = SpawnPoints::load_from(&path);
+        let _ = fs::remove_dir_all(&directory);
+
+        assert_eq!(restored.get("alice"), Some([2.5, 71.0, -4.5]));
+        assert_eq!(restored.get("bob"), Some([100.0, 64.0, 20.0]));
+        assert_eq!(restored.get("carol"), None);
+        assert!(!restored.modified);
+    }
+
+    #[test]
+    fn only_changed_spawn_points_are_saved() {
+        let mut spawns = SpawnPoints::default();
+        assert!(spawns.snapshot_modified().is_none());
+
+        spawns.set("alice", [0.0, 64.0, 0.0]);
+        assert!(spawns.snapshot_modified().is_some());
+        assert!(spawns.snapshot_modified().is_none());
+    }
+
+    #[test]
+    fn missing_file_has_no_spawn_points() {
+        let path = std::env::temp_dir().join("rustcraft-spawns-missing.json");
+
+        assert!(SpawnPoints::load_from(&path).points.is_empty());
+    }
+}
@@ -1,21 +1,84 @@
-use rc_networking::constants::CHUNK_SIZE;
 use nalgebra::Vector3;
 
+/// Splits a global block position into the position of its chunk and its position within that chunk, for chunks
+/// with `size` blocks along each side
 #[inline]
-pub fn global_to_local_position(vector: Vector3<i32>) -> (Vector3<i32>, Vector3<usize>) {
+pub fn global_to_local_position(
+    vector: Vector3<i32>,
+    size: usize,
+) -> (Vector3<i32>, Vector3<usize>) {
+    let size = size as i32;
+
     // Locate block
-    let inner_loc = Vector3::new(
-        ((vector.x as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-        ((vector.y as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-        ((vector.z as usize % CHUNK_SIZE) + CHUNK_SIZE) % CHUNK_SIZE,
-    );
+    let inner_loc = vector.map(|v| v.rem_euclid(size) as usize);
 
     // Locate chunk
-    let chunk_loc = Vector3::new(
-        (vector.x as f32 / CHUNK_SIZE as f32).floor() as i32,
-        (vector.y as f32 / CHUNK_SIZE as f32).floor() as i32,
-        (vector.z as f32 / CHUNK_SIZE as f32).floor() as i32,
-    );
+    let chunk_loc = vector.map(|v| v.div_euclid(size));
 
     (chunk_loc, inner_loc)
-}
\ No newline at end of file
+}
+
+/// Checks if a block is close enough to a position to be reached
+#[inline]
+pub fn within_reach(position: Vector3<f32>, block: Vector3<i32>, reach: f32) -> bool {
+    // Measure to the center of the block
+    let center = block.cast::<f32>() + Vector3::new(0.5, 0.5, 0.5);
+
+    (center - position).magnitude() <= reach
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc_networking::constants::CHUNK_SIZE;
+
+    #[test]
+    fn blocks_in_reach_are_accepted() {
+        let player = Vector3::new(8.5, 4.0, 8.5);
+
+        assert!(within_reach(player, Vector3::new(8, 4, 8), 5.0));
+        assert!(within_reach(player, Vector3::new(12, 4, 8), 5.0));
+    }
+
+    #[test]
+    fn blocks_out_of_reach_are_rejected() {
+        let player = Vector3::new(8.5, 4.0, 8.5);
+
+        assert!(!within_reach(player, Vector3::new(14, 4, 8), 5.0));
+        assert!(!within_reach(player, Vector3::new(8, 40, 8), 5.0));
+    }
+
+    #[test]
+    fn reach_carries_across_chunk_boundaries() {
+        let size = CHUNK_SIZE as i32;
+        let player = Vector3::new(size as f32 - 0.5, 4.0, 0.5);
+
+        // Just over the boundary into the next chunk, and into the chunk behind
+        let ahead = Vector3::new(size + 1, 4, 0);
+        let behind = Vector3::new(size - 1, 4, -2);
+        assert!(within_reach(player, ahead, 5.0));
+        assert!(within_reach(player, behind, 5.0));
+        assert_eq!(
+            global_to_local_position(ahead, CHUNK_SIZE),
+            (Vector3::new(1, 0, 0), Vector3::new(1, 4, 0))
+        );
+        assert_eq!(
+            global_to_local_position(behind, CHUNK_SIZE),
+            (
+                Vector3::new(0, 0, -1),
+                Vector3::new(CHUNK_SIZE - 1, 4, CHUNK_SIZE - 2)
+            )
+        );
+
+        // Far into the next chunk is still out of reach
+        assert!(!within_reach(player, Vector3::new(size * 2, 4, 0), 5.0));
+    }
+
+    #[test]
+    fn positions_split_by_the_chunk_size() {
+        assert_eq!(
+            global_to_local_position(Vector3::new(25, -1, -24), 12),
+            (Vector3::new(2, -1, -2), Vector3::new(1, 11, 0))
+        );
+    }
+}
@@ -8,19 +8,31 @@ pub mod error;
 pub mod events;
 pub mod game;
 pub mod helpers;
+pub mod metrics;
 mod systems;
 pub mod transport;
 
 use crate::config::{load_config, ServerConfig};
+use crate::console::ConsolePlugin;
+use crate::events::block_break::BlockBrokenEvent;
+use crate::game::collision::CollisionShapes;
+use crate::game::drops::DropTable;
+use crate::game::hardness::BlockHardness;
 use crate::game::world::data::WorldData;
+use crate::game::world::simulation::update_simulated_area;
 use crate::game::world::WorldPlugin;
+use crate::metrics::MetricsPlugin;
+use crate::systems::block_updates::PendingBlockUpdates;
 use crate::systems::chunk::ChunkPlugin;
+use crate::systems::entity_sync::SentPositions;
+use crate::systems::spawn_point::PendingTeleports;
 use crate::systems::tick::tick;
 use crate::transport::{TransportPlugin, TransportSystem};
 use bevy::app::{App, AppExit, CoreStage};
 use bevy::log::{info, Level, LogPlugin};
-use bevy::prelude::EventWriter;
+use bevy::prelude::{EventWriter, IntoSystemDescriptor};
 use bevy::MinimalPlugins;
+use rc_networking::dispatch::{PacketHandlerAppExt, PacketKind};
 use rc_networking::types::{ReceivePacket, SendPacket};
 use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -33,20 +45,26 @@ fn main() {
 
     info!("Rustcraft Server starting up");
 
+    let config = load_config();
+    let world =
+        WorldData::load_spawn_chunks(config.chunk_size(), config.seed, config.generation_mode);
+
     // Build App
     App::default()
-        .insert_resource(load_config())
+        .insert_resource(config)
         .add_plugins(MinimalPlugins)
         // Plugins
         .add_plugin(LogPlugin {
             filter: "rechannel=warn".into(),
             level: Level::DEBUG,
         })
+        .add_plugin(ConsolePlugin)
         .add_plugin(WorldPlugin)
         .add_plugin(TransportPlugin)
         .add_plugin(ChunkPlugin)
+        .add_plugin(MetricsPlugin)
         // Startup System
-        .insert_resource(WorldData::load_spawn_chunks())
+        .insert_resource(world)
         .add_event::<ReceivePacket>()
         .add_event::<SendPacket>()
         // Receive Server Events
@@ -54,8 +72,43 @@ fn main() {
         .add_system(systems::connection::connection_event)
         .add_system(systems::disconnect::disconnection_event)
         .add_system(systems::message::receive_message_event)
+        .insert_resource(PendingBlockUpdates::default())
+        .add_system(
+            systems::block_updates::send_block_updates
+                .after(systems::message::receive_message_event),
+        )
+        .handle_packets(&[
+            PacketKind::PlayerMove,
+            PacketKind::PlayerRotate,
+            PacketKind::BlockEdit,
+            PacketKind::BlockBreakProgress,
+        ])
+        .add_system(systems::game_mode::game_mode_command)
+        .add_system(systems::idle::track_player_input)
+        .add_system(systems::idle::disconnect_idle_players.after(systems::idle::track_player_input))
+        .add_system(systems::idle::afk_command)
+        .add_system(systems::fill::fill_command)
+        .add_system(systems::chat::chat_messages)
+        .handle_packets(&[PacketKind::ChatSent])
+        .insert_resource(PendingTeleports::default())
+        .add_system(systems::spawn_point::spawn_point_requests)
+        .handle_packets(&[PacketKind::SpawnPoint])
+        .add_system(systems::spawn_point::finish_teleports)
         // Gameplay Loop on Tick
         .add_system(tick)
+        .insert_resource(CollisionShapes::default())
+        .insert_resource(BlockHardness::default())
+        .add_system(systems::physics::entity_physics.after(update_simulated_area))
+        .insert_resource(SentPositions::default())
+        .add_system(
+            systems::entity_sync::sync_entity_positions.after(systems::physics::entity_physics),
+        )
+        .add_system(systems::pickup::pickup_items)
+        .add_event::<BlockBrokenEvent>()
+        .insert_resource(DropTable::default())
+        .add_system(
+            systems::drops::spawn_block_drops.after(systems::message::receive_message_event),
+        )
         .add_system_to_stage(CoreStage::PreUpdate, detect_shutdowns)
         // Run App
         .run();
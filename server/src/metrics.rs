@@ -0,0 +1,215 @@
+use crate::systems::chunk::ChunkSystem;
+use crate::{ServerConfig, TransportSystem, WorldData};
+use bevy::prelude::*;
+use rc_networking::Server;
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a metrics request has to send its request line before it's answered anyway
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Serves a snapshot of the server's state as JSON over HTTP on the metrics port, for monitoring
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        let port = app
+            .world
+            .get_resource::<ServerConfig>()
+            .map_or(0, |v| v.metrics_port);
+
+        let snapshot = SharedMetrics::default();
+
+        if port != 0 {
+            // Only reachable from this machine, put a proxy in front to expose it further
+            let address = SocketAddr::from(([127, 0, 0, 1], port));
+            match TcpListener::bind(address) {
+                Ok(listener) => {
+                    info!("Serving metrics on http://{}", address);
+                    let snapshot = snapshot.clone();
+
+                    // Requests are answered on their own thread so a slow client can't hold up the tick loop
+                    thread::spawn(move || {
+                        for stream in listener.incoming().flatten() {
+                            if let Err(err) = answer_request(stream, &snapshot) {
+                                warn!("Failed to answer metrics request: {}", err);
+                            }
+                        }
+                    });
+                }
+                Err(err) => error!("Failed to serve metrics on {}: {}", address, err),
+            }
+        }
+
+        app.insert_resource(snapshot)
+            .init_resource::<TickStart>()
+            .add_system_to_stage(CoreStage::First, start_tick)
+            .add_system_to_stage(CoreStage::Last, update_metrics);
+    }
+}
+
+/// The state of the server as of the latest tick
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    pub players_online: usize,
+    pub loaded_chunks: usize,
+    /// How long the latest tick took, in milliseconds
+    pub tick_time_ms: f32,
+    /// The traffic to and from every player added together, in kilobits per second
+    pub sent_kbps: f32,
+    pub received_kbps: f32,
+    /// Chunks waiting for a generation slot, and chunks being generated right now
+    pub generation_queue: usize,
+    pub generating: usize,
+}
+
+/// When the current tick started, so the time spent running its systems can be measured at the end of it
+#[derive(Resource, Default)]
+pub struct TickStart(Option<Instant>);
+
+impl TickStart {
+    /// How long it's been since the tick started, in milliseconds
+    pub fn elapsed_ms(&self) -> f32 {
+        self.0.map_or(0.0, |v| v.elapsed().as_secs_f32() * 1000.0)
+    }
+}
+
+fn start_tick(mut start: ResMut<TickStart>) {
+    start.0 = Some(Instant::now());
+}
+
+/// The latest snapshot, shared with the thread answering requests
+#[derive(Resource, Clone, Default)]
+pub struct SharedMetrics(Arc<Mutex<MetricsSnapshot>>);
+
+fn update_metrics(
+    metrics: Res<SharedMetrics>,
+    transport: Res<TransportSystem>,
+    world: Res<WorldData>,
+    chunks: Res<ChunkSystem>,
+    server: Res<Server>,
+    tick_start: Res<TickStart>,
+) {
+    let (sent_kbps, received_kbps) = server
+        .clients_id()
+        .into_iter()
+        .filter_map(|id| server.network_info(id))
+        .fold((0.0, 0.0), |(sent, received), info| {
+            (sent + info.sent_kbps, received + info.received_kbps)
+        });
+
+    let snapshot = MetricsSnapshot {
+        players_online: transport.clients.len(),
+        loaded_chunks: world.chunks.len(),
+        tick_time_ms: tick_start.elapsed_ms(),
+        sent_kbps,
+        received_kbps,
+        generation_queue: chunks.queue_depth(),
+        generating: chunks.generating(),
+    };
+
+    if let Ok(mut shared) = metrics.0.lock() {
+        *shared = snapshot;
+    }
+}
+
+/// Answers any request with the latest snapshot, there's only the one thing to ask for
+fn answer_request(mut stream: TcpStream, metrics: &SharedMetrics) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    // Read the request so the connection closes cleanly, its contents don't matter
+    let mut request = [0; 1024];
+    let _ = stream.read(&mut request);
+
+    let snapshot = metrics.0.lock().map(|v| v.clone()).unwrap_or_default();
+    let body = serde_json::to_string(&snapshot)?;
+
+    write!(
+        stream,
+        "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use serde_json::json;
+
+    fn snapshot() -> MetricsSnapshot {
+        MetricsSnapshot {
+            players_online: 3,
+            loaded_chunks: 420,
+            tick_time_ms: 12.5,
+            sent_kbps: 96.0,
+            received_kbps: 8.25,
+            generation_queue: 7,
+            generating: 2,
+        }
+    }
+
+    #[test]
+    fn snapshot_serializes_every_field() {
+        assert_eq!(
+            serde_json::to_value(snapshot()).unwrap(),
+            json!({
+                "players_online": 3,
+                "loaded_chunks": 420,
+                "tick_time_ms": 12.5,
+                "sent_kbps": 96.0,
+                "received_kbps": 8.25,
+                "generation_queue": 7,
+                "generating": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn requests_are_answered_with_the_latest_snapshot() {
+        let metrics = SharedMetrics::default();
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // Updated after the shared copy was handed out, as it is each tick
+        *metrics.0.lock().unwrap() = snapshot();
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        answer_request(stream, &metrics.clone()).unwrap();
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.0 200 OK"));
+        assert!(head.contains("Content-Type: application/json"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(body).unwrap(),
+            serde_json::to_value(snapshot()).unwrap()
+        );
+    }
+
+    #[test]
+    fn tick_time_is_measured_from_the_start_of_the_tick() {
+        let mut world = World::new();
+        world.init_resource::<TickStart>();
+        assert_eq!(world.resource::<TickStart>().elapsed_ms(), 0.0);
+
+        SystemStage::single_threaded()
+            .with_system(start_tick)
+            .run(&mut world);
+        thread::sleep(Duration::from_millis(20));
+
+        let elapsed = world.resource::<TickStart>().elapsed_ms();
+        assert!(elapsed >= 20.0, "{}", elapsed);
+    }
+}
@@ -1,16 +1,25 @@
 use crate::events::authorization::AuthorizationEvent;
+use crate::game::inventory::Inventory;
 use crate::game::transform::Transform;
 use bevy::ecs::change_detection::ResMut;
 use bevy::ecs::event::EventReader;
 use bevy::ecs::prelude::{Commands, EventWriter};
-use bevy::ecs::system::Query;
-use bevy::log::info;
+use bevy::ecs::system::{Query, Res};
+use bevy::log::{info, warn};
+use nalgebra::Vector3;
 use std::sync::atomic::Ordering;
+use std::time::Instant;
 
+use crate::game::world::clock::WorldClock;
 use crate::game::world::data::ENTITY_ID_COUNT;
-use crate::{TransportSystem, WorldData};
-use rc_networking::constants::{EntityId, UserId};
-use rc_networking::protocol::clientbound::chunk_update::FullChunkUpdate;
+use crate::game::world::inventories::SavedInventories;
+use crate::systems::chat::ChatLimiter;
+use crate::systems::chunk::send::ChunkSendQueue;
+use crate::{ServerConfig, TransportSystem, WorldData};
+use rc_networking::compression::ConnectionCompression;
+use rc_networking::constants::{EntityId, GameMode, UserId};
+use rc_networking::protocol::clientbound::inventory_update::InventoryUpdate;
+use rc_networking::protocol::clientbound::join_world::JoinWorld;
 use rc_networking::protocol::clientbound::spawn_entity::SpawnEntity;
 use rc_networking::protocol::Protocol;
 use rc_networking::types::SendPacket;
@@ -21,12 +30,68 @@ pub struct GameUser {
 
     pub user_id: UserId,
     pub entity_id: EntityId,
+
+    /// How many times the user has tried to edit blocks out of their reach
+    pub reach_violations: u32,
+
+    pub chat: ChatLimiter,
+
+    /// When the user last broke a block, to stop players outside creative breaking blocks instantly
+    pub last_break: Option<Instant>,
+    /// The block the user says they're mining and when they started. Players outside creative have to mine a block
+    /// for its hardness before breaking it
+    pub mining: Option<(Vector3<i32>, Instant)>,
+
+    /// When the user last moved or did something, to disconnect them once they've been idle too long
+    pub last_input: Instant,
+    /// Kept connected however long they're idle, toggled with `/afk`
+    pub idle_exempt: bool,
+
+    pub game_mode: GameMode,
 }
 
 impl GameUser {
+    /// A user who has just connected, yet to send their name or do anything
+    pub fn new(user_id: UserId, entity_id: EntityId, game_mode: GameMode) -> GameUser {
+        GameUser {
+            name: None,
+            user_id,
+            entity_id,
+            reach_violations: 0,
+            chat: Default::default(),
+            last_break: None,
+            mining: None,
+            last_input: Instant::now(),
+            idle_exempt: false,
+            game_mode,
+        }
+    }
+
+    /// A survival player who has already sent their name
+    #[cfg(test)]
+    pub fn named(id: u64, name: &str) -> GameUser {
+        let mut user = GameUser::new(UserId(id), EntityId(id), GameMode::Survival);
+        user.set_name(name.to_string());
+        user
+    }
+
     pub fn set_name(&mut self, name: String) {
         self.name = Some(name);
     }
+
+    /// What other players see the user called. Users who didn't send a name go by their user id, which commands
+    /// also accept
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.user_id.0.to_string())
+    }
+
+    /// The name a player's spawn point and inventory are saved under, the one they connected with. Players without
+    /// a name have nothing saved, so they can't end up with someone else's
+    pub fn save_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
 }
 
 pub fn authorization_event(
@@ -36,9 +101,38 @@ pub fn authorization_event(
     mut send_packet: EventWriter<SendPacket>,
     mut commands: Commands,
     transforms: Query<&Transform>,
+    clock: Res<WorldClock>,
+    inventories: Res<SavedInventories>,
+    mut send_queue: ResMut<ChunkSendQueue>,
+    config: Res<ServerConfig>,
+    compression: Res<ConnectionCompression>,
 ) {
     for client in event_reader.iter() {
         info!("Authorisation event");
+
+        let transform = Transform::default();
+        let game_mode = transport
+            .clients
+            .get(&client.client)
+            .map_or(GameMode::Survival, |v| v.game_mode);
+
+        // Tell the client about the world before anything else
+        let mut join = JoinWorld::new(
+            global.seed,
+            [
+                transform.position.x,
+                transform.position.y,
+                transform.position.z,
+            ],
+            clock.seconds,
+            game_mode,
+            config.column_sections,
+            config.dimension,
+            global.chunk_size as u32,
+        );
+        join.compression = compression.get(client.client.0);
+        send_packet.send(SendPacket(Protocol::JoinWorld(join), client.client));
+
         // Spawn other entities for new player
         for (id, entity) in &global.entities {
             let transform = transforms.get(*entity).unwrap();
@@ -54,14 +148,33 @@ pub fn authorization_event(
             send_packet.send(SendPacket(packet, client.client));
         }
 
-        let transform = Transform::default();
-
         // Create new entity for player
         let entity_id = EntityId(ENTITY_ID_COUNT.fetch_add(1, Ordering::Acquire));
 
         // Store player entity
         transport.clients.get_mut(&client.client).unwrap().entity_id = entity_id;
 
+        // Players are known by the name they connected with, unless someone online already has it
+        if let Some(name) = &client.name {
+            if transport
+                .clients
+                .values()
+                .any(|v| v.name.as_ref() == Some(name))
+            {
+                warn!(
+                    "{:?} connected as {}, who is already online",
+                    client.client, name
+                );
+            } else {
+                info!("{:?} is {}", client.client, name);
+                transport
+                    .clients
+                    .get_mut(&client.client)
+                    .unwrap()
+                    .set_name(name.clone());
+            }
+        }
+
         let packet = Protocol::SpawnEntity(SpawnEntity {
             id: entity_id,
             loc: [
@@ -81,19 +194,160 @@ pub fn authorization_event(
             send_packet.send(SendPacket(packet.clone(), *id));
         }
 
-        let entity = commands.spawn(transform).id();
-        global.entities.insert(entity_id, entity);
+        // Give back the inventory the player had last time, new players get the starter blocks
+        let saved = transport.clients[&client.client]
+            .save_name()
+            .filter(|_| config.save_inventories)
+            .and_then(|v| inventories.get(v));
+        let inventory = match saved {
+            Some(slots) => Inventory::restore(slots),
+            None => Inventory::starter(),
+        };
 
-        // Send world to client
-        for (loc, chunk) in global.chunks.iter() {
-            let chunk = Protocol::PartialChunkUpdate(FullChunkUpdate::new(
-                chunk.world,
-                loc.x,
-                loc.y,
-                loc.z,
+        // Every slot is sent so the client's default hotbar is replaced
+        for (slot, stack) in inventory.slots.iter().enumerate() {
+            let (item, amount) = stack.map_or((0, 0), |v| (v.item, v.amount));
+            send_packet.send(SendPacket(
+                Protocol::InventoryUpdate(InventoryUpdate::new(slot as u8, item, amount)),
+                client.client,
             ));
+        }
+
+        let entity = commands.spawn((transform, inventory)).id();
+        global.entities.insert(entity_id, entity);
+
+        // Send world to client, paced out nearest first
+        for loc in global.chunks.keys() {
+            send_queue.queue(client.client, *loc);
+        }
+    }
+}
 
-            send_packet.send(SendPacket(chunk, client.client));
+/// Sets up worlds and puts users through authorization the way connecting does, for testing what players can do
+/// once they've joined
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use crate::game::chunk::GenerationMode;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use bevy::ecs::world::World;
+    use std::collections::{HashMap, HashSet};
+
+    /// A world where users with the given ids have connected, but are yet to be authorized
+    pub fn world(config: ServerConfig, users: &[u64]) -> World {
+        let mut transport = TransportSystem::default();
+        for id in users {
+            let user = GameUser::new(UserId(*id), EntityId(*id), GameMode::Survival);
+            transport.clients.insert(UserId(*id), user);
         }
+
+        let mut world = World::new();
+        world.insert_resource(WorldData {
+            chunks: HashMap::new(),
+            entities: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            chunk_size: config.chunk_size(),
+            seed: 0,
+            generation_mode: GenerationMode::Heightmap,
+        });
+        world.insert_resource(transport);
+        world.insert_resource(config);
+        world.init_resource::<WorldClock>();
+        world.init_resource::<SavedInventories>();
+        world.init_resource::<ChunkSendQueue>();
+        world.init_resource::<ConnectionCompression>();
+        world.init_resource::<Events<AuthorizationEvent>>();
+        world.init_resource::<Events<SendPacket>>();
+        world
+    }
+
+    /// Authorizes a user who connected with the given name
+    pub fn authorize(world: &mut World, id: u64, name: Option<&str>) {
+        world
+            .resource_mut::<Events<AuthorizationEvent>>()
+            .send(AuthorizationEvent::new(
+                UserId(id),
+                name.map(str::to_string),
+            ));
+        SystemStage::single_threaded()
+            .with_system(authorization_event)
+            .run(world);
+        world.resource_mut::<Events<AuthorizationEvent>>().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{authorize, world};
+    use super::*;
+    use crate::systems::spawn_protection::edit_blocked_by_spawn_protection;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::world::World;
+
+    fn name(world: &World, id: u64) -> Option<&str> {
+        world.resource::<TransportSystem>().clients[&UserId(id)]
+            .name
+            .as_deref()
+    }
+
+    #[test]
+    fn operators_named_when_connecting_bypass_spawn_protection() {
+        let config = ServerConfig {
+            spawn_protection: 8.0,
+            operators: vec!["admin".to_string()],
+            ..Default::default()
+        };
+        let mut world = world(config.clone(), &[1, 2]);
+        authorize(&mut world, 1, Some("admin"));
+        authorize(&mut world, 2, None);
+
+        assert_eq!(name(&world, 1), Some("admin"));
+        assert_eq!(name(&world, 2), None);
+
+        let clients = &world.resource::<TransportSystem>().clients;
+        let inside = Vector3::new(3, 64, -3);
+        let spawn = Transform::default().position;
+        assert!(!edit_blocked_by_spawn_protection(
+            &config,
+            &clients[&UserId(1)],
+            inside,
+            spawn,
+        ));
+        assert!(edit_blocked_by_spawn_protection(
+            &config,
+            &clients[&UserId(2)],
+            inside,
+            spawn,
+        ));
+    }
+
+    #[test]
+    fn names_already_online_are_not_given_out_again() {
+        let mut world = world(ServerConfig::default(), &[1, 2]);
+        authorize(&mut world, 1, Some("alice"));
+        authorize(&mut world, 2, Some("alice"));
+
+        assert_eq!(name(&world, 1), Some("alice"));
+        assert_eq!(name(&world, 2), None);
+    }
+
+    #[test]
+    fn players_join_with_the_worlds_chunk_size() {
+        let config = ServerConfig {
+            chunk_size: 32,
+            ..Default::default()
+        };
+        let mut world = world(config, &[1]);
+        authorize(&mut world, 1, Some("alice"));
+
+        let join = world
+            .resource::<Events<SendPacket>>()
+            .iter_current_update_events()
+            .find_map(|v| match &v.0 {
+                Protocol::JoinWorld(join) => Some(join.chunk_size),
+                _ => None,
+            });
+        assert_eq!(join, Some(32));
     }
 }
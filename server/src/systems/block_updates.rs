@@ -0,0 +1,55 @@
+use crate::TransportSystem;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::UserId;
+use rc_networking::protocol::clientbound::batch_block_update::BatchBlockUpdate;
+use rc_networking::protocol::clientbound::block_update::BlockUpdate;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+use std::collections::HashMap;
+
+/// Block updates made this frame, sent together at the end of it so clients rebuild each chunk once
+#[derive(Default, Resource)]
+pub struct PendingBlockUpdates {
+    /// Updates in each chunk, with the client that made them so they aren't sent back to it
+    chunks: HashMap<Vector3<i32>, Vec<(BlockUpdate, UserId)>>,
+}
+
+impl PendingBlockUpdates {
+    /// Queues an update to a block in the chunk at `chunk_loc`
+    pub fn push(&mut self, chunk_loc: Vector3<i32>, update: BlockUpdate, from: UserId) {
+        self.chunks
+            .entry(chunk_loc)
+            .or_default()
+            .push((update, from));
+    }
+}
+
+/// Sends each client the updates it didn't make, a single update on its own and more than one as batches
+pub fn send_block_updates(
+    mut pending: ResMut<PendingBlockUpdates>,
+    transport: Res<TransportSystem>,
+    mut send_packets: EventWriter<SendPacket>,
+) {
+    for (chunk_loc, updates) in pending.chunks.drain() {
+        for client in transport.clients.keys() {
+            let updates = updates
+                .iter()
+                .filter(|(_, from)| from != client)
+                .map(|(update, _)| *update)
+                .collect::<Vec<BlockUpdate>>();
+
+            match updates.as_slice() {
+                [] => {}
+                [update] => {
+                    send_packets.send(SendPacket(Protocol::BlockUpdate(*update), *client));
+                }
+                _ => {
+                    for batch in BatchBlockUpdate::split(chunk_loc.into(), &updates) {
+                        send_packets.send(SendPacket(Protocol::BatchBlockUpdate(batch), *client));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{ServerConfig, TransportSystem};
+use bevy::ecs::prelude::*;
+use bevy::log::{info, warn};
+use rc_networking::protocol::clientbound::chat::ChatSent;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::{ReceivePacket, SendPacket};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// When a user's latest chat messages were sent, to limit how fast they can send more
+#[derive(Default, Debug)]
+pub struct ChatLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl ChatLimiter {
+    /// Records a message sent at a time, returning false without recording it
+    /// if the user has already sent the limit of messages within the window. A limit of 0 allows everything
+    pub fn try_send(&mut self, now: Instant, limit: usize, window: Duration) -> bool {
+        if limit == 0 {
+            return true;
+        }
+
+        while let Some(oldest) = self.sent.front() {
+            if now.duration_since(*oldest) < window {
+                break;
+            }
+            self.sent.pop_front();
+        }
+
+        if self.sent.len() >= limit {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// Removes control characters, such as newlines that could fake other messages, and cuts the message down to the max length in characters
+pub fn sanitize_message(message: &str, max_length: usize) -> String {
+    message
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(max_length)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Passes chat messages on to every player, dropping messages from users sending too many
+pub fn chat_messages(
+    mut packets: EventReader<ReceivePacket>,
+    mut send_packets: EventWriter<SendPacket>,
+    mut transport: ResMut<TransportSystem>,
+    config: Res<ServerConfig>,
+) {
+    let window = Duration::from_secs(config.chat_rate_window);
+
+    for packet in packets.iter() {
+        let chat = match &packet.0 {
+            Protocol::ChatSent(val) => val,
+            _ => continue,
+        };
+
+        let user = match transport.clients.get_mut(&packet.1) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        let message = sanitize_message(&chat.message, config.max_chat_length);
+        if message.is_empty() {
+            continue;
+        }
+
+        if !user
+            .chat
+            .try_send(Instant::now(), config.chat_rate_limit, window)
+        {
+            warn!(
+                "Dropped chat message from {:?} for sending too fast",
+                packet.1
+            );
+            send_packets.send(SendPacket(
+                Protocol::ChatSent(ChatSent {
+                    message: "You're sending messages too quickly".to_string(),
+                }),
+                packet.1,
+            ));
+            continue;
+        }
+
+        let message = format!("<{}> {}", user.display_name(), message);
+        info!("{}", message);
+
+        for client in transport.clients.keys() {
+            send_packets.send(SendPacket(
+                Protocol::ChatSent(ChatSent {
+                    message: message.clone(),
+                }),
+                *client,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::authorization::{testing, GameUser};
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::UserId;
+
+    const WINDOW: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn messages_past_the_limit_are_refused() {
+        let start = Instant::now();
+        let mut limiter = ChatLimiter::default();
+
+        for i in 0..5 {
+            assert!(limiter.try_send(start + Duration::from_secs(i), 5, WINDOW));
+        }
+        assert!(!limiter.try_send(start + Duration::from_secs(9), 5, WINDOW));
+    }
+
+    #[test]
+    fn window_slides_as_messages_age() {
+        let start = Instant::now();
+        let mut limiter = ChatLimiter::default();
+        for i in 0..5 {
+            assert!(limiter.try_send(start + Duration::from_secs(i), 5, WINDOW));
+        }
+
+        // Only the first message has left the window, so only one more fits
+        assert!(limiter.try_send(start + Duration::from_secs(10), 5, WINDOW));
+        assert!(!limiter.try_send(start + Duration::from_secs(10), 5, WINDOW));
+
+        // Refused messages don't count towards the limit
+        assert!(limiter.try_send(start + Duration::from_secs(11), 5, WINDOW));
+    }
+
+    #[test]
+    fn no_limit_allows_everything() {
+        let now = Instant::now();
+        let mut limiter = ChatLimiter::default();
+
+        for _ in 0..100 {
+            assert!(limiter.try_send(now, 0, WINDOW));
+        }
+    }
+
+    #[test]
+    fn messages_are_cut_to_the_max_length() {
+        assert_eq!(sanitize_message("hello world", 5), "hello");
+        assert_eq!(sanitize_message("héllo wörld", 8), "héllo wö");
+        assert_eq!(sanitize_message("short", 256), "short");
+    }
+
+    #[test]
+    fn control_characters_and_whitespace_are_removed() {
+        assert_eq!(
+            sanitize_message("hi\n<admin> fake\u{7}", 256),
+            "hi<admin> fake"
+        );
+        assert_eq!(sanitize_message("  padded  ", 256), "padded");
+        assert_eq!(sanitize_message("\n\r\t", 256), "");
+    }
+
+    /// The chat messages sent out, with who they were sent to
+    fn chat_sent(world: &World) -> Vec<(u64, String)> {
+        let mut sent: Vec<_> = world
+            .resource::<Events<SendPacket>>()
+            .iter_current_update_events()
+            .map(|v| match &v.0 {
+                Protocol::ChatSent(chat) => (v.1 .0, chat.message.clone()),
+                packet => panic!("Unexpected packet {:?}", packet),
+            })
+            .collect();
+        sent.sort();
+        sent
+    }
+
+    #[test]
+    fn chat_is_relayed_until_the_sender_is_limited() {
+        let mut transport = TransportSystem::default();
+        transport
+            .clients
+            .insert(UserId(1), GameUser::named(1, "alice"));
+        transport
+            .clients
+            .insert(UserId(2), GameUser::named(2, "bob"));
+
+        let mut world = World::new();
+        world.insert_resource(transport);
+        world.insert_resource(ServerConfig {
+            chat_rate_limit: 1,
+            ..Default::default()
+        });
+        world.init_resource::<Events<ReceivePacket>>();
+        world.init_resource::<Events<SendPacket>>();
+
+        for message in ["hello\n", "again", "   "] {
+            world
+                .resource_mut::<Events<ReceivePacket>>()
+                .send(ReceivePacket(
+                    Protocol::ChatSent(ChatSent {
+                        message: message.to_string(),
+                    }),
+                    UserId(1),
+                ));
+        }
+
+        SystemStage::single_threaded()
+            .with_system(chat_messages)
+            .run(&mut world);
+
+        assert_eq!(
+            chat_sent(&world),
+            vec![
+                (1, "<alice> hello".to_string()),
+                (1, "You're sending messages too quickly".to_string()),
+                (2, "<alice> hello".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn chat_is_prefixed_with_the_name_players_connected_with() {
+        let mut world = testing::world(ServerConfig::default(), &[1, 2]);
+        testing::authorize(&mut world, 1, Some("alice"));
+        testing::authorize(&mut world, 2, None);
+        world.init_resource::<Events<ReceivePacket>>();
+        world.resource_mut::<Events<SendPacket>>().clear();
+
+        for user in [1, 2] {
+            world
+                .resource_mut::<Events<ReceivePacket>>()
+                .send(ReceivePacket(
+                    Protocol::ChatSent(ChatSent {
+                        message: "hello".to_string(),
+                    }),
+                    UserId(user),
+                ));
+        }
+
+        SystemStage::single_threaded()
+            .with_system(chat_messages)
+            .run(&mut world);
+
+        // Players who didn't send a name go by their user id
+        assert_eq!(
+            chat_sent(&world),
+            vec![
+                (1, "<2> hello".to_string()),
+                (1, "<alice> hello".to_string()),
+                (2, "<2> hello".to_string()),
+                (2, "<alice> hello".to_string()),
+            ]
+        );
+    }
+}
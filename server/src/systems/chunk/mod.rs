@@ -1,90 +1,493 @@
+use crate::events::console::ConsoleCommandEvent;
+use crate::events::disconnect::DisconnectionEvent;
 use crate::game::chunk::ChunkData;
-use crate::{App, WorldData};
+use crate::systems::chunk::send::{send_queued_chunks, ChunkSendQueue};
+use crate::{App, ServerConfig, WorldData};
 use bevy::prelude::*;
 use nalgebra::Vector3;
-use rayon::iter::ParallelIterator;
-use rayon::prelude::IntoParallelRefIterator;
-use rc_client::helpers::TextureSubdivisionMethod::Full;
-use rc_networking::constants::UserId;
-use rc_networking::protocol::clientbound::chunk_update::FullChunkUpdate;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rc_networking::constants::{in_world_column, UserId};
+use rc_networking::dispatch::{PacketHandlerAppExt, PacketKind};
 use rc_networking::protocol::Protocol;
-use rc_networking::types::{ReceivePacket, SendPacket};
-use std::collections::HashMap;
+use rc_networking::types::ReceivePacket;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+pub mod send;
 
 pub struct ChunkPlugin;
 
 impl Plugin for ChunkPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(ChunkSystem {
-            generating_chunks: Default::default(),
-        })
-        .add_system(get_chunk_requests)
-        .add_system(generate_chunks);
+        let (threads, queue_size) = app
+            .world
+            .get_resource::<ServerConfig>()
+            .map_or((0, 64), |v| (v.generation_threads, v.generation_queue_size));
+
+        app.insert_resource(ChunkSystem::new(threads, queue_size))
+            .add_system(get_chunk_requests)
+            .add_system(forget_disconnected_requests.before(generate_chunks))
+            .handle_packets(&[PacketKind::RequestChunk])
+            .insert_resource(ChunkSendQueue::default())
+            .add_system(generate_chunks.after(get_chunk_requests))
+            .add_system(send_queued_chunks.after(generate_chunks))
+            .add_system(chunks_command);
     }
 }
 
 #[derive(Resource)]
 pub struct ChunkSystem {
+    /// The users waiting on each chunk
     generating_chunks: HashMap<Vector3<i32>, Vec<UserId>>,
+    /// The chunks each user is waiting on, in the order they were requested. Clients request the chunks they want first earliest
+    user_queues: HashMap<UserId, VecDeque<Vector3<i32>>>,
+    /// The order users are taken from when queueing generation, so one user can't starve the others
+    user_order: VecDeque<UserId>,
+    /// Chunks currently being loaded or generated on the pool. Later requests for these wait on the same result
+    in_flight: HashSet<Vector3<i32>>,
+    /// How many chunks can be generating at once
+    queue_size: usize,
+    pool: ThreadPool,
+    sender: Mutex<Sender<ChunkData>>,
+    receiver: Mutex<Receiver<ChunkData>>,
+}
+
+impl ChunkSystem {
+    /// Creates the system with a pool of generation threads, 0 for one per core
+    pub fn new(threads: usize, queue_size: usize) -> ChunkSystem {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("chunk-generation-{}", i))
+            .build()
+            .unwrap();
+
+        let (sender, receiver) = channel();
+
+        ChunkSystem {
+            generating_chunks: Default::default(),
+            user_queues: Default::default(),
+            user_order: Default::default(),
+            in_flight: Default::default(),
+            queue_size: queue_size.max(1),
+            pool,
+            sender: Mutex::new(sender),
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    /// Queues a chunk to be sent to a user, generating it if it isn't loaded
+    pub fn request(&mut self, pos: Vector3<i32>, user: UserId) {
+        let users = self.generating_chunks.entry(pos).or_default();
+        if users.contains(&user) {
+            return;
+        }
+        users.push(user);
+
+        // The user is sent the chunk along with everyone else waiting when it finishes
+        if self.in_flight.contains(&pos) {
+            return;
+        }
+
+        if !self.user_queues.contains_key(&user) {
+            self.user_order.push_back(user);
+        }
+        self.user_queues.entry(user).or_default().push_back(pos);
+    }
+
+    /// Drops everything a user was waiting on. Chunks nobody else asked for aren't generated, and ones already
+    /// generating are still loaded but not sent
+    pub fn forget_user(&mut self, user: UserId) {
+        self.user_queues.remove(&user);
+        self.user_order.retain(|v| *v != user);
+
+        self.generating_chunks.retain(|_, users| {
+            users.retain(|v| *v != user);
+            !users.is_empty()
+        });
+    }
+
+    /// How many chunks a user is waiting on a free generation slot for
+    pub fn queued(&self, user: UserId) -> usize {
+        self.user_queues.get(&user).map_or(0, |v| v.len())
+    }
+
+    /// How many chunks are waiting for a free generation slot
+    pub fn queue_depth(&self) -> usize {
+        self.user_queues.values().map(|v| v.len()).sum()
+    }
+
+    /// How many chunks are being loaded or generated on the pool right now
+    pub fn generating(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Takes the next chunk to generate, taking one from each waiting user in turn
+    fn next_chunk(&mut self) -> Option<Vector3<i32>> {
+        while let Some(user) = self.user_order.pop_front() {
+            let queue = match self.user_queues.get_mut(&user) {
+                Some(val) => val,
+                None => continue,
+            };
+
+            let chunk = queue.pop_front();
+
+            if queue.is_empty() {
+                self.user_queues.remove(&user);
+            } else {
+                self.user_order.push_back(user);
+            }
+
+            if chunk.is_some() {
+                return chunk;
+            }
+        }
+        None
+    }
 }
 
 pub fn generate_chunks(
     mut system: ResMut<ChunkSystem>,
     mut world: ResMut<WorldData>,
-    mut send_packets: EventWriter<SendPacket>,
+    mut send_queue: ResMut<ChunkSendQueue>,
 ) {
-    // Generate X chunks per loop
-    let chunks_per_loop = 5;
-
-    let build_chunks = system
-        .generating_chunks
-        .keys()
-        .take(chunks_per_loop)
-        .collect::<Vec<(&Vector3<i32>)>>();
-
-    #[cfg(not(target_arch = "wasm32"))]
-    let iterator = build_chunks.par_iter();
-    #[cfg(target_arch = "wasm32")]
-    let iterator = build_chunks.iter();
-
-    let chunks = iterator
-        .map(|pos| ChunkData::generate(**pos))
+    // Apply finished chunks in the order they completed
+    let finished = system
+        .receiver
+        .lock()
+        .unwrap()
+        .try_iter()
         .collect::<Vec<ChunkData>>();
 
-    for chunk in chunks {
-        let packet = FullChunkUpdate::new(
-            chunk.world,
-            chunk.position.x,
-            chunk.position.y,
-            chunk.position.z,
-        );
+    for chunk in finished {
+        system.in_flight.remove(&chunk.position);
 
         // Send to users
-        for user in system.generating_chunks.remove(&chunk.position).unwrap() {
-            send_packets.send(SendPacket(Protocol::PartialChunkUpdate(packet), user));
+        for user in system
+            .generating_chunks
+            .remove(&chunk.position)
+            .unwrap_or_default()
+        {
+            send_queue.queue(user, chunk.position);
         }
 
         world.chunks.insert(chunk.position, chunk);
     }
+
+    // Keep the pool busy without letting the backlog grow unbounded
+    while system.in_flight.len() < system.queue_size {
+        let pos = match system.next_chunk() {
+            Some(val) => val,
+            None => break,
+        };
+
+        // Already being generated for another user, who was waiting on it first
+        if system.in_flight.contains(&pos) {
+            continue;
+        }
+
+        // Chunks that are already loaded can be sent straight away rather than generated again
+        if world.chunks.contains_key(&pos) {
+            for user in system.generating_chunks.remove(&pos).unwrap_or_default() {
+                send_queue.queue(user, pos);
+            }
+            continue;
+        }
+
+        system.in_flight.insert(pos);
+
+        let sender = system.sender.lock().unwrap().clone();
+        let size = world.chunk_size;
+        let seed = world.seed;
+        let mode = world.generation_mode;
+        system.pool.spawn(move || {
+            // Chunks that were saved but since unloaded are read back rather than generated again
+            let _ = sender.send(WorldData::load_or_generate_chunk(pos, size, seed, mode));
+        });
+    }
 }
 
 // Respond to get chunk requests
 pub fn get_chunk_requests(
     mut request: EventReader<ReceivePacket>,
     mut system: ResMut<ChunkSystem>,
+    config: Res<ServerConfig>,
 ) {
+    let mut dropped = HashMap::new();
+
     for packet in request.iter() {
         if let Protocol::RequestChunk(request) = packet.0 {
             let pos = Vector3::new(request.x, request.y, request.z);
-            if system.generating_chunks.contains_key(&pos) {
-                system
-                    .generating_chunks
-                    .get_mut(&pos)
-                    .unwrap()
-                    .push(packet.1);
-            } else {
-                system.generating_chunks.insert(pos, vec![packet.1]);
+
+            // Ignore sections outside of the world column
+            if !in_world_column(pos.y, config.column_sections) {
+                continue;
             }
+
+            // A client asking for far more than it could see isn't allowed to fill the queue
+            if system.queued(packet.1) >= config.max_queued_chunks {
+                *dropped.entry(packet.1).or_insert(0) += 1;
+                continue;
+            }
+
+            system.request(pos, packet.1);
         }
     }
+
+    for (user, count) in dropped {
+        warn!(
+            "Dropped {} chunk requests from {:?}, they already have {} waiting",
+            count, user, config.max_queued_chunks
+        );
+    }
+}
+
+/// Stops generating chunks for users who have left
+pub fn forget_disconnected_requests(
+    mut disconnects: EventReader<DisconnectionEvent>,
+    mut system: ResMut<ChunkSystem>,
+) {
+    for event in disconnects.iter() {
+        system.forget_user(event.client);
+    }
+}
+
+/// `/chunks`, shows how busy chunk generation is
+fn chunks_command(mut commands: EventReader<ConsoleCommandEvent>, system: Res<ChunkSystem>) {
+    for command in commands.iter() {
+        if command.command != "chunks" {
+            continue;
+        }
+
+        info!(
+            "{} of {} chunk generation slots in use, {} chunks queued",
+            system.generating(),
+            system.queue_size,
+            system.queue_depth()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::chunk::GenerationMode;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::CHUNK_SIZE;
+    use rc_networking::protocol::serverbound::request_chunk::RequestChunk;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const FIRST: UserId = UserId(1);
+    const SECOND: UserId = UserId(2);
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.insert_resource(ChunkSystem::new(1, 4));
+        world.insert_resource(ChunkSendQueue::default());
+        world.insert_resource(WorldData {
+            chunks: HashMap::new(),
+            entities: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            chunk_size: CHUNK_SIZE,
+            seed: 0,
+            generation_mode: GenerationMode::Heightmap,
+        });
+        world
+    }
+
+    /// Runs generation each tick until the chunk is applied, returning how many ticks that took
+    fn generate_until_loaded(world: &mut World, pos: Vector3<i32>) -> usize {
+        let mut stage = SystemStage::single_threaded().with_system(generate_chunks);
+        let start = Instant::now();
+
+        let mut ticks = 0;
+        while !world.resource::<WorldData>().chunks.contains_key(&pos) {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            stage.run(world);
+            ticks += 1;
+            thread::sleep(Duration::from_millis(1));
+        }
+        ticks
+    }
+
+    #[test]
+    fn users_take_turns_generating() {
+        let mut system = ChunkSystem::new(1, 4);
+        for x in 0..3 {
+            system.request(Vector3::new(x, 0, 0), FIRST);
+        }
+        system.request(Vector3::new(0, 0, 5), SECOND);
+        assert_eq!(system.queue_depth(), 4);
+
+        // The second user's only chunk comes before the rest of the first user's
+        assert_eq!(system.next_chunk(), Some(Vector3::new(0, 0, 0)));
+        assert_eq!(system.next_chunk(), Some(Vector3::new(0, 0, 5)));
+        assert_eq!(system.next_chunk(), Some(Vector3::new(1, 0, 0)));
+        assert_eq!(system.next_chunk(), Some(Vector3::new(2, 0, 0)));
+        assert_eq!(system.next_chunk(), None);
+    }
+
+    #[test]
+    fn chunks_generate_off_the_tick() {
+        let mut world = world();
+        let pos = Vector3::new(-7000, 0, -7000);
+        world.resource_mut::<ChunkSystem>().request(pos, FIRST);
+
+        // The tick the chunk is requested in hands it to the pool rather than generating it there
+        let ticks = generate_until_loaded(&mut world, pos);
+        assert!(ticks > 1);
+
+        assert_eq!(world.resource::<ChunkSystem>().generating(), 0);
+        assert_eq!(world.resource::<ChunkSendQueue>().pending(FIRST), 1);
+    }
+
+    #[test]
+    fn users_waiting_on_the_same_chunk_share_its_result() {
+        let mut world = world();
+        let pos = Vector3::new(-7000, 0, -7001);
+        world.resource_mut::<ChunkSystem>().request(pos, FIRST);
+
+        SystemStage::single_threaded()
+            .with_system(generate_chunks)
+            .run(&mut world);
+        assert_eq!(world.resource::<ChunkSystem>().generating(), 1);
+
+        // Asked for while it's generating, so it isn't queued to be generated again
+        world.resource_mut::<ChunkSystem>().request(pos, SECOND);
+        assert_eq!(world.resource::<ChunkSystem>().queue_depth(), 0);
+
+        generate_until_loaded(&mut world, pos);
+        assert_eq!(world.resource::<ChunkSendQueue>().pending(FIRST), 1);
+        assert_eq!(world.resource::<ChunkSendQueue>().pending(SECOND), 1);
+    }
+
+    #[test]
+    fn requests_before_generation_starts_generate_once() {
+        let mut world = world();
+        let pos = Vector3::new(-7000, 0, -7002);
+        world.resource_mut::<ChunkSystem>().request(pos, FIRST);
+        world.resource_mut::<ChunkSystem>().request(pos, SECOND);
+
+        // Both users queued it, but only one generation is started
+        SystemStage::single_threaded()
+            .with_system(generate_chunks)
+            .run(&mut world);
+        assert_eq!(world.resource::<ChunkSystem>().generating(), 1);
+        assert_eq!(world.resource::<ChunkSystem>().queue_depth(), 0);
+
+        generate_until_loaded(&mut world, pos);
+        SystemStage::single_threaded()
+            .with_system(generate_chunks)
+            .run(&mut world);
+
+        assert_eq!(world.resource::<ChunkSystem>().generating(), 0);
+        assert_eq!(world.resource::<ChunkSendQueue>().pending(FIRST), 1);
+        assert_eq!(world.resource::<ChunkSendQueue>().pending(SECOND), 1);
+    }
+
+    #[test]
+    fn loaded_chunks_are_sent_without_generating() {
+        let mut world = world();
+        let pos = Vector3::new(-7000, 0, -7003);
+        world.resource_mut::<ChunkSystem>().request(pos, FIRST);
+        generate_until_loaded(&mut world, pos);
+
+        world.resource_mut::<ChunkSystem>().request(pos, SECOND);
+        SystemStage::single_threaded()
+            .with_system(generate_chunks)
+            .run(&mut world);
+
+        assert_eq!(world.resource::<ChunkSystem>().generating(), 0);
+        assert_eq!(world.resource::<ChunkSendQueue>().pending(SECOND), 1);
+    }
+
+    #[test]
+    fn bursts_of_requests_never_exceed_the_limit() {
+        let mut world = world();
+        let chunks: Vec<_> = (0..20).map(|z| Vector3::new(-7100, 0, z)).collect();
+        for (i, pos) in chunks.iter().enumerate() {
+            let user = if i % 2 == 0 { FIRST } else { SECOND };
+            world.resource_mut::<ChunkSystem>().request(*pos, user);
+        }
+        assert_eq!(world.resource::<ChunkSystem>().queue_depth(), 20);
+
+        let mut stage = SystemStage::single_threaded().with_system(generate_chunks);
+        let start = Instant::now();
+        loop {
+            stage.run(&mut world);
+
+            let system = world.resource::<ChunkSystem>();
+            let loaded = chunks
+                .iter()
+                .filter(|v| world.resource::<WorldData>().chunks.contains_key(*v))
+                .count();
+            assert!(system.generating() <= 4);
+            assert_eq!(system.generating() + system.queue_depth() + loaded, 20);
+
+            if loaded == 20 {
+                break;
+            }
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(world.resource::<ChunkSendQueue>().pending(FIRST), 10);
+        assert_eq!(world.resource::<ChunkSendQueue>().pending(SECOND), 10);
+    }
+
+    #[test]
+    fn leaving_forgets_a_users_requests() {
+        let mut system = ChunkSystem::new(1, 4);
+        let shared = Vector3::new(0, 0, 0);
+        system.request(shared, FIRST);
+        system.request(Vector3::new(1, 0, 0), FIRST);
+        system.request(shared, SECOND);
+
+        system.forget_user(FIRST);
+        assert_eq!(system.queued(FIRST), 0);
+
+        // The chunk the other user asked for is still generated for them
+        assert_eq!(system.queue_depth(), 1);
+        assert_eq!(system.next_chunk(), Some(shared));
+        assert_eq!(system.generating_chunks.get(&shared), Some(&vec![SECOND]));
+        assert!(!system
+            .generating_chunks
+            .contains_key(&Vector3::new(1, 0, 0)));
+    }
+
+    #[test]
+    fn requests_past_the_limit_are_dropped() {
+        let mut world = world();
+        world.init_resource::<Events<ReceivePacket>>();
+        world.insert_resource(ServerConfig {
+            max_queued_chunks: 3,
+            ..Default::default()
+        });
+
+        for x in 0..5 {
+            world
+                .resource_mut::<Events<ReceivePacket>>()
+                .send(ReceivePacket(
+                    Protocol::RequestChunk(RequestChunk::new(x, 0, 0)),
+                    FIRST,
+                ));
+        }
+        world
+            .resource_mut::<Events<ReceivePacket>>()
+            .send(ReceivePacket(
+                Protocol::RequestChunk(RequestChunk::new(0, 0, 0)),
+                SECOND,
+            ));
+
+        SystemStage::single_threaded()
+            .with_system(get_chunk_requests)
+            .run(&mut world);
+
+        // Each user has their own limit
+        let system = world.resource::<ChunkSystem>();
+        assert_eq!(system.queued(FIRST), 3);
+        assert_eq!(system.queued(SECOND), 1);
+    }
 }
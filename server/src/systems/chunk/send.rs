@@ -0,0 +1,175 @@
+use crate::game::transform::Transform;
+use crate::game::world::simulation::chunk_of;
+use crate::{ServerConfig, TransportSystem, WorldData};
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::UserId;
+use rc_networking::protocol::clientbound::chunk_update::FullChunkUpdate;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+use rc_networking::{Channel, Server};
+use std::collections::{HashMap, HashSet};
+
+/// Chunks waiting to be sent to each user. They're paced out over several ticks so a player joining
+/// doesn't have their connection filled with chunks ahead of everything else
+#[derive(Resource, Default)]
+pub struct ChunkSendQueue {
+    queues: HashMap<UserId, HashSet<Vector3<i32>>>,
+}
+
+impl ChunkSendQueue {
+    pub fn queue(&mut self, user: UserId, pos: Vector3<i32>) {
+        self.queues.entry(user).or_default().insert(pos);
+    }
+
+    /// How many chunks are waiting to be sent to a user
+    pub fn pending(&self, user: UserId) -> usize {
+        self.queues.get(&user).map_or(0, |v| v.len())
+    }
+
+    /// Takes up to a number of a user's chunks, nearest to a chunk first
+    pub fn take_nearest(
+        &mut self,
+        user: UserId,
+        center: Vector3<i32>,
+        count: usize,
+    ) -> Vec<Vector3<i32>> {
+        let queue = match self.queues.get_mut(&user) {
+            Some(val) => val,
+            None => return Vec::new(),
+        };
+
+        let mut nearest = queue.iter().copied().collect::<Vec<Vector3<i32>>>();
+        nearest.sort_by_key(|v| (v - center).abs().sum());
+        nearest.truncate(count);
+
+        for pos in &nearest {
+            queue.remove(pos);
+        }
+        if queue.is_empty() {
+            self.queues.remove(&user);
+        }
+
+        nearest
+    }
+}
+
+/// How many chunks each user can be sent in a tick, 0 for no limit
+pub fn chunk_budget(chunks_per_tick: usize) -> usize {
+    if chunks_per_tick == 0 {
+        usize::MAX
+    } else {
+        chunks_per_tick
+    }
+}
+
+/// Sends each user their nearest waiting chunks, up to the per tick budget.
+/// A user whose connection still has chunks queued from earlier ticks is skipped until it catches up
+pub fn send_queued_chunks(
+    mut queue: ResMut<ChunkSendQueue>,
+    world: Res<WorldData>,
+    transport: Res<TransportSystem>,
+    transforms: Query<&Transform>,
+    server: Res<Server>,
+    config: Res<ServerConfig>,
+    mut send_packets: EventWriter<SendPacket>,
+) {
+    // Forget users who have left
+    queue
+        .queues
+        .retain(|user, _| transport.clients.contains_key(user));
+
+    let budget = chunk_budget(config.chunks_per_tick);
+
+    for (user_id, user) in &transport.clients {
+        if queue.pending(*user_id) == 0 {
+            continue;
+        }
+
+        if !server.can_send_message(user_id.0, Channel::Block.into()) {
+            continue;
+        }
+
+        let center = world
+            .entities
+            .get(&user.entity_id)
+            .and_then(|v| transforms.get(*v).ok())
+            .map_or(Vector3::zeros(), |v| chunk_of(v.position, world.chunk_size));
+
+        for pos in queue.take_nearest(*user_id, center, budget) {
+            // Chunks unloaded since being queued are requested again by the client if it still needs them
+            if let Some(chunk) = world.chunks.get(&pos) {
+                let packet = FullChunkUpdate::new(chunk.world.clone(), pos.x, pos.y, pos.z);
+                send_packets.send(SendPacket(Protocol::PartialChunkUpdate(packet), *user_id));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: UserId = UserId(1);
+
+    /// A queue holding a 5x5 square of chunks around the origin
+    fn queue() -> ChunkSendQueue {
+        let mut queue = ChunkSendQueue::default();
+        for x in -2..=2 {
+            for z in -2..=2 {
+                queue.queue(USER, Vector3::new(x, 0, z));
+            }
+        }
+        queue
+    }
+
+    #[test]
+    fn each_tick_sends_no_more_than_the_budget() {
+        let mut queue = queue();
+        let budget = chunk_budget(4);
+
+        let mut ticks = Vec::new();
+        while queue.pending(USER) > 0 {
+            ticks.push(queue.take_nearest(USER, Vector3::zeros(), budget).len());
+        }
+
+        assert_eq!(ticks, vec![4, 4, 4, 4, 4, 4, 1]);
+    }
+
+    #[test]
+    fn no_budget_sends_everything_at_once() {
+        let mut queue = queue();
+
+        let sent = queue.take_nearest(USER, Vector3::zeros(), chunk_budget(0));
+
+        assert_eq!(sent.len(), 25);
+        assert_eq!(queue.pending(USER), 0);
+    }
+
+    #[test]
+    fn nearest_chunks_are_sent_first() {
+        let mut queue = queue();
+
+        let first = queue.take_nearest(USER, Vector3::zeros(), 5);
+        assert_eq!(first[0], Vector3::zeros());
+        assert!(first[1..].iter().all(|v| v.x.abs() + v.z.abs() == 1));
+
+        // Moving changes which chunks are nearest
+        let next = queue.take_nearest(USER, Vector3::new(2, 0, 2), 1);
+        assert_eq!(next, vec![Vector3::new(2, 0, 2)]);
+    }
+
+    #[test]
+    fn queued_chunks_are_only_sent_once() {
+        let mut queue = ChunkSendQueue::default();
+        queue.queue(USER, Vector3::zeros());
+        queue.queue(USER, Vector3::zeros());
+        assert_eq!(queue.pending(USER), 1);
+
+        assert_eq!(queue.take_nearest(USER, Vector3::zeros(), 4).len(), 1);
+        assert!(queue.take_nearest(USER, Vector3::zeros(), 4).is_empty());
+        assert!(queue
+            .take_nearest(UserId(2), Vector3::zeros(), 4)
+            .is_empty());
+    }
+}
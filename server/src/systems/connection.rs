@@ -13,7 +13,7 @@ pub fn connection_event<'world, 'state>(
     mut event_writer: EventWriter<AuthorizationEvent>
 ) {
     for connection in event_reader.iter() {
-        event_writer.send(AuthorizationEvent::new(connection.user));
+        event_writer.send(AuthorizationEvent::new(connection.user, connection.name.clone()));
     }
 
     // TODO: Add authorisation. For now just go straight to allow authorisation
@@ -0,0 +1,68 @@
+use crate::events::block_break::BlockBrokenEvent;
+use crate::game::drops::DropTable;
+use crate::game::entity::{DroppedItem, PhysicsBody};
+use crate::game::transform::Transform;
+use crate::game::world::data::ENTITY_ID_COUNT;
+use crate::{TransportSystem, WorldData};
+use bevy::prelude::*;
+use nalgebra::{Quaternion, Vector3};
+use rc_networking::constants::EntityId;
+use rc_networking::protocol::clientbound::spawn_entity::SpawnEntity;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+use std::sync::atomic::Ordering;
+
+/// The size of a dropped item's body
+pub const ITEM_HALF_WIDTH: f32 = 0.125;
+pub const ITEM_HEIGHT: f32 = 0.25;
+
+/// Leaves the drop of each broken block where the block was, for players to pick up
+pub fn spawn_block_drops(
+    mut broken: EventReader<BlockBrokenEvent>,
+    drops: Res<DropTable>,
+    transport: Res<TransportSystem>,
+    mut world: ResMut<WorldData>,
+    mut commands: Commands,
+    mut send_packets: EventWriter<SendPacket>,
+) {
+    for event in broken.iter() {
+        let game_mode = match transport.clients.get(&event.user) {
+            Some(val) => val.game_mode,
+            None => continue,
+        };
+
+        // The server doesn't know which item a player is holding, so blocks needing a tool never drop yet
+        let stack = match drops.resolve(event.block, game_mode, None) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        // Dropped at the bottom middle of the block, where the item's feet go
+        let position = event.position.cast::<f32>() + Vector3::new(0.5, 0.0, 0.5);
+
+        let entity_id = EntityId(ENTITY_ID_COUNT.fetch_add(1, Ordering::Acquire));
+        let entity = commands
+            .spawn((
+                Transform {
+                    position,
+                    rotation: Quaternion::default(),
+                },
+                DroppedItem {
+                    item: stack.item,
+                    amount: stack.amount,
+                },
+                PhysicsBody::new(ITEM_HALF_WIDTH, ITEM_HEIGHT),
+            ))
+            .id();
+        world.entities.insert(entity_id, entity);
+
+        let packet = Protocol::SpawnEntity(SpawnEntity {
+            id: entity_id,
+            loc: [position.x, position.y, position.z],
+            rot: [0.0; 4],
+        });
+        for client in transport.clients.keys() {
+            send_packets.send(SendPacket(packet.clone(), *client));
+        }
+    }
+}
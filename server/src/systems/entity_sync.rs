@@ -0,0 +1,129 @@
+use crate::game::entity::PhysicsBody;
+use crate::game::transform::Transform;
+use crate::{ServerConfig, TransportSystem, WorldData};
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::EntityId;
+use rc_networking::protocol::clientbound::entity_moved::EntityMoved;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+use std::collections::HashMap;
+
+/// The position each entity was last sent at, and how long ago in seconds
+#[derive(Default, Resource)]
+pub struct SentPositions {
+    entities: HashMap<EntityId, (Vector3<f32>, f32)>,
+}
+
+/// Whether an entity's position is sent, given how far it's moved from the last position sent and how long ago that
+/// was. Small moves and moves sooner than the rate allows wait, but every entity is sent once per keyframe interval
+pub fn should_send_move(
+    moved: f32,
+    since_sent: f32,
+    threshold: f32,
+    max_rate: f32,
+    keyframe_interval: f32,
+) -> bool {
+    if keyframe_interval > 0.0 && since_sent >= keyframe_interval {
+        return true;
+    }
+
+    let min_interval = if max_rate > 0.0 { 1.0 / max_rate } else { 0.0 };
+    moved > threshold && since_sent >= min_interval
+}
+
+/// Sends players where simulated entities have moved to, leaving out moves too small or too soon to be worth sending
+pub fn sync_entity_positions(
+    world: Res<WorldData>,
+    transport: Res<TransportSystem>,
+    config: Res<ServerConfig>,
+    time: Res<Time>,
+    bodies: Query<&Transform, With<PhysicsBody>>,
+    mut sent: ResMut<SentPositions>,
+    mut send_packet: EventWriter<SendPacket>,
+) {
+    // Entities that have gone no longer need tracking
+    sent.entities
+        .retain(|id, _| world.entities.contains_key(id));
+
+    for (id, entity) in &world.entities {
+        let position = match bodies.get(*entity) {
+            Ok(val) => val.position,
+            Err(_) => continue,
+        };
+
+        // Entities start where they were spawned, which players were already told
+        let (last_position, since_sent) = sent.entities.entry(*id).or_insert((position, 0.0));
+        *since_sent += time.delta_seconds();
+
+        if !should_send_move(
+            (position - *last_position).magnitude(),
+            *since_sent,
+            config.entity_move_threshold,
+            config.entity_move_rate,
+            config.entity_keyframe_interval,
+        ) {
+            continue;
+        }
+        *last_position = position;
+        *since_sent = 0.0;
+
+        let packet = Protocol::EntityMoved(EntityMoved {
+            entity: *id,
+            x: position.x,
+            y: position.y,
+            z: position.z,
+        });
+        for client in transport.clients.keys() {
+            send_packet.send(SendPacket(packet.clone(), *client));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: f32 = 0.05;
+    const RATE: f32 = 20.0;
+    const KEYFRAME: f32 = 5.0;
+
+    fn send(moved: f32, since_sent: f32) -> bool {
+        should_send_move(moved, since_sent, THRESHOLD, RATE, KEYFRAME)
+    }
+
+    #[test]
+    fn small_moves_wait_for_the_threshold() {
+        assert!(!send(0.0, 1.0));
+        assert!(!send(0.04, 1.0));
+        assert!(!send(THRESHOLD, 1.0));
+        assert!(send(0.06, 1.0));
+        assert!(send(3.0, 1.0));
+    }
+
+    #[test]
+    fn moves_are_capped_to_the_rate() {
+        // 20 a second, so a move every 0.05 seconds at most
+        assert!(!send(1.0, 0.0));
+        assert!(!send(1.0, 0.04));
+        assert!(send(1.0, 0.05));
+
+        // 0 sends as often as it moves
+        assert!(should_send_move(1.0, 0.0, THRESHOLD, 0.0, KEYFRAME));
+    }
+
+    #[test]
+    fn idle_entities_are_sent_each_keyframe() {
+        assert!(!send(0.0, 4.9));
+        assert!(send(0.0, KEYFRAME));
+        assert!(send(0.0, 60.0));
+        // Even if the move is too small to send otherwise
+        assert!(send(0.01, KEYFRAME));
+    }
+
+    #[test]
+    fn zero_keyframe_interval_never_resends() {
+        assert!(!should_send_move(0.0, 600.0, THRESHOLD, RATE, 0.0));
+        assert!(should_send_move(1.0, 600.0, THRESHOLD, RATE, 0.0));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::events::console::ConsoleCommandEvent;
+use crate::game::world::data::WorldData;
+use crate::TransportSystem;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::protocol::clientbound::fill_region::{FillRegion, MAX_FILL_VOLUME};
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+
+/// Writes a fill into the world, as long as every chunk it covers is loaded. Returns whether it was written
+pub fn apply_fill(world: &mut WorldData, fill: &FillRegion) -> bool {
+    let spans = fill.chunk_spans(world.chunk_size);
+
+    // Filling only the loaded part would leave the rest to be generated over the fill later
+    if spans
+        .iter()
+        .any(|span| !world.chunks.contains_key(&Vector3::from(span.chunk)))
+    {
+        return false;
+    }
+
+    let block = fill.block();
+    for span in spans {
+        let chunk_loc = Vector3::from(span.chunk);
+        if let Some(chunk) = world.chunks.get_mut(&chunk_loc) {
+            for [x, y, z] in span.positions() {
+                chunk.world.set(x, y, z, block);
+            }
+        }
+        world.modified_chunks.insert(chunk_loc);
+    }
+    true
+}
+
+/// `/fill <x1> <y1> <z1> <x2> <y2> <z2> <block id> [state]`, fills the cuboid between two corners with one block
+pub fn fill_command(
+    mut commands: EventReader<ConsoleCommandEvent>,
+    mut world: ResMut<WorldData>,
+    transport: Res<TransportSystem>,
+    mut send_packets: EventWriter<SendPacket>,
+) {
+    for command in commands.iter() {
+        if command.command != "fill" {
+            continue;
+        }
+
+        let numbers = command
+            .args
+            .iter()
+            .map(|v| v.parse::<i32>())
+            .collect::<Result<Vec<i32>, _>>();
+
+        let fill = match numbers.as_deref() {
+            Ok([x1, y1, z1, x2, y2, z2, id, state @ ..]) if *id >= 0 && state.len() <= 1 => {
+                FillRegion::new(
+                    [*x1, *y1, *z1],
+                    [*x2, *y2, *z2],
+                    *id as u32,
+                    state.first().copied().unwrap_or(0) as u8,
+                )
+            }
+            _ => {
+                warn!("Usage: /fill <x1> <y1> <z1> <x2> <y2> <z2> <block id> [state]");
+                continue;
+            }
+        };
+
+        if !fill.is_valid() {
+            warn!(
+                "Can't fill {} blocks, the most at once is {}",
+                fill.volume(),
+                MAX_FILL_VOLUME
+            );
+            continue;
+        }
+
+        if !apply_fill(&mut world, &fill) {
+            warn!("Can't fill a region that isn't completely loaded");
+            continue;
+        }
+
+        for client in transport.clients.keys() {
+            send_packets.send(SendPacket(Protocol::FillRegion(fill), *client));
+        }
+        info!("Filled {} blocks", fill.volume());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::chunk::{ChunkData, GenerationMode};
+    use rc_networking::constants::CHUNK_SIZE;
+    use std::collections::{HashMap, HashSet};
+
+    const STONE: u32 = 6;
+
+    fn world(chunks: &[[i32; 3]]) -> WorldData {
+        WorldData {
+            chunks: chunks
+                .iter()
+                .map(|v| {
+                    (
+                        Vector3::from(*v),
+                        ChunkData::blank(Vector3::from(*v), CHUNK_SIZE),
+                    )
+                })
+                .collect(),
+            entities: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            chunk_size: CHUNK_SIZE,
+            seed: 0,
+            generation_mode: GenerationMode::Normal,
+        }
+    }
+
+    /// Every filled block in the world, by its world position
+    fn filled(world: &WorldData) -> HashSet<[i32; 3]> {
+        let size = CHUNK_SIZE as i32;
+        let mut filled = HashSet::new();
+        for (position, chunk) in world.chunks.iter() {
+            for x in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for z in 0..CHUNK_SIZE {
+                        if chunk.world.get(x, y, z) != 0 {
+                            filled.insert([
+                                position.x * size + x as i32,
+                                position.y * size + y as i32,
+                                position.z * size + z as i32,
+                            ]);
+                        }
+                    }
+                }
+            }
+        }
+        filled
+    }
+
+    #[test]
+    fn fills_change_exactly_the_enclosed_blocks() {
+        let chunks = [[-1, 0, 0], [0, 0, 0], [-1, 0, 1], [0, 0, 1], [1, 0, 0]];
+        let mut world = world(&chunks);
+        let fill = FillRegion::new([-2, 5, 14], [1, 6, 17], STONE, 0);
+
+        assert!(apply_fill(&mut world, &fill));
+
+        let mut expected = HashSet::new();
+        for x in -2..=1 {
+            for y in 5..=6 {
+                for z in 14..=17 {
+                    expected.insert([x, y, z]);
+                }
+            }
+        }
+        assert_eq!(filled(&world), expected);
+
+        // Only the chunks the fill touched need saving
+        assert_eq!(
+            world.modified_chunks,
+            HashSet::from([
+                Vector3::new(-1, 0, 0),
+                Vector3::new(0, 0, 0),
+                Vector3::new(-1, 0, 1),
+                Vector3::new(0, 0, 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn fills_into_unloaded_chunks_are_refused() {
+        let mut world = world(&[[0, 0, 0]]);
+        let fill = FillRegion::new([14, 0, 0], [17, 0, 0], STONE, 0);
+
+        assert!(!apply_fill(&mut world, &fill));
+        assert!(filled(&world).is_empty());
+        assert!(world.modified_chunks.is_empty());
+    }
+}
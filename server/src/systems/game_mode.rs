@@ -0,0 +1,342 @@
+use crate::events::console::ConsoleCommandEvent;
+use crate::systems::authorization::GameUser;
+use crate::TransportSystem;
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::GameMode;
+use rc_networking::protocol::clientbound::set_game_mode::SetGameMode;
+use rc_networking::protocol::serverbound::block_break_progress::BlockBreakProgress;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+use std::time::Instant;
+
+/// How much sooner than a block's hardness a break is accepted, for the client starting its timer a frame before it
+/// reports mining and packets taking longer to arrive than each other
+const MINING_LENIENCY: f32 = 0.1;
+
+/// Changes a player's game mode and tells them about it
+pub fn set_game_mode(
+    user: &mut GameUser,
+    game_mode: GameMode,
+    send_packets: &mut EventWriter<SendPacket>,
+) {
+    user.game_mode = game_mode;
+    send_packets.send(SendPacket(
+        Protocol::SetGameMode(SetGameMode::new(game_mode)),
+        user.user_id,
+    ));
+}
+
+/// Whether a block break came too soon after the player's last one. Only creative players break blocks instantly,
+/// anyone else has to spend time mining each block
+pub fn breaking_too_fast(
+    game_mode: GameMode,
+    last_break: Option<Instant>,
+    now: Instant,
+    min_interval: f32,
+) -> bool {
+    if game_mode.instant_break() {
+        return false;
+    }
+
+    last_break.map_or(false, |v| {
+        now.saturating_duration_since(v).as_secs_f32() < min_interval
+    })
+}
+
+/// Where a player is mining once they've reported progress on a block. Progress on the same block keeps when they
+/// started mining it, so only the time spent on it counts, not how far they say they've got
+pub fn update_mining(
+    mining: Option<(Vector3<i32>, Instant)>,
+    progress: &BlockBreakProgress,
+    now: Instant,
+) -> Option<(Vector3<i32>, Instant)> {
+    let position = Vector3::new(progress.x, progress.y, progress.z);
+    match mining {
+        _ if progress.stopped() => None,
+        Some((target, started)) if target == position => Some((target, started)),
+        _ => Some((position, now)),
+    }
+}
+
+/// Whether a block is broken before the player has mined it for its hardness, in seconds, since they said they
+/// started. Creative players break blocks without mining them
+pub fn breaking_unmined(
+    game_mode: GameMode,
+    mining: Option<(Vector3<i32>, Instant)>,
+    position: Vector3<i32>,
+    now: Instant,
+    hardness: f32,
+) -> bool {
+    if game_mode.instant_break() {
+        return false;
+    }
+
+    match mining {
+        Some((target, started)) if target == position => {
+            let mined = now.saturating_duration_since(started).as_secs_f32();
+            mined + MINING_LENIENCY < hardness
+        }
+        _ => true,
+    }
+}
+
+/// `/gamemode <survival|creative|spectator> <player>`, where the player is the name they connected with or their user id
+pub fn game_mode_command(
+    mut commands: EventReader<ConsoleCommandEvent>,
+    mut transport: ResMut<TransportSystem>,
+    mut send_packets: EventWriter<SendPacket>,
+) {
+    for command in commands.iter() {
+        if command.command != "gamemode" {
+            continue;
+        }
+
+        let (game_mode, player) = match command.args.as_slice() {
+            [game_mode, player] => (game_mode, player),
+            _ => {
+                warn!("Usage: /gamemode <survival|creative|spectator> <player>");
+                continue;
+            }
+        };
+
+        let game_mode = match game_mode.parse::<GameMode>() {
+            Ok(val) => val,
+            Err(_) => {
+                warn!("Unknown game mode {}", game_mode);
+                continue;
+            }
+        };
+
+        let user = transport.clients.values_mut().find(|user| {
+            user.name.as_deref() == Some(player.as_str())
+                || player.parse::<u64>() == Ok(user.user_id.0)
+        });
+
+        match user {
+            Some(user) => {
+                set_game_mode(user, game_mode, &mut send_packets);
+                info!("Set {} to {:?}", player, game_mode);
+            }
+            None => warn!("No player called {}", player),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::authorization::testing;
+    use crate::ServerConfig;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::UserId;
+    use std::time::Duration;
+
+    /// Runs a console command against two survival players, returning the world afterwards
+    fn run_command(line: &str) -> World {
+        let mut transport = TransportSystem::default();
+        transport
+            .clients
+            .insert(UserId(1), GameUser::named(1, "alice"));
+        transport
+            .clients
+            .insert(UserId(2), GameUser::named(2, "bob"));
+
+        let mut world = World::new();
+        world.insert_resource(transport);
+        world.init_resource::<Events<SendPacket>>();
+        run(&mut world, line);
+        world
+    }
+
+    fn run(world: &mut World, line: &str) {
+        world.init_resource::<Events<ConsoleCommandEvent>>();
+        world
+            .resource_mut::<Events<ConsoleCommandEvent>>()
+            .send(ConsoleCommandEvent::parse(line).unwrap());
+
+        SystemStage::single_threaded()
+            .with_system(game_mode_command)
+            .run(world);
+    }
+
+    fn game_mode(world: &World, id: u64) -> GameMode {
+        world.resource::<TransportSystem>().clients[&UserId(id)].game_mode
+    }
+
+    fn sent(world: &World) -> Vec<(Protocol, UserId)> {
+        world
+            .resource::<Events<SendPacket>>()
+            .iter_current_update_events()
+            .map(|v| (v.0.clone(), v.1))
+            .collect()
+    }
+
+    #[test]
+    fn command_changes_only_that_player() {
+        let world = run_command("/gamemode creative alice");
+
+        assert_eq!(game_mode(&world, 1), GameMode::Creative);
+        assert_eq!(game_mode(&world, 2), GameMode::Survival);
+        assert_eq!(
+            sent(&world),
+            vec![(
+                Protocol::SetGameMode(SetGameMode::new(GameMode::Creative)),
+                UserId(1)
+            )]
+        );
+    }
+
+    #[test]
+    fn command_finds_players_by_user_id() {
+        let world = run_command("/gamemode Spectator 2");
+
+        assert_eq!(game_mode(&world, 2), GameMode::Spectator);
+        assert_eq!(sent(&world).len(), 1);
+    }
+
+    #[test]
+    fn command_finds_players_by_the_name_they_connected_with() {
+        let mut world = testing::world(ServerConfig::default(), &[1, 2]);
+        testing::authorize(&mut world, 1, Some("alice"));
+        testing::authorize(&mut world, 2, None);
+
+        run(&mut world, "/gamemode creative alice");
+
+        assert_eq!(game_mode(&world, 1), GameMode::Creative);
+        assert_eq!(game_mode(&world, 2), GameMode::Survival);
+    }
+
+    #[test]
+    fn bad_commands_change_nothing() {
+        for line in [
+            "/gamemode flying alice",
+            "/gamemode creative nobody",
+            "/gamemode creative",
+        ] {
+            let world = run_command(line);
+
+            assert_eq!(game_mode(&world, 1), GameMode::Survival, "{}", line);
+            assert!(sent(&world).is_empty(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn only_creative_players_break_instantly() {
+        let now = Instant::now();
+        let last_break = now.checked_sub(Duration::from_millis(100));
+
+        assert!(breaking_too_fast(GameMode::Survival, last_break, now, 0.2));
+        assert!(!breaking_too_fast(
+            GameMode::Survival,
+            last_break,
+            now,
+            0.05
+        ));
+        assert!(!breaking_too_fast(GameMode::Survival, None, now, 0.2));
+        assert!(!breaking_too_fast(GameMode::Creative, last_break, now, 0.2));
+
+        let position = Vector3::new(1, 2, 3);
+        let mined = now
+            .checked_sub(Duration::from_secs(2))
+            .map(|v| (position, v));
+        assert!(breaking_unmined(
+            GameMode::Survival,
+            None,
+            position,
+            now,
+            1.5
+        ));
+        assert!(!breaking_unmined(
+            GameMode::Survival,
+            mined,
+            position,
+            now,
+            1.5
+        ));
+        assert!(!breaking_unmined(
+            GameMode::Creative,
+            None,
+            position,
+            now,
+            1.5
+        ));
+    }
+
+    #[test]
+    fn spectators_dont_break_instantly() {
+        let now = Instant::now();
+        let last_break = now.checked_sub(Duration::from_millis(100));
+
+        assert!(breaking_too_fast(GameMode::Spectator, last_break, now, 0.2));
+        assert!(breaking_unmined(
+            GameMode::Spectator,
+            None,
+            Vector3::new(1, 2, 3),
+            now,
+            1.5
+        ));
+    }
+
+    fn progress(position: Vector3<i32>, progress: f32) -> BlockBreakProgress {
+        BlockBreakProgress::new(position.x, position.y, position.z, progress)
+    }
+
+    #[test]
+    fn breaks_sent_with_the_first_progress_are_rejected() {
+        let now = Instant::now();
+        let position = Vector3::new(1, 2, 3);
+
+        // Claiming to be nearly done doesn't skip the time it takes to mine
+        let mining = update_mining(None, &progress(position, 0.9), now);
+        assert!(breaking_unmined(
+            GameMode::Survival,
+            mining,
+            position,
+            now,
+            1.5
+        ));
+
+        // Blocks without hardness still break straight away
+        assert!(!breaking_unmined(
+            GameMode::Survival,
+            mining,
+            position,
+            now,
+            0.0
+        ));
+    }
+
+    #[test]
+    fn blocks_break_once_mined_for_their_hardness() {
+        let start = Instant::now();
+        let position = Vector3::new(1, 2, 3);
+        let after = |secs| start + Duration::from_secs_f32(secs);
+
+        let mut mining = update_mining(None, &progress(position, 0.1), start);
+        mining = update_mining(mining, &progress(position, 0.5), after(0.7));
+        assert!(breaking_unmined(
+            GameMode::Survival,
+            mining,
+            position,
+            after(1.0),
+            1.5
+        ));
+        assert!(!breaking_unmined(
+            GameMode::Survival,
+            mining,
+            position,
+            after(1.5),
+            1.5
+        ));
+
+        // Moving to another block or stopping starts again
+        let other = update_mining(mining, &progress(Vector3::new(1, 3, 3), 0.1), after(1.5));
+        assert_eq!(other, Some((Vector3::new(1, 3, 3), after(1.5))));
+        assert_eq!(
+            update_mining(mining, &progress(position, 0.0), after(1.5)),
+            None
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use crate::events::console::ConsoleCommandEvent;
+use crate::systems::authorization::GameUser;
+use crate::{ServerConfig, TransportSystem};
+use bevy::prelude::*;
+use rc_networking::constants::UserId;
+use rc_networking::protocol::clientbound::disconnect::{Disconnect, DisconnectReason};
+use rc_networking::protocol::Protocol;
+use rc_networking::types::{ReceivePacket, SendPacket};
+use rc_networking::Server;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long idle players are given to receive why they're being disconnected before they are
+const DISCONNECT_GRACE: Duration = Duration::from_millis(500);
+
+/// Whether a player has gone too long without doing anything. A timeout of 0 never counts anyone as idle
+pub fn is_idle(last_input: Instant, now: Instant, timeout: u64, exempt: bool) -> bool {
+    if timeout == 0 || exempt {
+        return false;
+    }
+    now.saturating_duration_since(last_input) > Duration::from_secs(timeout)
+}
+
+/// The players who've gone too long without doing anything
+pub fn idle_players(
+    clients: &HashMap<UserId, GameUser>,
+    timeout: u64,
+    now: Instant,
+) -> impl Iterator<Item = UserId> + '_ {
+    clients
+        .iter()
+        .filter(move |(_, user)| is_idle(user.last_input, now, timeout, user.idle_exempt))
+        .map(|(id, _)| *id)
+}
+
+/// Notes when players last moved, looked around, edited blocks or chatted.
+/// Keep-alives and chunk requests are sent by the game on its own, so they don't count
+pub fn track_player_input(
+    mut packets: EventReader<ReceivePacket>,
+    mut transport: ResMut<TransportSystem>,
+) {
+    for packet in packets.iter() {
+        match packet.0 {
+            Protocol::PlayerMove(_)
+            | Protocol::PlayerRotate(_)
+            | Protocol::BlockEdit(_)
+            | Protocol::BlockBreakProgress(_)
+            | Protocol::ChatSent(_)
+            | Protocol::SpawnPoint(_) => {}
+            _ => continue,
+        }
+
+        if let Some(user) = transport.clients.get_mut(&packet.1) {
+            user.last_input = Instant::now();
+        }
+    }
+}
+
+/// Tells idle players why they're being disconnected, then disconnects them to free up their slot
+pub fn disconnect_idle_players(
+    transport: Res<TransportSystem>,
+    config: Res<ServerConfig>,
+    mut server: ResMut<Server>,
+    mut send_packets: EventWriter<SendPacket>,
+    mut disconnecting: Local<HashMap<UserId, Instant>>,
+) {
+    let now = Instant::now();
+
+    disconnecting.retain(|user, since| {
+        if now.saturating_duration_since(*since) < DISCONNECT_GRACE {
+            return true;
+        }
+        server.disconnect(user.0);
+        false
+    });
+
+    for id in idle_players(&transport.clients, config.idle_timeout, now) {
+        if disconnecting.contains_key(&id) {
+            continue;
+        }
+
+        info!(
+            "Disconnecting {:?} for being idle longer than {}s",
+            id, config.idle_timeout
+        );
+        send_packets.send(SendPacket(
+            Protocol::Disconnect(Disconnect::new(DisconnectReason::Idle)),
+            id,
+        ));
+        disconnecting.insert(id, now);
+    }
+}
+
+/// `/afk <player>`, toggles whether a player is kept connected however long they're idle. The player is the name
+/// they connected with or their user id
+pub fn afk_command(
+    mut commands: EventReader<ConsoleCommandEvent>,
+    mut transport: ResMut<TransportSystem>,
+) {
+    for command in commands.iter() {
+        if command.command != "afk" {
+            continue;
+        }
+
+        let player = match command.args.as_slice() {
+            [player] => player,
+            _ => {
+                warn!("Usage: /afk <player>");
+                continue;
+            }
+        };
+
+        let user = transport.clients.values_mut().find(|user| {
+            user.name.as_deref() == Some(player.as_str())
+                || player.parse::<u64>() == Ok(user.user_id.0)
+        });
+
+        match user {
+            Some(user) => {
+                user.idle_exempt = !user.idle_exempt;
+                if user.idle_exempt {
+                    info!("{} won't be disconnected for being idle", player);
+                } else {
+                    info!("{} can be disconnected for being idle again", player);
+                }
+            }
+            None => warn!("No player called {}", player),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::authorization::testing;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::protocol::clientbound::keep_alive::KeepAlive;
+    use rc_networking::protocol::serverbound::player_move::PlayerMove;
+
+    const TIMEOUT: u64 = 60;
+
+    fn user(id: u64, name: &str, last_input: Instant) -> GameUser {
+        let mut user = GameUser::named(id, name);
+        user.last_input = last_input;
+        user
+    }
+
+    fn idle(clients: &HashMap<UserId, GameUser>, now: Instant) -> Vec<UserId> {
+        idle_players(clients, TIMEOUT, now).collect()
+    }
+
+    #[test]
+    fn players_are_idle_after_the_timeout() {
+        let start = Instant::now();
+        let after = |secs| start + Duration::from_secs(secs);
+
+        assert!(!is_idle(start, after(TIMEOUT), TIMEOUT, false));
+        assert!(is_idle(start, after(TIMEOUT + 1), TIMEOUT, false));
+        // Input newer than the time being checked isn't idle
+        assert!(!is_idle(after(5), start, TIMEOUT, false));
+    }
+
+    #[test]
+    fn no_timeout_or_exemption_is_never_idle() {
+        let start = Instant::now();
+        let later = start + Duration::from_secs(TIMEOUT * 100);
+
+        assert!(!is_idle(start, later, 0, false));
+        assert!(!is_idle(start, later, TIMEOUT, true));
+    }
+
+    #[test]
+    fn only_idle_players_are_disconnected() {
+        let start = Instant::now();
+        let now = start + Duration::from_secs(TIMEOUT + 10);
+
+        let mut clients = HashMap::new();
+        clients.insert(UserId(1), user(1, "idle", start));
+        clients.insert(UserId(2), user(2, "active", now - Duration::from_secs(5)));
+        let mut exempt = user(3, "afk", start);
+        exempt.idle_exempt = true;
+        clients.insert(UserId(3), exempt);
+
+        assert_eq!(idle(&clients, now), vec![UserId(1)]);
+    }
+
+    fn transport(last_input: Instant) -> TransportSystem {
+        let mut transport = TransportSystem::default();
+        for (id, name) in [(1, "mover"), (2, "pinger")] {
+            transport
+                .clients
+                .insert(UserId(id), user(id, name, last_input));
+        }
+        transport
+    }
+
+    #[test]
+    fn only_player_input_keeps_players_active() {
+        let start = Instant::now() - Duration::from_secs(1);
+        let mut world = World::new();
+        world.insert_resource(transport(start));
+        world.init_resource::<Events<ReceivePacket>>();
+
+        let mut packets = world.resource_mut::<Events<ReceivePacket>>();
+        packets.send(ReceivePacket(
+            Protocol::PlayerMove(PlayerMove::new(1.0, 2.0, 3.0)),
+            UserId(1),
+        ));
+        // Keep-alives are answered by the game on its own
+        packets.send(ReceivePacket(
+            Protocol::KeepAlive(KeepAlive::new(0)),
+            UserId(2),
+        ));
+
+        SystemStage::single_threaded()
+            .with_system(track_player_input)
+            .run(&mut world);
+
+        let clients = &world.resource::<TransportSystem>().clients;
+        assert!(clients[&UserId(1)].last_input > start);
+        assert_eq!(clients[&UserId(2)].last_input, start);
+    }
+
+    fn afk(world: &mut World, player: &str) {
+        world
+            .resource_mut::<Events<ConsoleCommandEvent>>()
+            .send(ConsoleCommandEvent {
+                command: "afk".to_string(),
+                args: vec![player.to_string()],
+            });
+        SystemStage::single_threaded()
+            .with_system(afk_command)
+            .run(world);
+        world.resource_mut::<Events<ConsoleCommandEvent>>().clear();
+    }
+
+    fn exempt(world: &World, id: u64) -> bool {
+        world.resource::<TransportSystem>().clients[&UserId(id)].idle_exempt
+    }
+
+    #[test]
+    fn afk_toggles_the_exemption() {
+        let mut world = World::new();
+        world.insert_resource(transport(Instant::now()));
+        world.init_resource::<Events<ConsoleCommandEvent>>();
+
+        afk(&mut world, "mover");
+        assert!(exempt(&world, 1));
+        assert!(!exempt(&world, 2));
+
+        // Players can also be picked by id
+        afk(&mut world, "1");
+        assert!(!exempt(&world, 1));
+
+        afk(&mut world, "nobody");
+        assert!(!exempt(&world, 1));
+        assert!(!exempt(&world, 2));
+    }
+
+    #[test]
+    fn afk_finds_players_by_the_name_they_connected_with() {
+        let mut world = testing::world(ServerConfig::default(), &[1, 2]);
+        testing::authorize(&mut world, 1, Some("mover"));
+        testing::authorize(&mut world, 2, None);
+        world.init_resource::<Events<ConsoleCommandEvent>>();
+
+        afk(&mut world, "mover");
+        assert!(exempt(&world, 1));
+        assert!(!exempt(&world, 2));
+    }
+}
@@ -1,25 +1,46 @@
-use crate::game::chunk::ChunkData;
+use crate::events::block_break::BlockBrokenEvent;
+use crate::game::collision::CollisionShapes;
+use crate::game::entity::{player_bounds, PhysicsBody};
+use crate::game::hardness::BlockHardness;
 use crate::game::transform::Transform;
-use crate::helpers::global_to_local_position;
-use crate::{TransportSystem, WorldData};
+use crate::helpers::{global_to_local_position, within_reach};
+use crate::systems::block_updates::PendingBlockUpdates;
+use crate::systems::game_mode::{breaking_too_fast, breaking_unmined, update_mining};
+use crate::systems::sound::broadcast_sound;
+use crate::systems::spawn_protection::{
+    edit_blocked_by_spawn_protection, SPAWN_PROTECTION_MESSAGE,
+};
+use crate::{ServerConfig, TransportSystem, WorldData};
 use bevy::ecs::event::{EventReader, EventWriter};
 use bevy::ecs::prelude::*;
 use bevy::ecs::system::ResMut;
-use bevy::log::info;
+use bevy::log::{info, warn};
 use nalgebra::{Quaternion, Vector3};
-use rc_networking::constants::CHUNK_SIZE;
+use rc_networking::constants::block_id;
+use rc_networking::protocol::clientbound::block_edit_ack::BlockEditAck;
 use rc_networking::protocol::clientbound::block_update::BlockUpdate;
+use rc_networking::protocol::clientbound::chat::ChatSent;
 use rc_networking::protocol::clientbound::entity_moved::EntityMoved;
 use rc_networking::protocol::clientbound::entity_rotated::EntityRotated;
+use rc_networking::protocol::clientbound::play_sound::{PlaySound, Sound};
 use rc_networking::protocol::Protocol;
 use rc_networking::types::{ReceivePacket, SendPacket};
+use rc_networking::Server;
+use std::time::Instant;
 
 pub fn receive_message_event(
     mut event_reader: EventReader<ReceivePacket>,
     mut event_writer: EventWriter<SendPacket>,
     mut global: ResMut<WorldData>,
-    system: Res<TransportSystem>,
+    mut system: ResMut<TransportSystem>,
     mut transforms: Query<&mut Transform>,
+    bodies: Query<(Entity, &PhysicsBody)>,
+    shapes: Res<CollisionShapes>,
+    hardness: Res<BlockHardness>,
+    mut server: ResMut<Server>,
+    config: Res<ServerConfig>,
+    mut block_updates: ResMut<PendingBlockUpdates>,
+    mut broken_blocks: EventWriter<BlockBrokenEvent>,
 ) {
     for event in event_reader.iter() {
         match &event.0 {
@@ -77,38 +98,207 @@ pub fn receive_message_event(
                         Quaternion::new(packet.x, packet.y, packet.z, packet.w);
                 }
             }
-            Protocol::BlockUpdate(packet) => {
-                // TODO: Don't trust user input
+            Protocol::BlockEdit(packet) => {
+                let position = Vector3::new(packet.x, packet.y, packet.z);
+                let (chunk_loc, inner_loc) = global_to_local_position(position, global.chunk_size);
 
-                let packet = BlockUpdate::new(packet.id, packet.x, packet.y, packet.z);
+                // Only allow edits within reach of the player in loaded chunks
+                let user = match system.clients.get_mut(&event.1) {
+                    Some(val) => val,
+                    None => continue,
+                };
+                let player_position = global
+                    .entities
+                    .get(&user.entity_id)
+                    .and_then(|v| transforms.get(*v).ok())
+                    .map(|v| v.position);
 
-                for (client, _) in &system.clients {
-                    if *client == event.1 {
+                let allowed = global.chunks.contains_key(&chunk_loc)
+                    && player_position
+                        .map_or(false, |v| within_reach(v, position, config.max_reach));
+                let can_edit = user.game_mode.can_edit_blocks();
+
+                // The block being replaced, which a rejected edit is reverted back to
+                let previous = global
+                    .chunks
+                    .get(&chunk_loc)
+                    .map_or(0, |v| v.world.get(inner_loc.x, inner_loc.y, inner_loc.z));
+
+                if !allowed || !can_edit {
+                    // Spectators can't edit, but aren't cheating by trying to
+                    if can_edit {
+                        user.reach_violations += 1;
+                    }
+                    warn!(
+                        "Rejected block update at {:?} from {:?} ({} violations)",
+                        position, event.1, user.reach_violations
+                    );
+
+                    // Tell the client what the block really is so it can revert the edit
+                    event_writer.send(SendPacket(
+                        Protocol::BlockEditAck(BlockEditAck::new(packet.sequence, false, previous)),
+                        event.1,
+                    ));
+
+                    if config.reach_kick_threshold != 0
+                        && user.reach_violations >= config.reach_kick_threshold
+                    {
+                        warn!("Kicking {:?} for editing blocks out of reach", event.1);
+                        server.disconnect(event.1 .0);
+                    }
+                    continue;
+                }
+
+                // The world spawn is where players without a spawn point of their own appear
+                if edit_blocked_by_spawn_protection(
+                    &config,
+                    user,
+                    position,
+                    Transform::default().position,
+                ) {
+                    info!(
+                        "Rejected block edit inside spawn protection at {:?} from {:?}",
+                        position, event.1
+                    );
+                    event_writer.send(SendPacket(
+                        Protocol::BlockEditAck(BlockEditAck::new(packet.sequence, false, previous)),
+                        event.1,
+                    ));
+                    event_writer.send(SendPacket(
+                        Protocol::ChatSent(ChatSent {
+                            message: SPAWN_PROTECTION_MESSAGE.to_string(),
+                        }),
+                        event.1,
+                    ));
+                    continue;
+                }
+
+                let breaking = block_id(packet.id) == 0;
+                if breaking {
+                    let now = Instant::now();
+                    if breaking_unmined(
+                        user.game_mode,
+                        user.mining,
+                        position,
+                        now,
+                        hardness.hardness(previous),
+                    ) {
+                        warn!(
+                            "Rejected block break at {:?} from {:?} that wasn't mined for long enough",
+                            position, event.1
+                        );
+                        event_writer.send(SendPacket(
+                            Protocol::BlockEditAck(BlockEditAck::new(
+                                packet.sequence,
+                                false,
+                                previous,
+                            )),
+                            event.1,
+                        ));
                         continue;
                     }
-                    info!("Block update packet sent to {:?}", client);
-                    event_writer.send(SendPacket(Protocol::BlockUpdate(packet), *client));
+
+                    if breaking_too_fast(
+                        user.game_mode,
+                        user.last_break,
+                        now,
+                        config.min_break_interval,
+                    ) {
+                        warn!(
+                            "Rejected instant block break at {:?} from {:?} in {:?}",
+                            position, event.1, user.game_mode
+                        );
+                        event_writer.send(SendPacket(
+                            Protocol::BlockEditAck(BlockEditAck::new(
+                                packet.sequence,
+                                false,
+                                previous,
+                            )),
+                            event.1,
+                        ));
+                        continue;
+                    }
+                    user.last_break = Some(now);
+                    user.mining = None;
                 }
 
-                let (chunk_loc, inner_loc) =
-                    global_to_local_position(Vector3::new(packet.x, packet.y, packet.z));
+                // A new block can't be placed where it would trap a player or entity inside it
+                let placing = !breaking && block_id(packet.id) != block_id(previous);
+                if placing {
+                    let players = system
+                        .clients
+                        .values()
+                        .filter_map(|v| global.entities.get(&v.entity_id))
+                        .filter_map(|v| transforms.get(*v).ok())
+                        .map(|v| player_bounds(v.position));
+                    let entities = bodies.iter().filter_map(|(entity, body)| {
+                        transforms.get(entity).ok().map(|v| body.bounds(v.position))
+                    });
 
-                // Store
-                if let Some(mut chunk) = global.chunks.get_mut(&chunk_loc) {
-                    // Found chunk! Update block
-                    chunk.world[inner_loc.x][inner_loc.y][inner_loc.z] = packet.id;
+                    let occupied = players
+                        .chain(entities)
+                        .any(|(min, max)| shapes.block_overlaps(packet.id, position, min, max));
+                    if occupied {
+                        info!(
+                            "Rejected block placed inside an entity at {:?} from {:?}",
+                            position, event.1
+                        );
+                        event_writer.send(SendPacket(
+                            Protocol::BlockEditAck(BlockEditAck::new(
+                                packet.sequence,
+                                false,
+                                previous,
+                            )),
+                            event.1,
+                        ));
+                        continue;
+                    }
+                }
+
+                event_writer.send(SendPacket(
+                    Protocol::BlockEditAck(BlockEditAck::new(packet.sequence, true, packet.id)),
+                    event.1,
+                ));
+
+                // Breaking leaves air, and a block changing to itself with another state is being used
+                let sound = if breaking {
+                    Sound::BlockBreak
+                } else if block_id(packet.id) == block_id(previous) {
+                    Sound::BlockUse
                 } else {
-                    // Create chunk data
-                    let mut chunk = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+                    Sound::BlockPlace
+                };
+                let center = position.cast::<f32>() + Vector3::new(0.5, 0.5, 0.5);
+                broadcast_sound(
+                    PlaySound::new(sound, center.x, center.y, center.z),
+                    config.sound_range,
+                    &system,
+                    &global,
+                    &transforms,
+                    &mut event_writer,
+                );
 
-                    // Set block
-                    chunk[inner_loc.x][inner_loc.y][inner_loc.z] = packet.id;
+                let packet = BlockUpdate::new(packet.id, packet.x, packet.y, packet.z);
+                block_updates.push(chunk_loc, packet, event.1);
 
-                    // Create chunk
-                    global
-                        .chunks
-                        .insert(chunk_loc, ChunkData::new(chunk_loc, chunk));
+                // Store, the chunk is known to be loaded
+                if let Some(mut chunk) = global.chunks.get_mut(&chunk_loc) {
+                    chunk
+                        .world
+                        .set(inner_loc.x, inner_loc.y, inner_loc.z, packet.id);
                 }
+                global.modified_chunks.insert(chunk_loc);
+
+                if breaking {
+                    broken_blocks.send(BlockBrokenEvent::new(event.1, position, previous));
+                }
+            }
+            Protocol::BlockBreakProgress(packet) => {
+                let user = match system.clients.get_mut(&event.1) {
+                    Some(val) => val,
+                    None => continue,
+                };
+                user.mining = update_mining(user.mining, packet, Instant::now());
             }
             _ => {}
         }
@@ -1,6 +1,18 @@
 pub mod authorization;
+pub mod block_updates;
+pub mod chat;
 pub mod chunk;
 pub mod connection;
 pub mod disconnect;
+pub mod drops;
+pub mod entity_sync;
+pub mod fill;
+pub mod game_mode;
+pub mod idle;
 pub mod message;
+pub mod physics;
+pub mod pickup;
+pub mod sound;
+pub mod spawn_point;
+pub mod spawn_protection;
 pub mod tick;
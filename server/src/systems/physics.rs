@@ -0,0 +1,326 @@
+use crate::game::collision::CollisionShapes;
+use crate::game::entity::PhysicsBody;
+use crate::game::transform::Transform;
+use crate::game::world::simulation::SimulatedArea;
+use crate::WorldData;
+use bevy::ecs::prelude::*;
+use bevy::time::Time;
+use nalgebra::Vector3;
+
+/// Downwards acceleration in blocks per second squared
+pub const GRAVITY: f32 = 32.0;
+
+/// The fastest an entity can fall, keeps entities from moving more than a block per step
+pub const TERMINAL_VELOCITY: f32 = 50.0;
+
+/// The longest step simulated at once, long frames are split into multiple steps
+const MAX_STEP: f32 = 1.0 / 60.0;
+
+/// The boxes of every block overlapping the bounds, or None if part of them is in an unloaded chunk
+fn overlapping_boxes(
+    world: &WorldData,
+    shapes: &CollisionShapes,
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+) -> Option<Vec<(Vector3<f32>, Vector3<f32>)>> {
+    // Shrink slightly so resting exactly on a box's face doesn't count as overlapping
+    let min = min + Vector3::new(0.001, 0.001, 0.001);
+    let max = max - Vector3::new(0.001, 0.001, 0.001);
+
+    let from = min.map(|v| v.floor() as i32);
+    let to = max.map(|v| v.floor() as i32);
+
+    let mut boxes = Vec::new();
+    for x in from.x..=to.x {
+        // Check a block lower too, as shapes like fences reach up into the block above
+        for y in (from.y - 1)..=to.y {
+            for z in from.z..=to.z {
+                let position = Vector3::new(x, y, z);
+                let block = world.get_block(position)?;
+
+                for (box_min, box_max) in shapes.boxes(block) {
+                    let box_min = box_min + position.cast::<f32>();
+                    let box_max = box_max + position.cast::<f32>();
+
+                    let overlaps = (0..3).all(|i| box_min[i] < max[i] && box_max[i] > min[i]);
+                    if overlaps {
+                        boxes.push((box_min, box_max));
+                    }
+                }
+            }
+        }
+    }
+    Some(boxes)
+}
+
+/// Whether any block's collision shape overlaps the bounds, or None if part of them is in an unloaded chunk
+fn collides(
+    world: &WorldData,
+    shapes: &CollisionShapes,
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+) -> Option<bool> {
+    overlapping_boxes(world, shapes, min, max).map(|boxes| !boxes.is_empty())
+}
+
+/// Advances a body by one step, moving each axis separately so it can slide along walls.
+/// Returns false if the body is near unloaded chunks and was frozen in place
+pub fn step_body(
+    world: &WorldData,
+    shapes: &CollisionShapes,
+    body: &mut PhysicsBody,
+    position: &mut Vector3<f32>,
+    delta: f32,
+) -> bool {
+    let (min, max) = body.bounds(*position);
+    if collides(
+        world,
+        shapes,
+        min - Vector3::new(1.0, 1.0, 1.0),
+        max + Vector3::new(1.0, 1.0, 1.0),
+    )
+    .is_none()
+    {
+        return false;
+    }
+
+    body.velocity.y = (body.velocity.y - GRAVITY * delta).max(-TERMINAL_VELOCITY);
+    body.on_ground = false;
+
+    for axis in 0..3 {
+        let movement = body.velocity[axis] * delta;
+        if movement == 0.0 {
+            continue;
+        }
+
+        let mut moved = *position;
+        moved[axis] += movement;
+
+        let (min, max) = body.bounds(moved);
+        let boxes = match overlapping_boxes(world, shapes, min, max) {
+            Some(val) => val,
+            None => continue,
+        };
+        if boxes.is_empty() {
+            *position = moved;
+            continue;
+        }
+
+        // Snap against the nearest face of the boxes that were hit
+        if movement > 0.0 {
+            let face = boxes
+                .iter()
+                .map(|(min, _)| min[axis])
+                .fold(f32::MAX, f32::min);
+            position[axis] = face - (max[axis] - moved[axis]);
+        } else {
+            let face = boxes
+                .iter()
+                .map(|(_, max)| max[axis])
+                .fold(f32::MIN, f32::max);
+            position[axis] = face + (moved[axis] - min[axis]);
+            if axis == 1 {
+                body.on_ground = true;
+            }
+        }
+        body.velocity[axis] = 0.0;
+    }
+
+    true
+}
+
+pub fn entity_physics(
+    world: Res<WorldData>,
+    shapes: Res<CollisionShapes>,
+    area: Res<SimulatedArea>,
+    mut bodies: Query<(&mut PhysicsBody, &mut Transform)>,
+    time: Res<Time>,
+) {
+    let mut delta = time.delta_seconds();
+    let mut steps = Vec::new();
+    while delta > 0.0 {
+        steps.push(delta.min(MAX_STEP));
+        delta -= MAX_STEP;
+    }
+
+    for entity in world.entities.values() {
+        let (mut body, mut transform) = match bodies.get_mut(*entity) {
+            Ok(val) => val,
+            Err(_) => continue,
+        };
+
+        // Entities away from every player are frozen, keeping their velocity for when a player returns
+        if !area.is_position_simulated(transform.position) {
+            continue;
+        }
+
+        // Resting bodies don't need simulating until something moves them
+        if body.on_ground && body.velocity.magnitude() == 0.0 {
+            let (min, max) = body.bounds(transform.position);
+            let below = Vector3::new(0.0, 0.01, 0.0);
+            if collides(&world, &shapes, min - below, max - below) != Some(false) {
+                continue;
+            }
+        }
+
+        let start = transform.position;
+        let mut position = start;
+        for step in &steps {
+            if !step_body(&world, &shapes, &mut body, &mut position, *step) {
+                break;
+            }
+        }
+
+        if position == start {
+            continue;
+        }
+        transform.position = position;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::chunk::{ChunkData, GenerationMode};
+    use crate::game::collision::CollisionShape;
+    use rc_networking::constants::pack_block;
+    use rc_networking::constants::CHUNK_SIZE;
+    use std::collections::{HashMap, HashSet};
+
+    const STONE: u32 = 6;
+
+    /// A world of just the chunk at the origin, with a stone floor at `floor`
+    fn world_with_floor(floor: usize) -> WorldData {
+        let mut chunk = ChunkData::blank(Vector3::zeros(), CHUNK_SIZE);
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.world.set(x, floor, z, STONE);
+            }
+        }
+
+        WorldData {
+            chunks: HashMap::from([(Vector3::zeros(), chunk)]),
+            entities: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            chunk_size: CHUNK_SIZE,
+            seed: 0,
+            generation_mode: GenerationMode::Normal,
+        }
+    }
+
+    #[test]
+    fn entity_falls_onto_the_floor() {
+        let world = world_with_floor(4);
+        let shapes = CollisionShapes::default();
+        let mut body = PhysicsBody::new(0.125, 0.25);
+        let mut position = Vector3::new(8.5, 10.0, 8.5);
+
+        for _ in 0..120 {
+            assert!(step_body(
+                &world,
+                &shapes,
+                &mut body,
+                &mut position,
+                MAX_STEP
+            ));
+        }
+
+        // Resting on top of the floor
+        assert_eq!(position, Vector3::new(8.5, 5.0, 8.5));
+        assert!(body.on_ground);
+        assert_eq!(body.velocity.y, 0.0);
+    }
+
+    #[test]
+    fn entity_near_unloaded_chunks_is_frozen() {
+        let world = world_with_floor(4);
+        let shapes = CollisionShapes::default();
+        let mut body = PhysicsBody::new(0.125, 0.25);
+        let mut position = Vector3::new(15.5, 10.0, 8.5);
+
+        assert!(!step_body(
+            &world,
+            &shapes,
+            &mut body,
+            &mut position,
+            MAX_STEP
+        ));
+        assert_eq!(position, Vector3::new(15.5, 10.0, 8.5));
+        assert_eq!(body.velocity.y, 0.0);
+    }
+
+    const SLAB: u32 = 20;
+    const FENCE: u32 = 21;
+
+    fn shapes() -> CollisionShapes {
+        let mut shapes = CollisionShapes::default();
+        shapes.register(SLAB, CollisionShape::Slab);
+        shapes.register(FENCE, CollisionShape::Fence);
+        shapes
+    }
+
+    /// About the size of a player
+    fn player_body() -> PhysicsBody {
+        PhysicsBody::new(0.3, 1.8)
+    }
+
+    /// Steps the body for a second, returning whether it was simulated every step
+    fn simulate(world: &WorldData, body: &mut PhysicsBody, position: &mut Vector3<f32>) -> bool {
+        let shapes = shapes();
+        (0..60).all(|_| step_body(world, &shapes, body, position, MAX_STEP))
+    }
+
+    #[test]
+    fn player_lands_on_a_slab() {
+        for (state, height) in [(0, 4.5), (0b100, 5.0)] {
+            let mut world = world_with_floor(3);
+            let chunk = world.chunks.get_mut(&Vector3::zeros()).unwrap();
+            chunk.world.set(8, 4, 8, pack_block(SLAB, state));
+
+            let mut body = player_body();
+            let mut position = Vector3::new(8.5, 10.0, 8.5);
+            assert!(simulate(&world, &mut body, &mut position));
+
+            assert_eq!(position.y, height, "slab state {}", state);
+            assert!(body.on_ground);
+        }
+    }
+
+    /// A row of fences along x = 10 with a gap at z = 8, on a floor
+    fn fenced_world() -> WorldData {
+        let mut world = world_with_floor(3);
+        let chunk = world.chunks.get_mut(&Vector3::zeros()).unwrap();
+        for z in 0..CHUNK_SIZE {
+            if z != 8 {
+                chunk.world.set(10, 4, z, FENCE);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn player_walks_through_a_gap_in_a_fence() {
+        let world = fenced_world();
+        let mut body = player_body();
+        body.velocity.x = 4.0;
+        let mut position = Vector3::new(8.5, 4.0, 8.5);
+
+        assert!(simulate(&world, &mut body, &mut position));
+        assert!(position.x > 12.0);
+        assert_eq!(position.y, 4.0);
+    }
+
+    #[test]
+    fn player_cant_walk_through_a_fence() {
+        let world = fenced_world();
+        let mut body = player_body();
+        body.velocity.x = 4.0;
+        let mut position = Vector3::new(8.5, 4.0, 6.5);
+
+        assert!(simulate(&world, &mut body, &mut position));
+
+        // Stopped against the fence post, rather than the edge of its block
+        assert!((position.x - (10.375 - 0.3)).abs() < 1e-4);
+        assert_eq!(body.velocity.x, 0.0);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::game::entity::DroppedItem;
+use crate::game::inventory::Inventory;
+use crate::game::transform::Transform;
+use crate::{TransportSystem, WorldData};
+use bevy::ecs::prelude::*;
+use rc_networking::constants::EntityId;
+use rc_networking::protocol::clientbound::despawn_entity::DespawnEntity;
+use rc_networking::protocol::clientbound::inventory_update::InventoryUpdate;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+
+/// How close, in blocks, a player has to be to an item to pick it up
+pub const PICKUP_RADIUS: f32 = 1.5;
+
+/// Moves dropped items into the inventories of players walking over them
+pub fn pickup_items(
+    mut world: ResMut<WorldData>,
+    transport: Res<TransportSystem>,
+    mut players: Query<(&Transform, &mut Inventory)>,
+    mut items: Query<(&Transform, &mut DroppedItem)>,
+    mut send_packet: EventWriter<SendPacket>,
+    mut commands: Commands,
+) {
+    let mut collected: Vec<EntityId> = Vec::new();
+
+    for (user_id, user) in &transport.clients {
+        let (player_transform, mut inventory) = match world
+            .entities
+            .get(&user.entity_id)
+            .and_then(|v| players.get_mut(*v).ok())
+        {
+            Some(val) => val,
+            None => continue,
+        };
+
+        for (id, entity) in &world.entities {
+            if collected.contains(id) {
+                continue;
+            }
+            let (item_transform, mut item) = match items.get_mut(*entity) {
+                Ok(val) => val,
+                Err(_) => continue,
+            };
+
+            if (item_transform.position - player_transform.position).magnitude() > PICKUP_RADIUS {
+                continue;
+            }
+
+            // Whatever doesn't fit in the inventory is left on the ground
+            let (remaining, changed) = inventory.add_item(item.item, item.amount);
+            item.amount = remaining;
+
+            for slot in changed {
+                let stack = inventory.slots[slot].unwrap();
+                send_packet.send(SendPacket(
+                    Protocol::InventoryUpdate(InventoryUpdate::new(
+                        slot as u8,
+                        stack.item,
+                        stack.amount,
+                    )),
+                    *user_id,
+                ));
+            }
+
+            if remaining == 0 {
+                collected.push(*id);
+            }
+        }
+    }
+
+    for id in collected {
+        if let Some(entity) = world.entities.remove(&id) {
+            commands.entity(entity).despawn();
+        }
+
+        for (client, _) in &transport.clients {
+            send_packet.send(SendPacket(
+                Protocol::DespawnEntity(DespawnEntity::new(id)),
+                *client,
+            ));
+        }
+    }
+}
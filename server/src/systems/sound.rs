@@ -0,0 +1,141 @@
+use crate::game::transform::Transform;
+use crate::{TransportSystem, WorldData};
+use bevy::ecs::event::EventWriter;
+use bevy::ecs::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::protocol::clientbound::play_sound::PlaySound;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::SendPacket;
+
+/// Sends a sound to every player within range of it, including whoever caused it
+pub fn broadcast_sound(
+    sound: PlaySound,
+    range: f32,
+    system: &TransportSystem,
+    world: &WorldData,
+    transforms: &Query<&mut Transform>,
+    event_writer: &mut EventWriter<SendPacket>,
+) {
+    let source = Vector3::new(sound.x, sound.y, sound.z);
+
+    for (client, user) in &system.clients {
+        let in_range = world
+            .entities
+            .get(&user.entity_id)
+            .and_then(|v| transforms.get(*v).ok())
+            .map_or(false, |v| (v.position - source).norm() <= range);
+
+        if in_range {
+            event_writer.send(SendPacket(Protocol::PlaySound(sound), *client));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::chunk::GenerationMode;
+    use crate::systems::authorization::GameUser;
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::{EntityId, GameMode, UserId, CHUNK_SIZE};
+    use rc_networking::protocol::clientbound::play_sound::Sound;
+    use std::collections::{HashMap, HashSet};
+
+    fn user(id: u64) -> GameUser {
+        GameUser::new(UserId(id), EntityId(id), GameMode::Survival)
+    }
+
+    /// Plays a sound at the origin with players standing at the given positions,
+    /// or without a body for None, returning who was sent it
+    fn hear(players: &[Option<Vector3<f32>>]) -> Vec<UserId> {
+        let mut world = World::new();
+        let mut transport = TransportSystem::default();
+        let mut data = WorldData {
+            chunks: HashMap::new(),
+            entities: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            chunk_size: CHUNK_SIZE,
+            seed: 0,
+            generation_mode: GenerationMode::Normal,
+        };
+
+        for (i, position) in players.iter().enumerate() {
+            let id = i as u64 + 1;
+            transport.clients.insert(UserId(id), user(id));
+
+            if let Some(position) = position {
+                let entity = world
+                    .spawn(Transform {
+                        position: *position,
+                        ..Default::default()
+                    })
+                    .id();
+                data.entities.insert(EntityId(id), entity);
+            }
+        }
+
+        world.insert_resource(transport);
+        world.insert_resource(data);
+        world.init_resource::<Events<SendPacket>>();
+
+        SystemStage::single_threaded()
+            .with_system(
+                |system: Res<TransportSystem>,
+                 world: Res<WorldData>,
+                 transforms: Query<&mut Transform>,
+                 mut event_writer: EventWriter<SendPacket>| {
+                    broadcast_sound(
+                        PlaySound::new(Sound::BlockPlace, 0.0, 0.0, 0.0),
+                        32.0,
+                        &system,
+                        &world,
+                        &transforms,
+                        &mut event_writer,
+                    );
+                },
+            )
+            .run(&mut world);
+
+        let mut heard: Vec<_> = world
+            .resource::<Events<SendPacket>>()
+            .iter_current_update_events()
+            .map(|v| match &v.0 {
+                Protocol::PlaySound(sound) => {
+                    assert_eq!(sound.sound_id, Sound::BlockPlace);
+                    v.1
+                }
+                packet => panic!("Unexpected packet {:?}", packet),
+            })
+            .collect();
+        heard.sort_by_key(|v| v.0);
+        heard
+    }
+
+    #[test]
+    fn players_in_range_hear_sounds() {
+        let heard = hear(&[
+            Some(Vector3::zeros()),
+            Some(Vector3::new(20.0, -10.0, 5.0)),
+            Some(Vector3::new(0.0, 0.0, 32.0)),
+        ]);
+
+        assert_eq!(heard, vec![UserId(1), UserId(2), UserId(3)]);
+    }
+
+    #[test]
+    fn players_out_of_range_hear_nothing() {
+        let heard = hear(&[
+            Some(Vector3::new(1.0, 2.0, 3.0)),
+            Some(Vector3::new(0.0, 32.1, 0.0)),
+            Some(Vector3::new(-30.0, 0.0, -30.0)),
+        ]);
+
+        assert_eq!(heard, vec![UserId(1)]);
+    }
+
+    #[test]
+    fn players_without_a_body_hear_nothing() {
+        assert_eq!(hear(&[None, Some(Vector3::zeros())]), vec![UserId(2)]);
+    }
+}
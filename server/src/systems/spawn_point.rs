@@ -0,0 +1,321 @@
+use crate::game::transform::Transform;
+use crate::game::world::spawns::SpawnPoints;
+use crate::helpers::global_to_local_position;
+use crate::systems::chunk::ChunkSystem;
+use crate::{ServerConfig, TransportSystem, WorldData};
+use bevy::prelude::*;
+use nalgebra::Vector3;
+use rc_networking::constants::UserId;
+use rc_networking::protocol::clientbound::teleport::Teleport;
+use rc_networking::protocol::serverbound::spawn_point::SpawnAction;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::{ReceivePacket, SendPacket};
+
+/// Players waiting to be teleported once the chunk they're going to has loaded
+#[derive(Resource, Default)]
+pub struct PendingTeleports(pub Vec<(UserId, Vector3<f32>)>);
+
+/// Starts moving a player, generating the chunk they're moving to first if it isn't loaded
+pub fn teleport_with_preload(
+    user: UserId,
+    destination: Vector3<f32>,
+    world: &WorldData,
+    chunks: &mut ChunkSystem,
+    pending: &mut PendingTeleports,
+) {
+    let (chunk, _) =
+        global_to_local_position(destination.map(|v| v.floor() as i32), world.chunk_size);
+
+    // The chunk is sent to the player as well, so it's there when they arrive
+    if !world.chunks.contains_key(&chunk) {
+        chunks.request(chunk, user);
+    }
+
+    pending.0.push((user, destination));
+}
+
+pub fn spawn_point_requests(
+    mut packets: EventReader<ReceivePacket>,
+    transport: Res<TransportSystem>,
+    world: Res<WorldData>,
+    transforms: Query<&Transform>,
+    mut spawns: ResMut<SpawnPoints>,
+    mut chunks: ResMut<ChunkSystem>,
+    mut pending: ResMut<PendingTeleports>,
+    config: Res<ServerConfig>,
+) {
+    for packet in packets.iter() {
+        let request = match &packet.0 {
+            Protocol::SpawnPoint(val) => val,
+            _ => continue,
+        };
+
+        if !config.personal_spawns {
+            warn!("Ignored spawn point request from {:?}", packet.1);
+            continue;
+        }
+
+        let user = match transport.clients.get(&packet.1) {
+            Some(val) => val,
+            None => continue,
+        };
+
+        match request.action {
+            SpawnAction::Set => {
+                let position = match world
+                    .entities
+                    .get(&user.entity_id)
+                    .and_then(|v| transforms.get(*v).ok())
+                {
+                    Some(val) => val.position,
+                    None => continue,
+                };
+
+                let name = match user.save_name() {
+                    Some(val) => val,
+                    None => {
+                        warn!("Ignored spawn point from {:?}, who has no name", packet.1);
+                        continue;
+                    }
+                };
+
+                spawns.set(name, position.into());
+                info!("Set spawn point of {:?} to {:?}", packet.1, position);
+            }
+            SpawnAction::Teleport => {
+                // Players who haven't set a spawn go back to the world spawn
+                let destination = user
+                    .save_name()
+                    .and_then(|v| spawns.get(v))
+                    .map_or(Transform::default().position, Vector3::from);
+
+                teleport_with_preload(packet.1, destination, &world, &mut chunks, &mut pending);
+            }
+        }
+    }
+}
+
+/// Teleports players whose destination chunk has finished loading
+pub fn finish_teleports(
+    mut pending: ResMut<PendingTeleports>,
+    world: Res<WorldData>,
+    transport: Res<TransportSystem>,
+    mut transforms: Query<&mut Transform>,
+    mut send_packets: EventWriter<SendPacket>,
+) {
+    pending.0.retain(|(user, destination)| {
+        // Players who left before arriving are forgotten
+        let client = match transport.clients.get(user) {
+            Some(val) => val,
+            None => return false,
+        };
+
+        let (chunk, _) =
+            global_to_local_position(destination.map(|v| v.floor() as i32), world.chunk_size);
+        if !world.chunks.contains_key(&chunk) {
+            return true;
+        }
+
+        if let Some(mut transform) = world
+            .entities
+            .get(&client.entity_id)
+            .and_then(|v| transforms.get_mut(*v).ok())
+        {
+            transform.position = *destination;
+        }
+
+        send_packets.send(SendPacket(
+            Protocol::Teleport(Teleport::new(destination.x, destination.y, destination.z)),
+            *user,
+        ));
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::chunk::{ChunkData, GenerationMode};
+    use crate::systems::authorization::{testing, GameUser};
+    use bevy::ecs::event::Events;
+    use bevy::ecs::schedule::{Stage, SystemStage};
+    use rc_networking::constants::{EntityId, CHUNK_SIZE};
+    use rc_networking::protocol::serverbound::spawn_point::SpawnPoint;
+    use std::collections::{HashMap, HashSet};
+
+    const PLAYER: UserId = UserId(1);
+
+    fn world_data() -> WorldData {
+        WorldData {
+            chunks: HashMap::new(),
+            entities: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            chunk_size: CHUNK_SIZE,
+            seed: 0,
+            generation_mode: GenerationMode::Heightmap,
+        }
+    }
+
+    /// A world with one player standing at the origin
+    fn world() -> World {
+        let mut transport = TransportSystem::default();
+        transport
+            .clients
+            .insert(PLAYER, GameUser::named(1, "alice"));
+
+        let mut world = World::new();
+        let player = world.spawn(Transform::default()).id();
+        let mut data = world_data();
+        data.entities.insert(EntityId(1), player);
+
+        world.insert_resource(data);
+        world.insert_resource(transport);
+        world.init_resource::<PendingTeleports>();
+        world.init_resource::<Events<SendPacket>>();
+        world
+    }
+
+    fn finish(world: &mut World) {
+        SystemStage::single_threaded()
+            .with_system(finish_teleports)
+            .run(world);
+    }
+
+    #[test]
+    fn unloaded_destinations_are_generated_first() {
+        let mut chunks = ChunkSystem::new(1, 4);
+        let mut pending = PendingTeleports::default();
+        let destination = Vector3::new(1000.5, 70.0, -1000.5);
+
+        teleport_with_preload(
+            PLAYER,
+            destination,
+            &world_data(),
+            &mut chunks,
+            &mut pending,
+        );
+
+        assert_eq!(chunks.queue_depth(), 1);
+        assert_eq!(pending.0, vec![(PLAYER, destination)]);
+    }
+
+    #[test]
+    fn loaded_destinations_are_not_generated_again() {
+        let mut chunks = ChunkSystem::new(1, 4);
+        let mut pending = PendingTeleports::default();
+        let destination = Vector3::new(1000.5, 70.0, -1000.5);
+        let (chunk, _) =
+            global_to_local_position(destination.map(|v| v.floor() as i32), CHUNK_SIZE);
+
+        let mut data = world_data();
+        data.chunks
+            .insert(chunk, ChunkData::blank(chunk, CHUNK_SIZE));
+        teleport_with_preload(PLAYER, destination, &data, &mut chunks, &mut pending);
+
+        assert_eq!(chunks.queue_depth(), 0);
+        assert_eq!(pending.0.len(), 1);
+    }
+
+    #[test]
+    fn players_wait_for_their_destination_to_load() {
+        let mut world = world();
+        let destination = Vector3::new(1000.5, 70.0, -1000.5);
+        let (chunk, _) =
+            global_to_local_position(destination.map(|v| v.floor() as i32), CHUNK_SIZE);
+        world
+            .resource_mut::<PendingTeleports>()
+            .0
+            .push((PLAYER, destination));
+
+        finish(&mut world);
+        assert_eq!(world.resource::<PendingTeleports>().0.len(), 1);
+        assert_eq!(world.resource::<Events<SendPacket>>().len(), 0);
+
+        world
+            .resource_mut::<WorldData>()
+            .chunks
+            .insert(chunk, ChunkData::blank(chunk, CHUNK_SIZE));
+        finish(&mut world);
+
+        assert!(world.resource::<PendingTeleports>().0.is_empty());
+        let sent: Vec<_> = world
+            .resource::<Events<SendPacket>>()
+            .iter_current_update_events()
+            .collect();
+        assert!(matches!(
+            sent.as_slice(),
+            [SendPacket(Protocol::Teleport(teleport), PLAYER)]
+                if *teleport == Teleport::new(1000.5, 70.0, -1000.5)
+        ));
+
+        let player = world.resource::<WorldData>().entities[&EntityId(1)];
+        assert_eq!(
+            world.get::<Transform>(player).unwrap().position,
+            destination
+        );
+    }
+
+    #[test]
+    fn players_who_leave_are_not_teleported() {
+        let mut world = world();
+        world
+            .resource_mut::<PendingTeleports>()
+            .0
+            .push((PLAYER, Vector3::new(1000.5, 70.0, -1000.5)));
+        world.resource_mut::<TransportSystem>().clients.clear();
+
+        finish(&mut world);
+
+        assert!(world.resource::<PendingTeleports>().0.is_empty());
+        assert_eq!(world.resource::<Events<SendPacket>>().len(), 0);
+    }
+
+    fn move_player(world: &mut World, id: u64, position: Vector3<f32>) {
+        let entity_id = world.resource::<TransportSystem>().clients[&UserId(id)].entity_id;
+        let entity = world.resource::<WorldData>().entities[&entity_id];
+        world.get_mut::<Transform>(entity).unwrap().position = position;
+    }
+
+    fn request(world: &mut World, id: u64, action: SpawnAction) {
+        world
+            .resource_mut::<Events<ReceivePacket>>()
+            .send(ReceivePacket(
+                Protocol::SpawnPoint(SpawnPoint::new(action)),
+                UserId(id),
+            ));
+        SystemStage::single_threaded()
+            .with_system(spawn_point_requests)
+            .run(world);
+        world.resource_mut::<Events<ReceivePacket>>().clear();
+    }
+
+    #[test]
+    fn players_each_return_to_their_own_spawn() {
+        let mut world = testing::world(ServerConfig::default(), &[1, 2, 3]);
+        testing::authorize(&mut world, 1, Some("alice"));
+        testing::authorize(&mut world, 2, Some("bob"));
+        testing::authorize(&mut world, 3, None);
+        world.init_resource::<Events<ReceivePacket>>();
+        world.init_resource::<SpawnPoints>();
+        world.init_resource::<PendingTeleports>();
+        world.insert_resource(ChunkSystem::new(1, 4));
+
+        let alice = Vector3::new(100.5, 70.0, 100.5);
+        move_player(&mut world, 1, alice);
+        move_player(&mut world, 3, Vector3::new(-50.5, 70.0, 20.5));
+        for id in [1, 3] {
+            request(&mut world, id, SpawnAction::Set);
+        }
+        for id in [1, 2, 3] {
+            request(&mut world, id, SpawnAction::Teleport);
+        }
+
+        // Players without a name can't set a spawn point, so there's none for them to share
+        let spawn = Transform::default().position;
+        assert_eq!(
+            world.resource::<PendingTeleports>().0,
+            vec![(UserId(1), alice), (UserId(2), spawn), (UserId(3), spawn)]
+        );
+        assert_eq!(world.resource::<SpawnPoints>().points.len(), 1);
+    }
+}
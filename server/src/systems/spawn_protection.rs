@@ -0,0 +1,142 @@
+use crate::systems::authorization::GameUser;
+use crate::ServerConfig;
+use nalgebra::Vector3;
+
+/// Sent to players whose edit was rejected for being too close to spawn
+pub const SPAWN_PROTECTION_MESSAGE: &str = "The area around spawn is protected";
+
+/// Whether a block is within the radius around the world spawn, measured across the ground so the protection
+/// reaches from the bottom to the top of the world. A radius of 0 protects nothing
+pub fn within_spawn_protection(position: Vector3<i32>, spawn: Vector3<f32>, radius: f32) -> bool {
+    if radius <= 0.0 {
+        return false;
+    }
+
+    let dx = position.x as f32 + 0.5 - spawn.x;
+    let dz = position.z as f32 + 0.5 - spawn.z;
+    dx * dx + dz * dz <= radius * radius
+}
+
+/// Whether a player is stopped from editing a block for being too close to spawn. Operators never are
+pub fn edit_blocked_by_spawn_protection(
+    config: &ServerConfig,
+    user: &GameUser,
+    position: Vector3<i32>,
+    spawn: Vector3<f32>,
+) -> bool {
+    let operator = user
+        .name
+        .as_deref()
+        .map_or(false, |name| config.is_operator(name));
+
+    !operator && within_spawn_protection(position, spawn, config.spawn_protection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc_networking::constants::{EntityId, GameMode, UserId};
+
+    fn user(name: Option<&str>) -> GameUser {
+        match name {
+            Some(name) => GameUser::named(1, name),
+            None => GameUser::new(UserId(1), EntityId(1), GameMode::Survival),
+        }
+    }
+
+    fn config(radius: f32) -> ServerConfig {
+        ServerConfig {
+            spawn_protection: radius,
+            operators: vec!["admin".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn blocks_inside_the_radius_are_protected() {
+        let spawn = Vector3::new(0.5, 64.0, 0.5);
+
+        assert!(within_spawn_protection(Vector3::new(0, 64, 0), spawn, 8.0));
+        assert!(within_spawn_protection(Vector3::new(8, 64, 0), spawn, 8.0));
+        assert!(within_spawn_protection(Vector3::new(-5, 64, 5), spawn, 8.0));
+        assert!(!within_spawn_protection(Vector3::new(9, 64, 0), spawn, 8.0));
+        assert!(!within_spawn_protection(Vector3::new(6, 64, 6), spawn, 8.0));
+    }
+
+    #[test]
+    fn protection_covers_the_full_height() {
+        let spawn = Vector3::new(0.5, 64.0, 0.5);
+
+        assert!(within_spawn_protection(Vector3::new(2, 0, 2), spawn, 8.0));
+        assert!(within_spawn_protection(Vector3::new(2, 255, 2), spawn, 8.0));
+        assert!(within_spawn_protection(
+            Vector3::new(2, -300, 2),
+            spawn,
+            8.0
+        ));
+    }
+
+    #[test]
+    fn zero_radius_protects_nothing() {
+        let spawn = Vector3::new(0.5, 64.0, 0.5);
+
+        assert!(!within_spawn_protection(Vector3::new(0, 64, 0), spawn, 0.0));
+        assert!(!within_spawn_protection(
+            Vector3::new(0, 64, 0),
+            spawn,
+            -1.0
+        ));
+        assert!(!edit_blocked_by_spawn_protection(
+            &config(0.0),
+            &user(Some("player")),
+            Vector3::new(0, 64, 0),
+            spawn,
+        ));
+    }
+
+    #[test]
+    fn players_cant_edit_inside_the_radius() {
+        let spawn = Vector3::new(0.5, 64.0, 0.5);
+        let config = config(8.0);
+
+        assert!(edit_blocked_by_spawn_protection(
+            &config,
+            &user(Some("player")),
+            Vector3::new(3, 64, -3),
+            spawn,
+        ));
+        // Players who haven't been named yet aren't operators
+        assert!(edit_blocked_by_spawn_protection(
+            &config,
+            &user(None),
+            Vector3::new(3, 64, -3),
+            spawn,
+        ));
+        assert!(!edit_blocked_by_spawn_protection(
+            &config,
+            &user(Some("player")),
+            Vector3::new(20, 64, -3),
+            spawn,
+        ));
+    }
+
+    #[test]
+    fn operators_bypass_protection() {
+        let spawn = Vector3::new(0.5, 64.0, 0.5);
+        let config = config(8.0);
+
+        assert!(!edit_blocked_by_spawn_protection(
+            &config,
+            &user(Some("admin")),
+            Vector3::new(3, 64, -3),
+            spawn,
+        ));
+        // Names have to match exactly
+        assert!(edit_blocked_by_spawn_protection(
+            &config,
+            &user(Some("Admin")),
+            Vector3::new(3, 64, -3),
+            spawn,
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::TransportSystem;
+use bevy::prelude::*;
+use rc_networking::constants::UserId;
+use rc_networking::protocol::clientbound::keep_alive::KeepAlive;
+use rc_networking::protocol::Protocol;
+use rc_networking::types::{ReceivePacket, SendPacket};
+use rc_networking::{KeepAliveSettings, Server};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Tracks when each player last answered a keep-alive
+#[derive(Resource)]
+pub struct KeepAlives {
+    pub settings: KeepAliveSettings,
+    since_sent: Duration,
+    next_id: u64,
+    /// The time, since the server started, each player last answered
+    last_answer: HashMap<UserId, Duration>,
+}
+
+impl KeepAlives {
+    pub fn new(settings: KeepAliveSettings) -> KeepAlives {
+        KeepAlives {
+            settings,
+            since_sent: Duration::ZERO,
+            next_id: 0,
+            last_answer: Default::default(),
+        }
+    }
+}
+
+pub fn receive_keep_alives(
+    mut keep_alives: ResMut<KeepAlives>,
+    mut receive: EventReader<ReceivePacket>,
+    time: Res<Time>,
+) {
+    for packet in receive.iter() {
+        if let Protocol::KeepAlive(_) = packet.0 {
+            keep_alives.last_answer.insert(packet.1, time.elapsed());
+        }
+    }
+}
+
+/// Sends keep-alives every interval and disconnects players who haven't answered one within the timeout
+pub fn send_keep_alives(
+    mut keep_alives: ResMut<KeepAlives>,
+    transport: Res<TransportSystem>,
+    mut server: ResMut<Server>,
+    mut send_packets: EventWriter<SendPacket>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed();
+
+    // Players that just connected are timed from when they're first seen
+    for user in transport.clients.keys() {
+        keep_alives.last_answer.entry(*user).or_insert(now);
+    }
+    keep_alives
+        .last_answer
+        .retain(|user, _| transport.clients.contains_key(user));
+
+    let timeout = keep_alives.settings.timeout;
+    for (user, last_answer) in keep_alives.last_answer.iter() {
+        if now.saturating_sub(*last_answer) > timeout {
+            info!("{:?} didn't answer a keep-alive within {:?}", user, timeout);
+            server.disconnect(user.0);
+        }
+    }
+
+    keep_alives.since_sent += time.delta();
+    if keep_alives.since_sent < keep_alives.settings.interval {
+        return;
+    }
+    keep_alives.since_sent = Duration::ZERO;
+
+    let packet = KeepAlive::new(keep_alives.next_id);
+    keep_alives.next_id += 1;
+
+    for user in transport.clients.keys() {
+        send_packets.send(SendPacket(Protocol::KeepAlive(packet), *user));
+    }
+}
@@ -1,13 +1,17 @@
 mod connection;
+pub mod keep_alive;
 
 use crate::events::authorization::AuthorizationEvent;
 use crate::events::connection::ConnectionEvent;
 use crate::events::disconnect::DisconnectionEvent;
 use crate::systems::authorization::GameUser;
 use crate::transport::connection::accept_connections;
+use crate::transport::keep_alive::{receive_keep_alives, send_keep_alives, KeepAlives};
 use bevy::app::{App, Plugin};
 
+use rc_networking::compression::ConnectionCompression;
 use rc_networking::constants::UserId;
+use rc_networking::dispatch::{PacketHandlerAppExt, PacketKind};
 use std::collections::HashMap;
 
 use std::net::{IpAddr, SocketAddr, UdpSocket};
@@ -15,7 +19,7 @@ use std::str::FromStr;
 
 use crate::ServerConfig;
 use bevy::ecs::prelude::Resource;
-use bevy::prelude::info;
+use bevy::prelude::{info, warn, IntoSystemDescriptor};
 use rc_networking::renet::{RenetServer, ServerAuthentication};
 use rc_networking::*;
 use std::time::SystemTime;
@@ -36,10 +40,37 @@ impl Default for TransportPlugin {
 
 impl Plugin for TransportPlugin {
     fn build(&self, app: &mut App) {
-        let bind_addr = {
+        let (bind_addr, max_clients, buffers, keep_alive, compression) = {
             let settings = app.world.get_resource::<ServerConfig>().unwrap();
 
-            SocketAddr::new(IpAddr::from_str(&settings.ip).unwrap(), settings.port)
+            let requested = settings.connection_buffers();
+            let buffers = requested.validated();
+            if buffers != requested {
+                warn!(
+                    "Connection buffer sizes raised to their minimums: {:?}",
+                    buffers
+                );
+            }
+
+            let keep_alive = match settings.keep_alive().validate() {
+                Ok(()) => settings.keep_alive(),
+                Err(err) => {
+                    warn!("Invalid keep-alive settings, using the defaults: {}", err);
+                    KeepAliveSettings::default()
+                }
+            };
+            info!(
+                "Sending keep-alives every {:?}, disconnecting after {:?} without an answer",
+                keep_alive.interval, keep_alive.timeout
+            );
+
+            (
+                SocketAddr::new(IpAddr::from_str(&settings.ip).unwrap(), settings.port),
+                settings.max_clients.max(1),
+                buffers,
+                keep_alive,
+                settings.compression.clone(),
+            )
         };
 
         let current_time = SystemTime::now()
@@ -49,14 +80,14 @@ impl Plugin for TransportPlugin {
         let server = RenetServer::new(
             current_time,
             renet::ServerConfig {
-                max_clients: 1024,
+                max_clients,
                 protocol_id: PROTOCOL_ID,
                 public_addr: bind_addr,
                 authentication: ServerAuthentication::Secure {
                     private_key: PRIVATE_KEY,
                 },
             },
-            get_renet_connection_config(),
+            connection_config(buffers),
             socket,
         )
         .unwrap();
@@ -67,8 +98,13 @@ impl Plugin for TransportPlugin {
 
         app.add_plugin(RenetServerPlugin)
             .insert_resource(Server(server))
+            .insert_resource(ConnectionCompression::new(compression))
             .insert_resource(transport_system)
             .add_system(accept_connections)
+            .insert_resource(KeepAlives::new(keep_alive))
+            .add_system(receive_keep_alives)
+            .handle_packets(&[PacketKind::KeepAlive])
+            .add_system(send_keep_alives.after(receive_keep_alives))
             .add_event::<ConnectionEvent>()
             .add_event::<AuthorizationEvent>()
             .add_event::<DisconnectionEvent>();